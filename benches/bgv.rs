@@ -42,7 +42,7 @@ pub fn criterion_benchmark(criterion: &mut Criterion) {
 
     group.bench_function("sample_centered_binomial", |b| {
         b.iter(|| {
-            sample_centered_binomial::<ToyCipher>(20);
+            sample_centered_binomial::<ToyCipher>(rand::thread_rng(), 20);
         })
     });
 
@@ -3,9 +3,10 @@ use std::future::Future;
 use std::time::{Duration, Instant};
 
 use criterion::{Bencher, Criterion};
+use multipars::low_gear_preproc;
 use multipars::low_gear_preproc::params::ToyPreprocK32S32;
 use multipars::low_gear_preproc::PreprocessorParameters;
-use multipars::{examples, low_gear_preproc};
+use multipars::orchestrator::{self, Config};
 use tokio::runtime::Runtime;
 
 const P0_ADDR: &str = "[::1]:50051";
@@ -14,7 +15,7 @@ const P1_ADDR: &str = "[::1]:50052";
 pub fn criterion_benchmark(criterion: &mut Criterion) {
     let mut group = criterion.benchmark_group("low_gear");
 
-    group.bench_function("toy_k32_s32", |b| bench_low_gear::<ToyPreprocK32S32>(b));
+    group.bench_function("toy_k32_s32", bench_low_gear::<ToyPreprocK32S32>);
 }
 
 async fn time<V, E: Debug>(fut: impl Future<Output = Result<V, E>>, denominator: u32) -> Duration {
@@ -33,22 +34,32 @@ where
                 async move {
                     tokio::try_join!(
                         tokio::task::spawn(async move {
-                            examples::low_gear::<PreprocParams, 0>(
-                                P0_ADDR,
-                                P1_ADDR,
-                                num_iterations as usize, // TODO: Maybe too many parallel tasks
-                                num_iterations as usize, // TODO: Maybe too many parallel tasks
-                            )
+                            orchestrator::run_preprocessing::<PreprocParams, 0>(Config {
+                                local_addr: P0_ADDR.into(),
+                                remote_addr: P1_ADDR.into(),
+                                num_threads: num_iterations as usize, // TODO: Maybe too many parallel tasks
+                                num_batches: num_iterations as usize, // TODO: Maybe too many parallel tasks
+                                retry: None,
+                                ready: None,
+                                memory_cap: None,
+                                audit_fraction: None,
+                                heartbeat: None,
+                            })
                             .await
                             .unwrap();
                         }),
                         tokio::task::spawn(async move {
-                            examples::low_gear::<PreprocParams, 1>(
-                                P1_ADDR,
-                                P0_ADDR,
-                                num_iterations as usize, // TODO: Maybe too many parallel tasks
-                                num_iterations as usize, // TODO: Maybe too many parallel tasks
-                            )
+                            orchestrator::run_preprocessing::<PreprocParams, 1>(Config {
+                                local_addr: P1_ADDR.into(),
+                                remote_addr: P0_ADDR.into(),
+                                num_threads: num_iterations as usize, // TODO: Maybe too many parallel tasks
+                                num_batches: num_iterations as usize, // TODO: Maybe too many parallel tasks
+                                retry: None,
+                                ready: None,
+                                memory_cap: None,
+                                audit_fraction: None,
+                                heartbeat: None,
+                            })
                             .await
                             .unwrap();
                         }),
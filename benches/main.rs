@@ -2,10 +2,13 @@ use criterion::{criterion_group, criterion_main, Criterion};
 
 mod bgv;
 mod low_gear;
+mod packing;
+mod zkpopk;
 
 criterion_group! {
     name = benches;
     config = Criterion::default();
-    targets = low_gear::criterion_benchmark, bgv::criterion_benchmark
+    targets = low_gear::criterion_benchmark, bgv::criterion_benchmark, packing::criterion_benchmark,
+        zkpopk::criterion_benchmark
 }
 criterion_main!(benches);
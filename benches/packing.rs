@@ -0,0 +1,166 @@
+#[cfg(feature = "params-k64")]
+use std::env;
+use std::time::Instant;
+
+use criterion::{black_box, Bencher, Criterion};
+use crypto_bigint::Random;
+use multipars::bgv::tweaked_interpolation_packing::{
+    get_random_unpacked, pack, pack_mask, packing_capacity, unpack,
+};
+use multipars::connection::Connection;
+use multipars::interface::MacKeyShare;
+#[cfg(feature = "params-k64")]
+use multipars::low_gear_preproc::params::PreprocK64S64;
+use multipars::low_gear_preproc::params::ToyPreprocK32S32;
+use multipars::low_gear_preproc::truncer::Truncer;
+use multipars::low_gear_preproc::PreprocessorParameters;
+use tokio::runtime::Runtime;
+
+const P0_ADDR: &str = "[::1]:50077";
+const P1_ADDR: &str = "[::1]:50078";
+
+/// The benchmarks of production parameters are only run if this environment variable is set,
+/// because they take considerably longer, and if the `params-k64` feature is enabled.
+#[cfg(feature = "params-k64")]
+const PRODUCTION_ENV: &str = "MULTIPARS_BENCH_PRODUCTION";
+
+pub fn criterion_benchmark(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("packing");
+
+    bench_params::<ToyPreprocK32S32>(&mut group, "toy_k32_s32");
+    #[cfg(feature = "params-k64")]
+    if env::var_os(PRODUCTION_ENV).is_some() {
+        bench_params::<PreprocK64S64>(&mut group, "k64_s64");
+    }
+}
+
+fn bench_params<P>(
+    group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+    name: &str,
+) where
+    P: PreprocessorParameters,
+{
+    group.bench_function(format!("{}_pack", name), pack_bench::<P>);
+    group.bench_function(format!("{}_pack_mask", name), pack_mask_bench::<P>);
+    group.bench_function(format!("{}_unpack", name), unpack_bench::<P>);
+    group.bench_function(format!("{}_truncate", name), truncate_bench::<P>);
+}
+
+fn pack_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let unpacked = get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng());
+    b.iter(|| pack::<P::PlaintextParams>(black_box(&unpacked)));
+}
+
+fn pack_mask_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let unpacked = get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng());
+    b.iter(|| pack_mask::<P::PlaintextParams>(black_box(&unpacked)));
+}
+
+fn unpack_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let unpacked = get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng());
+    // Only values scaled by `2^{2 delta}`, like products and masks, can be unpacked.
+    let packed = pack_mask::<P::PlaintextParams>(&unpacked);
+    b.iter(|| unpack::<_, P::K>(black_box(&packed)).unwrap());
+}
+
+/// Inputs of `Truncer::truncate()` for one party.
+struct TruncationInput<P>
+where
+    P: PreprocessorParameters,
+{
+    wide_a: Vec<P::KSS>,
+    wide_a_tags: Vec<P::KSS>,
+    b: Vec<P::K>,
+    b_tags: Vec<P::KS>,
+    wide_c: Vec<P::KSS>,
+    wide_c_tags: Vec<P::KSS>,
+}
+
+impl<P> TruncationInput<P>
+where
+    P: PreprocessorParameters,
+{
+    fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        let n = packing_capacity::<P::PlaintextParams>();
+        Self {
+            wide_a: (0..n).map(|_| P::KSS::random(&mut rng)).collect(),
+            wide_a_tags: (0..n).map(|_| P::KSS::random(&mut rng)).collect(),
+            b: (0..n).map(|_| P::K::random(&mut rng)).collect(),
+            b_tags: (0..n).map(|_| P::KS::random(&mut rng)).collect(),
+            wide_c: (0..n).map(|_| P::KSS::random(&mut rng)).collect(),
+            wide_c_tags: (0..n).map(|_| P::KSS::random(&mut rng)).collect(),
+        }
+    }
+
+    /// Truncates without the deferred `Truncer::batch_check()`, which is done once per batch.
+    async fn truncate<const PID: usize>(&self, truncer: &mut Truncer<P::S>) {
+        let _ = truncer
+            .truncate::<P::K, P::KS, P::KSS, PID>(
+                &self.wide_a,
+                &self.wide_a_tags,
+                &self.b,
+                &self.b_tags,
+                &self.wide_c,
+                &self.wide_c_tags,
+            )
+            .await;
+    }
+}
+
+/// Measures one truncation of a full plaintext of values by both parties over a loopback
+/// connection.
+fn truncate_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let runtime = Runtime::new().unwrap();
+    let (mut conn0, mut conn1) = runtime.block_on(async {
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        (conn0.unwrap(), conn1.unwrap())
+    });
+    let (mut truncer0, mut truncer1) = runtime.block_on(async {
+        let mut rng = rand::thread_rng();
+        let (mac_key0, mac_key1) = (
+            MacKeyShare::<P::S>::random(&mut rng),
+            MacKeyShare::<P::S>::random(&mut rng),
+        );
+        let (truncer0, truncer1) = tokio::join!(
+            Truncer::new(&mut conn0, mac_key0),
+            Truncer::new(&mut conn1, mac_key1)
+        );
+        (truncer0.unwrap(), truncer1.unwrap())
+    });
+    let (input0, input1) = (
+        TruncationInput::<P>::random(),
+        TruncationInput::<P>::random(),
+    );
+
+    b.iter_custom(|num_iterations| {
+        runtime.block_on(async {
+            let start = Instant::now();
+            for _ in 0..num_iterations {
+                tokio::join!(
+                    input0.truncate::<0>(&mut truncer0),
+                    input1.truncate::<1>(&mut truncer1)
+                );
+            }
+            start.elapsed()
+        })
+    });
+
+    // Keep the connections alive until the end.
+    drop((conn0, conn1));
+}
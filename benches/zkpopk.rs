@@ -0,0 +1,173 @@
+#[cfg(feature = "params-k64")]
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, Bencher, Criterion};
+use multipars::bgv::{
+    poly::{power::PowerPoly, CrtContext},
+    witness::EncryptionWitness,
+    zkpopk::{prover::Prover, verifier::Verifier, Challenge, Commitment, Response, Statement},
+    PreCiphertext, PublicKey, SecretKey,
+};
+#[cfg(feature = "params-k64")]
+use multipars::low_gear_preproc::params::PreprocK64S64;
+use multipars::low_gear_preproc::params::ToyPreprocK32S32;
+use multipars::low_gear_preproc::PreprocessorParameters;
+use tokio::runtime::Runtime;
+
+/// The benchmarks of production parameters are only run if this environment variable is set,
+/// because they take considerably longer, and if the `params-k64` feature is enabled.
+#[cfg(feature = "params-k64")]
+const PRODUCTION_ENV: &str = "MULTIPARS_BENCH_PRODUCTION";
+
+pub fn criterion_benchmark(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("zkpopk");
+
+    bench_params::<ToyPreprocK32S32>(&mut group, "toy_k32_s32");
+    #[cfg(feature = "params-k64")]
+    if env::var_os(PRODUCTION_ENV).is_some() {
+        bench_params::<PreprocK64S64>(&mut group, "k64_s64");
+    }
+}
+
+fn bench_params<P>(
+    group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+    name: &str,
+) where
+    P: PreprocessorParameters,
+{
+    group.bench_function(format!("{}_commit", name), commit_bench::<P>);
+    group.bench_function(format!("{}_respond", name), respond_bench::<P>);
+    group.bench_function(format!("{}_verify", name), verify_bench::<P>);
+}
+
+/// The ciphertexts that are proven, together with the keys they are encrypted under.
+struct Setup<P>
+where
+    P: PreprocessorParameters,
+{
+    ctx: Arc<CrtContext<P::CiphertextParams>>,
+    pk: PublicKey<P::BgvParams>,
+    inputs: Vec<EncryptionWitness<P::PlaintextParams>>,
+    ciphertexts: Vec<PreCiphertext<P::BgvParams>>,
+}
+
+impl<P> Setup<P>
+where
+    P: PreprocessorParameters,
+{
+    async fn gen() -> Self {
+        let mut rng = rand::thread_rng();
+        let ctx = Arc::new(CrtContext::gen().await);
+        let sk = SecretKey::<P::BgvParams>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let mut inputs = Vec::with_capacity(P::ZKPOPK_AMORTIZE);
+        let mut ciphertexts = Vec::with_capacity(P::ZKPOPK_AMORTIZE);
+        for _ in 0..P::ZKPOPK_AMORTIZE {
+            let plaintext = PowerPoly::random(&mut rng);
+            let mut ciphertext = PreCiphertext::default();
+            inputs.push(Prover::encrypt_into(&ctx, &pk, &plaintext, &mut ciphertext).await);
+            ciphertexts.push(ciphertext);
+        }
+        Self {
+            ctx,
+            pk,
+            inputs,
+            ciphertexts,
+        }
+    }
+
+    fn prover(&self) -> Prover<P::BgvParams> {
+        Prover::new(
+            P::ZKPOPK_INV_FAIL_PROB,
+            P::ZKPOPK_AMORTIZE,
+            P::ZKPOPK_SND_SEC,
+        )
+    }
+
+    /// Runs the prover until its response is not aborted.
+    async fn prove(
+        &self,
+        challenge: Challenge,
+    ) -> (Commitment<P::BgvParams>, Response<P::BgvParams>) {
+        let statement = Statement::of(&self.ciphertexts);
+        loop {
+            let prover = self.prover();
+            let commitment = prover.commit(&self.ctx, &self.pk).await;
+            if let Ok(response) = prover.respond(&self.inputs, &statement, challenge) {
+                return (commitment, response);
+            }
+        }
+    }
+}
+
+fn commit_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let runtime = Runtime::new().unwrap();
+    let setup = runtime.block_on(Setup::<P>::gen());
+    let prover = setup.prover();
+    b.to_async(runtime)
+        .iter(|| prover.commit(&setup.ctx, &setup.pk));
+}
+
+/// Only the response is timed, since `respond()` consumes the prover.  Aborted responses are
+/// timed as well, since they take as long as the others.
+fn respond_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let runtime = Runtime::new().unwrap();
+    let setup = runtime.block_on(Setup::<P>::gen());
+    let statement = Statement::of(&setup.ciphertexts);
+    b.iter_custom(|num_iterations| {
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..num_iterations {
+            let prover = setup.prover();
+            let challenge = Challenge::random(rand::thread_rng());
+            let start = Instant::now();
+            let _ = black_box(prover.respond(&setup.inputs, &statement, challenge));
+            elapsed += start.elapsed();
+        }
+        elapsed
+    });
+}
+
+/// Only the verification is timed, since `verify()` consumes the verifier and the commitment.
+fn verify_bench<P>(b: &mut Bencher)
+where
+    P: PreprocessorParameters,
+{
+    let runtime = Runtime::new().unwrap();
+    let setup = runtime.block_on(Setup::<P>::gen());
+    let setup = &setup;
+    b.to_async(runtime)
+        .iter_custom(|num_iterations| async move {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..num_iterations {
+                let challenge = Challenge::random(rand::thread_rng());
+                let (commitment, response) = setup.prove(challenge).await;
+                let verifier = Verifier::with_challenge(
+                    P::ZKPOPK_INV_FAIL_PROB,
+                    P::ZKPOPK_AMORTIZE,
+                    P::ZKPOPK_SND_SEC,
+                    challenge,
+                );
+                let start = Instant::now();
+                let verified = verifier
+                    .verify(
+                        &setup.ctx,
+                        &setup.pk,
+                        &setup.ciphertexts,
+                        commitment,
+                        response,
+                    )
+                    .await;
+                elapsed += start.elapsed();
+                assert!(verified);
+            }
+            elapsed
+        });
+}
@@ -0,0 +1,4 @@
+fn main() {
+    #[cfg(feature = "service-grpc")]
+    tonic_build::compile_protos("proto/multipars.proto").unwrap();
+}
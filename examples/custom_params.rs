@@ -0,0 +1,54 @@
+//! Defines BGV parameter sets outside of the crate and runs an encryption roundtrip with them.
+//!
+//! The parameters are the same as the toy parameters for `k=s=32` and are insecure.
+
+use multipars::bgv::{
+    self,
+    poly::{power::PowerPoly, CrtContext},
+    PublicKey, SecretKey,
+};
+
+multipars::define_bgv_params! {
+    ciphertext MyCipher {
+        limbs: 5,
+        modulus: "007fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed50d001",
+        m: 337,
+        cyclotomic_degree: 336,
+        factor_count: 336,
+        factor_degree: 1,
+        slot_generator: 10,
+        slot_generator_inverse: 236,
+        generator: 5,
+    }
+}
+
+multipars::define_bgv_params! {
+    plaintext MyPlain {
+        bits: 112,
+        limbs: 2,
+        m: 337,
+        cyclotomic_degree: 336,
+        crt {
+            factor_count: 16,
+            factor_degree: 21,
+            slot_generator: 191,
+            slot_generator_inverse: 30,
+            factors_file: "params/phi337_mod_t112.json",
+            delta: 8,
+        }
+    }
+}
+
+type MyBgv = (MyPlain, MyCipher);
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let ctx = CrtContext::gen().await;
+    let sk = SecretKey::<MyBgv>::gen(&ctx).await;
+    let pk = PublicKey::gen(&ctx, &sk).await;
+    let plaintext = PowerPoly::random(rand::thread_rng());
+    let ciphertext = bgv::encrypt(&ctx, &pk, &plaintext).await;
+    assert_eq!(bgv::decrypt(&ctx, &sk, &ciphertext).await, plaintext);
+    println!("roundtrip successful");
+}
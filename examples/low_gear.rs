@@ -1,10 +1,17 @@
+use std::time::Duration;
+
 use clap::Parser;
+#[cfg(feature = "params-k128")]
+use multipars::low_gear_preproc::params::PreprocK128S64;
+#[cfg(feature = "params-k32")]
+use multipars::low_gear_preproc::params::PreprocK32S32;
+#[cfg(feature = "params-k64")]
+use multipars::low_gear_preproc::params::PreprocK64S64;
 use multipars::{
-    examples,
-    low_gear_preproc::{
-        params::{PreprocK128S64, PreprocK32S32, PreprocK64S64, ToyPreprocK32S32},
-        PreprocessorParameters,
-    },
+    connection::RetryPolicy,
+    heartbeat::HeartbeatConfig,
+    low_gear_preproc::{params::ToyPreprocK32S32, PreprocessorParameters},
+    orchestrator,
 };
 
 #[derive(Clone, Debug, Parser)]
@@ -32,6 +39,30 @@ struct Args {
 
     #[arg(long, default_value_t = false)]
     toy: bool,
+
+    /// Retry connecting until the other player is reachable and print a readiness line to stderr
+    #[arg(long, default_value_t = false)]
+    wait: bool,
+
+    /// Interval between connection attempts in milliseconds (with --wait)
+    #[arg(long, default_value_t = 1000)]
+    retry_interval_ms: u64,
+
+    /// Maximum number of connection attempts (with --wait), unlimited if not given
+    #[arg(long)]
+    max_attempts: Option<usize>,
+
+    /// Fail before connecting if the estimated memory usage exceeds this many MiB
+    #[arg(long)]
+    memory_cap_mib: Option<usize>,
+
+    /// Open and check this fraction of the triples (which are lost) and report the result
+    #[arg(long)]
+    audit_fraction: Option<f64>,
+
+    /// Exchange heartbeats in this interval in milliseconds and report the round-trip time
+    #[arg(long)]
+    heartbeat_ms: Option<u64>,
 }
 
 #[derive(Clone, Debug, clap::ValueEnum)]
@@ -47,11 +78,14 @@ async fn main() {
     let args = Args::parse();
     match (args.toy, args.k, args.s) {
         (true, 32, 32) => run::<ToyPreprocK32S32>(args).await,
+        #[cfg(feature = "params-k32")]
         (false, 32, 32) => run::<PreprocK32S32>(args).await,
+        #[cfg(feature = "params-k64")]
         (false, 64, 64) => run::<PreprocK64S64>(args).await,
+        #[cfg(feature = "params-k128")]
         (false, 128, 64) => run::<PreprocK128S64>(args).await,
         _ => {
-            panic!("unsupported combination");
+            panic!("unsupported combination, or its `params-*` feature is disabled");
         }
     }
 }
@@ -60,17 +94,35 @@ async fn run<PreprocParams>(args: Args)
 where
     PreprocParams: PreprocessorParameters,
 {
+    let retry = args.wait.then(|| RetryPolicy {
+        interval: Duration::from_millis(args.retry_interval_ms),
+        max_attempts: args.max_attempts,
+        ..RetryPolicy::default()
+    });
+    let memory_cap = args.memory_cap_mib.map(|mib| mib << 20);
+    let heartbeat = args.heartbeat_ms.map(|ms| HeartbeatConfig {
+        interval: Duration::from_millis(ms),
+        ..HeartbeatConfig::default()
+    });
     let task_p0 = run_player::<PreprocParams, 0>(
         args.p0_addr.clone(),
         args.p1_addr.clone(),
         args.threads,
         args.batches,
+        retry,
+        memory_cap,
+        args.audit_fraction,
+        heartbeat,
     );
     let task_p1 = run_player::<PreprocParams, 1>(
         args.p1_addr.clone(),
         args.p0_addr.clone(),
         args.threads,
         args.batches,
+        retry,
+        memory_cap,
+        args.audit_fraction,
+        heartbeat,
     );
 
     match args.player {
@@ -82,15 +134,39 @@ where
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_player<PreprocParams, const PID: usize>(
     local_addr: String,
     remote_addr: String,
     num_threads: usize,
     num_batches: usize,
+    retry: Option<RetryPolicy>,
+    memory_cap: Option<usize>,
+    audit_fraction: Option<f64>,
+    heartbeat: Option<HeartbeatConfig>,
 ) where
     PreprocParams: PreprocessorParameters,
 {
-    examples::low_gear::<PreprocParams, PID>(&local_addr, &remote_addr, num_threads, num_batches)
-        .await
-        .unwrap();
+    let report = orchestrator::run_preprocessing::<PreprocParams, PID>(orchestrator::Config {
+        local_addr,
+        remote_addr,
+        num_threads,
+        num_batches,
+        ready: retry.map(|_| orchestrator::ReadyWriter::stderr()),
+        retry,
+        memory_cap,
+        audit_fraction,
+        heartbeat,
+    })
+    .await
+    .unwrap();
+    if let Some(audit) = report.audit {
+        eprintln!("Audit: {}", audit);
+    }
+    if let Some(health) = report.health {
+        eprintln!("Heartbeat: RTT {:?}", health.rtt);
+    }
+    // Output only the number of triples per second to stdout, so it can be parsed by benchmark
+    // scripts.
+    println!("{}", report.triples_per_sec());
 }
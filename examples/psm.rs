@@ -0,0 +1,252 @@
+//! Private set membership on top of the preprocessing: party 0 holds an element `x`, party 1 holds
+//! a set `Y`, and both learn whether `x \in Y`.
+//!
+//! The parties secret-share their inputs with the help of random triple components, compute
+//! `r \prod_{y \in Y} (x - y)` for a random `r` with Beaver multiplication, and open the result,
+//! which is zero if `x \in Y`.  The intermediate openings are MAC-checked in one batch before the
+//! result is opened.  Elements are taken modulo `2^k` and the size of `Y` is public.
+//!
+//! This example shows how `LowGearPreprocessor`, `BufferedPreprocessor` and `MacCheckOpener`
+//! compose; it is not meant as a secure PSM protocol.  In particular, since `\mathbb{Z}_{2^k}` is
+//! not a field, the opened value reveals the 2-adic valuation of the product, and it is zero with
+//! probability `2^{v - k}` although `x \notin Y`, where `v` is the valuation of the product.
+//! Moreover, the input owner does not check the MAC of the opened mask.
+
+use std::error::Error;
+
+use clap::Parser;
+use crypto_bigint::Zero;
+use futures_util::{SinkExt, StreamExt};
+#[cfg(feature = "params-k128")]
+use multipars::low_gear_preproc::params::PreprocK128S64;
+#[cfg(feature = "params-k32")]
+use multipars::low_gear_preproc::params::PreprocK32S32;
+#[cfg(feature = "params-k64")]
+use multipars::low_gear_preproc::params::PreprocK64S64;
+use multipars::{
+    bgv::residue::GenericResidue,
+    bi_channel::BiChannel,
+    buffered_preproc::BufferedPreprocessor,
+    connection::Connection,
+    interface::{BeaverTriple, MacKeyShare, Preprocessor, Share},
+    low_gear_preproc::{params::ToyPreprocK32S32, LowGearPreprocessor, PreprocessorParameters},
+    mac_check_opener::{MacCheckFailed, MacCheckOpener},
+};
+
+#[derive(Clone, Debug, Parser)]
+struct Args {
+    #[arg(long, default_value_t = String::from("[::1]:50053"))]
+    p0_addr: String,
+
+    #[arg(long, default_value_t = String::from("[::1]:50054"))]
+    p1_addr: String,
+
+    #[arg(long, value_enum, default_value_t = Player::Both)]
+    player: Player,
+
+    /// The element of party 0
+    #[arg(long, default_value_t = 0)]
+    element: u64,
+
+    /// The set of party 1, separated by commas
+    #[arg(long, value_delimiter = ',', default_value = "1,2,3")]
+    set: Vec<u64>,
+
+    #[arg(short, default_value_t = 32)]
+    k: usize,
+
+    #[arg(short, default_value_t = 32)]
+    s: usize,
+
+    #[arg(long, default_value_t = false)]
+    toy: bool,
+}
+
+#[derive(Clone, Debug, clap::ValueEnum)]
+enum Player {
+    Zero,
+    One,
+    Both,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    match (args.toy, args.k, args.s) {
+        (true, 32, 32) => run::<ToyPreprocK32S32>(args).await,
+        #[cfg(feature = "params-k32")]
+        (false, 32, 32) => run::<PreprocK32S32>(args).await,
+        #[cfg(feature = "params-k64")]
+        (false, 64, 64) => run::<PreprocK64S64>(args).await,
+        #[cfg(feature = "params-k128")]
+        (false, 128, 64) => run::<PreprocK128S64>(args).await,
+        _ => {
+            panic!("unsupported combination, or its `params-*` feature is disabled");
+        }
+    }
+}
+
+async fn run<PreprocParams>(args: Args)
+where
+    PreprocParams: PreprocessorParameters,
+{
+    let task_p0 = run_player::<PreprocParams, 0>(
+        args.p0_addr.clone(),
+        args.p1_addr.clone(),
+        Some(args.element),
+        Vec::new(),
+    );
+    let task_p1 = run_player::<PreprocParams, 1>(
+        args.p1_addr.clone(),
+        args.p0_addr.clone(),
+        None,
+        args.set.clone(),
+    );
+
+    let is_member = match args.player {
+        Player::Zero => task_p0.await,
+        Player::One => task_p1.await,
+        Player::Both => {
+            let (is_member_p0, is_member_p1) =
+                tokio::try_join!(tokio::task::spawn(task_p0), tokio::task::spawn(task_p1)).unwrap();
+            assert_eq!(is_member_p0, is_member_p1);
+            is_member_p0
+        }
+    };
+    println!("{}", is_member);
+}
+
+async fn run_player<P, const PID: usize>(
+    local_addr: String,
+    remote_addr: String,
+    element: Option<u64>,
+    set: Vec<u64>,
+) -> bool
+where
+    P: PreprocessorParameters,
+{
+    let mut conn = Connection::new(local_addr.parse().unwrap(), remote_addr.parse().unwrap())
+        .await
+        .unwrap();
+    let mut ch_set_size = BiChannel::<u64>::open(&mut conn, "psm:set_size")
+        .await
+        .unwrap();
+    let set_size = {
+        let (rx, tx) = ch_set_size.split();
+        if PID == 1 {
+            tx.send(set.len() as u64).await.unwrap();
+            set.len()
+        } else {
+            rx.next().await.unwrap().unwrap() as usize
+        }
+    };
+
+    // Inputs, multiplications, `r` and the mask of the MAC check.
+    let num_triples = 1 + 2 * set_size + 2;
+    let mut online = Online::<P, PID>::new(&mut conn, num_triples).await.unwrap();
+    let result = online.psm(element, &set, set_size).await.unwrap();
+    online.finish().await;
+    result
+}
+
+/// State of the online phase of one party.
+struct Online<P, const PID: usize>
+where
+    P: PreprocessorParameters,
+{
+    preproc: BufferedPreprocessor<P::KS, P::K, PID>,
+    opener: MacCheckOpener<P::KS, P::S>,
+    ch_input: BiChannel<P::KS>,
+    mac_key: MacKeyShare<P::S>,
+    triples: Vec<BeaverTriple<P::KS, P::K, PID>>,
+    /// Shares that were opened without checking their MAC tags yet.
+    unchecked: Vec<Share<P::KS, P::K, PID>>,
+}
+
+impl<P, const PID: usize> Online<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    async fn new(conn: &mut Connection, budget: usize) -> Result<Self, Box<dyn Error>> {
+        let preproc = LowGearPreprocessor::<P, PID>::new(&mut conn.fork()).await?;
+        let mac_key = preproc.mac_key().clone();
+        let mut conn = conn.fork();
+        Ok(Self {
+            preproc: BufferedPreprocessor::with_connection(&mut conn.fork(), preproc, budget)
+                .await?,
+            opener: MacCheckOpener::new(&mut conn, mac_key.clone()).await?,
+            ch_input: BiChannel::open(&mut conn, "psm:input").await?,
+            mac_key,
+            triples: Vec::new(),
+            unchecked: Vec::new(),
+        })
+    }
+
+    async fn psm(
+        &mut self,
+        element: Option<u64>,
+        set: &[u64],
+        set_size: usize,
+    ) -> Result<bool, MacCheckFailed> {
+        let num_triples = 1 + 2 * set_size + 2;
+        self.triples = self.preproc.get_beaver_triples(num_triples).await;
+
+        let x = self.input::<0>(element).await;
+        let mut product = self.next_triple().a;
+        for i in 0..set_size {
+            let y = self.input::<1>(set.get(i).copied()).await;
+            product = self.mul(product, x - y).await?;
+        }
+
+        let mask = self.next_triple().a;
+        let unchecked = std::mem::take(&mut self.unchecked);
+        self.opener.batch_check(unchecked.into_iter(), mask).await?;
+        let result = self.opener.single_check(product).await?;
+        Ok(result == P::K::ZERO)
+    }
+
+    fn next_triple(&mut self) -> BeaverTriple<P::KS, P::K, PID> {
+        // `unwrap()` cannot fail, because `psm()` fetches enough triples.
+        self.triples.pop().unwrap()
+    }
+
+    /// Shares the input of party `OWNER`, which passes `Some(value)`: the random `a` of a triple
+    /// is opened to the owner, who publishes `value - a`.
+    async fn input<const OWNER: usize>(&mut self, value: Option<u64>) -> Share<P::KS, P::K, PID> {
+        let mask = self.next_triple().a;
+        let (rx, tx) = self.ch_input.split();
+        let diff = if PID == OWNER {
+            // `unwrap()` cannot fail, because the owner passes its input.
+            let value = P::K::from_i64(value.unwrap() as i64);
+            let remote = rx.next().await.unwrap().unwrap();
+            let diff = value - P::K::from_unsigned(mask.val + remote);
+            tx.send(P::KS::from_unsigned(diff)).await.unwrap();
+            diff
+        } else {
+            tx.send(mask.val).await.unwrap();
+            P::K::from_unsigned(rx.next().await.unwrap().unwrap())
+        };
+        mask.add_public(diff, &self.mac_key)
+    }
+
+    /// Multiplies `x` and `y` with a Beaver triple.  The MAC tags of the opened values are checked
+    /// later.
+    async fn mul(
+        &mut self,
+        x: Share<P::KS, P::K, PID>,
+        y: Share<P::KS, P::K, PID>,
+    ) -> Result<Share<P::KS, P::K, PID>, MacCheckFailed> {
+        let BeaverTriple { a, b, c, .. } = self.next_triple();
+        let opened = [x - a, y - b];
+        let values = self.opener.open_unchecked(&opened).await?;
+        self.unchecked.extend(opened);
+        let (epsilon, delta) = (values[0], values[1]);
+        Ok((c + b * epsilon + a * delta).add_public(epsilon * delta, &self.mac_key))
+    }
+
+    async fn finish(self) {
+        self.opener.finish().await;
+        self.preproc.finish().await;
+    }
+}
@@ -0,0 +1,63 @@
+use clap::Parser;
+#[cfg(feature = "params-k128")]
+use multipars::low_gear_preproc::params::PreprocK128S64;
+#[cfg(feature = "params-k32")]
+use multipars::low_gear_preproc::params::PreprocK32S32;
+#[cfg(feature = "params-k64")]
+use multipars::low_gear_preproc::params::PreprocK64S64;
+use multipars::{
+    low_gear_preproc::{params::ToyPreprocK32S32, PreprocessorParameters},
+    selftest,
+};
+
+#[derive(Clone, Debug, Parser)]
+struct Args {
+    #[arg(long, default_value_t = String::from("[::1]:50051"))]
+    p0_addr: String,
+
+    #[arg(long, default_value_t = String::from("[::1]:50052"))]
+    p1_addr: String,
+
+    #[arg(short, default_value_t = 32)]
+    k: usize,
+
+    #[arg(short, default_value_t = 32)]
+    s: usize,
+
+    #[arg(long, default_value_t = false)]
+    toy: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    let passed = match (args.toy, args.k, args.s) {
+        (true, 32, 32) => run::<ToyPreprocK32S32>(args).await,
+        #[cfg(feature = "params-k32")]
+        (false, 32, 32) => run::<PreprocK32S32>(args).await,
+        #[cfg(feature = "params-k64")]
+        (false, 64, 64) => run::<PreprocK64S64>(args).await,
+        #[cfg(feature = "params-k128")]
+        (false, 128, 64) => run::<PreprocK128S64>(args).await,
+        _ => {
+            panic!("unsupported combination, or its `params-*` feature is disabled");
+        }
+    };
+    if !passed {
+        std::process::exit(1);
+    }
+}
+
+async fn run<PreprocParams>(args: Args) -> bool
+where
+    PreprocParams: PreprocessorParameters,
+{
+    let report = selftest::selftest::<PreprocParams>(
+        args.p0_addr.parse().unwrap(),
+        args.p1_addr.parse().unwrap(),
+    )
+    .await;
+    print!("{}", report);
+    report.passed()
+}
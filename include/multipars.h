@@ -0,0 +1,132 @@
+#ifndef MULTIPARS_H
+#define MULTIPARS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Triple generation for one party.
+typedef struct MultiparsPreprocessor MultiparsPreprocessor;
+
+// Connects to the other party and sets up the preprocessing.  Both parties must pass the same `k`,
+// `s` and `toy`.  `budget` is the number of triples that are generated ahead of time, and
+// `threads` is the number of worker threads, which must be positive.
+//
+// Returns null on failure.  Otherwise, the result must be passed to `multipars_finish()`.
+//
+// # Safety
+//
+// `local_addr` and `remote_addr` must be null or point to NUL-terminated strings.
+MultiparsPreprocessor *multipars_init(uint32_t player,
+                                      const char *local_addr,
+                                      const char *remote_addr,
+                                      uint32_t k,
+                                      uint32_t s,
+                                      bool toy,
+                                      uintptr_t budget,
+                                      uintptr_t threads);
+
+// Returns the number of limbs per residue of a triple.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.
+uintptr_t multipars_limbs(const MultiparsPreprocessor *preproc);
+
+// Returns the number of triples per packed plaintext of the parameters.  Requesting multiples of
+// it avoids discarding slots.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.
+uintptr_t multipars_packing_capacity(const MultiparsPreprocessor *preproc);
+
+// Returns the number of triples per packed plaintext of the parameters that `multipars_init()`
+// selects for `k`, `s` and `toy`, so that buffers can be sized before connecting.
+//
+// Returns 0 if the parameters are not supported.
+uintptr_t multipars_param_packing_capacity(uint32_t k, uint32_t s, bool toy);
+
+// Writes `n` triples to `out`.  Each triple consists of the shares of a, b and c, each of which
+// consists of the value and the MAC tag, each of which consists of `multipars_limbs()` limbs.
+// Hence, `out_len` must be `n * 6 * multipars_limbs()`.
+//
+// Returns 0 on success and -1 on failure.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+// be valid for writing `out_len` values.
+int32_t multipars_get_triples(MultiparsPreprocessor *preproc,
+                              uintptr_t n,
+                              uint64_t *out,
+                              uintptr_t out_len);
+
+// Like `multipars_get_triples()`, but writes the triples column by column, i.e., first the `n`
+// values of a, then the `n` MAC tags of a, then the values and MAC tags of b and c.  Each value and
+// MAC tag consists of `multipars_limbs()` limbs.  Hence, `out_len` must be
+// `n * 6 * multipars_limbs()`.
+//
+// Returns 0 on success and -1 on failure.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+// be valid for writing `out_len` values.
+int32_t multipars_get_triple_columns(MultiparsPreprocessor *preproc,
+                                     uintptr_t n,
+                                     uint64_t *out,
+                                     uintptr_t out_len);
+
+// Returns the number of limbs per share of a triple without MAC tags, see
+// `multipars_get_raw_triples()`.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.
+uintptr_t multipars_raw_limbs(const MultiparsPreprocessor *preproc);
+
+// Writes `n` triples without MAC tags to `out`, e.g., for semi-honest engines.  Each triple
+// consists of the shares of a, b and c modulo `2^k`, each of which consists of
+// `multipars_raw_limbs()` limbs.  Hence, `out_len` must be `n * 3 * multipars_raw_limbs()`.
+//
+// If `verify` is true, `2 n + 1` triples are consumed to check the triples by sacrificing before
+// the MAC tags are dropped.  Both parties must pass the same `n` and `verify`.
+//
+// Returns 0 on success and -1 on failure.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+// be valid for writing `out_len` values.
+int32_t multipars_get_raw_triples(MultiparsPreprocessor *preproc,
+                                  uintptr_t n,
+                                  bool verify,
+                                  uint64_t *out,
+                                  uintptr_t out_len);
+
+// Writes the limbs of this party's share of the MAC key to `out`.
+//
+// Returns the number of limbs of the MAC key on success and -1 if `out_len` is too small.
+//
+// # Safety
+//
+// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+// be valid for writing `out_len` values.
+int64_t multipars_get_mac_key_share(const MultiparsPreprocessor *preproc,
+                                    uint64_t *out,
+                                    uintptr_t out_len);
+
+// Closes the channels to the other party and frees `preproc`.
+//
+// # Safety
+//
+// `preproc` must be null or have been returned by `multipars_init()` and not yet been finished.
+void multipars_finish(MultiparsPreprocessor *preproc);
+
+// Returns the message of the last error on this thread, or null.  The string is valid until the
+// next call of a function of this API on this thread.
+const char *multipars_last_error(void);
+
+#endif /* MULTIPARS_H */
@@ -0,0 +1,35 @@
+#!/usr/bin/env python3
+"""Embeds `m`, `bits` and the checksum into a table of factors (see `CrtStrategy::Factors`).
+
+Usage: checksum_factors.py M BITS FILE
+
+The checksum is the SHA-256 digest of the compact JSON array `[m, bits, factors,
+basis_coefficients]`, which `FactorsContext::from_json()` recomputes when loading the table.
+"""
+
+import hashlib
+import json
+import sys
+
+
+def main():
+    if len(sys.argv) != 4:
+        sys.exit(__doc__)
+    m, bits, path = int(sys.argv[1]), int(sys.argv[2]), sys.argv[3]
+    with open(path) as f:
+        raw = f.read()
+    table = json.loads(raw)
+    canonical = json.dumps(
+        [m, bits, table["factors"], table["basis_coefficients"]], separators=(",", ":")
+    )
+    checksum = hashlib.sha256(canonical.encode()).hexdigest()
+
+    # Keep the layout of the file, which is large, and only replace the header.
+    body = raw[raw.index('"factors"') :]
+    header = '{\n    "m": %d,\n    "bits": %d,\n    "checksum": "%s",\n    ' % (m, bits, checksum)
+    with open(path, "w") as f:
+        f.write(header + body)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,301 @@
+//! Abort messages, with which a party tells the other party why it stops the protocol.
+//!
+//! When a check fails, the party that detects it returns an error, and the other party would only
+//! see a broken stream.  Before returning the error, the party sends an `Abort` on a dedicated
+//! control stream, so that operators can distinguish detected cheating from network failures.
+//!
+//! Each party generates an Ed25519 key pair per `AbortChannel`.  The verifying keys are exchanged
+//! at setup and bound to the session (see `AbortChannel::bind_session()`), and aborts are signed
+//! together with the session ID.  A received abort with a valid signature is hence evidence that
+//! the other party claimed the failure in this session, and aborts with an invalid signature are
+//! rejected.
+
+use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
+use async_bincode::AsyncDestination;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use futures_util::{SinkExt, StreamExt};
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+use crate::transcript::{SessionId, Transcript};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize, derive_more::Display)]
+pub enum AbortReason {
+    MacCheckFailed,
+    TruncationFailed,
+    /// The verification of the other party's ZKPoPK failed.
+    ZkpopkFailed,
+    /// The ZKPoPK was aborted `ZKPOPK_MAX_REPS` times.
+    ZkpopkMaxReps,
+    /// A decryption of the VOLE failed.
+    DecryptionFailed,
+    /// More than `MAX_DISCARDED_ITERATIONS` iterations were discarded because of faults.
+    TooManyFaults,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize, derive_more::Display)]
+#[display(fmt = "{} in batch {}, iteration {}", reason, batch, index)]
+pub struct Abort {
+    pub reason: AbortReason,
+    /// Number of batches that were completed before the failure.
+    pub batch: u64,
+    /// Iteration within the batch.  Checks of the whole batch report the last iteration.
+    pub index: u64,
+}
+
+/// An `Abort` with the signature of the sender.
+#[derive(Deserialize, Serialize)]
+struct SignedAbort {
+    abort: Abort,
+    signature: Vec<u8>,
+}
+
+/// The verifying key of the other party is not a valid Ed25519 key.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub struct InvalidVerifyingKey;
+
+/// State of the other party's control stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Remote {
+    Running,
+    /// The first abort that the other party sent.  Later ones are ignored.
+    Aborted(Abort),
+    /// The stream ended without an abort.
+    Closed,
+}
+
+pub struct AbortChannel {
+    writer: AsyncBincodeWriter<quinn::SendStream, SignedAbort, AsyncDestination>,
+    signing_key: SigningKey,
+    session_id: SessionId,
+    /// Taken by `bind_session()`, which starts receiving.
+    reader: Option<(
+        AsyncBincodeReader<quinn::RecvStream, SignedAbort>,
+        watch::Sender<Remote>,
+    )>,
+    remote: watch::Receiver<Remote>,
+}
+
+impl AbortChannel {
+    pub async fn open(conn: &mut Connection, name: &str) -> Result<Self, StreamError> {
+        let BiChannel { reader, writer, .. } = BiChannel::open(conn, name).await?;
+        let (remote_tx, remote) = watch::channel(Remote::Running);
+        Ok(Self {
+            writer,
+            signing_key: SigningKey::generate(&mut rand::rngs::OsRng),
+            session_id: SessionId::default(),
+            reader: Some((reader, remote_tx)),
+            remote,
+        })
+    }
+
+    /// This party's verifying key, which must be sent to the other party at setup.
+    pub fn verifying_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// Binds the signatures to the session and starts receiving aborts that are signed with
+    /// `remote_key`.  Both parties must call this before sending aborts, and `session_id` should
+    /// commit to the verifying keys of both parties.
+    pub fn bind_session(
+        &mut self,
+        remote_key: &[u8; 32],
+        session_id: SessionId,
+    ) -> Result<(), InvalidVerifyingKey> {
+        let remote_key = VerifyingKey::from_bytes(remote_key).map_err(|_| InvalidVerifyingKey)?;
+        self.session_id = session_id;
+        if let Some((reader, remote)) = self.reader.take() {
+            tokio::task::spawn(receive(reader, remote, remote_key, session_id));
+        }
+        Ok(())
+    }
+
+    /// Sends a signed `abort` to the other party.  This is best effort, because the connection
+    /// may already be broken.
+    pub async fn send(&mut self, abort: Abort) {
+        let signature = sign(&self.signing_key, &self.session_id, &abort);
+        if let Err(e) = self.writer.send(SignedAbort { abort, signature }).await {
+            error!("AbortChannel: failed to send {}: {}", abort, e);
+        }
+    }
+
+    /// The abort that the other party sent with a valid signature, if it was received yet.
+    pub fn remote_abort(&self) -> Option<Abort> {
+        match *self.remote.borrow() {
+            Remote::Aborted(abort) => Some(abort),
+            Remote::Running | Remote::Closed => None,
+        }
+    }
+
+    /// Waits until the other party sent an abort or closed its control stream.  Since the streams
+    /// are independent, an abort may arrive after another stream of the other party broke, so use
+    /// this (with a timeout) instead of `remote_abort()` in that case.
+    pub async fn wait_remote_abort(&self) -> Option<Abort> {
+        let mut remote = self.remote.clone();
+        loop {
+            match *remote.borrow_and_update() {
+                Remote::Aborted(abort) => return Some(abort),
+                Remote::Closed => return None,
+                Remote::Running => {}
+            }
+            if remote.changed().await.is_err() {
+                return self.remote_abort();
+            }
+        }
+    }
+
+    pub async fn finish(mut self) {
+        let _ = self.writer.get_mut().finish().await;
+    }
+}
+
+fn signed_message(session_id: &SessionId, abort: &Abort) -> SessionId {
+    let mut transcript = Transcript::new("Abort");
+    transcript.append_bytes("session_id", session_id);
+    transcript.append("abort", abort);
+    transcript.session_id()
+}
+
+fn sign(key: &SigningKey, session_id: &SessionId, abort: &Abort) -> Vec<u8> {
+    key.sign(&signed_message(session_id, abort))
+        .to_bytes()
+        .to_vec()
+}
+
+fn verify(key: &VerifyingKey, session_id: &SessionId, abort: &Abort, signature: &[u8]) -> bool {
+    match Signature::from_slice(signature) {
+        Ok(signature) => key
+            .verify(&signed_message(session_id, abort), &signature)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+async fn receive(
+    mut reader: AsyncBincodeReader<quinn::RecvStream, SignedAbort>,
+    remote: watch::Sender<Remote>,
+    remote_key: VerifyingKey,
+    session_id: SessionId,
+) {
+    while let Some(message) = reader.next().await {
+        match message {
+            Err(e) => {
+                error!("AbortChannel: failed to receive: {}", e);
+                break;
+            }
+            Ok(SignedAbort { abort, signature }) => {
+                if !verify(&remote_key, &session_id, &abort, &signature) {
+                    error!(
+                        "AbortChannel: rejecting abort with invalid signature: {}",
+                        abort
+                    );
+                } else if *remote.borrow() == Remote::Running {
+                    error!("The other party aborted: {}", abort);
+                    let _ = remote.send(Remote::Aborted(abort));
+                } else {
+                    error!("AbortChannel: ignoring another abort: {}", abort);
+                }
+            }
+        }
+    }
+    if *remote.borrow() == Remote::Running {
+        let _ = remote.send(Remote::Closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use crate::connection::Connection;
+    use crate::transcript::SessionId;
+
+    use super::{sign, verify, Abort, AbortChannel, AbortReason};
+
+    const ABORT: Abort = Abort {
+        reason: AbortReason::ZkpopkFailed,
+        batch: 3,
+        index: 1,
+    };
+
+    async fn connect(p0_addr: &str, p1_addr: &str) -> (Connection, Connection) {
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(p0_addr.parse().unwrap(), p1_addr.parse().unwrap()),
+            Connection::new(p1_addr.parse().unwrap(), p0_addr.parse().unwrap())
+        );
+        (conn0.unwrap(), conn1.unwrap())
+    }
+
+    /// Opens the channels of both parties and exchanges their verifying keys.
+    async fn open(
+        conn0: &mut Connection,
+        conn1: &mut Connection,
+        name: &str,
+        session_ids: [SessionId; 2],
+    ) -> (AbortChannel, AbortChannel) {
+        let (ch0, ch1) = tokio::join!(
+            AbortChannel::open(conn0, name),
+            AbortChannel::open(conn1, name)
+        );
+        let (mut ch0, mut ch1) = (ch0.unwrap(), ch1.unwrap());
+        let (key0, key1) = (ch0.verifying_key(), ch1.verifying_key());
+        ch0.bind_session(&key1, session_ids[0]).unwrap();
+        ch1.bind_session(&key0, session_ids[1]).unwrap();
+        (ch0, ch1)
+    }
+
+    #[test]
+    fn signature_binds_session_and_abort() {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let remote_key = key.verifying_key();
+        let signature = sign(&key, &[1; 32], &ABORT);
+        assert!(verify(&remote_key, &[1; 32], &ABORT, &signature));
+        assert!(!verify(&remote_key, &[2; 32], &ABORT, &signature));
+        let other = Abort { index: 2, ..ABORT };
+        assert!(!verify(&remote_key, &[1; 32], &other, &signature));
+        assert!(!verify(&remote_key, &[1; 32], &ABORT, &signature[1..]));
+    }
+
+    #[tokio::test]
+    async fn abort_is_delivered() {
+        let (mut conn0, mut conn1) = connect("[::1]:50079", "[::1]:50080").await;
+        let (mut ch0, ch1) = open(&mut conn0, &mut conn1, "test:abort", [[7; 32]; 2]).await;
+
+        // Only the first abort counts.
+        ch0.send(ABORT).await;
+        ch0.send(Abort { index: 2, ..ABORT }).await;
+        ch0.finish().await;
+        assert_eq!(ch1.wait_remote_abort().await, Some(ABORT));
+        assert_eq!(ch1.remote_abort(), Some(ABORT));
+
+        // The other party closes its control stream without an abort.
+        ch1.finish().await;
+        let (ch0, ch1) = open(&mut conn0, &mut conn1, "test:abort2", [[7; 32]; 2]).await;
+        ch1.finish().await;
+        assert_eq!(ch0.wait_remote_abort().await, None);
+        assert_eq!(ch0.remote_abort(), None);
+    }
+
+    #[tokio::test]
+    async fn forged_abort_is_rejected() {
+        let (mut conn0, mut conn1) = connect("[::1]:50111", "[::1]:50112").await;
+
+        // Party 0 signs with another key than the one it sent at setup.
+        let (mut ch0, ch1) = open(&mut conn0, &mut conn1, "test:abort", [[7; 32]; 2]).await;
+        ch0.signing_key = SigningKey::generate(&mut rand::rngs::OsRng);
+        ch0.send(ABORT).await;
+        ch0.finish().await;
+        assert_eq!(ch1.wait_remote_abort().await, None);
+        ch1.finish().await;
+
+        // An abort of another session is rejected as well.
+        let (mut ch0, ch1) = open(&mut conn0, &mut conn1, "test:abort2", [[7; 32], [8; 32]]).await;
+        ch0.send(ABORT).await;
+        ch0.finish().await;
+        assert_eq!(ch1.wait_remote_abort().await, None);
+        ch1.finish().await;
+    }
+}
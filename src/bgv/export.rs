@@ -0,0 +1,335 @@
+//! Export of keys and ciphertexts in a canonical form, e.g., for decrypting them with another HE
+//! library.
+//!
+//! A polynomial of the ciphertext ring `\mathbb{Z}_q[X]/\Phi_m(X)` is exported in the standard
+//! power basis `1, X, ..., X^{N-1}` for `N = \phi(m)`.  Each coefficient is in `[0, q)` and encoded
+//! as little-endian bytes of the same length as the modulus.  Since `q` is a single prime, this is
+//! the RNS representation with one modulus; libraries that split the modulus into several primes
+//! can compute the residues from it.  For prime `m`, `PowerPoly` uses the basis `X, ..., X^{m-1}`,
+//! which is converted via `X^{m-1} = -(1 + X + ... + X^{m-2})`.  For power-of-two `m` (see
+//! `CrtStrategy::Negacyclic`), the bases coincide, so the parameters align with, e.g., SEAL.
+//!
+//! Multipars decrypts a ciphertext `(c_0, c_1)` as `c_0 - c_1 s` (see `decrypt()`), whereas SEAL
+//! and OpenFHE compute `c_0 + c_1 s`.  For them, `c_1` (or `s`) has to be negated.
+
+use crypto_bigint::{Encoding, Integer, Zero};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    generic_uint::GenericUint,
+    poly::{crt::CrtPoly, crt::CrtPolyParameters, power::PowerPoly, CrtContext, PolyParameters},
+    residue::{vec::GenericResidueVec, GenericResidue},
+    BgvParameters, Ciphertext, PublicKey, SecretKey,
+};
+
+/// A polynomial in the canonical form described in the module documentation.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ExportedPoly {
+    /// The modulus `q` as little-endian bytes.
+    pub modulus: Vec<u8>,
+    /// The coefficients of `1, X, ..., X^{N-1}` as little-endian bytes of the same length as
+    /// `modulus`.
+    pub coefficients: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ExportedCiphertext {
+    pub c_0: ExportedPoly,
+    pub c_1: ExportedPoly,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ExportedPublicKey {
+    pub b: ExportedPoly,
+    pub a: ExportedPoly,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ExportedSecretKey {
+    pub s: ExportedPoly,
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum ImportError {
+    #[display(fmt = "the modulus does not match the parameters")]
+    ModulusMismatch,
+    #[display(fmt = "expected {} coefficients, got {}", expected, actual)]
+    WrongLength { expected: usize, actual: usize },
+    #[display(fmt = "coefficient {} is not reduced modulo q", _0)]
+    Unreduced(#[error(not(source))] usize),
+}
+
+fn modulus<P>() -> P::Uint
+where
+    P: PolyParameters,
+{
+    P::Residue::from_i64(-1)
+        .retrieve()
+        .wrapping_add(&<P::Uint as Integer>::ONE)
+}
+
+/// Converts between the basis of `PowerPoly` and the standard power basis.  For prime `m`, the
+/// conversion is `c_0 \mapsto -c_0` and `c_i \mapsto c_i - c_0`, which is its own inverse.
+fn change_basis<P>(coefficients: &mut P::Vec)
+where
+    P: PolyParameters,
+{
+    if P::M.is_power_of_two() {
+        return;
+    }
+    let first = coefficients[0];
+    coefficients[0] = P::Residue::ZERO - first;
+    for coeff in coefficients.iter_mut().skip(1) {
+        *coeff -= first;
+    }
+}
+
+impl ExportedPoly {
+    pub fn from_power<P>(poly: &PowerPoly<P>) -> Self
+    where
+        P: PolyParameters,
+    {
+        let mut coefficients = poly.coefficients.clone();
+        change_basis::<P>(&mut coefficients);
+        Self {
+            modulus: modulus::<P>().to_le_bytes().as_ref().to_vec(),
+            coefficients: coefficients
+                .iter()
+                .map(|coeff| coeff.retrieve().to_le_bytes().as_ref().to_vec())
+                .collect(),
+        }
+    }
+
+    pub async fn from_crt<P>(ctx: &CrtContext<P>, poly: &CrtPoly<P>) -> Self
+    where
+        P: CrtPolyParameters,
+    {
+        Self::from_power(&PowerPoly::from_crt(ctx, poly).await)
+    }
+
+    pub fn to_power<P>(&self) -> Result<PowerPoly<P>, ImportError>
+    where
+        P: PolyParameters,
+    {
+        let modulus = modulus::<P>();
+        if self.modulus != modulus.to_le_bytes().as_ref() {
+            return Err(ImportError::ModulusMismatch);
+        }
+        if self.coefficients.len() != P::CYCLOTOMIC_DEGREE {
+            return Err(ImportError::WrongLength {
+                expected: P::CYCLOTOMIC_DEGREE,
+                actual: self.coefficients.len(),
+            });
+        }
+        let mut poly = PowerPoly::<P>::new();
+        for (i, (dst, src)) in poly
+            .coefficients
+            .iter_mut()
+            .zip(&self.coefficients)
+            .enumerate()
+        {
+            let mut repr = P::Uint::ZERO.to_le_bytes();
+            if src.len() != repr.as_ref().len() {
+                return Err(ImportError::Unreduced(i));
+            }
+            repr.as_mut().copy_from_slice(src);
+            let uint = P::Uint::from_le_bytes(repr);
+            if uint >= modulus {
+                return Err(ImportError::Unreduced(i));
+            }
+            *dst = P::Residue::from_reduced(uint);
+        }
+        change_basis::<P>(&mut poly.coefficients);
+        Ok(poly)
+    }
+
+    pub async fn to_crt<P>(&self, ctx: &CrtContext<P>) -> Result<CrtPoly<P>, ImportError>
+    where
+        P: CrtPolyParameters,
+    {
+        Ok(CrtPoly::from_power(ctx, &self.to_power()?).await)
+    }
+}
+
+impl<P> Ciphertext<P>
+where
+    P: BgvParameters,
+{
+    pub async fn export(&self, ctx: &CrtContext<P::CiphertextParams>) -> ExportedCiphertext {
+        ExportedCiphertext {
+            c_0: ExportedPoly::from_crt(ctx, &self.c_0).await,
+            c_1: ExportedPoly::from_crt(ctx, &self.c_1).await,
+        }
+    }
+
+    pub async fn import(
+        ctx: &CrtContext<P::CiphertextParams>,
+        exported: &ExportedCiphertext,
+    ) -> Result<Self, ImportError> {
+        Ok(Self {
+            c_0: exported.c_0.to_crt(ctx).await?,
+            c_1: exported.c_1.to_crt(ctx).await?,
+        })
+    }
+}
+
+impl<P> PublicKey<P>
+where
+    P: BgvParameters,
+{
+    pub async fn export(&self, ctx: &CrtContext<P::CiphertextParams>) -> ExportedPublicKey {
+        ExportedPublicKey {
+            b: ExportedPoly::from_crt(ctx, &self.b).await,
+            a: ExportedPoly::from_crt(ctx, &self.a).await,
+        }
+    }
+
+    pub async fn import(
+        ctx: &CrtContext<P::CiphertextParams>,
+        exported: &ExportedPublicKey,
+    ) -> Result<Self, ImportError> {
+        Ok(Self {
+            b: exported.b.to_crt(ctx).await?,
+            a: exported.a.to_crt(ctx).await?,
+        })
+    }
+}
+
+impl<P> SecretKey<P>
+where
+    P: BgvParameters,
+{
+    pub async fn export(&self, ctx: &CrtContext<P::CiphertextParams>) -> ExportedSecretKey {
+        ExportedSecretKey {
+            s: ExportedPoly::from_crt(ctx, &self.s).await,
+        }
+    }
+
+    pub async fn import(
+        ctx: &CrtContext<P::CiphertextParams>,
+        exported: &ExportedSecretKey,
+    ) -> Result<Self, ImportError> {
+        Ok(Self {
+            s: exported.s.to_crt(ctx).await?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bgv::{
+        decrypt, encrypt,
+        params::{phi4096_mod_p54::Phi4096ModP54, ToyBgv, ToyCipher},
+        poly::{power::PowerPoly, CrtContext, PolyParameters},
+        residue::{vec::GenericResidueVec, GenericResidue},
+        Ciphertext, PublicKey, SecretKey,
+    };
+
+    use super::{ExportedPoly, ImportError};
+
+    /// Converts a big-endian hex string to little-endian bytes.
+    fn le_bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .rev()
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn power_poly_of_indices<P>(offset: i64) -> PowerPoly<P>
+    where
+        P: PolyParameters,
+    {
+        let mut poly = PowerPoly::<P>::new();
+        for (i, coeff) in poly.coefficients.iter_mut().enumerate() {
+            *coeff = P::Residue::from_i64(i as i64 + offset);
+        }
+        poly
+    }
+
+    #[test]
+    fn prime_m_golden() {
+        // Coefficient `i + 1` of `X^i` for `1 <= i < 336` and coefficient 1 of `X^336`.
+        let exported = ExportedPoly::from_power(&power_poly_of_indices::<ToyCipher>(1));
+        assert_eq!(
+            exported.modulus,
+            le_bytes(
+                "007fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed50d001"
+            )
+        );
+        assert_eq!(exported.coefficients.len(), 336);
+        assert_eq!(
+            exported.coefficients[0],
+            le_bytes(
+                "007fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed50d000"
+            )
+        );
+        for (i, coeff) in exported.coefficients.iter().enumerate().skip(1) {
+            let mut expected = vec![0; 40];
+            expected[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            assert_eq!(coeff, &expected);
+        }
+    }
+
+    #[test]
+    fn power_of_two_m_golden() {
+        let exported = ExportedPoly::from_power(&power_poly_of_indices::<Phi4096ModP54>(0));
+        assert_eq!(exported.modulus, le_bytes("003ffffffffed001"));
+        assert_eq!(exported.coefficients.len(), 2048);
+        for (i, coeff) in exported.coefficients.iter().enumerate() {
+            assert_eq!(coeff, &(i as u64).to_le_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn roundtrip_decrypts() {
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let plaintext = PowerPoly::random(&mut rng);
+        let ciphertext = encrypt(&ctx, &pk, &plaintext).await;
+
+        let sk_roundtrip = SecretKey::<ToyBgv>::import(&ctx, &sk.export(&ctx).await)
+            .await
+            .unwrap();
+        let pk_roundtrip = PublicKey::<ToyBgv>::import(&ctx, &pk.export(&ctx).await)
+            .await
+            .unwrap();
+        let ciphertext_roundtrip = Ciphertext::import(&ctx, &ciphertext.export(&ctx).await)
+            .await
+            .unwrap();
+        assert_eq!(sk_roundtrip, sk);
+        assert_eq!(pk_roundtrip, pk);
+        assert_eq!(
+            decrypt(&ctx, &sk_roundtrip, &ciphertext_roundtrip).await,
+            plaintext
+        );
+    }
+
+    #[test]
+    fn import_rejects_mismatches() {
+        let exported = ExportedPoly::from_power(&power_poly_of_indices::<Phi4096ModP54>(0));
+        assert!(matches!(
+            exported.to_power::<ToyCipher>(),
+            Err(ImportError::ModulusMismatch)
+        ));
+
+        let mut truncated = exported.clone();
+        truncated.coefficients.pop();
+        assert!(matches!(
+            truncated.to_power::<Phi4096ModP54>(),
+            Err(ImportError::WrongLength {
+                expected: 2048,
+                actual: 2047
+            })
+        ));
+
+        let mut unreduced = exported;
+        unreduced.coefficients[5] = unreduced.modulus.clone();
+        assert!(matches!(
+            unreduced.to_power::<Phi4096ModP54>(),
+            Err(ImportError::Unreduced(5))
+        ));
+    }
+}
@@ -1,5 +1,7 @@
 use std::mem;
 
+use crate::util;
+
 use super::residue::vec::GenericResidueVec;
 
 pub async fn fast_fourier_transform<ResidueVec>(
@@ -18,10 +20,18 @@ where
     for shift in 0..n.trailing_zeros() {
         let size = 1 << shift;
         let count = n >> (shift + 1);
-        for i in 0..count {
-            for j in 0..size {
-                let lhs = input[size * i + j];
-                let mut rhs = input[size * i + j + n / 2];
+        // The butterflies of different `i` touch disjoint chunks, so they could run in parallel.
+        let (input_low, input_high) = input.as_slice().split_at(n / 2);
+        let chunks = output.as_mut_slice().chunks_exact_mut(2 * size);
+        for (i, chunk) in chunks.enumerate() {
+            let (output_low, output_high) = chunk.split_at_mut(size);
+            let lhs_chunk = &input_low[size * i..size * (i + 1)];
+            let rhs_chunk = &input_high[size * i..size * (i + 1)];
+            let pairs = lhs_chunk.iter().zip(rhs_chunk);
+            let outputs = output_low.iter_mut().zip(output_high.iter_mut());
+            for (j, ((lhs, rhs), (dst_low, dst_high))) in pairs.zip(outputs).enumerate() {
+                let lhs = *lhs;
+                let mut rhs = *rhs;
                 if j != 0 {
                     let root_power_index = if inverse {
                         count * (n - j) % n
@@ -30,12 +40,12 @@ where
                     };
                     rhs *= root_powers[root_power_index];
                 }
-                output[size * (2 * i) + j] = lhs + rhs;
-                output[size * (2 * i + 1) + j] = lhs - rhs;
+                *dst_low = lhs + rhs;
+                *dst_high = lhs - rhs;
             }
         }
         mem::swap(&mut output, &mut input);
-        tokio::task::yield_now().await;
+        util::yield_now().await;
     }
 
     input
@@ -99,6 +99,11 @@ where
     }
 }
 
+/// Largest number of limbs for which `ExtendableUint` is implemented.  `crypto_bigint` implements
+/// `Encoding` (which `GenericUint` requires) for up to 16 limbs, and the extension needs one more
+/// limb.
+pub const MAX_LIMBS: usize = 15;
+
 pub trait ExtendableUint: GenericUint {
     type Extended: GenericUint;
 }
@@ -123,3 +128,22 @@ impl_extendable_uint!(9);
 impl_extendable_uint!(10);
 impl_extendable_uint!(11);
 impl_extendable_uint!(12);
+impl_extendable_uint!(13);
+impl_extendable_uint!(14);
+impl_extendable_uint!(15);
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::Uint;
+
+    use super::{ExtendableUint, GenericUint, MAX_LIMBS};
+
+    #[test]
+    fn max_limbs_is_extendable() {
+        assert_eq!(<Uint<MAX_LIMBS> as GenericUint>::NLIMBS, MAX_LIMBS);
+        assert_eq!(
+            <<Uint<MAX_LIMBS> as ExtendableUint>::Extended as GenericUint>::NLIMBS,
+            MAX_LIMBS + 1
+        );
+    }
+}
@@ -1,9 +1,12 @@
+pub mod export;
 pub mod fourier;
 pub mod generic_uint;
+pub mod noise;
 pub mod params;
 pub mod poly;
 pub mod residue;
 pub mod tweaked_interpolation_packing;
+pub mod witness;
 pub mod zkpopk;
 
 use std::{
@@ -12,16 +15,18 @@ use std::{
     ops::{AddAssign, MulAssign, SubAssign},
 };
 
-use crypto_bigint::{Integer, Limb, Word, Zero};
+use crypto_bigint::{Limb, Word};
 use rand::{CryptoRng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 
 use crate::bgv::generic_uint::GenericUint;
+use crate::util::zeroize;
 
 use self::{
     generic_uint::ExtendableUint,
     poly::{crt::CrtPoly, power::PowerPoly, CrtContext, FourierCrtPolyParameters, PolyParameters},
     residue::{native::GenericNativeResidue, vec::GenericResidueVec, GenericResidue},
+    witness::EncryptionWitness,
 };
 
 pub trait BgvParameters: PartialEq + Debug + Send + 'static {
@@ -79,20 +84,6 @@ where
     pub c_1: PowerPoly<P::CiphertextParams>, // TODO: non-public
 }
 
-// We give `P` as a generic parameter, because `P::CYCLOTOMIC_DEGREE` determines the length of the
-// stored vectors.
-#[derive(Deserialize, Serialize)]
-pub struct PreparedPlaintext<P>
-where
-    P: PolyParameters,
-    <P::Residue as GenericResidue>::Uint: ExtendableUint,
-{
-    noised_plaintext: Vec<<<P::Residue as GenericResidue>::Uint as ExtendableUint>::Extended>,
-    e_1: Vec<i64>,
-    v: Vec<i64>,
-    phantom: PhantomData<P>,
-}
-
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 pub struct Cleartext<P>(CrtPoly<P::CiphertextParams>)
 where
@@ -145,6 +136,21 @@ where
     }
 }
 
+impl<P> Ciphertext<P>
+where
+    P: BgvParameters,
+{
+    /// Adds all `ciphertexts` to `self` with lazy reduction, see `CrtPoly::accumulate()`.
+    pub fn accumulate<'a, I>(&mut self, ciphertexts: I)
+    where
+        I: IntoIterator<Item = &'a Self>,
+    {
+        let ciphertexts: Vec<_> = ciphertexts.into_iter().collect();
+        self.c_0.accumulate(ciphertexts.iter().map(|ct| &ct.c_0));
+        self.c_1.accumulate(ciphertexts.iter().map(|ct| &ct.c_1));
+    }
+}
+
 impl<P> AddAssign<&Self> for Ciphertext<P>
 where
     P: BgvParameters,
@@ -215,11 +221,12 @@ pub async fn encrypt<P>(
 where
     P: BgvParameters,
 {
-    let mut pre_ct = PreCiphertext::default();
-    prepare(plaintext).encrypt_into(ctx, pk, &mut pre_ct).await;
-    pre_ct.ciphertext(ctx).await
+    let mut ciphertext = Ciphertext::default();
+    encrypt_into(ctx, pk, plaintext, &mut ciphertext).await;
+    ciphertext
 }
 
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
 pub async fn encrypt_into<P>(
     ctx: &CrtContext<P::CiphertextParams>,
     pk: &PublicKey<P>,
@@ -229,10 +236,43 @@ pub async fn encrypt_into<P>(
     P: BgvParameters,
 {
     let mut pre_ct = PreCiphertext::default();
-    prepare(plaintext).encrypt_into(ctx, pk, &mut pre_ct).await;
+    EncryptionWitness::new_from_plaintext(plaintext)
+        .encrypt_into(ctx, pk, &mut pre_ct)
+        .await;
     pre_ct.ciphertext_into(ctx, ciphertext).await;
 }
 
+/// Like `encrypt()`, but samples the randomness with `rng` and returns it, such that the caller can
+/// commit to it or reproduce the encryption via `encrypt_witness()`.
+pub async fn encrypt_with_rng<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P>,
+    plaintext: &PowerPoly<P::PlaintextParams>,
+    rng: impl CryptoRng + RngCore,
+) -> (Ciphertext<P>, EncryptionWitness<P::PlaintextParams>)
+where
+    P: BgvParameters,
+{
+    let witness = EncryptionWitness::new_from_plaintext_with_rng(plaintext, rng);
+    let ciphertext = encrypt_witness(ctx, pk, &witness).await;
+    (ciphertext, witness)
+}
+
+/// Encrypts the plaintext of `witness` with its randomness, e.g., randomness that was sampled and
+/// committed to beforehand.
+pub async fn encrypt_witness<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P>,
+    witness: &EncryptionWitness<P::PlaintextParams>,
+) -> Ciphertext<P>
+where
+    P: BgvParameters,
+{
+    let mut pre_ct = PreCiphertext::default();
+    witness.encrypt_into(ctx, pk, &mut pre_ct).await;
+    pre_ct.ciphertext(ctx).await
+}
+
 pub async fn encrypt_and_drown<P>(
     ctx: &CrtContext<P::CiphertextParams>,
     pk: &PublicKey<P>,
@@ -247,6 +287,33 @@ where
     ct
 }
 
+/// Like `encrypt_and_drown()`, but samples the randomness with `rng`.  The drowning noise is not
+/// part of an `EncryptionWitness`, so the encryption is reproduced by passing an RNG in the same
+/// state, e.g., one seeded with a committed seed.
+pub async fn encrypt_and_drown_with_rng<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P>,
+    plaintext: &PowerPoly<P::PlaintextParams>,
+    noise_bits: usize,
+    rng: impl CryptoRng + RngCore,
+) -> Ciphertext<P>
+where
+    P: BgvParameters,
+{
+    let mut ct = Ciphertext::default();
+    encrypt_batch_into(
+        ctx,
+        pk,
+        std::slice::from_ref(plaintext),
+        std::slice::from_mut(&mut ct),
+        noise_bits,
+        rng,
+    )
+    .await;
+    ct
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
 pub async fn encrypt_and_drown_into<P>(
     ctx: &CrtContext<P::CiphertextParams>,
     pk: &PublicKey<P>,
@@ -255,180 +322,117 @@ pub async fn encrypt_and_drown_into<P>(
     noise_bits: usize,
 ) where
     P: BgvParameters,
+{
+    encrypt_batch_into(
+        ctx,
+        pk,
+        std::slice::from_ref(plaintext),
+        std::slice::from_mut(ciphertext),
+        noise_bits,
+        crate::rng::seeded_rng(),
+    )
+    .await;
+}
+
+/// Like `encrypt_and_drown()` for each of `plaintexts`, but the temporaries are allocated only
+/// once for the whole batch, and each kind of transform runs for all messages in a row, such that
+/// the twiddle factors of the `CrtContext` stay in the cache.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub async fn encrypt_batch<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P>,
+    plaintexts: &[PowerPoly<P::PlaintextParams>],
+    noise_bits: usize,
+) -> Vec<Ciphertext<P>>
+where
+    P: BgvParameters,
+{
+    encrypt_batch_with_rng(ctx, pk, plaintexts, noise_bits, crate::rng::seeded_rng()).await
+}
+
+/// Like `encrypt_batch()`, but samples the noise with `rng`.
+pub async fn encrypt_batch_with_rng<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P>,
+    plaintexts: &[PowerPoly<P::PlaintextParams>],
+    noise_bits: usize,
+    rng: impl CryptoRng + RngCore,
+) -> Vec<Ciphertext<P>>
+where
+    P: BgvParameters,
+{
+    let mut ciphertexts: Vec<_> = plaintexts.iter().map(|_| Ciphertext::default()).collect();
+    encrypt_batch_into(ctx, pk, plaintexts, &mut ciphertexts, noise_bits, rng).await;
+    ciphertexts
+}
+
+async fn encrypt_batch_into<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P>,
+    plaintexts: &[PowerPoly<P::PlaintextParams>],
+    ciphertexts: &mut [Ciphertext<P>],
+    noise_bits: usize,
+    mut rng: impl CryptoRng + RngCore,
+) where
+    P: BgvParameters,
 {
     type CiphertextResidue<P> =
         <<<P as BgvParameters>::CiphertextParams as PolyParameters>::Residue as GenericResidue>::Uint;
     type ExtendedUint<P> =
         <<<<P as BgvParameters>::PlaintextParams as PolyParameters>::Residue as GenericResidue>::Uint as ExtendableUint>::Extended;
 
-    let noised_plaintext: Vec<CiphertextResidue<P>> = add_uniform_scaled(plaintext, noise_bits);
-    // We approximate the discrete gaussian distribution of variance 10 with
-    // the centered binomial distribution of variance 10.  So the number of
-    // iterations and the maximum magnitude is 20.
-    let e_1: Vec<ExtendedUint<P>> =
-        add_centered_binomial_scaled(&PowerPoly::<P::PlaintextParams>::new(), 20);
-    let v = sample_centered_binomial::<P::PlaintextParams>(1);
+    debug_assert_eq!(plaintexts.len(), ciphertexts.len());
+    debug_assert!(
+        noise_bits <= max_drown_bits::<P>(),
+        "drowning noise exceeds the ciphertext modulus, see `noise::drown_bits()`"
+    );
 
     let mut temp_power = PowerPoly::new();
     let mut temp_crt = CrtPoly::new();
 
-    temp_power.clone_from_i64s(&v);
-    temp_crt.clone_from_power(ctx, &temp_power).await;
-
-    ciphertext.c_0.clone_from(&pk.b);
-    ciphertext.c_1.clone_from(&pk.a);
+    for ciphertext in ciphertexts.iter_mut() {
+        let v = sample_centered_binomial::<P::PlaintextParams>(&mut rng, 1);
+        temp_power.clone_from_i64s(&v);
+        temp_crt.clone_from_power(ctx, &temp_power).await;
 
-    ciphertext.c_0 *= &temp_crt;
-    ciphertext.c_1 *= &temp_crt;
+        ciphertext.c_0.clone_from(&pk.b);
+        ciphertext.c_1.clone_from(&pk.a);
 
-    temp_power.clone_from_signed_ints(&noised_plaintext);
-    temp_crt.clone_from_power(ctx, &temp_power).await;
-    ciphertext.c_0 += &temp_crt;
+        ciphertext.c_0 *= &temp_crt;
+        ciphertext.c_1 *= &temp_crt;
+    }
 
-    temp_power.clone_from_signed_ints(&e_1);
-    temp_crt.clone_from_power(ctx, &temp_power).await;
-    ciphertext.c_1 += &temp_crt;
-}
+    for (plaintext, ciphertext) in plaintexts.iter().zip(ciphertexts.iter_mut()) {
+        let noised_plaintext: Vec<CiphertextResidue<P>> =
+            add_uniform_scaled(&mut rng, plaintext, noise_bits);
+        temp_power.clone_from_signed_ints(&noised_plaintext);
+        temp_crt.clone_from_power(ctx, &temp_power).await;
+        ciphertext.c_0 += &temp_crt;
+    }
 
-fn prepare<P>(plaintext: &PowerPoly<P>) -> PreparedPlaintext<P>
-where
-    P: PolyParameters,
-    P::Residue: GenericNativeResidue,
-    <P::Residue as GenericResidue>::Uint: ExtendableUint,
-{
     // We approximate the discrete gaussian distribution of variance 10 with
     // the centered binomial distribution of variance 10.  So the number of
     // iterations and the maximum magnitude is 20.
-    let noised_plaintext = add_centered_binomial_scaled(&plaintext, 20);
-    let e_1 = sample_centered_binomial::<P>(20);
-    let v = sample_centered_binomial::<P>(1);
-    PreparedPlaintext {
-        noised_plaintext,
-        e_1,
-        v,
-        phantom: PhantomData::default(),
-    }
-}
-
-impl<P> PreparedPlaintext<P>
-where
-    P: PolyParameters,
-    <P::Residue as GenericResidue>::Uint: ExtendableUint,
-{
-    async fn encrypt_into<BgvParams>(
-        &self,
-        ctx: &CrtContext<BgvParams::CiphertextParams>,
-        pk: &PublicKey<BgvParams>,
-        ciphertext: &mut PreCiphertext<BgvParams>,
-    ) where
-        BgvParams: BgvParameters<PlaintextParams = P>,
-    {
-        type ExtendedUint<P> =
-            <<<P as PolyParameters>::Residue as GenericResidue>::Uint as ExtendableUint>::Extended;
-
-        let scaled_e_1: Vec<_> = self
-            .e_1
-            .iter()
-            .map(|e| {
-                let extended = ExtendedUint::<P>::from_i64(*e);
-                extended << BgvParams::PlaintextResidue::BITS
-            })
-            .collect();
-
-        let mut temp_power = PowerPoly::new();
-        let mut temp_crt = CrtPoly::new();
-
-        temp_power.clone_from_i64s(&self.v);
-        let v = CrtPoly::from_power(ctx, &temp_power).await;
-
-        temp_crt.clone_from(&pk.b);
-        temp_crt *= &v;
-        ciphertext.c_0.clone_from_crt(ctx, &temp_crt).await;
-        temp_power.clone_from_signed_ints(&self.noised_plaintext);
-        ciphertext.c_0 += &temp_power;
-
-        temp_crt.clone_from(&pk.a);
-        temp_crt *= &v;
-        ciphertext.c_1.clone_from_crt(ctx, &temp_crt).await;
-        temp_power.clone_from_signed_ints(&scaled_e_1);
-        ciphertext.c_1 += &temp_power;
-    }
-
-    fn add_assign_slided(&mut self, rhs: &Self, length: usize) {
-        if length == 0 {
-            return;
-        }
-        let mut sum_np = <<P::Residue as GenericResidue>::Uint as ExtendableUint>::Extended::ZERO;
-        let mut sum_e_1 = 0;
-        let mut sum_v = 0;
-        for power in 1..P::M {
-            let index = power % (P::M - 1);
-            sum_np = sum_np.wrapping_add(&rhs.noised_plaintext[index]);
-            sum_e_1 += rhs.e_1[index];
-            sum_v += rhs.v[index];
-            if power != length {
-                let rhs_index = (power + P::M - length) % P::M % (P::M - 1);
-                sum_np = sum_np.wrapping_sub(&rhs.noised_plaintext[rhs_index]);
-                sum_e_1 -= rhs.e_1[rhs_index];
-                sum_v -= rhs.v[rhs_index];
-            }
-            let np = &mut self.noised_plaintext[index];
-            *np = np.wrapping_add(&sum_np);
-            self.e_1[index] += sum_e_1;
-            self.v[index] += sum_v;
-        }
+    let zero = PowerPoly::<P::PlaintextParams>::new();
+    for ciphertext in ciphertexts.iter_mut() {
+        let e_1: Vec<ExtendedUint<P>> = add_centered_binomial_scaled(&mut rng, &zero, 20);
+        temp_power.clone_from_signed_ints(&e_1);
+        temp_crt.clone_from_power(ctx, &temp_power).await;
+        ciphertext.c_1 += &temp_crt;
     }
 }
 
-impl<P> Default for PreparedPlaintext<P>
+pub fn sample_centered_binomial<P>(mut rng: impl CryptoRng + RngCore, iterations: usize) -> Vec<i64>
 where
     P: PolyParameters,
-    <P::Residue as GenericResidue>::Uint: ExtendableUint,
 {
-    fn default() -> Self {
-        type ExtendedUint<P> = <<P as PolyParameters>::Uint as ExtendableUint>::Extended;
-        Self {
-            noised_plaintext: vec![ExtendedUint::<P>::default(); P::CYCLOTOMIC_DEGREE],
-            e_1: vec![0; P::CYCLOTOMIC_DEGREE],
-            v: vec![0; P::CYCLOTOMIC_DEGREE],
-            phantom: PhantomData::default(),
-        }
-    }
-}
-
-impl<P> Clone for PreparedPlaintext<P>
-where
-    P: PolyParameters,
-    <P::Residue as GenericResidue>::Uint: ExtendableUint,
-{
-    fn clone(&self) -> Self {
-        Self {
-            noised_plaintext: self.noised_plaintext.clone(),
-            e_1: self.e_1.clone(),
-            v: self.v.clone(),
-            phantom: PhantomData::default(),
-        }
-    }
-
-    fn clone_from(&mut self, source: &Self) {
-        self.noised_plaintext.clone_from(&source.noised_plaintext);
-        self.e_1.clone_from(&source.e_1);
-        self.v.clone_from(&source.v);
-    }
-}
-
-pub fn sample_centered_binomial<P>(iterations: usize) -> Vec<i64>
-where
-    P: PolyParameters,
-{
-    let mut rng = rand::thread_rng();
-
     (0..P::CYCLOTOMIC_DEGREE)
         .map(|_| sample_binomial(&mut rng, iterations) as i64 - iterations as i64)
         .collect()
 }
 
 fn add_centered_binomial_scaled<P, TargetInt>(
+    mut rng: impl CryptoRng + RngCore,
     src: &PowerPoly<P>,
     iterations: usize,
 ) -> Vec<TargetInt>
@@ -439,8 +443,6 @@ where
 {
     let nlimbs = <P::Residue as GenericResidue>::Uint::NLIMBS;
 
-    let mut rng = rand::thread_rng();
-
     src.coefficients
         .iter()
         .map(|coeff| {
@@ -454,7 +456,11 @@ where
 }
 
 // The added noise is between -2^(noise_bits-1) and 2^(noise_bits-1).
-fn add_uniform_scaled<P, TargetInt>(src: &PowerPoly<P>, noise_bits: usize) -> Vec<TargetInt>
+fn add_uniform_scaled<P, TargetInt>(
+    mut rng: impl CryptoRng + RngCore,
+    src: &PowerPoly<P>,
+    noise_bits: usize,
+) -> Vec<TargetInt>
 where
     P: PolyParameters,
     P::Residue: GenericNativeResidue,
@@ -465,7 +471,6 @@ where
     debug_assert!(0 < noise_bits);
     debug_assert!(noise_bits <= TargetInt::NLIMBS * Limb::BITS - P::Residue::BITS);
 
-    let mut rng = rand::thread_rng();
     // Set `minimum` to the expected value of `sample`, in order to center the distribution.
     let minimum = TargetInt::from_u32(1) << (noise_bits - 1);
 
@@ -474,7 +479,7 @@ where
         .map(|coeff| {
             let mut sample = TargetInt::ZERO;
             let mut remaining_noise_bits = noise_bits;
-            for limb in &mut sample.limbs_mut()[..(noise_bits + 63) / 64] {
+            for limb in &mut sample.limbs_mut()[..noise_bits.div_ceil(64)] {
                 limb.0 = if remaining_noise_bits >= 64 {
                     remaining_noise_bits -= 64;
                     rng.gen::<Word>()
@@ -510,6 +515,12 @@ where
     power
 }
 
+/// Integers with one more limb than the ciphertext modulus in two's complement, see
+/// `decrypt_centered()`.
+pub type CenteredUint<P> =
+    <<<P as BgvParameters>::CiphertextParams as PolyParameters>::Uint as ExtendableUint>::Extended;
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
 pub async fn decrypt_into<P>(
     ctx: &CrtContext<P::CiphertextParams>,
     secret_key: &SecretKey<P>,
@@ -518,26 +529,67 @@ pub async fn decrypt_into<P>(
 ) where
     P: BgvParameters,
 {
-    let noise_max = <<P::CiphertextParams as PolyParameters>::Residue as GenericResidue>::Uint::ONE
-        << (<P::CiphertextParams as PolyParameters>::Residue::BITS - 1);
+    let mut centered = decrypt_centered(ctx, secret_key, ciphertext).await;
+    reduce_centered_into::<P>(&centered, plaintext);
+    zeroize(centered.iter_mut());
+}
 
+/// Decrypts `ciphertext` without reducing modulo the plaintext modulus `t`.  Returns the
+/// coefficients of `c_0 - c_1 s`, which is `m + t e` modulo `q` for the plaintext `m` and the noise
+/// `t e`, as representatives in `(-q/2, q/2]` (see `GenericResidue::centered()`).
+///
+/// If all coefficients of `m + t e` have a magnitude below `q/2`, these are the coefficients of
+/// `m + t e` over the integers, and reducing them modulo `t` (see `reduce_centered_into()`) yields
+/// `m`.  The bounds of `noise` guarantee this for the ciphertexts of the protocols.
+pub async fn decrypt_centered<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    secret_key: &SecretKey<P>,
+    ciphertext: &Ciphertext<P>,
+) -> Vec<CenteredUint<P>>
+where
+    P: BgvParameters,
+{
     let mut temp = ciphertext.c_1.clone();
     temp *= &secret_key.s;
-    temp -= &ciphertext.c_0;
+    let mut temp = -temp;
+    temp += &ciphertext.c_0;
     let mut temp = PowerPoly::from_crt(ctx, &temp).await;
-    for coeff in temp.coefficients.iter_mut() {
-        *coeff = <P::CiphertextParams as PolyParameters>::Residue::from_reduced(noise_max) - *coeff;
-    }
-    plaintext.clone_from_power(&temp);
+    let centered = temp
+        .coefficients
+        .iter()
+        .map(|coeff| coeff.centered())
+        .collect();
+    zeroize(temp.coefficients.iter_mut());
+    centered
+}
+
+/// Reduces the coefficients of `decrypt_centered()` modulo the plaintext modulus `t`.  Since `t`
+/// is a power of two, this keeps the lowest bits of the two's complement.
+pub fn reduce_centered_into<P>(
+    centered: &[CenteredUint<P>],
+    plaintext: &mut PowerPoly<P::PlaintextParams>,
+) where
+    P: BgvParameters,
+{
+    plaintext.clone_from_signed_ints(centered);
 }
 
 impl<P> SecretKey<P>
 where
     P: BgvParameters,
 {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn gen(ctx: &CrtContext<P::CiphertextParams>) -> Self {
+        Self::gen_with_rng(ctx, crate::rng::os_rng()).await
+    }
+
+    /// Like `gen()`, but samples the key with `rng`.
+    pub async fn gen_with_rng(
+        ctx: &CrtContext<P::CiphertextParams>,
+        rng: impl CryptoRng + RngCore,
+    ) -> Self {
         // TODO: Ensure hamming weight N/2 where N is `P::CiphertextParams::CYCLOTOMIC_DEGREE`.
-        let e = sample_centered_binomial::<P::PlaintextParams>(1);
+        let e = sample_centered_binomial::<P::PlaintextParams>(rng, 1);
         let mut power_e = PowerPoly::new();
         power_e.clone_from_i64s(&e);
         let s = CrtPoly::from_power(ctx, &power_e).await;
@@ -549,10 +601,20 @@ impl<P> PublicKey<P>
 where
     P: BgvParameters,
 {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn gen(ctx: &CrtContext<P::CiphertextParams>, sk: &SecretKey<P>) -> Self {
+        Self::gen_with_rng(ctx, sk, crate::rng::os_rng()).await
+    }
+
+    /// Like `gen()`, but samples `a` and the noise with `rng`.
+    pub async fn gen_with_rng(
+        ctx: &CrtContext<P::CiphertextParams>,
+        sk: &SecretKey<P>,
+        mut rng: impl CryptoRng + RngCore,
+    ) -> Self {
         type ExtendedUint<P> =
             <<<<P as BgvParameters>::PlaintextParams as PolyParameters>::Residue as GenericResidue>::Uint as ExtendableUint>::Extended;
-        let a = CrtPoly::random(rand::thread_rng());
+        let a = CrtPoly::random(&mut rng);
         let mut b = a.clone();
         b *= &sk.s;
         // We approximate the discrete gaussian distribution of variance 10 with
@@ -560,7 +622,7 @@ where
         // iterations and the maximum magnitude is 20.
         const ITERATIONS: usize = 20;
         let e: Vec<ExtendedUint<P>> =
-            add_centered_binomial_scaled(&PowerPoly::<P::PlaintextParams>::new(), ITERATIONS);
+            add_centered_binomial_scaled(rng, &PowerPoly::<P::PlaintextParams>::new(), ITERATIONS);
         b += &CrtPoly::from_power(ctx, &PowerPoly::from_signed_ints(&e)).await;
         Self { b, a }
     }
@@ -610,21 +672,45 @@ where
     }
 }
 
+/// Minimum number of bits of drowning noise that the ciphertext modulus must leave room for.
+pub const MIN_DROWN_BITS: usize = 40;
+
+/// Number of bits of the noise with which ciphertexts can be drowned without overflowing the
+/// ciphertext modulus.  This fails to compile if it is smaller than `MIN_DROWN_BITS`.
 pub const fn max_drown_bits<P>() -> usize
 where
     P: BgvParameters,
 {
-    <P::CiphertextParams as PolyParameters>::Residue::BITS - P::PlaintextResidue::BITS - 1
+    DrownBits::<P>::MAX
+}
+
+struct DrownBits<P>(PhantomData<P>);
+
+impl<P> DrownBits<P>
+where
+    P: BgvParameters,
+{
+    const MAX: usize = {
+        let cipher_bits = <P::CiphertextParams as PolyParameters>::Residue::BITS;
+        let plain_bits = P::PlaintextResidue::BITS;
+        assert!(
+            plain_bits + MIN_DROWN_BITS < cipher_bits,
+            "ciphertext modulus must exceed plaintext modulus by MIN_DROWN_BITS + 1 bits"
+        );
+        cipher_bits - plain_bits - 1
+    };
 }
 
 #[cfg(test)]
 mod tests {
     use crate::bgv::{
-        decrypt, encrypt, encrypt_and_drown, max_drown_bits,
+        decrypt, decrypt_centered, encrypt, encrypt_and_drown, encrypt_and_drown_with_rng,
+        encrypt_batch, encrypt_with_rng, encrypt_witness, noise,
         params::ToyBgv,
         poly::{power::PowerPoly, CrtContext},
-        Cleartext, PublicKey, SecretKey,
+        reduce_centered_into, CenteredUint, Cleartext, PublicKey, SecretKey,
     };
+    use crate::rng::test_rng;
 
     use super::poly::crt::CrtPoly;
 
@@ -647,6 +733,17 @@ mod tests {
         assert_eq!(pk, pk_roundtrip);
     }
 
+    #[tokio::test]
+    async fn keys_from_same_rng_are_equal() {
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen_with_rng(&ctx, test_rng(1)).await;
+        let sk_again = SecretKey::<ToyBgv>::gen_with_rng(&ctx, test_rng(1)).await;
+        assert_eq!(sk, sk_again);
+        let pk = PublicKey::gen_with_rng(&ctx, &sk, test_rng(2)).await;
+        let pk_again = PublicKey::gen_with_rng(&ctx, &sk, test_rng(2)).await;
+        assert_eq!(pk, pk_again);
+    }
+
     #[tokio::test]
     async fn serde_roundtrip_ciphertext() {
         let mut rng = rand::thread_rng();
@@ -683,6 +780,50 @@ mod tests {
         assert_eq!(plaintext, plaintext_roundtrip);
     }
 
+    #[tokio::test]
+    async fn decrypt_centered_is_small() {
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let plaintext = PowerPoly::random(&mut rng);
+        let ciphertext = encrypt(&ctx, &pk, &plaintext).await;
+        let centered = decrypt_centered(&ctx, &sk, &ciphertext).await;
+
+        // `|m + t e| < t + 2^fresh_noise_bits <= 2^(fresh_noise_bits + 1)`.
+        let bound = CenteredUint::<ToyBgv>::from_u32(1)
+            .shl_vartime(noise::fresh_noise_bits::<ToyBgv>() + 1);
+        for coeff in &centered {
+            assert!(coeff.wrapping_add(&bound) < bound.shl_vartime(1));
+        }
+
+        let mut plaintext_roundtrip = PowerPoly::new();
+        reduce_centered_into::<ToyBgv>(&centered, &mut plaintext_roundtrip);
+        assert_eq!(plaintext, plaintext_roundtrip);
+    }
+
+    #[tokio::test]
+    async fn encryption_with_rng_is_reproducible() {
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen_with_rng(&ctx, test_rng(1)).await;
+        let pk = PublicKey::gen_with_rng(&ctx, &sk, test_rng(2)).await;
+        let plaintext = PowerPoly::random(&mut test_rng(3));
+
+        let (ciphertext, witness) = encrypt_with_rng(&ctx, &pk, &plaintext, test_rng(4)).await;
+        let (ciphertext_again, _) = encrypt_with_rng(&ctx, &pk, &plaintext, test_rng(4)).await;
+        assert_eq!(ciphertext, ciphertext_again);
+        assert_eq!(encrypt_witness(&ctx, &pk, &witness).await, ciphertext);
+        assert_eq!(decrypt(&ctx, &sk, &ciphertext).await, plaintext);
+
+        let noise_bits = noise::drown_bits::<ToyBgv>(noise::fresh_noise_bits::<ToyBgv>());
+        let drowned =
+            encrypt_and_drown_with_rng(&ctx, &pk, &plaintext, noise_bits, test_rng(5)).await;
+        let drowned_again =
+            encrypt_and_drown_with_rng(&ctx, &pk, &plaintext, noise_bits, test_rng(5)).await;
+        assert_eq!(drowned, drowned_again);
+        assert_eq!(decrypt(&ctx, &sk, &drowned).await, plaintext);
+    }
+
     #[tokio::test]
     async fn homomorphic_add() {
         let mut rng = rand::thread_rng();
@@ -813,6 +954,21 @@ mod tests {
         assert_eq!(result, correct_result);
     }
 
+    #[tokio::test]
+    async fn encrypt_batch_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let plaintexts: Vec<_> = (0..3).map(|_| PowerPoly::random(&mut rng)).collect();
+        let noise_bits = noise::drown_bits::<ToyBgv>(noise::fresh_noise_bits::<ToyBgv>());
+        let ciphertexts = encrypt_batch(&ctx, &pk, &plaintexts, noise_bits).await;
+        assert_eq!(ciphertexts.len(), plaintexts.len());
+        for (plaintext, ciphertext) in plaintexts.iter().zip(&ciphertexts) {
+            assert_eq!(&decrypt(&ctx, &sk, ciphertext).await, plaintext);
+        }
+    }
+
     #[tokio::test]
     async fn mask_and_drown() {
         let mut rng = rand::thread_rng();
@@ -824,8 +980,9 @@ mod tests {
         let rhs = CrtPoly::random(&mut rng);
         let mask = CrtPoly::random(&mut rng);
         let lhs_ciphertext = encrypt(&ctx_ct, &pk, &PowerPoly::from_crt(&ctx_pt, &lhs).await).await;
-        // 1 more is ok most of the time, so we use it in tests.
-        let noise_bits = max_drown_bits::<ToyBgv>() + 1;
+        let noise_bits = noise::drown_bits::<ToyBgv>(noise::product_noise_bits::<ToyBgv>(
+            noise::fresh_noise_bits::<ToyBgv>(),
+        ));
         let mask_ciphertext = encrypt_and_drown(
             &ctx_ct,
             &pk,
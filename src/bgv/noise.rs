@@ -0,0 +1,133 @@
+//! Worst-case noise bounds of BGV ciphertexts and the resulting budget for noise drowning.
+//!
+//! The noise of a ciphertext `(c_0, c_1)` with plaintext `m` is `c_0 - s c_1 - m`, which is a
+//! multiple of the plaintext modulus `t`.  Bounds are given in bits, i.e., a bound of `b` means
+//! that all coefficients of the noise have a magnitude below `2^b`.  Decryption is correct as long
+//! as the magnitude is below `q / 2`, for which `2^(CIPHER_BITS - 2)` is a lower bound.
+//!
+//! All polynomials have `N = CYCLOTOMIC_DEGREE` coefficients.  The product of two polynomials with
+//! coefficients bounded by `x` and `y` has coefficients bounded by `2 N x y`, because the reduction
+//! modulo the cyclotomic polynomial (for prime `M`) at most doubles the coefficients.
+
+use super::{poly::PolyParameters, residue::GenericResidue, BgvParameters};
+
+/// Number of bits of `x`, i.e., `x < 2^bits(x)`.
+const fn bits(x: usize) -> usize {
+    (usize::BITS - x.leading_zeros()) as usize
+}
+
+const fn max(lhs: usize, rhs: usize) -> usize {
+    if lhs > rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
+
+const fn degree<P>() -> usize
+where
+    P: BgvParameters,
+{
+    <P::CiphertextParams as PolyParameters>::CYCLOTOMIC_DEGREE
+}
+
+/// Noise of a fresh encryption (`encrypt()`), which is `t (v e_0 + e_2 - s e_1)`, where `v` and
+/// `s` have coefficients in `[-1, 1]` and `e_0`, `e_1` and `e_2` have coefficients in `[-20, 20]`.
+pub const fn fresh_noise_bits<P>() -> usize
+where
+    P: BgvParameters,
+{
+    P::PlaintextResidue::BITS + bits(2 * 2 * degree::<P>() * 20 + 20)
+}
+
+/// Noise of a ciphertext that was proven with a ZKPoPK of the given parameters.
+///
+/// The verifier checks the bound `B = 3 (M - 1)^2 num_ciphertexts num_proofs inv_fail_prob` for
+/// `v`, `20 B` for `e_1` and `21 B` for the noise of the plaintext.  The witness that can be
+/// extracted from a prover satisfies twice these bounds, so the noise is bounded by
+/// `t B (2 * 2N * 20 + 2N * 2 * 20 + 2 * 21)`.
+pub const fn zkpopk_noise_bits<P>(
+    num_ciphertexts: usize,
+    num_proofs: usize,
+    inv_fail_prob: usize,
+) -> usize
+where
+    P: BgvParameters,
+{
+    let m = P::PlaintextParams::M;
+    let bound_bits = bits(3 * (m - 1) * (m - 1))
+        + bits(num_ciphertexts)
+        + bits(num_proofs)
+        + bits(inv_fail_prob);
+    P::PlaintextResidue::BITS + bound_bits + bits(160 * degree::<P>() + 42)
+}
+
+/// Noise after multiplying a ciphertext with noise bounded by `noise_bits` by a `Cleartext`, whose
+/// coefficients are below `t`.
+pub const fn product_noise_bits<P>(noise_bits: usize) -> usize
+where
+    P: BgvParameters,
+{
+    noise_bits + P::PlaintextResidue::BITS + bits(2 * degree::<P>())
+}
+
+/// Number of bits of drowning noise for `encrypt_and_drown()`, whose result is subtracted from a
+/// ciphertext with noise bounded by `baseline_noise_bits`.
+///
+/// The encryption adds fresh noise and the drowning noise, which is below `2^(noise_bits - 1) t`.
+/// For the sum to stay below `2^(CIPHER_BITS - 2)`, the drowning noise gets half of it, and the
+/// baseline and fresh noise must fit into the other half.  In debug builds, this asserts that they
+/// do.
+pub const fn drown_bits<P>(baseline_noise_bits: usize) -> usize
+where
+    P: BgvParameters,
+{
+    let cipher_bits = <P::CiphertextParams as PolyParameters>::Residue::BITS;
+    let other_noise_bits = max(baseline_noise_bits, fresh_noise_bits::<P>()) + 1;
+    debug_assert!(
+        other_noise_bits + 3 <= cipher_bits,
+        "noise exceeds the decryption-correctness margin"
+    );
+    super::max_drown_bits::<P>() - 1
+}
+
+/// Number of bits by which the drowning noise of `drown_bits()` exceeds the baseline noise, which
+/// determines how well the drowning hides the baseline noise.  It should be at least
+/// `MIN_DROWN_BITS` for production parameters.
+pub const fn drown_margin_bits<P>(baseline_noise_bits: usize) -> usize
+where
+    P: BgvParameters,
+{
+    let drown_noise_bits = drown_bits::<P>(baseline_noise_bits) - 1 + P::PlaintextResidue::BITS;
+    drown_noise_bits.saturating_sub(baseline_noise_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bgv::params::ToyBgv;
+    use crate::bgv::poly::PolyParameters;
+    use crate::bgv::residue::GenericResidue;
+    use crate::bgv::BgvParameters;
+
+    use super::{drown_bits, fresh_noise_bits, product_noise_bits, zkpopk_noise_bits};
+
+    #[test]
+    fn toy_budgets() {
+        let cipher_bits =
+            <<ToyBgv as BgvParameters>::CiphertextParams as PolyParameters>::Residue::BITS;
+        let fresh = fresh_noise_bits::<ToyBgv>();
+        let proven = zkpopk_noise_bits::<ToyBgv>(16, 2, 256);
+        assert!(fresh < proven);
+        for baseline in [
+            fresh,
+            product_noise_bits::<ToyBgv>(fresh),
+            product_noise_bits::<ToyBgv>(proven),
+        ] {
+            assert!(baseline + 3 < cipher_bits);
+            assert_eq!(
+                drown_bits::<ToyBgv>(baseline),
+                crate::bgv::max_drown_bits::<ToyBgv>() - 1
+            );
+        }
+    }
+}
@@ -0,0 +1,241 @@
+/// Defines a parameter set for the plaintext or ciphertext ring of BGV.
+///
+/// A ciphertext parameter set has a prime modulus (given as big-endian hex string of `limbs` limbs)
+/// and uses the Fourier CRT strategy.  `generator` must generate the multiplicative group modulo the
+/// modulus.
+///
+/// ```ignore
+/// multipars::define_bgv_params! {
+///     ciphertext MyCipher {
+///         limbs: 5,
+///         modulus: "007fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed50d001",
+///         m: 337,
+///         cyclotomic_degree: 336,
+///         factor_count: 336,
+///         factor_degree: 1,
+///         slot_generator: 10,
+///         slot_generator_inverse: 236,
+///         generator: 5,
+///     }
+/// }
+/// ```
+///
+/// A plaintext parameter set has modulus `2^bits`.  If it is used with the tweaked interpolation
+/// packing, then the CRT factors are read from `factors_file` and `delta` must be given.  The file
+/// must carry `m`, `bits` and a checksum, see `scripts/checksum_factors.py`.
+///
+/// ```ignore
+/// multipars::define_bgv_params! {
+///     plaintext MyPlain {
+///         bits: 112,
+///         limbs: 2,
+///         m: 337,
+///         cyclotomic_degree: 336,
+///         crt {
+///             factor_count: 16,
+///             factor_degree: 21,
+///             slot_generator: 191,
+///             slot_generator_inverse: 30,
+///             factors_file: "params/phi337_mod_t112.json",
+///             delta: 8,
+///         }
+///     }
+/// }
+/// ```
+///
+/// The consistency of the given constants is checked at compile time.  At most
+/// `multipars::bgv::generic_uint::MAX_LIMBS` limbs are supported.
+#[macro_export]
+macro_rules! define_bgv_params {
+    (
+        ciphertext $name:ident {
+            limbs: $limbs:tt,
+            modulus: $modulus:literal,
+            m: $m:expr,
+            cyclotomic_degree: $degree:expr,
+            factor_count: $factor_count:expr,
+            factor_degree: $factor_degree:expr,
+            slot_generator: $slot_generator:expr,
+            slot_generator_inverse: $slot_generator_inverse:expr,
+            generator: $generator:expr $(,)?
+        }
+    ) => {
+        $crate::__check_limbs!($limbs);
+
+        $crate::bgv::params::__private::crypto_bigint::impl_modulus!(
+            $name,
+            $crate::bgv::params::__private::crypto_bigint::Uint<$limbs>,
+            $modulus
+        );
+
+        impl $crate::bgv::poly::PolyParameters for $name {
+            type Vec = $crate::bgv::residue::vec::ResidueVec<Self, $limbs>;
+            type Residue =
+                <Self::Vec as $crate::bgv::residue::vec::GenericResidueVec>::Residue;
+            type Uint = <Self::Residue as $crate::bgv::residue::GenericResidue>::Uint;
+
+            const M: usize = $m;
+            const CYCLOTOMIC_DEGREE: usize = $degree;
+        }
+
+        impl $crate::bgv::poly::crt::CrtPolyParameters for $name {
+            const FACTOR_COUNT: usize = $factor_count;
+            const FACTOR_DEGREE: usize = $factor_degree;
+            const SLOT_GENERATOR: usize = $slot_generator;
+            const SLOT_GENERATOR_INVERSE: usize = $slot_generator_inverse;
+            const CRT_STRATEGY: $crate::bgv::poly::CrtStrategy =
+                $crate::bgv::poly::CrtStrategy::Fourier;
+            const GENERATOR: Self::Residue =
+                $crate::bgv::params::__private::crypto_bigint::modular::constant_mod::Residue::new(
+                    &$crate::bgv::params::__private::crypto_bigint::Uint::<$limbs>::from_u64(
+                        $generator,
+                    ),
+                );
+        }
+
+        $crate::__impl_fourier_crt_poly_parameters!($name);
+
+        const _: () = {
+            $crate::bgv::params::__private::check_slots($m, $degree, $factor_count, $factor_degree);
+            $crate::bgv::params::__private::check_slot_generator(
+                $m,
+                $slot_generator,
+                $slot_generator_inverse,
+            );
+            // The Fourier CRT strategy requires an m-th root of unity.
+            let order = $crate::bgv::params::__private::crypto_bigint::Uint::<$limbs>::from_be_hex(
+                $modulus,
+            )
+            .wrapping_sub(&$crate::bgv::params::__private::crypto_bigint::Uint::ONE);
+            let rem = order
+                .const_rem(&$crate::bgv::params::__private::crypto_bigint::Uint::from_u64(
+                    $m as u64,
+                ))
+                .0;
+            let mut i = 0;
+            while i < $limbs {
+                assert!(rem.as_limbs()[i].0 == 0, "m must divide modulus - 1");
+                i += 1;
+            }
+        };
+    };
+    (
+        plaintext $name:ident {
+            bits: $bits:literal,
+            limbs: $limbs:tt,
+            m: $m:expr,
+            cyclotomic_degree: $degree:expr
+            $(,
+                crt {
+                    factor_count: $factor_count:expr,
+                    factor_degree: $factor_degree:expr,
+                    slot_generator: $slot_generator:expr,
+                    slot_generator_inverse: $slot_generator_inverse:expr,
+                    factors_file: $file:literal,
+                    delta: $delta:expr $(,)?
+                }
+            )? $(,)?
+        }
+    ) => {
+        #[derive(Debug, PartialEq)]
+        pub struct $name {}
+
+        impl $crate::bgv::poly::PolyParameters for $name {
+            type Vec = $crate::bgv::residue::vec::NativeResidueVec<$bits, $limbs>;
+            type Residue =
+                <Self::Vec as $crate::bgv::residue::vec::GenericResidueVec>::Residue;
+            type Uint = <Self::Residue as $crate::bgv::residue::GenericResidue>::Uint;
+
+            const M: usize = $m;
+            const CYCLOTOMIC_DEGREE: usize = $degree;
+        }
+
+        $crate::__check_limbs!($limbs);
+
+        const _: () = assert!(
+            $bits <= 64 * $limbs && $bits > 64 * ($limbs - 1),
+            "bits must fit into the last limb"
+        );
+
+        $(
+            impl $crate::bgv::poly::crt::CrtPolyParameters for $name {
+                const FACTOR_COUNT: usize = $factor_count;
+                const FACTOR_DEGREE: usize = $factor_degree;
+                const SLOT_GENERATOR: usize = $slot_generator;
+                const SLOT_GENERATOR_INVERSE: usize = $slot_generator_inverse;
+                const CRT_STRATEGY: $crate::bgv::poly::CrtStrategy =
+                    $crate::bgv::poly::CrtStrategy::Factors { file: $file };
+                // The multiplicative group is not cyclic
+                const GENERATOR: Self::Residue =
+                    $crate::bgv::params::__private::crypto_bigint::Zero::ZERO;
+            }
+
+            impl $crate::bgv::tweaked_interpolation_packing::TIPParameters for $name {
+                const DELTA: u32 = $delta;
+            }
+
+            const _: () = {
+                $crate::bgv::params::__private::check_slots(
+                    $m,
+                    $degree,
+                    $factor_count,
+                    $factor_degree,
+                );
+                $crate::bgv::params::__private::check_slot_generator(
+                    $m,
+                    $slot_generator,
+                    $slot_generator_inverse,
+                );
+            };
+        )?
+    };
+}
+
+/// Fails with a readable error if `limbs` exceeds `MAX_LIMBS`, before the compiler reports missing
+/// trait implementations.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __check_limbs {
+    (1) => {};
+    (2) => {};
+    (3) => {};
+    (4) => {};
+    (5) => {};
+    (6) => {};
+    (7) => {};
+    (8) => {};
+    (9) => {};
+    (10) => {};
+    (11) => {};
+    (12) => {};
+    (13) => {};
+    (14) => {};
+    (15) => {};
+    ($limbs:tt) => {
+        compile_error!(concat!(
+            "limbs must be a literal between 1 and 15 (MAX_LIMBS), got ",
+            stringify!($limbs)
+        ));
+    };
+}
+
+/// Items used by `define_bgv_params!`.
+#[doc(hidden)]
+pub mod __private {
+    pub use crypto_bigint;
+
+    pub const fn check_slots(m: usize, degree: usize, factor_count: usize, factor_degree: usize) {
+        assert!(degree < m, "cyclotomic_degree must be smaller than m");
+        assert!(
+            factor_count * factor_degree == degree,
+            "factor_count * factor_degree must equal cyclotomic_degree"
+        );
+    }
+
+    pub const fn check_slot_generator(m: usize, generator: usize, inverse: usize) {
+        assert!(
+            generator * inverse % m == 1,
+            "slot_generator_inverse must be the inverse of slot_generator modulo m"
+        );
+    }
+}
@@ -1,29 +1,53 @@
+mod define;
+
+#[doc(hidden)]
+pub use self::define::__private;
+
 // Toy parameters for k=s=32
 pub mod phi179_mod_p163;
 pub mod phi179_mod_t64;
-pub mod phi337_mod_p259;
-pub mod phi337_mod_t86;
+pub mod phi337_mod_p311;
+pub mod phi337_mod_t112;
+
+// The production parameters are behind the `params-*` features, since their const generics take
+// long to compile.
 
 // Production parameters for k=s=32
+#[cfg(feature = "params-k32")]
 pub mod phi21851_mod_p188;
+#[cfg(feature = "params-k32")]
 pub mod phi21851_mod_t64;
+#[cfg(feature = "params-k32")]
 pub mod phi43691_mod_p387;
+#[cfg(feature = "params-k32")]
 pub mod phi43691_mod_t135;
 
 // Production parameters for k=s=64
+#[cfg(feature = "params-k64")]
 pub mod phi21851_mod_p316;
+#[cfg(feature = "params-k64")]
 pub mod phi21851_mod_t128;
+#[cfg(feature = "params-k64")]
 pub mod phi43691_mod_p616;
+#[cfg(feature = "params-k64")]
 pub mod phi43691_mod_t233;
 
 // Production parameters for k=128, s=64
+#[cfg(feature = "params-k128")]
 pub mod phi21851_mod_p444;
+#[cfg(feature = "params-k128")]
 pub mod phi21851_mod_t192;
+#[cfg(feature = "params-k128")]
 pub mod phi43691_mod_p744;
+#[cfg(feature = "params-k128")]
 pub mod phi43691_mod_t297;
 
-use self::{phi337_mod_p259::Phi337ModP259, phi337_mod_t86::Phi337ModT86};
+// Power-of-two cyclotomic ciphertext parameters (negacyclic NTT) for `N = 2048`, e.g., for
+// interoperability with other HE libraries.  There is no matching plaintext parameter set.
+pub mod phi4096_mod_p54;
+
+use self::{phi337_mod_p311::Phi337ModP311, phi337_mod_t112::Phi337ModT112};
 
-pub type ToyCipher = Phi337ModP259;
-pub type ToyPlain = Phi337ModT86;
+pub type ToyCipher = Phi337ModP311;
+pub type ToyPlain = Phi337ModT112;
 pub type ToyBgv = (ToyPlain, ToyCipher);
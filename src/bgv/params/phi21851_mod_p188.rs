@@ -33,3 +33,5 @@ impl CrtPolyParameters for Phi21851ModP188 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&U192::from_u64(3));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi21851ModP188);
@@ -34,3 +34,5 @@ impl CrtPolyParameters for Phi21851ModP316 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&Uint::<5>::from_u64(7));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi21851ModP316);
@@ -34,3 +34,5 @@ impl CrtPolyParameters for Phi21851ModP444 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&U448::from_u64(5));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi21851ModP444);
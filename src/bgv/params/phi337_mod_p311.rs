@@ -11,12 +11,12 @@ use crate::bgv::{
 };
 
 impl_modulus!(
-    Phi337ModP259,
+    Phi337ModP311,
     Uint::<5>,
-    "0000000000000007ffffffffffffffffffffffffffffffffffffffffffffffffffffffffff975801"
+    "007fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed50d001"
 );
 
-impl PolyParameters for Phi337ModP259 {
+impl PolyParameters for Phi337ModP311 {
     type Vec = ResidueVec<Self, 5>;
     type Residue = <Self::Vec as GenericResidueVec>::Residue;
     type Uint = <Self::Residue as GenericResidue>::Uint;
@@ -25,7 +25,7 @@ impl PolyParameters for Phi337ModP259 {
     const CYCLOTOMIC_DEGREE: usize = 336;
 }
 
-impl CrtPolyParameters for Phi337ModP259 {
+impl CrtPolyParameters for Phi337ModP311 {
     const FACTOR_COUNT: usize = 336;
     const FACTOR_DEGREE: usize = 1;
     const SLOT_GENERATOR: usize = 10;
@@ -33,3 +33,5 @@ impl CrtPolyParameters for Phi337ModP259 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&Uint::<5>::from_u64(5));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi337ModP311);
@@ -12,10 +12,10 @@ use crate::bgv::{
 };
 
 #[derive(Debug, PartialEq)]
-pub struct Phi337ModT86 {}
+pub struct Phi337ModT112 {}
 
-impl PolyParameters for Phi337ModT86 {
-    type Vec = NativeResidueVec<86, 2>;
+impl PolyParameters for Phi337ModT112 {
+    type Vec = NativeResidueVec<112, 2>;
     type Residue = <Self::Vec as GenericResidueVec>::Residue;
     type Uint = <Self::Residue as GenericResidue>::Uint;
 
@@ -23,17 +23,17 @@ impl PolyParameters for Phi337ModT86 {
     const CYCLOTOMIC_DEGREE: usize = 336;
 }
 
-impl CrtPolyParameters for Phi337ModT86 {
+impl CrtPolyParameters for Phi337ModT112 {
     const FACTOR_COUNT: usize = 16;
     const FACTOR_DEGREE: usize = 21;
     const SLOT_GENERATOR: usize = 191;
     const SLOT_GENERATOR_INVERSE: usize = 30;
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Factors {
-        file: "params/phi337_mod_t86.json",
+        file: "params/phi337_mod_t112.json",
     };
     const GENERATOR: Self::Residue = Zero::ZERO; // Multiplicative group is not cyclic
 }
 
-impl TIPParameters for Phi337ModT86 {
+impl TIPParameters for Phi337ModT112 {
     const DELTA: u32 = 8;
 }
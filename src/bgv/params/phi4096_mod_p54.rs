@@ -0,0 +1,34 @@
+// Ciphertext parameters for the power-of-two cyclotomic `X^2048 + 1`, whose CRT basis is computed
+// by a negacyclic NTT
+
+use crypto_bigint::{impl_modulus, modular::constant_mod::Residue, Uint};
+
+use crate::bgv::{
+    poly::{crt::CrtPolyParameters, CrtStrategy, PolyParameters},
+    residue::{
+        vec::{GenericResidueVec, ResidueVec},
+        GenericResidue,
+    },
+};
+
+impl_modulus!(Phi4096ModP54, Uint::<1>, "003ffffffffed001");
+
+impl PolyParameters for Phi4096ModP54 {
+    type Vec = ResidueVec<Self, 1>;
+    type Residue = <Self::Vec as GenericResidueVec>::Residue;
+    type Uint = <Self::Residue as GenericResidue>::Uint;
+
+    const M: usize = 4096;
+    const CYCLOTOMIC_DEGREE: usize = 2048;
+}
+
+impl CrtPolyParameters for Phi4096ModP54 {
+    const FACTOR_COUNT: usize = 2048;
+    const FACTOR_DEGREE: usize = 1;
+    // The unit group modulo `M` is not cyclic, so the slot generator is not used by the
+    // negacyclic strategy.
+    const SLOT_GENERATOR: usize = 5;
+    const SLOT_GENERATOR_INVERSE: usize = 3277;
+    const CRT_STRATEGY: CrtStrategy = CrtStrategy::Negacyclic;
+    const GENERATOR: Self::Residue = Residue::new(&Uint::<1>::from_u64(11));
+}
@@ -34,3 +34,5 @@ impl CrtPolyParameters for Phi43691ModP387 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&U448::from_u64(17));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi43691ModP387);
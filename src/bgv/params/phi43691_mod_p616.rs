@@ -35,3 +35,5 @@ impl CrtPolyParameters for Phi43691ModP616 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&Uint::<10>::from_u64(7));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi43691ModP616);
@@ -35,3 +35,5 @@ impl CrtPolyParameters for Phi43691ModP744 {
     const CRT_STRATEGY: CrtStrategy = CrtStrategy::Fourier;
     const GENERATOR: Self::Residue = Residue::new(&U768::from_u64(3));
 }
+
+crate::__impl_fourier_crt_poly_parameters!(Phi43691ModP744);
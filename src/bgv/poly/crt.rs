@@ -1,15 +1,20 @@
-use std::ops::{AddAssign, MulAssign, SubAssign};
+use std::borrow::Borrow;
+use std::ops::{AddAssign, MulAssign, Neg, ShlAssign, SubAssign};
 
 use crypto_bigint::{Random, Zero};
 use forward_ref_generic::forward_ref_op_assign;
 use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 
-use crate::bgv::{fourier::fast_fourier_transform, residue::vec::GenericResidueVec};
+use crate::bgv::{
+    fourier::fast_fourier_transform,
+    residue::{vec::GenericResidueVec, GenericResidue},
+};
+use crate::util;
 
 use super::{
-    power::PowerPoly, CrtContext, CrtStrategy, Diagonal, FactorsContext, FourierContext,
-    FourierCrtPolyParameters, PolyParameters,
+    power::PowerPoly, small_residue, CrtContext, CrtStrategy, Diagonal, FactorsContext,
+    FourierContext, FourierCrtPolyParameters, NegacyclicContext, PolyParameters,
 };
 
 pub trait CrtPolyParameters: PolyParameters {
@@ -27,9 +32,19 @@ pub struct CrtPoly<P>
 where
     P: CrtPolyParameters,
 {
+    #[serde(deserialize_with = "super::deserialize_coefficients::<P, _>")]
     pub coefficients: P::Vec, // TODO: Non-public.
 }
 
+impl<P> Default for CrtPoly<P>
+where
+    P: CrtPolyParameters,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<P> CrtPoly<P>
 where
     P: CrtPolyParameters,
@@ -49,6 +64,7 @@ where
         match ctx {
             CrtContext::Factors(ctx) => self.clone_from_power_via_factors(ctx, power).await,
             CrtContext::Fourier(ctx) => self.clone_from_power_via_fourier(ctx, power).await,
+            CrtContext::Negacyclic(ctx) => self.clone_from_power_via_negacyclic(ctx, power).await,
         }
     }
 
@@ -58,8 +74,7 @@ where
         power: &PowerPoly<P>,
     ) {
         for factor_index in 0..P::FACTOR_COUNT {
-            let mut reduced = Vec::new();
-            reduced.reserve(P::M);
+            let mut reduced = Vec::with_capacity(P::M);
             reduced.extend(power.coefficients.iter());
             reduced.push(reduced[0]);
             reduced[0] = Zero::ZERO;
@@ -70,10 +85,10 @@ where
                     reduced[leading_exp - P::FACTOR_DEGREE + exp] -= offset;
                 }
             }
-            for exp in 0..P::FACTOR_DEGREE {
-                self.coefficients[factor_index * P::FACTOR_DEGREE + exp] = reduced[exp];
-            }
-            tokio::task::yield_now().await;
+            self.coefficients.as_mut_slice()
+                [factor_index * P::FACTOR_DEGREE..(factor_index + 1) * P::FACTOR_DEGREE]
+                .copy_from_slice(&reduced[..P::FACTOR_DEGREE]);
+            util::yield_now().await;
         }
     }
 
@@ -105,17 +120,32 @@ where
         }
         let padded = fast_fourier_transform(&ctx.dft_root_powers, true, padded_fft).await;
 
-        for (dst, src) in self.coefficients.iter_mut().zip(padded.iter()) {
-            *dst = *src; // TODO: Use vectorized copy
+        let (low, high) = padded.as_slice().split_at(P::CYCLOTOMIC_DEGREE);
+        self.coefficients.as_mut_slice().copy_from_slice(low);
+        for (dst, src) in self
+            .coefficients
+            .iter_mut()
+            .zip(&high[..P::CYCLOTOMIC_DEGREE - 1])
+        {
+            *dst += *src;
         }
-        for (dst, src) in self.coefficients.iter_mut().zip(
-            padded
-                .iter()
-                .skip(P::CYCLOTOMIC_DEGREE)
-                .take(P::CYCLOTOMIC_DEGREE - 1),
-        ) {
-            *dst += *src; // TODO: Use vectorized copy
+    }
+
+    async fn clone_from_power_via_negacyclic(
+        &mut self,
+        ctx: &NegacyclicContext<P>,
+        power: &PowerPoly<P>,
+    ) {
+        // Evaluating at `\psi^{2i+1}` is a DFT of the coefficients twisted by powers of `\psi`.
+        let mut twisted = P::Vec::new(P::CYCLOTOMIC_DEGREE);
+        for ((dst, src), psi_power) in twisted
+            .iter_mut()
+            .zip(power.coefficients.iter())
+            .zip(ctx.psi_powers.iter())
+        {
+            *dst = *src * *psi_power;
         }
+        self.coefficients = fast_fourier_transform(&ctx.dft_root_powers, false, twisted).await;
     }
 
     pub async fn from_power(ctx: &CrtContext<P>, power: &PowerPoly<P>) -> Self {
@@ -131,6 +161,88 @@ where
         }
         this
     }
+
+    /// Adds all `polys` to `self`, reducing each coefficient only once at the end (see
+    /// `GenericResidue::add_lazy()`).
+    pub fn accumulate<I>(&mut self, polys: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self>,
+    {
+        let mut sums =
+            vec![<P::Residue as GenericResidue>::LazySum::default(); P::CYCLOTOMIC_DEGREE];
+        for (sum, coeff) in sums.iter_mut().zip(self.coefficients.iter()) {
+            P::Residue::add_lazy(sum, *coeff);
+        }
+        for poly in polys {
+            for (sum, coeff) in sums.iter_mut().zip(poly.borrow().coefficients.iter()) {
+                P::Residue::add_lazy(sum, *coeff);
+            }
+        }
+        for (dst, sum) in self.coefficients.iter_mut().zip(sums) {
+            *dst = P::Residue::reduce_lazy(sum);
+        }
+    }
+}
+
+impl<P> CrtPoly<P>
+where
+    P: FourierCrtPolyParameters,
+{
+    /// Adds `rhs * X^rotate_right` like `PowerPoly::add_assign_rotated()`, but in the slot domain,
+    /// where slot `i` is the evaluation at `\zeta^{g^i}`.
+    pub fn add_assign_rotated(&mut self, ctx: &CrtContext<P>, rhs: &Self, rotate_right: usize) {
+        let ctx = fourier_context(ctx);
+        let mut exp = 1;
+        for (dst, src) in self.coefficients.iter_mut().zip(rhs.coefficients.iter()) {
+            *dst += *src * ctx.mth_root_powers[exp * (rotate_right % P::M) % P::M];
+            exp = exp * P::SLOT_GENERATOR % P::M;
+        }
+    }
+
+    /// Adds `rhs * (1 + X + ... + X^{length - 1})` like `PowerPoly::add_assign_slided()`, but in
+    /// the slot domain, where the sum is `(\zeta^{g^i length} - 1) / (\zeta^{g^i} - 1)` in slot
+    /// `i`.
+    pub fn add_assign_slided(&mut self, ctx: &CrtContext<P>, rhs: &Self, length: usize) {
+        for_each_slide_factor(ctx, length, |slot, factor| {
+            self.coefficients[slot] += rhs.coefficients[slot] * factor;
+        });
+    }
+
+    /// Sets `self` to `rhs * (1 + X + ... + X^{length - 1})`, see `add_assign_slided()`.
+    pub fn clone_from_slided(&mut self, ctx: &CrtContext<P>, rhs: &Self, length: usize) {
+        for_each_slide_factor(ctx, length, |slot, factor| {
+            self.coefficients[slot] = rhs.coefficients[slot] * factor;
+        });
+    }
+}
+
+/// Calls `f` with each slot and the evaluation of `1 + X + ... + X^{length - 1}` in it.
+fn for_each_slide_factor<P, F>(ctx: &CrtContext<P>, length: usize, mut f: F)
+where
+    P: CrtPolyParameters,
+    F: FnMut(usize, P::Residue),
+{
+    let ctx = fourier_context(ctx);
+    let one = ctx.mth_root_powers[0];
+    let mut exp = 1;
+    for slot in 0..P::CYCLOTOMIC_DEGREE {
+        let numerator = ctx.mth_root_powers[exp * (length % P::M) % P::M] - one;
+        f(slot, numerator * ctx.slide_denominators[exp]);
+        exp = exp * P::SLOT_GENERATOR % P::M;
+    }
+}
+
+fn fourier_context<P>(ctx: &CrtContext<P>) -> &FourierContext<P>
+where
+    P: CrtPolyParameters,
+{
+    match ctx {
+        CrtContext::Fourier(ctx) => ctx,
+        CrtContext::Factors(_) | CrtContext::Negacyclic(_) => {
+            panic!("slot-domain operations require a Fourier context")
+        }
+    }
 }
 
 impl<P> Clone for CrtPoly<P>
@@ -220,13 +332,13 @@ where
             for factor_index in 0..P::FACTOR_COUNT {
                 for j in (0..P::FACTOR_DEGREE).rev() {
                     let rhs_coeff = rhs.coefficients[factor_index * P::FACTOR_DEGREE + j];
-                    for i in 0..P::FACTOR_DEGREE {
+                    for (i, temp_coeff) in temp.iter_mut().enumerate() {
                         let lhs_coeff = self.coefficients[factor_index * P::FACTOR_DEGREE + i];
                         let prod = lhs_coeff * rhs_coeff;
                         if j == P::FACTOR_DEGREE - 1 {
-                            temp[i] = prod;
+                            *temp_coeff = prod;
                         } else {
-                            temp[i] += prod;
+                            *temp_coeff += prod;
                         }
                     }
                     if j != 0 {
@@ -240,9 +352,9 @@ where
                             temp[i] = shifted - offset;
                         }
                     } else {
-                        for i in 0..P::FACTOR_DEGREE {
-                            self.coefficients[factor_index * P::FACTOR_DEGREE + i] = temp[i];
-                        }
+                        self.coefficients.as_mut_slice()[factor_index * P::FACTOR_DEGREE
+                            ..(factor_index + 1) * P::FACTOR_DEGREE]
+                            .copy_from_slice(&temp);
                     }
                 }
             }
@@ -282,6 +394,66 @@ forward_ref_op_assign!(
     where P: CrtPolyParameters
 );
 
+impl<P> Neg for CrtPoly<P>
+where
+    P: CrtPolyParameters,
+{
+    type Output = Self;
+
+    fn neg(mut self) -> Self {
+        for coeff in self.coefficients.iter_mut() {
+            *coeff = P::Residue::ZERO - *coeff;
+        }
+        self
+    }
+}
+
+impl<P> Neg for &CrtPoly<P>
+where
+    P: CrtPolyParameters,
+{
+    type Output = CrtPoly<P>;
+
+    fn neg(self) -> CrtPoly<P> {
+        -self.clone()
+    }
+}
+
+/// Multiplication by a small integer, e.g. `3 * poly`, without constructing a `Diagonal`.
+impl<P> MulAssign<u64> for CrtPoly<P>
+where
+    P: CrtPolyParameters,
+{
+    fn mul_assign(&mut self, rhs: u64) {
+        match rhs {
+            0 => {
+                for coeff in self.coefficients.iter_mut() {
+                    *coeff = P::Residue::ZERO;
+                }
+            }
+            1 => {}
+            2 => {
+                for coeff in self.coefficients.iter_mut() {
+                    *coeff = *coeff + *coeff;
+                }
+            }
+            _ => *self *= Diagonal(small_residue::<P::Residue>(rhs)),
+        }
+    }
+}
+
+/// Multiplication by `2^bits`.
+impl<P> ShlAssign<usize> for CrtPoly<P>
+where
+    P: CrtPolyParameters,
+{
+    fn shl_assign(&mut self, bits: usize) {
+        if bits != 0 {
+            *self *= Diagonal(small_residue::<P::Residue>(2).pow_usize_vartime(bits));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crypto_bigint::{Random, Zero};
@@ -459,4 +631,43 @@ mod tests {
 
         assert_eq!(result, correct_result);
     }
+
+    #[tokio::test]
+    async fn ciphertext_slot_domain_rotate_and_slide() {
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::<ToyCipher>::gen().await;
+        let lhs = PowerPoly::<ToyCipher>::random(&mut rng);
+        let rhs = PowerPoly::<ToyCipher>::random(&mut rng);
+        let crt_lhs = CrtPoly::from_power(&ctx, &lhs).await;
+        let crt_rhs = CrtPoly::from_power(&ctx, &rhs).await;
+
+        for shift in [0, 1, rng.gen_range(0..ToyCipher::M), ToyCipher::M - 1] {
+            let mut expected = lhs.clone();
+            expected.add_assign_rotated(&rhs, shift);
+            let mut actual = crt_lhs.clone();
+            actual.add_assign_rotated(&ctx, &crt_rhs, shift);
+            assert_eq!(PowerPoly::from_crt(&ctx, &actual).await, expected);
+
+            let mut expected = lhs.clone();
+            expected.add_assign_slided(&rhs, shift);
+            let mut actual = crt_lhs.clone();
+            actual.add_assign_slided(&ctx, &crt_rhs, shift);
+            assert_eq!(PowerPoly::from_crt(&ctx, &actual).await, expected);
+        }
+    }
+
+    #[test]
+    fn ciphertext_accumulate() {
+        let mut rng = rand::thread_rng();
+        let mut actual = CrtPoly::<ToyCipher>::random(&mut rng);
+        let mut expected = actual.clone();
+        let polys: Vec<_> = (0..100)
+            .map(|_| CrtPoly::<ToyCipher>::random(&mut rng))
+            .collect();
+        for poly in &polys {
+            expected += poly;
+        }
+        actual.accumulate(&polys);
+        assert_eq!(actual, expected);
+    }
 }
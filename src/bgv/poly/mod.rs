@@ -1,7 +1,8 @@
-use std::{fmt::Debug, fs::File, io::BufReader};
+use std::{fmt::Debug, io};
 
-use crypto_bigint::{Integer, U64};
-use serde::{Deserialize, Serialize};
+use crypto_bigint::{Integer, Zero, U64};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::bgv::generic_uint::GenericUint;
 
@@ -14,8 +15,38 @@ use super::{
 };
 
 pub mod crt;
+// The bound on `CRT_STRATEGY` is rejected by the parser of stable toolchains even if it is
+// configured out, so it lives in a module that is only read with the `nightly` feature.
+#[cfg(feature = "nightly")]
+mod nightly;
 pub mod power;
 
+#[cfg(feature = "nightly")]
+pub use self::nightly::FourierCrtPolyParameters;
+
+/// Converts a small integer, e.g. a scalar factor, to a residue.
+pub(crate) fn small_residue<R: GenericResidue>(value: u64) -> R {
+    R::from_uint(U64::from_u64(value))
+}
+
+/// Deserializes the coefficients of a polynomial, for `#[serde(deserialize_with = "...")]`.  The
+/// other party determines the length, but the operations on polynomials index the coefficients up
+/// to `P::CYCLOTOMIC_DEGREE` and would panic on a shorter vector.
+pub(crate) fn deserialize_coefficients<'de, P, D>(deserializer: D) -> Result<P::Vec, D::Error>
+where
+    P: PolyParameters,
+    D: Deserializer<'de>,
+{
+    let coefficients = P::Vec::deserialize(deserializer)?;
+    if coefficients.len() != P::CYCLOTOMIC_DEGREE {
+        return Err(de::Error::invalid_length(
+            coefficients.len(),
+            &format!("{} coefficients", P::CYCLOTOMIC_DEGREE).as_str(),
+        ));
+    }
+    Ok(coefficients)
+}
+
 // We currently need to wrap residues in this annoying `Diagonal` struct when
 // using some overloaded operators, because otherwise the compiler refuses to
 // compile the overloaded operators due to conflicting implementations.
@@ -27,27 +58,62 @@ pub trait PolyParameters: PartialEq + Debug + Send + Sync + 'static {
     type Residue: GenericResidue<Uint = Self::Uint>;
     type Uint: ExtendableUint;
 
-    /// Determines the polynomial `\Phi_m(X)`.
+    /// Determines the polynomial `\Phi_m(X)`.  Either a prime or, for `CrtStrategy::Negacyclic`, a
+    /// power of two.
     const M: usize;
 
     /// Must be the degree of `\Phi_m(X)`, i.e. `\phi(m)`.
     const CYCLOTOMIC_DEGREE: usize;
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CrtStrategy {
-    Factors { file: &'static str },
+    Factors {
+        file: &'static str,
+    },
     Fourier,
+    /// Factors of degree 1, which are derived from the `m`-th roots of unity instead of being read
+    /// from a file.  This requires the same prime moduli as `Fourier` and is only meant for
+    /// experiments via `CrtContext::gen_with()`.
+    LinearFactors,
+    /// For `m = 2^n`, where `\Phi_m(X) = X^{m/2} + 1`.  The slots are the evaluations at the odd
+    /// powers of an `m`-th root of unity, which are computed by a negacyclic NTT.  This requires a
+    /// prime modulus `q` such that `q - 1` is a multiple of `m`.  In contrast to the other
+    /// strategies, coefficient `i` of a `PowerPoly` is the one of `X^i`, and the slot-domain
+    /// operations as well as the rotations and slides of `PowerPoly` are not supported.
+    Negacyclic,
 }
 
-pub trait FourierCrtPolyParameters: CrtPolyParameters
-where
-    Self: CrtPolyParameters<CRT_STRATEGY = { CrtStrategy::Fourier }>,
-{
+/// Parameters whose `CRT_STRATEGY` is `CrtStrategy::Fourier`.
+///
+/// With the `nightly` feature, this is implemented for all such parameters and the strategy is
+/// checked by the type system.  Otherwise, it must be implemented with
+/// `__impl_fourier_crt_poly_parameters!`, which checks the strategy at compile time.
+#[cfg(not(feature = "nightly"))]
+pub trait FourierCrtPolyParameters: CrtPolyParameters {}
+
+#[cfg(feature = "nightly")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_fourier_crt_poly_parameters {
+    ($name:ty) => {};
 }
 
-impl<P> FourierCrtPolyParameters for P where
-    P: CrtPolyParameters<CRT_STRATEGY = { CrtStrategy::Fourier }>
-{
+#[cfg(not(feature = "nightly"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_fourier_crt_poly_parameters {
+    ($name:ty) => {
+        impl $crate::bgv::poly::FourierCrtPolyParameters for $name {}
+
+        const _: () = assert!(
+            matches!(
+                <$name as $crate::bgv::poly::crt::CrtPolyParameters>::CRT_STRATEGY,
+                $crate::bgv::poly::CrtStrategy::Fourier
+            ),
+            "CRT_STRATEGY must be CrtStrategy::Fourier"
+        );
+    };
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -57,6 +123,7 @@ where
 {
     Factors(FactorsContext<P>),
     Fourier(FourierContext<P>),
+    Negacyclic(NegacyclicContext<P>),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -68,6 +135,139 @@ where
     pub basis_coefficients: P::Vec,
 }
 
+/// The file format of `CrtStrategy::Factors`.  `m` and `bits` identify the parameters the table
+/// was generated for, and `checksum` is the SHA-256 digest (in hex) of the compact JSON array
+/// `[m, bits, factors, basis_coefficients]`, see `scripts/checksum_factors.py`.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = ""))]
+struct FactorsFile<P>
+where
+    P: CrtPolyParameters,
+{
+    m: usize,
+    bits: usize,
+    checksum: String,
+    factors: P::Vec,
+    basis_coefficients: P::Vec,
+}
+
+/// Why a table of factors could not be loaded, see `CrtContext::try_gen()`.  `params` is the type
+/// name of the parameters that the table was loaded for.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum FactorsError {
+    #[display(fmt = "{}: failed to read factors for {}: {}", path, params, source)]
+    Io {
+        path: String,
+        params: &'static str,
+        source: io::Error,
+    },
+    #[display(fmt = "{}: failed to parse factors for {}: {}", path, params, source)]
+    Parse {
+        path: String,
+        params: &'static str,
+        source: serde_json::Error,
+    },
+    /// The table was generated for other parameters.
+    #[display(
+        fmt = "{}: factors do not match {}: expected {} {}, found {}",
+        path,
+        params,
+        expected,
+        field,
+        found
+    )]
+    Mismatch {
+        path: String,
+        params: &'static str,
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[display(fmt = "{}: factor {} for {} is not monic", path, index, params)]
+    NotMonic {
+        path: String,
+        params: &'static str,
+        index: usize,
+    },
+    /// The table was modified after its checksum was computed.
+    #[display(fmt = "{}: checksum mismatch for {}", path, params)]
+    ChecksumMismatch { path: String, params: &'static str },
+}
+
+impl<P> FactorsContext<P>
+where
+    P: CrtPolyParameters,
+{
+    /// Parses a table of factors and checks it against the parameters and its checksum.  `path` is
+    /// only used for the errors.
+    pub fn from_json(path: &str, json: &[u8]) -> Result<Self, FactorsError> {
+        let params = std::any::type_name::<P>();
+        let file: FactorsFile<P> =
+            serde_json::from_slice(json).map_err(|source| FactorsError::Parse {
+                path: path.into(),
+                params,
+                source,
+            })?;
+        let mismatch = |field, expected, found| FactorsError::Mismatch {
+            path: path.into(),
+            params,
+            field,
+            expected,
+            found,
+        };
+        let fields = [
+            ("m", P::M, file.m),
+            ("bits", P::Residue::BITS, file.bits),
+            (
+                "factor coefficients",
+                P::FACTOR_COUNT * (P::FACTOR_DEGREE + 1),
+                file.factors.len(),
+            ),
+            (
+                "basis coefficients",
+                P::FACTOR_COUNT,
+                file.basis_coefficients.len(),
+            ),
+        ];
+        for (field, expected, found) in fields {
+            if expected != found {
+                return Err(mismatch(field, expected, found));
+            }
+        }
+
+        // The checksum covers the entries as they are interpreted, i.e., after deserialization.
+        let canonical =
+            serde_json::to_vec(&(file.m, file.bits, &file.factors, &file.basis_coefficients))
+                .expect("serializing residues can't fail");
+        let checksum: String = Sha256::digest(canonical)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        if !checksum.eq_ignore_ascii_case(&file.checksum) {
+            return Err(FactorsError::ChecksumMismatch {
+                path: path.into(),
+                params,
+            });
+        }
+
+        let one = P::Residue::from_reduced(<P::Residue as GenericResidue>::Uint::ONE);
+        for index in 0..P::FACTOR_COUNT {
+            if file.factors[index * (P::FACTOR_DEGREE + 1) + P::FACTOR_DEGREE] != one {
+                return Err(FactorsError::NotMonic {
+                    path: path.into(),
+                    params,
+                    index,
+                });
+            }
+        }
+
+        Ok(Self {
+            factors: file.factors,
+            basis_coefficients: file.basis_coefficients,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FourierContext<P>
 where
@@ -76,6 +276,11 @@ where
     m_inverse: P::Residue,
     mth_root: P::Residue,
     mth_root_inverse: P::Residue,
+    /// `\zeta^k` for `k` in `0..m`, used for rotations in the slot domain.
+    mth_root_powers: P::Vec,
+    /// `(\zeta^k - 1)^{-1}` for `k` in `1..m` (and zero for `k = 0`), used for slides in the slot
+    /// domain.
+    slide_denominators: P::Vec,
     pub dft_size: usize,
     pub dft_size_inverse: P::Residue,
     kernel_from_crt: P::Vec,
@@ -83,38 +288,169 @@ where
     pub dft_root_powers: P::Vec,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NegacyclicContext<P>
+where
+    P: CrtPolyParameters,
+{
+    /// `\psi^i` for `i` in `0..m/2`, where `\psi` is a primitive `m`-th root of unity.
+    psi_powers: P::Vec,
+    /// `\psi^{-i} / (m/2)` for `i` in `0..m/2`.
+    psi_inverse_powers: P::Vec,
+    /// `\psi^{2i}` for `i` in `0..m/2`.
+    pub dft_root_powers: P::Vec,
+}
+
 impl<P> CrtContext<P>
 where
     P: CrtPolyParameters,
 {
+    /// # Panics
+    ///
+    /// Panics if the factors can't be loaded, see `try_gen()`.
     pub async fn gen() -> Self {
-        match P::CRT_STRATEGY {
-            CrtStrategy::Factors { file } => Self::read_factors(file).await,
-            CrtStrategy::Fourier => Self::gen_fourier().await,
+        Self::gen_with(P::CRT_STRATEGY).await
+    }
+
+    /// Like `gen()`, but returns an error if the factors of `CrtStrategy::Factors` can't be read or
+    /// don't match the parameters.
+    pub async fn try_gen() -> Result<Self, FactorsError> {
+        Self::try_gen_with(P::CRT_STRATEGY).await
+    }
+
+    /// Generates a context that uses `strategy` instead of `P::CRT_STRATEGY`.  All strategies
+    /// compute the same conversions, but `Fourier` and `LinearFactors` panic unless the modulus is
+    /// a prime `q` such that `q - 1` is a multiple of `m` (and, for `Fourier`, of the DFT size).
+    pub async fn gen_with(strategy: CrtStrategy) -> Self {
+        match Self::try_gen_with(strategy).await {
+            Ok(ctx) => ctx,
+            Err(err) => panic!("{}", err),
         }
     }
 
-    async fn read_factors(path: &str) -> Self {
-        // TODO: Error handling
-        let file = File::open(path).unwrap();
-        let reader = BufReader::new(file);
-        CrtContext::Factors(serde_json::from_reader(reader).unwrap())
+    /// Like `gen_with()`, but returns an error if the factors can't be loaded.
+    pub async fn try_gen_with(strategy: CrtStrategy) -> Result<Self, FactorsError> {
+        Ok(match strategy {
+            CrtStrategy::Factors { file } => Self::read_factors(file).await?,
+            CrtStrategy::Fourier => Self::gen_fourier().await,
+            CrtStrategy::LinearFactors => Self::gen_linear_factors().await,
+            CrtStrategy::Negacyclic => Self::gen_negacyclic().await,
+        })
     }
 
-    async fn gen_fourier() -> Self {
-        let (m_inverse, exists) = P::Residue::from_uint(U64::from_u64(P::M as u64)).invert();
-        assert!(bool::from(exists));
+    async fn read_factors(path: &str) -> Result<Self, FactorsError> {
+        // Without the `protocol` feature, there is no tokio runtime to offload the IO to.
+        #[cfg(feature = "protocol")]
+        let json = tokio::fs::read(path).await;
+        #[cfg(not(feature = "protocol"))]
+        let json = std::fs::read(path);
+        let json = json.map_err(|source| FactorsError::Io {
+            path: path.into(),
+            params: std::any::type_name::<P>(),
+            source,
+        })?;
+        Ok(CrtContext::Factors(FactorsContext::from_json(path, &json)?))
+    }
 
+    /// Returns a primitive `m`-th root of unity.
+    fn mth_root() -> P::Residue {
         // We have prime modulus. For prime modulus q, the group order is phi(q) = q-1.
         // We can use -1 which gets reduced to q-1.
         let group_order = P::Residue::from_i64(-1).retrieve();
 
         // TODO: mention in the paper that we require m-1 to be a multiple of m and dft_size.
-        let mth_root = {
-            let (div, rem) = group_order.div_rem_u64(P::M as u64);
-            assert_eq!(rem, 0);
-            P::GENERATOR.pow_vartime(div)
-        };
+        let (div, rem) = group_order.div_rem_u64(P::M as u64);
+        assert_eq!(rem, 0);
+        P::GENERATOR.pow_vartime(div)
+    }
+
+    /// Generates a `FactorsContext` whose slots are the evaluations at `\zeta^{g^i}`, where
+    /// `\zeta` is the `m`-th root of unity and `g` is the `SLOT_GENERATOR`, like for the `Fourier`
+    /// strategy.
+    async fn gen_linear_factors() -> Self {
+        assert_eq!(
+            P::FACTOR_DEGREE,
+            1,
+            "linear factors require that \\Phi_m(X) splits into linear factors"
+        );
+        let one = P::Residue::from_reduced(<P::Residue as GenericResidue>::Uint::ONE);
+        let (m_inverse, exists) = P::Residue::from_uint(U64::from_u64(P::M as u64)).invert();
+        assert!(bool::from(exists));
+        let mth_root = Self::mth_root();
+        let (mth_root_inverse, exists) = mth_root.invert();
+        assert!(bool::from(exists));
+
+        // The factor of slot `i` is `X - \zeta^{g^i}`, stored as its coefficients `-\zeta^{g^i}`
+        // and `1`.
+        let mut factors = P::Vec::new(2 * P::FACTOR_COUNT);
+        let mut root = mth_root;
+        for factor in factors.iter_mut().step_by(2) {
+            *factor = P::Residue::ZERO - root;
+            root = root.pow_usize_vartime(P::SLOT_GENERATOR);
+        }
+        for factor in factors.iter_mut().skip(1).step_by(2) {
+            *factor = one;
+        }
+
+        // Interpolation: the coefficient of `X^{g^j}` is
+        // `\sum_i (\zeta^{-g^{i+j}} - 1) / m` times slot `i`.
+        let mut basis_coefficients = P::Vec::new(P::FACTOR_COUNT);
+        let mut root = mth_root_inverse;
+        for entry in basis_coefficients.iter_mut() {
+            *entry = (root - one) * m_inverse;
+            root = root.pow_usize_vartime(P::SLOT_GENERATOR);
+        }
+
+        CrtContext::Factors(FactorsContext {
+            factors,
+            basis_coefficients,
+        })
+    }
+
+    async fn gen_negacyclic() -> Self {
+        assert!(
+            P::M.is_power_of_two() && P::CYCLOTOMIC_DEGREE == P::M / 2,
+            "the negacyclic strategy requires that m is a power of two"
+        );
+        let one = P::Residue::from_reduced(<P::Residue as GenericResidue>::Uint::ONE);
+        let (degree_inverse, exists) =
+            P::Residue::from_uint(U64::from_u64(P::CYCLOTOMIC_DEGREE as u64)).invert();
+        assert!(bool::from(exists));
+        let psi = Self::mth_root();
+        let (psi_inverse, exists) = psi.invert();
+        assert!(bool::from(exists));
+        let dft_root = psi * psi;
+
+        let mut psi_powers = P::Vec::new(P::CYCLOTOMIC_DEGREE);
+        let mut psi_inverse_powers = P::Vec::new(P::CYCLOTOMIC_DEGREE);
+        let mut dft_root_powers = P::Vec::new(P::CYCLOTOMIC_DEGREE);
+        let (mut power, mut inverse_power, mut dft_power) = (one, degree_inverse, one);
+        for ((psi_entry, psi_inverse_entry), dft_entry) in psi_powers
+            .iter_mut()
+            .zip(psi_inverse_powers.iter_mut())
+            .zip(dft_root_powers.iter_mut())
+        {
+            *psi_entry = power;
+            *psi_inverse_entry = inverse_power;
+            *dft_entry = dft_power;
+            power *= psi;
+            inverse_power *= psi_inverse;
+            dft_power *= dft_root;
+        }
+
+        CrtContext::Negacyclic(NegacyclicContext {
+            psi_powers,
+            psi_inverse_powers,
+            dft_root_powers,
+        })
+    }
+
+    async fn gen_fourier() -> Self {
+        let (m_inverse, exists) = P::Residue::from_uint(U64::from_u64(P::M as u64)).invert();
+        assert!(bool::from(exists));
+
+        let group_order = P::Residue::from_i64(-1).retrieve();
+        let mth_root = Self::mth_root();
 
         let (mth_root_inverse, exists) = mth_root.invert();
         assert!(bool::from(exists));
@@ -138,10 +474,37 @@ where
             }
         }
 
+        let mut mth_root_powers = P::Vec::new(P::M);
+        {
+            let mut current = P::Residue::from_reduced(<P::Residue as GenericResidue>::Uint::ONE);
+            for entry in mth_root_powers.iter_mut() {
+                *entry = current;
+                current *= mth_root;
+            }
+        }
+
+        // Batch inversion of `\zeta^k - 1`, which needs a single inversion.
+        let mut slide_denominators = P::Vec::new(P::M);
+        {
+            let one = P::Residue::from_reduced(<P::Residue as GenericResidue>::Uint::ONE);
+            for (dst, power) in slide_denominators
+                .iter_mut()
+                .zip(mth_root_powers.iter())
+                .skip(1)
+            {
+                *dst = *power - one;
+            }
+            assert!(P::Residue::invert_batch(
+                &mut slide_denominators.as_mut_slice()[1..]
+            ));
+        }
+
         CrtContext::Fourier(FourierContext {
             m_inverse,
             mth_root,
             mth_root_inverse,
+            mth_root_powers,
+            slide_denominators,
             dft_size,
             dft_size_inverse,
             kernel_from_crt: {
@@ -173,11 +536,13 @@ where
 #[cfg(test)]
 mod tests {
     use crate::bgv::{
-        params::{ToyCipher, ToyPlain},
-        poly::{crt::CrtPoly, power::PowerPoly, CrtContext},
+        params::{phi4096_mod_p54::Phi4096ModP54, ToyCipher, ToyPlain},
+        poly::{crt::CrtPoly, power::PowerPoly, CrtContext, CrtStrategy, PolyParameters},
+        residue::vec::GenericResidueVec,
     };
 
     use super::crt::CrtPolyParameters;
+    use super::{FactorsContext, FactorsError};
 
     #[tokio::test]
     async fn ciphertext_basis_roundtrip_crt() {
@@ -189,6 +554,11 @@ mod tests {
         basis_roundtrip_crt::<ToyPlain>().await;
     }
 
+    #[tokio::test]
+    async fn negacyclic_basis_roundtrip_crt() {
+        basis_roundtrip_crt::<Phi4096ModP54>().await;
+    }
+
     async fn basis_roundtrip_crt<P>()
     where
         P: CrtPolyParameters,
@@ -211,6 +581,11 @@ mod tests {
         basis_roundtrip_power::<ToyPlain>().await;
     }
 
+    #[tokio::test]
+    async fn negacyclic_basis_roundtrip_power() {
+        basis_roundtrip_power::<Phi4096ModP54>().await;
+    }
+
     async fn basis_roundtrip_power<P>()
     where
         P: CrtPolyParameters,
@@ -222,4 +597,88 @@ mod tests {
         let power_roundtrip = PowerPoly::from_crt(&ctx, &crt).await;
         assert_eq!(power, power_roundtrip);
     }
+
+    #[tokio::test]
+    async fn ciphertext_strategies_agree() {
+        let mut rng = rand::thread_rng();
+        let fourier = CrtContext::<ToyCipher>::gen_with(CrtStrategy::Fourier).await;
+        let factors = CrtContext::<ToyCipher>::gen_with(CrtStrategy::LinearFactors).await;
+
+        let power = PowerPoly::random(&mut rng);
+        let crt = CrtPoly::from_power(&fourier, &power).await;
+        assert_eq!(crt, CrtPoly::from_power(&factors, &power).await);
+
+        let other = CrtPoly::random(&mut rng);
+        assert_eq!(
+            PowerPoly::from_crt(&fourier, &other).await,
+            PowerPoly::from_crt(&factors, &other).await
+        );
+
+        let mut product_fourier = crt.clone();
+        product_fourier *= (&other, &fourier);
+        let mut product_factors = crt;
+        product_factors *= (&other, &factors);
+        assert_eq!(product_fourier, product_factors);
+    }
+
+    #[tokio::test]
+    async fn negacyclic_product() {
+        const DEGREE: usize = Phi4096ModP54::CYCLOTOMIC_DEGREE;
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::<Phi4096ModP54>::gen().await;
+        let lhs = PowerPoly::<Phi4096ModP54>::random(&mut rng);
+        let rhs = PowerPoly::<Phi4096ModP54>::random(&mut rng);
+
+        // Schoolbook multiplication modulo `X^DEGREE + 1`.
+        let mut expected = PowerPoly::<Phi4096ModP54>::new();
+        for (i, lhs_coeff) in lhs.coefficients.iter().enumerate() {
+            for (j, rhs_coeff) in rhs.coefficients.iter().enumerate() {
+                let prod = *lhs_coeff * *rhs_coeff;
+                if i + j < DEGREE {
+                    expected.coefficients[i + j] += prod;
+                } else {
+                    expected.coefficients[i + j - DEGREE] -= prod;
+                }
+            }
+        }
+
+        let mut product = CrtPoly::from_power(&ctx, &lhs).await;
+        product *= (&CrtPoly::from_power(&ctx, &rhs).await, &ctx);
+        assert_eq!(PowerPoly::from_crt(&ctx, &product).await, expected);
+    }
+
+    #[test]
+    fn stale_factors_are_rejected() {
+        const PATH: &str = "params/phi337_mod_t112.json";
+        let json = std::fs::read(PATH).unwrap();
+        FactorsContext::<ToyPlain>::from_json(PATH, &json).unwrap();
+
+        let tampered = |modify: &dyn Fn(&mut serde_json::Value)| {
+            let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+            modify(&mut value);
+            FactorsContext::<ToyPlain>::from_json(PATH, &serde_json::to_vec(&value).unwrap())
+        };
+        assert!(matches!(
+            tampered(&|value| value["m"] = 4096.into()),
+            Err(FactorsError::Mismatch { field: "m", .. })
+        ));
+        assert!(matches!(
+            tampered(&|value| value["basis_coefficients"]
+                .as_array_mut()
+                .unwrap()
+                .truncate(8)),
+            Err(FactorsError::Mismatch {
+                field: "basis coefficients",
+                ..
+            })
+        ));
+        assert!(matches!(
+            tampered(&|value| value["factors"][1] = "01000000000000000000000000000000".into()),
+            Err(FactorsError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            tampered(&|value| value["checksum"] = "00".into()),
+            Err(FactorsError::ChecksumMismatch { .. })
+        ));
+    }
 }
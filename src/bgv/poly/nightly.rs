@@ -0,0 +1,17 @@
+use super::{crt::CrtPolyParameters, CrtStrategy};
+
+/// Parameters whose `CRT_STRATEGY` is `CrtStrategy::Fourier`.
+///
+/// With the `nightly` feature, this is implemented for all such parameters and the strategy is
+/// checked by the type system.  Otherwise, it must be implemented with
+/// `__impl_fourier_crt_poly_parameters!`, which checks the strategy at compile time.
+pub trait FourierCrtPolyParameters: CrtPolyParameters
+where
+    Self: CrtPolyParameters<CRT_STRATEGY = { CrtStrategy::Fourier }>,
+{
+}
+
+impl<P> FourierCrtPolyParameters for P where
+    P: CrtPolyParameters<CRT_STRATEGY = { CrtStrategy::Fourier }>
+{
+}
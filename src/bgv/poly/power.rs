@@ -1,4 +1,4 @@
-use std::ops::{AddAssign, MulAssign, SubAssign};
+use std::ops::{AddAssign, MulAssign, Neg, ShlAssign, SubAssign};
 
 use crypto_bigint::{Random, Zero};
 use forward_ref_generic::forward_ref_op_assign;
@@ -13,7 +13,8 @@ use crate::bgv::{
 
 use super::{
     crt::{CrtPoly, CrtPolyParameters},
-    CrtContext, Diagonal, FactorsContext, FourierContext, PolyParameters,
+    small_residue, CrtContext, Diagonal, FactorsContext, FourierContext, NegacyclicContext,
+    PolyParameters,
 };
 
 /// An element of the cyclotomic ring of integers `\mathbb{Z}[X]/\Phi_m(X)` in power basis (i.e. in
@@ -24,9 +25,19 @@ where
     P: PolyParameters,
 {
     /// Vector of coefficients.
+    #[serde(deserialize_with = "super::deserialize_coefficients::<P, _>")]
     pub coefficients: P::Vec,
 }
 
+impl<P> Default for PowerPoly<P>
+where
+    P: PolyParameters,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<P> PowerPoly<P>
 where
     P: PolyParameters,
@@ -78,6 +89,7 @@ where
         match ctx {
             CrtContext::Factors(ctx) => self.clone_from_crt_via_factors(ctx, crt),
             CrtContext::Fourier(ctx) => self.clone_from_crt_via_fourier(ctx, crt).await,
+            CrtContext::Negacyclic(ctx) => self.clone_from_crt_via_negacyclic(ctx, crt).await,
         }
     }
 
@@ -139,9 +151,7 @@ where
         }
 
         let mut padded = P::Vec::new(ctx.dft_size);
-        for (dst, src) in padded.iter_mut().zip(crt.coefficients.iter()) {
-            *dst = *src; // TODO: use vectorized copy
-        }
+        padded.as_mut_slice()[..P::CYCLOTOMIC_DEGREE].copy_from_slice(crt.coefficients.as_slice());
 
         let mut padded_fft = fast_fourier_transform(&ctx.dft_root_powers, false, padded).await;
 
@@ -175,6 +185,22 @@ where
         }
     }
 
+    async fn clone_from_crt_via_negacyclic(&mut self, ctx: &NegacyclicContext<P>, crt: &CrtPoly<P>)
+    where
+        P: CrtPolyParameters,
+    {
+        let twisted =
+            fast_fourier_transform(&ctx.dft_root_powers, true, crt.coefficients.clone()).await;
+        for ((dst, src), factor) in self
+            .coefficients
+            .iter_mut()
+            .zip(twisted.iter())
+            .zip(ctx.psi_inverse_powers.iter())
+        {
+            *dst = *src * *factor;
+        }
+    }
+
     pub async fn from_crt(ctx: &CrtContext<P>, crt: &CrtPoly<P>) -> Self
     where
         P: CrtPolyParameters,
@@ -310,6 +336,66 @@ forward_ref_op_assign!(
     where P: PolyParameters
 );
 
+impl<P> Neg for PowerPoly<P>
+where
+    P: PolyParameters,
+{
+    type Output = Self;
+
+    fn neg(mut self) -> Self {
+        for coeff in self.coefficients.iter_mut() {
+            *coeff = P::Residue::ZERO - *coeff;
+        }
+        self
+    }
+}
+
+impl<P> Neg for &PowerPoly<P>
+where
+    P: PolyParameters,
+{
+    type Output = PowerPoly<P>;
+
+    fn neg(self) -> PowerPoly<P> {
+        -self.clone()
+    }
+}
+
+/// Multiplication by a small integer, e.g. `3 * poly`, without constructing a `Diagonal`.
+impl<P> MulAssign<u64> for PowerPoly<P>
+where
+    P: PolyParameters,
+{
+    fn mul_assign(&mut self, rhs: u64) {
+        match rhs {
+            0 => {
+                for coeff in self.coefficients.iter_mut() {
+                    *coeff = P::Residue::ZERO;
+                }
+            }
+            1 => {}
+            2 => {
+                for coeff in self.coefficients.iter_mut() {
+                    *coeff = *coeff + *coeff;
+                }
+            }
+            _ => *self *= Diagonal(small_residue::<P::Residue>(rhs)),
+        }
+    }
+}
+
+/// Multiplication by `2^bits`.
+impl<P> ShlAssign<usize> for PowerPoly<P>
+where
+    P: PolyParameters,
+{
+    fn shl_assign(&mut self, bits: usize) {
+        if bits != 0 {
+            *self *= Diagonal(small_residue::<P::Residue>(2).pow_usize_vartime(bits));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rand::Rng;
@@ -318,6 +404,7 @@ mod tests {
     use crate::bgv::{
         params::{ToyCipher, ToyPlain},
         poly::{power::PowerPoly, PolyParameters},
+        residue::vec::GenericResidueVec,
     };
 
     #[test]
@@ -343,6 +430,21 @@ mod tests {
         assert_eq!(power, power_roundtrip);
     }
 
+    #[test]
+    fn deserialize_rejects_wrong_degree() {
+        for len in [
+            0,
+            ToyCipher::CYCLOTOMIC_DEGREE - 1,
+            ToyCipher::CYCLOTOMIC_DEGREE + 1,
+        ] {
+            let power = PowerPoly::<ToyCipher> {
+                coefficients: GenericResidueVec::new(len),
+            };
+            let bytes = bincode::serialize(&power).unwrap();
+            assert!(bincode::deserialize::<PowerPoly<ToyCipher>>(&bytes).is_err());
+        }
+    }
+
     #[test]
     fn ciphertext_add_assign_slided() {
         add_assign_slided::<ToyCipher>();
@@ -373,4 +475,46 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn ciphertext_scalar_ops() {
+        scalar_ops::<ToyCipher>();
+    }
+
+    #[test]
+    fn plaintext_scalar_ops() {
+        scalar_ops::<ToyPlain>();
+    }
+
+    fn scalar_ops<P>()
+    where
+        P: PolyParameters,
+    {
+        let mut rng = rand::thread_rng();
+        let poly = PowerPoly::<P>::random(&mut rng);
+
+        let mut sum = -&poly;
+        sum += &poly;
+        assert_eq!(sum, PowerPoly::new());
+
+        for factor in 0..5u64 {
+            let mut expected = PowerPoly::new();
+            for _ in 0..factor {
+                expected += &poly;
+            }
+            let mut actual = poly.clone();
+            actual *= factor;
+            assert_eq!(actual, expected);
+        }
+
+        for bits in [0, 1, 7, 70] {
+            let mut expected = poly.clone();
+            for _ in 0..bits {
+                expected *= 2;
+            }
+            let mut actual = poly.clone();
+            actual <<= bits;
+            assert_eq!(actual, expected);
+        }
+    }
 }
@@ -1,5 +1,6 @@
 pub mod native;
 pub mod vec;
+pub mod wire;
 
 use std::{
     cmp::min,
@@ -39,6 +40,14 @@ pub trait GenericResidue:
 
     type Uint: ExtendableUint;
 
+    /// Unreduced sum of residues, see `add_lazy()`.
+    type LazySum: Copy + Default + Send;
+
+    /// Adds `summand` to `sum` without reducing it.  The sum of any number of residues is reduced
+    /// only once by `reduce_lazy()`.
+    fn add_lazy(sum: &mut Self::LazySum, summand: Self);
+    fn reduce_lazy(sum: Self::LazySum) -> Self;
+
     fn retrieve(&self) -> Self::Uint;
     fn from_uint<SourceUint: GenericUint>(source: SourceUint) -> Self;
     fn from_i64(source: i64) -> Self;
@@ -46,10 +55,55 @@ pub trait GenericResidue:
     fn from_reduced<SourceUint: GenericUint>(source: SourceUint) -> Self;
     fn invert(&self) -> (Self, CtChoice);
 
+    /// Converts the representative in `[0, q)` of `source`.  If it does not fit, the upper limbs
+    /// are discarded (native residues) or it is reduced (prime moduli), see
+    /// `try_from_unsigned()`.
     fn from_unsigned<SourceRes: GenericResidue>(source: SourceRes) -> Self {
         Self::from_uint(source.retrieve())
     }
 
+    /// Like `from_unsigned()`, but returns `None` if the representative of `source` does not fit
+    /// into `Self`, i.e., if the conversion would lose information.
+    fn try_from_unsigned<SourceRes: GenericResidue>(source: SourceRes) -> Option<Self> {
+        let converted = Self::from_unsigned(source);
+        uint_eq(&converted.retrieve(), &source.retrieve()).then_some(converted)
+    }
+
+    /// `self / divisor`, or `None` if `divisor` is not invertible.
+    fn checked_div(self, divisor: Self) -> Option<Self> {
+        let (inverse, exists) = divisor.invert();
+        bool::from(exists).then(|| self * inverse)
+    }
+
+    /// Replaces each of `values` by its inverse with a single `invert()` (Montgomery's trick), at
+    /// the price of three multiplications per value.  Returns `false` and leaves `values`
+    /// unchanged if their product is not invertible, i.e., if one of them is not.
+    fn invert_batch(values: &mut [Self]) -> bool {
+        let mut prefixes = Vec::with_capacity(values.len());
+        let mut product = Self::from_reduced(Self::Uint::ONE);
+        for value in values.iter() {
+            prefixes.push(product);
+            product *= *value;
+        }
+        let (mut inverse, exists) = product.invert();
+        if !bool::from(exists) {
+            return false;
+        }
+        // Now `inverse` is the inverse of the product of the values up to the current one.
+        for (value, prefix) in values.iter_mut().zip(prefixes).rev() {
+            let original = *value;
+            *value = inverse * prefix;
+            inverse *= original;
+        }
+        true
+    }
+
+    /// Computes `self += a * b`.  Implementations may fuse the multiplication and the addition.
+    #[inline(always)]
+    fn mul_add_assign(&mut self, a: Self, b: Self) {
+        *self += a * b;
+    }
+
     /// This method is constant-time only with respect to `self`.  Depending on
     /// `exp`, timing can and will vary.
     fn pow_usize_vartime(mut self, mut exp: usize) -> Self {
@@ -77,6 +131,33 @@ pub trait GenericResidue:
         }
         result
     }
+
+    /// The representative of `self` in `(-q/2, q/2]` as a two's complement integer with one more
+    /// limb than `Self::Uint`, e.g., for `from_signed_int()` into a residue of another modulus.
+    ///
+    /// For `h = floor(q/2)`, the representative is `((self + h) mod q) - h`, which is computed
+    /// without branching on `self`.
+    fn centered(&self) -> <Self::Uint as ExtendableUint>::Extended {
+        type Extended<R> = <<R as GenericResidue>::Uint as ExtendableUint>::Extended;
+        let nlimbs = Self::Uint::NLIMBS;
+        let extend = |uint: Self::Uint| {
+            let mut extended = Extended::<Self>::ZERO;
+            extended.limbs_mut()[..nlimbs].clone_from_slice(uint.limbs());
+            extended
+        };
+
+        let half = Self::from_i64(-1).retrieve().shr_vartime(1);
+        let shifted = (*self + Self::from_reduced(half)).retrieve();
+        extend(shifted).wrapping_sub(&extend(half))
+    }
+}
+
+/// Whether `a` and `b` are the same integer, regardless of their numbers of limbs.
+fn uint_eq<A: GenericUint, B: GenericUint>(a: &A, b: &B) -> bool {
+    let (a, b) = (a.limbs(), b.limbs());
+    let n = min(a.len(), b.len());
+    a[..n].iter().zip(&b[..n]).all(|(a, b)| a.0 == b.0)
+        && a[n..].iter().chain(&b[n..]).all(|limb| limb.0 == 0)
 }
 
 impl<MOD, const NLIMBS: usize> GenericResidue for Residue<MOD, NLIMBS>
@@ -88,6 +169,24 @@ where
 
     type Uint = Uint<NLIMBS>;
 
+    /// Sum of the Montgomery forms and the number of carries out of the last limb.
+    type LazySum = (Uint<NLIMBS>, Word);
+
+    #[inline(always)]
+    fn add_lazy(sum: &mut Self::LazySum, summand: Self) {
+        let (lo, carry) = sum.0.adc(summand.as_montgomery(), Limb::ZERO);
+        sum.0 = lo;
+        sum.1 += carry.0;
+    }
+
+    #[inline(always)]
+    fn reduce_lazy(sum: Self::LazySum) -> Self {
+        // With `R = 2^(64 NLIMBS)`, the Montgomery form `lo + carries R` represents
+        // `lo R^{-1} + carries`.
+        let r_inverse = Self::from_montgomery(Uint::ONE);
+        Self::new(&sum.0) * r_inverse + Self::new(&Uint::from_word(sum.1))
+    }
+
     #[inline(always)]
     fn retrieve(&self) -> Self::Uint {
         self.retrieve()
@@ -167,7 +266,7 @@ where
 
     #[inline(always)]
     fn invert(&self) -> (Self, CtChoice) {
-        Residue::invert(&self)
+        Residue::invert(self)
     }
 }
 
@@ -177,12 +276,44 @@ mod tests {
     use rand::Rng;
 
     use crate::bgv::{
+        generic_uint::GenericUint,
         params::{ToyCipher, ToyPlain},
         poly::PolyParameters,
     };
 
     use super::GenericResidue;
 
+    #[test]
+    fn ciphertext_residue_centered() {
+        residue_centered::<<ToyCipher as PolyParameters>::Residue>();
+    }
+
+    #[test]
+    fn plaintext_residue_centered() {
+        residue_centered::<<ToyPlain as PolyParameters>::Residue>();
+    }
+
+    fn residue_centered<Residue>()
+    where
+        Residue: GenericResidue,
+    {
+        let mut rng = rand::thread_rng();
+        for value in [0, 1, -1, rng.gen::<i64>(), rng.gen::<i64>()] {
+            let centered = Residue::from_i64(value).centered();
+            assert_eq!(centered, GenericUint::from_i64(value));
+        }
+
+        // The largest representative `q - 1` is `-1`, and `h = floor((q - 1)/2)` is positive and
+        // `-h` negative, for odd (ciphertext) and even (plaintext) moduli.
+        let max = Residue::from_i64(-1).retrieve();
+        let half = max.shr_vartime(1);
+        let centered = Residue::from_reduced(half).centered();
+        assert_eq!(centered.limbs()[..max.limbs().len()], half.limbs()[..]);
+        assert_eq!(centered.limbs().last().unwrap().0, 0);
+        let centered = (Residue::ZERO - Residue::from_reduced(half)).centered();
+        assert_eq!(centered.limbs().last().unwrap().0 >> 63, 1);
+    }
+
     #[test]
     fn ciphertext_residue_add_assign() {
         residue_add_assign::<<ToyCipher as PolyParameters>::Residue>();
@@ -276,4 +407,59 @@ mod tests {
         let result = Residue::from_uint(U64::from_u64(lhs_num - rhs_num));
         assert_eq!(lhs, result);
     }
+
+    #[test]
+    fn ciphertext_residue_lazy_sum() {
+        residue_lazy_sum::<<ToyCipher as PolyParameters>::Residue>();
+    }
+
+    #[test]
+    fn plaintext_residue_lazy_sum() {
+        residue_lazy_sum::<<ToyPlain as PolyParameters>::Residue>();
+    }
+
+    fn residue_lazy_sum<Residue>()
+    where
+        Residue: GenericResidue,
+    {
+        let mut rng = rand::thread_rng();
+        let mut sum = Residue::LazySum::default();
+        let mut expected = Residue::ZERO;
+        for _ in 0..1000 {
+            let summand = Residue::random(&mut rng);
+            Residue::add_lazy(&mut sum, summand);
+            expected += summand;
+        }
+        assert_eq!(Residue::reduce_lazy(sum), expected);
+    }
+
+    #[test]
+    fn ciphertext_residue_invert_batch() {
+        residue_invert_batch::<<ToyCipher as PolyParameters>::Residue>();
+    }
+
+    #[test]
+    fn plaintext_residue_invert_batch() {
+        residue_invert_batch::<<ToyPlain as PolyParameters>::Residue>();
+    }
+
+    fn residue_invert_batch<Residue>()
+    where
+        Residue: GenericResidue,
+    {
+        let mut rng = rand::thread_rng();
+        let one = Residue::from_uint(U64::ONE);
+        // Odd values are invertible modulo both the prime and the power of two.
+        let values: Vec<_> = (0..10)
+            .map(|_| Residue::from_uint(U64::from_u64(rng.gen::<u64>() | 1)))
+            .collect();
+        let mut inverses = values.clone();
+        assert!(Residue::invert_batch(&mut inverses));
+        for (value, inverse) in values.iter().zip(&inverses) {
+            assert_eq!(*value * *inverse, one);
+            assert_eq!(value.invert().0, *inverse);
+            assert_eq!(one.checked_div(*value), Some(*inverse));
+        }
+        assert!(Residue::invert_batch(&mut []));
+    }
 }
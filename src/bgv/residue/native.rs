@@ -6,23 +6,53 @@ use std::{
 use crypto_bigint::{
     rand_core::CryptoRngCore,
     subtle::{Choice, ConstantTimeEq},
-    CtChoice, Encoding, Limb, Random, Uint, Word, Zero,
+    CtChoice, Limb, Random, Uint, Word, Zero,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::bgv::generic_uint::{ExtendableUint, GenericUint};
 
-use super::GenericResidue;
+use super::{wire, GenericResidue};
 
 pub trait GenericNativeResidue: GenericResidue {
     fn shr_vartime(&self, shift: usize) -> Self;
     fn shl_vartime(&self, shift: usize) -> Self;
+
+    /// Returns `W::from_unsigned(*self) * W::from_unsigned(*rhs)` without widening the operands
+    /// first.  Since the operands are reduced, their upper limbs are zero, so only the limb
+    /// products below `W::BITS` are computed.
+    #[inline(always)]
+    fn mul_widening<R, W>(&self, rhs: &R) -> W
+    where
+        R: GenericNativeResidue,
+        W: GenericNativeResidue,
+    {
+        let lhs_uint = self.retrieve();
+        let rhs_uint = rhs.retrieve();
+        let lhs_limbs = &lhs_uint.limbs()[..Self::BITS.div_ceil(Limb::BITS)];
+        let rhs_limbs = &rhs_uint.limbs()[..R::BITS.div_ceil(Limb::BITS)];
+        let mut product = W::Uint::ZERO;
+        let nlimbs = product.limbs().len();
+        for (i, a) in lhs_limbs.iter().enumerate().take(nlimbs) {
+            let mut carry = Limb::ZERO;
+            for (j, b) in rhs_limbs.iter().enumerate().take(nlimbs - i) {
+                let (lo, hi) = product.limbs()[i + j].mac(*a, *b, carry);
+                product.limbs_mut()[i + j] = lo;
+                carry = hi;
+            }
+            // This limb has not been written by the previous rows.
+            if i + rhs_limbs.len() < nlimbs {
+                product.limbs_mut()[i + rhs_limbs.len()] = carry;
+            }
+        }
+        let result = W::from_uint(product);
+        debug_assert!(result == W::from_unsigned(*self) * W::from_unsigned(*rhs));
+        result
+    }
 }
 
-// TODO: Serialize and Deserialize must use reduced form for security (and shortness).
-#[derive(Clone, Copy, Debug, Eq, Serialize, Deserialize)]
-#[serde(bound(deserialize = "Uint<NLIMBS>: Encoding"))]
-#[serde(bound(serialize = "Uint<NLIMBS>: Encoding"))]
+/// Binary formats use the reduced encoding of `wire`, and human-readable formats the one of `Uint`.
+#[derive(Clone, Copy, Debug, Eq)]
 pub struct NativeResidue<const BITS: usize, const NLIMBS: usize>(Uint<NLIMBS>)
 where
     Uint<NLIMBS>: ExtendableUint;
@@ -68,6 +98,38 @@ where
     }
 }
 
+impl<const BITS: usize, const NLIMBS: usize> Serialize for NativeResidue<BITS, NLIMBS>
+where
+    Uint<NLIMBS>: ExtendableUint,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            self.0.serialize(serializer)
+        } else {
+            wire::serialize(self, serializer)
+        }
+    }
+}
+
+impl<'de, const BITS: usize, const NLIMBS: usize> Deserialize<'de> for NativeResidue<BITS, NLIMBS>
+where
+    Uint<NLIMBS>: ExtendableUint,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            Uint::deserialize(deserializer).map(Self)
+        } else {
+            wire::deserialize(deserializer)
+        }
+    }
+}
+
 impl<const BITS: usize, const NLIMBS: usize> Random for NativeResidue<BITS, NLIMBS>
 where
     Uint<NLIMBS>: ExtendableUint,
@@ -145,11 +207,24 @@ where
 
     type Uint = Uint<NLIMBS>;
 
+    /// The representation is reduced lazily anyway.
+    type LazySum = Uint<NLIMBS>;
+
+    #[inline(always)]
+    fn add_lazy(sum: &mut Self::LazySum, summand: Self) {
+        *sum = sum.wrapping_add(&summand.0);
+    }
+
+    #[inline(always)]
+    fn reduce_lazy(sum: Self::LazySum) -> Self {
+        Self(sum)
+    }
+
     #[inline(always)]
     fn retrieve(&self) -> Self::Uint {
         let mut repr = self.0;
-        let cutoff = Uint::NLIMBS * 64 - BITS;
-        debug_assert!(cutoff < 64);
+        let cutoff = Uint::NLIMBS * Limb::BITS - BITS;
+        debug_assert!(cutoff < Limb::BITS);
         repr.limbs_mut()[Uint::NLIMBS - 1].0 &= Word::MAX >> cutoff;
         repr
     }
@@ -192,4 +267,110 @@ where
         // TODO: to implement this correctly, we need to return False if `self` is even.
         (Self(self.0.inv_mod2k_vartime(BITS)), CtChoice::TRUE)
     }
+
+    #[inline(always)]
+    fn mul_add_assign(&mut self, a: Self, b: Self) {
+        // Schoolbook multiplication that accumulates into `self` directly.  Carries beyond the
+        // last limb are dropped, which is fine since we compute modulo 2^(64 * NLIMBS).
+        let acc = self.0.limbs_mut();
+        for (i, a_limb) in a.0.limbs().iter().enumerate() {
+            let mut carry = Limb::ZERO;
+            for (j, b_limb) in b.0.limbs().iter().enumerate().take(NLIMBS - i) {
+                let (lo, hi) = acc[i + j].mac(*a_limb, *b_limb, carry);
+                acc[i + j] = lo;
+                carry = hi;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bgv::residue::GenericResidue;
+
+    use super::{GenericNativeResidue, NativeResidue};
+
+    #[test]
+    fn mul_widening_k32_s32() {
+        mul_widening::<NativeResidue<64, 1>, NativeResidue<32, 1>, NativeResidue<96, 2>>();
+    }
+
+    #[test]
+    fn mul_widening_k128_s64() {
+        mul_widening::<NativeResidue<192, 3>, NativeResidue<128, 2>, NativeResidue<256, 4>>();
+    }
+
+    #[test]
+    fn mul_add_assign_k32_s32() {
+        mul_add_assign::<NativeResidue<96, 2>>();
+    }
+
+    #[test]
+    fn mul_add_assign_k128_s64() {
+        mul_add_assign::<NativeResidue<256, 4>>();
+    }
+
+    #[test]
+    fn try_from_unsigned() {
+        type K = NativeResidue<32, 1>;
+        type KS = NativeResidue<64, 1>;
+        #[allow(clippy::upper_case_acronyms)]
+        type KSS = NativeResidue<96, 2>;
+
+        let small = KSS::from_i64(0xffff_ffff);
+        assert_eq!(K::try_from_unsigned(small), Some(K::from_i64(0xffff_ffff)));
+        assert_eq!(K::try_from_unsigned(small + KSS::from_i64(1)), None);
+        // The upper limb is discarded, too.
+        let large = KSS::from_i64(1).shl_vartime(64);
+        assert_eq!(KS::try_from_unsigned(large), None);
+        // Widening is lossless.
+        let max = KS::from_i64(-1);
+        assert_eq!(KSS::try_from_unsigned(max), Some(KSS::from_unsigned(max)));
+    }
+
+    #[test]
+    fn serialization_is_little_endian() {
+        // The wire format must not depend on the byte order of the host, and it has 12 bytes
+        // instead of the 16 bytes of two limbs, see `wire`.
+        let x = NativeResidue::<96, 2>::from_i64(0x0102_0304_0506_0708);
+        let bytes = bincode::serialize(&x).unwrap();
+        let mut expected = vec![8, 7, 6, 5, 4, 3, 2, 1];
+        expected.resize(12, 0);
+        assert_eq!(bytes, expected);
+        assert_eq!(
+            bincode::deserialize::<NativeResidue<96, 2>>(&bytes).unwrap(),
+            x
+        );
+    }
+
+    fn mul_add_assign<R>()
+    where
+        R: GenericNativeResidue,
+    {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let mut acc = R::random(&mut rng);
+            let a = R::random(&mut rng);
+            let b = R::random(&mut rng);
+            let expected = acc + a * b;
+            acc.mul_add_assign(a, b);
+            assert_eq!(acc, expected);
+        }
+    }
+
+    fn mul_widening<A, B, W>()
+    where
+        A: GenericNativeResidue,
+        B: GenericNativeResidue,
+        W: GenericNativeResidue,
+    {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let a = A::random(&mut rng);
+            let b = B::random(&mut rng);
+            let expected = W::from_unsigned(a) * W::from_unsigned(b);
+            assert_eq!(a.mul_widening::<_, W>(&b), expected);
+            assert_eq!(b.mul_widening::<_, W>(&a), expected);
+        }
+    }
 }
@@ -29,17 +29,32 @@ pub trait GenericResidueVec:
 
     fn len(&self) -> usize;
 
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     fn iter(&self) -> impl ExactSizeIterator + DoubleEndedIterator<Item = &Self::Residue>;
 
     fn iter_mut(
         &mut self,
     ) -> impl ExactSizeIterator + DoubleEndedIterator<Item = &mut Self::Residue>;
+
+    fn as_slice(&self) -> &[Self::Residue];
+
+    fn as_mut_slice(&mut self) -> &mut [Self::Residue];
+
+    /// Splits into two disjoint mutable slices at `mid`, e.g., for processing them in parallel.
+    fn split_at_mut(&mut self, mid: usize) -> (&mut [Self::Residue], &mut [Self::Residue]) {
+        self.as_mut_slice().split_at_mut(mid)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(bound(deserialize = ""))]
 #[serde(bound(serialize = ""))]
-pub struct ResidueVec<MOD, const NLIMBS: usize>(Vec<Residue<MOD, NLIMBS>>)
+pub struct ResidueVec<MOD, const NLIMBS: usize>(
+    #[serde(with = "super::wire::vec")] Vec<Residue<MOD, NLIMBS>>,
+)
 where
     MOD: ResidueParams<NLIMBS>,
     Uint<NLIMBS>: ExtendableUint;
@@ -90,6 +105,14 @@ where
     ) -> impl ExactSizeIterator + DoubleEndedIterator<Item = &mut Self::Residue> {
         self.0.iter_mut()
     }
+
+    fn as_slice(&self) -> &[Self::Residue] {
+        &self.0
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Self::Residue] {
+        &mut self.0
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -144,4 +167,12 @@ where
     ) -> impl ExactSizeIterator + DoubleEndedIterator<Item = &mut Self::Residue> {
         self.0.iter_mut()
     }
+
+    fn as_slice(&self) -> &[Self::Residue] {
+        &self.0
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Self::Residue] {
+        &mut self.0
+    }
 }
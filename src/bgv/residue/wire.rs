@@ -0,0 +1,281 @@
+//! Encoding of residues on the wire that does not depend on the limb size of `crypto-bigint`.
+//!
+//! A residue is encoded as the `wire_len()` little-endian bytes of its representative in `[0, q)`.
+//! In contrast, the `serde` encoding of `Uint<NLIMBS>` has `NLIMBS * Limb::BYTES` bytes, and the
+//! one of `Residue` is the Montgomery form, which both depend on the limb size of the platform.
+//! Decoding rejects representatives that are not reduced, so each residue has a single encoding.
+//!
+//! `NativeResidue` and the residue vectors use this encoding for binary formats like `bincode`.
+//! Human-readable formats keep the encoding of `crypto-bigint`, which the tables of factors (see
+//! `FactorsContext::from_json()`) and their checksums are based on.  Other containers of residues
+//! of a prime modulus can wrap them in `Wire`, or use `#[serde(with = "wire::vec")]`.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crypto_bigint::{Limb, Word, Zero};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{uint_eq, GenericResidue};
+use crate::bgv::generic_uint::GenericUint;
+
+/// Number of bytes of the encoding of a residue of `R`.
+pub const fn wire_len<R>() -> usize
+where
+    R: GenericResidue,
+{
+    R::BITS.div_ceil(8)
+}
+
+/// The `wire_len()` little-endian bytes of the representative of `residue`.
+pub fn to_le_bytes<R>(residue: &R) -> impl Iterator<Item = u8>
+where
+    R: GenericResidue,
+{
+    let uint = residue.retrieve();
+    (0..wire_len::<R>())
+        .map(move |i| (uint.limbs()[i / Limb::BYTES].0 >> (8 * (i % Limb::BYTES))) as u8)
+}
+
+/// Decodes `bytes`, or returns `None` if it has the wrong length or the representative is not
+/// reduced.
+pub fn from_le_bytes<R>(bytes: &[u8]) -> Option<R>
+where
+    R: GenericResidue,
+{
+    if bytes.len() != wire_len::<R>() {
+        return None;
+    }
+    let mut uint = R::Uint::ZERO;
+    for (i, byte) in bytes.iter().enumerate() {
+        set_byte(&mut uint, i, *byte);
+    }
+    from_representative(uint)
+}
+
+fn set_byte<U>(uint: &mut U, index: usize, byte: u8)
+where
+    U: GenericUint,
+{
+    uint.limbs_mut()[index / Limb::BYTES].0 |= (byte as Word) << (8 * (index % Limb::BYTES));
+}
+
+fn from_representative<R>(uint: R::Uint) -> Option<R>
+where
+    R: GenericResidue,
+{
+    let residue = R::from_uint(uint);
+    uint_eq(&residue.retrieve(), &uint).then_some(residue)
+}
+
+/// Serializes `residue` as a tuple of `wire_len()` bytes, regardless of the format.
+pub fn serialize<R, S>(residue: &R, serializer: S) -> Result<S::Ok, S::Error>
+where
+    R: GenericResidue,
+    S: Serializer,
+{
+    let mut tuple = serializer.serialize_tuple(wire_len::<R>())?;
+    for byte in to_le_bytes(residue) {
+        tuple.serialize_element(&byte)?;
+    }
+    tuple.end()
+}
+
+/// Inverse of `serialize()`.
+pub fn deserialize<'de, R, D>(deserializer: D) -> Result<R, D::Error>
+where
+    R: GenericResidue,
+    D: Deserializer<'de>,
+{
+    struct WireVisitor<R>(PhantomData<R>);
+
+    impl<'de, R> Visitor<'de> for WireVisitor<R>
+    where
+        R: GenericResidue,
+    {
+        type Value = R;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "{} little-endian bytes of a reduced residue",
+                wire_len::<R>()
+            )
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<R, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut uint = R::Uint::ZERO;
+            for i in 0..wire_len::<R>() {
+                let byte = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                set_byte(&mut uint, i, byte);
+            }
+            from_representative(uint).ok_or_else(|| de::Error::custom("the residue is not reduced"))
+        }
+    }
+
+    deserializer.deserialize_tuple(wire_len::<R>(), WireVisitor(PhantomData))
+}
+
+/// A residue that is serialized with `serialize()` in binary formats, and with its own encoding in
+/// human-readable formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wire<R>(pub R);
+
+impl<R> Serialize for Wire<R>
+where
+    R: GenericResidue,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            self.0.serialize(serializer)
+        } else {
+            serialize(&self.0, serializer)
+        }
+    }
+}
+
+impl<'de, R> Deserialize<'de> for Wire<R>
+where
+    R: GenericResidue,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            R::deserialize(deserializer).map(Self)
+        } else {
+            deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+/// Serializes a `Vec` of residues like a `Vec` of `Wire`s, for `#[serde(with = "wire::vec")]`.
+pub mod vec {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Wire;
+    use crate::bgv::residue::GenericResidue;
+
+    pub fn serialize<R, S>(values: &[R], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: GenericResidue,
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(|value| Wire(*value)))
+    }
+
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<Vec<R>, D::Error>
+    where
+        R: GenericResidue,
+        D: Deserializer<'de>,
+    {
+        let values = Vec::<Wire<R>>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|Wire(value)| value).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{Limb, Random};
+
+    use crate::bgv::generic_uint::GenericUint;
+    use crate::bgv::params::phi337_mod_p311::Phi337ModP311;
+    use crate::bgv::poly::PolyParameters;
+    use crate::bgv::residue::native::NativeResidue;
+    use crate::bgv::residue::vec::GenericResidueVec;
+    use crate::bgv::residue::GenericResidue;
+
+    use super::{from_le_bytes, to_le_bytes, wire_len};
+
+    type K = NativeResidue<32, 1>;
+    #[allow(clippy::upper_case_acronyms)]
+    type KSS = NativeResidue<96, 2>;
+    type Q = <Phi337ModP311 as PolyParameters>::Residue;
+    type QVec = <Phi337ModP311 as PolyParameters>::Vec;
+
+    /// Encodes the representative of `residue` limb by limb, like a platform with 32-bit limbs.
+    fn le_bytes_of_u32_limbs<R>(residue: &R) -> Vec<u8>
+    where
+        R: GenericResidue,
+    {
+        let mut bytes: Vec<u8> = residue
+            .retrieve()
+            .limbs()
+            .iter()
+            .flat_map(|limb| {
+                let word = limb.0 as u128;
+                (0..Limb::BYTES / 4).map(move |i| (word >> (32 * i)) as u32)
+            })
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        bytes.truncate(wire_len::<R>());
+        bytes
+    }
+
+    #[test]
+    fn encoding_is_independent_of_limbs() {
+        let mut rng = rand::thread_rng();
+        let kss = KSS::random(&mut rng);
+        let q = Q::random(&mut rng);
+        assert_eq!(wire_len::<K>(), 4);
+        assert_eq!(wire_len::<KSS>(), 12);
+        assert_eq!(
+            to_le_bytes(&kss).collect::<Vec<_>>(),
+            le_bytes_of_u32_limbs(&kss)
+        );
+        assert_eq!(
+            to_le_bytes(&q).collect::<Vec<_>>(),
+            le_bytes_of_u32_limbs(&q)
+        );
+
+        // A 32-bit party encodes a residue of `K` like this.
+        let k = K::from_i64(0x0102_0304);
+        assert_eq!(bincode::serialize(&k).unwrap(), [4, 3, 2, 1]);
+        assert_eq!(bincode::deserialize::<K>(&[4, 3, 2, 1]).unwrap(), k);
+        assert_eq!(
+            bincode::serialize(&kss).unwrap(),
+            to_le_bytes(&kss).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn roundtrip_and_canonical() {
+        let mut rng = rand::thread_rng();
+        let kss = KSS::random(&mut rng);
+        assert_eq!(
+            bincode::deserialize::<KSS>(&bincode::serialize(&kss).unwrap()).unwrap(),
+            kss
+        );
+
+        let mut vec = QVec::new(3);
+        for coeff in vec.iter_mut() {
+            *coeff = Q::random(&mut rng);
+        }
+        let bytes = bincode::serialize(&vec).unwrap();
+        assert_eq!(bytes.len(), 8 + 3 * wire_len::<Q>());
+        assert_eq!(bincode::deserialize::<QVec>(&bytes).unwrap(), vec);
+
+        // Bits above `BITS`, representatives of at least the modulus and wrong lengths are
+        // rejected.
+        type Narrow = NativeResidue<60, 1>;
+        let mut bytes = vec![0xff; wire_len::<Narrow>()];
+        assert!(from_le_bytes::<Narrow>(&bytes).is_none());
+        assert!(bincode::deserialize::<Narrow>(&bytes).is_err());
+        bytes[wire_len::<Narrow>() - 1] = 0x0f;
+        assert!(from_le_bytes::<Narrow>(&bytes).is_some());
+        bytes.push(0);
+        assert!(from_le_bytes::<Narrow>(&bytes).is_none());
+        assert!(from_le_bytes::<Q>(&vec![0xff; wire_len::<Q>()]).is_none());
+    }
+}
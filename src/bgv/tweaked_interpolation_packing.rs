@@ -2,6 +2,7 @@ use crypto_bigint::{Zero, U64};
 use rand::{CryptoRng, RngCore};
 
 use crate::bgv::{poly::PolyParameters, residue::GenericResidue};
+use crate::sampling;
 
 use super::{
     poly::crt::{CrtPoly, CrtPolyParameters},
@@ -25,18 +26,16 @@ pub const fn packing_capacity_per_slot<P>() -> usize
 where
     P: CrtPolyParameters,
 {
-    (P::FACTOR_DEGREE + 1) / 2
+    P::FACTOR_DEGREE.div_ceil(2)
 }
 
-pub fn get_random_unpacked<P, T>(mut rng: impl CryptoRng + RngCore) -> Vec<T>
+pub fn get_random_unpacked<P, T>(rng: impl CryptoRng + RngCore) -> Vec<T>
 where
     P: TIPParameters,
     P::Residue: GenericNativeResidue,
     T: GenericNativeResidue,
 {
-    (0..packing_capacity::<P>())
-        .map(|_| T::random(&mut rng))
-        .collect()
+    sampling::random_vec(rng, packing_capacity::<P>())
 }
 
 pub fn pack<P>(unpacked: &[impl GenericNativeResidue]) -> CrtPoly<P>
@@ -46,9 +45,58 @@ where
 {
     assert!(unpacked.len() <= packing_capacity::<P>());
 
+    let lagrange_polys = lagrange_polys::<P>();
+
+    let mut result = CrtPoly::<P>::new();
+
+    // The slots are disjoint, so they could be processed in parallel.
+    for (slot, chunk) in result
+        .coefficients
+        .as_mut_slice()
+        .chunks_exact_mut(P::FACTOR_DEGREE)
+        .zip(unpacked.chunks(packing_capacity_per_slot::<P>()))
+    {
+        for (entry, lp) in chunk.iter().zip(lagrange_polys.iter()) {
+            // Values wider than the plaintext modulus, like the masks of `pack_mask()`, are reduced
+            // modulo it.
+            let extended: <P as PolyParameters>::Residue = GenericResidue::from_unsigned(*entry);
+            for (dst, lp_coeff) in slot.iter_mut().zip(lp.as_slice()) {
+                dst.mul_add_assign(extended, *lp_coeff);
+            }
+        }
+    }
+
+    // // Alternative implementation, TODO: check which one is more cache-friendly
+    // for (chunk, lp) in unpacked
+    //     .chunks(P::FACTOR_COUNT)
+    //     .zip(lagrange_polys.iter())
+    // {
+    //     // Here we treat chunk as a CrtPoly where each slot is constant,
+    //     // and we compute result += chunk * lp.
+    //     for (factor_index, entry) in chunk.iter().enumerate() {
+    //         let slot_begin = factor_index * P::FACTOR_DEGREE;
+    //         for i in 0..P::FACTOR_DEGREE {
+    //             let extended: <P as PolyParameters>::Residue =
+    //                 GenericResidue::from_unsigned(*entry);
+    //             result.coefficients[slot_begin + i] += extended * lp[i];
+    //         }
+    //     }
+    // }
+
+    result
+}
+
+/// The polynomials that are `2^delta` at one point of a slot and zero at the other points.
+fn lagrange_polys<P>() -> Vec<<P as PolyParameters>::Vec>
+where
+    P: TIPParameters,
+    P::Residue: GenericNativeResidue,
+{
     // TODO: Precompute
     let mut lagrange_polys =
         vec![<P as PolyParameters>::Vec::new(P::FACTOR_DEGREE); packing_capacity_per_slot::<P>()];
+    let mut denoms = Vec::with_capacity(lagrange_polys.len());
+    let mut shifts = Vec::with_capacity(lagrange_polys.len());
     for (j, lp) in lagrange_polys.iter_mut().enumerate() {
         lp[0] = GenericResidue::from_uint(U64::ONE);
         let mut trailing_zeros = 0u32;
@@ -69,54 +117,21 @@ where
         }
 
         assert!(trailing_zeros <= P::DELTA);
+        denoms.push(<P as PolyParameters>::Residue::from_i64(denom));
+        shifts.push((P::DELTA - trailing_zeros) as usize);
+    }
 
-        // Compute factor := 2^delta / denom
-        let denom = <P as PolyParameters>::Residue::from_i64(denom);
-        let factor = denom
-            .invert()
-            .0
-            .shl_vartime((P::DELTA - trailing_zeros) as usize);
-
-        // Compute lp *= factor
+    // The denominators are odd, so they are invertible.
+    assert!(<P as PolyParameters>::Residue::invert_batch(&mut denoms));
+    for ((lp, inverse), shift) in lagrange_polys.iter_mut().zip(&denoms).zip(shifts) {
+        // Compute lp *= 2^delta / denom
+        let factor = inverse.shl_vartime(shift);
         for entry in lp.iter_mut() {
             *entry *= factor;
         }
     }
 
-    let mut result = CrtPoly::<P>::new();
-
-    for (factor_index, chunk) in unpacked
-        .chunks(packing_capacity_per_slot::<P>())
-        .enumerate()
-    {
-        let slot_begin = factor_index * P::FACTOR_DEGREE;
-        for (entry, lp) in chunk.iter().zip(lagrange_polys.iter()) {
-            for i in 0..P::FACTOR_DEGREE {
-                let extended: <P as PolyParameters>::Residue =
-                    GenericResidue::from_unsigned(*entry);
-                result.coefficients[slot_begin + i] += extended * lp[i];
-            }
-        }
-    }
-
-    // // Alternative implementation, TODO: check which one is more cache-friendly
-    // for (chunk, lp) in unpacked
-    //     .chunks(P::FACTOR_COUNT)
-    //     .zip(lagrange_polys.iter())
-    // {
-    //     // Here we treat chunk as a CrtPoly where each slot is constant,
-    //     // and we compute result += chunk * lp.
-    //     for (factor_index, entry) in chunk.iter().enumerate() {
-    //         let slot_begin = factor_index * P::FACTOR_DEGREE;
-    //         for i in 0..P::FACTOR_DEGREE {
-    //             let extended: <P as PolyParameters>::Residue =
-    //                 GenericResidue::from_unsigned(*entry);
-    //             result.coefficients[slot_begin + i] += extended * lp[i];
-    //         }
-    //     }
-    // }
-
-    result
+    lagrange_polys
 }
 
 pub fn pack_diagonal<P>(unpacked: impl GenericNativeResidue) -> CrtPoly<P>
@@ -145,6 +160,12 @@ where
     result
 }
 
+/// Evaluates the slots of `crt` and drops the lowest `2 delta` bits of each value.
+///
+/// Returns `None` if a value is not divisible by `2^{2 delta}`, which holds for products of packed
+/// values (plus masks from `pack_mask()`) unless the decryption failed, e.g., because the noise
+/// exceeded the correctness bound.  A failure that happens to keep all values divisible is missed
+/// with probability about `2^{-2 delta}` per value.
 pub fn unpack<P, T>(crt: &CrtPoly<P>) -> Option<Vec<T>>
 where
     P: TIPParameters,
@@ -176,9 +197,13 @@ where
         for (entry, b_powers) in chunk.iter_mut().zip(powers.iter()) {
             let mut evaluated = <P as PolyParameters>::Residue::ZERO;
             for i in 0..P::FACTOR_DEGREE {
-                evaluated += crt.coefficients[slot_begin + i] * b_powers[i];
+                evaluated.mul_add_assign(crt.coefficients[slot_begin + i], b_powers[i]);
+            }
+            let low_bits =
+                evaluated.shl_vartime(<P as PolyParameters>::Residue::BITS - 2 * P::DELTA as usize);
+            if low_bits != <P as PolyParameters>::Residue::ZERO {
+                return None;
             }
-            // TODO: Check that `evaluated` is divisible by 2^(2delta)
             *entry = GenericResidue::from_unsigned(evaluated.shr_vartime(2 * P::DELTA as usize));
         }
     }
@@ -186,19 +211,96 @@ where
     Some(result)
 }
 
-#[cfg(test)]
+/// Evaluates a slot of `crt` at the point of the value with index `index`, i.e., the value is
+/// scaled by `2^delta` if `crt` is the result of `pack()`.
+fn evaluate<P>(crt: &CrtPoly<P>, index: usize) -> <P as PolyParameters>::Residue
+where
+    P: TIPParameters,
+    P::Residue: GenericNativeResidue,
+{
+    assert!(index < packing_capacity::<P>());
+    let slot_begin = index / packing_capacity_per_slot::<P>() * P::FACTOR_DEGREE;
+    let base: <P as PolyParameters>::Residue = GenericResidue::from_uint(U64::from_u64(
+        (index % packing_capacity_per_slot::<P>()) as u64,
+    ));
+    let mut power = GenericResidue::from_uint(U64::ONE);
+    let mut evaluated = <P as PolyParameters>::Residue::ZERO;
+    for coeff in &crt.coefficients.as_slice()[slot_begin..slot_begin + P::FACTOR_DEGREE] {
+        evaluated.mul_add_assign(*coeff, power);
+        power *= base;
+    }
+    evaluated
+}
+
+/// Returns the value with index `index` of `packed`, which is the result of `pack()` (and
+/// `set_slot()`), without unpacking the other values.
+///
+/// Returns `None` if the value is not divisible by `2^delta`, i.e., if `packed` is no such result.
+pub fn get_slot<P, T>(packed: &CrtPoly<P>, index: usize) -> Option<T>
+where
+    P: TIPParameters,
+    P::Residue: GenericNativeResidue,
+    T: GenericNativeResidue,
+{
+    let evaluated = evaluate(packed, index);
+    let low_bits = evaluated.shl_vartime(<P as PolyParameters>::Residue::BITS - P::DELTA as usize);
+    if low_bits != <P as PolyParameters>::Residue::ZERO {
+        return None;
+    }
+    Some(GenericResidue::from_unsigned(
+        evaluated.shr_vartime(P::DELTA as usize),
+    ))
+}
+
+/// Replaces the value with index `index` of `packed`, which is the result of `pack()`, by `value`.
+/// Only the coefficients of one slot are updated, and the other values are left unchanged.
+pub fn set_slot<P>(packed: &mut CrtPoly<P>, index: usize, value: impl GenericNativeResidue)
+where
+    P: TIPParameters,
+    P::Residue: GenericNativeResidue,
+{
+    debug_assert!(
+        <P as PolyParameters>::Residue::try_from_unsigned(value).is_some(),
+        "packed value exceeds the plaintext modulus"
+    );
+    // The old value is only determined modulo `2^{BITS - delta}`, but the Lagrange polynomial
+    // scales the difference by `2^delta`, so this suffices.
+    let old = evaluate(packed, index).shr_vartime(P::DELTA as usize);
+    let diff = <P as PolyParameters>::Residue::from_unsigned(value) - old;
+
+    // TODO: Precompute
+    let lp = &lagrange_polys::<P>()[index % packing_capacity_per_slot::<P>()];
+    let slot_begin = index / packing_capacity_per_slot::<P>() * P::FACTOR_DEGREE;
+    for (dst, lp_coeff) in packed.coefficients.as_mut_slice()
+        [slot_begin..slot_begin + P::FACTOR_DEGREE]
+        .iter_mut()
+        .zip(lp.as_slice())
+    {
+        dst.mul_add_assign(diff, *lp_coeff);
+    }
+}
+
+// Most of the tests use the production parameters.
+#[cfg(all(
+    test,
+    feature = "params-k32",
+    feature = "params-k64",
+    feature = "params-k128"
+))]
 mod tests {
     use crypto_bigint::Random;
 
     use crate::{
         bgv::{
             poly::CrtContext,
+            residue::GenericResidue,
             tweaked_interpolation_packing::{
-                get_random_unpacked, pack, pack_diagonal, pack_mask, packing_capacity, unpack,
+                get_random_unpacked, get_slot, pack, pack_diagonal, pack_mask, packing_capacity,
+                set_slot, unpack,
             },
         },
         low_gear_preproc::{
-            params::{PreprocK128S64, PreprocK32S32, PreprocK64S64},
+            params::{PreprocK128S64, PreprocK32S32, PreprocK64S64, ToyPreprocK32S32},
             PreprocessorParameters,
         },
     };
@@ -261,6 +363,11 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[tokio::test]
+    async fn pack_mul_mask_unpack_toy() {
+        pack_mul_mask_unpack::<ToyPreprocK32S32>().await;
+    }
+
     #[tokio::test]
     async fn pack_mul_mask_unpack_t96() {
         pack_mul_mask_unpack::<PreprocK32S32>().await;
@@ -298,6 +405,24 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[tokio::test]
+    async fn unpack_detects_indivisible_values() {
+        type Plain = <PreprocK32S32 as PreprocessorParameters>::PlaintextParams;
+        #[allow(clippy::upper_case_acronyms)]
+        type KSS = <PreprocK32S32 as PreprocessorParameters>::KSS;
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::gen().await;
+        let a = get_random_unpacked::<Plain, KSS>(&mut rng);
+        let packed_a = pack::<Plain>(&a);
+        let mut packed_prod = packed_a.clone();
+        packed_prod *= (&packed_a, &ctx);
+        assert!(unpack::<_, KSS>(&packed_prod).is_some());
+
+        // Adding 1 to the constant coefficient of a slot changes all of its values by 1.
+        packed_prod.coefficients[0] += GenericResidue::from_i64(1);
+        assert!(unpack::<_, KSS>(&packed_prod).is_none());
+    }
+
     #[tokio::test]
     async fn pack_diagonal_eq_t96() {
         pack_diagonal_eq::<PreprocK32S32>().await;
@@ -323,4 +448,27 @@ mod tests {
         let expected = pack::<P::PlaintextParams>(&diag);
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn get_and_set_slot() {
+        type P = PreprocK32S32;
+        type Plain = <P as PreprocessorParameters>::PlaintextParams;
+        #[allow(clippy::upper_case_acronyms)]
+        type KSS = <P as PreprocessorParameters>::KSS;
+        let mut rng = rand::thread_rng();
+        let mut a = get_random_unpacked::<Plain, KSS>(&mut rng);
+        let mut packed_a = pack::<Plain>(&a);
+        for (i, val) in a.iter().enumerate() {
+            assert_eq!(get_slot::<_, KSS>(&packed_a, i), Some(*val));
+        }
+
+        for i in [0, 1, packing_capacity::<Plain>() - 1] {
+            a[i] = KSS::random(&mut rng);
+            set_slot(&mut packed_a, i, a[i]);
+        }
+        assert_eq!(packed_a, pack::<Plain>(&a));
+        for (i, val) in a.iter().enumerate() {
+            assert_eq!(get_slot::<_, KSS>(&packed_a, i), Some(*val));
+        }
+    }
 }
@@ -0,0 +1,342 @@
+//! The randomness of a BGV encryption, which also serves as the witness of the ZKPoPK.
+//!
+//! A ciphertext is `(c_0, c_1) = (b v + m + t e_0, a v + t e_1)` for the public key `(b, a)`,
+//! where `t` is the plaintext modulus.  The witness consists of the noised plaintext `m + t e_0`,
+//! `e_1` and `v` in power basis.  Since the encryption is linear in the witness, the ZKPoPK prover
+//! accumulates witnesses like the verifier accumulates the ciphertexts.
+
+use std::marker::PhantomData;
+
+use rand::{CryptoRng, RngCore};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use super::{
+    add_centered_binomial_scaled,
+    generic_uint::{ExtendableUint, GenericUint},
+    poly::{crt::CrtPoly, power::PowerPoly, CrtContext, PolyParameters},
+    residue::{native::GenericNativeResidue, GenericResidue},
+    sample_centered_binomial,
+    zkpopk::response_bound,
+    BgvParameters, PreCiphertext, PublicKey,
+};
+
+type ExtendedUint<P> =
+    <<<P as PolyParameters>::Residue as GenericResidue>::Uint as ExtendableUint>::Extended;
+
+/// Randomness of an encryption of a plaintext in `R_t = \mathbb{Z}_t[X]/\Phi_M(X)`.
+///
+/// We give `P` as a generic parameter, because `P::CYCLOTOMIC_DEGREE` determines the length of the
+/// stored vectors.
+#[derive(Deserialize, Serialize)]
+#[serde(bound = "")]
+pub struct EncryptionWitness<P>
+where
+    P: PolyParameters,
+    <P::Residue as GenericResidue>::Uint: ExtendableUint,
+{
+    #[serde(deserialize_with = "deserialize_coefficients::<P, _, _>")]
+    pub(super) noised_plaintext: Vec<ExtendedUint<P>>,
+    #[serde(deserialize_with = "deserialize_coefficients::<P, _, _>")]
+    pub(super) e_1: Vec<i64>,
+    #[serde(deserialize_with = "deserialize_coefficients::<P, _, _>")]
+    pub(super) v: Vec<i64>,
+    pub(super) phantom: PhantomData<P>,
+}
+
+/// Deserializes a component of a witness, which must have `P::CYCLOTOMIC_DEGREE` coefficients like
+/// in `EncryptionWitness::from_parts()`.
+fn deserialize_coefficients<'de, P, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    P: PolyParameters,
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let coefficients = Vec::<T>::deserialize(deserializer)?;
+    if coefficients.len() != P::CYCLOTOMIC_DEGREE {
+        return Err(de::Error::invalid_length(
+            coefficients.len(),
+            &format!("{} coefficients", P::CYCLOTOMIC_DEGREE).as_str(),
+        ));
+    }
+    Ok(coefficients)
+}
+
+/// Bounds on the coefficients of an `EncryptionWitness`.  A witness satisfies them if, for all
+/// coefficients, `-noised_plaintext * t <= m + t e_0 < noised_plaintext * t` (as signed integer),
+/// `-e_1 <= e_1 < e_1` and `-v <= v < v`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WitnessBounds {
+    pub noised_plaintext: i64,
+    pub e_1: i64,
+    pub v: i64,
+}
+
+impl WitnessBounds {
+    /// Bounds of the witnesses of `EncryptionWitness::new_from_plaintext()`, whose noise is
+    /// sampled from a centered binomial distribution with maximum magnitude 20 for `e_0` and `e_1`
+    /// and 1 for `v`.
+    pub const FRESH: Self = Self {
+        noised_plaintext: 21,
+        e_1: 21,
+        v: 2,
+    };
+
+    /// Bounds that the ZKPoPK verifier checks for the accumulated witnesses, which are
+    /// `21 B`, `20 B` and `B` for `B = 3 (M - 1)^2 num_ciphertexts num_proofs inv_fail_prob`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds overflow, see `try_zkpopk()`.
+    pub fn zkpopk<P>(inv_fail_prob: usize, num_ciphertexts: usize, num_proofs: usize) -> Self
+    where
+        P: PolyParameters,
+    {
+        Self::try_zkpopk::<P>(inv_fail_prob, num_ciphertexts, num_proofs)
+            .expect("the ZKPoPK bounds overflow i64")
+    }
+
+    /// Like `zkpopk()`, but returns `None` if the bounds overflow, see `zkpopk::response_bound()`.
+    pub fn try_zkpopk<P>(
+        inv_fail_prob: usize,
+        num_ciphertexts: usize,
+        num_proofs: usize,
+    ) -> Option<Self>
+    where
+        P: PolyParameters,
+    {
+        let bound = response_bound(P::M, inv_fail_prob, num_ciphertexts, num_proofs)?;
+        Some(Self {
+            noised_plaintext: 21 * bound,
+            e_1: 20 * bound,
+            v: bound,
+        })
+    }
+}
+
+impl<P> EncryptionWitness<P>
+where
+    P: PolyParameters,
+    <P::Residue as GenericResidue>::Uint: ExtendableUint,
+{
+    /// Samples fresh randomness for encrypting `plaintext`.
+    pub fn new_from_plaintext(plaintext: &PowerPoly<P>) -> Self
+    where
+        P::Residue: GenericNativeResidue,
+    {
+        Self::new_from_plaintext_with_rng(plaintext, crate::rng::seeded_rng())
+    }
+
+    /// Like `new_from_plaintext()`, but samples the randomness with `rng`.
+    pub fn new_from_plaintext_with_rng(
+        plaintext: &PowerPoly<P>,
+        mut rng: impl CryptoRng + RngCore,
+    ) -> Self
+    where
+        P::Residue: GenericNativeResidue,
+    {
+        // We approximate the discrete gaussian distribution of variance 10 with
+        // the centered binomial distribution of variance 10.  So the number of
+        // iterations and the maximum magnitude is 20.
+        let noised_plaintext = add_centered_binomial_scaled(&mut rng, plaintext, 20);
+        let e_1 = sample_centered_binomial::<P>(&mut rng, 20);
+        let v = sample_centered_binomial::<P>(&mut rng, 1);
+        let witness = Self {
+            noised_plaintext,
+            e_1,
+            v,
+            phantom: PhantomData,
+        };
+        debug_assert!(witness.satisfies(&WitnessBounds::FRESH));
+        witness
+    }
+
+    /// Assembles a witness from its components, see the accessors.  Returns `None` unless each of
+    /// them has `P::CYCLOTOMIC_DEGREE` coefficients.
+    pub fn from_parts(
+        noised_plaintext: Vec<ExtendedUint<P>>,
+        e_1: Vec<i64>,
+        v: Vec<i64>,
+    ) -> Option<Self> {
+        if noised_plaintext.len() != P::CYCLOTOMIC_DEGREE
+            || e_1.len() != P::CYCLOTOMIC_DEGREE
+            || v.len() != P::CYCLOTOMIC_DEGREE
+        {
+            return None;
+        }
+        Some(Self {
+            noised_plaintext,
+            e_1,
+            v,
+            phantom: PhantomData,
+        })
+    }
+
+    /// The coefficients of `m + t e_0` as two's complement integers.
+    pub fn noised_plaintext(&self) -> &[ExtendedUint<P>] {
+        &self.noised_plaintext
+    }
+
+    /// The coefficients of `e_1`, the noise of `c_1` divided by `t`.
+    pub fn e_1(&self) -> &[i64] {
+        &self.e_1
+    }
+
+    /// The coefficients of `v`, by which the public key is multiplied.
+    pub fn v(&self) -> &[i64] {
+        &self.v
+    }
+
+    /// Whether all coefficients are within `bounds`.
+    pub fn satisfies(&self, bounds: &WitnessBounds) -> bool {
+        let shifted_bound =
+            ExtendedUint::<P>::from_i64(bounds.noised_plaintext) << P::Residue::BITS;
+        let positive_bound = shifted_bound << 1;
+        let in_bounds = |val: &i64, bound: i64| -bound <= *val && *val < bound;
+
+        self.noised_plaintext
+            .iter()
+            .all(|uint| uint.wrapping_add(&shifted_bound) < positive_bound)
+            && self.e_1.iter().all(|val| in_bounds(val, bounds.e_1))
+            && self.v.iter().all(|val| in_bounds(val, bounds.v))
+    }
+
+    /// Encrypts the plaintext of this witness with its randomness.
+    pub async fn encrypt_into<BgvParams>(
+        &self,
+        ctx: &CrtContext<BgvParams::CiphertextParams>,
+        pk: &PublicKey<BgvParams>,
+        ciphertext: &mut PreCiphertext<BgvParams>,
+    ) where
+        BgvParams: BgvParameters<PlaintextParams = P>,
+    {
+        let scaled_e_1: Vec<_> = self
+            .e_1
+            .iter()
+            .map(|e| {
+                let extended = ExtendedUint::<P>::from_i64(*e);
+                extended << BgvParams::PlaintextResidue::BITS
+            })
+            .collect();
+
+        let mut temp_power = PowerPoly::new();
+        let mut temp_crt = CrtPoly::new();
+
+        temp_power.clone_from_i64s(&self.v);
+        let v = CrtPoly::from_power(ctx, &temp_power).await;
+
+        temp_crt.clone_from(&pk.b);
+        temp_crt *= &v;
+        ciphertext.c_0.clone_from_crt(ctx, &temp_crt).await;
+        temp_power.clone_from_signed_ints(&self.noised_plaintext);
+        ciphertext.c_0 += &temp_power;
+
+        temp_crt.clone_from(&pk.a);
+        temp_crt *= &v;
+        ciphertext.c_1.clone_from_crt(ctx, &temp_crt).await;
+        temp_power.clone_from_signed_ints(&scaled_e_1);
+        ciphertext.c_1 += &temp_power;
+    }
+
+    pub(super) fn add_assign_slided(&mut self, rhs: &Self, length: usize) {
+        if length == 0 {
+            return;
+        }
+        let mut sum_np = ExtendedUint::<P>::default();
+        let mut sum_e_1 = 0;
+        let mut sum_v = 0;
+        for power in 1..P::M {
+            let index = power % (P::M - 1);
+            sum_np = sum_np.wrapping_add(&rhs.noised_plaintext[index]);
+            sum_e_1 += rhs.e_1[index];
+            sum_v += rhs.v[index];
+            if power != length {
+                let rhs_index = (power + P::M - length) % P::M % (P::M - 1);
+                sum_np = sum_np.wrapping_sub(&rhs.noised_plaintext[rhs_index]);
+                sum_e_1 -= rhs.e_1[rhs_index];
+                sum_v -= rhs.v[rhs_index];
+            }
+            let np = &mut self.noised_plaintext[index];
+            *np = np.wrapping_add(&sum_np);
+            self.e_1[index] += sum_e_1;
+            self.v[index] += sum_v;
+        }
+    }
+}
+
+impl<P> Default for EncryptionWitness<P>
+where
+    P: PolyParameters,
+    <P::Residue as GenericResidue>::Uint: ExtendableUint,
+{
+    fn default() -> Self {
+        Self {
+            noised_plaintext: vec![ExtendedUint::<P>::default(); P::CYCLOTOMIC_DEGREE],
+            e_1: vec![0; P::CYCLOTOMIC_DEGREE],
+            v: vec![0; P::CYCLOTOMIC_DEGREE],
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P> Clone for EncryptionWitness<P>
+where
+    P: PolyParameters,
+    <P::Residue as GenericResidue>::Uint: ExtendableUint,
+{
+    fn clone(&self) -> Self {
+        Self {
+            noised_plaintext: self.noised_plaintext.clone(),
+            e_1: self.e_1.clone(),
+            v: self.v.clone(),
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.noised_plaintext.clone_from(&source.noised_plaintext);
+        self.e_1.clone_from(&source.e_1);
+        self.v.clone_from(&source.v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bgv::{
+        params::{ToyBgv, ToyPlain},
+        poly::{power::PowerPoly, CrtContext},
+        PreCiphertext, PublicKey, SecretKey,
+    };
+
+    use super::{EncryptionWitness, WitnessBounds};
+
+    #[tokio::test]
+    async fn serde_roundtrip_reencrypts() {
+        let mut rng = rand::thread_rng();
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let plaintext = PowerPoly::<ToyPlain>::random(&mut rng);
+        let witness = EncryptionWitness::new_from_plaintext(&plaintext);
+        assert!(witness.satisfies(&WitnessBounds::FRESH));
+        assert!(!witness.satisfies(&WitnessBounds {
+            noised_plaintext: 21,
+            e_1: 21,
+            v: 0,
+        }));
+
+        let bytes = bincode::serialize(&witness).unwrap();
+        let roundtrip: EncryptionWitness<ToyPlain> = bincode::deserialize(&bytes).unwrap();
+        let rebuilt = EncryptionWitness::<ToyPlain>::from_parts(
+            roundtrip.noised_plaintext().to_vec(),
+            roundtrip.e_1().to_vec(),
+            roundtrip.v().to_vec(),
+        )
+        .unwrap();
+
+        let mut expected = PreCiphertext::<ToyBgv>::default();
+        witness.encrypt_into(&ctx, &pk, &mut expected).await;
+        let mut actual = PreCiphertext::<ToyBgv>::default();
+        rebuilt.encrypt_into(&ctx, &pk, &mut actual).await;
+        assert_eq!(actual, expected);
+    }
+}
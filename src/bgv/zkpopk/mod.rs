@@ -1,10 +1,14 @@
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::bgv::generic_uint::ExtendableUint;
+use crate::crypto_suite::CryptoSuite;
+use crate::transcript::Transcript;
 
 use super::{
-    generic_uint::GenericUint, poly::PolyParameters, residue::GenericResidue, BgvParameters,
-    PreCiphertext, PreparedPlaintext,
+    poly::PolyParameters,
+    witness::{EncryptionWitness, WitnessBounds},
+    BgvParameters, PreCiphertext,
 };
 
 pub mod prover;
@@ -20,13 +24,106 @@ where
 #[derive(Clone, Copy, Deserialize, Serialize)]
 pub struct Challenge([u8; 32]);
 
+impl Challenge {
+    pub fn random(mut rng: impl CryptoRng + RngCore) -> Self {
+        Self(rng.gen())
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
 #[derive(Deserialize, Serialize)]
-pub struct Response<P>(Vec<PreparedPlaintext<P::PlaintextParams>>)
+pub struct Response<P>(Vec<EncryptionWitness<P::PlaintextParams>>)
 where
     P: BgvParameters;
 
+/// How the per-ciphertext challenges are derived from a `Challenge`.  Prover and verifier must use
+/// the same version.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum ZkpopkVersion {
+    /// ChaCha20 seeded with the challenge.  Only for verifying proofs of earlier runs.
+    V1,
+    /// ChaCha20 seeded with a hash of the challenge and the `Statement`, which makes the
+    /// challenges depend on the ciphertexts that are proven.
+    #[default]
+    V2,
+}
+
+/// Hash of the ciphertexts that a ZKPoPK is about, in order.  The prover appends its ciphertexts
+/// when sending them, the verifier computes it from the received ones.  Both must use the same
+/// `CryptoSuite`, which determines the challenges in `ZkpopkVersion::V2`.
+#[derive(Clone)]
+pub struct Statement {
+    transcript: Transcript,
+}
+
+impl Statement {
+    pub fn new() -> Self {
+        Self::with_suite(CryptoSuite::default())
+    }
+
+    pub fn with_suite(suite: CryptoSuite) -> Self {
+        Self {
+            transcript: Transcript::with_suite(suite, "ZKPoPK"),
+        }
+    }
+
+    pub fn of<P>(ciphertexts: &[PreCiphertext<P>]) -> Self
+    where
+        P: BgvParameters,
+    {
+        Self::of_with_suite(CryptoSuite::default(), ciphertexts)
+    }
+
+    pub fn of_with_suite<P>(suite: CryptoSuite, ciphertexts: &[PreCiphertext<P>]) -> Self
+    where
+        P: BgvParameters,
+    {
+        let mut statement = Self::with_suite(suite);
+        for ciphertext in ciphertexts {
+            statement.append(ciphertext);
+        }
+        statement
+    }
+
+    pub fn append<P>(&mut self, ciphertext: &PreCiphertext<P>)
+    where
+        P: BgvParameters,
+    {
+        self.transcript.append("ciphertext", ciphertext);
+    }
+}
+
+impl Default for Statement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The PRG from which the challenges of the ciphertexts are sampled, one per proof and ciphertext.
+fn challenge_prng(
+    version: ZkpopkVersion,
+    challenge: &Challenge,
+    statement: &Statement,
+) -> ChaCha20Rng {
+    match version {
+        ZkpopkVersion::V1 => ChaCha20Rng::from_seed(challenge.0),
+        ZkpopkVersion::V2 => {
+            let mut transcript = statement.transcript.clone();
+            transcript.append_bytes("challenge", &challenge.0);
+            ChaCha20Rng::from_seed(transcript.session_id())
+        }
+    }
+}
+
 fn check_bounds<P>(
-    prepared_plaintext: &PreparedPlaintext<P::PlaintextParams>,
+    witness: &EncryptionWitness<P::PlaintextParams>,
     inv_fail_prob: usize,
     num_ciphertexts: usize,
     num_proofs: usize,
@@ -34,37 +131,16 @@ fn check_bounds<P>(
 where
     P: BgvParameters,
 {
-    type ExtendedUint<P> =
-        <<<<P as BgvParameters>::PlaintextParams as PolyParameters>::Residue as GenericResidue>::Uint as ExtendableUint>::Extended;
-
-    let bound = (3
-        * (P::PlaintextParams::M - 1)
-        * (P::PlaintextParams::M - 1)
-        * num_ciphertexts
-        * num_proofs
-        * inv_fail_prob) as i64;
-
-    let shifted_bound = ExtendedUint::<P>::from_i64(21 * bound) << P::PlaintextResidue::BITS;
-
-    for uint in &prepared_plaintext.noised_plaintext {
-        let positive_val = uint.wrapping_add(&shifted_bound);
-        let positive_bound = shifted_bound << 1;
-        if positive_val >= positive_bound {
-            return false;
-        }
-    }
-    for val in &prepared_plaintext.e_1 {
-        if -20 * bound > *val || *val >= 20 * bound {
-            return false;
-        }
-    }
-    for val in &prepared_plaintext.v {
-        if -bound > *val || *val >= bound {
-            return false;
-        }
+    // Overflowing bounds are rejected by `Prover::new()` and `Verifier::new()`.  Should they occur
+    // nevertheless, the response is rejected instead of being checked against wrapped bounds.
+    match WitnessBounds::try_zkpopk::<P::PlaintextParams>(
+        inv_fail_prob,
+        num_ciphertexts,
+        num_proofs,
+    ) {
+        Some(bounds) => witness.satisfies(&bounds),
+        None => false,
     }
-
-    true
 }
 
 pub fn num_proofs<P>(snd_sec: usize) -> usize
@@ -74,15 +150,95 @@ where
     ((snd_sec + 2) as f64 / ((P::PlaintextParams::M - 1) as f64).log2()).ceil() as usize
 }
 
+/// Panics unless `response_bound()` returns a bound, which `Prover` and `Verifier` check on
+/// construction, so that their bounds cannot silently overflow.
+fn assert_bounds_fit<P>(inv_fail_prob: usize, num_ciphertexts: usize, num_proofs: usize)
+where
+    P: BgvParameters,
+{
+    assert!(
+        response_bound(
+            P::PlaintextParams::M,
+            inv_fail_prob,
+            num_ciphertexts,
+            num_proofs
+        )
+        .is_some(),
+        "the ZKPoPK bounds overflow i64 for inv_fail_prob {}, {} ciphertexts and {} proofs",
+        inv_fail_prob,
+        num_ciphertexts,
+        num_proofs
+    );
+}
+
+/// An upper bound on `num_proofs()` that can be evaluated at compile time.
+pub const fn max_num_proofs(m: usize, snd_sec: usize) -> usize {
+    let log = (m - 1).ilog2() as usize;
+    (snd_sec + 2).div_ceil(log)
+}
+
+/// The bound `B = 3 (M - 1)^2 num_ciphertexts num_proofs inv_fail_prob` on the coefficients of `v`
+/// of the responses, see `WitnessBounds::zkpopk()`.
+///
+/// Returns `None` if the coefficients of the pseudo-inputs or the responses might overflow `i64`,
+/// in which case the ZKPoPK cannot be run with these parameters.  Their magnitude is below
+/// `21 (B + 2 (M - 1) num_ciphertexts)`.
+pub const fn response_bound(
+    m: usize,
+    inv_fail_prob: usize,
+    num_ciphertexts: usize,
+    num_proofs: usize,
+) -> Option<i64> {
+    let m_1 = (m - 1) as u128;
+    let Some(bound) = checked_product(&[
+        3,
+        m_1,
+        m_1,
+        num_ciphertexts as u128,
+        num_proofs as u128,
+        inv_fail_prob as u128,
+    ]) else {
+        return None;
+    };
+    // `bound` and the factors are below `2^64`, so this cannot overflow `u128`.
+    let max_coefficient = 21 * (bound + 2 * m_1 * num_ciphertexts as u128);
+    if max_coefficient > i64::MAX as u128 {
+        return None;
+    }
+    Some(bound as i64)
+}
+
+const fn checked_product(factors: &[u128]) -> Option<u128> {
+    let mut product: u128 = 1;
+    let mut i = 0;
+    while i < factors.len() {
+        product = match product.checked_mul(factors[i]) {
+            Some(product) if product <= u64::MAX as u128 => product,
+            _ => return None,
+        };
+        i += 1;
+    }
+    Some(product)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use crate::bgv::{
         params::ToyBgv,
         poly::{power::PowerPoly, CrtContext},
         PreCiphertext, PublicKey, SecretKey,
     };
+    use crate::crypto_suite::CryptoSuite;
 
-    use super::{prover::Prover, verifier::Verifier};
+    use super::{
+        max_num_proofs,
+        prover::Prover,
+        response_bound,
+        verifier::{VerificationFailed, Verifier},
+        Challenge, Statement, ZkpopkVersion,
+    };
 
     #[tokio::test]
     async fn zkpopk() {
@@ -91,7 +247,7 @@ mod tests {
         const SND_SEC: usize = 64;
 
         let mut rng = rand::thread_rng();
-        let ctx = CrtContext::gen().await;
+        let ctx = Arc::new(CrtContext::gen().await);
         let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
         let pk = PublicKey::gen(&ctx, &sk).await;
         let mut ciphertexts = Vec::new();
@@ -110,12 +266,186 @@ mod tests {
         let verifier = Verifier::new(INV_FAIL_PROB, NUM_CIPHERTEXTS, SND_SEC);
         let challenge = verifier.challenge();
 
-        let response = prover.respond(&inputs, *challenge).unwrap();
+        let response = prover
+            .respond(&inputs, &Statement::of(&ciphertexts), *challenge)
+            .unwrap();
 
         assert!(
             verifier
-                .verify(&ctx, &pk, &ciphertexts, commitment, &response)
+                .verify(&ctx, &pk, &ciphertexts, commitment, response)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn versions_are_not_interchangeable() {
+        const INV_FAIL_PROB: usize = 1 << 20;
+        const SND_SEC: usize = 64;
+
+        let mut rng = rand::thread_rng();
+        let ctx = Arc::new(CrtContext::gen().await);
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let mut ciphertexts = vec![PreCiphertext::default()];
+        let inputs = vec![
+            Prover::encrypt_into(&ctx, &pk, &PowerPoly::random(&mut rng), &mut ciphertexts[0])
+                .await,
+        ];
+        let challenge = Challenge::random(&mut rng);
+
+        let (ctx, pk, inputs, ciphertexts) = (&ctx, &pk, &inputs, &ciphertexts);
+        let prove = |version| async move {
+            let prover = Prover::<ToyBgv>::new(INV_FAIL_PROB, 1, SND_SEC).with_version(version);
+            let commitment = prover.commit(ctx, pk).await;
+            let response = prover
+                .respond(inputs, &Statement::of(ciphertexts), challenge)
+                .unwrap();
+            (commitment, response)
+        };
+        let verify = |version, (commitment, response)| async move {
+            Verifier::with_challenge(INV_FAIL_PROB, 1, SND_SEC, challenge)
+                .with_version(version)
+                .verify(ctx, pk, ciphertexts, commitment, response)
+                .await
+        };
+
+        assert!(verify(ZkpopkVersion::V1, prove(ZkpopkVersion::V1).await).await);
+        assert!(!verify(ZkpopkVersion::V2, prove(ZkpopkVersion::V1).await).await);
+        assert!(!verify(ZkpopkVersion::V1, prove(ZkpopkVersion::V2).await).await);
+    }
+
+    #[tokio::test]
+    async fn suites_are_not_interchangeable() {
+        const INV_FAIL_PROB: usize = 1 << 20;
+        const SND_SEC: usize = 64;
+
+        let mut rng = rand::thread_rng();
+        let ctx = Arc::new(CrtContext::gen().await);
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let mut ciphertexts = vec![PreCiphertext::default()];
+        let inputs = vec![
+            Prover::encrypt_into(&ctx, &pk, &PowerPoly::random(&mut rng), &mut ciphertexts[0])
+                .await,
+        ];
+        let challenge = Challenge::random(&mut rng);
+
+        let (ctx, pk, inputs, ciphertexts) = (&ctx, &pk, &inputs, &ciphertexts);
+        let prove = |suite| async move {
+            let prover = Prover::<ToyBgv>::new(INV_FAIL_PROB, 1, SND_SEC);
+            let commitment = prover.commit(ctx, pk).await;
+            let statement = Statement::of_with_suite(suite, ciphertexts);
+            let response = prover.respond(inputs, &statement, challenge).unwrap();
+            (commitment, response)
+        };
+        let verify = |suite, (commitment, response)| async move {
+            Verifier::with_challenge(INV_FAIL_PROB, 1, SND_SEC, challenge)
+                .with_suite(suite)
+                .verify(ctx, pk, ciphertexts, commitment, response)
                 .await
+        };
+
+        for &prover_suite in CryptoSuite::ALL {
+            for &verifier_suite in CryptoSuite::ALL {
+                assert_eq!(
+                    verify(verifier_suite, prove(prover_suite).await).await,
+                    prover_suite == verifier_suite
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_proof_is_reported() {
+        const INV_FAIL_PROB: usize = 1 << 20;
+        const SND_SEC: usize = 64;
+
+        let mut rng = rand::thread_rng();
+        let ctx = Arc::new(CrtContext::gen().await);
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        let mut ciphertexts = vec![PreCiphertext::default()];
+        let inputs = vec![
+            Prover::encrypt_into(&ctx, &pk, &PowerPoly::random(&mut rng), &mut ciphertexts[0])
+                .await,
+        ];
+
+        let prover = Prover::<ToyBgv>::new(INV_FAIL_PROB, 1, SND_SEC);
+        let mut commitment = prover.commit(&ctx, &pk).await;
+        let other_commitment = Prover::<ToyBgv>::new(INV_FAIL_PROB, 1, SND_SEC)
+            .commit(&ctx, &pk)
+            .await;
+        let tampered = commitment.0.len() - 1;
+        commitment.0[tampered] = other_commitment.0.into_iter().last().unwrap();
+
+        let verifier = Verifier::new(INV_FAIL_PROB, 1, SND_SEC);
+        let response = prover
+            .respond(&inputs, &Statement::of(&ciphertexts), *verifier.challenge())
+            .unwrap();
+        let result = verifier
+            .try_verify(&ctx, &pk, &ciphertexts, commitment, response)
+            .await;
+        assert!(matches!(result, Err(VerificationFailed::Mismatch(i)) if i == tampered));
+    }
+
+    #[tokio::test]
+    async fn pseudo_inputs_are_rederived() {
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+
+        // The commitment is computed from the pseudo-inputs derived from the prover's seed, so
+        // committing again yields the same ciphertexts.
+        let prover = Prover::<ToyBgv>::new(1 << 20, 5, 64);
+        let commitment = bincode::serialize(&prover.commit(&ctx, &pk).await).unwrap();
+        let recommitment = bincode::serialize(&prover.commit(&ctx, &pk).await).unwrap();
+        assert_eq!(commitment, recommitment);
+
+        let other = Prover::<ToyBgv>::new(1 << 20, 5, 64);
+        let other_commitment = bincode::serialize(&other.commit(&ctx, &pk).await).unwrap();
+        assert_ne!(commitment, other_commitment);
+    }
+
+    #[test]
+    fn response_bound_at_the_overflow_edge() {
+        // The parameters of `PreprocK128S64` and a large `inv_fail_prob`.
+        const M: usize = 43691;
+        const NUM_CIPHERTEXTS: usize = 20;
+        let num_proofs = max_num_proofs(M, 57);
+
+        // The largest `inv_fail_prob` for which `21 (B + 2 (M - 1) num_ciphertexts)` fits `i64`.
+        let m_1 = (M - 1) as u128;
+        let per_inv_fail_prob = 3 * m_1 * m_1 * NUM_CIPHERTEXTS as u128 * num_proofs as u128;
+        let largest = ((i64::MAX as u128 / 21 - 2 * m_1 * NUM_CIPHERTEXTS as u128)
+            / per_inv_fail_prob) as usize;
+
+        let bound = response_bound(M, largest, NUM_CIPHERTEXTS, num_proofs).unwrap();
+        assert_eq!(bound as u128, per_inv_fail_prob * largest as u128);
+        assert_eq!(
+            response_bound(M, largest + 1, NUM_CIPHERTEXTS, num_proofs),
+            None
+        );
+        assert_eq!(
+            response_bound(M, usize::MAX, NUM_CIPHERTEXTS, num_proofs),
+            None
         );
+        assert_eq!(response_bound(M, 1 << 20, usize::MAX, usize::MAX), None);
+    }
+
+    #[test]
+    fn max_num_proofs_is_an_upper_bound() {
+        for snd_sec in [26, 40, 57, 64, 128] {
+            for m in [179, 337, 21851, 43691] {
+                let max = max_num_proofs(m, snd_sec);
+                let exact = ((snd_sec + 2) as f64 / ((m - 1) as f64).log2()).ceil() as usize;
+                assert!(exact <= max && max <= exact + 1);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow")]
+    fn overflowing_bounds_are_rejected() {
+        Prover::<ToyBgv>::new(usize::MAX, 1, 64);
     }
 }
@@ -1,20 +1,26 @@
 use std::marker::PhantomData;
 
-use crypto_bigint::{Random, Zero};
+use crypto_bigint::{Limb, Random, Zero};
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::bgv::{
-    self,
     generic_uint::{ExtendableUint, GenericUint},
     poly::{power::PowerPoly, CrtContext, PolyParameters},
     residue::GenericResidue,
-    zkpopk, BgvParameters, PreCiphertext, PreparedPlaintext, PublicKey,
+    witness::EncryptionWitness,
+    zkpopk, BgvParameters, PreCiphertext, PublicKey,
 };
 
-use super::{check_bounds, Challenge, Commitment, Response};
+use super::{
+    assert_bounds_fit, challenge_prng, check_bounds, response_bound, Challenge, Commitment,
+    Response, Statement, ZkpopkVersion,
+};
 
+/// The pseudo-inputs are not stored, but derived from a secret seed whenever they are needed.
+/// Hence, `commit()` and `respond()` only hold one pseudo-input at a time (besides the response),
+/// instead of `num_proofs` of them for the whole lifetime of the prover.
 pub struct Prover<P>
 where
     P: BgvParameters,
@@ -22,7 +28,9 @@ where
     inv_fail_prob: usize,
     num_ciphertexts: usize,
     num_proofs: usize,
-    pseudo_inputs: Vec<PreparedPlaintext<P::PlaintextParams>>,
+    version: ZkpopkVersion,
+    seed: [u8; 32],
+    phantom: PhantomData<fn() -> P>,
 }
 
 #[derive(Debug, derive_more::Display, derive_more::Error, Deserialize, Serialize)]
@@ -37,68 +45,100 @@ where
         pk: &PublicKey<P>,
         plaintext: &PowerPoly<P::PlaintextParams>,
         ciphertext: &mut PreCiphertext<P>,
-    ) -> PreparedPlaintext<P::PlaintextParams>
+    ) -> EncryptionWitness<P::PlaintextParams>
     where
         P: BgvParameters,
     {
-        let input = bgv::prepare(plaintext);
+        let input = EncryptionWitness::new_from_plaintext(plaintext);
         input.encrypt_into(ctx, pk, ciphertext).await;
         input
     }
 
+    /// # Panics
+    ///
+    /// Panics if the bounds of the proof overflow, see `zkpopk::response_bound()`.
     pub fn new(inv_fail_prob: usize, num_ciphertexts: usize, snd_sec: usize) -> Self {
         let num_proofs = zkpopk::num_proofs::<P>(snd_sec);
-        let mut rng = rand::thread_rng();
-        let pseudo_inputs = (0..num_proofs)
-            .map(|_| {
-                make_pseudo_input::<P, _>(&mut rng, inv_fail_prob, num_ciphertexts, num_proofs)
-            })
-            .collect();
+        assert_bounds_fit::<P>(inv_fail_prob, num_ciphertexts, num_proofs);
         Self {
             inv_fail_prob,
             num_ciphertexts,
             num_proofs,
-            pseudo_inputs,
+            version: ZkpopkVersion::default(),
+            seed: crate::rng::os_rng().gen(),
+            phantom: PhantomData,
         }
     }
 
+    /// Derives the challenges of the ciphertexts as in the given version, see `ZkpopkVersion`.
+    pub fn with_version(mut self, version: ZkpopkVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Draws the seed of the pseudo-inputs from `rng` instead of `rng::os_rng()`.
+    pub fn with_seed_from(mut self, mut rng: impl CryptoRng + RngCore) -> Self {
+        self.seed = rng.gen();
+        self
+    }
+
+    /// Derives the pseudo-input of the proof with the given index from the seed.
+    fn pseudo_input(&self, index: usize) -> EncryptionWitness<P::PlaintextParams> {
+        let mut rng = ChaCha20Rng::from_seed(self.seed);
+        rng.set_stream(index as u64);
+        make_pseudo_input::<P, _>(
+            rng,
+            self.inv_fail_prob,
+            self.num_ciphertexts,
+            self.num_proofs,
+        )
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn commit(
         &self,
         ctx: &CrtContext<P::CiphertextParams>,
         pk: &PublicKey<P>,
     ) -> Commitment<P> {
-        let mut ciphertexts = Vec::new();
-        for pi in &self.pseudo_inputs {
+        let mut ciphertexts = Vec::with_capacity(self.num_proofs);
+        for index in 0..self.num_proofs {
             let mut ciphertext = PreCiphertext::default();
-            pi.encrypt_into(ctx, pk, &mut ciphertext).await;
+            self.pseudo_input(index)
+                .encrypt_into(ctx, pk, &mut ciphertext)
+                .await;
             ciphertexts.push(ciphertext);
         }
         Commitment(ciphertexts)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    /// Responds to `challenge` for the ciphertexts of `inputs`, which `statement` must hash in the
+    /// same order.
     pub fn respond(
         self,
-        inputs: &[PreparedPlaintext<P::PlaintextParams>],
+        inputs: &[EncryptionWitness<P::PlaintextParams>],
+        statement: &Statement,
         challenge: Challenge,
     ) -> Result<Response<P>, ResponseAborted> {
         debug_assert_eq!(self.num_ciphertexts, inputs.len());
 
-        // TODO: Use random oracle instead
-        let mut prng = ChaCha20Rng::from_seed(challenge.0);
-        let mut accumulated = self.pseudo_inputs;
-        for acc in &mut accumulated {
+        let mut prng = challenge_prng(self.version, &challenge, statement);
+        let mut accumulated = Vec::with_capacity(self.num_proofs);
+        for index in 0..self.num_proofs {
+            let mut acc = self.pseudo_input(index);
             for input in inputs {
                 let challenge = prng.gen_range(0..P::PlaintextParams::M);
                 acc.add_assign_slided(input, challenge);
             }
             if !check_bounds::<P>(
-                acc,
+                &acc,
                 self.inv_fail_prob,
                 self.num_ciphertexts,
                 self.num_proofs,
             ) {
                 return Err(ResponseAborted);
             }
+            accumulated.push(acc);
         }
         Ok(Response(accumulated))
     }
@@ -109,20 +149,22 @@ fn make_pseudo_input<P, Rng>(
     inv_fail_prob: usize,
     num_ciphertexts: usize,
     num_proofs: usize,
-) -> PreparedPlaintext<P::PlaintextParams>
+) -> EncryptionWitness<P::PlaintextParams>
 where
     P: BgvParameters,
     Rng: CryptoRng + RngCore,
 {
-    let bound = ((3 * (P::PlaintextParams::M - 1) * num_proofs * inv_fail_prob + 1)
-        * (P::PlaintextParams::M - 1)
-        * num_ciphertexts) as i64;
+    // `(3 (M - 1) num_proofs inv_fail_prob + 1) (M - 1) num_ciphertexts`, which does not overflow,
+    // see `Prover::new()`.
+    let m = P::PlaintextParams::M;
+    let bound = response_bound(m, inv_fail_prob, num_ciphertexts, num_proofs).unwrap()
+        + ((m - 1) * num_ciphertexts) as i64;
 
     type ExtendedUint<P> =
         <<<<P as BgvParameters>::PlaintextParams as PolyParameters>::Residue as GenericResidue>::Uint as ExtendableUint>::Extended;
 
     assert!(
-        ExtendedUint::<P>::NLIMBS * 64
+        ExtendedUint::<P>::NLIMBS * Limb::BITS
             > 64 - (21 * bound).leading_zeros() as usize + P::PlaintextResidue::BITS
     );
 
@@ -150,10 +192,10 @@ where
         .map(|_| rng.gen_range(-bound..bound))
         .collect();
 
-    PreparedPlaintext {
+    EncryptionWitness {
         noised_plaintext,
         e_1,
         v,
-        phantom: PhantomData::default(),
+        phantom: PhantomData,
     }
 }
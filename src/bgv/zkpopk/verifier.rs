@@ -1,14 +1,21 @@
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 
-use rand::{Rng, SeedableRng};
-use rand_chacha::ChaCha20Rng;
+use rand::Rng;
 
 use crate::bgv::{
-    poly::{CrtContext, PolyParameters},
-    zkpopk, BgvParameters, PreCiphertext, PublicKey,
+    poly::{crt::CrtPoly, CrtContext, PolyParameters},
+    zkpopk, BgvParameters, Ciphertext, PreCiphertext, PublicKey,
 };
+use crate::crypto_suite::CryptoSuite;
+use crate::util::run_parallel;
 
-use super::{check_bounds, Challenge, Commitment, Response};
+use super::{
+    assert_bounds_fit, challenge_prng, check_bounds, Challenge, Commitment, Response, Statement,
+    ZkpopkVersion,
+};
 
 pub struct Verifier<P>
 where
@@ -17,79 +24,181 @@ where
     inv_fail_prob: usize,
     num_ciphertexts: usize,
     num_proofs: usize,
+    version: ZkpopkVersion,
+    suite: CryptoSuite,
     challenge: Challenge,
     phantom: PhantomData<P>,
 }
 
-pub struct VerificationFailed;
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum VerificationFailed {
+    #[display(fmt = "expected {} proofs", _0)]
+    WrongNumberOfProofs(#[error(not(source))] usize),
+    #[display(fmt = "response {} exceeds the bounds", _0)]
+    OutOfBounds(#[error(not(source))] usize),
+    #[display(fmt = "response {} does not match the commitment", _0)]
+    Mismatch(#[error(not(source))] usize),
+}
 
 impl<P> Verifier<P>
 where
     P: BgvParameters,
 {
     pub fn new(inv_fail_prob: usize, num_ciphertexts: usize, snd_sec: usize) -> Self {
+        let challenge = Challenge::random(rand::thread_rng());
+        Self::with_challenge(inv_fail_prob, num_ciphertexts, snd_sec, challenge)
+    }
+
+    /// Creates a verifier with the given challenge, e.g., for checking a recorded proof.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds of the proof overflow, see `zkpopk::response_bound()`.
+    pub fn with_challenge(
+        inv_fail_prob: usize,
+        num_ciphertexts: usize,
+        snd_sec: usize,
+        challenge: Challenge,
+    ) -> Self {
         let num_proofs = zkpopk::num_proofs::<P>(snd_sec);
-        let mut rng = rand::thread_rng();
-        let challenge = Challenge(rng.gen());
+        assert_bounds_fit::<P>(inv_fail_prob, num_ciphertexts, num_proofs);
         Self {
             inv_fail_prob,
             num_ciphertexts,
             num_proofs,
+            version: ZkpopkVersion::default(),
+            suite: CryptoSuite::default(),
             challenge,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         }
     }
 
+    /// Derives the challenges of the ciphertexts as in the given version, see `ZkpopkVersion`.
+    pub fn with_version(mut self, version: ZkpopkVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Hashes the `Statement` with `suite`, which must be the one of the prover's statement.
+    pub fn with_suite(mut self, suite: CryptoSuite) -> Self {
+        self.suite = suite;
+        self
+    }
+
     pub fn challenge(&self) -> &Challenge {
         &self.challenge
     }
 
     pub async fn verify(
         self,
-        ctx: &CrtContext<P::CiphertextParams>,
+        ctx: &Arc<CrtContext<P::CiphertextParams>>,
         pk: &PublicKey<P>,
         ciphertexts: &[PreCiphertext<P>],
         commitment: Commitment<P>,
-        response: &Response<P>,
+        response: Response<P>,
     ) -> bool {
-        if commitment.0.len() != self.num_proofs {
-            return false;
-        }
-        if response.0.len() != self.num_proofs {
-            return false;
+        self.try_verify(ctx, pk, ciphertexts, commitment, response)
+            .await
+            .is_ok()
+    }
+
+    /// Like `verify()`, but returns which proof failed.  The responses are re-encrypted on
+    /// blocking threads (see `util::run_parallel()`), which stop at the first mismatch.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub async fn try_verify(
+        self,
+        ctx: &Arc<CrtContext<P::CiphertextParams>>,
+        pk: &PublicKey<P>,
+        ciphertexts: &[PreCiphertext<P>],
+        commitment: Commitment<P>,
+        response: Response<P>,
+    ) -> Result<(), VerificationFailed> {
+        if commitment.0.len() != self.num_proofs || response.0.len() != self.num_proofs {
+            return Err(VerificationFailed::WrongNumberOfProofs(self.num_proofs));
         }
 
-        for prepared_plaintext in &response.0 {
+        for (i, witness) in response.0.iter().enumerate() {
             if !check_bounds::<P>(
-                prepared_plaintext,
+                witness,
                 self.inv_fail_prob,
                 self.num_ciphertexts,
                 self.num_proofs,
             ) {
-                return false;
+                return Err(VerificationFailed::OutOfBounds(i));
             }
         }
 
-        let mut prng = ChaCha20Rng::from_seed(self.challenge.0);
-        let mut accumulated = commitment.0;
+        // Accumulate in the slot domain, where slides are cheap.
+        let mut remote_ciphertexts = Vec::with_capacity(ciphertexts.len());
+        for output in ciphertexts {
+            remote_ciphertexts.push(output.ciphertext(ctx).await);
+        }
+        let mut accumulated = Vec::with_capacity(commitment.0.len());
+        for committed in &commitment.0 {
+            accumulated.push(committed.ciphertext(ctx).await);
+        }
+
+        let slided = |poly: &CrtPoly<P::CiphertextParams>, length: usize| {
+            let mut term = CrtPoly::new();
+            term.clone_from_slided(ctx, poly, length);
+            term
+        };
+        let statement = Statement::of_with_suite(self.suite, ciphertexts);
+        let mut prng = challenge_prng(self.version, &self.challenge, &statement);
         for acc in &mut accumulated {
-            for output in ciphertexts {
-                let challenge = prng.gen_range(0..P::PlaintextParams::M);
-                acc.c_0.add_assign_slided(&output.c_0, challenge);
-                acc.c_1.add_assign_slided(&output.c_1, challenge);
-            }
+            let challenges: Vec<_> = (0..remote_ciphertexts.len())
+                .map(|_| prng.gen_range(0..P::PlaintextParams::M))
+                .collect();
+            let terms = remote_ciphertexts.iter().zip(&challenges);
+            acc.c_0.accumulate(
+                terms
+                    .clone()
+                    .map(|(output, challenge)| slided(&output.c_0, *challenge)),
+            );
+            acc.c_1
+                .accumulate(terms.map(|(output, challenge)| slided(&output.c_1, *challenge)));
         }
 
-        let mut ciphertext = PreCiphertext::default();
-        for (prepared_plaintext, acc) in response.0.iter().zip(&accumulated) {
-            prepared_plaintext
-                .encrypt_into(ctx, pk, &mut ciphertext)
-                .await;
-            if &ciphertext != acc {
-                return false;
+        // The proofs are independent, so each worker checks a contiguous range of them.
+        let num_workers = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(self.num_proofs)
+            .max(1);
+        let chunk_size = self.num_proofs.div_ceil(num_workers);
+        let failed = Arc::new(AtomicBool::new(false));
+        let pk = Arc::new(pk.clone());
+        let mut witnesses = response.0.into_iter();
+        let mut accumulated = accumulated.into_iter();
+        let workers = (0..self.num_proofs.div_ceil(chunk_size)).map(|chunk_index| {
+            let witnesses: Vec<_> = witnesses.by_ref().take(chunk_size).collect();
+            let accumulated: Vec<_> = accumulated.by_ref().take(chunk_size).collect();
+            let (ctx, pk, failed) = (Arc::clone(ctx), Arc::clone(&pk), Arc::clone(&failed));
+            async move {
+                let mut pre_ciphertext = PreCiphertext::default();
+                let mut ciphertext = Ciphertext::default();
+                for (i, (witness, acc)) in witnesses.iter().zip(&accumulated).enumerate() {
+                    if failed.load(Ordering::Relaxed) {
+                        return None;
+                    }
+                    witness.encrypt_into(&ctx, &pk, &mut pre_ciphertext).await;
+                    pre_ciphertext.ciphertext_into(&ctx, &mut ciphertext).await;
+                    if &ciphertext != acc {
+                        failed.store(true, Ordering::Relaxed);
+                        return Some(chunk_index * chunk_size + i);
+                    }
+                }
+                None
             }
-        }
+        });
+        let first_mismatch = run_parallel(workers.collect::<Vec<_>>())
+            .await
+            .into_iter()
+            .flatten()
+            .min();
 
-        true
+        match first_mismatch {
+            Some(i) => Err(VerificationFailed::Mismatch(i)),
+            None => Ok(()),
+        }
     }
 }
@@ -1,11 +1,24 @@
 use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
 use async_bincode::AsyncDestination;
+use futures_util::SinkExt;
+use serde::Serialize;
 
 use crate::connection::{Connection, StreamError};
 
+/// A bidirectional channel of bincode-encoded messages.
+///
+/// `SinkExt::send()` on the writer sends each message on its own.  Many small messages of a round,
+/// e.g., challenges and seeds, can instead be queued via `feed()` and sent together on `flush()` at
+/// the end of the round.  For latency-critical channels, `set_nodelay()` makes `feed()` send each
+/// message immediately.
+///
+/// The queued messages are only passed to the writer on `flush()`, since the writer sends a message
+/// as soon as it gets the next one.
 pub struct BiChannel<Message> {
     pub reader: AsyncBincodeReader<quinn::RecvStream, Message>,
     pub writer: AsyncBincodeWriter<quinn::SendStream, Message, AsyncDestination>,
+    queued: Vec<Message>,
+    nodelay: bool,
 }
 
 impl<Message> BiChannel<Message> {
@@ -17,9 +30,20 @@ impl<Message> BiChannel<Message> {
         Ok(BiChannel {
             reader: AsyncBincodeReader::from(rx),
             writer: AsyncBincodeWriter::from(tx).for_async(),
+            queued: Vec::new(),
+            nodelay: false,
         })
     }
 
+    /// Whether `feed()` sends each message immediately instead of queueing it until `flush()`.
+    pub fn set_nodelay(&mut self, nodelay: bool) {
+        self.nodelay = nodelay;
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
     pub fn split(
         &mut self,
     ) -> (
@@ -29,3 +53,385 @@ impl<Message> BiChannel<Message> {
         (&mut self.reader, &mut self.writer)
     }
 }
+
+impl<Message> BiChannel<Message>
+where
+    Message: Serialize,
+{
+    /// Queues `message`, which is sent on the next `flush()` together with the other queued
+    /// messages, or immediately in `nodelay` mode.
+    pub async fn feed(&mut self, message: Message) -> Result<(), bincode::Error> {
+        self.queued.push(message);
+        if self.nodelay {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Sends the queued messages, e.g., at the end of a round.
+    pub async fn flush(&mut self) -> Result<(), bincode::Error> {
+        for message in self.queued.drain(..) {
+            self.writer.feed(message).await?;
+        }
+        self.writer.flush().await
+    }
+}
+
+/// The channel type used for bulk transfers of ciphertexts, which is a `CheckedBiChannel` if the
+/// `checksums` feature is enabled.
+#[cfg(feature = "checksums")]
+pub type BulkChannel<Message> = CheckedBiChannel<Message>;
+
+/// The channel type used for bulk transfers of ciphertexts, which is a `CheckedBiChannel` if the
+/// `checksums` feature is enabled.
+#[cfg(not(feature = "checksums"))]
+pub type BulkChannel<Message> = BiChannel<Message>;
+
+#[cfg(feature = "checksums")]
+pub use self::checked::{CheckedBiChannel, CheckedReader, CheckedWriter, ChecksumError};
+
+#[cfg(feature = "checksums")]
+mod checked {
+    use std::collections::VecDeque;
+    use std::marker::PhantomData;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
+    use async_bincode::AsyncDestination;
+    use futures_util::sink::Sink;
+    use futures_util::{ready, SinkExt, Stream, StreamExt};
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
+
+    use crate::connection::{Connection, StreamError};
+
+    #[derive(Deserialize, Serialize)]
+    struct Frame {
+        payload: Vec<u8>,
+        /// Number of payloads that the sender has received so far.
+        received: u64,
+        /// Digest of the transcript of these payloads.
+        digest: [u8; 32],
+    }
+
+    #[derive(Debug, derive_more::Display, derive_more::Error)]
+    pub enum ChecksumError {
+        FailedToReceive(bincode::ErrorKind),
+        FailedToDeserialize(bincode::ErrorKind),
+        /// The remote party received a different transcript than we sent.
+        DigestMismatch,
+    }
+
+    /// Like `BiChannel`, but each message carries a BLAKE3 digest of the transcript that the
+    /// sender has received so far, which the receiver compares with the transcript that it sent.
+    /// This detects divergence of the parties' views of the channel at the next message in the
+    /// opposite direction.
+    pub struct CheckedBiChannel<Message> {
+        pub reader: CheckedReader<Message>,
+        pub writer: CheckedWriter<Message>,
+        queued: Vec<Message>,
+        nodelay: bool,
+    }
+
+    pub struct CheckedReader<Message> {
+        inner: AsyncBincodeReader<quinn::RecvStream, Frame>,
+        transcripts: Arc<Mutex<Transcripts>>,
+        phantom: PhantomData<fn() -> Message>,
+    }
+
+    pub struct CheckedWriter<Message> {
+        inner: AsyncBincodeWriter<quinn::SendStream, Frame, AsyncDestination>,
+        transcripts: Arc<Mutex<Transcripts>>,
+        phantom: PhantomData<fn(Message)>,
+    }
+
+    /// This party's view of the channel, shared by the reader and the writer.
+    struct Transcripts {
+        sent: blake3::Hasher,
+        /// Digests of `sent` after `confirmed`, `confirmed + 1`, ... payloads.  The digests before
+        /// `confirmed` payloads are dropped once the remote party reports to have received them.
+        sent_digests: VecDeque<[u8; 32]>,
+        confirmed: u64,
+        received: blake3::Hasher,
+        num_received: u64,
+    }
+
+    impl Transcripts {
+        fn new() -> Self {
+            let sent = blake3::Hasher::new();
+            Self {
+                sent_digests: VecDeque::from([*sent.finalize().as_bytes()]),
+                sent,
+                confirmed: 0,
+                received: blake3::Hasher::new(),
+                num_received: 0,
+            }
+        }
+
+        fn record_sent(&mut self, payload: &[u8]) {
+            let digest = absorb(&mut self.sent, payload);
+            self.sent_digests.push_back(digest);
+        }
+
+        /// Checks the remote party's view of the payloads that we sent, and appends `payload` to
+        /// the transcript of the received payloads.
+        fn record_received(&mut self, frame: &Frame) -> Result<(), ChecksumError> {
+            let index = frame
+                .received
+                .checked_sub(self.confirmed)
+                .ok_or(ChecksumError::DigestMismatch)?;
+            match self.sent_digests.get(index as usize) {
+                Some(digest) if *digest == frame.digest => {}
+                _ => return Err(ChecksumError::DigestMismatch),
+            }
+            self.sent_digests.drain(..index as usize);
+            self.confirmed = frame.received;
+            absorb(&mut self.received, &frame.payload);
+            self.num_received += 1;
+            Ok(())
+        }
+    }
+
+    impl<Message> CheckedBiChannel<Message> {
+        pub async fn open(
+            conn: &mut Connection,
+            name: &str,
+        ) -> Result<CheckedBiChannel<Message>, StreamError> {
+            let (tx, rx) = conn.open_bi(name).await?;
+            let transcripts = Arc::new(Mutex::new(Transcripts::new()));
+            Ok(CheckedBiChannel {
+                reader: CheckedReader {
+                    inner: AsyncBincodeReader::from(rx),
+                    transcripts: Arc::clone(&transcripts),
+                    phantom: PhantomData,
+                },
+                writer: CheckedWriter {
+                    inner: AsyncBincodeWriter::from(tx).for_async(),
+                    transcripts,
+                    phantom: PhantomData,
+                },
+                queued: Vec::new(),
+                nodelay: false,
+            })
+        }
+
+        pub fn split(&mut self) -> (&mut CheckedReader<Message>, &mut CheckedWriter<Message>) {
+            (&mut self.reader, &mut self.writer)
+        }
+
+        /// See `BiChannel::set_nodelay()`.
+        pub fn set_nodelay(&mut self, nodelay: bool) {
+            self.nodelay = nodelay;
+        }
+
+        pub fn nodelay(&self) -> bool {
+            self.nodelay
+        }
+    }
+
+    impl<Message> CheckedBiChannel<Message>
+    where
+        Message: Serialize,
+    {
+        /// See `BiChannel::feed()`.
+        pub async fn feed(&mut self, message: Message) -> Result<(), bincode::Error> {
+            self.queued.push(message);
+            if self.nodelay {
+                self.flush().await?;
+            }
+            Ok(())
+        }
+
+        /// See `BiChannel::flush()`.
+        pub async fn flush(&mut self) -> Result<(), bincode::Error> {
+            for message in self.queued.drain(..) {
+                self.writer.feed(message).await?;
+            }
+            self.writer.flush().await
+        }
+    }
+
+    /// Appends `payload` to the transcript and returns the digest of the transcript.
+    fn absorb(transcript: &mut blake3::Hasher, payload: &[u8]) -> [u8; 32] {
+        transcript.update(&(payload.len() as u64).to_le_bytes());
+        transcript.update(payload);
+        *transcript.finalize().as_bytes()
+    }
+
+    impl<Message> Stream for CheckedReader<Message>
+    where
+        Message: DeserializeOwned,
+    {
+        type Item = Result<Message, ChecksumError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = &mut *self;
+            let frame = match ready!(this.inner.poll_next_unpin(cx)) {
+                None => return Poll::Ready(None),
+                Some(Err(e)) => return Poll::Ready(Some(Err(ChecksumError::FailedToReceive(*e)))),
+                Some(Ok(frame)) => frame,
+            };
+            if let Err(e) = this.transcripts.lock().unwrap().record_received(&frame) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            Poll::Ready(Some(
+                bincode::deserialize(&frame.payload)
+                    .map_err(|e| ChecksumError::FailedToDeserialize(*e)),
+            ))
+        }
+    }
+
+    impl<Message> Sink<Message> for CheckedWriter<Message>
+    where
+        Message: Serialize,
+    {
+        type Error = bincode::Error;
+
+        fn poll_ready(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready_unpin(cx)
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+            let this = &mut *self;
+            let payload = bincode::serialize(&item)?;
+            let mut transcripts = this.transcripts.lock().unwrap();
+            transcripts.record_sent(&payload);
+            let frame = Frame {
+                received: transcripts.num_received,
+                digest: *transcripts.received.finalize().as_bytes(),
+                payload,
+            };
+            drop(transcripts);
+            this.inner.start_send_unpin(frame)
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_flush_unpin(cx)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_close_unpin(cx)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use futures_util::{SinkExt, StreamExt};
+
+        use crate::connection::Connection;
+
+        use super::{CheckedBiChannel, ChecksumError, Frame, Transcripts};
+
+        fn send(transcripts: &mut Transcripts, payload: &[u8]) -> Frame {
+            transcripts.record_sent(payload);
+            Frame {
+                payload: payload.to_vec(),
+                received: transcripts.num_received,
+                digest: *transcripts.received.finalize().as_bytes(),
+            }
+        }
+
+        #[test]
+        fn detects_divergence() {
+            let (mut t0, mut t1) = (Transcripts::new(), Transcripts::new());
+
+            // Messages in flight are confirmed later.
+            let frame0 = send(&mut t0, b"zero");
+            let frame1 = send(&mut t0, b"one");
+            t1.record_received(&frame0).unwrap();
+            t0.record_received(&send(&mut t1, b"two")).unwrap();
+            t1.record_received(&frame1).unwrap();
+            t0.record_received(&send(&mut t1, b"three")).unwrap();
+
+            // Party 1 receives something else than party 0 sent, which party 0 notices on the
+            // next message from party 1.
+            let mut frame = send(&mut t0, b"four");
+            frame.payload = b"five".to_vec();
+            t1.record_received(&frame).unwrap();
+            assert!(matches!(
+                t0.record_received(&send(&mut t1, b"six")),
+                Err(ChecksumError::DigestMismatch)
+            ));
+        }
+
+        #[tokio::test]
+        async fn checked_exchange() {
+            const P0_ADDR: &str = "[::1]:50067";
+            const P1_ADDR: &str = "[::1]:50068";
+
+            let (conn0, conn1) = tokio::join!(
+                Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+                Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+            );
+            let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+            let (ch0, ch1) = tokio::join!(
+                CheckedBiChannel::<Vec<u64>>::open(&mut conn0, "test:checked"),
+                CheckedBiChannel::<Vec<u64>>::open(&mut conn1, "test:checked")
+            );
+            let (mut ch0, mut ch1) = (ch0.unwrap(), ch1.unwrap());
+
+            for i in 0..4 {
+                let message = vec![i; 1000];
+                ch0.writer.send(message.clone()).await.unwrap();
+                assert_eq!(ch1.reader.next().await.unwrap().unwrap(), message);
+                ch1.writer.send(message.clone()).await.unwrap();
+                assert_eq!(ch0.reader.next().await.unwrap().unwrap(), message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use crate::connection::Connection;
+
+    use super::BiChannel;
+
+    #[tokio::test]
+    async fn feed_and_flush() {
+        const P0_ADDR: &str = "[::1]:50101";
+        const P1_ADDR: &str = "[::1]:50102";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (ch0, ch1) = tokio::join!(
+            BiChannel::<u64>::open(&mut conn0, "test:feed"),
+            BiChannel::<u64>::open(&mut conn1, "test:feed")
+        );
+        let (mut ch0, mut ch1) = (ch0.unwrap(), ch1.unwrap());
+
+        // Queued messages are only sent on flush.
+        for i in 0..16 {
+            ch0.feed(i).await.unwrap();
+        }
+        let early = tokio::time::timeout(Duration::from_millis(100), ch1.reader.next()).await;
+        assert!(early.is_err());
+        ch0.flush().await.unwrap();
+        for i in 0..16 {
+            assert_eq!(ch1.reader.next().await.unwrap().unwrap(), i);
+        }
+
+        // In nodelay mode, each message is sent immediately.
+        ch1.set_nodelay(true);
+        ch1.feed(42).await.unwrap();
+        assert_eq!(ch0.reader.next().await.unwrap().unwrap(), 42);
+    }
+}
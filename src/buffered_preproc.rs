@@ -1,15 +1,347 @@
 use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
-use log::warn;
-use tokio::sync::{oneshot, Mutex, Semaphore};
+use futures_util::{SinkExt, StreamExt};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, watch, Mutex, Semaphore};
 
 use crate::{
     bgv::residue::native::GenericNativeResidue,
-    interface::{BatchedPreprocessor, BeaverTriple, Preprocessor},
+    bi_channel::BiChannel,
+    connection::{Connection, StreamError},
+    interface::{BatchedPreprocessor, BeaverTriple, MacKeyShare, Preprocessor, TripleOrigin},
+    role::Role,
+    transcript::SessionId,
 };
 
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Decides when the producer of a `BufferedPreprocessor` may start the next batch.
+///
+/// Since the batches are generated interactively, both parties should use the same schedule.
+/// Otherwise, the party that starts a batch waits for the other one.
+pub trait Schedule: Send + 'static {
+    /// How long to wait until the next batch may start, where `Duration::ZERO` starts it now.  The
+    /// producer calls this again after waiting, so the result may be an estimate.
+    fn delay(&mut self, now: SystemTime) -> Duration;
+
+    /// Called when a batch has been completed.
+    fn batch_completed(&mut self, _now: SystemTime) {}
+}
+
+impl<F> Schedule for F
+where
+    F: FnMut(SystemTime) -> Duration + Send + 'static,
+{
+    fn delay(&mut self, now: SystemTime) -> Duration {
+        self(now)
+    }
+}
+
+/// Starts batches only within daily time windows (in UTC) and not earlier than a cooldown after
+/// the previous batch.  A batch that is started within a window is completed even if the window
+/// ends in the meantime.
+#[derive(Clone, Debug)]
+pub struct Windows {
+    /// Ranges of seconds since midnight.  A range with `start > end` wraps around midnight.
+    windows: Vec<Range<u32>>,
+    cooldown: Duration,
+    last_completed: Option<SystemTime>,
+}
+
+impl Windows {
+    /// Allows starting batches at any time of the day.
+    pub fn always() -> Self {
+        let day = 0..SECS_PER_DAY as u32;
+        Self::daily(vec![day])
+    }
+
+    /// Allows starting batches within `windows`, which are ranges of seconds since midnight (UTC).
+    /// A window with `start > end` wraps around midnight, e.g., `22 * 3600..6 * 3600`.
+    pub fn daily(windows: Vec<Range<u32>>) -> Self {
+        assert!(
+            windows
+                .iter()
+                .all(|window| window.start < SECS_PER_DAY as u32
+                    && window.end <= SECS_PER_DAY as u32),
+            "windows must be within a day"
+        );
+        Self {
+            windows,
+            cooldown: Duration::ZERO,
+            last_completed: None,
+        }
+    }
+
+    /// Waits at least `cooldown` between the completion of a batch and the start of the next one.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    fn contains(window: &Range<u32>, secs: u32) -> bool {
+        if window.start <= window.end {
+            window.contains(&secs)
+        } else {
+            secs >= window.start || secs < window.end
+        }
+    }
+}
+
+impl Schedule for Windows {
+    fn delay(&mut self, now: SystemTime) -> Duration {
+        if let Some(last_completed) = self.last_completed {
+            let elapsed = now.duration_since(last_completed).unwrap_or_default();
+            if elapsed < self.cooldown {
+                return self.cooldown - elapsed;
+            }
+        }
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = (since_epoch.as_secs() % SECS_PER_DAY) as u32;
+        if self
+            .windows
+            .iter()
+            .any(|window| window.start != window.end && Self::contains(window, secs))
+        {
+            return Duration::ZERO;
+        }
+        // Wait until the start of the next window, minus the fraction of the current second.
+        self.windows
+            .iter()
+            .filter(|window| window.start != window.end)
+            .map(|window| {
+                let secs_until = (window.start as u64 + SECS_PER_DAY - secs as u64) % SECS_PER_DAY;
+                Duration::from_secs(secs_until)
+                    .saturating_sub(Duration::from_nanos(since_epoch.subsec_nanos() as u64))
+            })
+            .min()
+            // Without any (non-empty) window, check again in a day.
+            .unwrap_or(Duration::from_secs(SECS_PER_DAY))
+    }
+
+    fn batch_completed(&mut self, now: SystemTime) {
+        self.last_completed = Some(now);
+    }
+}
+
+/// The triples of a `BufferedPreprocessor` that were generated but not yet consumed, e.g., for
+/// persisting them across restarts.  Both parties must restore their inventories of the same point
+/// in time, since the triples are only consistent together, see `BufferedPreprocessor::restore()`.
+///
+/// The triples are only valid under the MAC key of the session that generated them, so the
+/// inventory contains a digest of this party's share of the MAC key (see `MacKeyShare::digest()`),
+/// but not the share itself.  The triples are secret shares, so the inventory must be stored
+/// securely.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(deserialize = ""))]
+pub struct Inventory<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    pub triples: Vec<BeaverTriple<KS, K, PID>>,
+    /// Number of batches that were completed so far.
+    pub batches: u64,
+    /// Digest of this party's share of the MAC key of the triples, see
+    /// `TripleOrigin::mac_key_share()`.
+    pub mac_key_digest: [u8; 32],
+    /// The session of the preprocessor that took the snapshot.
+    pub session_id: SessionId,
+}
+
+/// A second, small-batch source of triples for a `BufferedPreprocessor`, see `with_express()`.
+///
+/// While a large batch of the main preprocessor is in flight, a small request would wait for it.
+/// The express lane serves such requests instead, e.g., with a `LowGearPreprocessor` of toy-sized
+/// amortization on a forked connection.  A request of at most `threshold` triples is served by the
+/// express lane only if the main lane has fewer triples buffered, i.e., if it would wait.  Since
+/// the main lanes of both parties may complete a batch at slightly different times, party 0
+/// decides and tells party 1, so that both take the triples from the same lane.
+pub struct ExpressLane<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    inner: Box<dyn Lane<KS, K, PID>>,
+    ch_hello: BiChannel<ExpressHello>,
+    /// Whether the express lane serves the current small request, sent by party 0.
+    ch_decision: BiChannel<bool>,
+    threshold: usize,
+    served: u64,
+}
+
+/// Sent by both parties in `BufferedPreprocessor::with_express()`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+struct ExpressHello {
+    threshold: u64,
+    mac_key_matches: bool,
+}
+
+impl<KS, K, const PID: usize> ExpressLane<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    /// Serves requests of at most `threshold` triples from `inner`.  `inner` must use the MAC key
+    /// share of the main preprocessor, e.g., by building it with
+    /// `LowGearPreprocessorBuilder::mac_key()`, so that the triples of both lanes can be mixed.
+    /// `conn` must not be used by `inner` or the main preprocessor.
+    pub async fn new<Preproc>(
+        conn: &mut Connection,
+        inner: Preproc,
+        threshold: usize,
+    ) -> Result<Self, StreamError>
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID> + TripleOrigin<KS> + Send + 'static,
+    {
+        Ok(Self {
+            inner: Box::new(CachedPreprocessor::new(inner)),
+            ch_hello: BiChannel::open(conn, "ExpressLane:hello").await?,
+            ch_decision: BiChannel::open(conn, "ExpressLane:decision").await?,
+            threshold,
+            served: 0,
+        })
+    }
+
+    /// Returns whether the express lane serves a small request, where party 0 decides by
+    /// `main_short` and party 1 follows.  Party 1 falls back to the main lane if the decision
+    /// cannot be received.
+    async fn decide(&mut self, main_short: bool) -> bool {
+        if Role::of::<PID>().is_p0() {
+            if let Err(e) = self.ch_decision.writer.send(main_short).await {
+                warn!("ExpressLane: failed to send the decision: {}", e);
+            }
+            return main_short;
+        }
+        match self.ch_decision.reader.next().await {
+            Some(Ok(express)) => express,
+            Some(Err(e)) => {
+                warn!("ExpressLane: failed to receive the decision: {}", e);
+                false
+            }
+            None => {
+                warn!("ExpressLane: party 0 closed the decision stream");
+                false
+            }
+        }
+    }
+
+    async fn finish(mut self) {
+        let _ = self.ch_hello.writer.get_mut().finish().await;
+        let _ = self.ch_decision.writer.get_mut().finish().await;
+        self.inner.finish().await;
+    }
+}
+
+/// Object-safe part of `Preprocessor`, so that `ExpressLane` does not depend on the type of its
+/// preprocessor.
+#[async_trait]
+trait Lane<KS, K, const PID: usize>: Send
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    async fn get_beaver_triples(&mut self, n: usize) -> Vec<BeaverTriple<KS, K, PID>>;
+
+    fn mac_key_share(&self) -> KS;
+
+    async fn finish(self: Box<Self>);
+}
+
+#[async_trait]
+impl<Preproc, KS, K, const PID: usize> Lane<KS, K, PID> for CachedPreprocessor<Preproc, KS, K, PID>
+where
+    Preproc: BatchedPreprocessor<KS, K, PID> + TripleOrigin<KS> + Send + 'static,
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    async fn get_beaver_triples(&mut self, n: usize) -> Vec<BeaverTriple<KS, K, PID>> {
+        Preprocessor::get_beaver_triples(self, n).await
+    }
+
+    fn mac_key_share(&self) -> KS {
+        self.inner.mac_key_share()
+    }
+
+    async fn finish(self: Box<Self>) {
+        Preprocessor::finish(*self).await;
+    }
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum SetupError {
+    FailedToOpen(StreamError),
+    FailedToExchange(bincode::ErrorKind),
+    ConnectionClosed,
+    /// The inventory was taken with another MAC key share than the one of the preprocessor.
+    MacKeyMismatch,
+    /// The other party restored an inventory of another session or number of batches, or one
+    /// that does not match its MAC key share.
+    InventoryMismatch,
+    /// The express lane of the other party has another threshold or MAC key share than its main
+    /// preprocessor.
+    ExpressMismatch,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+enum Control {
+    Run,
+    Pause,
+    Stop,
+}
+
+/// Messages between the producers of both parties.  Both parties tell each other when their
+/// `Control` changes, and party 0 decides when the next batch starts.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+enum Signal {
+    /// Sent by both parties when they are set up, see `BufferedPreprocessor::restore()`.
+    Hello {
+        session_id: SessionId,
+        batches: u64,
+        mac_key_matches: bool,
+    },
+    Control(Control),
+    /// Party 0 starts the next batch.
+    Start,
+    /// Party 0 waits at a batch boundary because a party paused.  `controls` is the number of
+    /// `Control`s that party 0 received so far, so party 1 can tell whether its latest one was
+    /// taken into account.
+    Idle {
+        batches: u64,
+        controls: u64,
+    },
+}
+
+/// What the producer knows about both parties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Status {
+    /// The `Control` of the other party.
+    remote: Control,
+    /// Whether the producers of both parties wait at the same batch boundary because a party
+    /// paused.
+    idle: bool,
+}
+
+/// The MAC key and session of the triples of a `BufferedPreprocessor`, see `TripleOrigin`.
+#[derive(Clone, Copy)]
+struct Origin {
+    mac_key_digest: [u8; 32],
+    session_id: SessionId,
+}
+
+/// Returns the digest of a share of `TripleOrigin::mac_key_share()`, which is zeroized afterwards.
+fn mac_key_digest<KS>(mac_key: KS) -> [u8; 32]
+where
+    KS: GenericNativeResidue,
+{
+    MacKeyShare::from_secret(mac_key).digest()
+}
+
 pub struct BufferedPreprocessor<KS, K, const PID: usize>
 where
     KS: GenericNativeResidue,
@@ -18,7 +350,13 @@ where
     queue: Arc<Mutex<VecDeque<BeaverTriple<KS, K, PID>>>>,
     producer_sem: Arc<Semaphore>,
     consumer_sem: Arc<Semaphore>,
+    control: watch::Sender<Control>,
+    status: watch::Receiver<Status>,
+    batches: Arc<AtomicU64>,
+    /// Unknown if the preprocessor was created by `new()`.
+    origin: Option<Origin>,
     terminated_rx: Option<oneshot::Receiver<()>>,
+    express: Option<ExpressLane<KS, K, PID>>,
 }
 
 impl<KS, K, const PID: usize> BufferedPreprocessor<KS, K, PID>
@@ -26,27 +364,266 @@ where
     KS: GenericNativeResidue,
     K: GenericNativeResidue,
 {
+    /// Generates batches of `inner` in the background, such that about `budget` triples are
+    /// buffered.
+    ///
+    /// The producers of both parties do not coordinate, so `pause()` only pauses this party, and
+    /// there is no `inventory()` to restore.  Use `with_connection()` for these.
     pub fn new<Preproc>(inner: Preproc, budget: usize) -> Self
     where
         Preproc: BatchedPreprocessor<KS, K, PID> + Send + 'static,
     {
-        let queue = Arc::default();
-        let producer_sem = Arc::new(Semaphore::new(budget + Preproc::BATCH_SIZE)); // Target number of triples
-        let consumer_sem = Arc::new(Semaphore::new(0)); // Initial number of triples
+        Self::spawn(inner, budget, Windows::always(), Vec::new(), 0, None, None)
+    }
+
+    /// Like `new()`, but the producers of both parties coordinate on `conn`, which must not be
+    /// used by `inner`.
+    pub async fn with_connection<Preproc>(
+        conn: &mut Connection,
+        inner: Preproc,
+        budget: usize,
+    ) -> Result<Self, SetupError>
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID> + TripleOrigin<KS> + Send + 'static,
+    {
+        Self::with_schedule(conn, inner, budget, Windows::always()).await
+    }
+
+    /// Like `with_connection()`, but party 0 starts a batch only when `schedule` allows it.  Party
+    /// 1 follows the decisions of party 0, so its schedule is not used.
+    pub async fn with_schedule<Preproc>(
+        conn: &mut Connection,
+        inner: Preproc,
+        budget: usize,
+        schedule: impl Schedule,
+    ) -> Result<Self, SetupError>
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID> + TripleOrigin<KS> + Send + 'static,
+    {
+        let inventory = Inventory {
+            triples: Vec::new(),
+            batches: 0,
+            mac_key_digest: mac_key_digest(inner.mac_key_share()),
+            session_id: inner.session_id(),
+        };
+        Self::restore(conn, inner, budget, schedule, inventory).await
+    }
+
+    /// Like `with_schedule()`, but starts with the triples of `inventory`, see `inventory()`.
+    ///
+    /// Fails if the MAC key share of `inner` differs from the one of `inventory`, or if the other
+    /// party restores an inventory of another session or number of batches.
+    pub async fn restore<Preproc>(
+        conn: &mut Connection,
+        inner: Preproc,
+        budget: usize,
+        schedule: impl Schedule,
+        inventory: Inventory<KS, K, PID>,
+    ) -> Result<Self, SetupError>
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID> + TripleOrigin<KS> + Send + 'static,
+    {
+        let mut ch_signal = BiChannel::open(conn, "BufferedPreprocessor:signal")
+            .await
+            .map_err(SetupError::FailedToOpen)?;
+        // Both parties check the inventories, so that they fail together.
+        let hello = Signal::Hello {
+            session_id: inventory.session_id,
+            batches: inventory.batches,
+            mac_key_matches: inventory.mac_key_digest == mac_key_digest(inner.mac_key_share()),
+        };
+        let (rx, tx) = ch_signal.split();
+        let (sent, received) = tokio::join!(tx.send(hello), rx.next());
+        sent.map_err(|e| SetupError::FailedToExchange(*e))?;
+        let remote_hello = received
+            .ok_or(SetupError::ConnectionClosed)?
+            .map_err(|e| SetupError::FailedToExchange(*e))?;
+        if let Signal::Hello {
+            mac_key_matches: false,
+            ..
+        } = hello
+        {
+            return Err(SetupError::MacKeyMismatch);
+        }
+        if remote_hello != hello {
+            return Err(SetupError::InventoryMismatch);
+        }
+
+        let origin = Origin {
+            mac_key_digest: inventory.mac_key_digest,
+            session_id: inner.session_id(),
+        };
+        Ok(Self::spawn(
+            inner,
+            budget,
+            schedule,
+            inventory.triples,
+            inventory.batches,
+            Some(ch_signal),
+            Some(origin),
+        ))
+    }
+
+    /// Spawns the producer, which coordinates with the other party on `ch_signal` if given.
+    fn spawn<Preproc>(
+        inner: Preproc,
+        budget: usize,
+        schedule: impl Schedule,
+        triples: Vec<BeaverTriple<KS, K, PID>>,
+        batches: u64,
+        ch_signal: Option<BiChannel<Signal>>,
+        origin: Option<Origin>,
+    ) -> Self
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID> + Send + 'static,
+    {
+        let num_triples = triples.len();
+        let queue = Arc::new(Mutex::new(VecDeque::from(triples)));
+        let producer_sem = Arc::new(Semaphore::new(
+            (budget + Preproc::BATCH_SIZE).saturating_sub(num_triples),
+        )); // Target number of triples
+        let consumer_sem = Arc::new(Semaphore::new(num_triples)); // Initial number of triples
+        let (control, control_rx) = watch::channel(Control::Run);
+        let (status_tx, status) = watch::channel(Status {
+            remote: Control::Run,
+            idle: false,
+        });
+        let batches = Arc::new(AtomicU64::new(batches));
         let (terminated_tx, terminated_rx) = oneshot::channel();
         let preproc = Self {
             queue: Arc::clone(&queue),
             producer_sem: Arc::clone(&producer_sem),
             consumer_sem: Arc::clone(&consumer_sem),
+            control,
+            status,
+            batches: Arc::clone(&batches),
+            origin,
             terminated_rx: Some(terminated_rx),
+            express: None,
         };
 
         tokio::task::spawn(async move {
-            produce(inner, &queue, &producer_sem, &consumer_sem, terminated_tx).await;
+            let producer = Producer {
+                queue,
+                producer_sem,
+                consumer_sem,
+                control_rx,
+                batches,
+                ch_signal,
+                status: status_tx,
+                sent: Control::Run,
+                sent_controls: 0,
+                received_controls: 0,
+            };
+            producer.produce(inner, schedule, terminated_tx).await;
         });
 
         preproc
     }
+
+    /// Serves small requests from `express` while the main lane has too few triples, see
+    /// `ExpressLane`.  Both parties must configure an express lane with the same threshold.
+    ///
+    /// Fails if the express lane uses another MAC key share than this preprocessor, or if the
+    /// other party's express lane does not match.  The MAC key share of a preprocessor that was
+    /// created by `new()` is unknown, so it fails as well.
+    pub async fn with_express(
+        mut self,
+        mut express: ExpressLane<KS, K, PID>,
+    ) -> Result<Self, SetupError> {
+        let digest = mac_key_digest(express.inner.mac_key_share());
+        // Both parties check their express lanes, so that they fail together.
+        let hello = ExpressHello {
+            threshold: express.threshold as u64,
+            mac_key_matches: self
+                .origin
+                .is_some_and(|origin| origin.mac_key_digest == digest),
+        };
+        let (rx, tx) = express.ch_hello.split();
+        let (sent, received) = tokio::join!(tx.send(hello), rx.next());
+        sent.map_err(|e| SetupError::FailedToExchange(*e))?;
+        let remote_hello = received
+            .ok_or(SetupError::ConnectionClosed)?
+            .map_err(|e| SetupError::FailedToExchange(*e))?;
+        if !hello.mac_key_matches {
+            return Err(SetupError::MacKeyMismatch);
+        }
+        if remote_hello != hello {
+            return Err(SetupError::ExpressMismatch);
+        }
+        self.express = Some(express);
+        Ok(self)
+    }
+
+    /// Number of requests that the express lane served so far.
+    pub fn express_served(&self) -> u64 {
+        self.express.as_ref().map_or(0, |express| express.served)
+    }
+
+    /// Stops starting new batches on both parties.  The producers of both parties stop at the same
+    /// batch boundary, after the batch that is currently generated, see `wait_paused()`.  The
+    /// buffered triples can still be consumed.
+    pub fn pause(&self) {
+        self.control.send_if_modified(|control| {
+            let modified = *control == Control::Run;
+            if modified {
+                *control = Control::Pause;
+            }
+            modified
+        });
+    }
+
+    /// Undoes `pause()`.  Batches are started again once neither party is paused.
+    pub fn resume(&self) {
+        self.control.send_if_modified(|control| {
+            let modified = *control == Control::Pause;
+            if modified {
+                *control = Control::Run;
+            }
+            modified
+        });
+    }
+
+    /// Whether this party or the other party paused.
+    pub fn is_paused(&self) -> bool {
+        *self.control.borrow() == Control::Pause || self.status.borrow().remote == Control::Pause
+    }
+
+    /// Waits until the producers of both parties stopped at the same batch boundary because a
+    /// party paused, and returns the number of batches that were completed.  Until a party
+    /// resumes, `inventory()` is then consistent with the one of the other party.
+    pub async fn wait_paused(&self) -> u64 {
+        let mut status = self.status.clone();
+        // A terminated producer does not start batches anymore either.
+        while !status.borrow_and_update().idle && status.changed().await.is_ok() {}
+        self.batches()
+    }
+
+    /// Number of triples that can be obtained without waiting for another batch.
+    pub fn buffered(&self) -> usize {
+        self.consumer_sem.available_permits()
+    }
+
+    /// Snapshot of the buffered triples.  To persist them consistently, `pause()` one party and
+    /// take the snapshots after `wait_paused()` on both parties.
+    ///
+    /// Returns `None` if the preprocessor was created by `new()`, since the origin of its triples
+    /// is unknown.
+    pub async fn inventory(&self) -> Option<Inventory<KS, K, PID>> {
+        let origin = self.origin?;
+        let queue = self.queue.lock().await;
+        Some(Inventory {
+            triples: queue.iter().cloned().collect(),
+            batches: self.batches(),
+            mac_key_digest: origin.mac_key_digest,
+            session_id: origin.session_id,
+        })
+    }
+
+    /// Number of batches that were completed so far, including the ones of a restored inventory.
+    pub fn batches(&self) -> u64 {
+        self.batches.load(Ordering::SeqCst)
+    }
 }
 
 impl<KS, K, const PID: usize> Drop for BufferedPreprocessor<KS, K, PID>
@@ -55,38 +632,242 @@ where
     K: GenericNativeResidue,
 {
     fn drop(&mut self) {
-        if let Some(_) = self.terminated_rx {
+        if self.terminated_rx.is_some() {
             warn!("BufferedPreprocessor dropped without calling finish()");
+            self.control.send_replace(Control::Stop);
             self.producer_sem.close();
         }
     }
 }
 
-async fn produce<KS, K, Preproc, const PID: usize>(
-    mut inner: Preproc,
-    queue: &Mutex<VecDeque<BeaverTriple<KS, K, PID>>>,
-    producer_sem: &Semaphore,
-    consumer_sem: &Semaphore,
-    terminated_tx: oneshot::Sender<()>,
-) where
+struct Producer<KS, K, const PID: usize>
+where
     KS: GenericNativeResidue,
     K: GenericNativeResidue,
-    Preproc: BatchedPreprocessor<KS, K, PID>,
 {
-    loop {
-        if let Ok(permit) = producer_sem.acquire_many(Preproc::BATCH_SIZE as u32).await {
-            permit.forget();
-        } else {
-            // TODO: Synchronize producer termination with the remote party.
-            inner.finish().await;
-            let _ = terminated_tx.send(());
-            return;
+    queue: Arc<Mutex<VecDeque<BeaverTriple<KS, K, PID>>>>,
+    producer_sem: Arc<Semaphore>,
+    consumer_sem: Arc<Semaphore>,
+    control_rx: watch::Receiver<Control>,
+    batches: Arc<AtomicU64>,
+    /// `None` if the producers of both parties do not coordinate, see
+    /// `BufferedPreprocessor::new()`.
+    ch_signal: Option<BiChannel<Signal>>,
+    status: watch::Sender<Status>,
+    /// The last `Control` that was sent to the other party.
+    sent: Control,
+    sent_controls: u64,
+    received_controls: u64,
+}
+
+impl<KS, K, const PID: usize> Producer<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    async fn produce<Preproc>(
+        mut self,
+        mut inner: Preproc,
+        mut schedule: impl Schedule,
+        terminated_tx: oneshot::Sender<()>,
+    ) where
+        Preproc: BatchedPreprocessor<KS, K, PID>,
+    {
+        loop {
+            // Without coordination, every party leads its own producer.
+            let start = if self.ch_signal.is_none() || Role::of::<PID>().is_p0() {
+                self.lead::<Preproc>(&mut schedule).await
+            } else {
+                self.follow::<Preproc>().await
+            };
+            if !start {
+                break;
+            }
+
+            let triples = inner.get_beaver_triples().await;
+            self.queue.lock().await.extend(triples);
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            schedule.batch_completed(SystemTime::now());
+
+            self.consumer_sem.add_permits(Preproc::BATCH_SIZE);
+        }
+
+        // The other party may have stopped first, but then it waits for this party to stop.
+        if self.sent != Control::Stop {
+            self.send(Signal::Control(Control::Stop)).await;
+        }
+        if let Some(ch_signal) = &mut self.ch_signal {
+            let _ = ch_signal.writer.get_mut().finish().await;
+        }
+        inner.finish().await;
+        let _ = terminated_tx.send(());
+    }
+
+    /// Party 0 waits until the next batch may start and tells party 1.  Returns `false` if a party
+    /// stopped.
+    async fn lead<Preproc>(&mut self, schedule: &mut impl Schedule) -> bool
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID>,
+    {
+        let mut acquired = false;
+        loop {
+            let local = self.sync_control().await;
+            let Status { remote, idle } = *self.status.borrow();
+            if local == Control::Stop || remote == Control::Stop {
+                return false;
+            }
+            let paused = local == Control::Pause || remote == Control::Pause;
+            if paused && !idle {
+                let batches = self.batches.load(Ordering::SeqCst);
+                info!("BufferedPreprocessor: paused after {} batches", batches);
+                self.send(Signal::Idle {
+                    batches,
+                    controls: self.received_controls,
+                })
+                .await;
+                self.status.send_modify(|status| status.idle = true);
+            }
+
+            let delay = (!paused && acquired).then(|| schedule.delay(SystemTime::now()));
+            if delay == Some(Duration::ZERO) {
+                return self.send(Signal::Start).await;
+            }
+            if let Some(delay) = delay {
+                info!("BufferedPreprocessor: next batch in {:?}", delay);
+            }
+            // The permit borrows the semaphore, which must not borrow `self`.
+            let producer_sem = Arc::clone(&self.producer_sem);
+            tokio::select! {
+                permit = producer_sem.acquire_many(Preproc::BATCH_SIZE as u32), if !acquired => {
+                    match permit {
+                        Ok(permit) => {
+                            permit.forget();
+                            acquired = true;
+                        }
+                        // `finish()` closed the semaphore.
+                        Err(_) => return false,
+                    }
+                }
+                _ = tokio::time::sleep(delay.unwrap_or_default()), if delay.is_some() => {}
+                changed = self.control_rx.changed() => {
+                    if changed.is_err() {
+                        return false;
+                    }
+                }
+                signal = next_signal(&mut self.ch_signal) => {
+                    if let Some(signal) = self.receive(signal) {
+                        warn!("BufferedPreprocessor: unexpected {:?} from party 1", signal);
+                    }
+                }
+            }
         }
+    }
+
+    /// Party 1 waits until party 0 starts the next batch.  Returns `false` if a party stopped.
+    async fn follow<Preproc>(&mut self) -> bool
+    where
+        Preproc: BatchedPreprocessor<KS, K, PID>,
+    {
+        loop {
+            let local = self.sync_control().await;
+            if self.status.borrow().remote == Control::Stop {
+                return false;
+            }
+            tokio::select! {
+                // After a local `Stop`, this party waits for party 0 to stop as well, since party 0
+                // may have started a batch in the meantime.
+                _ = self.control_rx.changed(), if local != Control::Stop => {}
+                signal = next_signal(&mut self.ch_signal) => match self.receive(signal) {
+                    Some(Signal::Start) => {
+                        // The semaphore is closed after `finish()`, but the batch must be generated
+                        // anyway, since party 0 started it.
+                        if let Ok(permit) =
+                            self.producer_sem.acquire_many(Preproc::BATCH_SIZE as u32).await
+                        {
+                            permit.forget();
+                        }
+                        return true;
+                    }
+                    Some(Signal::Idle { batches, controls }) if controls == self.sent_controls => {
+                        let own_batches = self.batches.load(Ordering::SeqCst);
+                        if batches != own_batches {
+                            error!(
+                                "BufferedPreprocessor: party 0 paused after {} batches, but party 1 after {}",
+                                batches, own_batches
+                            );
+                        }
+                        info!("BufferedPreprocessor: paused after {} batches", own_batches);
+                        self.status.send_modify(|status| status.idle = true);
+                    }
+                    // Otherwise, party 0 did not yet receive the latest `Control` of this party and
+                    // may still start a batch.
+                    Some(Signal::Idle { .. }) => {}
+                    Some(signal) => warn!("BufferedPreprocessor: unexpected {:?} from party 0", signal),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    /// Tells the other party if the local `Control` changed, and returns it.
+    async fn sync_control(&mut self) -> Control {
+        let control = *self.control_rx.borrow_and_update();
+        if control != self.sent {
+            self.sent = control;
+            self.sent_controls += 1;
+            self.status.send_modify(|status| status.idle = false);
+            self.send(Signal::Control(control)).await;
+        }
+        control
+    }
 
-        let triples = inner.get_beaver_triples().await;
-        queue.lock().await.extend(triples.into_iter());
+    /// Records a `Control` of the other party and returns any other signal.  A broken stream
+    /// counts as `Control::Stop`.
+    fn receive(&mut self, signal: Option<Result<Signal, bincode::Error>>) -> Option<Signal> {
+        let signal = match signal {
+            Some(Ok(signal)) => signal,
+            Some(Err(e)) => {
+                warn!("BufferedPreprocessor: failed to receive: {}", e);
+                Signal::Control(Control::Stop)
+            }
+            None => Signal::Control(Control::Stop),
+        };
+        match signal {
+            Signal::Control(control) => {
+                self.received_controls += 1;
+                self.status.send_modify(|status| {
+                    status.remote = control;
+                    status.idle = false;
+                });
+                None
+            }
+            signal => Some(signal),
+        }
+    }
+
+    /// Sends `signal` and returns whether that succeeded.  Without coordination, there is nobody
+    /// to tell.
+    async fn send(&mut self, signal: Signal) -> bool {
+        let Some(ch_signal) = &mut self.ch_signal else {
+            return true;
+        };
+        match ch_signal.writer.send(signal).await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("BufferedPreprocessor: failed to send {:?}: {}", signal, e);
+                false
+            }
+        }
+    }
+}
 
-        consumer_sem.add_permits(Preproc::BATCH_SIZE);
+/// Receives the next `Signal` of the other party, or waits forever without coordination.
+async fn next_signal(
+    ch_signal: &mut Option<BiChannel<Signal>>,
+) -> Option<Result<Signal, bincode::Error>> {
+    match ch_signal {
+        Some(ch_signal) => ch_signal.reader.next().await,
+        None => std::future::pending().await,
     }
 }
 
@@ -97,6 +878,18 @@ where
     K: GenericNativeResidue,
 {
     async fn get_beaver_triples(&mut self, n: usize) -> Vec<BeaverTriple<KS, K, PID>> {
+        let main_short = self.buffered() < n;
+        if let Some(express) = &mut self.express {
+            if n <= express.threshold && express.decide(main_short).await {
+                info!(
+                    "BufferedPreprocessor: serving {} triples from the express lane",
+                    n
+                );
+                express.served += 1;
+                return express.inner.get_beaver_triples(n).await;
+            }
+        }
+
         self.consumer_sem
             .acquire_many(n as u32)
             .await
@@ -115,9 +908,353 @@ where
 
     async fn finish(mut self) {
         if let Some(terminated_rx) = std::mem::take(&mut self.terminated_rx) {
+            self.control.send_replace(Control::Stop);
             self.producer_sem.close();
             // This cannot fail, because `produce()` never drops the `Sender` without sending.
             terminated_rx.await.unwrap();
         }
+        if let Some(express) = self.express.take() {
+            express.finish().await;
+        }
+    }
+}
+
+/// Adapts a `BatchedPreprocessor` to `Preprocessor` by keeping the unused part of the last batch.
+///
+/// In contrast to `BufferedPreprocessor`, no background task is spawned: batches are only generated
+/// when `get_beaver_triples()` needs them, within the calling task.
+pub struct CachedPreprocessor<Preproc, KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    inner: Preproc,
+    cache: VecDeque<BeaverTriple<KS, K, PID>>,
+}
+
+impl<Preproc, KS, K, const PID: usize> CachedPreprocessor<Preproc, KS, K, PID>
+where
+    Preproc: BatchedPreprocessor<KS, K, PID>,
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    pub fn new(inner: Preproc) -> Self {
+        Self {
+            inner,
+            cache: VecDeque::new(),
+        }
+    }
+
+    /// Number of triples that can be obtained without generating another batch.
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+}
+
+#[async_trait]
+impl<Preproc, KS, K, const PID: usize> Preprocessor<KS, K, PID>
+    for CachedPreprocessor<Preproc, KS, K, PID>
+where
+    Preproc: BatchedPreprocessor<KS, K, PID> + Send,
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    async fn get_beaver_triples(&mut self, n: usize) -> Vec<BeaverTriple<KS, K, PID>> {
+        while self.cache.len() < n {
+            let triples = self.inner.get_beaver_triples().await;
+            self.cache.extend(triples);
+        }
+        self.cache.drain(..n).collect()
+    }
+
+    async fn finish(self) {
+        self.inner.finish().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use async_trait::async_trait;
+    use crypto_bigint::Zero;
+
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::{BatchedPreprocessor, BeaverTriple, Preprocessor, Share, TripleOrigin};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+    use crate::transcript::SessionId;
+
+    use super::{
+        mac_key_digest, BufferedPreprocessor, CachedPreprocessor, ExpressLane, Inventory, Schedule,
+        SetupError, Windows,
+    };
+
+    type KS = <ToyPreprocK32S32 as PreprocessorParameters>::KS;
+    type K = <ToyPreprocK32S32 as PreprocessorParameters>::K;
+
+    /// Numbers its triples consecutively (in `a`) and counts the generated batches.
+    #[derive(Default)]
+    struct Counter {
+        next: i64,
+        batches: usize,
+    }
+
+    #[async_trait]
+    impl<const PID: usize> BatchedPreprocessor<KS, K, PID> for Counter {
+        const BATCH_SIZE: usize = 10;
+
+        async fn get_beaver_triples(&mut self) -> Vec<BeaverTriple<KS, K, PID>> {
+            self.batches += 1;
+            (0..<Self as BatchedPreprocessor<KS, K, PID>>::BATCH_SIZE)
+                .map(|_| {
+                    self.next += 1;
+                    let a = Share::new(KS::from_i64(self.next - 1), KS::ZERO);
+                    BeaverTriple::new(a, Share::ZERO, Share::ZERO)
+                })
+                .collect()
+        }
+
+        async fn finish(self) {}
+    }
+
+    impl TripleOrigin<KS> for Counter {
+        fn mac_key_share(&self) -> KS {
+            KS::ZERO
+        }
+
+        fn session_id(&self) -> SessionId {
+            [0; 32]
+        }
+    }
+
+    fn inventory<const PID: usize>(len: i64) -> Inventory<KS, K, PID> {
+        Inventory {
+            triples: (0..len)
+                .map(|i| {
+                    let a = Share::new(KS::from_i64(i), KS::ZERO);
+                    BeaverTriple::new(a, Share::ZERO, Share::ZERO)
+                })
+                .collect(),
+            batches: 0,
+            mac_key_digest: mac_key_digest(KS::ZERO),
+            session_id: [0; 32],
+        }
+    }
+
+    fn never() -> impl Schedule {
+        |_: SystemTime| Duration::from_secs(3600)
+    }
+
+    async fn connect(p0_addr: &str, p1_addr: &str) -> (Connection, Connection) {
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(p0_addr.parse().unwrap(), p1_addr.parse().unwrap()),
+            Connection::new(p1_addr.parse().unwrap(), p0_addr.parse().unwrap())
+        );
+        (conn0.unwrap(), conn1.unwrap())
+    }
+
+    #[tokio::test]
+    async fn cached_preprocessor() {
+        let mut preproc = CachedPreprocessor::<_, KS, K, 0>::new(Counter::default());
+        let mut next = 0;
+        for n in [3, 7, 0, 15, 1, 24] {
+            let triples = preproc.get_beaver_triples(n).await;
+            assert_eq!(triples.len(), n);
+            for triple in triples {
+                assert_eq!(triple.a.val, KS::from_i64(next));
+                next += 1;
+            }
+            assert_eq!(preproc.inner.batches, (next as usize).div_ceil(10));
+            assert_eq!(preproc.cached(), preproc.inner.batches * 10 - next as usize);
+        }
+        preproc.finish().await;
+    }
+
+    #[tokio::test]
+    async fn uncoordinated() {
+        let mut preproc = BufferedPreprocessor::<KS, K, 0>::new(Counter::default(), 10);
+        let triples = preproc.get_beaver_triples(15).await;
+        assert_eq!(triples[14].a.val, KS::from_i64(14));
+        // Without a connection, the origin of the triples is unknown.
+        assert!(preproc.inventory().await.is_none());
+
+        preproc.pause();
+        preproc.wait_paused().await;
+        assert!(preproc.is_paused());
+        preproc.finish().await;
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)] // The first window wraps around midnight.
+    fn windows() {
+        // 1970-01-02, 23:00 UTC
+        let at = |hours: u64| UNIX_EPOCH + Duration::from_secs((24 + hours) * 3600);
+        let mut schedule = Windows::daily(vec![22 * 3600..6 * 3600, 12 * 3600..13 * 3600])
+            .with_cooldown(Duration::from_secs(3600));
+        assert_eq!(schedule.delay(at(23)), Duration::ZERO);
+        assert_eq!(schedule.delay(at(5)), Duration::ZERO);
+        assert_eq!(schedule.delay(at(6)), Duration::from_secs(6 * 3600));
+        assert_eq!(schedule.delay(at(14)), Duration::from_secs(8 * 3600));
+
+        schedule.batch_completed(at(23));
+        assert_eq!(
+            schedule.delay(at(23) + Duration::from_secs(600)),
+            Duration::from_secs(3000)
+        );
+        assert_eq!(schedule.delay(at(24)), Duration::ZERO);
+
+        let mut never = Windows::daily(vec![]);
+        assert_eq!(never.delay(at(0)), Duration::from_secs(24 * 3600));
+    }
+
+    #[tokio::test]
+    async fn pause_resume_and_restore() {
+        let (mut conn0, mut conn1) = connect("[::1]:50161", "[::1]:50162").await;
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (preproc0, preproc1) = tokio::join!(
+            BufferedPreprocessor::<KS, K, 0>::with_connection(&mut fork0, Counter::default(), 10),
+            BufferedPreprocessor::<KS, K, 1>::with_connection(&mut fork1, Counter::default(), 10)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+
+        // Pausing one party pauses both at the same batch boundary.
+        preproc1.pause();
+        let (batches0, batches1) = tokio::join!(preproc0.wait_paused(), preproc1.wait_paused());
+        assert_eq!(batches0, batches1);
+        assert!(preproc0.is_paused());
+        assert!(preproc1.is_paused());
+
+        preproc1.resume();
+        let (triples0, triples1) = tokio::join!(
+            preproc0.get_beaver_triples(15),
+            preproc1.get_beaver_triples(15)
+        );
+        assert_eq!(triples0[14].a.val, KS::from_i64(14));
+        assert_eq!(triples1[14].a.val, KS::from_i64(14));
+
+        preproc0.pause();
+        let (batches0, batches1) = tokio::join!(preproc0.wait_paused(), preproc1.wait_paused());
+        assert_eq!(batches0, batches1);
+        let (inventory0, inventory1) = tokio::join!(preproc0.inventory(), preproc1.inventory());
+        let (inventory0, inventory1) = (inventory0.unwrap(), inventory1.unwrap());
+        assert_eq!(inventory0.batches, batches0);
+        assert_eq!(inventory0.triples.len() as u64, batches0 * 10 - 15);
+        assert_eq!(inventory0.triples[0].a.val, KS::from_i64(15));
+        assert_eq!(inventory1.triples.len(), inventory0.triples.len());
+        assert_eq!(inventory1.session_id, inventory0.session_id);
+        tokio::join!(preproc0.finish(), preproc1.finish());
+
+        let bytes = bincode::serialize(&inventory0).unwrap();
+        let inventory0: Inventory<KS, K, 0> = bincode::deserialize(&bytes).unwrap();
+        let never = |_: SystemTime| Duration::from_secs(3600);
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (restored0, restored1) = tokio::join!(
+            BufferedPreprocessor::restore(&mut fork0, Counter::default(), 10, never, inventory0),
+            BufferedPreprocessor::restore(&mut fork1, Counter::default(), 10, never, inventory1)
+        );
+        let (mut restored0, mut restored1) = (restored0.unwrap(), restored1.unwrap());
+        assert_eq!(restored0.batches(), batches0);
+        let (triples0, triples1) = tokio::join!(
+            restored0.get_beaver_triples(5),
+            restored1.get_beaver_triples(5)
+        );
+        assert_eq!(triples0[0].a.val, KS::from_i64(15));
+        assert_eq!(triples1[0].a.val, KS::from_i64(15));
+        tokio::join!(restored0.finish(), restored1.finish());
+    }
+
+    #[tokio::test]
+    async fn restore_mismatch() {
+        let (mut conn0, mut conn1) = connect("[::1]:50163", "[::1]:50164").await;
+
+        // The parties restore different numbers of batches.
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let ahead = Inventory {
+            batches: 1,
+            ..inventory::<0>(5)
+        };
+        let (restored0, restored1) = tokio::join!(
+            BufferedPreprocessor::restore(&mut fork0, Counter::default(), 10, never(), ahead),
+            BufferedPreprocessor::restore(
+                &mut fork1,
+                Counter::default(),
+                10,
+                never(),
+                inventory::<1>(5)
+            )
+        );
+        assert!(matches!(restored0, Err(SetupError::InventoryMismatch)));
+        assert!(matches!(restored1, Err(SetupError::InventoryMismatch)));
+
+        // Party 0 restores an inventory of another MAC key.
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let other_key = Inventory {
+            mac_key_digest: mac_key_digest(KS::from_i64(1)),
+            ..inventory::<0>(5)
+        };
+        let (restored0, restored1) = tokio::join!(
+            BufferedPreprocessor::restore(&mut fork0, Counter::default(), 10, never(), other_key),
+            BufferedPreprocessor::restore(
+                &mut fork1,
+                Counter::default(),
+                10,
+                never(),
+                inventory::<1>(5)
+            )
+        );
+        assert!(matches!(restored0, Err(SetupError::MacKeyMismatch)));
+        assert!(matches!(restored1, Err(SetupError::InventoryMismatch)));
+    }
+
+    #[tokio::test]
+    async fn express_lane() {
+        let (mut conn0, mut conn1) = connect("[::1]:50081", "[::1]:50082").await;
+        let express = || Counter {
+            next: 1000,
+            batches: 0,
+        };
+        let (express0, express1) = tokio::join!(
+            ExpressLane::<KS, K, 0>::new(&mut conn0, express(), 2),
+            ExpressLane::<KS, K, 1>::new(&mut conn1, express(), 2)
+        );
+        // The main lanes never start a batch, so only the restored triples are buffered.
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (preproc0, preproc1) = tokio::join!(
+            BufferedPreprocessor::restore(
+                &mut fork0,
+                Counter::default(),
+                10,
+                never(),
+                inventory::<0>(7)
+            ),
+            BufferedPreprocessor::restore(
+                &mut fork1,
+                Counter::default(),
+                10,
+                never(),
+                inventory::<1>(7)
+            )
+        );
+        let (preproc0, preproc1) = tokio::join!(
+            preproc0.unwrap().with_express(express0.unwrap()),
+            preproc1.unwrap().with_express(express1.unwrap())
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+
+        // Small requests take the express lane only if the main lane has too few triples.
+        for (n, first) in [(3, 0), (2, 3), (1, 5), (2, 1000), (1, 6), (1, 1002)] {
+            let (triples0, triples1) = tokio::join!(
+                preproc0.get_beaver_triples(n),
+                preproc1.get_beaver_triples(n)
+            );
+            assert_eq!(triples0.len(), n);
+            assert_eq!(triples0[0].a.val, KS::from_i64(first));
+            assert_eq!(triples1[0].a.val, KS::from_i64(first));
+        }
+        assert_eq!(preproc0.express_served(), 2);
+        assert_eq!(preproc1.express_served(), 2);
+        tokio::join!(preproc0.finish(), preproc1.finish());
     }
 }
@@ -0,0 +1,88 @@
+//! Decoding of the messages of the other party with a limit on their size.
+//!
+//! The other party controls the length prefixes of the collections in its messages.  With the
+//! default options, `bincode` has no limit on the size of what it decodes, so the deserializers of
+//! the wire messages go through the functions of this module instead.  Each of them matches the
+//! encoding of the corresponding serializer and fails instead of decoding more than
+//! `MAX_MESSAGE_SIZE` bytes.  The fuzz targets under `fuzz/` feed arbitrary bytes into them, see
+//! `fuzzing`.
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+
+/// Largest number of bytes that a message may decode to.  The largest messages of the shipped
+/// parameters are the responses of the ZKPoPK for `k=128, s=64`, which stay far below this.
+pub const MAX_MESSAGE_SIZE: u64 = 1 << 30;
+
+/// Largest number of bytes of the ID of a stream, see `Connection::open_bi()`.
+pub const MAX_STREAM_ID_SIZE: u64 = 1024;
+
+/// Decodes a message that was encoded by `bincode::serialize()`, e.g., the payloads of a
+/// `Multiplexer` and the requests of an `Enclave`.  Trailing bytes are rejected.
+pub fn deserialize<T>(bytes: &[u8]) -> bincode::Result<T>
+where
+    T: DeserializeOwned,
+{
+    check_size(bytes, MAX_MESSAGE_SIZE)?;
+    bincode::options()
+        .with_fixint_encoding()
+        .with_limit(MAX_MESSAGE_SIZE)
+        .deserialize(bytes)
+}
+
+/// Decodes a message of a stream, which `AsyncBincodeWriter` encodes with `bincode::options()`.
+pub fn deserialize_framed<T>(bytes: &[u8]) -> bincode::Result<T>
+where
+    T: DeserializeOwned,
+{
+    check_size(bytes, MAX_MESSAGE_SIZE)?;
+    bincode::options()
+        .with_limit(MAX_MESSAGE_SIZE)
+        .deserialize(bytes)
+}
+
+/// Decodes the ID that the other party sent at the start of a stream.
+pub fn deserialize_stream_id(bytes: &[u8]) -> bincode::Result<Vec<u32>> {
+    check_size(bytes, MAX_STREAM_ID_SIZE)?;
+    bincode::options()
+        .with_limit(MAX_STREAM_ID_SIZE)
+        .deserialize(bytes)
+}
+
+/// `bincode` ignores the limit when it decodes from a slice (since the slice bounds what can be
+/// read), so the length of the slice itself is checked.
+fn check_size(bytes: &[u8], limit: u64) -> bincode::Result<()> {
+    if bytes.len() as u64 > limit {
+        return Err(Box::new(bincode::ErrorKind::SizeLimit));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bincode::Options;
+
+    use super::{deserialize, deserialize_framed, deserialize_stream_id};
+
+    #[test]
+    fn roundtrip_and_limits() {
+        let message = vec![1u64, 2, 3];
+        let bytes = bincode::serialize(&message).unwrap();
+        assert_eq!(deserialize::<Vec<u64>>(&bytes).unwrap(), message);
+        let framed = bincode::options().serialize(&message).unwrap();
+        assert_eq!(deserialize_framed::<Vec<u64>>(&framed).unwrap(), message);
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(deserialize::<Vec<u64>>(&trailing).is_err());
+
+        // A length prefix that exceeds the input is rejected.
+        let huge = bincode::serialize(&u64::MAX).unwrap();
+        assert!(deserialize::<Vec<u8>>(&huge).is_err());
+
+        let id = vec![u32::MAX; 300];
+        let bytes = bincode::options().serialize(&id).unwrap();
+        assert!(bytes.len() as u64 > super::MAX_STREAM_ID_SIZE);
+        assert!(deserialize_stream_id(&bytes).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Hash-based commitments.
+//!
+//! A commitment to `msg` is the hash of the randomness and the `bincode` serialization of `msg`,
+//! computed with the `CryptoSuite` of the party.  The type parameter ties a commitment to the type
+//! of the committed message.
+
+use std::marker::PhantomData;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto_suite::CryptoSuite;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(bound = "")]
+pub struct Commitment<T> {
+    digest: [u8; 32],
+    phantom: PhantomData<T>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Opening<T> {
+    pub msg: T,
+    pub randomness: [u8; 32],
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub struct CommitmentMismatch {}
+
+pub fn commit<T>(suite: CryptoSuite, msg: &T, randomness: &[u8; 32]) -> Commitment<T>
+where
+    T: Serialize,
+{
+    let mut hasher = suite.hasher();
+    hasher.update(randomness);
+    hasher.update(bincode::serialize(msg).unwrap());
+    Commitment {
+        digest: hasher.finalize(),
+        phantom: PhantomData,
+    }
+}
+
+/// Commits to `msg` using fresh randomness and returns the commitment and its opening.
+pub fn commit_random<T>(suite: CryptoSuite, msg: T) -> (Commitment<T>, Opening<T>)
+where
+    T: Serialize,
+{
+    let randomness: [u8; 32] = rand::thread_rng().gen();
+    let com = commit(suite, &msg, &randomness);
+    (com, Opening { msg, randomness })
+}
+
+/// Returns the committed message if `opening` matches `com`, which must have been computed with the
+/// same `suite`.
+pub fn open<T>(
+    suite: CryptoSuite,
+    com: &Commitment<T>,
+    opening: Opening<T>,
+) -> Result<T, CommitmentMismatch>
+where
+    T: Serialize,
+{
+    if commit(suite, &opening.msg, &opening.randomness).digest != com.digest {
+        return Err(CommitmentMismatch {});
+    }
+    Ok(opening.msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_suite::CryptoSuite;
+
+    use super::{commit_random, open, Opening};
+
+    #[test]
+    fn commit_open() {
+        for &suite in CryptoSuite::ALL {
+            let (com, opening) = commit_random(suite, vec![1u64, 2, 3]);
+            assert_eq!(open(suite, &com, opening).unwrap(), vec![1u64, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn open_rejects_other_message() {
+        let (com, opening) = commit_random(CryptoSuite::default(), vec![1u64, 2, 3]);
+        let opening = Opening {
+            msg: vec![1u64, 2, 4],
+            randomness: opening.randomness,
+        };
+        assert!(open(CryptoSuite::default(), &com, opening).is_err());
+    }
+
+    #[test]
+    fn open_rejects_other_randomness() {
+        let (com, opening) = commit_random(CryptoSuite::default(), 42u64);
+        let mut randomness = opening.randomness;
+        randomness[0] ^= 1;
+        let opening = Opening {
+            msg: opening.msg,
+            randomness,
+        };
+        assert!(open(CryptoSuite::default(), &com, opening).is_err());
+    }
+
+    #[test]
+    fn open_rejects_other_suite() {
+        for (i, &suite) in CryptoSuite::ALL.iter().enumerate() {
+            for &other in &CryptoSuite::ALL[i + 1..] {
+                let (com, opening) = commit_random(suite, 42u64);
+                assert!(open(other, &com, opening.clone()).is_err());
+                assert_eq!(open(suite, &com, opening).unwrap(), 42u64);
+            }
+        }
+    }
+}
@@ -1,14 +1,23 @@
-use std::{io, net::SocketAddr, sync::Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
 
 use async_bincode::tokio::AsyncBincodeWriter;
-use bincode::Options;
+use futures_util::future::try_join_all;
 use futures_util::{SinkExt, StreamExt};
-use log::{debug, error};
+use log::{debug, error, info};
 use quinn::{Incoming, NewConnection, TransportConfig};
 use rcgen::RcgenError;
 use tokio::io::AsyncReadExt;
 
-use crate::oneshot_map::OneshotMap;
+use crate::codec;
+use crate::oneshot_map::{OneshotMap, OneshotMapLimits, OneshotMapStats, RecvError};
+
+/// Limits of the incoming streams that were not yet opened locally.  The remote party chooses
+/// their IDs, so it could otherwise make us keep arbitrarily many streams.
+const PENDING_STREAM_LIMITS: OneshotMapLimits = OneshotMapLimits {
+    capacity: Some(1024),
+    ttl: Some(Duration::from_secs(600)),
+};
 
 struct SkipServerVerification;
 
@@ -40,12 +49,36 @@ pub enum ConnectionError {
     BindError(io::Error),
     InvalidClientConfig(quinn::ConnectError),
     FailedToConnect(quinn::ConnectionError),
+    TimedOut,
 }
 
 #[derive(Debug, derive_more::Display, derive_more::Error)]
 pub enum StreamError {
     FailedToOpen(quinn::ConnectionError),
     FailedToSendID(bincode::ErrorKind),
+    FailedToAccept(RecvError),
+}
+
+/// How `Connection::with_retry()` retries connecting to the remote party, e.g. while it is not
+/// started yet.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Timeout of a single connection attempt.
+    pub attempt_timeout: Duration,
+    /// Delay between two connection attempts.
+    pub interval: Duration,
+    /// Maximum number of connection attempts, or `None` to retry forever.
+    pub max_attempts: Option<usize>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempt_timeout: Duration::from_secs(5),
+            interval: Duration::from_secs(1),
+            max_attempts: None,
+        }
+    }
 }
 
 pub struct Connection {
@@ -53,12 +86,19 @@ pub struct Connection {
     id: Vec<u32>,
     num_children: u32,
     num_streams: u32,
+    /// Index of the QUIC connection in `ConnectionState::shards` that carries the streams.
+    shard: usize,
     state: Arc<ConnectionState>,
     recv_mapper: Arc<OneshotMap<Vec<u32>, quinn::RecvStream>>,
+    #[cfg(test)]
+    faults: Option<Arc<crate::fault_injection::FaultInjector>>,
 }
 
 struct ConnectionState {
-    connection: quinn::Connection,
+    /// The underlying QUIC connections, see `Connection::sharded()`.
+    shards: Vec<quinn::Connection>,
+    /// The shard of the next child, see `Connection::fork()`.
+    next_shard: AtomicUsize,
 }
 
 impl Connection {
@@ -66,61 +106,81 @@ impl Connection {
         listen_addr: SocketAddr,
         remote_addr: SocketAddr,
     ) -> Result<Self, ConnectionError> {
-        let id = Vec::new();
-
-        let mut transport_config = TransportConfig::default();
-        transport_config.max_idle_timeout(None); // TODO: Can we get low gear to work with idle timeout?
-        transport_config.max_concurrent_uni_streams(1024u32.into());
-        let transport_config = Arc::new(transport_config);
-
-        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()])
-            .map_err(ConnectionError::CertGenerationError)?;
-        let key = rustls::PrivateKey(cert.serialize_private_key_der());
-        let cert = vec![rustls::Certificate(
-            cert.serialize_der()
-                .map_err(ConnectionError::CertSerializationError)?,
-        )];
-        let server_crypto = rustls::ServerConfig::builder()
-            .with_safe_defaults()
-            .with_no_client_auth()
-            .with_single_cert(cert, key)
-            .map_err(ConnectionError::InvalidLocalCert)?;
-        let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(server_crypto));
-        server_config.transport = Arc::clone(&transport_config);
-        let (_endpoint, incoming) = quinn::Endpoint::server(server_config, listen_addr)
-            .map_err(ConnectionError::BindError)?;
-        let client_crypto = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_custom_certificate_verifier(SkipServerVerification::new()) // TODO: Verify server cert
-            .with_no_client_auth();
-        let mut client_config = quinn::ClientConfig::new(Arc::new(client_crypto));
-        client_config.transport = transport_config;
-        let client_bind_addr = match remote_addr {
-            SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
-            SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
-        };
-        let client_connecting = quinn::Endpoint::client(client_bind_addr)
-            .map_err(ConnectionError::BindError)?
-            .connect_with(client_config, remote_addr, "localhost")
-            .map_err(ConnectionError::InvalidClientConfig)?;
-        let NewConnection { connection, .. } = client_connecting
-            .await
-            .map_err(ConnectionError::FailedToConnect)?;
-        let recv_mapper = Arc::new(OneshotMap::default());
-        tokio::task::spawn(handle_incoming(
+        Self::connect(listen_addr, remote_addr, None).await
+    }
+
+    /// Like `new()`, but retries connecting until the remote party is reachable.
+    pub async fn with_retry(
+        listen_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        retry: RetryPolicy,
+    ) -> Result<Self, ConnectionError> {
+        Self::connect(listen_addr, remote_addr, Some(retry)).await
+    }
+
+    /// Like `new()`, but establishes one QUIC connection per pair of `listen_addrs` and
+    /// `remote_addrs`, e.g., via different NICs or ports, across which the forked channels are
+    /// striped.  A single QUIC connection may not saturate a fast link due to its congestion
+    /// control.  Both parties must pass their addresses in the same order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no addresses are given or if the numbers of addresses differ.
+    pub async fn sharded(
+        listen_addrs: &[SocketAddr],
+        remote_addrs: &[SocketAddr],
+        retry: Option<RetryPolicy>,
+    ) -> Result<Self, ConnectionError> {
+        assert!(!listen_addrs.is_empty(), "no addresses given");
+        assert_eq!(
+            listen_addrs.len(),
+            remote_addrs.len(),
+            "numbers of listen and remote addresses differ"
+        );
+        let recv_mapper = Arc::new(OneshotMap::with_limits(PENDING_STREAM_LIMITS));
+        let shards = try_join_all(listen_addrs.iter().zip(remote_addrs).map(
+            |(listen_addr, remote_addr)| {
+                connect_shard(*listen_addr, *remote_addr, retry, Arc::clone(&recv_mapper))
+            },
+        ))
+        .await?;
+        Ok(Self::with_shards(listen_addrs[0], shards, recv_mapper))
+    }
+
+    async fn connect(
+        listen_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        retry: Option<RetryPolicy>,
+    ) -> Result<Self, ConnectionError> {
+        let recv_mapper = Arc::new(OneshotMap::with_limits(PENDING_STREAM_LIMITS));
+        let connection =
+            connect_shard(listen_addr, remote_addr, retry, Arc::clone(&recv_mapper)).await?;
+        Ok(Self::with_shards(
             listen_addr,
-            incoming,
-            Arc::clone(&recv_mapper),
-        ));
+            vec![connection],
+            recv_mapper,
+        ))
+    }
 
-        Ok(Self {
+    fn with_shards(
+        listen_addr: SocketAddr,
+        shards: Vec<quinn::Connection>,
+        recv_mapper: Arc<OneshotMap<Vec<u32>, quinn::RecvStream>>,
+    ) -> Self {
+        Self {
             listen_addr,
-            id,
+            id: Vec::new(),
             num_children: 0,
             num_streams: 0,
-            state: Arc::new(ConnectionState { connection }),
+            shard: 0,
+            state: Arc::new(ConnectionState {
+                shards,
+                next_shard: AtomicUsize::new(1),
+            }),
             recv_mapper,
-        })
+            #[cfg(test)]
+            faults: None,
+        }
     }
 
     pub async fn open_bi(
@@ -130,9 +190,7 @@ impl Connection {
         let mut id = self.id.clone();
         id.push(self.num_streams);
 
-        let mut send = self
-            .state
-            .connection
+        let mut send = self.state.shards[self.shard]
             .open_uni()
             .await
             .map_err(StreamError::FailedToOpen)?;
@@ -146,14 +204,22 @@ impl Connection {
             .await
             .map_err(|b| StreamError::FailedToSendID(*b))?;
 
-        // `unwrap()` cannot fail, because we never reuse IDs.
-        let recv = self.recv_mapper.recv(id.clone()).await.unwrap();
+        let recv = self
+            .recv_mapper
+            .recv(id.clone())
+            .await
+            .map_err(StreamError::FailedToAccept)?;
         debug!(
             "{} {:?} {}: Handling incoming stream",
             self.listen_addr, id, name
         );
 
         self.num_streams += 1;
+        #[cfg(test)]
+        let send = match &self.faults {
+            Some(faults) => faults.tap(name, send).await?,
+            None => send,
+        };
         Ok((send, recv))
     }
 
@@ -161,53 +227,159 @@ impl Connection {
         let mut id = self.id.clone();
         id.push(self.num_children);
         self.num_children += 1;
+        // The children are striped across the shards in the order in which they are forked.
+        let shard = self.state.next_shard.fetch_add(1, Ordering::Relaxed) % self.num_shards();
         Self {
             listen_addr: self.listen_addr,
             id,
             num_children: 0,
             num_streams: 0,
+            shard,
             state: Arc::clone(&self.state),
             recv_mapper: Arc::clone(&self.recv_mapper),
+            #[cfg(test)]
+            faults: self.faults.clone(),
         }
     }
 
+    /// Passes the frames that this party sends on the channels of `faults` through its faults,
+    /// including the channels of children that are forked afterwards.
+    #[cfg(test)]
+    pub(crate) fn inject_faults(&mut self, faults: Arc<crate::fault_injection::FaultInjector>) {
+        self.faults = Some(faults);
+    }
+
     pub fn listen_addr(&self) -> &SocketAddr {
         &self.listen_addr
     }
+
+    /// Number of underlying QUIC connections, see `sharded()`.
+    pub fn num_shards(&self) -> usize {
+        self.state.shards.len()
+    }
+
+    /// Number of bytes sent so far via each of the underlying QUIC connections.
+    pub fn bytes_sent_per_shard(&self) -> Vec<u64> {
+        self.state
+            .shards
+            .iter()
+            .map(|connection| connection.stats().udp_tx.bytes)
+            .collect()
+    }
+
+    /// Number of incoming streams that were not yet opened locally and of `open_bi()` calls that
+    /// wait for the remote party.
+    pub async fn pending_streams(&self) -> OneshotMapStats {
+        self.recv_mapper.stats().await
+    }
 }
 
 impl Drop for ConnectionState {
     fn drop(&mut self) {
-        self.connection.close(0u32.into(), b"done");
+        for connection in &self.shards {
+            connection.close(0u32.into(), b"done");
+        }
     }
 }
 
+/// Establishes a QUIC connection from `listen_addr` to `remote_addr` and forwards its incoming
+/// streams to `recv_mapper`.
+async fn connect_shard(
+    listen_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    retry: Option<RetryPolicy>,
+    recv_mapper: Arc<OneshotMap<Vec<u32>, quinn::RecvStream>>,
+) -> Result<quinn::Connection, ConnectionError> {
+    let mut transport_config = TransportConfig::default();
+    transport_config.max_idle_timeout(None); // TODO: Can we get low gear to work with idle timeout?
+    transport_config.max_concurrent_uni_streams(1024u32.into());
+    let transport_config = Arc::new(transport_config);
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()])
+        .map_err(ConnectionError::CertGenerationError)?;
+    let key = rustls::PrivateKey(cert.serialize_private_key_der());
+    let cert = vec![rustls::Certificate(
+        cert.serialize_der()
+            .map_err(ConnectionError::CertSerializationError)?,
+    )];
+    let server_crypto = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert, key)
+        .map_err(ConnectionError::InvalidLocalCert)?;
+    let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(server_crypto));
+    server_config.transport = Arc::clone(&transport_config);
+    let (_endpoint, incoming) =
+        quinn::Endpoint::server(server_config, listen_addr).map_err(ConnectionError::BindError)?;
+    let client_crypto = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(SkipServerVerification::new()) // TODO: Verify server cert
+        .with_no_client_auth();
+    let mut client_config = quinn::ClientConfig::new(Arc::new(client_crypto));
+    client_config.transport = transport_config;
+    let client_bind_addr = match remote_addr {
+        SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+        SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+    };
+    let client_endpoint =
+        quinn::Endpoint::client(client_bind_addr).map_err(ConnectionError::BindError)?;
+    let mut attempt = 0;
+    let NewConnection { connection, .. } = loop {
+        attempt += 1;
+        let client_connecting = client_endpoint
+            .connect_with(client_config.clone(), remote_addr, "localhost")
+            .map_err(ConnectionError::InvalidClientConfig)?;
+        let retry = match retry {
+            None => {
+                break client_connecting
+                    .await
+                    .map_err(ConnectionError::FailedToConnect)?
+            }
+            Some(retry) => retry,
+        };
+        let is_last_attempt = retry.max_attempts.is_some_and(|max| attempt >= max);
+        match tokio::time::timeout(retry.attempt_timeout, client_connecting).await {
+            Ok(Ok(new_conn)) => break new_conn,
+            Ok(Err(e)) if is_last_attempt => return Err(ConnectionError::FailedToConnect(e)),
+            Err(_) if is_last_attempt => return Err(ConnectionError::TimedOut),
+            Ok(Err(e)) => info!(
+                "{}: Connection attempt {} failed: {}",
+                listen_addr, attempt, e
+            ),
+            Err(_) => info!("{}: Connection attempt {} timed out", listen_addr, attempt),
+        }
+        tokio::time::sleep(retry.interval).await;
+    };
+    tokio::task::spawn(handle_incoming(listen_addr, incoming, recv_mapper));
+    Ok(connection)
+}
+
 async fn handle_incoming(
     listen_addr: SocketAddr,
     mut incoming: Incoming,
     recv_mapper: Arc<OneshotMap<Vec<u32>, quinn::RecvStream>>,
 ) {
     // TODO: Support multiple remote parties connecting on the same port.
-    let connecting = match incoming.next().await {
-        None => {
-            error!(
-                "{}: Did not receive any incoming QUIC connection",
-                listen_addr
-            );
-            return;
-        }
-        Some(connecting) => connecting,
-    };
-
-    let mut new_conn = match connecting.await {
-        Err(e) => {
-            error!(
+    // With a `RetryPolicy`, the other party abandons attempts that time out, and these may still
+    // arrive here before the attempt that succeeds.  So take the first connection that establishes.
+    let mut new_conn = loop {
+        let connecting = match incoming.next().await {
+            None => {
+                error!(
+                    "{}: Did not receive any incoming QUIC connection",
+                    listen_addr
+                );
+                return;
+            }
+            Some(connecting) => connecting,
+        };
+        match connecting.await {
+            Err(e) => info!(
                 "{}: Incoming QUIC connection failed to establish: {}",
                 listen_addr, e
-            );
-            return;
+            ),
+            Ok(new_conn) => break new_conn,
         }
-        Ok(new_conn) => new_conn,
     };
 
     while let Some(recv) = new_conn.uni_streams.next().await {
@@ -234,7 +406,7 @@ async fn handle_incoming(
             Ok(id_len) => id_len,
         };
 
-        if id_len > 1024 {
+        if id_len as u64 > codec::MAX_STREAM_ID_SIZE {
             error!(
                 "{}: Ignoring incoming stream due to ID too long",
                 listen_addr
@@ -251,7 +423,7 @@ async fn handle_incoming(
             continue;
         }
 
-        let id: Vec<u32> = match bincode::options().deserialize(&id_buffer) {
+        let id = match codec::deserialize_stream_id(&id_buffer) {
             Err(e) => {
                 error!(
                     "{}: Ignoring incoming stream due to failure to deserialize ID: {}",
@@ -262,10 +434,10 @@ async fn handle_incoming(
             Ok(id) => id,
         };
 
-        if let Err(_) = recv_mapper.send(id.clone(), recv).await {
+        if let Err(e) = recv_mapper.send(id.clone(), recv).await {
             error!(
-                "{}, ID {:?}: Incoming stream with duplicate ID",
-                listen_addr, id
+                "{}, ID {:?}: Ignoring incoming stream: {}",
+                listen_addr, id, e
             );
         }
     }
@@ -274,11 +446,13 @@ async fn handle_incoming(
 #[cfg(test)]
 mod tests {
     use std::error::Error;
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
 
     use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
     use futures_util::{SinkExt, StreamExt};
 
-    use super::Connection;
+    use super::{Connection, RetryPolicy};
 
     #[tokio::test]
     async fn connection() {
@@ -296,11 +470,114 @@ mod tests {
         .unwrap();
     }
 
+    #[tokio::test]
+    async fn connection_with_retry() {
+        const P0_ADDR: &str = "[::1]:50053";
+        const P1_ADDR: &str = "[::1]:50054";
+
+        let retry = RetryPolicy {
+            attempt_timeout: Duration::from_millis(500),
+            interval: Duration::from_millis(100),
+            max_attempts: Some(20),
+        };
+
+        tokio::try_join!(
+            tokio::task::spawn(async move {
+                run_party_with_retry(P0_ADDR, P1_ADDR, Some(retry))
+                    .await
+                    .unwrap();
+            }),
+            tokio::task::spawn(async move {
+                // The other party must wait until this one is reachable.
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                run_party_with_retry(P1_ADDR, P0_ADDR, Some(retry))
+                    .await
+                    .unwrap();
+            }),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sharded() {
+        const P0_ADDRS: [&str; 4] = ["[::1]:50089", "[::1]:50090", "[::1]:50091", "[::1]:50092"];
+        const P1_ADDRS: [&str; 4] = ["[::1]:50093", "[::1]:50094", "[::1]:50095", "[::1]:50096"];
+        const NUM_CHANNELS: usize = 8;
+        const PAYLOAD_LEN: usize = 1 << 20;
+
+        let p0_addrs: Vec<SocketAddr> = P0_ADDRS.iter().map(|a| a.parse().unwrap()).collect();
+        let p1_addrs: Vec<SocketAddr> = P1_ADDRS.iter().map(|a| a.parse().unwrap()).collect();
+        let (conn0, conn1) = tokio::join!(
+            Connection::sharded(&p0_addrs, &p1_addrs, None),
+            Connection::sharded(&p1_addrs, &p0_addrs, None)
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        assert_eq!(conn0.num_shards(), 4);
+
+        let start = Instant::now();
+        let mut tasks = Vec::new();
+        for _ in 0..NUM_CHANNELS {
+            let (mut child0, mut child1) = (conn0.fork(), conn1.fork());
+            tasks.push(tokio::task::spawn(async move {
+                tokio::try_join!(
+                    exchange_payload(&mut child0, PAYLOAD_LEN),
+                    exchange_payload(&mut child1, PAYLOAD_LEN)
+                )
+                .unwrap();
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        let total = 2 * NUM_CHANNELS * PAYLOAD_LEN;
+        log::info!(
+            "Sharded: {} MiB in {:?} ({:.1} MiB/s)",
+            total >> 20,
+            elapsed,
+            (total >> 20) as f64 / elapsed.as_secs_f64()
+        );
+
+        // The channels are striped across the shards, so each shard carries two of them.
+        for conn in [&conn0, &conn1] {
+            for bytes in conn.bytes_sent_per_shard() {
+                assert!(bytes >= 2 * PAYLOAD_LEN as u64);
+            }
+        }
+    }
+
+    async fn exchange_payload(
+        conn: &mut Connection,
+        len: usize,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (mut tx, mut rx) = conn.open_bi("test:exchange_payload").await?;
+        let payload = vec![0x5a; len];
+        let mut writer = AsyncBincodeWriter::<_, Vec<u8>, _>::from(&mut tx).for_async();
+        let mut reader = AsyncBincodeReader::<_, Vec<u8>>::from(&mut rx);
+        let (sent, received) = tokio::join!(writer.send(payload.clone()), reader.next());
+        drop((writer, reader));
+        sent?;
+        assert_eq!(received.unwrap()?, payload);
+        let _ = tx.finish().await;
+        Ok(())
+    }
+
     async fn run_party(local: &str, remote: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        run_party_with_retry(local, remote, None).await
+    }
+
+    async fn run_party_with_retry(
+        local: &str,
+        remote: &str,
+        retry: Option<RetryPolicy>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let local_addr = local.parse().unwrap();
         let remote_addr = remote.parse().unwrap();
 
-        let mut conn1 = Connection::new(local_addr, remote_addr).await?;
+        let mut conn1 = match retry {
+            None => Connection::new(local_addr, remote_addr).await?,
+            Some(retry) => Connection::with_retry(local_addr, remote_addr, retry).await?,
+        };
         let mut conn2 = conn1.fork();
         let mut conn3 = conn1.fork();
         let mut conn4 = conn2.fork();
@@ -0,0 +1,82 @@
+//! Sharing of `CrtContext`s between subprotocols.
+//!
+//! Generating a context (or reading its factors) takes seconds for production parameters.  The
+//! `LowGearPreprocessor`, its dealer and the preprocessors of parallel batches often use the same
+//! parameters, so a `ContextSet` generates each context once, keyed by its parameter type.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OnceCell;
+
+use crate::bgv::poly::{crt::CrtPolyParameters, CrtContext};
+use crate::util::run_blocking;
+
+type Slot = Arc<OnceCell<Arc<dyn Any + Send + Sync>>>;
+
+/// Lazily generated `CrtContext`s for any parameters, see the module documentation.
+#[derive(Default)]
+pub struct ContextSet {
+    contexts: Mutex<HashMap<TypeId, Slot>>,
+}
+
+impl ContextSet {
+    /// Returns the context for `P`, which is generated by the first caller on a blocking thread, so
+    /// that the contexts for different parameters are generated in parallel.  Callers for other
+    /// parameters do not wait for it.
+    pub async fn get<P>(&self) -> Arc<CrtContext<P>>
+    where
+        P: CrtPolyParameters,
+    {
+        let slot = Arc::clone(
+            self.contexts
+                .lock()
+                .unwrap()
+                .entry(TypeId::of::<P>())
+                .or_default(),
+        );
+        let ctx = slot
+            .get_or_init(|| async {
+                Arc::new(run_blocking(CrtContext::<P>::gen()).await) as Arc<dyn Any + Send + Sync>
+            })
+            .await;
+        // `unwrap()` cannot fail, because the slot of `P` only holds a `CrtContext<P>`.
+        Arc::downcast(Arc::clone(ctx)).unwrap()
+    }
+
+    /// Number of contexts that were generated so far.
+    pub fn len(&self) -> usize {
+        self.contexts
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|slot| slot.initialized())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::bgv::params::{ToyCipher, ToyPlain};
+
+    use super::ContextSet;
+
+    #[tokio::test]
+    async fn contexts_are_shared_per_parameters() {
+        let contexts = ContextSet::default();
+        let (cipher_0, cipher_1, _plain) = tokio::join!(
+            contexts.get::<ToyCipher>(),
+            contexts.get::<ToyCipher>(),
+            contexts.get::<ToyPlain>()
+        );
+        assert!(Arc::ptr_eq(&cipher_0, &cipher_1));
+        assert_eq!(contexts.len(), 2);
+    }
+}
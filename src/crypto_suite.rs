@@ -0,0 +1,134 @@
+//! Selection of the hash function of the protocol primitives.
+//!
+//! The commitments (see `commitment`), the transcripts (see `transcript`) and thereby the
+//! challenges of the ZKPoPK (see `ZkpopkVersion::V2`) and the seeds of the MAC checks all hash with
+//! the `CryptoSuite` of the party.  Both parties must use the same suite.  SHA-256 is always
+//! available, the others are behind the `suite-*` features.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Hash function with 256-bit digests.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum CryptoSuite {
+    #[default]
+    Sha256,
+    /// SHA3-256 (requires the `suite-sha3` feature).
+    #[cfg(feature = "suite-sha3")]
+    Sha3_256,
+    /// BLAKE3 with 256-bit output (requires the `suite-blake3` feature).
+    #[cfg(feature = "suite-blake3")]
+    Blake3,
+}
+
+impl CryptoSuite {
+    /// All suites that are available with the enabled features.
+    pub const ALL: &'static [Self] = &[
+        Self::Sha256,
+        #[cfg(feature = "suite-sha3")]
+        Self::Sha3_256,
+        #[cfg(feature = "suite-blake3")]
+        Self::Blake3,
+    ];
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            #[cfg(feature = "suite-sha3")]
+            Self::Sha3_256 => Hasher::Sha3_256(Box::new(sha3::Sha3_256::new())),
+            #[cfg(feature = "suite-blake3")]
+            Self::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// The digest of `bytes`.
+    pub fn hash(self, bytes: &[u8]) -> [u8; 32] {
+        let mut hasher = self.hasher();
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+}
+
+/// Incremental hashing with a `CryptoSuite`.
+#[derive(Clone)]
+pub enum Hasher {
+    Sha256(Sha256),
+    #[cfg(feature = "suite-sha3")]
+    Sha3_256(Box<sha3::Sha3_256>),
+    #[cfg(feature = "suite-blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn update(&mut self, bytes: impl AsRef<[u8]>) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(bytes),
+            #[cfg(feature = "suite-sha3")]
+            Self::Sha3_256(hasher) => hasher.update(bytes),
+            #[cfg(feature = "suite-blake3")]
+            Self::Blake3(hasher) => {
+                hasher.update(bytes.as_ref());
+            }
+        }
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        match self {
+            Self::Sha256(hasher) => hasher.finalize().into(),
+            #[cfg(feature = "suite-sha3")]
+            Self::Sha3_256(hasher) => hasher.finalize().into(),
+            #[cfg(feature = "suite-blake3")]
+            Self::Blake3(hasher) => hasher.finalize().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CryptoSuite;
+
+    #[test]
+    fn sha256_test_vector() {
+        // FIPS 180-2, appendix B.1.
+        let digest = CryptoSuite::Sha256.hash(b"abc");
+        assert_eq!(digest[..4], [0xba, 0x78, 0x16, 0xbf]);
+        assert_eq!(digest[28..], [0xf2, 0x00, 0x15, 0xad]);
+    }
+
+    #[cfg(feature = "suite-sha3")]
+    #[test]
+    fn sha3_256_test_vector() {
+        // FIPS 202 example of SHA3-256("abc").
+        let digest = CryptoSuite::Sha3_256.hash(b"abc");
+        assert_eq!(digest[..4], [0x3a, 0x98, 0x5d, 0xa7]);
+        assert_eq!(digest[28..], [0x11, 0x43, 0x15, 0x32]);
+    }
+
+    #[cfg(feature = "suite-blake3")]
+    #[test]
+    fn blake3_test_vector() {
+        // Official test vector of the empty input.
+        let digest = CryptoSuite::Blake3.hash(b"");
+        assert_eq!(digest[..4], [0xaf, 0x13, 0x49, 0xb9]);
+        assert_eq!(digest[28..], [0xe4, 0x1f, 0x32, 0x62]);
+    }
+
+    #[test]
+    fn suites_differ() {
+        for (i, lhs) in CryptoSuite::ALL.iter().enumerate() {
+            for rhs in &CryptoSuite::ALL[i + 1..] {
+                assert_ne!(lhs.hash(b"multipars"), rhs.hash(b"multipars"));
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_matches_oneshot() {
+        for suite in CryptoSuite::ALL {
+            let mut hasher = suite.hasher();
+            hasher.update(b"multi");
+            hasher.update(b"pars");
+            assert_eq!(hasher.finalize(), suite.hash(b"multipars"));
+        }
+    }
+}
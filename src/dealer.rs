@@ -0,0 +1,111 @@
+//! The backends that authenticate the values of the preprocessor, see `DealerBackend`.
+
+use crate::connection::{Connection, StreamError};
+use crate::context_set::ContextSet;
+use crate::crypto_suite::CryptoSuite;
+use crate::interface::MacKeyShare;
+use crate::low_gear_dealer::{DealerError, DealerParameters, DealerState, LowGearDealer};
+use crate::third_party_dealer::ThirdPartyDealer;
+
+/// Selects the backend of the dealer, e.g., via `LowGearPreprocessorBuilder::dealer()`.  Both
+/// parties must select the same backend.
+#[derive(Default)]
+pub enum DealerBackend {
+    /// The BGV-based `LowGearDealer` between the two parties.
+    #[default]
+    LowGear,
+    /// A `ThirdPartyDealer`, which is reached on the given connection.
+    ThirdParty(Connection),
+}
+
+pub enum Dealer<P>
+where
+    P: DealerParameters,
+{
+    LowGear(LowGearDealer<P>),
+    ThirdParty(ThirdPartyDealer<P>),
+}
+
+impl<P> Dealer<P>
+where
+    P: DealerParameters,
+{
+    /// Sets up the dealer of `backend`.  The `LowGearDealer` runs on `conn` and takes its
+    /// `CrtContext` from `contexts`.
+    pub async fn new(
+        conn: &mut Connection,
+        backend: DealerBackend,
+        mac_key: MacKeyShare<P::S>,
+        contexts: &ContextSet,
+    ) -> Result<Self, StreamError> {
+        Ok(match backend {
+            DealerBackend::LowGear => {
+                Self::LowGear(LowGearDealer::with_contexts(conn, mac_key, contexts).await?)
+            }
+            DealerBackend::ThirdParty(dealer_conn) => {
+                Self::ThirdParty(ThirdPartyDealer::new(dealer_conn, mac_key).await?)
+            }
+        })
+    }
+
+    pub fn state(&self) -> DealerState {
+        match self {
+            Self::LowGear(dealer) => dealer.state(),
+            Self::ThirdParty(dealer) => dealer.state(),
+        }
+    }
+
+    /// See `LowGearDealer::authenticate()`.
+    pub async fn authenticate(&mut self, values: &[P::K]) -> Vec<P::KS> {
+        match self {
+            Self::LowGear(dealer) => dealer.authenticate(values).await,
+            Self::ThirdParty(dealer) => dealer.authenticate(values).await,
+        }
+    }
+
+    /// See `LowGearDealer::try_authenticate()`.
+    pub async fn try_authenticate(&mut self, values: &[P::K]) -> Result<Vec<P::KS>, DealerError> {
+        match self {
+            Self::LowGear(dealer) => dealer.try_authenticate(values).await,
+            Self::ThirdParty(dealer) => dealer.try_authenticate(values).await,
+        }
+    }
+
+    /// See `LowGearDealer::set_strict()`.  The tags of the third party are correct by assumption,
+    /// so this only applies to the `LowGearDealer`.
+    pub fn set_strict(&mut self, strict: bool) {
+        if let Self::LowGear(dealer) = self {
+            dealer.set_strict(strict);
+        }
+    }
+
+    /// See `LowGearDealer::set_crypto_suite()`.  The third party uses no commitments, so this only
+    /// applies to the `LowGearDealer`.
+    pub fn set_crypto_suite(&mut self, suite: CryptoSuite) {
+        if let Self::LowGear(dealer) = self {
+            dealer.set_crypto_suite(suite);
+        }
+    }
+
+    pub fn is_strict(&self) -> bool {
+        match self {
+            Self::LowGear(dealer) => dealer.is_strict(),
+            Self::ThirdParty(_) => false,
+        }
+    }
+
+    /// See `LowGearDealer::refresh_keys()`.  The third party has no keys to refresh, so this only
+    /// applies to the `LowGearDealer`.
+    pub async fn refresh_keys(&mut self) {
+        if let Self::LowGear(dealer) = self {
+            dealer.refresh_keys().await;
+        }
+    }
+
+    pub async fn finish(self) {
+        match self {
+            Self::LowGear(dealer) => dealer.finish().await,
+            Self::ThirdParty(dealer) => dealer.finish().await,
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Generation of daBits and edaBits for mixed arithmetic/boolean computation.
+//!
+//! Each party samples random bits `b_0` and `b_1` locally and authenticates them via the dealer,
+//! where the other party inputs zero.  The arithmetic sharing of `b = b_0 XOR b_1` is then computed
+//! as `[b_0] + [b_1] - 2 [b_0 b_1]` using one Beaver triple per bit, while the local bits serve as
+//! XOR shares of `b`.  The authenticated shares `[b_0]` and `[b_1]` are kept, such that the XOR
+//! shares can be opened with a MAC check, see `open_booleans()`.
+//!
+//! A party could input an arbitrary value instead of a bit.  Since the bit of the other party is
+//! `0` or `1`, `b` is then `v` or `1 - v` for the value `v` of the cheating party, which are both
+//! bits only if `v` is one.  Hence, `[b (1 - b)]` is computed with a second Beaver triple and opened,
+//! and the generation fails unless it is zero.  This is sound in `Z_{2^k}`, since one of `b` and
+//! `1 - b` is odd and hence invertible.
+
+use crypto_bigint::{Random, Zero};
+use rand::Rng;
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::bgv::residue::GenericResidue;
+use crate::dealer::Dealer;
+use crate::interface::{
+    BeaverTriple, BitDecomposition, DaBit, EdaBit, MacKeyShare, Share, XorShares,
+};
+use crate::low_gear_preproc::PreprocessorParameters;
+use crate::mac_check_opener::{MacCheckFailed, MacCheckOpener};
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum DaBitError {
+    MacCheckFailed(MacCheckFailed),
+    /// A bit of the other party, or an opened XOR share, is not `0` or `1`.
+    NotABit,
+}
+
+/// Generates one daBit per two given triples.
+///
+/// # Panics
+///
+/// Panics if the number of triples is odd.
+pub async fn gen_dabits<P, const PID: usize>(
+    dealer: &mut Dealer<P::DealerParams>,
+    opener: &mut MacCheckOpener<P::KS, P::S>,
+    mac_key: &MacKeyShare<P::S>,
+    triples: &[BeaverTriple<P::KS, P::K, PID>],
+) -> Result<Vec<DaBit<P::KS, P::K, PID>>, DaBitError>
+where
+    P: PreprocessorParameters,
+{
+    let bits: Vec<P::K> = {
+        let mut rng = rand::thread_rng();
+        (0..triples.len() / 2)
+            .map(|_| P::K::from_i64(rng.gen::<bool>() as i64))
+            .collect()
+    };
+    gen_dabits_from::<P, PID>(dealer, opener, mac_key, &bits, triples).await
+}
+
+/// Like `gen_dabits()`, but with the given bits of this party, which are only checked to be bits
+/// afterwards, such that tests can input other values.
+pub(crate) async fn gen_dabits_from<P, const PID: usize>(
+    dealer: &mut Dealer<P::DealerParams>,
+    opener: &mut MacCheckOpener<P::KS, P::S>,
+    mac_key: &MacKeyShare<P::S>,
+    bits: &[P::K],
+    triples: &[BeaverTriple<P::KS, P::K, PID>],
+) -> Result<Vec<DaBit<P::KS, P::K, PID>>, DaBitError>
+where
+    P: PreprocessorParameters,
+{
+    assert_eq!(triples.len() % 2, 0, "two triples per daBit are needed");
+    let n = triples.len() / 2;
+    assert_eq!(bits.len(), n);
+    let (triples, check_triples) = triples.split_at(n);
+
+    // Each party inputs its bits at its own position and zeros at the other party's position.  The
+    // last inputs are used for the batch check mask.
+    let mask_strategy = opener.mask_strategy::<P::K>();
+    let input = {
+        let mut rng = rand::thread_rng();
+        let mut input = vec![P::K::ZERO; 2 * n];
+        input[PID * n..(PID + 1) * n].copy_from_slice(bits);
+        input.extend((0..mask_strategy.num_values()).map(|_| P::K::random(&mut rng)));
+        input
+    };
+
+    let output = dealer.authenticate(&input).await;
+
+    let shares: Vec<Share<P::KS, P::K, PID>> = input
+        .iter()
+        .zip(output)
+        .map(|(val, tag)| Share::new(P::KS::from_unsigned(*val), tag))
+        .collect();
+    let (shares, masking_values) = shares.split_at(2 * n);
+    let batch_check_mask = mask_strategy.combine(masking_values);
+    let (bits_0, bits_1) = shares.split_at(n);
+
+    // Multiply `[b_0]` and `[b_1]` using Beaver's trick.
+    let masked: Vec<_> = bits_0
+        .iter()
+        .zip(triples)
+        .map(|(x, triple)| *x - triple.a)
+        .chain(bits_1.iter().zip(triples).map(|(y, triple)| *y - triple.b))
+        .collect();
+    let opened = opener
+        .open_unchecked(&masked)
+        .await
+        .map_err(DaBitError::MacCheckFailed)?;
+    let arith: Vec<_> = beaver_products(triples, &opened, mac_key)
+        .zip(bits_0.iter().zip(bits_1))
+        .map(|(prod, (x, y))| *x + *y - (prod << 1))
+        .collect();
+
+    // Multiply `[b]` and `[1 - b]` to check that `b` is a bit.
+    let check_masked: Vec<_> = arith
+        .iter()
+        .zip(check_triples)
+        .map(|(b, triple)| *b - triple.a)
+        .chain(
+            arith
+                .iter()
+                .zip(check_triples)
+                .map(|(b, triple)| (Share::public(P::K::from_i64(1), mac_key) - *b) - triple.b),
+        )
+        .collect();
+    let check_opened = opener
+        .open_unchecked(&check_masked)
+        .await
+        .map_err(DaBitError::MacCheckFailed)?;
+    let products: Vec<_> = beaver_products(check_triples, &check_opened, mac_key).collect();
+    let opened_products = opener
+        .open_unchecked(&products)
+        .await
+        .map_err(DaBitError::MacCheckFailed)?;
+
+    // The openings are checked before their result is used, since the other party could have
+    // changed them.
+    opener
+        .batch_check(
+            masked.into_iter().chain(check_masked).chain(products),
+            batch_check_mask,
+        )
+        .await
+        .map_err(DaBitError::MacCheckFailed)?;
+    if opened_products.iter().any(|prod| *prod != P::K::ZERO) {
+        return Err(DaBitError::NotABit);
+    }
+
+    Ok(arith
+        .into_iter()
+        .zip(bits_0.iter().zip(bits_1))
+        .zip(bits)
+        .map(|((arith, (x, y)), bit)| DaBit {
+            arith,
+            boolean: *bit != P::K::ZERO,
+            xor_shares: [*x, *y],
+        })
+        .collect())
+}
+
+/// Computes the products of Beaver's trick, where `opened` holds the values of `x - a` followed by
+/// those of `y - b`.
+fn beaver_products<'a, KS, K, S, const PID: usize>(
+    triples: &'a [BeaverTriple<KS, K, PID>],
+    opened: &'a [K],
+    mac_key: &'a MacKeyShare<S>,
+) -> impl Iterator<Item = Share<KS, K, PID>> + 'a
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    let (epsilon, delta) = opened.split_at(triples.len());
+    triples
+        .iter()
+        .zip(epsilon.iter().zip(delta))
+        .map(move |(triple, (e, d))| {
+            (triple.c + triple.b * *e + triple.a * *d).add_public(*e * *d, mac_key)
+        })
+}
+
+/// Opens the XOR shares of daBits, edaBits or `BitDecomposition`s with a MAC check and returns
+/// the bits `b_0 XOR b_1`.  Both parties must pass the same number of XOR shares.
+pub async fn open_booleans<KS, K, S, const PID: usize>(
+    opener: &mut MacCheckOpener<KS, S>,
+    xor_shares: &[XorShares<KS, K, PID>],
+) -> Result<Vec<bool>, DaBitError>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    let shares: Vec<_> = xor_shares.iter().flatten().copied().collect();
+    let values = opener
+        .check_many(&shares)
+        .await
+        .map_err(DaBitError::MacCheckFailed)?;
+    values
+        .chunks_exact(2)
+        .map(|pair| match (to_bool(pair[0]), to_bool(pair[1])) {
+            (Some(b_0), Some(b_1)) => Ok(b_0 ^ b_1),
+            _ => Err(DaBitError::NotABit),
+        })
+        .collect()
+}
+
+fn to_bool<K>(value: K) -> Option<bool>
+where
+    K: GenericNativeResidue,
+{
+    if value == K::ZERO {
+        Some(false)
+    } else if value == K::from_i64(1) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Combines consecutive chunks of `len` daBits into edaBits.
+pub fn combine_edabits<KS, K, const PID: usize>(
+    dabits: &[DaBit<KS, K, PID>],
+    len: usize,
+) -> Vec<EdaBit<KS, K, PID>>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    dabits
+        .chunks_exact(len)
+        .map(|chunk| {
+            let bits: Vec<_> = chunk.iter().map(|dabit| dabit.arith).collect();
+            EdaBit {
+                arith: Share::compose(&bits),
+                booleans: chunk.iter().map(|dabit| dabit.boolean).collect(),
+                xor_shares: chunk.iter().map(|dabit| dabit.xor_shares).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Combines consecutive chunks of `len` daBits into `BitDecomposition`s, which keep the arithmetic
+/// shares of the bits.
+pub fn combine_bit_decompositions<KS, K, const PID: usize>(
+    dabits: &[DaBit<KS, K, PID>],
+    len: usize,
+) -> Vec<BitDecomposition<KS, K, PID>>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    dabits
+        .chunks_exact(len)
+        .map(|chunk| {
+            let bits: Vec<_> = chunk.iter().map(|dabit| dabit.arith).collect();
+            BitDecomposition {
+                arith: Share::compose(&bits),
+                bits,
+                booleans: chunk.iter().map(|dabit| dabit.boolean).collect(),
+                xor_shares: chunk.iter().map(|dabit| dabit.xor_shares).collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::Random;
+    use rand::Rng;
+
+    use crate::bgv::residue::{native::NativeResidue, GenericResidue};
+    use crate::interface::{DaBit, Share};
+
+    use super::{combine_bit_decompositions, combine_edabits};
+
+    type K = NativeResidue<32, 1>;
+    type KS = NativeResidue<64, 1>;
+
+    #[test]
+    fn combine_edabits_reconstructs() {
+        const LEN: usize = 8;
+        let mut rng = rand::thread_rng();
+        let mut dabits_0 = Vec::new();
+        let mut dabits_1 = Vec::new();
+        let mut expected = 0u64;
+        for i in 0..LEN {
+            let (bool_0, bool_1): (bool, bool) = (rng.gen(), rng.gen());
+            let bit = KS::from_i64((bool_0 ^ bool_1) as i64);
+            let val_0 = KS::random(&mut rng);
+            dabits_0.push(DaBit {
+                arith: Share::<KS, K, 0>::new(val_0, KS::random(&mut rng)),
+                boolean: bool_0,
+                xor_shares: [Share::ZERO; 2],
+            });
+            dabits_1.push(DaBit {
+                arith: Share::<KS, K, 1>::new(bit - val_0, KS::random(&mut rng)),
+                boolean: bool_1,
+                xor_shares: [Share::ZERO; 2],
+            });
+            expected |= ((bool_0 ^ bool_1) as u64) << i;
+        }
+
+        let edabits_0 = combine_edabits(&dabits_0, LEN);
+        let edabits_1 = combine_edabits(&dabits_1, LEN);
+        let (edabit_0, edabit_1) = (&edabits_0[0], &edabits_1[0]);
+        let actual = K::from_unsigned(edabit_0.arith.val + edabit_1.arith.val);
+        assert_eq!(actual, K::from_i64(expected as i64));
+        for (i, (b_0, b_1)) in edabit_0.booleans.iter().zip(&edabit_1.booleans).enumerate() {
+            assert_eq!((b_0 ^ b_1) as u64, (expected >> i) & 1);
+        }
+    }
+    #[test]
+    fn combine_bit_decompositions_reconstructs() {
+        const LEN: usize = 8;
+        let mut rng = rand::thread_rng();
+        let mut dabits_0 = Vec::new();
+        let mut dabits_1 = Vec::new();
+        let mut expected = 0u64;
+        for i in 0..LEN {
+            let (bool_0, bool_1): (bool, bool) = (rng.gen(), rng.gen());
+            let bit = KS::from_i64((bool_0 ^ bool_1) as i64);
+            let (val_0, tag_0) = (KS::random(&mut rng), KS::random(&mut rng));
+            dabits_0.push(DaBit {
+                arith: Share::<KS, K, 0>::new(val_0, tag_0),
+                boolean: bool_0,
+                xor_shares: [Share::ZERO; 2],
+            });
+            dabits_1.push(DaBit {
+                arith: Share::<KS, K, 1>::new(bit - val_0, KS::random(&mut rng)),
+                boolean: bool_1,
+                xor_shares: [Share::ZERO; 2],
+            });
+            expected |= ((bool_0 ^ bool_1) as u64) << i;
+        }
+
+        let decomps_0 = combine_bit_decompositions(&dabits_0, LEN);
+        let decomps_1 = combine_bit_decompositions(&dabits_1, LEN);
+        let (decomp_0, decomp_1) = (&decomps_0[0], &decomps_1[0]);
+        let actual = K::from_unsigned(decomp_0.arith.val + decomp_1.arith.val);
+        assert_eq!(actual, K::from_i64(expected as i64));
+        assert_eq!(decomp_0.arith, Share::compose(&decomp_0.bits));
+        for (i, (bit_0, bit_1)) in decomp_0.bits.iter().zip(&decomp_1.bits).enumerate() {
+            let bit = K::from_unsigned(bit_0.val + bit_1.val);
+            assert_eq!(bit, K::from_i64(((expected >> i) & 1) as i64));
+        }
+        assert_eq!(
+            decomp_0.booleans,
+            combine_edabits(&dabits_0, LEN)[0].booleans
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! A message-passing core that runs the secret-dependent BGV operations of a party in an enclave
+//! (e.g., SGX or SEV), while the host outside of the enclave runs the networking.
+//!
+//! Like `verify`, this module compiles without the `protocol` feature and hence without `tokio` and
+//! `quinn`, e.g., with `--no-default-features --features enclave`.  The host passes each `Request`
+//! to `Enclave::handle()`, or its `bincode` serialization to `Enclave::handle_bytes()`, and sends
+//! the contents of the `Reply` to the other party.  The BGV secret key and the randomness of the
+//! encryptions never leave the enclave: the host only sees the public key, the ciphertexts, the
+//! messages of the ZKPoPK and the decryptions that it requests.  The ZKPoPKs of the other party
+//! don't need secrets, so the host verifies them itself, see `verify::verify_zkpopk()`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::bgv::poly::power::PowerPoly;
+use crate::bgv::poly::CrtContext;
+use crate::bgv::witness::EncryptionWitness;
+use crate::bgv::zkpopk::prover::{Prover, ResponseAborted};
+use crate::bgv::zkpopk::{Challenge, Commitment, Response, Statement};
+use crate::bgv::{self, BgvParameters, Ciphertext, PreCiphertext, PublicKey, SecretKey};
+use crate::codec;
+use crate::util::block_on;
+
+#[derive(Deserialize, Serialize)]
+#[serde(bound(deserialize = ""))]
+#[serde(bound(serialize = ""))]
+pub enum Request<P>
+where
+    P: BgvParameters,
+{
+    /// Returns the public key of the enclave.
+    PublicKey,
+    /// Encrypts `plaintexts` under the public key of the enclave and commits to a ZKPoPK for the
+    /// ciphertexts, whose parameters are `inv_fail_prob` and `snd_sec`.
+    Encrypt {
+        plaintexts: Vec<PowerPoly<P::PlaintextParams>>,
+        inv_fail_prob: usize,
+        snd_sec: usize,
+    },
+    /// Responds to the challenge of the other party for the last `Encrypt`.
+    Respond {
+        challenge: Challenge,
+    },
+    Decrypt {
+        ciphertext: Ciphertext<P>,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(bound(deserialize = ""))]
+#[serde(bound(serialize = ""))]
+pub enum Reply<P>
+where
+    P: BgvParameters,
+{
+    PublicKey(PublicKey<P>),
+    Encrypted {
+        ciphertexts: Vec<PreCiphertext<P>>,
+        commitment: Commitment<P>,
+    },
+    Response(Response<P>),
+    /// The response was aborted, so the proof is repeated with a new commitment, for which the
+    /// host requests another challenge.
+    Aborted {
+        commitment: Commitment<P>,
+    },
+    Decrypted(PowerPoly<P::PlaintextParams>),
+    Failed(EnclaveError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, derive_more::Display)]
+pub enum EnclaveError {
+    /// `Respond` was requested without a preceding `Encrypt`.
+    NoPendingProof,
+    /// The serialized request could not be deserialized, see `Enclave::handle_bytes()`.
+    MalformedRequest,
+}
+
+/// The ZKPoPK of the last `Encrypt`, until its response is sent.
+struct PendingProof<P>
+where
+    P: BgvParameters,
+{
+    prover: Prover<P>,
+    inputs: Vec<EncryptionWitness<P::PlaintextParams>>,
+    statement: Statement,
+    inv_fail_prob: usize,
+    snd_sec: usize,
+}
+
+pub struct Enclave<P>
+where
+    P: BgvParameters,
+{
+    ctx: CrtContext<P::CiphertextParams>,
+    sk: SecretKey<P>,
+    pk: PublicKey<P>,
+    pending: Option<PendingProof<P>>,
+}
+
+impl<P> Enclave<P>
+where
+    P: BgvParameters,
+{
+    /// Generates the `CrtContext` and the key pair of the enclave.
+    pub async fn new() -> Self {
+        let ctx = CrtContext::gen().await;
+        let sk = SecretKey::gen(&ctx).await;
+        let pk = PublicKey::gen(&ctx, &sk).await;
+        Self {
+            ctx,
+            sk,
+            pk,
+            pending: None,
+        }
+    }
+
+    pub async fn handle(&mut self, request: Request<P>) -> Reply<P> {
+        match request {
+            Request::PublicKey => Reply::PublicKey(self.pk.clone()),
+            Request::Encrypt {
+                plaintexts,
+                inv_fail_prob,
+                snd_sec,
+            } => {
+                let mut ciphertexts = Vec::with_capacity(plaintexts.len());
+                let mut inputs = Vec::with_capacity(plaintexts.len());
+                for plaintext in &plaintexts {
+                    let mut ciphertext = PreCiphertext::default();
+                    let input =
+                        Prover::encrypt_into(&self.ctx, &self.pk, plaintext, &mut ciphertext).await;
+                    ciphertexts.push(ciphertext);
+                    inputs.push(input);
+                }
+                let prover = Prover::new(inv_fail_prob, inputs.len(), snd_sec);
+                let commitment = prover.commit(&self.ctx, &self.pk).await;
+                self.pending = Some(PendingProof {
+                    prover,
+                    inputs,
+                    statement: Statement::of(&ciphertexts),
+                    inv_fail_prob,
+                    snd_sec,
+                });
+                Reply::Encrypted {
+                    ciphertexts,
+                    commitment,
+                }
+            }
+            Request::Respond { challenge } => {
+                let pending = match self.pending.take() {
+                    Some(pending) => pending,
+                    None => return Reply::Failed(EnclaveError::NoPendingProof),
+                };
+                match pending
+                    .prover
+                    .respond(&pending.inputs, &pending.statement, challenge)
+                {
+                    Ok(response) => Reply::Response(response),
+                    Err(ResponseAborted) => {
+                        let prover = Prover::new(
+                            pending.inv_fail_prob,
+                            pending.inputs.len(),
+                            pending.snd_sec,
+                        );
+                        let commitment = prover.commit(&self.ctx, &self.pk).await;
+                        self.pending = Some(PendingProof { prover, ..pending });
+                        Reply::Aborted { commitment }
+                    }
+                }
+            }
+            Request::Decrypt { ciphertext } => {
+                Reply::Decrypted(bgv::decrypt(&self.ctx, &self.sk, &ciphertext).await)
+            }
+        }
+    }
+
+    /// Like `handle()`, but takes and returns the `bincode` serializations, and blocks until the
+    /// reply is computed.  This is the whole boundary that the host drives.
+    pub fn handle_bytes(&mut self, request: &[u8]) -> Vec<u8> {
+        let reply = match codec::deserialize(request) {
+            Ok(request) => block_on(self.handle(request)),
+            Err(_) => Reply::Failed(EnclaveError::MalformedRequest),
+        };
+        bincode::serialize(&reply).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::bgv::params::ToyBgv;
+    use crate::bgv::poly::power::PowerPoly;
+    use crate::bgv::poly::CrtContext;
+    use crate::bgv::zkpopk::{Challenge, ZkpopkVersion};
+    use crate::verify::verify_zkpopk;
+
+    use super::{Enclave, EnclaveError, Reply, Request};
+
+    const INV_FAIL_PROB: usize = 1 << 20;
+    const SND_SEC: usize = 64;
+
+    #[tokio::test]
+    async fn prove_and_decrypt() {
+        let mut rng = rand::thread_rng();
+        let mut enclave = Enclave::<ToyBgv>::new().await;
+        let pk = match enclave.handle(Request::PublicKey).await {
+            Reply::PublicKey(pk) => pk,
+            _ => panic!("expected the public key"),
+        };
+
+        let plaintexts: Vec<_> = (0..2).map(|_| PowerPoly::random(&mut rng)).collect();
+        let request = Request::Encrypt {
+            plaintexts: plaintexts.clone(),
+            inv_fail_prob: INV_FAIL_PROB,
+            snd_sec: SND_SEC,
+        };
+        let (ciphertexts, mut commitment) = match enclave.handle(request).await {
+            Reply::Encrypted {
+                ciphertexts,
+                commitment,
+            } => (ciphertexts, commitment),
+            _ => panic!("expected the ciphertexts"),
+        };
+        let (challenge, response) = loop {
+            let challenge = Challenge::random(&mut rng);
+            match enclave.handle(Request::Respond { challenge }).await {
+                Reply::Response(response) => break (challenge, response),
+                Reply::Aborted {
+                    commitment: next_commitment,
+                } => commitment = next_commitment,
+                _ => panic!("expected a response"),
+            }
+        };
+        let ctx = Arc::new(CrtContext::gen().await);
+        assert!(
+            verify_zkpopk(
+                &ctx,
+                &pk,
+                &ciphertexts,
+                commitment,
+                challenge,
+                response,
+                INV_FAIL_PROB,
+                SND_SEC,
+                ZkpopkVersion::default(),
+            )
+            .await
+        );
+
+        for (ciphertext, plaintext) in ciphertexts.iter().zip(&plaintexts) {
+            let ciphertext = ciphertext.ciphertext(&ctx).await;
+            match enclave.handle(Request::Decrypt { ciphertext }).await {
+                Reply::Decrypted(decrypted) => assert_eq!(&decrypted, plaintext),
+                _ => panic!("expected the plaintext"),
+            }
+        }
+
+        let reply: Reply<ToyBgv> = bincode::deserialize(&enclave.handle_bytes(&[0xff; 3])).unwrap();
+        assert!(matches!(
+            reply,
+            Reply::Failed(EnclaveError::MalformedRequest)
+        ));
+    }
+}
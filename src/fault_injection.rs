@@ -0,0 +1,313 @@
+//! Injection of faults into the frames of named channels, for testing the resilience of the
+//! protocols.
+//!
+//! The channels of a `Connection` are concrete QUIC streams, so the faults cannot be injected by
+//! another transport.  Instead, a `FaultInjector` taps the outgoing stream of each faulty channel:
+//! `Connection::open_bi()` returns the sending half of a stream on a loopback connection, and a
+//! background task relays its frames to the actual stream, dropping, delaying, duplicating or
+//! reordering them on the way.  The frames are the length-prefixed messages of
+//! `AsyncBincodeWriter`, so the faults apply to whole messages.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::sync::Mutex;
+
+use crate::connection::{Connection, ConnectionError, StreamError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The frame is not sent.
+    Drop,
+    /// The frame and the ones after it are sent after the delay.
+    Delay(Duration),
+    /// The frame is sent twice.
+    Duplicate,
+    /// The frame is sent after the next one.
+    Reorder,
+}
+
+/// A fault of the `frame`-th message (starting at 0) that this party sends on `channel`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FaultRule {
+    pub channel: &'static str,
+    pub frame: usize,
+    pub fault: Fault,
+}
+
+pub struct FaultInjector {
+    loopback: Mutex<Connection>,
+    rules: Vec<FaultRule>,
+}
+
+impl FaultInjector {
+    /// Connects the loopback connection on `loopback_addr`, which must be a free port.
+    pub async fn new(
+        loopback_addr: SocketAddr,
+        rules: Vec<FaultRule>,
+    ) -> Result<Arc<Self>, ConnectionError> {
+        let loopback = Connection::new(loopback_addr, loopback_addr).await?;
+        Ok(Arc::new(Self {
+            loopback: Mutex::new(loopback),
+            rules,
+        }))
+    }
+
+    /// Returns the stream to which the channel `name` sends instead of `send`, or `send` itself if
+    /// no rule applies to the channel.
+    pub(crate) async fn tap(
+        &self,
+        name: &str,
+        send: quinn::SendStream,
+    ) -> Result<quinn::SendStream, StreamError> {
+        let rules: Vec<_> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.channel == name)
+            .copied()
+            .collect();
+        if rules.is_empty() {
+            return Ok(send);
+        }
+        // The loopback connection has no faults, but `open_bi()` may recurse into `tap()`.
+        let (tap_send, tap_recv) = Box::pin(self.loopback.lock().await.open_bi(name)).await?;
+        tokio::task::spawn(relay(tap_recv, send, rules));
+        Ok(tap_send)
+    }
+}
+
+async fn relay(mut from: quinn::RecvStream, mut to: quinn::SendStream, rules: Vec<FaultRule>) {
+    let mut held = None;
+    let mut index = 0;
+    while let Some(frame) = read_frame(&mut from).await {
+        let fault = rules
+            .iter()
+            .find(|rule| rule.frame == index)
+            .map(|rule| rule.fault);
+        index += 1;
+        let sent = match fault {
+            None => to.write_all(&frame).await,
+            Some(Fault::Drop) => Ok(()),
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                to.write_all(&frame).await
+            }
+            Some(Fault::Duplicate) => match to.write_all(&frame).await {
+                Ok(()) => to.write_all(&frame).await,
+                Err(e) => Err(e),
+            },
+            Some(Fault::Reorder) => {
+                held = Some(frame);
+                continue;
+            }
+        };
+        let sent = match (sent, held.take()) {
+            (Ok(()), Some(frame)) => to.write_all(&frame).await,
+            (sent, _) => sent,
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+    if let Some(frame) = held {
+        let _ = to.write_all(&frame).await;
+    }
+    let _ = to.finish().await;
+}
+
+/// Reads a frame including its length prefix, or returns `None` at the end of the stream.
+async fn read_frame(from: &mut quinn::RecvStream) -> Option<Vec<u8>> {
+    let len = from.read_u32().await.ok()?;
+    let mut frame = vec![0; 4 + len as usize];
+    frame[..4].copy_from_slice(&len.to_be_bytes());
+    from.read_exact(&mut frame[4..]).await.ok()?;
+    Some(frame)
+}
+
+/// Each test runs a batch of `LowGearPreprocessor` with a fault in the frames that party 0 sends.
+/// Whatever the fault, no party may output triples that are incorrect or not authenticated.
+///
+/// The preprocessor does not time out while waiting for a message, so a fault that leaves a
+/// party waiting (e.g., a dropped frame) stalls both parties until `DEADLINE`.
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::connection::Connection;
+    use crate::interface::{reconstruct, BeaverTriple, MacKeyShare};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::{LowGearPreprocessor, PreprocessorError, PreprocessorParameters};
+
+    use super::{Fault, FaultInjector, FaultRule};
+
+    type P = ToyPreprocK32S32;
+    type K = <P as PreprocessorParameters>::K;
+    type KS = <P as PreprocessorParameters>::KS;
+    type S = <P as PreprocessorParameters>::S;
+
+    const DEADLINE: Duration = Duration::from_secs(60);
+
+    enum Outcome<const PID: usize> {
+        Completed(Vec<BeaverTriple<KS, K, PID>>, MacKeyShare<S>),
+        Failed,
+        Panicked,
+        TimedOut,
+    }
+
+    impl<const PID: usize> Outcome<PID> {
+        fn is_completed(&self) -> bool {
+            matches!(self, Outcome::Completed(..))
+        }
+    }
+
+    /// Runs a batch, where party 0 sends with `rules`.  The ports `base_port..base_port + 3` must
+    /// be free.
+    async fn run(base_port: u16, rules: Vec<FaultRule>) -> (Outcome<0>, Outcome<1>) {
+        let addr = |offset: u16| -> SocketAddr {
+            format!("[::1]:{}", base_port + offset).parse().unwrap()
+        };
+        let (p0_addr, p1_addr, loopback_addr) = (addr(0), addr(1), addr(2));
+
+        let faults = FaultInjector::new(loopback_addr, rules).await.unwrap();
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(p0_addr, p1_addr),
+            Connection::new(p1_addr, p0_addr)
+        );
+        let (mut conn0, conn1) = (conn0.unwrap(), conn1.unwrap());
+        conn0.inject_faults(Arc::clone(&faults));
+
+        let task0 = tokio::task::spawn(run_party::<0>(conn0));
+        let task1 = tokio::task::spawn(run_party::<1>(conn1));
+        let (result0, result1) = tokio::join!(
+            tokio::time::timeout(DEADLINE, task0),
+            tokio::time::timeout(DEADLINE, task1)
+        );
+        (outcome(result0), outcome(result1))
+    }
+
+    /// Returns the connection along with the triples, such that it is only closed once both
+    /// parties are done.  A party that fails drops its connection, as it would in production.
+    async fn run_party<const PID: usize>(
+        mut conn: Connection,
+    ) -> Result<(Connection, Vec<BeaverTriple<KS, K, PID>>, MacKeyShare<S>), PreprocessorError>
+    {
+        let mut preproc = LowGearPreprocessor::<P, PID>::new(&mut conn).await.unwrap();
+        let triples = preproc.try_get_beaver_triples().await?;
+        let mac_key = preproc.mac_key().clone();
+        Ok((conn, triples, mac_key))
+    }
+
+    type PartyResult<const PID: usize> = Result<
+        Result<(Connection, Vec<BeaverTriple<KS, K, PID>>, MacKeyShare<S>), PreprocessorError>,
+        tokio::task::JoinError,
+    >;
+
+    fn outcome<const PID: usize>(
+        result: Result<PartyResult<PID>, tokio::time::error::Elapsed>,
+    ) -> Outcome<PID> {
+        match result {
+            Ok(Ok(Ok((_, triples, mac_key)))) => Outcome::Completed(triples, mac_key),
+            Ok(Ok(Err(e))) => {
+                log::info!("party {} failed: {}", PID, e);
+                Outcome::Failed
+            }
+            Ok(Err(_)) => Outcome::Panicked,
+            Err(_) => Outcome::TimedOut,
+        }
+    }
+
+    /// Asserts that the triples are correct and authenticated if both parties output them.
+    fn assert_no_bad_triples(outcomes: &(Outcome<0>, Outcome<1>)) {
+        let (triples0, mac_key0, triples1, mac_key1) = match outcomes {
+            (Outcome::Completed(triples0, mac_key0), Outcome::Completed(triples1, mac_key1)) => {
+                (triples0, mac_key0, triples1, mac_key1)
+            }
+            _ => return,
+        };
+        let mac_key = mac_key0.widen::<KS>() + mac_key1.widen::<KS>();
+        assert_eq!(triples0.len(), triples1.len());
+        for (triple0, triple1) in triples0.iter().zip(triples1) {
+            let a = reconstruct(&triple0.a, &triple1.a);
+            let b = reconstruct(&triple0.b, &triple1.b);
+            let c = reconstruct(&triple0.c, &triple1.c);
+            assert_eq!(a * b, c);
+            for (share0, share1) in [
+                (&triple0.a, &triple1.a),
+                (&triple0.b, &triple1.b),
+                (&triple0.c, &triple1.c),
+            ] {
+                let value = share0.val + share1.val;
+                assert_eq!(share0.tag + share1.tag, value * mac_key);
+            }
+        }
+    }
+
+    /// Asserts that a party detected the fault with a typed error.
+    fn assert_detected(outcomes: &(Outcome<0>, Outcome<1>)) {
+        assert!(
+            matches!(outcomes.0, Outcome::Failed) || matches!(outcomes.1, Outcome::Failed),
+            "no party returned an error"
+        );
+    }
+
+    fn rule(channel: &'static str, frame: usize, fault: Fault) -> Vec<FaultRule> {
+        vec![FaultRule {
+            channel,
+            frame,
+            fault,
+        }]
+    }
+
+    #[tokio::test]
+    async fn delayed_frame() {
+        let outcomes = run(
+            50107,
+            rule(
+                "LowGearPreprocessor:ciphertext_there",
+                0,
+                Fault::Delay(Duration::from_millis(500)),
+            ),
+        )
+        .await;
+        assert_no_bad_triples(&outcomes);
+        assert!(outcomes.0.is_completed() && outcomes.1.is_completed());
+    }
+
+    #[tokio::test]
+    async fn duplicated_frame() {
+        // Party 1 proves the ZKPoPK for a shifted list of ciphertexts.
+        let outcomes = run(
+            50110,
+            rule("LowGearPreprocessor:ciphertext_there", 0, Fault::Duplicate),
+        )
+        .await;
+        assert_no_bad_triples(&outcomes);
+        assert_detected(&outcomes);
+    }
+
+    #[tokio::test]
+    async fn reordered_frame() {
+        let outcomes = run(
+            50113,
+            rule("LowGearPreprocessor:ciphertext_there", 0, Fault::Reorder),
+        )
+        .await;
+        assert_no_bad_triples(&outcomes);
+        assert_detected(&outcomes);
+    }
+
+    #[tokio::test]
+    async fn dropped_frame() {
+        let outcomes = run(
+            50116,
+            rule("LowGearPreprocessor:ciphertext_there", 0, Fault::Drop),
+        )
+        .await;
+        assert_no_bad_triples(&outcomes);
+        assert!(!outcomes.0.is_completed() && !outcomes.1.is_completed());
+    }
+}
@@ -0,0 +1,425 @@
+//! C API for embedding the triple generation in other MPC engines (feature `ffi`).
+//!
+//! The header is `include/multipars.h`, which is generated with
+//! `cbindgen --config cbindgen.toml --output include/multipars.h`.  Residues are exported as
+//! little-endian 64-bit limbs, see `multipars_get_triples()` for the layout.
+//!
+//! Functions that can fail return a null pointer, a negative value or a size of zero.  The error
+//! message can then be obtained from `multipars_last_error()`.  Panics do not unwind into the
+//! caller but are reported as failures as well; afterwards, the preprocessor can only be passed to
+//! `multipars_finish()`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use tokio::runtime::Runtime;
+
+use crate::session::{self, LimbPreprocessor, SessionError};
+
+/// Triple generation for one party.
+pub struct MultiparsPreprocessor {
+    runtime: Runtime,
+    inner: Box<dyn LimbPreprocessor>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(msg: impl ToString) {
+    LAST_ERROR.with(|last_error| {
+        *last_error.borrow_mut() = CString::new(msg.to_string()).ok();
+    });
+}
+
+/// Runs `f` and returns `on_panic` if it panics, so that no panic unwinds across the C boundary.
+fn catch_panic<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let msg = match (
+            payload.downcast_ref::<&str>(),
+            payload.downcast_ref::<String>(),
+        ) {
+            (Some(msg), _) => *msg,
+            (_, Some(msg)) => msg.as_str(),
+            _ => "unknown cause",
+        };
+        set_last_error(format!("panicked: {}", msg));
+        on_panic
+    })
+}
+
+/// Returns whether `out_len` is `n * factor * limbs` (without overflow), and sets the last error
+/// otherwise.
+fn check_out_len(n: usize, factor: usize, limbs: usize, out_len: usize, name: &str) -> bool {
+    let len = n.checked_mul(factor).and_then(|len| len.checked_mul(limbs));
+    if len != Some(out_len) {
+        set_last_error(format!("out must have length n * {} * {}()", factor, name));
+        return false;
+    }
+    true
+}
+
+unsafe fn to_str<'a>(s: *const c_char, name: &str) -> Option<&'a str> {
+    if s.is_null() {
+        set_last_error(format!("{} is null", name));
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(err) => {
+            set_last_error(format!("{}: {}", name, err));
+            None
+        }
+    }
+}
+
+/// Connects to the other party and sets up the preprocessing.  Both parties must pass the same `k`,
+/// `s` and `toy`.  `budget` is the number of triples that are generated ahead of time, and
+/// `threads` is the number of worker threads, which must be positive.
+///
+/// Returns null on failure.  Otherwise, the result must be passed to `multipars_finish()`.
+///
+/// # Safety
+///
+/// `local_addr` and `remote_addr` must be null or point to NUL-terminated strings.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn multipars_init(
+    player: u32,
+    local_addr: *const c_char,
+    remote_addr: *const c_char,
+    k: u32,
+    s: u32,
+    toy: bool,
+    budget: usize,
+    threads: usize,
+) -> *mut MultiparsPreprocessor {
+    catch_panic(ptr::null_mut(), || {
+        let (Some(local_addr), Some(remote_addr)) = (
+            to_str(local_addr, "local_addr"),
+            to_str(remote_addr, "remote_addr"),
+        ) else {
+            return ptr::null_mut();
+        };
+        match session::connect(
+            player as usize,
+            local_addr,
+            remote_addr,
+            k as usize,
+            s as usize,
+            toy,
+            budget,
+            threads,
+        ) {
+            Ok((runtime, inner)) => {
+                Box::into_raw(Box::new(MultiparsPreprocessor { runtime, inner }))
+            }
+            Err(err) => {
+                set_last_error(err);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Returns the number of limbs per residue of a triple.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_limbs(preproc: *const MultiparsPreprocessor) -> usize {
+    (*preproc).inner.limbs()
+}
+
+/// Returns the number of triples per packed plaintext of the parameters.  Requesting multiples of
+/// it avoids discarding slots.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_packing_capacity(
+    preproc: *const MultiparsPreprocessor,
+) -> usize {
+    (*preproc).inner.param_info().packing_capacity
+}
+
+/// Returns the number of triples per packed plaintext of the parameters that `multipars_init()`
+/// selects for `k`, `s` and `toy`, so that buffers can be sized before connecting.
+///
+/// Returns 0 if the parameters are not supported.
+#[no_mangle]
+pub extern "C" fn multipars_param_packing_capacity(k: u32, s: u32, toy: bool) -> usize {
+    catch_panic(0, || {
+        match session::param_info(k as usize, s as usize, toy) {
+            Some(info) => info.packing_capacity,
+            None => {
+                set_last_error(SessionError::UnsupportedParameters);
+                0
+            }
+        }
+    })
+}
+
+/// Writes `n` triples to `out`.  Each triple consists of the shares of a, b and c, each of which
+/// consists of the value and the MAC tag, each of which consists of `multipars_limbs()` limbs.
+/// Hence, `out_len` must be `n * 6 * multipars_limbs()`.
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+/// be valid for writing `out_len` values.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_get_triples(
+    preproc: *mut MultiparsPreprocessor,
+    n: usize,
+    out: *mut u64,
+    out_len: usize,
+) -> i32 {
+    let preproc = &mut *preproc;
+    if out.is_null() || !check_out_len(n, 6, preproc.inner.limbs(), out_len, "multipars_limbs") {
+        return -1;
+    }
+    let out = slice::from_raw_parts_mut(out, out_len);
+    catch_panic(-1, || {
+        preproc.runtime.block_on(preproc.inner.get_triples(n, out));
+        0
+    })
+}
+
+/// Like `multipars_get_triples()`, but writes the triples column by column, i.e., first the `n`
+/// values of a, then the `n` MAC tags of a, then the values and MAC tags of b and c.  Each value and
+/// MAC tag consists of `multipars_limbs()` limbs.  Hence, `out_len` must be
+/// `n * 6 * multipars_limbs()`.
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+/// be valid for writing `out_len` values.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_get_triple_columns(
+    preproc: *mut MultiparsPreprocessor,
+    n: usize,
+    out: *mut u64,
+    out_len: usize,
+) -> i32 {
+    let preproc = &mut *preproc;
+    if out.is_null() || !check_out_len(n, 6, preproc.inner.limbs(), out_len, "multipars_limbs") {
+        return -1;
+    }
+    let out = slice::from_raw_parts_mut(out, out_len);
+    catch_panic(-1, || {
+        preproc
+            .runtime
+            .block_on(preproc.inner.get_triple_columns(n, out));
+        0
+    })
+}
+
+/// Returns the number of limbs per share of a triple without MAC tags, see
+/// `multipars_get_raw_triples()`.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_raw_limbs(preproc: *const MultiparsPreprocessor) -> usize {
+    (*preproc).inner.raw_limbs()
+}
+
+/// Writes `n` triples without MAC tags to `out`, e.g., for semi-honest engines.  Each triple
+/// consists of the shares of a, b and c modulo `2^k`, each of which consists of
+/// `multipars_raw_limbs()` limbs.  Hence, `out_len` must be `n * 3 * multipars_raw_limbs()`.
+///
+/// If `verify` is true, `2 n + 1` triples are consumed to check the triples by sacrificing before
+/// the MAC tags are dropped.  Both parties must pass the same `n` and `verify`.
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+/// be valid for writing `out_len` values.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_get_raw_triples(
+    preproc: *mut MultiparsPreprocessor,
+    n: usize,
+    verify: bool,
+    out: *mut u64,
+    out_len: usize,
+) -> i32 {
+    let preproc = &mut *preproc;
+    let limbs = preproc.inner.raw_limbs();
+    if out.is_null() || !check_out_len(n, 3, limbs, out_len, "multipars_raw_limbs") {
+        return -1;
+    }
+    let out = slice::from_raw_parts_mut(out, out_len);
+    catch_panic(-1, || {
+        match preproc
+            .runtime
+            .block_on(preproc.inner.get_raw_triples(n, verify, out))
+        {
+            Ok(()) => 0,
+            Err(err) => {
+                set_last_error(err);
+                -1
+            }
+        }
+    })
+}
+
+/// Writes the limbs of this party's share of the MAC key to `out`.
+///
+/// Returns the number of limbs of the MAC key on success and -1 if `out_len` is too small.
+///
+/// # Safety
+///
+/// `preproc` must have been returned by `multipars_init()` and not yet been finished.  `out` must
+/// be valid for writing `out_len` values.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_get_mac_key_share(
+    preproc: *const MultiparsPreprocessor,
+    out: *mut u64,
+    out_len: usize,
+) -> i64 {
+    let mac_key = (*preproc).inner.mac_key();
+    if out.is_null() || out_len < mac_key.len() {
+        set_last_error(format!("out must have length {}", mac_key.len()));
+        return -1;
+    }
+    slice::from_raw_parts_mut(out, mac_key.len()).copy_from_slice(mac_key);
+    mac_key.len() as i64
+}
+
+/// Closes the channels to the other party and frees `preproc`.
+///
+/// # Safety
+///
+/// `preproc` must be null or have been returned by `multipars_init()` and not yet been finished.
+#[no_mangle]
+pub unsafe extern "C" fn multipars_finish(preproc: *mut MultiparsPreprocessor) {
+    if preproc.is_null() {
+        return;
+    }
+    let MultiparsPreprocessor { runtime, inner } = *Box::from_raw(preproc);
+    catch_panic((), || runtime.block_on(inner.finish()));
+}
+
+/// Returns the message of the last error on this thread, or null.  The string is valid until the
+/// next call of a function of this API on this thread.
+#[no_mangle]
+pub extern "C" fn multipars_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::low_gear_preproc::param_info::ParamInfo;
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+
+    const P0_ADDR: &str = "[::1]:50159";
+    const P1_ADDR: &str = "[::1]:50160";
+
+    fn c_string(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    fn last_error() -> String {
+        let msg = multipars_last_error();
+        assert!(!msg.is_null());
+        unsafe { CStr::from_ptr(msg) }.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn init_rejects_invalid_arguments() {
+        let (p0_addr, p1_addr) = (c_string(P0_ADDR), c_string(P1_ADDR));
+        unsafe {
+            let preproc = multipars_init(0, ptr::null(), p1_addr.as_ptr(), 32, 32, true, 0, 1);
+            assert!(preproc.is_null());
+            assert_eq!(last_error(), "local_addr is null");
+
+            let preproc = multipars_init(0, p0_addr.as_ptr(), p1_addr.as_ptr(), 32, 32, true, 0, 0);
+            assert!(preproc.is_null());
+            assert_eq!(last_error(), "InvalidThreads");
+        }
+    }
+
+    #[test]
+    fn param_packing_capacity() {
+        assert_eq!(
+            multipars_param_packing_capacity(32, 32, true),
+            ParamInfo::of::<ToyPreprocK32S32>().packing_capacity
+        );
+        assert_eq!(multipars_param_packing_capacity(32, 64, true), 0);
+        assert_eq!(last_error(), "UnsupportedParameters");
+    }
+
+    #[test]
+    fn catches_panics() {
+        assert_eq!(catch_panic(-1, || panic!("at the disco")), -1);
+        assert_eq!(last_error(), "panicked: at the disco");
+    }
+
+    #[test]
+    fn get_triples() {
+        let run = |player, local_addr, remote_addr| {
+            let (local_addr, remote_addr) = (c_string(local_addr), c_string(remote_addr));
+            thread::spawn(move || unsafe {
+                let preproc = multipars_init(
+                    player,
+                    local_addr.as_ptr(),
+                    remote_addr.as_ptr(),
+                    32,
+                    32,
+                    true,
+                    0,
+                    1,
+                );
+                assert!(!preproc.is_null());
+                let limbs = multipars_limbs(preproc);
+                let mut out = vec![0; 10 * 6 * limbs];
+
+                // A length that overflows is rejected before any triples are drawn.
+                let huge = usize::MAX / 2;
+                let ret = multipars_get_triples(preproc, huge, out.as_mut_ptr(), out.len());
+                assert_eq!(ret, -1);
+                assert_eq!(
+                    last_error(),
+                    "out must have length n * 6 * multipars_limbs()"
+                );
+                let ret = multipars_get_raw_triples(preproc, huge, false, out.as_mut_ptr(), 0);
+                assert_eq!(ret, -1);
+
+                let ret = multipars_get_triples(preproc, 10, out.as_mut_ptr(), out.len());
+                assert_eq!(ret, 0);
+                let mut columns = vec![0; 10 * 6 * limbs];
+                let ret =
+                    multipars_get_triple_columns(preproc, 10, columns.as_mut_ptr(), columns.len());
+                assert_eq!(ret, 0);
+                multipars_finish(preproc);
+                (out, columns)
+            })
+        };
+        let p0 = run(0, P0_ADDR, P1_ADDR);
+        let p1 = run(1, P1_ADDR, P0_ADDR);
+        let ((out0, columns0), (out1, columns1)) = (p0.join().unwrap(), p1.join().unwrap());
+        assert_ne!(out0, out1);
+        assert!(out0.iter().any(|&limb| limb != 0));
+        assert_ne!(columns0, columns1);
+        assert!(columns0.iter().any(|&limb| limb != 0));
+    }
+}
@@ -0,0 +1,71 @@
+//! Entry points of the fuzz targets under `fuzz/`, which feed arbitrary bytes into the
+//! deserializers of the wire messages (see `cargo fuzz list`).
+//!
+//! Each entry point decodes the bytes like the receiving party does and, if that succeeds, checks
+//! that the value encodes and decodes again.  Any panic, e.g., an out-of-bounds index or a failed
+//! allocation, is a finding.  The messages use the toy parameters, since the deserializers do not
+//! depend on the size of the parameters.
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::bgv::params::ToyBgv;
+use crate::bgv::zkpopk::{Commitment, Response};
+use crate::bgv::{Ciphertext, PreCiphertext};
+use crate::codec;
+
+/// Decodes a message of a stream (see `codec::deserialize_framed()`) and checks the roundtrip.
+pub(crate) fn framed_roundtrip<T>(bytes: &[u8])
+where
+    T: Serialize + DeserializeOwned,
+{
+    let value = match codec::deserialize_framed::<T>(bytes) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    let encoded = bincode::options().serialize(&value).unwrap();
+    let decoded = codec::deserialize_framed::<T>(&encoded).expect("failed to decode an encoding");
+    assert_eq!(bincode::options().serialize(&decoded).unwrap(), encoded);
+}
+
+/// A message between the `LowGearDealer`s.
+pub fn dealer_message(bytes: &[u8]) {
+    crate::low_gear_dealer::fuzz_message(bytes);
+}
+
+/// The opening of the commitment of the `Truncer`.
+pub fn truncer_opening(bytes: &[u8]) {
+    crate::low_gear_preproc::truncer::fuzz_opening(bytes);
+}
+
+/// A frame of a `Multiplexer`, whose payload is decoded as a ciphertext.
+pub fn mux_frame(bytes: &[u8]) {
+    crate::mux_channel::fuzz_frame(bytes);
+}
+
+/// The commitment of a ZKPoPK.
+pub fn zkpopk_commitment(bytes: &[u8]) {
+    framed_roundtrip::<Commitment<ToyBgv>>(bytes);
+}
+
+/// The response of a ZKPoPK.
+pub fn zkpopk_response(bytes: &[u8]) {
+    framed_roundtrip::<Response<ToyBgv>>(bytes);
+}
+
+/// A ciphertext in CRT basis and one in power basis.
+pub fn ciphertext(bytes: &[u8]) {
+    framed_roundtrip::<Ciphertext<ToyBgv>>(bytes);
+    framed_roundtrip::<PreCiphertext<ToyBgv>>(bytes);
+}
+
+/// The ID at the start of an incoming stream, see `Connection`.
+pub fn stream_id(bytes: &[u8]) {
+    let id = match codec::deserialize_stream_id(bytes) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
+    let encoded = bincode::options().serialize(&id).unwrap();
+    assert_eq!(codec::deserialize_stream_id(&encoded).unwrap(), id);
+}
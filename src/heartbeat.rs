@@ -0,0 +1,210 @@
+//! Application-level heartbeats, which tell whether the other party is alive during long batches.
+//!
+//! Each party pings the other party on a dedicated channel in a fixed interval and answers its
+//! pings.  A `ConnectionHealth` records when the other party was last heard of and estimates the
+//! round-trip time.  The heartbeats stop when all handles are dropped or the connection is closed.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// Time between two pings.
+    pub interval: Duration,
+    /// A warning is logged if the other party was not heard of for this long.
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+enum Heartbeat {
+    Ping(u32),
+    Pong(u32),
+}
+
+struct State {
+    started: Instant,
+    last_seen: Option<Instant>,
+    rtt: Option<Duration>,
+}
+
+/// Liveness of the other party as observed by the heartbeats, see `start()`.
+#[derive(Clone)]
+pub struct ConnectionHealth {
+    state: Arc<Mutex<State>>,
+}
+
+/// A snapshot of a `ConnectionHealth`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HealthSnapshot {
+    /// Time since the last message of the other party, or since the start if there was none.
+    pub silence: Duration,
+    /// Smoothed round-trip time, or `None` if no ping was answered yet.
+    pub rtt: Option<Duration>,
+}
+
+impl ConnectionHealth {
+    /// When the last message of the other party arrived.
+    pub fn last_seen(&self) -> Option<Instant> {
+        self.state.lock().unwrap().last_seen
+    }
+
+    /// Smoothed round-trip time of the pings, or `None` if no ping was answered yet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.state.lock().unwrap().rtt
+    }
+
+    /// Whether the other party was heard of within `timeout`.
+    pub fn is_alive(&self, timeout: Duration) -> bool {
+        self.snapshot().silence <= timeout
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        let state = self.state.lock().unwrap();
+        HealthSnapshot {
+            silence: state.last_seen.unwrap_or(state.started).elapsed(),
+            rtt: state.rtt,
+        }
+    }
+}
+
+/// Opens the heartbeat channel and starts sending heartbeats in the background.  Both parties must
+/// call this at the same point, since it opens a channel on `conn`.
+pub async fn start(
+    conn: &mut Connection,
+    config: HeartbeatConfig,
+) -> Result<ConnectionHealth, StreamError> {
+    let channel = BiChannel::open(conn, "Heartbeat").await?;
+    let state = Arc::new(Mutex::new(State {
+        started: Instant::now(),
+        last_seen: None,
+        rtt: None,
+    }));
+    tokio::task::spawn(run(channel, config, Arc::downgrade(&state)));
+    Ok(ConnectionHealth { state })
+}
+
+async fn run(channel: BiChannel<Heartbeat>, config: HeartbeatConfig, state: Weak<Mutex<State>>) {
+    let BiChannel {
+        mut reader,
+        mut writer,
+        ..
+    } = channel;
+    let mut ticker = tokio::time::interval(config.interval);
+    let mut seq = 0u32;
+    let mut outstanding: Option<(u32, Instant)> = None;
+    let mut warned = false;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let state = match state.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                let silence = {
+                    let state = state.lock().unwrap();
+                    state.last_seen.unwrap_or(state.started).elapsed()
+                };
+                if silence > config.timeout && !warned {
+                    warn!("Heartbeat: the other party was silent for {:?}", silence);
+                    warned = true;
+                }
+                seq = seq.wrapping_add(1);
+                // A ping that was not answered until the next one is not used for the RTT.
+                outstanding = Some((seq, Instant::now()));
+                if writer.send(Heartbeat::Ping(seq)).await.is_err() {
+                    return;
+                }
+            }
+            msg = reader.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    // The connection was closed or the other party stopped its heartbeats.
+                    _ => return,
+                };
+                let state = match state.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                let now = Instant::now();
+                let pong = {
+                    let mut state = state.lock().unwrap();
+                    state.last_seen = Some(now);
+                    warned = false;
+                    match msg {
+                        Heartbeat::Ping(n) => Some(n),
+                        Heartbeat::Pong(n) => {
+                            if let Some((sent_seq, sent)) = outstanding {
+                                if sent_seq == n {
+                                    let sample = now.duration_since(sent);
+                                    // Smoothed like the RTT of TCP (RFC 6298).
+                                    state.rtt = Some(match state.rtt {
+                                        Some(rtt) => (rtt * 7 + sample) / 8,
+                                        None => sample,
+                                    });
+                                    outstanding = None;
+                                }
+                            }
+                            None
+                        }
+                    }
+                };
+                if let Some(n) = pong {
+                    if writer.send(Heartbeat::Pong(n)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::connection::Connection;
+
+    use super::{start, HeartbeatConfig};
+
+    #[tokio::test]
+    async fn heartbeat() {
+        const P0_ADDR: &str = "[::1]:50085";
+        const P1_ADDR: &str = "[::1]:50086";
+
+        let config = HeartbeatConfig {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(1),
+        };
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (health0, health1) = tokio::join!(start(&mut conn0, config), start(&mut conn1, config));
+        let (health0, health1) = (health0.unwrap(), health1.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        for health in [&health0, &health1] {
+            assert!(health.last_seen().is_some());
+            assert!(health.rtt().is_some());
+            assert!(health.is_alive(config.timeout));
+        }
+    }
+}
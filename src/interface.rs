@@ -1,12 +1,113 @@
+use std::fmt::{self, Debug, Formatter};
+use std::iter::Sum;
 use std::marker::PhantomData;
 use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Shl, Shr, Sub, SubAssign};
 
 use async_trait::async_trait;
+use crypto_bigint::rand_core::CryptoRngCore;
 use forward_ref_generic::{forward_ref_binop, forward_ref_op_assign, forward_ref_unop};
+use serde::{Deserialize, Serialize};
 
 use crate::bgv::residue::native::GenericNativeResidue;
+use crate::role::Role;
+use crate::transcript::{SessionId, Transcript};
+use crate::util::zeroize;
+
+/// This party's share of the global MAC key, which is the sum of both parties' shares in the ring
+/// of the MAC tags.
+///
+/// In contrast to a bare residue, it cannot be serialized or printed and is zeroized when dropped.
+/// Use `expose_secret()` to export it deliberately.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MacKeyShare<S>(S)
+where
+    S: GenericNativeResidue;
+
+impl<S> MacKeyShare<S>
+where
+    S: GenericNativeResidue,
+{
+    pub fn random(rng: &mut impl CryptoRngCore) -> Self {
+        Self(S::random(rng))
+    }
+
+    /// Wraps a share that was sampled before, e.g., by the caller of a test or a restored run.
+    pub fn from_secret(share: S) -> Self {
+        Self(share)
+    }
+
+    pub fn expose_secret(&self) -> S {
+        self.0
+    }
+
+    /// Returns a hash of the share, e.g., to recognize the MAC key of saved triples without saving
+    /// the share itself.
+    ///
+    /// The hash hides the share only up to the `S::BITS` bits of its entropy, so for small `S`, it
+    /// must be kept as confidential as the triples that it belongs to.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut transcript = Transcript::new("MacKeyShare:digest");
+        transcript.append("share", &self.0);
+        transcript.session_id()
+    }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+    /// Lifts the share to a larger ring, e.g., `KS` of the MAC tags.
+    pub fn widen<W>(&self) -> W
+    where
+        W: GenericNativeResidue,
+    {
+        W::try_from_unsigned(self.0).expect("W is narrower than the MAC key")
+    }
+
+    /// Returns `value` times the share in `W`, see `GenericNativeResidue::mul_widening()`.
+    pub fn mul_widening<R, W>(&self, value: &R) -> W
+    where
+        R: GenericNativeResidue,
+        W: GenericNativeResidue,
+    {
+        value.mul_widening(&self.0)
+    }
+}
+
+impl<S> Debug for MacKeyShare<S>
+where
+    S: GenericNativeResidue,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("MacKeyShare(..)")
+    }
+}
+
+impl<S> Drop for MacKeyShare<S>
+where
+    S: GenericNativeResidue,
+{
+    fn drop(&mut self) {
+        zeroize([&mut self.0]);
+    }
+}
+
+/// The rings of SPDZ2k in one type, so that the types below can be named with a single parameter,
+/// e.g., `ShareOf<P, PID>` instead of `Share<KS, K, PID>`, which cannot mix up the rings of
+/// different parameter sets.  Every `PreprocessorParameters` implements it.
+pub trait SpdzParams: 'static {
+    /// Ring of the values, i.e., `Z_{2^k}`.
+    type K: GenericNativeResidue;
+    /// Ring of the MAC key, i.e., `Z_{2^s}`.
+    type S: GenericNativeResidue;
+    /// Ring of the shares and MAC tags, i.e., `Z_{2^{k+s}}`.
+    type KS: GenericNativeResidue;
+    /// Ring in which the products are computed before truncation, i.e., `Z_{2^{k+2s}}`.
+    type KSS: GenericNativeResidue;
+}
+
+pub type MacKeyOf<P> = MacKeyShare<<P as SpdzParams>::S>;
+pub type ShareOf<P, const PID: usize> = Share<<P as SpdzParams>::KS, <P as SpdzParams>::K, PID>;
+pub type TripleOf<P, const PID: usize> =
+    BeaverTriple<<P as SpdzParams>::KS, <P as SpdzParams>::K, PID>;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(bound(deserialize = ""))]
 pub struct Share<KS, K, const PID: usize>
 where
     KS: GenericNativeResidue,
@@ -20,7 +121,8 @@ where
     pub phantom: PhantomData<K>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(deserialize = ""))]
 pub struct BeaverTriple<KS, K, const PID: usize>
 where
     KS: GenericNativeResidue,
@@ -32,6 +134,90 @@ where
     pub phantom: PhantomData<K>,
 }
 
+/// A `BeaverTriple` without MAC tags, for semi-honest deployments, see `BeaverTriple::strip_macs()`.
+///
+/// The shares are reduced modulo `2^k`, so it takes less than half the space of a `BeaverTriple`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(bound(deserialize = ""))]
+pub struct RawTriple<K, const PID: usize>
+where
+    K: GenericNativeResidue,
+{
+    pub a: K,
+    pub b: K,
+    pub c: K,
+}
+
+/// `BeaverTriple`s in structure-of-arrays form, i.e., with one contiguous vector per component.
+///
+/// Compared to a `Vec<BeaverTriple>`, this allows exporting each component with a single copy,
+/// e.g. to files or via FFI.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(bound(deserialize = ""))]
+pub struct TripleBatch<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    a_vals: Vec<KS>,
+    a_tags: Vec<KS>,
+    b_vals: Vec<KS>,
+    b_tags: Vec<KS>,
+    c_vals: Vec<KS>,
+    c_tags: Vec<KS>,
+    phantom: PhantomData<K>,
+}
+
+/// Authenticated arithmetic shares of the XOR shares `b_0` and `b_1` of a bit `b = b_0 XOR b_1`,
+/// where party `i` holds `b_i`, see `edabit::open_booleans()`.
+pub type XorShares<KS, K, const PID: usize> = [Share<KS, K, PID>; 2];
+
+/// A random bit `b` that is shared both arithmetically (authenticated) and as XOR shares.
+///
+/// Binary MACs are not supported yet, so the XOR shares are authenticated in the arithmetic domain
+/// instead: `xor_shares` bind the boolean share of each party to the MAC key.
+#[derive(Clone, Copy, Debug)]
+pub struct DaBit<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    pub arith: Share<KS, K, PID>,
+    pub boolean: bool,
+    pub xor_shares: XorShares<KS, K, PID>,
+}
+
+/// A random value `r = \sum_i 2^i b_i` that is shared arithmetically, together with XOR shares of
+/// its bits `b_i` (least significant first) and their authentication, see `DaBit`.
+#[derive(Clone, Debug)]
+pub struct EdaBit<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    pub arith: Share<KS, K, PID>,
+    pub booleans: Vec<bool>,
+    pub xor_shares: Vec<XorShares<KS, K, PID>>,
+}
+
+/// A random value `r = \sum_i 2^i r_i` that is shared arithmetically, together with authenticated
+/// arithmetic shares and XOR shares of its bits `r_i` (least significant first).
+///
+/// This is the material that online protocols (e.g. comparisons) need for converting a share `[x]`
+/// to bit-decomposed form: open `x + r`, decompose the public value, and combine it with the bits
+/// of `r`.  The converse direction is `Share::compose()`.
+#[derive(Clone, Debug)]
+pub struct BitDecomposition<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    pub arith: Share<KS, K, PID>,
+    pub bits: Vec<Share<KS, K, PID>>,
+    pub booleans: Vec<bool>,
+    pub xor_shares: Vec<XorShares<KS, K, PID>>,
+}
+
 #[async_trait]
 pub trait Preprocessor<KS, K, const PID: usize>
 where
@@ -58,6 +244,49 @@ where
     async fn finish(self);
 }
 
+#[async_trait]
+pub trait BitPreprocessor<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    /// Returns `n` `DaBit`s
+    async fn get_dabits(&mut self, n: usize) -> Vec<DaBit<KS, K, PID>>;
+
+    /// Returns `n` `EdaBit`s of `len` bits each
+    async fn get_edabits(&mut self, n: usize, len: usize) -> Vec<EdaBit<KS, K, PID>>;
+
+    /// Returns `n` `BitDecomposition`s of `len` bits each
+    async fn get_bit_decompositions(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Vec<BitDecomposition<KS, K, PID>>;
+}
+
+#[async_trait]
+pub trait ZeroSharePreprocessor<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    /// Returns `n` authenticated sharings of zero with random MAC tag shares, e.g. for
+    /// re-randomization
+    async fn get_zero_shares(&mut self, n: usize) -> Vec<Share<KS, K, PID>>;
+}
+
+/// The MAC key share and the session that the triples of a preprocessor belong to, e.g., to store
+/// them together with persisted triples.
+pub trait TripleOrigin<KS>
+where
+    KS: GenericNativeResidue,
+{
+    /// This party's share of the MAC key, lifted to `KS`.
+    fn mac_key_share(&self) -> KS;
+
+    fn session_id(&self) -> SessionId;
+}
+
 pub fn get_batch_size<Preproc, KS, K, const PID: usize>(_preproc: &Preproc) -> usize
 where
     Preproc: BatchedPreprocessor<KS, K, PID>,
@@ -80,6 +309,119 @@ where
             phantom: PhantomData,
         }
     }
+
+    /// Drops the MAC tags and the upper bits of the shares.  Afterwards, the triple can no longer
+    /// be checked, so triples of an untrusted source should be verified before, see
+    /// `TripleVerifier::verify_and_strip()`.
+    pub fn strip_macs(&self) -> RawTriple<K, PID> {
+        RawTriple {
+            a: K::from_unsigned(self.a.val),
+            b: K::from_unsigned(self.b.val),
+            c: K::from_unsigned(self.c.val),
+        }
+    }
+}
+
+impl<KS, K, const PID: usize> TripleBatch<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            a_vals: Vec::with_capacity(capacity),
+            a_tags: Vec::with_capacity(capacity),
+            b_vals: Vec::with_capacity(capacity),
+            b_tags: Vec::with_capacity(capacity),
+            c_vals: Vec::with_capacity(capacity),
+            c_tags: Vec::with_capacity(capacity),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.a_vals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.a_vals.is_empty()
+    }
+
+    pub fn push(&mut self, triple: &BeaverTriple<KS, K, PID>) {
+        self.a_vals.push(triple.a.val);
+        self.a_tags.push(triple.a.tag);
+        self.b_vals.push(triple.b.val);
+        self.b_tags.push(triple.b.tag);
+        self.c_vals.push(triple.c.val);
+        self.c_tags.push(triple.c.tag);
+    }
+
+    pub fn get(&self, index: usize) -> Option<BeaverTriple<KS, K, PID>> {
+        (index < self.len()).then(|| {
+            BeaverTriple::new(
+                Share::new(self.a_vals[index], self.a_tags[index]),
+                Share::new(self.b_vals[index], self.b_tags[index]),
+                Share::new(self.c_vals[index], self.c_tags[index]),
+            )
+        })
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = BeaverTriple<KS, K, PID>> + '_ {
+        // `unwrap()` cannot fail, because the index is in range.
+        (0..self.len()).map(move |index| self.get(index).unwrap())
+    }
+
+    /// Returns the components in the order `a_vals`, `a_tags`, `b_vals`, `b_tags`, `c_vals`,
+    /// `c_tags`.
+    pub fn columns(&self) -> [&[KS]; 6] {
+        [
+            &self.a_vals,
+            &self.a_tags,
+            &self.b_vals,
+            &self.b_tags,
+            &self.c_vals,
+            &self.c_tags,
+        ]
+    }
+
+    /// Returns the columns of a, b and c without MAC tags, see `BeaverTriple::strip_macs()`.
+    pub fn strip_macs(&self) -> [Vec<K>; 3] {
+        [&self.a_vals, &self.b_vals, &self.c_vals]
+            .map(|vals| vals.iter().map(|val| K::from_unsigned(*val)).collect())
+    }
+
+    /// Inverse of `columns()`.  Returns `None` if the columns differ in length.
+    pub fn from_columns(columns: [Vec<KS>; 6]) -> Option<Self> {
+        let len = columns[0].len();
+        if columns.iter().any(|column| column.len() != len) {
+            return None;
+        }
+        let [a_vals, a_tags, b_vals, b_tags, c_vals, c_tags] = columns;
+        Some(Self {
+            a_vals,
+            a_tags,
+            b_vals,
+            b_tags,
+            c_vals,
+            c_tags,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<KS, K, const PID: usize> FromIterator<BeaverTriple<KS, K, PID>> for TripleBatch<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    fn from_iter<I: IntoIterator<Item = BeaverTriple<KS, K, PID>>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut batch = Self::with_capacity(iter.size_hint().0);
+        for triple in iter {
+            batch.push(&triple);
+        }
+        batch
+    }
 }
 
 impl<KS, K, const PID: usize> Share<KS, K, PID>
@@ -89,6 +431,9 @@ where
 {
     pub const ZERO: Self = Self::new(KS::ZERO, KS::ZERO);
 
+    /// The role of the party that holds this share.
+    pub const ROLE: Role = Role::of::<PID>();
+
     pub const fn new(val: KS, tag: KS) -> Self {
         Self {
             val,
@@ -96,6 +441,90 @@ where
             phantom: PhantomData,
         }
     }
+
+    /// Adds the public value `value`, including the MAC tag (for which this party's share of the
+    /// MAC key is needed).
+    pub fn add_public<S>(mut self, value: K, mac_key: &MacKeyShare<S>) -> Self
+    where
+        S: GenericNativeResidue,
+    {
+        let value = KS::from_unsigned(value);
+        if Self::ROLE.is_p0() {
+            self.val += value;
+        }
+        self.tag += value * mac_key.widen::<KS>();
+        self
+    }
+
+    /// A share of the public value `value` with a correct MAC tag, unlike `From<K>`.
+    pub fn public<S>(value: K, mac_key: &MacKeyShare<S>) -> Self
+    where
+        S: GenericNativeResidue,
+    {
+        Self::ZERO.add_public(value, mac_key)
+    }
+
+    /// Computes `\sum_i c_i x_i + constant` for the public coefficients `c_i` and the shares `x_i`.
+    /// Like `add_public()`, this needs this party's share of the MAC key for the constant.
+    pub fn affine<S>(
+        terms: impl IntoIterator<Item = (K, Self)>,
+        constant: K,
+        mac_key: &MacKeyShare<S>,
+    ) -> Self
+    where
+        S: GenericNativeResidue,
+    {
+        terms
+            .into_iter()
+            .map(|(coeff, share)| share * coeff)
+            .sum::<Self>()
+            .add_public(constant, mac_key)
+    }
+
+    /// Composes shares of the bits `b_i` (least significant first) to a share of `\sum_i 2^i b_i`.
+    pub fn compose(bits: &[Self]) -> Self {
+        bits.iter()
+            .enumerate()
+            .fold(Self::ZERO, |acc, (i, bit)| acc + (*bit << i))
+    }
+
+    /// Views the share as the share of the party `OTHER`, e.g., to reconstruct values in tests.
+    ///
+    /// The components are unchanged, so the sum of the shares of both parties stays the same.  The
+    /// result must not be used in a protocol, since the parties add public values differently.
+    pub fn as_pid<const OTHER: usize>(self) -> Share<KS, K, OTHER> {
+        Share::new(self.val, self.tag)
+    }
+}
+
+/// Reconstructs the value of the shares of both parties, without checking the MAC tag (see
+/// `verify::check_mac()`).
+pub fn reconstruct<KS, K>(share_0: &Share<KS, K, 0>, share_1: &Share<KS, K, 1>) -> K
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    K::from_unsigned(share_0.val + share_1.val)
+}
+
+impl<KS, K, const PID: usize> Sum for Share<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |acc, share| acc + share)
+    }
+}
+
+impl<'a, KS, K, const PID: usize> Sum<&'a Self> for Share<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |acc, share| acc + share)
+    }
 }
 
 impl<KS, K, const PID: usize> From<K> for Share<KS, K, PID>
@@ -105,7 +534,7 @@ where
 {
     fn from(cleartext: K) -> Self {
         Self::new(
-            if PID == 0 {
+            if Self::ROLE.is_p0() {
                 KS::from_uint(cleartext.retrieve())
             } else {
                 KS::ZERO
@@ -322,3 +751,152 @@ where
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{Random, Zero};
+
+    use crate::bgv::residue::native::{GenericNativeResidue, NativeResidue};
+    use crate::bgv::residue::GenericResidue;
+
+    use crate::verify::check_mac;
+
+    use super::{reconstruct, BeaverTriple, MacKeyShare, Share, TripleBatch};
+
+    type K = NativeResidue<32, 1>;
+    type KS = NativeResidue<64, 1>;
+    type S = NativeResidue<32, 1>;
+
+    /// Authenticated shares of `value` under the MAC key `mac_key_0 + mac_key_1`.
+    fn share(
+        value: K,
+        mac_key_0: &MacKeyShare<S>,
+        mac_key_1: &MacKeyShare<S>,
+    ) -> (Share<KS, K, 0>, Share<KS, K, 1>) {
+        let mut rng = rand::thread_rng();
+        let value = KS::from_unsigned(value);
+        let tag = value * (mac_key_0.widen::<KS>() + mac_key_1.widen::<KS>());
+        let share_0 = Share::new(KS::random(&mut rng), KS::random(&mut rng));
+        let share_1 = Share::new(value - share_0.val, tag - share_0.tag);
+        (share_0, share_1)
+    }
+
+    #[test]
+    fn public_constants() {
+        let mut rng = rand::thread_rng();
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let (x, c) = (K::random(&mut rng), K::random(&mut rng));
+        let (x0, x1) = share(x, &mac_keys[0], &mac_keys[1]);
+
+        let (c0, c1) = (
+            Share::<KS, K, 0>::public(c, &mac_keys[0]),
+            Share::<KS, K, 1>::public(c, &mac_keys[1]),
+        );
+        assert_eq!(reconstruct(&c0, &c1), c);
+        assert!(check_mac(&c0, &c1, &mac_keys[0], &mac_keys[1]));
+
+        let (y0, y1) = (x0 * c, x1 * c);
+        assert_eq!(reconstruct(&y0, &y1), x * c);
+        assert!(check_mac(&y0, &y1, &mac_keys[0], &mac_keys[1]));
+    }
+
+    #[test]
+    fn affine_and_sum() {
+        let mut rng = rand::thread_rng();
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let values: Vec<_> = (0..5).map(|_| K::random(&mut rng)).collect();
+        let coeffs: Vec<_> = (0..5).map(|_| K::random(&mut rng)).collect();
+        let constant = K::random(&mut rng);
+        let (shares0, shares1): (Vec<_>, Vec<_>) = values
+            .iter()
+            .map(|value| share(*value, &mac_keys[0], &mac_keys[1]))
+            .unzip();
+
+        let sum0: Share<KS, K, 0> = shares0.iter().sum();
+        let sum1: Share<KS, K, 1> = shares1.iter().copied().sum();
+        let expected = values.iter().fold(K::ZERO, |acc, value| acc + *value);
+        assert_eq!(reconstruct(&sum0, &sum1), expected);
+        assert!(check_mac(&sum0, &sum1, &mac_keys[0], &mac_keys[1]));
+
+        let affine0 = Share::affine(
+            coeffs.iter().copied().zip(shares0.iter().copied()),
+            constant,
+            &mac_keys[0],
+        );
+        let affine1 = Share::affine(
+            coeffs.iter().copied().zip(shares1.iter().copied()),
+            constant,
+            &mac_keys[1],
+        );
+        let expected = coeffs
+            .iter()
+            .zip(&values)
+            .fold(constant, |acc, (coeff, value)| acc + *coeff * *value);
+        assert_eq!(reconstruct(&affine0, &affine1), expected);
+        assert!(check_mac(&affine0, &affine1, &mac_keys[0], &mac_keys[1]));
+    }
+
+    #[test]
+    fn viewpoints() {
+        let mut rng = rand::thread_rng();
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let x = K::random(&mut rng);
+        let (x0, x1) = share(x, &mac_keys[0], &mac_keys[1]);
+
+        // Swapping the viewpoints of both shares preserves the value and the MAC.
+        let (y0, y1) = (x1.as_pid::<0>(), x0.as_pid::<1>());
+        assert_eq!(reconstruct(&y0, &y1), x);
+        assert!(check_mac(&y0, &y1, &mac_keys[1], &mac_keys[0]));
+        assert_eq!(y1.as_pid::<0>(), x0);
+    }
+
+    #[test]
+    fn triple_batch_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let mut share = || Share::<KS, K, 0>::new(KS::random(&mut rng), KS::random(&mut rng));
+        let triples: Vec<_> = (0..10)
+            .map(|_| BeaverTriple::new(share(), share(), share()))
+            .collect();
+
+        let batch: TripleBatch<KS, K, 0> = triples.iter().cloned().collect();
+        assert_eq!(batch.len(), 10);
+        for (actual, expected) in batch.iter().zip(&triples) {
+            assert_eq!(actual.a, expected.a);
+            assert_eq!(actual.b, expected.b);
+            assert_eq!(actual.c, expected.c);
+        }
+        assert!(batch.get(10).is_none());
+
+        let columns = batch.columns().map(<[KS]>::to_vec);
+        assert_eq!(columns[3][7], triples[7].b.tag);
+        assert_eq!(TripleBatch::from_columns(columns), Some(batch));
+
+        let mut uneven = [(); 6].map(|_| vec![KS::random(&mut rng); 3]);
+        uneven[5].pop();
+        assert_eq!(TripleBatch::<KS, K, 0>::from_columns(uneven), None);
+    }
+
+    #[test]
+    fn strip_macs() {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (KS::random(&mut rng), KS::random(&mut rng));
+        // Shares of `a`, `b` and `a b` with garbage in the upper bits of the second party's shares.
+        let shares0 = [(); 3].map(|_| KS::random(&mut rng));
+        let mut shares1 = [a, b, a * b];
+        for (x1, x0) in shares1.iter_mut().zip(shares0) {
+            *x1 = *x1 - x0 + KS::random(&mut rng).shl_vartime(K::BITS);
+        }
+        let [a0, b0, c0] = shares0.map(|x0| Share::<KS, K, 0>::new(x0, KS::random(&mut rng)));
+        let [a1, b1, c1] = shares1.map(|x1| Share::<KS, K, 1>::new(x1, KS::random(&mut rng)));
+
+        let raw0 = BeaverTriple::new(a0, b0, c0).strip_macs();
+        let raw1 = BeaverTriple::new(a1, b1, c1).strip_macs();
+        assert_eq!((raw0.a + raw1.a) * (raw0.b + raw1.b), raw0.c + raw1.c);
+
+        let batch: TripleBatch<KS, K, 0> = [BeaverTriple::new(a0, b0, c0)].into_iter().collect();
+        assert_eq!(
+            batch.strip_macs(),
+            [vec![raw0.a], vec![raw0.b], vec![raw0.c]]
+        );
+    }
+}
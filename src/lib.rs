@@ -1,95 +1,86 @@
-#![feature(associated_const_equality)]
+#![cfg_attr(feature = "nightly", feature(associated_const_equality))]
 
+// The parameters are given in 64-bit limbs, e.g., `Uint::<5>` for a 320-bit modulus and
+// `NativeResidue<64, 1>`, and `crypto-bigint` uses 32-bit limbs on 32-bit targets.  Without this
+// check, the build fails with obscure errors in the const evaluation of the moduli.  The byte
+// order does not matter: the limbs are serialized as little-endian bytes on all targets.
+#[cfg(not(target_pointer_width = "64"))]
+compile_error!(
+    "multipars requires a 64-bit target (`target_pointer_width = \"64\"`), since its parameters \
+     are given in 64-bit limbs; 32-bit targets such as ARM32 and wasm32 are not supported"
+);
+
+#[cfg(feature = "protocol")]
+pub mod abort;
 pub mod bgv;
+#[cfg(feature = "protocol")]
 pub mod bi_channel;
+#[cfg(feature = "protocol")]
 pub mod buffered_preproc;
+pub mod codec;
+pub mod commitment;
+#[cfg(feature = "protocol")]
 pub mod connection;
+#[cfg(feature = "protocol")]
+pub mod context_set;
+pub mod crypto_suite;
+#[cfg(feature = "protocol")]
+pub mod dealer;
+#[cfg(feature = "protocol")]
+pub mod edabit;
+#[cfg(feature = "enclave")]
+pub mod enclave;
+#[cfg(all(test, feature = "protocol"))]
+mod fault_injection;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "protocol")]
+pub mod heartbeat;
 pub mod interface;
+#[cfg(feature = "protocol")]
+pub mod lockstep;
+#[cfg(feature = "protocol")]
 pub mod low_gear_dealer;
+#[cfg(feature = "protocol")]
 pub mod low_gear_preproc;
+#[cfg(feature = "protocol")]
 pub mod mac_check_opener;
+#[cfg(feature = "protocol")]
+pub mod mux_channel;
+#[cfg(feature = "protocol")]
+pub mod ole;
+#[cfg(feature = "protocol")]
 pub mod oneshot_map;
+#[cfg(feature = "protocol")]
+pub mod orchestrator;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "protocol")]
+pub mod rate_limiter;
+pub mod rng;
+pub mod role;
+#[cfg(feature = "protocol")]
+pub mod round_channel;
+#[cfg(feature = "protocol")]
+pub mod rss_bridge;
+pub mod sampling;
+#[cfg(feature = "protocol")]
+pub mod selftest;
+#[cfg(feature = "service-grpc")]
+pub mod service;
+#[cfg(any(feature = "ffi", feature = "python"))]
+mod session;
+#[cfg(all(test, feature = "protocol"))]
+mod testdata;
+#[cfg(feature = "protocol")]
+pub mod third_party_dealer;
+pub mod transcript;
+#[cfg(feature = "protocol")]
+pub mod triple_audit;
+#[cfg(feature = "protocol")]
+pub mod triple_verifier;
 pub mod util;
+pub mod verify;
 pub mod zero_preproc;
-
-pub mod examples {
-    use std::error::Error;
-    use std::time::Instant;
-
-    use log::info;
-
-    use crate::connection::Connection;
-    use crate::interface::BatchedPreprocessor;
-    use crate::low_gear_preproc::{self, LowGearPreprocessor, PreprocessorParameters};
-    use crate::util::resolve_host;
-
-    pub async fn low_gear<PreprocParams, const PID: usize>(
-        local: &str,
-        remote: &str,
-        num_threads: usize,
-        num_batches: usize,
-    ) -> Result<(), Box<dyn Error>>
-    where
-        PreprocParams: PreprocessorParameters,
-    {
-        let local_addr = local.parse()?;
-        let remote_addr = resolve_host(remote)?;
-
-        let mut conn = Connection::new(local_addr, remote_addr).await?;
-
-        tokio::task::spawn_blocking(move || {
-            tokio::runtime::Builder::new_multi_thread()
-                .worker_threads(num_threads)
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async {
-                    let mut conns = Vec::new();
-                    for _ in 0..num_batches {
-                        conns.push(conn.fork());
-                    }
-                    let preprocs: Vec<_> =
-                        futures_util::future::join_all(conns.into_iter().map(|mut conn| {
-                            tokio::task::spawn(async move {
-                                LowGearPreprocessor::<PreprocParams, PID>::new(&mut conn)
-                                    .await
-                                    .unwrap()
-                            })
-                        }))
-                        .await;
-
-                    let now = Instant::now();
-
-                    let preprocs: Vec<_> = futures_util::future::join_all(
-                        preprocs.into_iter().map(Result::unwrap).map(|mut preproc| {
-                            tokio::task::spawn(async move {
-                                preproc.get_beaver_triples().await;
-                                preproc
-                            })
-                        }),
-                    )
-                    .await;
-
-                    let elapsed_time = now.elapsed();
-                    let num_triples = low_gear_preproc::batch_size::<PreprocParams>() * num_batches;
-                    let triples_per_sec =
-                        num_triples as f64 * 1_000_000_000f64 / elapsed_time.as_nanos() as f64;
-                    info!(
-                        "{} triples/s (produced {} triples in {} ms)",
-                        triples_per_sec,
-                        num_triples,
-                        elapsed_time.as_millis()
-                    );
-                    // Output only the number of triples per second to stdout, so it can be parsed
-                    // by benchmark scripts.
-                    println!("{}", triples_per_sec);
-
-                    for preproc in preprocs.into_iter() {
-                        preproc.unwrap().finish().await;
-                    }
-                })
-        })
-        .await?;
-        Ok(())
-    }
-}
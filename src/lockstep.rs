@@ -0,0 +1,148 @@
+//! Round barriers that make concurrent halves of a subprotocol proceed round by round.
+//!
+//! The prover and the verifier of the ZKPoPK run concurrently via `tokio::join!`, so their
+//! messages and log lines interleave differently in each run.  In lockstep, the halves wait for
+//! each other at the end of each round, such that the transcripts of two runs can be compared round
+//! by round, e.g., for debugging.  A half that finishes (or fails) early leaves the lockstep, and
+//! the other halves continue without it.
+
+use std::sync::Mutex;
+
+use log::debug;
+use tokio::sync::Notify;
+
+pub struct Lockstep {
+    enabled: bool,
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+struct State {
+    participants: usize,
+    arrived: usize,
+    round: u64,
+}
+
+impl Lockstep {
+    /// A lockstep of `participants` halves.  If not `enabled`, the rounds don't wait at all.
+    pub fn new(participants: usize, enabled: bool) -> Self {
+        Self {
+            enabled,
+            state: Mutex::new(State {
+                participants,
+                arrived: 0,
+                round: 0,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Number of completed rounds.
+    pub fn round(&self) -> u64 {
+        self.state.lock().unwrap().round
+    }
+
+    /// Takes part in the lockstep as one of the halves, which leaves it when dropped.
+    pub fn participant(&self) -> Participant<'_> {
+        Participant { lockstep: self }
+    }
+
+    fn complete_round(&self, state: &mut State) {
+        state.arrived = 0;
+        state.round += 1;
+        debug!("Lockstep: round {} completed", state.round);
+        self.notify.notify_waiters();
+    }
+}
+
+/// A half of a `Lockstep`, see `Lockstep::participant()`.
+pub struct Participant<'a> {
+    lockstep: &'a Lockstep,
+}
+
+impl Participant<'_> {
+    /// Waits until all remaining halves have reached the end of the current round.
+    pub async fn end_round(&self) {
+        let lockstep = self.lockstep;
+        if !lockstep.enabled {
+            return;
+        }
+        let round = {
+            let mut state = lockstep.state.lock().unwrap();
+            state.arrived += 1;
+            if state.arrived >= state.participants {
+                lockstep.complete_round(&mut state);
+                return;
+            }
+            state.round
+        };
+        loop {
+            let notified = lockstep.notify.notified();
+            tokio::pin!(notified);
+            // Register before checking the round, such that a completion in between is not missed.
+            notified.as_mut().enable();
+            if lockstep.state.lock().unwrap().round != round {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for Participant<'_> {
+    fn drop(&mut self) {
+        let lockstep = self.lockstep;
+        if !lockstep.enabled {
+            return;
+        }
+        let mut state = lockstep.state.lock().unwrap();
+        state.participants -= 1;
+        // The others may already wait for this half.
+        if state.arrived > 0 && state.arrived >= state.participants {
+            lockstep.complete_round(&mut state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::Lockstep;
+
+    #[tokio::test]
+    async fn rounds_are_aligned() {
+        let lockstep = Lockstep::new(2, true);
+        let log = Mutex::new(Vec::new());
+
+        tokio::join!(
+            async {
+                let step = lockstep.participant();
+                for round in 0..3 {
+                    tokio::task::yield_now().await;
+                    log.lock().unwrap().push(("fast", round));
+                    step.end_round().await;
+                }
+            },
+            async {
+                let step = lockstep.participant();
+                for round in 0..5 {
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                    log.lock().unwrap().push(("slow", round));
+                    step.end_round().await;
+                }
+            }
+        );
+
+        // Within each of the first three rounds, both halves log once before the next round.
+        let log = log.into_inner().unwrap();
+        for (i, pair) in log[..6].chunks(2).enumerate() {
+            let mut rounds: Vec<_> = pair.iter().map(|(_, round)| *round).collect();
+            rounds.dedup();
+            assert_eq!(rounds, [i]);
+        }
+        // The fast half left after three rounds, so the slow half continues alone.
+        assert_eq!(log[6..], [("slow", 3), ("slow", 4)]);
+        assert_eq!(lockstep.round(), 5);
+    }
+}
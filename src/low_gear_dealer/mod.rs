@@ -1,12 +1,15 @@
 pub mod params;
 
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
 use async_bincode::AsyncDestination;
 use crypto_bigint::{Random, Zero};
 use futures_util::{SinkExt, StreamExt};
 use log::info;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::bgv::poly::crt::CrtPolyParameters;
@@ -15,8 +18,13 @@ use crate::bgv::poly::{CrtContext, PolyParameters};
 use crate::bgv::residue::native::GenericNativeResidue;
 use crate::bgv::residue::vec::GenericResidueVec;
 use crate::bgv::residue::GenericResidue;
-use crate::bgv::{self, BgvParameters, Ciphertext, Cleartext, PublicKey, SecretKey};
+use crate::bgv::{self, noise, BgvParameters, Ciphertext, Cleartext, PublicKey, SecretKey};
+use crate::commitment::{self, Commitment, Opening};
 use crate::connection::{Connection, StreamError};
+use crate::context_set::ContextSet;
+use crate::crypto_suite::CryptoSuite;
+use crate::interface::MacKeyShare;
+use crate::util::{run_blocking, zeroize, SlotUsage};
 
 pub trait DealerParameters: PartialEq + Debug + Send + Sync + 'static {
     type PlaintextParams: PolyParameters<Residue = Self::KS>;
@@ -35,16 +43,152 @@ pub trait DealerParameters: PartialEq + Debug + Send + Sync + 'static {
     type KS: GenericNativeResidue;
 }
 
+/// The state of a `LowGearDealer`.  A dealer starts in `Init`, is `Ready` after the keys have been
+/// exchanged, and returns to `Ready` after each call of `authenticate()` and `refresh_keys()`.  It
+/// ends in `Failed` once any round fails, since the parties can't resynchronize afterwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DealerState {
+    /// Exchanging the public keys and the encrypted MAC keys.
+    Init,
+    Ready,
+    /// Within the `round`-th call of `authenticate()`, after `received` of `expected` ciphertexts
+    /// have been received.
+    Authenticate {
+        round: u64,
+        received: usize,
+        expected: usize,
+    },
+    /// Exchanging the keys of `epoch`.
+    Refresh {
+        epoch: u64,
+    },
+    /// Exchanging the encrypted MAC keys of `domain`.
+    AddDomain {
+        domain: KeyDomain,
+    },
+    /// Checking the tags of the `round`-th call of `authenticate()`, see `set_strict()`.
+    Check {
+        round: u64,
+    },
+    Failed,
+}
+
+/// Identifies one of the MAC keys of a `LowGearDealer`, see `LowGearDealer::add_domain()`.
+///
+/// The dealer authenticates values under the MAC key of a domain, such that independent consumers
+/// (e.g., preprocessing sessions) can share one dealer and its connection.  The initial MAC key of
+/// the dealer is the `DEFAULT` domain, and the added domains are numbered consecutively.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct KeyDomain(pub u32);
+
+impl KeyDomain {
+    pub const DEFAULT: Self = Self(0);
+}
+
+/// The kind of a message of the dealer, which is reported if it arrives in the wrong state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageKind {
+    Init,
+    Tags,
+    Refresh,
+    AddDomain,
+    Check,
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum DealerError {
+    FailedToOpen(StreamError),
+    FailedToSend(bincode::ErrorKind),
+    FailedToReceive(bincode::ErrorKind),
+    /// The remote party closed the stream.
+    ConnectionClosed,
+    /// The dealer is not `Ready`, e.g., because an earlier round failed or was cancelled.
+    #[display(fmt = "dealer is not ready (state {:?})", _0)]
+    NotReady(#[error(not(source))] DealerState),
+    /// The remote party is in a different round, e.g., it refreshes its keys while we
+    /// authenticate.
+    #[display(fmt = "unexpected {:?} message in state {:?}", received, state)]
+    UnexpectedMessage {
+        state: DealerState,
+        received: MessageKind,
+    },
+    /// A ciphertext of the tags is repeated or out of order.
+    #[display(
+        fmt = "expected tags {} of round {}, but received tags {} of round {}",
+        expected_chunk,
+        expected_round,
+        chunk,
+        round
+    )]
+    TagsOutOfOrder {
+        expected_round: u64,
+        expected_chunk: usize,
+        round: u64,
+        chunk: usize,
+    },
+    #[display(
+        fmt = "expected keys of epoch {}, but received epoch {}",
+        expected,
+        received
+    )]
+    EpochMismatch {
+        expected: u64,
+        received: u64,
+    },
+    /// The dealer has no MAC key of this domain.
+    #[display(fmt = "unknown key domain {:?}", _0)]
+    UnknownDomain(#[error(not(source))] KeyDomain),
+    /// The remote party uses another domain, e.g., it authenticates under another MAC key.
+    #[display(
+        fmt = "expected key domain {:?}, but received {:?}",
+        expected,
+        received
+    )]
+    DomainMismatch {
+        expected: KeyDomain,
+        received: KeyDomain,
+    },
+    /// The remote party refreshed the keys of a different number of domains.
+    #[display(
+        fmt = "expected keys of {} domains, but received {}",
+        expected,
+        received
+    )]
+    DomainCountMismatch {
+        expected: usize,
+        received: usize,
+    },
+    /// The consistency check of the tags failed, i.e., the remote party cheated, see
+    /// `set_strict()`.
+    CheckFailed,
+}
+
 pub struct LowGearDealer<P>
 where
     P: DealerParameters,
 {
     bincode_tx: AsyncBincodeWriter<quinn::SendStream, Message<P>, AsyncDestination>,
     bincode_rx: AsyncBincodeReader<quinn::RecvStream, Message<P>>,
-    ctx: CrtContext<P::CiphertextParams>,
+    ctx: Arc<CrtContext<P::CiphertextParams>>,
     sk: SecretKey<P::BgvParams>,
+    pk: PublicKey<P::BgvParams>,
     remote_pk: PublicKey<P::BgvParams>,
-    mac_key: P::S,
+    /// The MAC keys, indexed by `KeyDomain`.
+    domains: Vec<Domain<P>>,
+    epoch: u64,
+    round: u64,
+    state: DealerState,
+    slot_usage: SlotUsage,
+    strict: bool,
+    crypto_suite: CryptoSuite,
+}
+
+/// This party's MAC key of a `KeyDomain` and the remote party's, encrypted under its public key.
+struct Domain<P>
+where
+    P: DealerParameters,
+{
+    mac_key: MacKeyShare<P::S>,
     remote_mac_key: Ciphertext<P::BgvParams>,
 }
 
@@ -59,49 +203,136 @@ where
         pk: PublicKey<P::BgvParams>,
         mac_key: Ciphertext<P::BgvParams>,
     },
-    Tags(Ciphertext<P::BgvParams>),
+    /// The `chunk`-th ciphertext of the `round`-th call of `authenticate()`, under the MAC key of
+    /// `domain`.
+    Tags {
+        domain: KeyDomain,
+        round: u64,
+        chunk: usize,
+        ciphertext: Ciphertext<P::BgvParams>,
+    },
+    /// The fresh public key and the MAC keys of all domains encrypted under it.
+    Refresh {
+        epoch: u64,
+        pk: PublicKey<P::BgvParams>,
+        mac_keys: Vec<Ciphertext<P::BgvParams>>,
+    },
+    AddDomain {
+        domain: KeyDomain,
+        mac_key: Ciphertext<P::BgvParams>,
+    },
+    /// A step of the consistency check of the `round`-th call of `authenticate()`.
+    Check { round: u64, step: CheckStep<P::KS> },
+}
+
+/// The steps of the consistency check, in order, see `LowGearDealer::set_strict()`.
+#[derive(Deserialize, Serialize)]
+enum CheckStep<KS> {
+    /// Commitment to the seed of the random coefficients.
+    SeedCommitment(Commitment<[u8; 32]>),
+    Seed(Opening<[u8; 32]>),
+    /// Share of the random linear combination of the values.
+    Value(KS),
+    /// Commitment to the share of the tag of the opened linear combination minus the MAC key
+    /// share times it.
+    SigmaCommitment(Commitment<KS>),
+    Sigma(Opening<KS>),
+}
+
+impl<P> Message<P>
+where
+    P: DealerParameters,
+{
+    fn kind(&self) -> MessageKind {
+        match self {
+            Message::Init { .. } => MessageKind::Init,
+            Message::Tags { .. } => MessageKind::Tags,
+            Message::Refresh { .. } => MessageKind::Refresh,
+            Message::AddDomain { .. } => MessageKind::AddDomain,
+            Message::Check { .. } => MessageKind::Check,
+        }
+    }
+}
+
+#[cfg(feature = "fuzzing")]
+pub(crate) fn fuzz_message(bytes: &[u8]) {
+    crate::fuzzing::framed_roundtrip::<Message<params::ToyDealerK32S32>>(bytes);
 }
 
 impl<P> LowGearDealer<P>
 where
     P: DealerParameters,
 {
-    pub async fn new(conn: &mut Connection, mac_key: P::S) -> Result<Self, StreamError> {
-        let (tx, rx) = conn.open_bi("LowGearDealer").await?;
+    pub async fn new(
+        conn: &mut Connection,
+        mac_key: MacKeyShare<P::S>,
+    ) -> Result<Self, StreamError> {
+        Self::with_contexts(conn, mac_key, &ContextSet::default()).await
+    }
+
+    /// Like `new()`, but takes the context from `contexts`, so that it can be shared, e.g., with
+    /// other dealers.
+    pub async fn with_contexts(
+        conn: &mut Connection,
+        mac_key: MacKeyShare<P::S>,
+        contexts: &ContextSet,
+    ) -> Result<Self, StreamError> {
+        match Self::try_with_contexts(conn, mac_key, contexts).await {
+            Ok(dealer) => Ok(dealer),
+            Err(DealerError::FailedToOpen(err)) => Err(err),
+            // TODO: return error instead of unwrapping.
+            Err(err) => panic!("Dealer initialization failed: {}", err),
+        }
+    }
+
+    /// Like `with_contexts()`, but also returns an error if the exchange of the keys fails.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "dealer_init", skip_all)
+    )]
+    pub async fn try_with_contexts(
+        conn: &mut Connection,
+        mac_key: MacKeyShare<P::S>,
+        contexts: &ContextSet,
+    ) -> Result<Self, DealerError> {
+        let (stream, ctx) = tokio::join!(
+            conn.open_bi("LowGearDealer"),
+            contexts.get::<P::CiphertextParams>()
+        );
+        let (tx, rx) = stream.map_err(DealerError::FailedToOpen)?;
         let mut bincode_tx = AsyncBincodeWriter::from(tx).for_async();
         let mut bincode_rx = AsyncBincodeReader::from(rx);
-        let ctx = CrtContext::gen().await;
-        let sk = SecretKey::gen(&ctx).await;
-        let pk = PublicKey::gen(&ctx, &sk).await;
-        // TODO: Can the noise bound be improved via secret-key encryption?
-        let encrypted_mac_key = {
-            // TODO: Use Neg once available
-            let negative = P::KS::ZERO - P::KS::from_unsigned(mac_key);
-            let mut power = PowerPoly::<P::PlaintextParams>::new();
-            for coeff in power.coefficients.iter_mut() {
-                *coeff = negative;
+        let (sk, pk, encrypted_mac_key) = run_blocking({
+            let (ctx, mac_key) = (Arc::clone(&ctx), mac_key.clone());
+            async move {
+                let (sk, pk) = gen_keys::<P>(&ctx).await;
+                let encrypted_mac_key = encrypt_mac_key::<P>(&ctx, &pk, &mac_key).await;
+                (sk, pk, encrypted_mac_key)
             }
-            bgv::encrypt(&ctx, &pk, &power).await
-        };
-        let (_, (remote_pk, remote_mac_key)) = tokio::join!(
+        })
+        .await;
+        let (sent, received) = tokio::join!(
             // Send our message to the other party.
-            async {
-                bincode_tx
-                    .send(Message::Init {
-                        pk,
-                        mac_key: encrypted_mac_key,
-                    })
-                    .await
-                    .unwrap();
-            },
-            // Concurrently receive the message from the other party.
-            async {
-                match bincode_rx.next().await.unwrap().unwrap() {
-                    Message::Init { pk, mac_key } => (pk, mac_key),
-                    _ => panic!("Received message with wrong round number"),
+            send(
+                &mut bincode_tx,
+                Message::Init {
+                    pk: pk.clone(),
+                    mac_key: encrypted_mac_key,
                 }
-            }
+            ),
+            // Concurrently receive the message from the other party.
+            recv(&mut bincode_rx)
         );
+        sent?;
+        let (remote_pk, remote_mac_key) = match received? {
+            Message::Init { pk, mac_key } => (pk, mac_key),
+            message => {
+                return Err(DealerError::UnexpectedMessage {
+                    state: DealerState::Init,
+                    received: message.kind(),
+                })
+            }
+        };
 
         // TODO: Perform ZKPoPK
 
@@ -110,127 +341,870 @@ where
             bincode_rx,
             ctx,
             sk,
+            pk,
             remote_pk,
+            domains: vec![Domain {
+                mac_key,
+                remote_mac_key,
+            }],
+            epoch: 0,
+            round: 0,
+            state: DealerState::Ready,
+            slot_usage: SlotUsage::default(),
+            strict: false,
+            crypto_suite: CryptoSuite::default(),
+        })
+    }
+
+    /// Generates a fresh key pair, under which our MAC key is re-encrypted, and obtains the
+    /// remote party's fresh public key and encrypted MAC key.
+    ///
+    /// Both parties must call this between the same two calls of `authenticate()`.  Since the
+    /// messages of a dealer are delivered in order, the tags of earlier calls are still decrypted
+    /// with the old secret key, while later calls use the new keys.  Other dealers (e.g. of other
+    /// batches) are not affected.
+    pub async fn refresh_keys(&mut self) {
+        // TODO: return error instead of unwrapping.
+        self.try_refresh_keys().await.unwrap();
+    }
+
+    /// Like `refresh_keys()`, but returns an error if the other party is not refreshing its keys
+    /// as well.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "dealer_refresh", skip_all)
+    )]
+    pub async fn try_refresh_keys(&mut self) -> Result<(), DealerError> {
+        self.check_ready()?;
+        let epoch = self.epoch + 1;
+        self.state = DealerState::Refresh { epoch };
+        let (sk, pk) = gen_keys::<P>(&self.ctx).await;
+        let mut encrypted_mac_keys = Vec::with_capacity(self.domains.len());
+        for domain in &self.domains {
+            encrypted_mac_keys.push(encrypt_mac_key::<P>(&self.ctx, &pk, &domain.mac_key).await);
+        }
+        let (sent, received) = tokio::join!(
+            send(
+                &mut self.bincode_tx,
+                Message::Refresh {
+                    epoch,
+                    pk: pk.clone(),
+                    mac_keys: encrypted_mac_keys,
+                }
+            ),
+            recv(&mut self.bincode_rx)
+        );
+        let num_domains = self.domains.len();
+        let result = sent.and(received).and_then(|message| match message {
+            Message::Refresh {
+                epoch: remote_epoch,
+                mac_keys,
+                ..
+            } if remote_epoch == epoch && mac_keys.len() != num_domains => {
+                Err(DealerError::DomainCountMismatch {
+                    expected: num_domains,
+                    received: mac_keys.len(),
+                })
+            }
+            Message::Refresh {
+                epoch: remote_epoch,
+                pk,
+                mac_keys,
+            } if remote_epoch == epoch => Ok((pk, mac_keys)),
+            Message::Refresh {
+                epoch: remote_epoch,
+                ..
+            } => Err(DealerError::EpochMismatch {
+                expected: epoch,
+                received: remote_epoch,
+            }),
+            message => Err(DealerError::UnexpectedMessage {
+                state: self.state,
+                received: message.kind(),
+            }),
+        });
+        let (remote_pk, remote_mac_keys) = self.fail_on_error(result)?;
+        info!("Dealer: refreshed keys (epoch {})", epoch);
+
+        self.sk = sk;
+        self.pk = pk;
+        self.remote_pk = remote_pk;
+        for (domain, remote_mac_key) in self.domains.iter_mut().zip(remote_mac_keys) {
+            domain.remote_mac_key = remote_mac_key;
+        }
+        self.epoch = epoch;
+        self.state = DealerState::Ready;
+        Ok(())
+    }
+
+    /// Adds a domain with the MAC key `mac_key`, under which `authenticate_in()` authenticates
+    /// values.  Both parties must add their domains in the same order.
+    pub async fn add_domain(&mut self, mac_key: MacKeyShare<P::S>) -> KeyDomain {
+        // TODO: return error instead of unwrapping.
+        self.try_add_domain(mac_key).await.unwrap()
+    }
+
+    /// Like `add_domain()`, but returns an error if the other party does not add the same domain.
+    pub async fn try_add_domain(
+        &mut self,
+        mac_key: MacKeyShare<P::S>,
+    ) -> Result<KeyDomain, DealerError> {
+        self.check_ready()?;
+        let domain = KeyDomain(self.domains.len() as u32);
+        self.state = DealerState::AddDomain { domain };
+        let encrypted_mac_key = encrypt_mac_key::<P>(&self.ctx, &self.pk, &mac_key).await;
+        let (sent, received) = tokio::join!(
+            send(
+                &mut self.bincode_tx,
+                Message::AddDomain {
+                    domain,
+                    mac_key: encrypted_mac_key,
+                }
+            ),
+            recv(&mut self.bincode_rx)
+        );
+        let result = sent.and(received).and_then(|message| match message {
+            Message::AddDomain {
+                domain: remote_domain,
+                mac_key,
+            } if remote_domain == domain => Ok(mac_key),
+            Message::AddDomain {
+                domain: remote_domain,
+                ..
+            } => Err(DealerError::DomainMismatch {
+                expected: domain,
+                received: remote_domain,
+            }),
+            message => Err(DealerError::UnexpectedMessage {
+                state: self.state,
+                received: message.kind(),
+            }),
+        });
+        let remote_mac_key = self.fail_on_error(result)?;
+        info!("Dealer: added key domain {}", domain.0);
+
+        self.domains.push(Domain {
             mac_key,
             remote_mac_key,
-        })
+        });
+        self.state = DealerState::Ready;
+        Ok(domain)
+    }
+
+    /// Number of key domains, including `KeyDomain::DEFAULT`.
+    pub fn num_domains(&self) -> usize {
+        self.domains.len()
+    }
+
+    /// Number of times the keys have been refreshed.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Number of completed calls of `authenticate()`.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn state(&self) -> DealerState {
+        self.state
+    }
+
+    /// Enables steps 2 and 4-6 of the authentication, which are skipped by default: each call of
+    /// `authenticate()` authenticates an extra random value, and the parties open a random linear
+    /// combination of the values masked by it and check its tag.  This detects a party that sends
+    /// inconsistent ciphertexts of the tags, at the cost of an extra slot and five rounds per call.
+    /// Both parties must use the same mode.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
 
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets the hash function of the commitments of the consistency check, see `set_strict()`.
+    /// Both parties must use the same suite.
+    pub fn set_crypto_suite(&mut self, suite: CryptoSuite) {
+        self.crypto_suite = suite;
+    }
+
+    /// Number of slots used for and discarded by `authenticate()` so far.
+    pub fn slot_usage(&self) -> SlotUsage {
+        self.slot_usage
+    }
+
+    /// Returns the MAC tag shares of `values`.  Any number of values is supported: they are split
+    /// into batches of `packing_capacity()` values, and the unused slots of the last batch are
+    /// zeroized and discarded.
     pub async fn authenticate(&mut self, values: &[P::K]) -> Vec<P::KS> {
-        if values.len() > packing_capacity::<P::PlaintextParams>() {
-            panic!(
-                "Batch size {} is too large. \
-                `LowGearDealer` can authenticate at most {} values at once",
-                values.len(),
-                packing_capacity::<P::PlaintextParams>(),
-            );
+        // TODO: return error instead of unwrapping.
+        self.try_authenticate(values).await.unwrap()
+    }
+
+    /// Like `authenticate()`, but under the MAC key of `domain`.
+    pub async fn authenticate_in(&mut self, domain: KeyDomain, values: &[P::K]) -> Vec<P::KS> {
+        // TODO: return error instead of unwrapping.
+        self.try_authenticate_in(domain, values).await.unwrap()
+    }
+
+    /// Like `authenticate()`, but returns an error if the messages of the other party don't belong
+    /// to this round.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "dealer_authenticate", skip_all)
+    )]
+    pub async fn try_authenticate(&mut self, values: &[P::K]) -> Result<Vec<P::KS>, DealerError> {
+        self.try_authenticate_in(KeyDomain::DEFAULT, values).await
+    }
+
+    /// Like `authenticate_in()`, but returns an error if the messages of the other party don't
+    /// belong to this round or domain.
+    pub async fn try_authenticate_in(
+        &mut self,
+        domain: KeyDomain,
+        values: &[P::K],
+    ) -> Result<Vec<P::KS>, DealerError> {
+        self.check_ready()?;
+        let keys = self
+            .domains
+            .get(domain.0 as usize)
+            .ok_or(DealerError::UnknownDomain(domain))?;
+        // 2. In strict mode, the last value is a random element, which masks the opened value.
+        let mut values: Vec<P::KS> = values.iter().map(|v| P::KS::from_unsigned(*v)).collect();
+        if self.strict {
+            values.push(P::KS::random(&mut rand::thread_rng()));
         }
+        let values = &values[..];
+        let capacity = packing_capacity::<P::PlaintextParams>();
+        self.state = DealerState::Authenticate {
+            round: self.round,
+            received: 0,
+            expected: values.len().div_ceil(capacity),
+        };
 
-        // 2. - 6.
-        let (mut tags, tags2) = tokio::join!(
+        // 3.
+        let (tags, tags2) = tokio::join!(
             send_mac_tags(
                 &mut self.bincode_tx,
                 &self.ctx,
                 &self.remote_pk,
-                self.mac_key,
-                &self.remote_mac_key,
+                &keys.mac_key,
+                &keys.remote_mac_key,
+                domain,
+                self.round,
                 values
             ),
-            recv_mac_tags(&mut self.bincode_rx, &self.ctx, &self.sk, values.len()),
+            recv_mac_tags(
+                &mut self.bincode_rx,
+                &self.ctx,
+                &self.sk,
+                &mut self.state,
+                domain,
+                values.len()
+            ),
         );
+        let (mut tags, tags2) = self.fail_on_error(tags.and_then(|tags| Ok((tags, tags2?))))?;
 
         // 7. - 8.
         for (t, t2) in tags.iter_mut().zip(&tags2) {
             *t += *t2; // TODO: Can we support references on the RHS, too?
         }
 
-        tags
+        // 4. - 6.
+        if self.strict {
+            let result = self.check_tags(domain, values, &tags).await;
+            self.fail_on_error(result)?;
+            tags.pop();
+        }
+
+        for chunk in values.chunks(capacity) {
+            self.slot_usage.record(chunk.len(), capacity);
+        }
+        self.round += 1;
+        self.state = DealerState::Ready;
+        Ok(tags)
     }
 
     pub async fn finish(self) {
         let _ = self.bincode_tx.into_inner().finish().await;
     }
+
+    /// Opens a random linear combination of `values`, whose last element is random, and checks
+    /// its tag like `MacCheckOpener` does.  The random coefficients are drawn from a seed that
+    /// both parties contribute to.
+    async fn check_tags(
+        &mut self,
+        domain: KeyDomain,
+        values: &[P::KS],
+        tags: &[P::KS],
+    ) -> Result<(), DealerError> {
+        let round = self.round;
+        self.state = DealerState::Check { round };
+
+        let mut seed: [u8; 32] = rand::random();
+        let (seed_com, seed_opening) = commitment::commit_random(self.crypto_suite, seed);
+        let remote_seed_com = self
+            .exchange_check_step(
+                round,
+                CheckStep::SeedCommitment(seed_com),
+                |step| match step {
+                    CheckStep::SeedCommitment(com) => Some(com),
+                    _ => None,
+                },
+            )
+            .await?;
+        let remote_seed_opening = self
+            .exchange_check_step(round, CheckStep::Seed(seed_opening), |step| match step {
+                CheckStep::Seed(opening) => Some(opening),
+                _ => None,
+            })
+            .await?;
+        let remote_seed =
+            commitment::open(self.crypto_suite, &remote_seed_com, remote_seed_opening)
+                .map_err(|_| DealerError::CheckFailed)?;
+        for (byte, remote_byte) in seed.iter_mut().zip(remote_seed) {
+            *byte ^= remote_byte;
+        }
+
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        let (mut value, values) = values.split_last().map(|(v, vs)| (*v, vs)).unwrap();
+        let (mut tag, tags) = tags.split_last().map(|(t, ts)| (*t, ts)).unwrap();
+        for (v, t) in values.iter().zip(tags) {
+            let coeff = P::KS::random(&mut rng);
+            value += coeff * *v;
+            tag += coeff * *t;
+        }
+        let remote_value = self
+            .exchange_check_step(round, CheckStep::Value(value), |step| match step {
+                CheckStep::Value(value) => Some(value),
+                _ => None,
+            })
+            .await?;
+
+        let mut mac_key = self.domains[domain.0 as usize].mac_key.widen::<P::KS>();
+        let sigma = tag - (value + remote_value) * mac_key;
+        zeroize([&mut mac_key]);
+        let (sigma_com, sigma_opening) = commitment::commit_random(self.crypto_suite, sigma);
+        let remote_sigma_com = self
+            .exchange_check_step(
+                round,
+                CheckStep::SigmaCommitment(sigma_com),
+                |step| match step {
+                    CheckStep::SigmaCommitment(com) => Some(com),
+                    _ => None,
+                },
+            )
+            .await?;
+        let remote_sigma_opening = self
+            .exchange_check_step(round, CheckStep::Sigma(sigma_opening), |step| match step {
+                CheckStep::Sigma(opening) => Some(opening),
+                _ => None,
+            })
+            .await?;
+        let remote_sigma =
+            commitment::open(self.crypto_suite, &remote_sigma_com, remote_sigma_opening)
+                .map_err(|_| DealerError::CheckFailed)?;
+        if sigma + remote_sigma != P::KS::ZERO {
+            return Err(DealerError::CheckFailed);
+        }
+        Ok(())
+    }
+
+    /// Sends `step` of the consistency check and receives the same step of the other party, which
+    /// `extract` returns.
+    async fn exchange_check_step<T>(
+        &mut self,
+        round: u64,
+        step: CheckStep<P::KS>,
+        extract: fn(CheckStep<P::KS>) -> Option<T>,
+    ) -> Result<T, DealerError> {
+        let (sent, received) = tokio::join!(
+            send(&mut self.bincode_tx, Message::Check { round, step }),
+            recv(&mut self.bincode_rx)
+        );
+        sent?;
+        let unexpected = DealerError::UnexpectedMessage {
+            state: self.state,
+            received: MessageKind::Check,
+        };
+        match received? {
+            Message::Check { round: r, step } if r == round => extract(step).ok_or(unexpected),
+            message => Err(DealerError::UnexpectedMessage {
+                state: self.state,
+                received: message.kind(),
+            }),
+        }
+    }
+
+    fn check_ready(&self) -> Result<(), DealerError> {
+        match self.state {
+            DealerState::Ready => Ok(()),
+            state => Err(DealerError::NotReady(state)),
+        }
+    }
+
+    /// Moves to `Failed` if `result` is an error, since the messages of the failed round may
+    /// still be in flight.
+    fn fail_on_error<T>(&mut self, result: Result<T, DealerError>) -> Result<T, DealerError> {
+        if result.is_err() {
+            self.state = DealerState::Failed;
+        }
+        result
+    }
+}
+
+async fn send<P>(
+    bincode_tx: &mut AsyncBincodeWriter<quinn::SendStream, Message<P>, AsyncDestination>,
+    message: Message<P>,
+) -> Result<(), DealerError>
+where
+    P: DealerParameters,
+{
+    bincode_tx
+        .send(message)
+        .await
+        .map_err(|err| DealerError::FailedToSend(*err))
+}
+
+async fn recv<P>(
+    bincode_rx: &mut AsyncBincodeReader<quinn::RecvStream, Message<P>>,
+) -> Result<Message<P>, DealerError>
+where
+    P: DealerParameters,
+{
+    match bincode_rx.next().await {
+        Some(Ok(message)) => Ok(message),
+        Some(Err(err)) => Err(DealerError::FailedToReceive(*err)),
+        None => Err(DealerError::ConnectionClosed),
+    }
+}
+
+/// Generates a key pair.
+async fn gen_keys<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+) -> (SecretKey<P::BgvParams>, PublicKey<P::BgvParams>)
+where
+    P: DealerParameters,
+{
+    let sk = SecretKey::gen(ctx).await;
+    let pk = PublicKey::gen(ctx, &sk).await;
+    (sk, pk)
+}
+
+/// Encrypts the negated MAC key under `pk`.
+async fn encrypt_mac_key<P>(
+    ctx: &CrtContext<P::CiphertextParams>,
+    pk: &PublicKey<P::BgvParams>,
+    mac_key: &MacKeyShare<P::S>,
+) -> Ciphertext<P::BgvParams>
+where
+    P: DealerParameters,
+{
+    // TODO: Can the noise bound be improved via secret-key encryption?
+    let mut wide_mac_key = mac_key.widen::<P::KS>();
+    let mut power = PowerPoly::<P::PlaintextParams>::new();
+    for coeff in power.coefficients.iter_mut() {
+        *coeff = wide_mac_key;
+    }
+    let mut power = -power;
+    let ciphertext = bgv::encrypt(ctx, pk, &power).await;
+    zeroize([&mut wide_mac_key]);
+    zeroize(power.coefficients.iter_mut());
+    ciphertext
 }
 
+/// Number of bits of drowning noise for the MAC tags.  The remote MAC key is a fresh encryption
+/// (see `gen_keys()`), which is multiplied by the values.
+const fn tags_drown_bits<P>() -> usize
+where
+    P: DealerParameters,
+{
+    let fresh_noise_bits = noise::fresh_noise_bits::<P::BgvParams>();
+    noise::drown_bits::<P::BgvParams>(noise::product_noise_bits::<P::BgvParams>(fresh_noise_bits))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn send_mac_tags<P>(
     bincode_tx: &mut AsyncBincodeWriter<quinn::SendStream, Message<P>, AsyncDestination>,
     ctx: &CrtContext<P::CiphertextParams>,
     remote_pk: &PublicKey<P::BgvParams>,
-    mac_key: P::S,
+    mac_key: &MacKeyShare<P::S>,
     remote_mac_key: &Ciphertext<P::BgvParams>,
-    values: &[P::K],
-) -> Vec<P::KS>
+    domain: KeyDomain,
+    round: u64,
+    values: &[P::KS],
+) -> Result<Vec<P::KS>, DealerError>
 where
     P: DealerParameters,
 {
-    // We skip steps 4-6, because in practice the check in step 6 is not required.  Hence, we also
-    // don't need the random element from step 2.
+    // Steps 2 and 4-6 are only performed in strict mode, see `LowGearDealer::set_strict()`.
 
-    let plain_e = {
-        let mut temp = PowerPoly::<P::PlaintextParams>::new();
-        let mut rng = rand::thread_rng();
-        for coeff in temp.coefficients.iter_mut().take(values.len()) {
-            *coeff = P::KS::random(&mut rng);
-        }
-        temp
-    };
+    let capacity = packing_capacity::<P::PlaintextParams>();
+    let mut plain_es: Vec<_> = values
+        .chunks(capacity)
+        .map(|chunk| {
+            let mut temp = PowerPoly::<P::PlaintextParams>::new();
+            let mut rng = rand::thread_rng();
+            for coeff in temp.coefficients.iter_mut().take(chunk.len()) {
+                *coeff = P::KS::random(&mut rng);
+            }
+            temp
+        })
+        .collect();
 
-    {
+    // The masks of all batches are encrypted at once, see `bgv::encrypt_batch()`.
+    let masks = bgv::encrypt_batch(ctx, remote_pk, &plain_es, tags_drown_bits::<P>()).await;
+    for (chunk, (values_chunk, mask)) in values.chunks(capacity).zip(&masks).enumerate() {
         let plain_values = {
             let mut temp = PowerPoly::<P::PlaintextParams>::new();
-            for (coeff, val) in temp.coefficients.iter_mut().zip(values.iter()) {
-                *coeff = P::KS::from_unsigned(*val);
+            for (coeff, val) in temp.coefficients.iter_mut().zip(values_chunk.iter()) {
+                *coeff = *val;
             }
             temp
         };
         let mut ciphertext = remote_mac_key.clone();
         ciphertext *= &Cleartext::new(ctx, &plain_values).await;
-        ciphertext -= &bgv::encrypt_and_drown(
-            ctx,
-            remote_pk,
-            &plain_e,
-            bgv::max_drown_bits::<P::BgvParams>(),
-        )
-        .await;
-        // TODO: return error instead of unwrapping.
-        bincode_tx.send(Message::Tags(ciphertext)).await.unwrap();
+        ciphertext -= mask;
+        let message = Message::Tags {
+            domain,
+            round,
+            chunk,
+            ciphertext,
+        };
+        if let Err(err) = send(bincode_tx, message).await {
+            for plain_e in plain_es.iter_mut() {
+                zeroize(plain_e.coefficients.iter_mut());
+            }
+            return Err(err);
+        }
     }
 
-    let wide_mac_key = P::KS::from_unsigned(mac_key);
+    let mut wide_mac_key = mac_key.widen::<P::KS>();
 
-    values
-        .iter()
-        .zip(plain_e.coefficients.iter())
-        .map(|(val, tag)| {
-            let val = P::KS::from_unsigned(*val);
-            *tag + val * wide_mac_key
-        })
-        .collect()
+    let tags = values
+        .chunks(capacity)
+        .zip(&plain_es)
+        .flat_map(|(chunk, plain_e)| chunk.iter().zip(plain_e.coefficients.iter()))
+        .map(|(val, tag)| *tag + *val * wide_mac_key)
+        .collect();
+    zeroize([&mut wide_mac_key]);
+    for plain_e in plain_es.iter_mut() {
+        zeroize(plain_e.coefficients.iter_mut());
+    }
+    Ok(tags)
 }
 
+/// Receives and decrypts the tags of the current round, see `DealerState::Authenticate`, which
+/// is updated after each ciphertext.
 async fn recv_mac_tags<P>(
     bincode_rx: &mut AsyncBincodeReader<quinn::RecvStream, Message<P>>,
     ctx: &CrtContext<P::CiphertextParams>,
     sk: &SecretKey<P::BgvParams>,
+    state: &mut DealerState,
+    domain: KeyDomain,
     n: usize,
-) -> Vec<P::KS>
+) -> Result<Vec<P::KS>, DealerError>
 where
     P: DealerParameters,
 {
-    // We skip steps 4-6, because in practice the check in step 6 is not required.
+    // Steps 2 and 4-6 are only performed in strict mode, see `LowGearDealer::set_strict()`.
 
-    // TODO: return error instead of unwrapping.
-    let plain_d = match bincode_rx.next().await.unwrap().unwrap() {
-        Message::Tags(ciphertext) => bgv::decrypt(ctx, sk, &ciphertext).await,
-        _ => panic!("Received message with wrong round number"),
+    let (round, expected) = match *state {
+        DealerState::Authenticate {
+            round, expected, ..
+        } => (round, expected),
+        _ => unreachable!("tags are only received while authenticating"),
     };
-    info!("Auth: decrypted ciphertext");
-    plain_d.coefficients.iter().take(n).copied().collect()
+    let capacity = packing_capacity::<P::PlaintextParams>();
+    let mut tags = Vec::with_capacity(n);
+    let mut received = 0;
+    while tags.len() < n {
+        let ciphertext = match recv(bincode_rx).await? {
+            Message::Tags {
+                domain: d,
+                round: r,
+                chunk: c,
+                ciphertext,
+            } if (d, r, c) == (domain, round, received) => ciphertext,
+            Message::Tags { domain: d, .. } if d != domain => {
+                return Err(DealerError::DomainMismatch {
+                    expected: domain,
+                    received: d,
+                })
+            }
+            Message::Tags {
+                round: r, chunk: c, ..
+            } => {
+                return Err(DealerError::TagsOutOfOrder {
+                    expected_round: round,
+                    expected_chunk: received,
+                    round: r,
+                    chunk: c,
+                })
+            }
+            message => {
+                return Err(DealerError::UnexpectedMessage {
+                    state: *state,
+                    received: message.kind(),
+                })
+            }
+        };
+        // The tags are correct if the coefficients of `m + t e` have a magnitude below `q/2`, which
+        // the drowning noise of the other party leaves room for, see `noise::drown_bits()`.
+        let mut centered = bgv::decrypt_centered(ctx, sk, &ciphertext).await;
+        let mut plain_d = PowerPoly::new();
+        bgv::reduce_centered_into::<P::BgvParams>(&centered, &mut plain_d);
+        zeroize(centered.iter_mut());
+        info!("Auth: decrypted ciphertext");
+        let len = capacity.min(n - tags.len());
+        tags.extend(plain_d.coefficients.iter().take(len).copied());
+        zeroize(plain_d.coefficients.iter_mut());
+        received += 1;
+        *state = DealerState::Authenticate {
+            round,
+            received,
+            expected,
+        };
+    }
+    Ok(tags)
 }
 
-const fn packing_capacity<P>() -> usize
+pub const fn packing_capacity<P>() -> usize
 where
     P: PolyParameters,
 {
     P::CYCLOTOMIC_DEGREE
 }
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::Random;
+
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::MacKeyShare;
+    use crate::util::SlotUsage;
+
+    use super::params::ToyDealerK32S32;
+    use super::{
+        packing_capacity, DealerError, DealerParameters, DealerState, KeyDomain, LowGearDealer,
+        MessageKind,
+    };
+
+    type P = ToyDealerK32S32;
+    type K = <P as DealerParameters>::K;
+    type S = <P as DealerParameters>::S;
+    type KS = <P as DealerParameters>::KS;
+
+    #[tokio::test]
+    async fn refresh_keys() {
+        const P0_ADDR: &str = "[::1]:50069";
+        const P1_ADDR: &str = "[::1]:50070";
+
+        let (mac_keys, values) = {
+            let mut rng = rand::thread_rng();
+            let mac_keys = [S::random(&mut rng), S::random(&mut rng)];
+            let values = [(); 2].map(|_| [(); 8].map(|_| K::random(&mut rng)));
+            (mac_keys, values)
+        };
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (dealer0, dealer1) = tokio::join!(
+            LowGearDealer::<P>::new(&mut conn0, MacKeyShare::from_secret(mac_keys[0])),
+            LowGearDealer::<P>::new(&mut conn1, MacKeyShare::from_secret(mac_keys[1]))
+        );
+        let mut dealers = [dealer0.unwrap(), dealer1.unwrap()];
+
+        for epoch in 0..3 {
+            let [dealer0, dealer1] = &mut dealers;
+            assert_eq!(dealer0.epoch(), epoch);
+            let (tags0, tags1) = tokio::join!(
+                dealer0.authenticate(&values[0]),
+                dealer1.authenticate(&values[1])
+            );
+            let mac_key = KS::from_unsigned(mac_keys[0]) + KS::from_unsigned(mac_keys[1]);
+            for (((x0, x1), t0), t1) in values[0].iter().zip(&values[1]).zip(&tags0).zip(&tags1) {
+                let x = KS::from_unsigned(*x0) + KS::from_unsigned(*x1);
+                assert_eq!(*t0 + *t1, x * mac_key);
+            }
+            tokio::join!(dealer0.refresh_keys(), dealer1.refresh_keys());
+        }
+
+        let capacity = packing_capacity::<<P as DealerParameters>::PlaintextParams>() as u64;
+        let expected = SlotUsage {
+            used: 3 * 8,
+            discarded: 3 * (capacity - 8),
+        };
+        assert_eq!(dealers[0].slot_usage(), expected);
+        assert_eq!(dealers[1].slot_usage(), expected);
+
+        let [dealer0, dealer1] = dealers;
+        tokio::join!(dealer0.finish(), dealer1.finish());
+    }
+
+    #[tokio::test]
+    async fn rounds_out_of_order() {
+        const P0_ADDR: &str = "[::1]:50087";
+        const P1_ADDR: &str = "[::1]:50088";
+
+        let values = {
+            let mut rng = rand::thread_rng();
+            [(); 8].map(|_| K::random(&mut rng))
+        };
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (dealer0, dealer1) = tokio::join!(
+            LowGearDealer::<P>::new(&mut conn0, MacKeyShare::random(&mut rand::thread_rng())),
+            LowGearDealer::<P>::new(&mut conn1, MacKeyShare::random(&mut rand::thread_rng()))
+        );
+        let (mut dealer0, mut dealer1) = (dealer0.unwrap(), dealer1.unwrap());
+        assert_eq!(dealer0.state(), DealerState::Ready);
+        assert_eq!(dealer1.state(), DealerState::Ready);
+
+        // Party 0 refreshes its keys, while party 1 authenticates.
+        let (res0, res1) = tokio::join!(
+            dealer0.try_refresh_keys(),
+            dealer1.try_authenticate(&values)
+        );
+        assert!(matches!(
+            res0,
+            Err(DealerError::UnexpectedMessage {
+                state: DealerState::Refresh { epoch: 1 },
+                received: MessageKind::Tags,
+            })
+        ));
+        assert!(matches!(
+            res1,
+            Err(DealerError::UnexpectedMessage {
+                state: DealerState::Authenticate {
+                    round: 0,
+                    received: 0,
+                    expected: 1,
+                },
+                received: MessageKind::Refresh,
+            })
+        ));
+        assert_eq!(dealer0.epoch(), 0);
+        assert_eq!(dealer1.round(), 0);
+
+        // The dealers can't recover, since the messages of the failed round are still in flight.
+        for dealer in [&mut dealer0, &mut dealer1] {
+            assert_eq!(dealer.state(), DealerState::Failed);
+            assert!(matches!(
+                dealer.try_authenticate(&values).await,
+                Err(DealerError::NotReady(DealerState::Failed))
+            ));
+        }
+
+        tokio::join!(dealer0.finish(), dealer1.finish());
+    }
+
+    #[tokio::test]
+    async fn key_domains() {
+        const P0_ADDR: &str = "[::1]:50105";
+        const P1_ADDR: &str = "[::1]:50106";
+
+        let (mac_keys, values) = {
+            let mut rng = rand::thread_rng();
+            let mac_keys = [(); 2].map(|_| [S::random(&mut rng), S::random(&mut rng)]);
+            let values = [(); 2].map(|_| [(); 8].map(|_| K::random(&mut rng)));
+            (mac_keys, values)
+        };
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (dealer0, dealer1) = tokio::join!(
+            LowGearDealer::<P>::new(&mut conn0, MacKeyShare::from_secret(mac_keys[0][0])),
+            LowGearDealer::<P>::new(&mut conn1, MacKeyShare::from_secret(mac_keys[0][1]))
+        );
+        let (mut dealer0, mut dealer1) = (dealer0.unwrap(), dealer1.unwrap());
+        let (domain0, domain1) = tokio::join!(
+            dealer0.add_domain(MacKeyShare::from_secret(mac_keys[1][0])),
+            dealer1.add_domain(MacKeyShare::from_secret(mac_keys[1][1]))
+        );
+        assert_eq!((domain0, domain1), (KeyDomain(1), KeyDomain(1)));
+        assert_eq!(dealer0.num_domains(), 2);
+
+        for refresh in [false, true] {
+            if refresh {
+                tokio::join!(dealer0.refresh_keys(), dealer1.refresh_keys());
+            }
+            for (domain, mac_keys) in [KeyDomain::DEFAULT, KeyDomain(1)].into_iter().zip(mac_keys) {
+                let (tags0, tags1) = tokio::join!(
+                    dealer0.authenticate_in(domain, &values[0]),
+                    dealer1.authenticate_in(domain, &values[1])
+                );
+                let mac_key = KS::from_unsigned(mac_keys[0]) + KS::from_unsigned(mac_keys[1]);
+                for (((x0, x1), t0), t1) in values[0].iter().zip(&values[1]).zip(&tags0).zip(&tags1)
+                {
+                    let x = KS::from_unsigned(*x0) + KS::from_unsigned(*x1);
+                    assert_eq!(*t0 + *t1, x * mac_key);
+                }
+            }
+        }
+
+        assert!(matches!(
+            dealer0.try_authenticate_in(KeyDomain(2), &values[0]).await,
+            Err(DealerError::UnknownDomain(KeyDomain(2)))
+        ));
+        assert_eq!(dealer0.state(), DealerState::Ready);
+
+        tokio::join!(dealer0.finish(), dealer1.finish());
+    }
+    #[tokio::test]
+    async fn strict_check() {
+        const P0_ADDR: &str = "[::1]:50121";
+        const P1_ADDR: &str = "[::1]:50122";
+
+        let (mac_keys, values) = {
+            let mut rng = rand::thread_rng();
+            let mac_keys = [S::random(&mut rng), S::random(&mut rng)];
+            let values = [(); 2].map(|_| [(); 8].map(|_| K::random(&mut rng)));
+            (mac_keys, values)
+        };
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (dealer0, dealer1) = tokio::join!(
+            LowGearDealer::<P>::new(&mut conn0, MacKeyShare::from_secret(mac_keys[0])),
+            LowGearDealer::<P>::new(&mut conn1, MacKeyShare::from_secret(mac_keys[1]))
+        );
+        let (mut dealer0, mut dealer1) = (dealer0.unwrap(), dealer1.unwrap());
+        dealer0.set_strict(true);
+        dealer1.set_strict(true);
+
+        for _ in 0..2 {
+            let (tags0, tags1) = tokio::join!(
+                dealer0.try_authenticate(&values[0]),
+                dealer1.try_authenticate(&values[1])
+            );
+            let (tags0, tags1) = (tags0.unwrap(), tags1.unwrap());
+            assert_eq!(tags0.len(), values[0].len());
+            let mac_key = KS::from_unsigned(mac_keys[0]) + KS::from_unsigned(mac_keys[1]);
+            for (((x0, x1), t0), t1) in values[0].iter().zip(&values[1]).zip(&tags0).zip(&tags1) {
+                let x = KS::from_unsigned(*x0) + KS::from_unsigned(*x1);
+                assert_eq!(*t0 + *t1, x * mac_key);
+            }
+        }
+        assert_eq!(dealer0.state(), DealerState::Ready);
+        assert_eq!(dealer1.state(), DealerState::Ready);
+        assert_eq!(dealer0.round(), 2);
+
+        tokio::join!(dealer0.finish(), dealer1.finish());
+    }
+}
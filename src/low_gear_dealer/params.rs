@@ -1,10 +1,11 @@
+#[cfg(feature = "params-k32")]
+use crate::bgv::params::{phi21851_mod_p188::Phi21851ModP188, phi21851_mod_t64::Phi21851ModT64};
+#[cfg(feature = "params-k64")]
+use crate::bgv::params::{phi21851_mod_p316::Phi21851ModP316, phi21851_mod_t128::Phi21851ModT128};
+#[cfg(feature = "params-k128")]
+use crate::bgv::params::{phi21851_mod_p444::Phi21851ModP444, phi21851_mod_t192::Phi21851ModT192};
 use crate::bgv::{
-    params::{
-        phi179_mod_p163::Phi179ModP163, phi179_mod_t64::Phi179ModT64,
-        phi21851_mod_p188::Phi21851ModP188, phi21851_mod_p316::Phi21851ModP316,
-        phi21851_mod_p444::Phi21851ModP444, phi21851_mod_t128::Phi21851ModT128,
-        phi21851_mod_t192::Phi21851ModT192, phi21851_mod_t64::Phi21851ModT64,
-    },
+    params::{phi179_mod_p163::Phi179ModP163, phi179_mod_t64::Phi179ModT64},
     residue::native::NativeResidue,
 };
 
@@ -22,9 +23,11 @@ impl DealerParameters for ToyDealerK32S32 {
     type KS = NativeResidue<64, 1>;
 }
 
+#[cfg(feature = "params-k32")]
 #[derive(Debug, PartialEq)]
 pub struct DealerK32S32 {}
 
+#[cfg(feature = "params-k32")]
 impl DealerParameters for DealerK32S32 {
     type PlaintextParams = Phi21851ModT64;
     type CiphertextParams = Phi21851ModP188;
@@ -34,9 +37,11 @@ impl DealerParameters for DealerK32S32 {
     type KS = NativeResidue<64, 1>;
 }
 
+#[cfg(feature = "params-k64")]
 #[derive(Debug, PartialEq)]
 pub struct DealerK64S64 {}
 
+#[cfg(feature = "params-k64")]
 impl DealerParameters for DealerK64S64 {
     type PlaintextParams = Phi21851ModT128;
     type CiphertextParams = Phi21851ModP316;
@@ -46,9 +51,11 @@ impl DealerParameters for DealerK64S64 {
     type KS = NativeResidue<128, 2>;
 }
 
+#[cfg(feature = "params-k128")]
 #[derive(Debug, PartialEq)]
 pub struct DealerK128S64 {}
 
+#[cfg(feature = "params-k128")]
 impl DealerParameters for DealerK128S64 {
     type PlaintextParams = Phi21851ModT192;
     type CiphertextParams = Phi21851ModP444;
@@ -0,0 +1,309 @@
+//! Selection of the subprotocols that are set up, see `LowGearPreprocessorBuilder`.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+
+use crate::bgv::residue::GenericResidue;
+use crate::bgv::zkpopk::ZkpopkVersion;
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+use crate::context_set::ContextSet;
+use crate::crypto_suite::CryptoSuite;
+use crate::dealer::{Dealer, DealerBackend};
+use crate::interface::{MacKeyOf, MacKeyShare, Share, ShareOf, ZeroSharePreprocessor};
+use crate::mac_check_opener::{MacCheckFailed, MacCheckOpener, MaskStrategy, OpenerOf};
+use crate::role::Role;
+use crate::transcript::Transcript;
+
+use super::{
+    get_zero_shares_with, validate, InitError, LowGearPreprocessor, PreprocessorParameters,
+};
+
+/// Builds either a full `LowGearPreprocessor` or a `LowGearAuthenticator`, which only sets up the
+/// subprotocols needed for authentication.
+///
+/// Beaver triples always need the dealer, the opener and the truncer: the truncation is required
+/// whenever `S` has a positive number of bits, which `validate()` enforces via `KS = K + S`.
+pub struct LowGearPreprocessorBuilder<P, const PID: usize>
+where
+    P: PreprocessorParameters,
+{
+    contexts: Option<Arc<ContextSet>>,
+    mac_key: Option<MacKeyOf<P>>,
+    dealer: DealerBackend,
+    crypto_suite: CryptoSuite,
+    zkpopk_version: ZkpopkVersion,
+    _params: PhantomData<P>,
+}
+
+impl<P, const PID: usize> LowGearPreprocessorBuilder<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    pub fn new() -> Self {
+        Self {
+            contexts: None,
+            mac_key: None,
+            dealer: DealerBackend::default(),
+            crypto_suite: CryptoSuite::default(),
+            zkpopk_version: ZkpopkVersion::default(),
+            _params: PhantomData,
+        }
+    }
+
+    /// Takes the `CrtContext`s from `contexts` instead of generating them.
+    pub fn contexts(mut self, contexts: Arc<ContextSet>) -> Self {
+        self.contexts = Some(contexts);
+        self
+    }
+
+    /// Uses `mac_key` instead of a random MAC key share, e.g., the one of a `Checkpoint` to
+    /// resume its batch.
+    pub fn mac_key(mut self, mac_key: MacKeyOf<P>) -> Self {
+        self.mac_key = Some(mac_key);
+        self
+    }
+
+    /// Authenticates with `dealer` instead of the `LowGearDealer`, e.g., with a
+    /// `ThirdPartyDealer`.
+    pub fn dealer(mut self, dealer: DealerBackend) -> Self {
+        self.dealer = dealer;
+        self
+    }
+
+    /// Hashes the commitments, the transcripts and the challenges of the ZKPoPK with `suite`
+    /// instead of SHA-256.  Both parties must use the same suite.
+    pub fn crypto_suite(mut self, suite: CryptoSuite) -> Self {
+        self.crypto_suite = suite;
+        self
+    }
+
+    /// Derives the challenges of the ZKPoPK as in `version` instead of the latest version, e.g., to
+    /// reproduce the proofs of an earlier run.  The parties exchange their versions during the
+    /// setup, and `build()` fails if they differ.
+    pub fn zkpopk_version(mut self, version: ZkpopkVersion) -> Self {
+        self.zkpopk_version = version;
+        self
+    }
+
+    /// Sets up all subprotocols, see `LowGearPreprocessor::with_contexts()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are inconsistent, see `validate()`.
+    pub async fn build(
+        self,
+        conn: &mut Connection,
+    ) -> Result<LowGearPreprocessor<P, PID>, InitError> {
+        let contexts = self.contexts.unwrap_or_default();
+        let mac_key = self
+            .mac_key
+            .unwrap_or_else(|| MacKeyShare::random(&mut rand::thread_rng()));
+        LowGearPreprocessor::with_dealer(
+            conn,
+            &contexts,
+            mac_key,
+            self.dealer,
+            self.crypto_suite,
+            self.zkpopk_version,
+        )
+        .await
+    }
+
+    /// Sets up only the dealer and the opener, i.e., no BGV keys of the preprocessor, no ZKPoPK
+    /// and no truncation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are inconsistent, see `validate()`.
+    pub async fn build_authenticator(
+        self,
+        conn: &mut Connection,
+    ) -> Result<LowGearAuthenticator<P, PID>, StreamError> {
+        if let Err(e) = validate::<P>() {
+            panic!("invalid parameters {}: {}", std::any::type_name::<P>(), e);
+        }
+        let contexts = self.contexts.unwrap_or_default();
+        let mac_key = self
+            .mac_key
+            .unwrap_or_else(|| MacKeyShare::random(&mut rand::thread_rng()));
+
+        let (mut conn_dealer, mut conn_opener) = (conn.fork(), conn.fork());
+        let (dealer, opener, ch_init) = tokio::join!(
+            Dealer::new(&mut conn_dealer, self.dealer, mac_key.clone(), &contexts),
+            MacCheckOpener::new(&mut conn_opener, mac_key.clone()),
+            BiChannel::<[u8; 32]>::open(conn, "LowGearAuthenticator:init"),
+        );
+        let (mut dealer, mut opener, mut ch_init) = (dealer?, opener?, ch_init?);
+        dealer.set_crypto_suite(self.crypto_suite);
+        opener.set_crypto_suite(self.crypto_suite);
+
+        // Without public keys of its own, the authenticator binds the session to fresh nonces.
+        let nonce: [u8; 32] = rand::random();
+        let (rx_init, tx_init) = ch_init.split();
+        let (_, remote_nonce) = tokio::join!(
+            async {
+                // TODO: return error instead of unwrapping.
+                tx_init.send(nonce).await.unwrap();
+            },
+            // TODO: return error instead of unwrapping.
+            async { rx_init.next().await.unwrap().unwrap() }
+        );
+        let mut transcript = Transcript::with_suite(self.crypto_suite, "LowGearAuthenticator");
+        if Role::of::<PID>().is_p0() {
+            transcript.append_bytes("nonce_0", &nonce);
+            transcript.append_bytes("nonce_1", &remote_nonce);
+        } else {
+            transcript.append_bytes("nonce_0", &remote_nonce);
+            transcript.append_bytes("nonce_1", &nonce);
+        }
+        opener.bind_session(transcript.session_id());
+
+        Ok(LowGearAuthenticator {
+            dealer,
+            opener,
+            mac_key,
+        })
+    }
+}
+
+impl<P, const PID: usize> Default for LowGearPreprocessorBuilder<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The authentication part of a `LowGearPreprocessor`, see
+/// `LowGearPreprocessorBuilder::build_authenticator()`.
+pub struct LowGearAuthenticator<P, const PID: usize>
+where
+    P: PreprocessorParameters,
+{
+    dealer: Dealer<P::DealerParams>,
+    opener: OpenerOf<P>,
+    mac_key: MacKeyOf<P>,
+}
+
+impl<P, const PID: usize> LowGearAuthenticator<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    /// This party's share of the MAC key.
+    pub fn mac_key(&self) -> &MacKeyOf<P> {
+        &self.mac_key
+    }
+
+    /// Authenticates this party's additive shares of values.  Both parties must call this with
+    /// the same number of values.
+    pub async fn authenticate(&mut self, values: &[P::K]) -> Vec<ShareOf<P, PID>> {
+        let tags = self.dealer.authenticate(values).await;
+        values
+            .iter()
+            .zip(tags)
+            .map(|(val, tag)| Share::new(P::KS::from_unsigned(*val), tag))
+            .collect()
+    }
+
+    /// Like `ZeroSharePreprocessor::get_zero_shares()`, but returns an error if the MAC check
+    /// fails.
+    pub async fn try_get_zero_shares(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<ShareOf<P, PID>>, MacCheckFailed> {
+        get_zero_shares_with::<P, PID>(&mut self.dealer, &mut self.opener, &self.mac_key, n).await
+    }
+
+    /// Sets how the masks of the MAC checks are derived, see `MaskStrategy`.  Both parties must use
+    /// the same strategy.
+    pub fn set_mask_strategy(&mut self, strategy: MaskStrategy) {
+        self.opener.set_mask_strategy(strategy);
+    }
+
+    /// Lets the dealer check the consistency of the MAC tags, see `LowGearDealer::set_strict()`.
+    /// Both parties must use the same setting.
+    pub fn set_strict_authentication(&mut self, strict: bool) {
+        self.dealer.set_strict(strict);
+    }
+
+    /// Refreshes the keys of the dealer, see `LowGearDealer::refresh_keys()`.
+    pub async fn refresh_dealer_keys(&mut self) {
+        self.dealer.refresh_keys().await;
+    }
+
+    pub async fn finish(self) {
+        self.dealer.finish().await;
+        self.opener.finish().await;
+    }
+}
+
+#[async_trait]
+impl<P, const PID: usize> ZeroSharePreprocessor<P::KS, P::K, PID> for LowGearAuthenticator<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    async fn get_zero_shares(&mut self, n: usize) -> Vec<ShareOf<P, PID>> {
+        // TODO: return error instead of unwrapping.
+        self.try_get_zero_shares(n).await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{Random, Zero};
+
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+    use crate::verify::check_mac;
+
+    use super::LowGearPreprocessorBuilder;
+
+    type P = ToyPreprocK32S32;
+    type K = <P as PreprocessorParameters>::K;
+
+    #[tokio::test]
+    async fn authenticator() {
+        const P0_ADDR: &str = "[::1]:50083";
+        const P1_ADDR: &str = "[::1]:50084";
+
+        let values = {
+            let mut rng = rand::thread_rng();
+            [(); 2].map(|_| [(); 8].map(|_| K::random(&mut rng)))
+        };
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (auth0, auth1) = tokio::join!(
+            LowGearPreprocessorBuilder::<P, 0>::new().build_authenticator(&mut conn0),
+            LowGearPreprocessorBuilder::<P, 1>::new().build_authenticator(&mut conn1)
+        );
+        let (mut auth0, mut auth1) = (auth0.unwrap(), auth1.unwrap());
+
+        let (shares0, shares1) = tokio::join!(
+            auth0.authenticate(&values[0]),
+            auth1.authenticate(&values[1])
+        );
+        for (share0, share1) in shares0.iter().zip(&shares1) {
+            assert!(check_mac(share0, share1, auth0.mac_key(), auth1.mac_key()));
+        }
+
+        let (zeros0, zeros1) =
+            tokio::join!(auth0.try_get_zero_shares(4), auth1.try_get_zero_shares(4));
+        for (zero0, zero1) in zeros0.unwrap().iter().zip(&zeros1.unwrap()) {
+            assert_eq!(K::from_unsigned(zero0.val + zero1.val), K::ZERO);
+            assert!(check_mac(zero0, zero1, auth0.mac_key(), auth1.mac_key()));
+        }
+
+        tokio::join!(auth0.finish(), auth1.finish());
+    }
+}
@@ -0,0 +1,297 @@
+//! Checkpoints of long batches, see `LowGearPreprocessor::try_get_beaver_triples_checkpointed()`.
+//!
+//! After each of the `ZKPOPK_AMORTIZE` iterations of a batch, the triples of the iterations that
+//! were completed so far are saved to a `CheckpointStore`.  If a party crashes, both parties set up
+//! a new `LowGearPreprocessor` with the MAC key share that they used before (see
+//! `LowGearPreprocessorBuilder::mac_key()`), and the next checkpointed batch starts with a resume
+//! handshake: if both parties have a checkpoint of the same batch and iteration, they only generate
+//! the remaining iterations.  Otherwise, both discard their checkpoints and restart the batch.
+//!
+//! The MAC key share itself is not part of a checkpoint, so the application must keep it
+//! separately.  A checkpoint only contains its digest (see `MacKeyShare::digest()`), which
+//! identifies the key of the triples.  The triples are this party's secret shares, so a checkpoint
+//! must still be stored as securely as the triples themselves.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::interface::BeaverTriple;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(deserialize = ""))]
+pub struct Checkpoint<KS, K, S, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    /// Number of batches that were completed before this one.
+    pub batch: u64,
+    /// Number of iterations of this batch that were completed.
+    pub iterations: usize,
+    /// The triples of the completed iterations.
+    pub triples: Vec<BeaverTriple<KS, K, PID>>,
+    /// Digest of this party's share of the MAC key under which the triples are authenticated, see
+    /// `MacKeyShare::digest()`.
+    pub mac_key_digest: [u8; 32],
+    pub phantom: PhantomData<S>,
+}
+
+impl<KS, K, S, const PID: usize> Checkpoint<KS, K, S, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    pub fn resume_point(&self) -> ResumePoint {
+        ResumePoint {
+            batch: self.batch,
+            iterations: self.iterations,
+        }
+    }
+}
+
+/// The position of a checkpoint, which the parties exchange in the resume handshake.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ResumePoint {
+    pub batch: u64,
+    pub iterations: usize,
+}
+
+pub trait CheckpointStore<KS, K, S, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    /// Returns the last saved checkpoint, if any.
+    fn load(&mut self) -> io::Result<Option<Checkpoint<KS, K, S, PID>>>;
+
+    /// Replaces the saved checkpoint.
+    fn save(&mut self, checkpoint: &Checkpoint<KS, K, S, PID>) -> io::Result<()>;
+
+    /// Removes the saved checkpoint, e.g., once its batch is completed.
+    fn clear(&mut self) -> io::Result<()>;
+}
+
+/// Keeps the checkpoint in a file.  The file is replaced atomically, so a crash while saving
+/// leaves the previous checkpoint.
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl<KS, K, S, const PID: usize> CheckpointStore<KS, K, S, PID> for FileCheckpointStore
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+    Checkpoint<KS, K, S, PID>: Serialize + DeserializeOwned,
+{
+    fn load(&mut self) -> io::Result<Option<Checkpoint<KS, K, S, PID>>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => bincode::deserialize(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&mut self, checkpoint: &Checkpoint<KS, K, S, PID>) -> io::Result<()> {
+        let bytes = bincode::serialize(checkpoint)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Keeps the checkpoint in memory, e.g., for tests or to hand it to another store.
+pub struct MemoryCheckpointStore<KS, K, S, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    pub checkpoint: Option<Checkpoint<KS, K, S, PID>>,
+}
+
+impl<KS, K, S, const PID: usize> Default for MemoryCheckpointStore<KS, K, S, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    fn default() -> Self {
+        Self { checkpoint: None }
+    }
+}
+
+impl<KS, K, S, const PID: usize> CheckpointStore<KS, K, S, PID>
+    for MemoryCheckpointStore<KS, K, S, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    fn load(&mut self) -> io::Result<Option<Checkpoint<KS, K, S, PID>>> {
+        Ok(self.checkpoint.clone())
+    }
+
+    fn save(&mut self, checkpoint: &Checkpoint<KS, K, S, PID>) -> io::Result<()> {
+        self.checkpoint = Some(checkpoint.clone());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.checkpoint = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::connection::Connection;
+    use crate::interface::{reconstruct, BeaverTriple, MacKeyShare};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::{LowGearPreprocessor, PreprocessorError, PreprocessorParameters};
+
+    use super::{Checkpoint, CheckpointStore, MemoryCheckpointStore};
+
+    type P = ToyPreprocK32S32;
+    type K = <P as PreprocessorParameters>::K;
+    type KS = <P as PreprocessorParameters>::KS;
+    type S = <P as PreprocessorParameters>::S;
+
+    /// A store that fails to save the checkpoint of iteration `crash_at`, like a crashed party.
+    struct CrashingStore<const PID: usize> {
+        inner: MemoryCheckpointStore<KS, K, S, PID>,
+        crash_at: Option<usize>,
+    }
+
+    impl<const PID: usize> CheckpointStore<KS, K, S, PID> for CrashingStore<PID> {
+        fn load(&mut self) -> io::Result<Option<Checkpoint<KS, K, S, PID>>> {
+            self.inner.load()
+        }
+
+        fn save(&mut self, checkpoint: &Checkpoint<KS, K, S, PID>) -> io::Result<()> {
+            if Some(checkpoint.iterations) == self.crash_at {
+                return Err(io::Error::other("crash"));
+            }
+            self.inner.save(checkpoint)
+        }
+
+        fn clear(&mut self) -> io::Result<()> {
+            self.inner.clear()
+        }
+    }
+
+    async fn run_batch<const PID: usize>(
+        conn: &mut Connection,
+        store: &mut CrashingStore<PID>,
+        mac_key: &MacKeyShare<S>,
+    ) -> Result<Vec<BeaverTriple<KS, K, PID>>, PreprocessorError> {
+        let mut preproc = LowGearPreprocessor::<P, PID>::builder()
+            .mac_key(mac_key.clone())
+            .build(conn)
+            .await
+            .unwrap();
+        preproc.try_get_beaver_triples_checkpointed(store).await
+    }
+
+    #[tokio::test]
+    async fn resume_after_crash() {
+        const P0_ADDR: &str = "[::1]:50119";
+        const P1_ADDR: &str = "[::1]:50120";
+        const CRASH_AT: usize = 2;
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let mut store0 = CrashingStore::<0> {
+            inner: MemoryCheckpointStore::default(),
+            crash_at: Some(CRASH_AT),
+        };
+        let mut store1 = CrashingStore::<1> {
+            inner: MemoryCheckpointStore::default(),
+            crash_at: Some(CRASH_AT),
+        };
+        // The application keeps the MAC key shares, since the checkpoints only contain digests.
+        let mut rng = rand::thread_rng();
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (result0, result1) = tokio::join!(
+            run_batch(&mut fork0, &mut store0, &mac_keys[0]),
+            run_batch(&mut fork1, &mut store1, &mac_keys[1])
+        );
+        assert!(matches!(
+            result0,
+            Err(PreprocessorError::CheckpointFailed(_))
+        ));
+        assert!(matches!(
+            result1,
+            Err(PreprocessorError::CheckpointFailed(_))
+        ));
+        let checkpoint0 = store0.inner.checkpoint.clone().unwrap();
+        let checkpoint1 = store1.inner.checkpoint.clone().unwrap();
+        assert_eq!(checkpoint0.resume_point(), checkpoint1.resume_point());
+        assert_eq!(checkpoint0.iterations, CRASH_AT - 1);
+        assert_eq!(checkpoint0.mac_key_digest, mac_keys[0].digest());
+
+        store0.crash_at = None;
+        store1.crash_at = None;
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (triples0, triples1) = tokio::join!(
+            run_batch(&mut fork0, &mut store0, &mac_keys[0]),
+            run_batch(&mut fork1, &mut store1, &mac_keys[1])
+        );
+        let (triples0, triples1) = (triples0.unwrap(), triples1.unwrap());
+        assert!(store0.inner.checkpoint.is_none() && store1.inner.checkpoint.is_none());
+
+        // The triples of the checkpoint are kept, and the others are generated under the same MAC
+        // key.
+        assert_eq!(triples0.len(), triples1.len());
+        for (resumed, checkpointed) in triples0.iter().zip(&checkpoint0.triples) {
+            assert_eq!(resumed.c.tag, checkpointed.c.tag);
+        }
+        let mac_key = mac_keys[0].widen::<KS>() + mac_keys[1].widen::<KS>();
+        for (triple0, triple1) in triples0.iter().zip(&triples1) {
+            let a = reconstruct(&triple0.a, &triple1.a);
+            let b = reconstruct(&triple0.b, &triple1.b);
+            assert_eq!(a * b, reconstruct(&triple0.c, &triple1.c));
+            for (share0, share1) in [
+                (&triple0.a, &triple1.a),
+                (&triple0.b, &triple1.b),
+                (&triple0.c, &triple1.c),
+            ] {
+                assert_eq!(share0.tag + share1.tag, (share0.val + share1.val) * mac_key);
+            }
+        }
+    }
+}
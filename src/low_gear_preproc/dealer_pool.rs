@@ -0,0 +1,117 @@
+//! Prefetching of authenticated random values, see `LowGearPreprocessor::enable_dealer_prefetch()`.
+//!
+//! Each iteration of a batch authenticates the packed values of `b` and the masking values of the
+//! batch check before its VOLE, which stalls the iteration for the rounds of the dealer.  A
+//! `DealerPool` runs a second `LowGearDealer` in a background task that keeps up to `depth` of these
+//! authentications ready, so the dealer overlaps with the ZKPoPK and the VOLE.  Together with a
+//! `BufferedPreprocessor`, which buffers the triples, the preprocessing is buffered at two levels.
+//!
+//! The tasks of both parties run the same sequence of dealer rounds, and the preprocessors take the
+//! results in the same order, so the values of both parties that are taken together belong to the
+//! same round.
+
+use log::warn;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::bgv::tweaked_interpolation_packing::get_random_unpacked;
+use crate::connection::{Connection, StreamError};
+use crate::crypto_suite::CryptoSuite;
+use crate::interface::MacKeyShare;
+use crate::low_gear_dealer::{DealerError, LowGearDealer};
+use crate::sampling;
+
+use super::PreprocessorParameters;
+
+/// Random values of `K` and their MAC tags, which the dealer authenticated in one round.
+pub struct Authenticated<P>
+where
+    P: PreprocessorParameters,
+{
+    pub values: Vec<P::K>,
+    pub tags: Vec<P::KS>,
+}
+
+pub struct DealerPool<P>
+where
+    P: PreprocessorParameters,
+{
+    rx: mpsc::Receiver<Result<Authenticated<P>, DealerError>>,
+    num_masks: usize,
+    strict: bool,
+    task: JoinHandle<()>,
+}
+
+impl<P> DealerPool<P>
+where
+    P: PreprocessorParameters,
+{
+    /// Sets up the dealer of the pool on `conn` and starts prefetching.  Each authentication
+    /// covers the values of `b` of an iteration and `num_masks` masking values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is zero.
+    pub async fn new(
+        conn: &mut Connection,
+        mac_key: MacKeyShare<P::S>,
+        depth: usize,
+        num_masks: usize,
+        strict: bool,
+        crypto_suite: CryptoSuite,
+    ) -> Result<Self, StreamError> {
+        assert!(depth > 0, "the depth of the dealer pool must be positive");
+        let mut dealer = LowGearDealer::<P::DealerParams>::new(conn, mac_key).await?;
+        dealer.set_strict(strict);
+        dealer.set_crypto_suite(crypto_suite);
+        let (tx, rx) = mpsc::channel(depth);
+        let task = tokio::task::spawn(async move {
+            loop {
+                let mut values =
+                    get_random_unpacked::<P::PlaintextParams, P::K>(rand::thread_rng());
+                sampling::extend_random(rand::thread_rng(), &mut values, num_masks);
+                let result = dealer
+                    .try_authenticate(&values)
+                    .await
+                    .map(|tags| Authenticated { values, tags });
+                let failed = result.is_err();
+                // The pool is dropped or the dealer failed, which ends the dealer of the other
+                // party as well.
+                if tx.send(result).await.is_err() || failed {
+                    break;
+                }
+            }
+            dealer.finish().await;
+        });
+        Ok(Self {
+            rx,
+            num_masks,
+            strict,
+            task,
+        })
+    }
+
+    /// Whether the pooled values fit an iteration with `num_masks` masking values and a dealer
+    /// with the given `strict` setting.  Otherwise, the preprocessor authenticates with its own
+    /// dealer, e.g., after `set_mask_strategy()`.
+    pub fn matches(&self, num_masks: usize, strict: bool) -> bool {
+        self.num_masks == num_masks && self.strict == strict
+    }
+
+    /// Takes the next authentication, waiting for it if none is ready.
+    pub async fn take(&mut self) -> Result<Authenticated<P>, DealerError> {
+        match self.rx.recv().await {
+            Some(result) => result,
+            // The task only stops after an error, which was taken before.
+            None => panic!("dealer pool stopped after an error"),
+        }
+    }
+
+    /// Stops prefetching and finishes the dealer of the pool.  Pooled values are discarded.
+    pub async fn finish(self) {
+        drop(self.rx);
+        if let Err(e) = self.task.await {
+            warn!("dealer pool: task failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Estimates of the memory usage of a `LowGearPreprocessor`.
+//!
+//! The estimates are computed from the parameters alone, nothing is measured.  They are meant for
+//! choosing the number of concurrent batches, and the actual usage may differ.
+//!
+//! The dominating parts are the polynomials of the ciphertext ring.  In particular, a preprocessor
+//! holds up to `max_amortize()` ciphertexts on its `a_stack` plus the same number of ciphertexts
+//! that are in flight during the ZKPoPK, see `ZKPOPK_MAX_STACK_BATCHES`.  The estimates ignore
+//! allocator overhead and small buffers.
+
+use std::mem::size_of;
+
+use crate::bgv::poly::crt::CrtPolyParameters;
+use crate::bgv::poly::{CrtStrategy, PolyParameters};
+use crate::bgv::tweaked_interpolation_packing::packing_capacity;
+
+use super::{max_amortize, PreprocessorParameters};
+
+/// Estimated peak memory usage of one `LowGearPreprocessor` in bytes, split by purpose.  This is
+/// not a measurement, see the module documentation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryEstimate {
+    /// Own and remote keys of the preprocessor and its dealer.
+    pub keys: usize,
+    /// CRT contexts of the ciphertext and plaintext rings.
+    pub contexts: usize,
+    /// Ciphertexts and packed values on the `a_stack`.
+    pub a_stack: usize,
+    /// Ciphertexts and prepared plaintexts in flight during the ZKPoPK.
+    pub zkpopk: usize,
+    /// Ciphertexts and unpacked values in flight during one iteration of the VOLE.
+    pub vole: usize,
+}
+
+impl MemoryEstimate {
+    /// The estimated total in bytes.
+    pub fn total(&self) -> usize {
+        self.keys + self.contexts + self.a_stack + self.zkpopk + self.vole
+    }
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+#[display(
+    fmt = "estimated memory usage of {} bytes exceeds the cap of {} bytes",
+    estimate,
+    cap
+)]
+pub struct MemoryCapExceeded {
+    pub estimate: usize,
+    pub cap: usize,
+}
+
+/// Estimates the memory usage of one `LowGearPreprocessor` with the parameters `P`.
+pub fn memory_footprint<P>() -> MemoryEstimate
+where
+    P: PreprocessorParameters,
+{
+    let cipher_poly = poly_size::<P::CiphertextParams>();
+    let ciphertext = 2 * cipher_poly;
+    let plain_poly = poly_size::<P::PlaintextParams>();
+    let unpacked = packing_capacity::<P::PlaintextParams>() * size_of::<P::KSS>();
+
+    MemoryEstimate {
+        // Secret key, own and remote public key, and the dealer's secret key, remote public key
+        // and remote encrypted MAC key.
+        keys: 10 * cipher_poly,
+        // The dealer has its own ciphertext context.
+        contexts: 2 * context_size::<P::CiphertextParams>() + context_size::<P::PlaintextParams>(),
+        a_stack: max_amortize::<P>() * (ciphertext + unpacked),
+        // Received pre-ciphertexts, and own prepared plaintexts, which hold the noised plaintext
+        // in an extended integer and two noise vectors.
+        zkpopk: max_amortize::<P>() * (ciphertext + 2 * plain_poly + 2 * cipher_poly),
+        // Three ciphertexts in each direction, three masks, and the wide values of a, b, c and
+        // their tags.
+        vole: 6 * ciphertext + 9 * unpacked,
+    }
+}
+
+/// Checks that the estimated memory usage of `num_instances` preprocessors running concurrently
+/// stays below `cap` bytes.
+pub fn check_memory_cap<P>(
+    num_instances: usize,
+    cap: usize,
+) -> Result<MemoryEstimate, MemoryCapExceeded>
+where
+    P: PreprocessorParameters,
+{
+    let footprint = memory_footprint::<P>();
+    let estimate = footprint.total().saturating_mul(num_instances);
+    if estimate > cap {
+        return Err(MemoryCapExceeded { estimate, cap });
+    }
+    Ok(footprint)
+}
+
+fn poly_size<P>() -> usize
+where
+    P: PolyParameters,
+{
+    P::CYCLOTOMIC_DEGREE * size_of::<P::Residue>()
+}
+
+fn context_size<P>() -> usize
+where
+    P: CrtPolyParameters,
+{
+    match P::CRT_STRATEGY {
+        CrtStrategy::Factors { .. } | CrtStrategy::LinearFactors => 2 * poly_size::<P>(),
+        CrtStrategy::Fourier => {
+            let dft_size = (2 * P::CYCLOTOMIC_DEGREE - 1).next_power_of_two();
+            (3 * dft_size + 2 * P::M) * size_of::<P::Residue>()
+        }
+        CrtStrategy::Negacyclic => 3 * poly_size::<P>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+
+    use super::{check_memory_cap, memory_footprint};
+
+    #[test]
+    fn memory_cap() {
+        let total = memory_footprint::<ToyPreprocK32S32>().total();
+        assert!(total > 0);
+        check_memory_cap::<ToyPreprocK32S32>(2, 2 * total).unwrap();
+        let err = check_memory_cap::<ToyPreprocK32S32>(2, 2 * total - 1).unwrap_err();
+        assert_eq!(err.estimate, 2 * total);
+    }
+}
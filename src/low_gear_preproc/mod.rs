@@ -1,35 +1,69 @@
+pub mod builder;
+pub mod checkpoint;
+pub mod dealer_pool;
+pub mod memory;
+pub mod param_info;
 pub mod params;
+pub mod rounds;
 pub mod truncer;
+pub mod zkpopk_stats;
 
 use std::fmt::Debug;
+use std::io;
+use std::marker::PhantomData;
+use std::sync::Arc;
 
 use async_trait::async_trait;
-use crypto_bigint::Random;
+use crypto_bigint::Zero;
 use futures_util::{SinkExt, StreamExt};
-use log::info;
+use log::{error, info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+use crate::abort::{Abort, AbortChannel, AbortReason, InvalidVerifyingKey};
 use crate::bgv::poly::crt::{CrtPoly, CrtPolyParameters};
 use crate::bgv::poly::power::PowerPoly;
-use crate::bgv::poly::CrtContext;
+use crate::bgv::poly::{CrtContext, PolyParameters};
 use crate::bgv::residue::native::GenericNativeResidue;
+use crate::bgv::residue::vec::GenericResidueVec;
+use crate::bgv::residue::wire::wire_len;
 use crate::bgv::tweaked_interpolation_packing::{
     get_random_unpacked, pack, pack_diagonal, pack_mask, packing_capacity, unpack, TIPParameters,
 };
-use crate::bgv::zkpopk::prover::{Prover, ResponseAborted};
+use crate::bgv::witness::EncryptionWitness;
+use crate::bgv::zkpopk::prover::Prover;
 use crate::bgv::zkpopk::verifier::Verifier;
-use crate::bgv::zkpopk::{Challenge, Commitment, Response};
-use crate::bgv::PreparedPlaintext;
+use crate::bgv::zkpopk::{self, Statement, ZkpopkVersion};
 use crate::bgv::{
-    self, residue::GenericResidue, BgvParameters, Ciphertext, Cleartext, PreCiphertext, PublicKey,
-    SecretKey,
+    self, noise, residue::GenericResidue, BgvParameters, Ciphertext, Cleartext, PreCiphertext,
+    PublicKey, SecretKey,
 };
-use crate::bi_channel::BiChannel;
+use crate::bi_channel::BulkChannel;
 use crate::connection::{Connection, StreamError};
-use crate::interface::{BatchedPreprocessor, BeaverTriple, Share};
-use crate::low_gear_dealer::{DealerParameters, LowGearDealer};
-use crate::mac_check_opener::MacCheckOpener;
+use crate::context_set::ContextSet;
+use crate::crypto_suite::CryptoSuite;
+use crate::dealer::{Dealer, DealerBackend};
+use crate::edabit::{self, DaBitError};
+use crate::interface::{
+    BatchedPreprocessor, BeaverTriple, BitDecomposition, BitPreprocessor, DaBit, EdaBit, MacKeyOf,
+    MacKeyShare, Share, ShareOf, SpdzParams, TripleOf, TripleOrigin, ZeroSharePreprocessor,
+};
+use crate::lockstep::Lockstep;
+use crate::low_gear_dealer::{DealerParameters, DealerState};
+use crate::mac_check_opener::{MacCheckFailed, MacCheckOpener, MaskStrategy, OpenerOf};
+use crate::rate_limiter::{RateLimit, RateLimiter};
+use crate::role::Role;
+use crate::round_channel::{RoundChannel, RoundError};
+use crate::sampling;
+use crate::transcript::{SessionId, Transcript};
+use crate::util::{phase, run_blocking, zeroize};
 
-use self::truncer::Truncer;
+use self::builder::LowGearPreprocessorBuilder;
+use self::checkpoint::{Checkpoint, CheckpointStore};
+use self::dealer_pool::DealerPool;
+use self::rounds::VoleOutcome;
+use self::truncer::{Truncation, TruncationError, Truncer, TruncerOf};
+use self::zkpopk_stats::ZkpopkStats;
 
 // Low gear parameters
 pub trait PreprocessorParameters: PartialEq + Debug + Send + Sync + 'static {
@@ -59,68 +93,334 @@ pub trait PreprocessorParameters: PartialEq + Debug + Send + Sync + 'static {
     const ZKPOPK_INV_FAIL_PROB: usize = 256;
 
     const ZKPOPK_MAX_REPS: usize = 16;
+
+    /// Largest number of batches whose values of `a` one ZKPoPK may prove, see
+    /// `set_zkpopk_stack_batches()`.  The bounds of the ZKPoPK grow with the number of proven
+    /// ciphertexts, so the parameters must leave room for `ZKPOPK_MAX_STACK_BATCHES *
+    /// ZKPOPK_AMORTIZE` of them.
+    const ZKPOPK_MAX_STACK_BATCHES: usize = 1;
+
+    /// Number of iterations that may be discarded over the lifetime of a preprocessor because a
+    /// fault was detected, see `RedundancyStats`.
+    const MAX_DISCARDED_ITERATIONS: u64 = 4;
 }
 
+impl<P> SpdzParams for P
+where
+    P: PreprocessorParameters,
+{
+    type K = P::K;
+    type S = P::S;
+    type KS = P::KS;
+    type KSS = P::KSS;
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum PreprocessorError {
+    TruncationFailed(TruncationError),
+    MacCheckFailed(MacCheckFailed),
+    ZkpopkFailed,
+    /// A decryption of the VOLE failed, see `DecryptionStats`.
+    DecryptionFailed,
+    /// More than `MAX_DISCARDED_ITERATIONS` iterations were discarded, see `RedundancyStats`.
+    TooManyFaults,
+    /// More shares of b than `BATCH_SIZE` were given, or a value exceeds `K`, see
+    /// `try_get_correlated_triples()`.
+    InvalidInput,
+    /// The `CheckpointStore` or the resume handshake failed, see
+    /// `try_get_beaver_triples_checkpointed()`.
+    CheckpointFailed(io::Error),
+    /// The other party input a value that is not a bit, see `try_get_dabits()`.
+    NotABit,
+}
+
+/// An error during the setup of a `LowGearPreprocessor`.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum InitError {
+    FailedToOpen(StreamError),
+    FailedToExchange(RoundError),
+    /// The parties use different versions of the ZKPoPK, see
+    /// `LowGearPreprocessorBuilder::zkpopk_version()`.
+    #[display(
+        fmt = "ZKPoPK version mismatch: {:?} (local) and {:?} (remote)",
+        local,
+        remote
+    )]
+    VersionMismatch {
+        local: ZkpopkVersion,
+        remote: ZkpopkVersion,
+    },
+    /// The other party sent an invalid key for the signatures of its aborts.
+    InvalidAbortKey(InvalidVerifyingKey),
+}
+
+impl PreprocessorError {
+    fn from_dabit_error(e: DaBitError) -> Self {
+        match e {
+            DaBitError::MacCheckFailed(e) => Self::MacCheckFailed(e),
+            DaBitError::NotABit => Self::NotABit,
+        }
+    }
+}
+
+/// Failed decryptions in the VOLE, which are detected by `unpack()`.
+///
+/// If the noise of a ciphertext exceeds the correctness bound, the decrypted triples are garbage.
+/// Each party checks its decryptions, and both parties abort if one of them failed.  Both parties
+/// observe the same failures, with `local` and `remote` swapped.  Failures that `unpack()` misses
+/// are caught by the MAC check.
+///
+/// Since the other party controls the noise of the ciphertexts that this party decrypts, a failure
+/// might leak a bit about the secret key.  Hence, the iteration is not regenerated: the first
+/// failure ends the preprocessor, so at most one such bit leaks per key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecryptionStats {
+    /// Number of iterations in which a decryption of this party failed.
+    pub local_failures: u64,
+    /// Number of iterations in which a decryption of the other party failed, or in which the other
+    /// party detected a fault, see `RedundancyStats`.
+    pub remote_failures: u64,
+    /// Number of iterations that were discarded because a party detected a fault, see
+    /// `RedundancyStats`.
+    pub discarded_iterations: u64,
+}
+
+/// Iterations whose local computations were repeated to detect faults, see
+/// `LowGearPreprocessor::set_redundancy()`.
+///
+/// A fault of the hardware, e.g., a flipped bit in an FFT, corrupts the ciphertexts or the
+/// triples of an iteration without any error.  A corrupted ciphertext of this party or a corrupted
+/// decryption is caught by the MAC check at the end of the batch, which aborts the whole batch and
+/// cannot tell a fault from a cheating party.  A redundant computation catches it before, and both
+/// parties discard the iteration and regenerate it with the same value of `a`.  At most
+/// `MAX_DISCARDED_ITERATIONS` iterations are discarded over the lifetime of a preprocessor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RedundancyStats {
+    /// Number of iterations whose products and decryptions were computed twice.
+    pub checked_iterations: u64,
+    /// Number of checked iterations in which the two computations differed.
+    pub faults: u64,
+}
+
+/// Identifies a batch in logs and errors, see `LowGearPreprocessor::batch_id()`.
+///
+/// Both parties derive the same ID from the session ID of the preprocessor and the number of
+/// batches it completed before, so the logs of both parties can be correlated, and the batches of
+/// concurrent preprocessors have different IDs.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize, derive_more::Display)]
+#[display(fmt = "{:016x}/{}", session, index)]
+pub struct BatchId {
+    /// Prefix of the session ID of the preprocessor.
+    pub session: u64,
+    /// Number of batches that the preprocessor completed before this one.
+    pub index: u64,
+}
+
+impl BatchId {
+    fn new(session_id: &SessionId, index: u64) -> Self {
+        Self {
+            session: u64::from_be_bytes(session_id[..8].try_into().unwrap()),
+            index,
+        }
+    }
+}
+
+/// Unpacked values of `a` together with their ciphertext, see `get_a()`.
+type EncryptedAOf<P> = (
+    Vec<<P as PreprocessorParameters>::KSS>,
+    Ciphertext<<P as PreprocessorParameters>::BgvParams>,
+);
+
 pub struct LowGearPreprocessor<P, const PID: usize>
 where
     P: PreprocessorParameters,
 {
-    dealer: LowGearDealer<P::DealerParams>,
-    opener: MacCheckOpener<P::KS, P::S>,
-    truncer: Truncer<P::S>,
-
-    ch_ciphertext_there: BiChannel<PreCiphertext<P::BgvParams>>,
-    ch_commitment: BiChannel<Commitment<P::BgvParams>>,
-    ch_challenge: BiChannel<Challenge>,
-    ch_response: BiChannel<Result<Response<P::BgvParams>, ResponseAborted>>,
-    ch_ciphertext_back: BiChannel<Ciphertext<P::BgvParams>>,
-
-    ctx_cipher: CrtContext<<P::BgvParams as BgvParameters>::CiphertextParams>,
-    ctx_plain: CrtContext<P::PlaintextParams>,
+    dealer: Dealer<P::DealerParams>,
+    dealer_pool: Option<DealerPool<P>>,
+    opener: OpenerOf<P>,
+    truncer: TruncerOf<P>,
+
+    ch_ciphertext_there: BulkChannel<PreCiphertext<P::BgvParams>>,
+    ch_commitment: RoundChannel<rounds::Commitment<P>>,
+    ch_challenge: RoundChannel<rounds::Challenge>,
+    ch_response: RoundChannel<rounds::Response<P>>,
+    /// Batches of the ciphertexts of the VOLE, see `set_ciphertext_batching()`.
+    ch_ciphertext_back: BulkChannel<Vec<Ciphertext<P::BgvParams>>>,
+    ch_decrypted: RoundChannel<rounds::Decrypted>,
+    ch_resume: RoundChannel<rounds::Resume>,
+    ch_abort: AbortChannel,
+
+    ctx_cipher: Arc<CrtContext<<P::BgvParams as BgvParameters>::CiphertextParams>>,
+    ctx_plain: Arc<CrtContext<P::PlaintextParams>>,
     sk: SecretKey<P::BgvParams>,
     pk: PublicKey<P::BgvParams>,
     remote_pk: PublicKey<P::BgvParams>,
-    mac_key: P::S,
+    mac_key: MacKeyOf<P>,
+
+    a_stack: Vec<EncryptedAOf<P>>,
+    spare_triples: Vec<TripleOf<P, PID>>,
 
-    a_stack: Vec<(Vec<P::KSS>, Ciphertext<P::BgvParams>)>,
+    zkpopk_stack_batches: usize,
+    zkpopk_stats: ZkpopkStats,
+    lockstep: bool,
+    ciphertext_batching: bool,
+    decryption_stats: DecryptionStats,
+    redundancy: f64,
+    redundancy_stats: RedundancyStats,
+    rate_limiter: Arc<RateLimiter>,
+    session_id: SessionId,
+    crypto_suite: CryptoSuite,
+    zkpopk_version: ZkpopkVersion,
+    num_batches: u64,
 }
 
 impl<P, const PID: usize> LowGearPreprocessor<P, PID>
 where
     P: PreprocessorParameters,
 {
-    pub async fn new(conn: &mut Connection) -> Result<Self, StreamError> {
-        let mac_key = P::S::random(&mut rand::thread_rng());
-
-        // Initialize subprotocols
-        let dealer = LowGearDealer::new(conn, mac_key).await?;
-        let opener = MacCheckOpener::new(conn, mac_key).await?;
-        let trunc = Truncer::new(conn, mac_key).await?;
-
-        // Open channels used by this protocol
-        let mut ch_init = BiChannel::open(conn, "LowGearPreprocessor:init").await?;
-        let ch_ciphertext_there =
-            BiChannel::open(conn, "LowGearPreprocessor:ciphertext_there").await?;
-        let ch_commitment = BiChannel::open(conn, "LowGearPreprocessor:commitment").await?;
-        let ch_challenge = BiChannel::open(conn, "LowGearPreprocessor:challenge").await?;
-        let ch_response = BiChannel::open(conn, "LowGearPreprocessor:response").await?;
-        let ch_ciphertext_back =
-            BiChannel::open(conn, "LowGearPreprocessor:ciphertext_back").await?;
-
-        // Generate cryptographic material
-        let ctx_cipher = CrtContext::gen().await;
-        let ctx_plain = CrtContext::gen().await;
-        let sk = SecretKey::gen(&ctx_cipher).await;
-        let pk = PublicKey::gen(&ctx_cipher, &sk).await;
+    /// # Panics
+    ///
+    /// Panics if the parameters are inconsistent, see `validate()`.
+    pub async fn new(conn: &mut Connection) -> Result<Self, InitError> {
+        Self::with_contexts(conn, &ContextSet::default()).await
+    }
 
-        // Initial protocol message
-        let (rx_init, tx_init) = ch_init.split();
-        let (_, remote_pk) = tokio::join!(
+    /// Selects the subprotocols to set up, e.g., only those needed for authentication.
+    pub fn builder() -> LowGearPreprocessorBuilder<P, PID> {
+        LowGearPreprocessorBuilder::new()
+    }
+
+    /// Like `new()`, but takes the `CrtContext`s from `contexts`, e.g., to share them between the
+    /// preprocessors of parallel batches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are inconsistent, see `validate()`.
+    pub async fn with_contexts(
+        conn: &mut Connection,
+        contexts: &ContextSet,
+    ) -> Result<Self, InitError> {
+        let mac_key = MacKeyShare::random(&mut rand::thread_rng());
+        Self::with_dealer(
+            conn,
+            contexts,
+            mac_key,
+            DealerBackend::default(),
+            CryptoSuite::default(),
+            ZkpopkVersion::default(),
+        )
+        .await
+    }
+
+    /// Like `with_contexts()`, but with the given MAC key share, dealer, `CryptoSuite` and
+    /// `ZkpopkVersion`, see `LowGearPreprocessorBuilder`.
+    ///
+    /// Fails if the other party uses another `ZkpopkVersion`.
+    async fn with_dealer(
+        conn: &mut Connection,
+        contexts: &ContextSet,
+        mac_key: MacKeyOf<P>,
+        dealer: DealerBackend,
+        crypto_suite: CryptoSuite,
+        zkpopk_version: ZkpopkVersion,
+    ) -> Result<Self, InitError> {
+        if let Err(e) = validate::<P>() {
+            panic!("invalid parameters {}: {}", std::any::type_name::<P>(), e);
+        }
+
+        // The subprotocols, the channels of this protocol and the key generation are set up
+        // concurrently, so the subprotocols get their own forks of the connection.
+        let (mut conn_dealer, mut conn_opener, mut conn_trunc) =
+            (conn.fork(), conn.fork(), conn.fork());
+        let (dealer, opener, trunc, channels, (ctx_cipher, ctx_plain, sk, pk)) = tokio::join!(
+            Dealer::new(&mut conn_dealer, dealer, mac_key.clone(), contexts),
+            MacCheckOpener::new(&mut conn_opener, mac_key.clone()),
+            Truncer::new(&mut conn_trunc, mac_key.clone()),
             async {
-                tx_init.send(pk.clone()).await.unwrap();
+                Ok::<_, StreamError>((
+                    RoundChannel::<rounds::Init<P>>::open(conn).await?,
+                    BulkChannel::open(conn, "LowGearPreprocessor:ciphertext_there").await?,
+                    RoundChannel::open(conn).await?,
+                    RoundChannel::open(conn).await?,
+                    RoundChannel::open(conn).await?,
+                    BulkChannel::open(conn, "LowGearPreprocessor:ciphertext_back").await?,
+                    RoundChannel::open(conn).await?,
+                    RoundChannel::open(conn).await?,
+                    AbortChannel::open(conn, "LowGearPreprocessor:abort").await?,
+                ))
             },
-            async { rx_init.next().await.unwrap().unwrap() }
+            phase!("keygen", async {
+                let (ctx_cipher, ctx_plain) = tokio::join!(
+                    contexts.get::<<P::BgvParams as BgvParameters>::CiphertextParams>(),
+                    contexts.get::<P::PlaintextParams>()
+                );
+                let (sk, pk) = run_blocking({
+                    let ctx_cipher = Arc::clone(&ctx_cipher);
+                    async move {
+                        let sk = SecretKey::<P::BgvParams>::gen(&ctx_cipher).await;
+                        let pk = PublicKey::gen(&ctx_cipher, &sk).await;
+                        (sk, pk)
+                    }
+                })
+                .await;
+                (ctx_cipher, ctx_plain, sk, pk)
+            })
+        );
+        let (mut dealer, mut opener, mut trunc) = (
+            dealer.map_err(InitError::FailedToOpen)?,
+            opener.map_err(InitError::FailedToOpen)?,
+            trunc.map_err(InitError::FailedToOpen)?,
         );
+        dealer.set_crypto_suite(crypto_suite);
+        opener.set_crypto_suite(crypto_suite);
+        trunc.set_crypto_suite(crypto_suite);
+        let (
+            mut ch_init,
+            ch_ciphertext_there,
+            ch_commitment,
+            ch_challenge,
+            ch_response,
+            ch_ciphertext_back,
+            ch_decrypted,
+            ch_resume,
+            mut ch_abort,
+        ) = channels.map_err(InitError::FailedToOpen)?;
+
+        // Initial protocol message
+        let remote_init = ch_init
+            .exchange(rounds::InitMessage {
+                zkpopk_version,
+                pk: pk.clone(),
+                abort_key: ch_abort.verifying_key(),
+            })
+            .await
+            .map_err(InitError::FailedToExchange)?;
+        if remote_init.zkpopk_version != zkpopk_version {
+            return Err(InitError::VersionMismatch {
+                local: zkpopk_version,
+                remote: remote_init.zkpopk_version,
+            });
+        }
+        let remote_pk = remote_init.pk;
+
+        // Bind the session to the initial protocol messages of both parties
+        let mut transcript = Transcript::with_suite(crypto_suite, "LowGearPreprocessor");
+        let abort_key = ch_abort.verifying_key();
+        if Role::of::<PID>().is_p0() {
+            transcript.append("pk_0", &pk);
+            transcript.append("pk_1", &remote_pk);
+            transcript.append("abort_key_0", &abort_key);
+            transcript.append("abort_key_1", &remote_init.abort_key);
+        } else {
+            transcript.append("pk_0", &remote_pk);
+            transcript.append("pk_1", &pk);
+            transcript.append("abort_key_0", &remote_init.abort_key);
+            transcript.append("abort_key_1", &abort_key);
+        }
+        opener.bind_session(transcript.session_id());
+        ch_abort
+            .bind_session(&remote_init.abort_key, transcript.session_id())
+            .map_err(InitError::InvalidAbortKey)?;
 
         Ok(Self {
             ch_ciphertext_there,
@@ -128,8 +428,12 @@ where
             ch_challenge,
             ch_response,
             ch_ciphertext_back,
+            ch_decrypted,
+            ch_resume,
+            ch_abort,
             truncer: trunc,
             dealer,
+            dealer_pool: None,
             opener,
             ctx_cipher,
             ctx_plain,
@@ -138,34 +442,246 @@ where
             remote_pk,
             mac_key,
             a_stack: Vec::new(),
+            spare_triples: Vec::new(),
+            zkpopk_stack_batches: 1,
+            zkpopk_stats: ZkpopkStats::default(),
+            lockstep: false,
+            ciphertext_batching: false,
+            decryption_stats: DecryptionStats::default(),
+            redundancy: 0.0,
+            redundancy_stats: RedundancyStats::default(),
+            rate_limiter: Arc::default(),
+            session_id: transcript.session_id(),
+            crypto_suite,
+            zkpopk_version,
+            num_batches: 0,
         })
     }
 
-    async fn get_a(&mut self) -> (Vec<P::KSS>, Ciphertext<P::BgvParams>) {
+    /// This party's share of the MAC key.
+    pub fn mac_key(&self) -> &MacKeyOf<P> {
+        &self.mac_key
+    }
+
+    /// The hash function of the commitments and transcripts, see
+    /// `LowGearPreprocessorBuilder::crypto_suite()`.
+    pub fn crypto_suite(&self) -> CryptoSuite {
+        self.crypto_suite
+    }
+
+    /// The ID of the current batch, i.e., of the next batch or of the one that failed.  Attach it
+    /// to the errors of this preprocessor to correlate them with the logs of both parties.
+    pub fn batch_id(&self) -> BatchId {
+        BatchId::new(&self.session_id, self.num_batches)
+    }
+
+    /// Authenticates this party's additive shares of values, e.g., as inputs of
+    /// `try_get_correlated_triples()`.  Both parties must call this with the same number of values.
+    pub async fn authenticate(&mut self, values: &[P::K]) -> Vec<ShareOf<P, PID>> {
+        let tags = self.dealer.authenticate(values).await;
+        values
+            .iter()
+            .zip(tags)
+            .map(|(val, tag)| Share::new(P::KS::from_unsigned(*val), tag))
+            .collect()
+    }
+
+    /// Refreshes the keys of the dealer, see `LowGearDealer::refresh_keys()`.  Both parties must
+    /// call this between the same two batches.
+    pub async fn refresh_dealer_keys(&mut self) {
+        self.dealer.refresh_keys().await;
+    }
+
+    /// Authenticates the values of `b` and the masking values of the iterations in advance, with a
+    /// second `LowGearDealer` on `conn` that keeps up to `depth` authentications ready in a
+    /// background task, see `DealerPool`.  `conn` must not be used otherwise.
+    ///
+    /// Both parties must call this between the same two batches with the same `depth`.  The pool
+    /// takes the current mask strategy and strictness, and is only used while they are unchanged.
+    /// The pool's dealer always uses the `LowGearDealer` backend and is not affected by
+    /// `refresh_dealer_keys()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is zero.
+    pub async fn enable_dealer_prefetch(
+        &mut self,
+        conn: &mut Connection,
+        depth: usize,
+    ) -> Result<(), StreamError> {
+        let num_masks = self.opener.mask_strategy::<P::K>().num_values();
+        let pool = DealerPool::new(
+            conn,
+            self.mac_key.clone(),
+            depth,
+            num_masks,
+            self.dealer.is_strict(),
+            self.crypto_suite,
+        )
+        .await?;
+        if let Some(old_pool) = self.dealer_pool.replace(pool) {
+            old_pool.finish().await;
+        }
+        Ok(())
+    }
+
+    /// The state of the dealer, e.g., for debugging a stalled batch.
+    pub fn dealer_state(&self) -> DealerState {
+        self.dealer.state()
+    }
+
+    /// Makes the prover and the verifier of the ZKPoPK proceed round by round, see `Lockstep`.
+    /// This is meant for debugging, since the directions no longer overlap across rounds.
+    pub fn set_lockstep(&mut self, enabled: bool) {
+        self.lockstep = enabled;
+    }
+
+    /// Sends the three ciphertexts of the VOLE of an iteration as one message instead of each on
+    /// its own, which saves the per-message overhead (and the checksums, see `BulkChannel`) at the
+    /// price of latency: the other party only starts decrypting once all three products are
+    /// computed.  The products cannot share the slots of a ciphertext, since each of them fills all
+    /// slots of `a`.  Both parties must use the same setting.
+    pub fn set_ciphertext_batching(&mut self, enabled: bool) {
+        self.ciphertext_batching = enabled;
+    }
+
+    /// Computes the products and the decryptions of the VOLE of each iteration twice with
+    /// probability `rate` and compares the results, see `RedundancyStats`.  This covers the values
+    /// and the MAC tags of the triples, at the price of about `rate` times the cost of the VOLE.
+    /// The parties may use different rates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not in `[0, 1]`.
+    pub fn set_redundancy(&mut self, rate: f64) {
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "the rate of redundant iterations must be in [0, 1]"
+        );
+        self.redundancy = rate;
+    }
+
+    /// Redundantly computed iterations so far.
+    pub fn redundancy_stats(&self) -> RedundancyStats {
+        self.redundancy_stats
+    }
+
+    /// Aborts of the ZKPoPKs so far.
+    pub fn zkpopk_stats(&self) -> ZkpopkStats {
+        self.zkpopk_stats
+    }
+
+    /// Failed decryptions so far.
+    pub fn decryption_stats(&self) -> DecryptionStats {
+        self.decryption_stats
+    }
+
+    /// Lets each ZKPoPK prove the values of `a` for the next `batches` batches instead of one.  The
+    /// verified ciphertexts that are left over at the end of a batch are used by the next ones, so
+    /// fewer proofs are run, at the price of larger proofs and of `batches` times the memory of the
+    /// `a_stack`.  Both parties must call this between the same two batches with the same value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batches` is zero or exceeds `ZKPOPK_MAX_STACK_BATCHES`.
+    pub fn set_zkpopk_stack_batches(&mut self, batches: usize) {
+        assert!(
+            (1..=P::ZKPOPK_MAX_STACK_BATCHES).contains(&batches),
+            "the number of stacked batches must be between 1 and ZKPOPK_MAX_STACK_BATCHES"
+        );
+        self.zkpopk_stack_batches = batches;
+    }
+
+    /// Sets how the masks of the MAC checks are derived, see `MaskStrategy`.  Both parties must use
+    /// the same strategy.
+    pub fn set_mask_strategy(&mut self, strategy: MaskStrategy) {
+        self.opener.set_mask_strategy(strategy);
+    }
+
+    /// Lets the dealer check the consistency of the MAC tags, see `LowGearDealer::set_strict()`.
+    /// Both parties must use the same setting.
+    pub fn set_strict_authentication(&mut self, strict: bool) {
+        self.dealer.set_strict(strict);
+    }
+
+    /// Throttles the ciphertexts sent by this party, see `RateLimiter`.  A ciphertext is in flight
+    /// until the corresponding ciphertext of the other party has been received.
+    pub fn set_rate_limit(&self, limit: RateLimit) {
+        self.rate_limiter.set_limit(limit);
+    }
+
+    /// The rate limiter of the ciphertexts.  Obtain it before moving the preprocessor into a
+    /// `BufferedPreprocessor` to adjust the limits while the producer is running.
+    pub fn rate_limiter(&self) -> Arc<RateLimiter> {
+        self.rate_limiter.clone()
+    }
+
+    /// The abort that the other party signed before it stopped, if it was received yet.
+    pub fn remote_abort(&self) -> Option<Abort> {
+        self.ch_abort.remote_abort()
+    }
+
+    /// Waits for the abort of the other party, see `AbortChannel::wait_remote_abort()`.  Use this
+    /// (with a timeout) to tell detected cheating from a network failure when a stream of the
+    /// other party breaks.
+    pub async fn wait_remote_abort(&self) -> Option<Abort> {
+        self.ch_abort.wait_remote_abort().await
+    }
+
+    /// Tells the other party why this party stops if `result` is an error, see `AbortChannel`.
+    async fn abort_on_err<T, E>(
+        &mut self,
+        result: Result<T, E>,
+        reason: AbortReason,
+        index: usize,
+    ) -> Result<T, E> {
+        if result.is_err() {
+            let abort = Abort {
+                reason,
+                batch: self.num_batches,
+                index: index as u64,
+            };
+            error!("batch {}: aborting with {}", self.batch_id(), abort);
+            self.ch_abort.send(abort).await;
+        }
+        result
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "zkpopk", skip_all))]
+    async fn get_a(&mut self, iteration_num: usize) -> Result<EncryptedAOf<P>, PreprocessorError> {
         if self.a_stack.is_empty() {
             let mut unpacked_a_vec = Vec::new();
             let mut pre_cipher_a_vec = Vec::new();
 
+            let batch_id = self.batch_id();
             let (rx_ciphertext, tx_ciphertext) = self.ch_ciphertext_there.split();
-            let (rx_commitment, tx_commitment) = self.ch_commitment.split();
-            let (rx_challenge, tx_challenge) = self.ch_challenge.split();
-            let (rx_response, tx_response) = self.ch_response.split();
+            let (mut rx_commitment, mut tx_commitment) = self.ch_commitment.split();
+            let (mut rx_challenge, mut tx_challenge) = self.ch_challenge.split();
+            let (mut rx_response, mut tx_response) = self.ch_response.split();
 
-            info!("ZKPoK: amortizing over {} ciphertexts", P::ZKPOPK_AMORTIZE);
+            let amortize = self.zkpopk_stack_batches * P::ZKPOPK_AMORTIZE;
+            info!(
+                "batch {}: ZKPoK: amortizing over {} ciphertexts",
+                batch_id, amortize
+            );
 
-            tokio::join!(
+            // The rounds are the ciphertexts and, per repetition, the commitment, the challenge
+            // and the response.
+            let lockstep = Lockstep::new(2, self.lockstep);
+            let (local_result, remote_result) = tokio::join!(
                 async {
+                    let step = lockstep.participant();
                     let mut inputs = Vec::new();
-                    for _ in 0..P::ZKPOPK_AMORTIZE {
-                        let unpacked_a =
-                            get_random_unpacked::<P::PlaintextParams, P::KS>(rand::thread_rng())
-                                .iter()
-                                .map(|a| P::KSS::from_unsigned(*a))
-                                .collect::<Vec<_>>();
+                    let mut statement = Statement::with_suite(self.crypto_suite);
+                    for _ in 0..amortize {
+                        let unpacked_a = sampling::random_widened::<P::KS, P::KSS>(
+                            rand::thread_rng(),
+                            packing_capacity::<P::PlaintextParams>(),
+                        );
                         let power_a =
                             PowerPoly::from_crt(&self.ctx_plain, &pack(&unpacked_a)).await;
                         let mut cipher_a = PreCiphertext::default();
-                        let input: PreparedPlaintext<
+                        let input: EncryptionWitness<
                             <P::BgvParams as BgvParameters>::PlaintextParams,
                         > = Prover::<P::BgvParams>::encrypt_into(
                             &self.ctx_cipher,
@@ -174,195 +690,654 @@ where
                             &mut cipher_a,
                         )
                         .await;
+                        self.rate_limiter.acquire(ciphertext_size::<P>()).await;
+                        statement.append(&cipher_a);
                         tx_ciphertext.send(cipher_a).await.unwrap();
                         inputs.push(input);
                         unpacked_a_vec.push(unpacked_a);
                     }
+                    step.end_round().await;
 
+                    let mut aborts = 0;
                     for rep in 0..P::ZKPOPK_MAX_REPS {
-                        let prover = Prover::new(
-                            P::ZKPOPK_INV_FAIL_PROB,
-                            P::ZKPOPK_AMORTIZE,
-                            P::ZKPOPK_SND_SEC,
-                        );
+                        let prover =
+                            Prover::new(P::ZKPOPK_INV_FAIL_PROB, amortize, P::ZKPOPK_SND_SEC)
+                                .with_version(self.zkpopk_version);
                         let commitment = prover.commit(&self.ctx_cipher, &self.pk).await;
                         tx_commitment.send(commitment).await.unwrap();
+                        step.end_round().await;
 
-                        let challenge = rx_challenge.next().await.unwrap().unwrap();
+                        let challenge = rx_challenge.recv().await.unwrap();
+                        step.end_round().await;
 
-                        let response = prover.respond(&inputs, challenge);
+                        let response = prover.respond(&inputs, &statement, challenge);
                         let is_ok = response.is_ok();
                         tx_response.send(response).await.unwrap();
+                        step.end_round().await;
                         if is_ok {
                             break;
                         }
+                        aborts += 1;
 
                         if rep == P::ZKPOPK_MAX_REPS - 1 {
-                            panic!("my ZKPoPK still failed after maximum number of attempts")
+                            error!(
+                                "batch {}: my ZKPoPK still failed after maximum number of attempts",
+                                batch_id
+                            );
+                            return Err(AbortReason::ZkpopkMaxReps);
                         }
                     }
+                    Ok(aborts)
                 },
                 async {
-                    for iteration_num in 0..P::ZKPOPK_AMORTIZE {
+                    let step = lockstep.participant();
+                    for iteration_num in 0..amortize {
                         let cipher_a = rx_ciphertext.next().await.unwrap().unwrap();
+                        self.rate_limiter.release();
                         pre_cipher_a_vec.push(cipher_a);
                         info!(
-                            "ZKPoK: received ciphertext {}/{}",
+                            "batch {}: ZKPoK: received ciphertext {}/{}",
+                            batch_id,
                             iteration_num + 1,
-                            P::ZKPOPK_AMORTIZE
+                            amortize
                         );
                     }
+                    step.end_round().await;
 
+                    let mut aborts = 0;
                     for rep in 0..P::ZKPOPK_MAX_REPS {
-                        let commitment = rx_commitment.next().await.unwrap().unwrap();
+                        let commitment = rx_commitment.recv().await.unwrap();
+                        step.end_round().await;
 
-                        let verifier = Verifier::new(
-                            P::ZKPOPK_INV_FAIL_PROB,
-                            P::ZKPOPK_AMORTIZE,
-                            P::ZKPOPK_SND_SEC,
-                        );
+                        let verifier =
+                            Verifier::new(P::ZKPOPK_INV_FAIL_PROB, amortize, P::ZKPOPK_SND_SEC)
+                                .with_suite(self.crypto_suite)
+                                .with_version(self.zkpopk_version);
                         let challenge = verifier.challenge();
                         tx_challenge.send(*challenge).await.unwrap();
-                        let response = rx_response.next().await.unwrap().unwrap();
+                        step.end_round().await;
+
+                        let response = rx_response.recv().await.unwrap();
+                        step.end_round().await;
 
                         if let Ok(response) = response {
-                            if !verifier
-                                .verify(
+                            if let Err(e) = verifier
+                                .try_verify(
                                     &self.ctx_cipher,
                                     &self.remote_pk,
                                     &pre_cipher_a_vec[..],
                                     commitment,
-                                    &response,
+                                    response,
                                 )
                                 .await
                             {
-                                panic!("verification of their ZKPoPK failed");
+                                error!(
+                                    "batch {}: verification of their ZKPoPK failed: {}",
+                                    batch_id, e
+                                );
+                                return Err(AbortReason::ZkpopkFailed);
                             }
                             break;
                         }
+                        aborts += 1;
 
                         if rep == P::ZKPOPK_MAX_REPS - 1 {
-                            panic!("their ZKPoPK still failed after maximum number of attempts")
+                            error!(
+                                "batch {}: their ZKPoPK still failed after maximum number of \
+                                 attempts",
+                                batch_id
+                            );
+                            return Err(AbortReason::ZkpopkMaxReps);
                         }
                     }
 
-                    info!("ZKPoK: verification successful");
+                    info!("batch {}: ZKPoK: verification successful", batch_id);
+                    Ok(aborts)
                 }
             );
+            let (local_aborts, remote_aborts) = match (local_result, remote_result) {
+                (Ok(local_aborts), Ok(remote_aborts)) => (local_aborts, remote_aborts),
+                (Err(reason), _) | (_, Err(reason)) => {
+                    return self
+                        .abort_on_err(Err(PreprocessorError::ZkpopkFailed), reason, iteration_num)
+                        .await;
+                }
+            };
 
-            for (unpacked_a, pre_cipher_a) in
-                unpacked_a_vec.into_iter().zip(pre_cipher_a_vec.into_iter())
-            {
+            self.zkpopk_stats.proofs += 1;
+            self.zkpopk_stats.local_aborts += local_aborts;
+            self.zkpopk_stats.remote_aborts += remote_aborts;
+            info!("batch {}: ZKPoK: {}", batch_id, self.zkpopk_stats);
+
+            for (unpacked_a, pre_cipher_a) in unpacked_a_vec.into_iter().zip(pre_cipher_a_vec) {
                 let cipher_a = pre_cipher_a.ciphertext(&self.ctx_cipher).await;
                 self.a_stack.push((unpacked_a, cipher_a));
             }
         }
 
-        self.a_stack.pop().unwrap()
+        Ok(self.a_stack.pop().unwrap())
     }
-}
 
-#[async_trait]
-impl<P, const PID: usize> BatchedPreprocessor<P::KS, P::K, PID> for LowGearPreprocessor<P, PID>
-where
-    P: PreprocessorParameters,
-{
-    const BATCH_SIZE: usize = batch_size::<P>();
+    /// Like `BatchedPreprocessor::get_beaver_triples()`, but returns an error if a check fails.
+    /// In this case, the other party also returns an error and this preprocessor must not be used
+    /// anymore.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "batch", skip_all, fields(id = %self.batch_id()))
+    )]
+    pub async fn try_get_beaver_triples(
+        &mut self,
+    ) -> Result<Vec<TripleOf<P, PID>>, PreprocessorError> {
+        self.get_batch_triples(&[]).await
+    }
 
-    async fn get_beaver_triples(&mut self) -> Vec<BeaverTriple<P::KS, P::K, PID>> {
-        let mac_key_wide = P::KSS::from_unsigned(self.mac_key);
+    /// Like `BitPreprocessor::get_dabits()`, but returns an error if a check fails, e.g., if the
+    /// other party input a value that is not a bit, see `edabit`.  Each daBit consumes two triples.
+    pub async fn try_get_dabits(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<DaBit<P::KS, P::K, PID>>, PreprocessorError> {
+        while self.spare_triples.len() < 2 * n {
+            let triples = self.try_get_beaver_triples().await?;
+            self.spare_triples.extend(triples);
+        }
+        let triples: Vec<_> = self.spare_triples.drain(..2 * n).collect();
+        edabit::gen_dabits::<P, PID>(&mut self.dealer, &mut self.opener, &self.mac_key, &triples)
+            .await
+            .map_err(PreprocessorError::from_dabit_error)
+    }
 
+    /// Like `BitPreprocessor::get_edabits()`, but returns an error if a check fails.
+    pub async fn try_get_edabits(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Result<Vec<EdaBit<P::KS, P::K, PID>>, PreprocessorError> {
+        let dabits = self.try_get_dabits(n * len).await?;
+        Ok(edabit::combine_edabits(&dabits, len))
+    }
+
+    /// Like `BitPreprocessor::get_bit_decompositions()`, but returns an error if a check fails.
+    pub async fn try_get_bit_decompositions(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Result<Vec<BitDecomposition<P::KS, P::K, PID>>, PreprocessorError> {
+        let dabits = self.try_get_dabits(n * len).await?;
+        Ok(edabit::combine_bit_decompositions(&dabits, len))
+    }
+
+    /// Like `try_get_beaver_triples()`, but the values of b of the first `b.len()` triples are the
+    /// given authenticated shares instead of fresh ones from the dealer, e.g., values that were
+    /// committed earlier.  The remaining triples of the batch are random as usual.
+    ///
+    /// Both parties must pass the same number of shares.  The values of the shares must be in `K`,
+    /// like the ones of `LowGearAuthenticator::authenticate()`.  Their MACs are checked together
+    /// with the triples, so this fails with `MacCheckFailed` if a share is not authenticated under
+    /// the MAC key of this preprocessor.
+    pub async fn try_get_correlated_triples(
+        &mut self,
+        b: &[ShareOf<P, PID>],
+    ) -> Result<Vec<TripleOf<P, PID>>, PreprocessorError> {
+        if b.len() > batch_size::<P>() || b.iter().any(|b| P::K::try_from_unsigned(b.val).is_none())
+        {
+            return Err(PreprocessorError::InvalidInput);
+        }
+        self.get_batch_triples(b).await
+    }
+
+    async fn get_batch_triples(
+        &mut self,
+        b: &[ShareOf<P, PID>],
+    ) -> Result<Vec<TripleOf<P, PID>>, PreprocessorError> {
+        let capacity = packing_capacity::<P::PlaintextParams>();
         let mut triples = Vec::new();
-        for iteration_num in 0..P::ZKPOPK_AMORTIZE {
-            let (unpacked_wide_a, cipher_a) = self.get_a().await;
-            info!(
-                "started iteration {}/{}",
-                iteration_num + 1,
-                P::ZKPOPK_AMORTIZE
-            );
-            let mut unpacked_wide_a_tags: Vec<_> =
-                unpacked_wide_a.iter().map(|a| *a * mac_key_wide).collect();
-
-            let (batch_check_mask, unpacked_b, unpacked_b_tags) = {
-                let mut input = get_random_unpacked::<P::PlaintextParams, P::K>(rand::thread_rng());
-                input.push(P::K::random(&mut rand::thread_rng()));
-                input.push(P::K::random(&mut rand::thread_rng()));
-                let mut output = self.dealer.authenticate(&input).await;
-                let r = Share::new(
-                    P::KS::from_unsigned(input.pop().unwrap()),
-                    output.pop().unwrap(),
-                );
-                let m = Share::new(
-                    P::KS::from_unsigned(input.pop().unwrap()),
-                    output.pop().unwrap(),
+        let mut iteration_num = 0;
+        while iteration_num < P::ZKPOPK_AMORTIZE {
+            let iteration_b = b.chunks(capacity).nth(iteration_num).unwrap_or(&[]);
+            match self
+                .get_iteration_triples(iteration_num, iteration_b)
+                .await?
+            {
+                Some(iteration_triples) => {
+                    triples.extend(iteration_triples);
+                    iteration_num += 1;
+                }
+                None => self.retry_iteration(iteration_num).await?,
+            }
+        }
+
+        // Discarded iterations put their values of `a` back, so the values of `a` of whole batches
+        // are left over, see `set_zkpopk_stack_batches()`.
+        assert!(self.a_stack.len().is_multiple_of(P::ZKPOPK_AMORTIZE));
+
+        let result = self
+            .truncer
+            .batch_check::<P::KSS, _>(&mut self.opener)
+            .await;
+        self.abort_on_err(
+            result,
+            AbortReason::TruncationFailed,
+            P::ZKPOPK_AMORTIZE - 1,
+        )
+        .await
+        .map_err(PreprocessorError::TruncationFailed)?;
+
+        info!(
+            "batch {} of size {} completed",
+            self.batch_id(),
+            triples.len()
+        );
+        self.num_batches += 1;
+
+        Ok(triples)
+    }
+
+    /// Like `try_get_beaver_triples()`, but passes the triples of each of the `ZKPOPK_AMORTIZE`
+    /// iterations to `sink` as soon as they are checked, instead of returning the whole batch at
+    /// once.  To this end, the truncations are checked once per iteration instead of once per
+    /// batch.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "batch", skip_all, fields(id = %self.batch_id()))
+    )]
+    pub async fn get_beaver_triples_with<F>(&mut self, mut sink: F) -> Result<(), PreprocessorError>
+    where
+        F: FnMut(Vec<TripleOf<P, PID>>),
+    {
+        let mut num_triples = 0;
+        let mut iteration_num = 0;
+        while iteration_num < P::ZKPOPK_AMORTIZE {
+            let triples = match self.get_iteration_triples(iteration_num, &[]).await? {
+                Some(triples) => triples,
+                None => {
+                    self.retry_iteration(iteration_num).await?;
+                    continue;
+                }
+            };
+            let result = self
+                .truncer
+                .batch_check::<P::KSS, _>(&mut self.opener)
+                .await;
+            self.abort_on_err(result, AbortReason::TruncationFailed, iteration_num)
+                .await
+                .map_err(PreprocessorError::TruncationFailed)?;
+            num_triples += triples.len();
+            sink(triples);
+            iteration_num += 1;
+        }
+
+        // Discarded iterations put their values of `a` back, so the values of `a` of whole batches
+        // are left over, see `set_zkpopk_stack_batches()`.
+        assert!(self.a_stack.len().is_multiple_of(P::ZKPOPK_AMORTIZE));
+
+        info!(
+            "batch {} of size {} completed",
+            self.batch_id(),
+            num_triples
+        );
+        self.num_batches += 1;
+
+        Ok(())
+    }
+
+    /// Like `get_beaver_triples_with()`, but saves a `Checkpoint` of the completed iterations to
+    /// `store` after each iteration, and resumes from the checkpoint in `store` if the other party
+    /// has one of the same batch and iteration, see `checkpoint`.  The checkpoint is cleared once
+    /// the batch is completed.
+    ///
+    /// A checkpoint is only resumed if this preprocessor has its MAC key share.  Both parties must
+    /// call this for the same batches.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "batch", skip_all, fields(id = %self.batch_id()))
+    )]
+    pub async fn try_get_beaver_triples_checkpointed<C>(
+        &mut self,
+        store: &mut C,
+    ) -> Result<Vec<TripleOf<P, PID>>, PreprocessorError>
+    where
+        C: CheckpointStore<P::KS, P::K, P::S, PID>,
+    {
+        let mac_key_digest = self.mac_key.digest();
+        let local = store
+            .load()
+            .map_err(PreprocessorError::CheckpointFailed)?
+            .filter(|checkpoint| {
+                checkpoint.mac_key_digest == mac_key_digest
+                    && checkpoint.iterations < P::ZKPOPK_AMORTIZE
+            });
+        let local_point = local.as_ref().map(Checkpoint::resume_point);
+        let remote_point = self
+            .ch_resume
+            .exchange(local_point)
+            .await
+            .map_err(|err| PreprocessorError::CheckpointFailed(io::Error::other(err)))?;
+        let resumed = local.is_some() && local_point == remote_point;
+        let mut checkpoint = match local {
+            Some(checkpoint) if resumed => {
+                self.num_batches = checkpoint.batch;
+                info!(
+                    "batch {}: resuming at iteration {}/{}",
+                    self.batch_id(),
+                    checkpoint.iterations + 1,
+                    P::ZKPOPK_AMORTIZE
                 );
-                (m + (r << P::K::BITS), input, output)
+                checkpoint
+            }
+            _ => {
+                if local_point.is_some() || remote_point.is_some() {
+                    warn!(
+                        "batch {}: discarding the checkpoints {:?} (local) and {:?} (remote)",
+                        self.batch_id(),
+                        local_point,
+                        remote_point
+                    );
+                }
+                Checkpoint {
+                    batch: self.num_batches,
+                    iterations: 0,
+                    triples: Vec::new(),
+                    mac_key_digest,
+                    phantom: PhantomData,
+                }
+            }
+        };
+
+        while checkpoint.iterations < P::ZKPOPK_AMORTIZE {
+            let iteration_num = checkpoint.iterations;
+            let triples = match self.get_iteration_triples(iteration_num, &[]).await? {
+                Some(triples) => triples,
+                None => {
+                    self.retry_iteration(iteration_num).await?;
+                    continue;
+                }
+            };
+            let result = self
+                .truncer
+                .batch_check::<P::KSS, _>(&mut self.opener)
+                .await;
+            self.abort_on_err(result, AbortReason::TruncationFailed, iteration_num)
+                .await
+                .map_err(PreprocessorError::TruncationFailed)?;
+            checkpoint.triples.extend(triples);
+            checkpoint.iterations += 1;
+            store
+                .save(&checkpoint)
+                .map_err(PreprocessorError::CheckpointFailed)?;
+        }
+
+        // After resuming, the values of `a` for the iterations before the checkpoint are left over.
+        // Both parties discard them, such that the next batch starts with a fresh ZKPoPK.  Otherwise,
+        // the leftovers are those of stacked batches, see `set_zkpopk_stack_batches()`.
+        if resumed {
+            self.a_stack.clear();
+        }
+        store.clear().map_err(PreprocessorError::CheckpointFailed)?;
+
+        info!(
+            "batch {} of size {} completed",
+            self.batch_id(),
+            checkpoint.triples.len()
+        );
+        self.num_batches += 1;
+
+        Ok(std::mem::take(&mut checkpoint.triples))
+    }
+
+    /// Returns an error if more than `MAX_DISCARDED_ITERATIONS` iterations were discarded over the
+    /// lifetime of this preprocessor.
+    async fn retry_iteration(&mut self, iteration_num: usize) -> Result<(), PreprocessorError> {
+        let discarded = self.decryption_stats.discarded_iterations;
+        if discarded > P::MAX_DISCARDED_ITERATIONS {
+            return self
+                .abort_on_err(
+                    Err(PreprocessorError::TooManyFaults),
+                    AbortReason::TooManyFaults,
+                    iteration_num,
+                )
+                .await;
+        }
+        warn!(
+            "batch {}: discarding iteration {}/{} after a fault ({}/{})",
+            self.batch_id(),
+            iteration_num + 1,
+            P::ZKPOPK_AMORTIZE,
+            discarded,
+            P::MAX_DISCARDED_ITERATIONS
+        );
+        Ok(())
+    }
+
+    /// The `i`-th ciphertext of the VOLE, which encrypts `a * factor` masked with `mask`.
+    async fn vole_product(
+        ctx_cipher: &CrtContext<<P::BgvParams as BgvParameters>::CiphertextParams>,
+        ctx_plain: &CrtContext<P::PlaintextParams>,
+        cipher_a: &Ciphertext<P::BgvParams>,
+        factor: &CrtPoly<P::PlaintextParams>,
+        mask: &Ciphertext<P::BgvParams>,
+    ) -> Ciphertext<P::BgvParams> {
+        let mut cipher_d = cipher_a.clone();
+        cipher_d *=
+            &Cleartext::new(ctx_cipher, &PowerPoly::from_crt(ctx_plain, factor).await).await;
+        cipher_d -= mask;
+        cipher_d
+    }
+
+    /// Decrypts and unpacks a ciphertext of the VOLE, or returns `None` if the decryption failed.
+    async fn vole_decrypt(
+        ctx_cipher: &CrtContext<<P::BgvParams as BgvParameters>::CiphertextParams>,
+        ctx_plain: &CrtContext<P::PlaintextParams>,
+        sk: &SecretKey<P::BgvParams>,
+        cipher_d: &Ciphertext<P::BgvParams>,
+    ) -> Option<Vec<P::KSS>> {
+        // `vole_drown_bits()` keeps the coefficients of `m + t e` below `q/2` in magnitude, so
+        // reducing the centered coefficients modulo `t` yields the plaintext `m`.
+        let mut centered = bgv::decrypt_centered(ctx_cipher, sk, cipher_d).await;
+        let mut plain_d = PowerPoly::new();
+        bgv::reduce_centered_into::<P::BgvParams>(&centered, &mut plain_d);
+        zeroize(centered.iter_mut());
+        unpack::<_, P::KSS>(&CrtPoly::from_power(ctx_plain, &plain_d).await)
+    }
+
+    /// Runs one of the `ZKPOPK_AMORTIZE` iterations of a batch.  The MACs of the returned triples
+    /// are checked, but the truncations are not.  Returns `None` if the iteration was discarded by
+    /// both parties because a fault was detected, see `RedundancyStats`, and an error if a
+    /// decryption failed, see `DecryptionStats`.
+    ///
+    /// The first `b.len()` values of b are taken from `b` instead of the dealer.
+    async fn get_iteration_triples(
+        &mut self,
+        iteration_num: usize,
+        b: &[ShareOf<P, PID>],
+    ) -> Result<Option<Vec<TripleOf<P, PID>>>, PreprocessorError> {
+        let (unpacked_wide_a, cipher_a) = self.get_a(iteration_num).await?;
+        info!(
+            "batch {}: started iteration {}/{}",
+            self.batch_id(),
+            iteration_num + 1,
+            P::ZKPOPK_AMORTIZE
+        );
+        // The values of a are sampled in KS, so the products with a only need to be widened to
+        // KSS at the end (see `mul_widening()`).
+        let narrow_a: Vec<P::KS> = sampling::narrow(&unpacked_wide_a);
+        debug_assert!(unpacked_wide_a
+            .iter()
+            .all(|a| P::KS::try_from_unsigned(*a).is_some()));
+        let mut unpacked_wide_a_tags: Vec<_> = narrow_a
+            .iter()
+            .map(|a| self.mac_key.mul_widening::<_, P::KSS>(a))
+            .collect();
+
+        let (batch_check_mask, unpacked_b, unpacked_b_tags) = {
+            let mask_strategy = self.opener.mask_strategy::<P::K>();
+            let num_masks = mask_strategy.num_values();
+            let (mut input, mut output) = match &mut self.dealer_pool {
+                Some(pool) if pool.matches(num_masks, self.dealer.is_strict()) => {
+                    // TODO: return error instead of unwrapping.
+                    let mut authenticated = pool.take().await.unwrap();
+                    // The values in place of the given shares of b are not used.
+                    authenticated.values.drain(..b.len());
+                    authenticated.tags.drain(..b.len());
+                    (authenticated.values, authenticated.tags)
+                }
+                _ => {
+                    let mut input =
+                        get_random_unpacked::<P::PlaintextParams, P::K>(rand::thread_rng());
+                    input.drain(..b.len());
+                    sampling::extend_random(rand::thread_rng(), &mut input, num_masks);
+                    let output = self.dealer.authenticate(&input).await;
+                    (input, output)
+                }
             };
+            let num_slots = input.len() - num_masks;
+            let masking_values = authenticated_shares::<P, PID>(
+                &input.split_off(num_slots),
+                output.split_off(num_slots),
+            );
+            // The given values fit `K`, see `try_get_correlated_triples()`.
+            let values = b.iter().map(|b| P::K::from_unsigned(b.val)).chain(input);
+            let tags = b.iter().map(|b| b.tag).chain(output);
+            (
+                mask_strategy.combine(&masking_values),
+                values.collect::<Vec<_>>(),
+                tags.collect::<Vec<_>>(),
+            )
+        };
 
-            let mut unpacked_wide_c: Vec<_> = unpacked_wide_a
-                .iter()
-                .zip(&unpacked_b)
-                .map(|(a, b)| *a * P::KSS::from_unsigned(*b))
-                .collect();
-            let mut unpacked_wide_c_tags: Vec<_> = unpacked_wide_a
-                .iter()
-                .zip(&unpacked_b_tags)
-                .map(|(a, b_tag)| *a * P::KSS::from_unsigned(*b_tag))
-                .collect();
+        let mut unpacked_wide_c: Vec<_> = narrow_a
+            .iter()
+            .zip(&unpacked_b)
+            .map(|(a, b)| a.mul_widening::<_, P::KSS>(b))
+            .collect();
+        let mut unpacked_wide_c_tags: Vec<_> = narrow_a
+            .iter()
+            .zip(&unpacked_b_tags)
+            .map(|(a, b_tag)| a.mul_widening::<_, P::KSS>(b_tag))
+            .collect();
 
-            let unpacked_e_arr = [(); 3]
-                .map(|_| get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng()));
+        let unpacked_e_arr =
+            [(); 3].map(|_| get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng()));
 
-            let (rx_ciphertext, tx_ciphertext) = self.ch_ciphertext_back.split();
+        let drown_bits = vole_drown_bits::<P>();
+        let batch_id = self.batch_id();
+        let ciphertext_batching = self.ciphertext_batching;
+        let redundant = rand::thread_rng().gen_bool(self.redundancy);
+        let (rx_ciphertext, tx_ciphertext) = self.ch_ciphertext_back.split();
 
+        let (sent_consistent, (decrypted, faulty)) = phase!("vole", async {
             tokio::join!(
                 async {
-                    let unpacked_wide_b: Vec<_> = unpacked_b
-                        .iter()
-                        .map(|b| P::KSS::from_unsigned(*b))
-                        .collect();
-                    let unpacked_wide_b_tags: Vec<_> = unpacked_b_tags
-                        .iter()
-                        .map(|b_tag| P::KSS::from_unsigned(*b_tag))
-                        .collect();
-                    for (i, unpacked_e) in unpacked_e_arr.iter().enumerate() {
-                        let power_e = pack_mask(unpacked_e);
-                        let mut cipher_d = cipher_a.clone();
-                        cipher_d *= &Cleartext::new(
-                            &self.ctx_cipher,
-                            &PowerPoly::from_crt(
-                                &self.ctx_plain,
-                                &match i {
-                                    0 => pack_diagonal(self.mac_key),
-                                    1 => pack(&unpacked_wide_b),
-                                    _ => pack(&unpacked_wide_b_tags),
-                                },
-                            )
-                            .await,
-                        )
-                        .await;
-                        cipher_d -= &bgv::encrypt_and_drown(
+                    let mut power_e_arr = Vec::with_capacity(unpacked_e_arr.len());
+                    for unpacked_e in &unpacked_e_arr {
+                        power_e_arr.push(
+                            PowerPoly::from_crt(&self.ctx_plain, &pack_mask(unpacked_e)).await,
+                        );
+                    }
+                    let masks = bgv::encrypt_batch(
+                        &self.ctx_cipher,
+                        &self.remote_pk,
+                        &power_e_arr,
+                        drown_bits,
+                    )
+                    .await;
+                    let mut consistent = true;
+                    let mut batch = Vec::with_capacity(masks.len());
+                    for (i, mask) in masks.iter().enumerate() {
+                        // Packing lifts the values to the plaintext ring anyway, so b and its tags
+                        // need not be widened to KSS first.
+                        let mut factor = match i {
+                            0 => pack_diagonal(self.mac_key.expose_secret()),
+                            1 => pack(&unpacked_b),
+                            _ => pack(&unpacked_b_tags),
+                        };
+                        let cipher_d = Self::vole_product(
                             &self.ctx_cipher,
-                            &self.remote_pk,
-                            &PowerPoly::from_crt(&self.ctx_plain, &power_e).await,
-                            bgv::max_drown_bits::<P::BgvParams>(),
+                            &self.ctx_plain,
+                            &cipher_a,
+                            &factor,
+                            mask,
                         )
                         .await;
+                        if redundant
+                            && Self::vole_product(
+                                &self.ctx_cipher,
+                                &self.ctx_plain,
+                                &cipher_a,
+                                &factor,
+                                mask,
+                            )
+                            .await
+                                != cipher_d
+                        {
+                            error!("batch {}: VOLE: fault in product {}/3", batch_id, i + 1);
+                            // The ciphertext is sent anyway to keep the channel in sync.
+                            consistent = false;
+                        }
+                        // The factors contain the MAC key share and the shares of b.
+                        zeroize(factor.coefficients.iter_mut());
+                        batch.push(cipher_d);
+                        if ciphertext_batching && batch.len() < masks.len() {
+                            continue;
+                        }
+                        let batch = std::mem::take(&mut batch);
+                        self.rate_limiter
+                            .acquire(batch.len() * ciphertext_size::<P>())
+                            .await;
                         // TODO: return error instead of unwrapping.
-                        tx_ciphertext.send(cipher_d).await.unwrap();
+                        tx_ciphertext.send(batch).await.unwrap();
                     }
+                    consistent
                 },
                 async {
+                    let mut decrypted = true;
+                    let mut faulty = false;
+                    // The batches of the other party may have any size.
+                    let mut batch = Vec::new().into_iter();
                     for (i, unpacked_e) in unpacked_e_arr.iter().enumerate() {
-                        // TODO: return error instead of unwrapping.
-                        let cipher_d = rx_ciphertext.next().await.unwrap().unwrap();
-                        let plain_d = bgv::decrypt(&self.ctx_cipher, &self.sk, &cipher_d).await;
-                        // TODO: return error instead of unwrapping when unpacking fails.
-                        let unpacked_d = unpack::<_, P::KSS>(
-                            &CrtPoly::from_power(&self.ctx_plain, &plain_d).await,
+                        let cipher_d = loop {
+                            if let Some(cipher_d) = batch.next() {
+                                break cipher_d;
+                            }
+                            // TODO: return error instead of unwrapping.
+                            batch = rx_ciphertext.next().await.unwrap().unwrap().into_iter();
+                            self.rate_limiter.release();
+                        };
+                        if !decrypted {
+                            // Receive the remaining ciphertexts to keep the channel in sync.
+                            continue;
+                        }
+                        let unpacked_d = Self::vole_decrypt(
+                            &self.ctx_cipher,
+                            &self.ctx_plain,
+                            &self.sk,
+                            &cipher_d,
                         )
-                        .unwrap();
-                        info!("VOLE: decrypted & unpacked {}/3", i + 1);
+                        .await;
+                        if redundant
+                            && Self::vole_decrypt(
+                                &self.ctx_cipher,
+                                &self.ctx_plain,
+                                &self.sk,
+                                &cipher_d,
+                            )
+                            .await
+                                != unpacked_d
+                        {
+                            error!("batch {}: VOLE: fault in decryption {}/3", batch_id, i + 1);
+                            decrypted = false;
+                            faulty = true;
+                            continue;
+                        }
+                        let unpacked_d = match unpacked_d {
+                            Some(unpacked_d) => unpacked_d,
+                            None => {
+                                error!("batch {}: VOLE: decryption {}/3 failed", batch_id, i + 1);
+                                decrypted = false;
+                                continue;
+                            }
+                        };
+                        info!("batch {}: VOLE: decrypted & unpacked {}/3", batch_id, i + 1);
                         let target = match i {
                             0 => &mut unpacked_wide_a_tags,
                             1 => &mut unpacked_wide_c,
@@ -372,62 +1347,308 @@ where
                             *t += *d + *e;
                         }
                     }
+                    (decrypted, faulty)
                 }
-            );
+            )
+        })
+        .await;
+        self.redundancy_stats.checked_iterations += redundant as u64;
+        self.redundancy_stats.faults += (faulty || !sent_consistent) as u64;
 
-            let (unpacked_a, unpacked_a_tags, unpacked_c, unpacked_c_tags) = self
-                .truncer
-                .truncate::<_, _, _, PID>(
-                    &unpacked_wide_a,
-                    &unpacked_wide_a_tags,
-                    &unpacked_b,
-                    &unpacked_b_tags,
-                    &unpacked_wide_c,
-                    &unpacked_wide_c_tags,
+        // Both parties have to abort if one of the decryptions failed, and discard the iteration if
+        // a fault was detected.
+        let local_outcome = if faulty || !sent_consistent {
+            VoleOutcome::Fault
+        } else if !decrypted {
+            VoleOutcome::DecryptionFailed
+        } else {
+            VoleOutcome::Ok
+        };
+        // TODO: return error instead of unwrapping.
+        let remote_outcome = self.ch_decrypted.exchange(local_outcome).await.unwrap();
+        if local_outcome == VoleOutcome::DecryptionFailed
+            || remote_outcome == VoleOutcome::DecryptionFailed
+        {
+            self.decryption_stats.local_failures +=
+                (local_outcome == VoleOutcome::DecryptionFailed) as u64;
+            self.decryption_stats.remote_failures +=
+                (remote_outcome == VoleOutcome::DecryptionFailed) as u64;
+            return self
+                .abort_on_err(
+                    Err(PreprocessorError::DecryptionFailed),
+                    AbortReason::DecryptionFailed,
+                    iteration_num,
                 )
                 .await;
+        }
+        if local_outcome == VoleOutcome::Fault || remote_outcome == VoleOutcome::Fault {
+            self.decryption_stats.discarded_iterations += 1;
+            // The value of `a` is used again with fresh masks, so its proof is not wasted.
+            self.a_stack.push((unpacked_wide_a, cipher_a));
+            return Ok(None);
+        }
 
-            triples.extend(
-                unpacked_a
-                    .iter()
-                    .zip(&unpacked_a_tags)
-                    .zip(&unpacked_b)
-                    .zip(&unpacked_b_tags)
-                    .zip(&unpacked_c)
-                    .zip(&unpacked_c_tags)
-                    .map(|(((((a, a_tag), b), b_tag), c), c_tag)| {
-                        BeaverTriple::new(
-                            Share::new(*a, *a_tag),
-                            Share::new(P::KS::from_unsigned(*b), *b_tag),
-                            Share::new(*c, *c_tag),
-                        )
-                    }),
-            );
+        let result = self
+            .truncer
+            .truncate::<_, _, _, PID>(
+                &unpacked_wide_a,
+                &unpacked_wide_a_tags,
+                &unpacked_b,
+                &unpacked_b_tags,
+                &unpacked_wide_c,
+                &unpacked_wide_c_tags,
+            )
+            .await;
+        let Truncation {
+            a: unpacked_a,
+            a_tags: unpacked_a_tags,
+            c: unpacked_c,
+            c_tags: unpacked_c_tags,
+        } = self
+            .abort_on_err(result, AbortReason::TruncationFailed, iteration_num)
+            .await
+            .map_err(PreprocessorError::TruncationFailed)?;
 
-            let iter = triples
-                .iter()
-                .cloned()
-                .map(|triple| [triple.a, triple.b, triple.c])
-                .flatten();
-            self.opener
-                .batch_check::<P::K, PID>(iter, batch_check_mask)
-                .await
-                .unwrap();
-        }
+        let triples: Vec<_> = unpacked_a
+            .iter()
+            .zip(&unpacked_a_tags)
+            .zip(&unpacked_b)
+            .zip(&unpacked_b_tags)
+            .zip(&unpacked_c)
+            .zip(&unpacked_c_tags)
+            .map(|(((((a, a_tag), b), b_tag), c), c_tag)| {
+                BeaverTriple::new(
+                    Share::new(*a, *a_tag),
+                    Share::new(P::KS::from_unsigned(*b), *b_tag),
+                    Share::new(*c, *c_tag),
+                )
+            })
+            .collect();
+
+        let iter = triples
+            .iter()
+            .cloned()
+            .flat_map(|triple| [triple.a, triple.b, triple.c]);
+        let result = self
+            .opener
+            .batch_check::<P::K, PID>(iter, batch_check_mask)
+            .await;
+        self.abort_on_err(result, AbortReason::MacCheckFailed, iteration_num)
+            .await
+            .map_err(PreprocessorError::MacCheckFailed)?;
+
+        Ok(Some(triples))
+    }
+}
 
-        assert!(self.a_stack.is_empty());
+impl<P, const PID: usize> TripleOrigin<P::KS> for LowGearPreprocessor<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    fn mac_key_share(&self) -> P::KS {
+        self.mac_key.widen()
+    }
+
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+}
 
-        info!("batch of size {} completed", triples.len());
+#[async_trait]
+impl<P, const PID: usize> BatchedPreprocessor<P::KS, P::K, PID> for LowGearPreprocessor<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    const BATCH_SIZE: usize = batch_size::<P>();
 
-        triples
+    async fn get_beaver_triples(&mut self) -> Vec<TripleOf<P, PID>> {
+        // TODO: return error instead of unwrapping.
+        self.try_get_beaver_triples().await.unwrap()
     }
 
     async fn finish(self) {
+        if let Some(pool) = self.dealer_pool {
+            pool.finish().await;
+        }
         self.dealer.finish().await;
         self.opener.finish().await;
+        self.ch_abort.finish().await;
+    }
+}
+
+#[async_trait]
+impl<P, const PID: usize> BitPreprocessor<P::KS, P::K, PID> for LowGearPreprocessor<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    async fn get_dabits(&mut self, n: usize) -> Vec<DaBit<P::KS, P::K, PID>> {
+        // TODO: return error instead of unwrapping.
+        self.try_get_dabits(n).await.unwrap()
+    }
+
+    async fn get_edabits(&mut self, n: usize, len: usize) -> Vec<EdaBit<P::KS, P::K, PID>> {
+        // TODO: return error instead of unwrapping.
+        self.try_get_edabits(n, len).await.unwrap()
+    }
+
+    async fn get_bit_decompositions(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Vec<BitDecomposition<P::KS, P::K, PID>> {
+        // TODO: return error instead of unwrapping.
+        self.try_get_bit_decompositions(n, len).await.unwrap()
     }
 }
 
+#[async_trait]
+impl<P, const PID: usize> ZeroSharePreprocessor<P::KS, P::K, PID> for LowGearPreprocessor<P, PID>
+where
+    P: PreprocessorParameters,
+{
+    async fn get_zero_shares(&mut self, n: usize) -> Vec<ShareOf<P, PID>> {
+        // TODO: return error instead of unwrapping.
+        get_zero_shares_with::<P, PID>(&mut self.dealer, &mut self.opener, &self.mac_key, n)
+            .await
+            .unwrap()
+    }
+}
+
+/// Both parties authenticate random values, which are then opened and subtracted.
+async fn get_zero_shares_with<P, const PID: usize>(
+    dealer: &mut Dealer<P::DealerParams>,
+    opener: &mut OpenerOf<P>,
+    mac_key: &MacKeyOf<P>,
+    n: usize,
+) -> Result<Vec<ShareOf<P, PID>>, MacCheckFailed>
+where
+    P: PreprocessorParameters,
+{
+    // The last values are used for the batch check mask.
+    let mask_strategy = opener.mask_strategy::<P::K>();
+    let mut input: Vec<P::K> =
+        sampling::random_vec(rand::thread_rng(), n + mask_strategy.num_values());
+    let mut output = dealer.authenticate(&input).await;
+
+    let batch_check_mask = mask_strategy.combine(&authenticated_shares::<P, PID>(
+        &input.split_off(n),
+        output.split_off(n),
+    ));
+
+    let shares = authenticated_shares::<P, PID>(&input, output);
+    let opened = opener.open_unchecked(&shares).await?;
+    opener
+        .batch_check(shares.iter().copied(), batch_check_mask)
+        .await?;
+
+    Ok(shares
+        .into_iter()
+        .zip(opened)
+        .map(|(share, val)| share.add_public(P::K::ZERO - val, mac_key))
+        .collect())
+}
+
+/// Pairs values that were authenticated by the dealer with their MAC tags.
+fn authenticated_shares<P, const PID: usize>(
+    values: &[P::K],
+    tags: Vec<P::KS>,
+) -> Vec<ShareOf<P, PID>>
+where
+    P: PreprocessorParameters,
+{
+    values
+        .iter()
+        .zip(tags)
+        .map(|(val, tag)| Share::new(P::KS::from_unsigned(*val), tag))
+        .collect()
+}
+
+/// A violated relation between the parameters of a `PreprocessorParameters` and its
+/// `DealerParameters`, see `validate()`.
+#[derive(Debug, PartialEq, Eq, derive_more::Display, derive_more::Error)]
+pub enum ParameterError {
+    #[display(fmt = "KS has {} bits, but K and S have {} bits", actual, expected)]
+    KsBits { expected: usize, actual: usize },
+    #[display(fmt = "KSS has {} bits, but KS and S have {} bits", actual, expected)]
+    KssBits { expected: usize, actual: usize },
+    #[display(
+        fmt = "the plaintext modulus has {} bits, but packing KSS needs at least {} bits",
+        actual,
+        required
+    )]
+    PlaintextBits { required: usize, actual: usize },
+    #[display(
+        fmt = "the dealer packs {} values, but an iteration authenticates {} values",
+        available,
+        required
+    )]
+    DealerCapacity { required: usize, available: usize },
+    #[display(
+        fmt = "ZKPOPK_AMORTIZE, ZKPOPK_MAX_REPS and ZKPOPK_MAX_STACK_BATCHES must be positive"
+    )]
+    ZkpopkZero,
+    #[display(fmt = "the ZKPoPK bounds overflow for ZKPOPK_INV_FAIL_PROB")]
+    ZkpopkBoundOverflow,
+}
+
+/// Checks the relations between the parameters that the associated types cannot express.  The
+/// dealer's `K`, `S` and `KS` equal the preprocessor's ones by the bounds of `DealerParams`.
+pub const fn validate<P>() -> Result<(), ParameterError>
+where
+    P: PreprocessorParameters,
+{
+    let k_bits = <P::K as GenericResidue>::BITS;
+    let s_bits = <P::S as GenericResidue>::BITS;
+    let ks_bits = <P::KS as GenericResidue>::BITS;
+    let kss_bits = <P::KSS as GenericResidue>::BITS;
+    if ks_bits != k_bits + s_bits {
+        return Err(ParameterError::KsBits {
+            expected: k_bits + s_bits,
+            actual: ks_bits,
+        });
+    }
+    // This also ensures that the truncation check has at least `2 s` bits.
+    if kss_bits != ks_bits + s_bits {
+        return Err(ParameterError::KssBits {
+            expected: ks_bits + s_bits,
+            actual: kss_bits,
+        });
+    }
+    // `unpack()` drops the lowest `2 delta` bits, and the products `c = ab` of the VOLE have to
+    // survive in `KSS`, since the truncation shifts them by `s` bits.
+    let plaintext_bits = <P::PlaintextResidue as GenericResidue>::BITS;
+    let required_bits = kss_bits + 2 * <P::PlaintextParams as TIPParameters>::DELTA as usize;
+    if plaintext_bits < required_bits {
+        return Err(ParameterError::PlaintextBits {
+            required: required_bits,
+            actual: plaintext_bits,
+        });
+    }
+    // Each iteration authenticates the packed `b` and the two values of the batch check mask,
+    // which should take a single round of the dealer.
+    let required = packing_capacity::<P::PlaintextParams>() + 2;
+    let available = crate::low_gear_dealer::packing_capacity::<
+        <P::DealerParams as DealerParameters>::PlaintextParams,
+    >();
+    if available < required {
+        return Err(ParameterError::DealerCapacity {
+            required,
+            available,
+        });
+    }
+    if P::ZKPOPK_AMORTIZE == 0 || P::ZKPOPK_MAX_REPS == 0 || P::ZKPOPK_MAX_STACK_BATCHES == 0 {
+        return Err(ParameterError::ZkpopkZero);
+    }
+    // `zkpopk::num_proofs()` is not `const`, so this checks an upper bound on the number of proofs.
+    let m = <P::PlaintextParams as PolyParameters>::M;
+    let num_proofs = zkpopk::max_num_proofs(m, P::ZKPOPK_SND_SEC);
+    if zkpopk::response_bound(m, P::ZKPOPK_INV_FAIL_PROB, max_amortize::<P>(), num_proofs).is_none()
+    {
+        return Err(ParameterError::ZkpopkBoundOverflow);
+    }
+    Ok(())
+}
+
 pub const fn batch_size<P>() -> usize
 where
     P: PreprocessorParameters,
@@ -435,5 +1656,462 @@ where
     P::ZKPOPK_AMORTIZE * packing_capacity::<P::PlaintextParams>()
 }
 
+/// Largest number of ciphertexts that one ZKPoPK proves, see `ZKPOPK_MAX_STACK_BATCHES`.
+pub const fn max_amortize<P>() -> usize
+where
+    P: PreprocessorParameters,
+{
+    P::ZKPOPK_MAX_STACK_BATCHES * P::ZKPOPK_AMORTIZE
+}
+
+/// Size of a ciphertext on the wire (up to a few bytes), as accounted for by the rate limiter.
+fn ciphertext_size<P>() -> usize
+where
+    P: PreprocessorParameters,
+{
+    let degree = <P::CiphertextParams as PolyParameters>::CYCLOTOMIC_DEGREE;
+    2 * degree * wire_len::<<P::CiphertextParams as PolyParameters>::Residue>()
+}
+
+/// Number of bits of drowning noise for the VOLE.  The remote party's ciphertexts of `a` were
+/// proven with a ZKPoPK (over at most `max_amortize()` ciphertexts) and are multiplied by the MAC
+/// key, `b` or its tags.
+fn vole_drown_bits<P>() -> usize
+where
+    P: PreprocessorParameters,
+{
+    let num_proofs = zkpopk::num_proofs::<P::BgvParams>(P::ZKPOPK_SND_SEC);
+    let proven_noise_bits = noise::zkpopk_noise_bits::<P::BgvParams>(
+        max_amortize::<P>(),
+        num_proofs,
+        P::ZKPOPK_INV_FAIL_PROB,
+    );
+    noise::drown_bits::<P::BgvParams>(noise::product_noise_bits::<P::BgvParams>(proven_noise_bits))
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::bgv::params::{phi337_mod_p311::Phi337ModP311, phi337_mod_t112::Phi337ModT112};
+    use crate::bgv::residue::native::NativeResidue;
+    use crate::bgv::residue::GenericResidue;
+    use crate::bgv::zkpopk::ZkpopkVersion;
+    use crate::connection::Connection;
+    use crate::crypto_suite::CryptoSuite;
+    use crate::edabit::{self, DaBitError};
+    use crate::interface::{reconstruct, BatchedPreprocessor};
+    use crate::low_gear_dealer::params::ToyDealerK32S32;
+    use crate::verify::check_mac;
+
+    #[cfg(feature = "params-k128")]
+    use super::params::PreprocK128S64;
+    #[cfg(feature = "params-k32")]
+    use super::params::PreprocK32S32;
+    #[cfg(feature = "params-k64")]
+    use super::params::PreprocK64S64;
+    use super::params::ToyPreprocK32S32;
+    use super::{validate, InitError, LowGearPreprocessor, ParameterError, PreprocessorParameters};
+
+    #[test]
+    fn shipped_parameters_are_valid() {
+        assert_eq!(validate::<ToyPreprocK32S32>(), Ok(()));
+        #[cfg(feature = "params-k32")]
+        assert_eq!(validate::<PreprocK32S32>(), Ok(()));
+        #[cfg(feature = "params-k64")]
+        assert_eq!(validate::<PreprocK64S64>(), Ok(()));
+        #[cfg(feature = "params-k128")]
+        assert_eq!(validate::<PreprocK128S64>(), Ok(()));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct NarrowKss {}
+
+    impl PreprocessorParameters for NarrowKss {
+        type DealerParams = ToyDealerK32S32;
+        type PlaintextResidue = NativeResidue<112, 2>;
+        type PlaintextParams = Phi337ModT112;
+        type CiphertextParams = Phi337ModP311;
+        type BgvParams = (Self::PlaintextParams, Self::CiphertextParams);
+        type K = NativeResidue<32, 1>;
+        type S = NativeResidue<32, 1>;
+        type KS = NativeResidue<64, 1>;
+        type KSS = NativeResidue<64, 1>;
+
+        const ZKPOPK_AMORTIZE: usize = 4 * 4;
+        const ZKPOPK_SND_SEC: usize = 26;
+    }
+
+    #[test]
+    fn mismatch_is_reported() {
+        assert_eq!(
+            validate::<NarrowKss>(),
+            Err(ParameterError::KssBits {
+                expected: 96,
+                actual: 64
+            })
+        );
+    }
+
+    #[cfg(feature = "params-k128")]
+    #[derive(Debug, PartialEq)]
+    struct HugeInvFailProb {}
+
+    #[cfg(feature = "params-k128")]
+    impl PreprocessorParameters for HugeInvFailProb {
+        type DealerParams = <PreprocK128S64 as PreprocessorParameters>::DealerParams;
+        type PlaintextResidue = <PreprocK128S64 as PreprocessorParameters>::PlaintextResidue;
+        type PlaintextParams = <PreprocK128S64 as PreprocessorParameters>::PlaintextParams;
+        type CiphertextParams = <PreprocK128S64 as PreprocessorParameters>::CiphertextParams;
+        type BgvParams = (Self::PlaintextParams, Self::CiphertextParams);
+        type K = <PreprocK128S64 as PreprocessorParameters>::K;
+        type S = <PreprocK128S64 as PreprocessorParameters>::S;
+        type KS = <PreprocK128S64 as PreprocessorParameters>::KS;
+        type KSS = <PreprocK128S64 as PreprocessorParameters>::KSS;
+
+        const ZKPOPK_AMORTIZE: usize = PreprocK128S64::ZKPOPK_AMORTIZE;
+        const ZKPOPK_SND_SEC: usize = PreprocK128S64::ZKPOPK_SND_SEC;
+        const ZKPOPK_INV_FAIL_PROB: usize = 1 << 20;
+    }
+
+    #[cfg(feature = "params-k128")]
+    #[test]
+    fn overflowing_zkpopk_bounds_are_reported() {
+        assert_eq!(
+            validate::<HugeInvFailProb>(),
+            Err(ParameterError::ZkpopkBoundOverflow)
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct StackedToy {}
+
+    impl PreprocessorParameters for StackedToy {
+        type DealerParams = <ToyPreprocK32S32 as PreprocessorParameters>::DealerParams;
+        type PlaintextResidue = <ToyPreprocK32S32 as PreprocessorParameters>::PlaintextResidue;
+        type PlaintextParams = <ToyPreprocK32S32 as PreprocessorParameters>::PlaintextParams;
+        type CiphertextParams = <ToyPreprocK32S32 as PreprocessorParameters>::CiphertextParams;
+        type BgvParams = (Self::PlaintextParams, Self::CiphertextParams);
+        type K = <ToyPreprocK32S32 as PreprocessorParameters>::K;
+        type S = <ToyPreprocK32S32 as PreprocessorParameters>::S;
+        type KS = <ToyPreprocK32S32 as PreprocessorParameters>::KS;
+        type KSS = <ToyPreprocK32S32 as PreprocessorParameters>::KSS;
+
+        const ZKPOPK_AMORTIZE: usize = ToyPreprocK32S32::ZKPOPK_AMORTIZE;
+        const ZKPOPK_SND_SEC: usize = ToyPreprocK32S32::ZKPOPK_SND_SEC;
+        const ZKPOPK_MAX_STACK_BATCHES: usize = 2;
+    }
+
+    #[tokio::test]
+    async fn stacked_zkpopk() {
+        const P0_ADDR: &str = "[::1]:50131";
+        const P1_ADDR: &str = "[::1]:50132";
+
+        assert_eq!(validate::<StackedToy>(), Ok(()));
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<StackedToy, 0>::new(&mut conn0),
+            LowGearPreprocessor::<StackedToy, 1>::new(&mut conn1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+        preproc0.set_zkpopk_stack_batches(2);
+        preproc1.set_zkpopk_stack_batches(2);
+
+        for _ in 0..2 {
+            let (triples0, triples1) = tokio::join!(
+                preproc0.try_get_beaver_triples(),
+                preproc1.try_get_beaver_triples()
+            );
+            assert_eq!(triples0.unwrap().len(), triples1.unwrap().len());
+        }
+        assert_eq!(preproc0.zkpopk_stats().proofs, 1);
+        assert!(preproc0.a_stack.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dealer_prefetch() {
+        const P0_ADDR: &str = "[::1]:50133";
+        const P1_ADDR: &str = "[::1]:50134";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<ToyPreprocK32S32, 0>::new(&mut fork0),
+            LowGearPreprocessor::<ToyPreprocK32S32, 1>::new(&mut fork1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (pool0, pool1) = tokio::join!(
+            preproc0.enable_dealer_prefetch(&mut fork0, 2),
+            preproc1.enable_dealer_prefetch(&mut fork1, 2)
+        );
+        pool0.unwrap();
+        pool1.unwrap();
+
+        // The MAC check of each batch covers the values of b from the pool.
+        for _ in 0..2 {
+            let (triples0, triples1) = tokio::join!(
+                preproc0.try_get_beaver_triples(),
+                preproc1.try_get_beaver_triples()
+            );
+            assert_eq!(triples0.unwrap().len(), triples1.unwrap().len());
+        }
+        tokio::join!(preproc0.finish(), preproc1.finish());
+    }
+
+    #[tokio::test]
+    async fn ciphertext_batching() {
+        const P0_ADDR: &str = "[::1]:50135";
+        const P1_ADDR: &str = "[::1]:50136";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<ToyPreprocK32S32, 0>::new(&mut conn0),
+            LowGearPreprocessor::<ToyPreprocK32S32, 1>::new(&mut conn1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+        preproc0.set_ciphertext_batching(true);
+        preproc1.set_ciphertext_batching(true);
+
+        let (triples0, triples1) = tokio::join!(
+            preproc0.try_get_beaver_triples(),
+            preproc1.try_get_beaver_triples()
+        );
+        let (triples0, triples1) = (triples0.unwrap(), triples1.unwrap());
+        assert_eq!(triples0.len(), triples1.len());
+        for (triple0, triple1) in triples0.iter().zip(&triples1) {
+            let a = reconstruct(&triple0.a, &triple1.a);
+            let b = reconstruct(&triple0.b, &triple1.b);
+            assert_eq!(a * b, reconstruct(&triple0.c, &triple1.c));
+        }
+        // Each batch of ciphertexts was answered by the other party.  An answer that arrived
+        // before the batch was acquired counts as well, so this does not depend on the timing.
+        assert_eq!(preproc0.rate_limiter().in_flight(), 0);
+        assert_eq!(preproc1.rate_limiter().in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn redundancy() {
+        const P0_ADDR: &str = "[::1]:50137";
+        const P1_ADDR: &str = "[::1]:50138";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<ToyPreprocK32S32, 0>::new(&mut conn0),
+            LowGearPreprocessor::<ToyPreprocK32S32, 1>::new(&mut conn1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+        // The parties may use different rates.
+        preproc0.set_redundancy(1.0);
+        preproc1.set_redundancy(0.5);
+
+        let (triples0, triples1) = tokio::join!(
+            preproc0.try_get_beaver_triples(),
+            preproc1.try_get_beaver_triples()
+        );
+        assert_eq!(triples0.unwrap().len(), triples1.unwrap().len());
+        let stats = preproc0.redundancy_stats();
+        assert_eq!(
+            stats.checked_iterations,
+            ToyPreprocK32S32::ZKPOPK_AMORTIZE as u64
+        );
+        assert_eq!(stats.faults, 0);
+        assert_eq!(preproc1.redundancy_stats().faults, 0);
+    }
+
+    #[tokio::test]
+    async fn crypto_suites() {
+        // Two ports per suite, starting at 50141.
+        for (i, &suite) in CryptoSuite::ALL.iter().enumerate() {
+            let p0_addr = format!("[::1]:{}", 50141 + 2 * i);
+            let p1_addr = format!("[::1]:{}", 50142 + 2 * i);
+
+            let (conn0, conn1) = tokio::join!(
+                Connection::new(p0_addr.parse().unwrap(), p1_addr.parse().unwrap()),
+                Connection::new(p1_addr.parse().unwrap(), p0_addr.parse().unwrap())
+            );
+            let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+            let (preproc0, preproc1) = tokio::join!(
+                LowGearPreprocessor::<ToyPreprocK32S32, 0>::builder()
+                    .crypto_suite(suite)
+                    .build(&mut conn0),
+                LowGearPreprocessor::<ToyPreprocK32S32, 1>::builder()
+                    .crypto_suite(suite)
+                    .build(&mut conn1)
+            );
+            let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+            assert_eq!(preproc0.crypto_suite(), suite);
+            preproc0.set_strict_authentication(true);
+            preproc1.set_strict_authentication(true);
+
+            let (triples0, triples1) = tokio::join!(
+                preproc0.try_get_beaver_triples(),
+                preproc1.try_get_beaver_triples()
+            );
+            assert_eq!(triples0.unwrap().len(), triples1.unwrap().len());
+            assert_eq!(preproc0.batch_id(), preproc1.batch_id());
+        }
+    }
+
+    #[tokio::test]
+    async fn zkpopk_version_mismatch() {
+        const P0_ADDR: &str = "[::1]:50167";
+        const P1_ADDR: &str = "[::1]:50168";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<ToyPreprocK32S32, 0>::builder()
+                .zkpopk_version(ZkpopkVersion::V1)
+                .build(&mut conn0),
+            LowGearPreprocessor::<ToyPreprocK32S32, 1>::builder().build(&mut conn1)
+        );
+        assert!(matches!(
+            preproc0,
+            Err(InitError::VersionMismatch {
+                local: ZkpopkVersion::V1,
+                remote: ZkpopkVersion::V2
+            })
+        ));
+        assert!(matches!(
+            preproc1,
+            Err(InitError::VersionMismatch {
+                local: ZkpopkVersion::V2,
+                remote: ZkpopkVersion::V1
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn batch_ids() {
+        const P0_ADDR: &str = "[::1]:50123";
+        const P1_ADDR: &str = "[::1]:50124";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<ToyPreprocK32S32, 0>::new(&mut fork0),
+            LowGearPreprocessor::<ToyPreprocK32S32, 1>::new(&mut fork1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+        let first = preproc0.batch_id();
+        assert_eq!(first.index, 0);
+        assert_eq!(preproc1.batch_id(), first);
+
+        let (triples0, triples1) = tokio::join!(
+            preproc0.try_get_beaver_triples(),
+            preproc1.try_get_beaver_triples()
+        );
+        triples0.unwrap();
+        triples1.unwrap();
+        assert_eq!(preproc0.batch_id().session, first.session);
+        assert_eq!(preproc0.batch_id().index, 1);
+        assert_eq!(preproc1.batch_id(), preproc0.batch_id());
+
+        // Concurrent preprocessors on the same connection have different sessions.
+        let (mut fork0, mut fork1) = (conn0.fork(), conn1.fork());
+        let (other0, other1) = tokio::join!(
+            LowGearPreprocessor::<ToyPreprocK32S32, 0>::new(&mut fork0),
+            LowGearPreprocessor::<ToyPreprocK32S32, 1>::new(&mut fork1)
+        );
+        let (other0, other1) = (other0.unwrap(), other1.unwrap());
+        assert_eq!(other0.batch_id(), other1.batch_id());
+        assert_ne!(other0.batch_id().session, first.session);
+    }
+
+    #[tokio::test]
+    async fn dabits() {
+        const P0_ADDR: &str = "[::1]:50147";
+        const P1_ADDR: &str = "[::1]:50148";
+        type P = ToyPreprocK32S32;
+        type K = <P as PreprocessorParameters>::K;
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<P, 0>::new(&mut conn0),
+            LowGearPreprocessor::<P, 1>::new(&mut conn1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+
+        let (dabits0, dabits1) =
+            tokio::join!(preproc0.try_get_dabits(8), preproc1.try_get_dabits(8));
+        let (dabits0, dabits1) = (dabits0.unwrap(), dabits1.unwrap());
+        let mut expected = Vec::new();
+        for (dabit0, dabit1) in dabits0.iter().zip(&dabits1) {
+            let bit = dabit0.boolean ^ dabit1.boolean;
+            assert_eq!(
+                reconstruct(&dabit0.arith, &dabit1.arith),
+                K::from_i64(bit as i64)
+            );
+            assert!(check_mac(
+                &dabit0.arith,
+                &dabit1.arith,
+                preproc0.mac_key(),
+                preproc1.mac_key()
+            ));
+            expected.push(bit);
+        }
+
+        // The XOR shares are authenticated.
+        let xor_shares0: Vec<_> = dabits0.iter().map(|dabit| dabit.xor_shares).collect();
+        let xor_shares1: Vec<_> = dabits1.iter().map(|dabit| dabit.xor_shares).collect();
+        let (opened0, opened1) = tokio::join!(
+            edabit::open_booleans(&mut preproc0.opener, &xor_shares0),
+            edabit::open_booleans(&mut preproc1.opener, &xor_shares1)
+        );
+        assert_eq!(opened0.unwrap(), expected);
+        assert_eq!(opened1.unwrap(), expected);
+
+        // A party that inputs a value other than a bit is caught by both parties.
+        let (triples0, triples1) = tokio::join!(
+            preproc0.try_get_beaver_triples(),
+            preproc1.try_get_beaver_triples()
+        );
+        let (triples0, triples1) = (triples0.unwrap(), triples1.unwrap());
+        let bits = vec![K::from_i64(2); 4];
+        let (result0, result1) = tokio::join!(
+            edabit::gen_dabits_from::<P, 0>(
+                &mut preproc0.dealer,
+                &mut preproc0.opener,
+                &preproc0.mac_key,
+                &bits,
+                &triples0[..8]
+            ),
+            edabit::gen_dabits::<P, 1>(
+                &mut preproc1.dealer,
+                &mut preproc1.opener,
+                &preproc1.mac_key,
+                &triples1[..8]
+            )
+        );
+        assert!(matches!(result0, Err(DaBitError::NotABit)));
+        assert!(matches!(result1, Err(DaBitError::NotABit)));
+
+        tokio::join!(preproc0.finish(), preproc1.finish());
+    }
+}
@@ -0,0 +1,86 @@
+//! Runtime description of `PreprocessorParameters`, e.g., for clients that select the parameters
+//! by name or need the packing capacity without naming the generic types.
+
+use serde::Serialize;
+
+use crate::bgv::poly::crt::CrtPolyParameters;
+use crate::bgv::poly::PolyParameters;
+use crate::bgv::residue::GenericResidue;
+use crate::bgv::tweaked_interpolation_packing::{packing_capacity, TIPParameters};
+
+use super::PreprocessorParameters;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ParamInfo {
+    /// Name of the parameter type, e.g., `PreprocK64S64`.
+    pub name: &'static str,
+    pub k: usize,
+    pub s: usize,
+    /// `m` of the cyclotomic polynomial `\Phi_m(X)` of both rings.
+    pub m: usize,
+    pub cyclotomic_degree: usize,
+    /// Number and degree of the factors of `\Phi_m(X)` modulo the plaintext modulus.
+    pub factor_count: usize,
+    pub factor_degree: usize,
+    /// See `TIPParameters::DELTA`.
+    pub delta: u32,
+    /// Number of triples per packed plaintext.
+    pub packing_capacity: usize,
+    pub plaintext_bits: usize,
+    pub ciphertext_bits: usize,
+    pub zkpopk_amortize: usize,
+    /// See `PreprocessorParameters::ZKPOPK_MAX_STACK_BATCHES`.
+    pub zkpopk_max_stack_batches: usize,
+    pub zkpopk_snd_sec: usize,
+}
+
+impl ParamInfo {
+    pub fn of<P>() -> Self
+    where
+        P: PreprocessorParameters,
+    {
+        let type_name = std::any::type_name::<P>();
+        Self {
+            name: type_name.rsplit("::").next().unwrap_or(type_name),
+            k: P::K::BITS,
+            s: P::S::BITS,
+            m: <P::PlaintextParams as PolyParameters>::M,
+            cyclotomic_degree: <P::PlaintextParams as PolyParameters>::CYCLOTOMIC_DEGREE,
+            factor_count: <P::PlaintextParams as CrtPolyParameters>::FACTOR_COUNT,
+            factor_degree: <P::PlaintextParams as CrtPolyParameters>::FACTOR_DEGREE,
+            delta: <P::PlaintextParams as TIPParameters>::DELTA,
+            packing_capacity: packing_capacity::<P::PlaintextParams>(),
+            plaintext_bits:
+                <<P::PlaintextParams as PolyParameters>::Residue as GenericResidue>::BITS,
+            ciphertext_bits:
+                <<P::CiphertextParams as PolyParameters>::Residue as GenericResidue>::BITS,
+            zkpopk_amortize: P::ZKPOPK_AMORTIZE,
+            zkpopk_max_stack_batches: P::ZKPOPK_MAX_STACK_BATCHES,
+            zkpopk_snd_sec: P::ZKPOPK_SND_SEC,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "params-k64"))]
+mod tests {
+    use crate::low_gear_preproc::params::PreprocK64S64;
+
+    use super::ParamInfo;
+
+    #[test]
+    fn of() {
+        let info = ParamInfo::of::<PreprocK64S64>();
+        assert_eq!(info.name, "PreprocK64S64");
+        assert_eq!((info.k, info.s), (64, 64));
+        assert_eq!(info.m, 43691);
+        assert_eq!(
+            info.factor_count * info.factor_degree,
+            info.cyclotomic_degree
+        );
+        assert_eq!(
+            info.packing_capacity,
+            info.factor_count * info.factor_degree.div_ceil(2)
+        );
+        assert!(info.ciphertext_bits > info.plaintext_bits);
+    }
+}
@@ -1,15 +1,22 @@
+#[cfg(feature = "params-k32")]
+use crate::bgv::params::{phi43691_mod_p387::Phi43691ModP387, phi43691_mod_t135::Phi43691ModT135};
+#[cfg(feature = "params-k64")]
+use crate::bgv::params::{phi43691_mod_p616::Phi43691ModP616, phi43691_mod_t233::Phi43691ModT233};
+#[cfg(feature = "params-k128")]
+use crate::bgv::params::{phi43691_mod_p744::Phi43691ModP744, phi43691_mod_t297::Phi43691ModT297};
+#[cfg(feature = "params-k128")]
+use crate::low_gear_dealer::params::DealerK128S64;
+#[cfg(feature = "params-k32")]
+use crate::low_gear_dealer::params::DealerK32S32;
+#[cfg(feature = "params-k64")]
+use crate::low_gear_dealer::params::DealerK64S64;
 use crate::{
     bgv::{
-        params::{
-            phi337_mod_p259::Phi337ModP259, phi337_mod_t86::Phi337ModT86,
-            phi43691_mod_p387::Phi43691ModP387, phi43691_mod_p616::Phi43691ModP616,
-            phi43691_mod_p744::Phi43691ModP744, phi43691_mod_t135::Phi43691ModT135,
-            phi43691_mod_t233::Phi43691ModT233, phi43691_mod_t297::Phi43691ModT297,
-        },
+        params::{phi337_mod_p311::Phi337ModP311, phi337_mod_t112::Phi337ModT112},
         poly::PolyParameters,
         residue::native::NativeResidue,
     },
-    low_gear_dealer::params::{DealerK128S64, DealerK32S32, DealerK64S64, ToyDealerK32S32},
+    low_gear_dealer::params::ToyDealerK32S32,
 };
 
 use super::PreprocessorParameters;
@@ -20,8 +27,8 @@ pub struct ToyPreprocK32S32 {}
 impl PreprocessorParameters for ToyPreprocK32S32 {
     type DealerParams = ToyDealerK32S32;
     type PlaintextResidue = <Self::PlaintextParams as PolyParameters>::Residue;
-    type PlaintextParams = Phi337ModT86;
-    type CiphertextParams = Phi337ModP259;
+    type PlaintextParams = Phi337ModT112;
+    type CiphertextParams = Phi337ModP311;
     type BgvParams = (Self::PlaintextParams, Self::CiphertextParams);
     type K = NativeResidue<32, 1>;
     type S = NativeResidue<32, 1>;
@@ -33,9 +40,11 @@ impl PreprocessorParameters for ToyPreprocK32S32 {
     const ZKPOPK_SND_SEC: usize = 26;
 }
 
+#[cfg(feature = "params-k32")]
 #[derive(Debug, PartialEq)]
 pub struct PreprocK32S32 {}
 
+#[cfg(feature = "params-k32")]
 impl PreprocessorParameters for PreprocK32S32 {
     type DealerParams = DealerK32S32;
     type PlaintextResidue = <Self::PlaintextParams as PolyParameters>::Residue;
@@ -52,9 +61,11 @@ impl PreprocessorParameters for PreprocK32S32 {
     const ZKPOPK_SND_SEC: usize = 26;
 }
 
+#[cfg(feature = "params-k64")]
 #[derive(Debug, PartialEq)]
 pub struct PreprocK64S64 {}
 
+#[cfg(feature = "params-k64")]
 impl PreprocessorParameters for PreprocK64S64 {
     type DealerParams = DealerK64S64;
     type PlaintextResidue = <Self::PlaintextParams as PolyParameters>::Residue;
@@ -71,9 +82,11 @@ impl PreprocessorParameters for PreprocK64S64 {
     const ZKPOPK_SND_SEC: usize = 57;
 }
 
+#[cfg(feature = "params-k128")]
 #[derive(Debug, PartialEq)]
 pub struct PreprocK128S64 {}
 
+#[cfg(feature = "params-k128")]
 impl PreprocessorParameters for PreprocK128S64 {
     type DealerParams = DealerK128S64;
     type PlaintextResidue = <Self::PlaintextParams as PolyParameters>::Residue;
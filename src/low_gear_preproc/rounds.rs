@@ -0,0 +1,102 @@
+//! The rounds of `LowGearPreprocessor`, see `RoundChannel`.
+//!
+//! The ciphertexts are sent on `BulkChannel`s instead, which may carry checksums.
+
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bgv::zkpopk::prover::ResponseAborted;
+use crate::bgv::zkpopk::{self, Response as ZkpopkResponse, ZkpopkVersion};
+use crate::bgv::PublicKey;
+use crate::round_channel::Round;
+
+use super::checkpoint::ResumePoint;
+use super::PreprocessorParameters;
+
+/// The public keys, the abort keys and the protocol versions, which are exchanged once.
+pub struct Init<P>(PhantomData<P>);
+
+impl<P> Round for Init<P>
+where
+    P: PreprocessorParameters,
+{
+    const NAME: &'static str = "LowGearPreprocessor:init";
+
+    type Message = InitMessage<P>;
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound = "")]
+pub struct InitMessage<P>
+where
+    P: PreprocessorParameters,
+{
+    /// Both parties must use the same version, see `LowGearPreprocessorBuilder::zkpopk_version()`.
+    pub zkpopk_version: ZkpopkVersion,
+    pub pk: PublicKey<P::BgvParams>,
+    /// The key that verifies the signatures of this party's aborts, see `AbortChannel`.
+    pub abort_key: [u8; 32],
+}
+
+/// The commitment of the ZKPoPK prover.
+pub struct Commitment<P>(PhantomData<P>);
+
+impl<P> Round for Commitment<P>
+where
+    P: PreprocessorParameters,
+{
+    const NAME: &'static str = "LowGearPreprocessor:commitment";
+
+    type Message = zkpopk::Commitment<P::BgvParams>;
+}
+
+/// The challenge of the ZKPoPK verifier.
+pub struct Challenge;
+
+impl Round for Challenge {
+    const NAME: &'static str = "LowGearPreprocessor:challenge";
+
+    type Message = zkpopk::Challenge;
+}
+
+/// The response of the ZKPoPK prover, or that it aborted and starts another repetition.
+pub struct Response<P>(PhantomData<P>);
+
+impl<P> Round for Response<P>
+where
+    P: PreprocessorParameters,
+{
+    const NAME: &'static str = "LowGearPreprocessor:response";
+
+    type Message = Result<ZkpopkResponse<P::BgvParams>, ResponseAborted>;
+}
+
+/// The outcome of the VOLE of an iteration, see `VoleOutcome`.
+pub struct Decrypted;
+
+impl Round for Decrypted {
+    const NAME: &'static str = "LowGearPreprocessor:decrypted";
+
+    type Message = VoleOutcome;
+}
+
+/// The outcome of the VOLE of an iteration at one party, see `DecryptionStats` and
+/// `RedundancyStats`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum VoleOutcome {
+    Ok,
+    /// A redundant computation differed, so both parties discard the iteration.
+    Fault,
+    /// A decryption failed, so both parties abort.
+    DecryptionFailed,
+}
+
+/// The checkpoint of each party at the start of a checkpointed batch, see `checkpoint`.
+pub struct Resume;
+
+impl Round for Resume {
+    const NAME: &'static str = "LowGearPreprocessor:resume";
+
+    type Message = Option<ResumePoint>;
+}
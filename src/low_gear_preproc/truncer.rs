@@ -1,13 +1,46 @@
-use futures_util::{SinkExt, StreamExt};
-use log::info;
-use serde::{Deserialize, Serialize};
+use log::{error, info};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     bgv::residue::native::GenericNativeResidue,
     bi_channel::BiChannel,
+    commitment::{self, Commitment, Opening},
     connection::{Connection, StreamError},
+    crypto_suite::CryptoSuite,
+    interface::{MacKeyShare, SpdzParams},
+    mac_check_opener::MacCheckOpener,
+    mux_channel::{Multiplexer, MuxError, SubChannel},
+    role::Role,
+    sampling,
 };
 
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum TruncationError {
+    /// The inputs or a message of the other party have the wrong number of values.
+    WrongLength,
+    /// The other party's opening does not match its commitment.
+    InvalidOpening,
+    /// The seed of the random combination could not be sampled jointly.
+    InvalidSeed,
+    CheckFailed,
+    AbortedByPeer,
+    FailedToSend(bincode::ErrorKind),
+    FailedToReceive(bincode::ErrorKind),
+    ConnectionClosed,
+}
+
+/// The truncated shares returned by `Truncer::truncate()`.
+pub struct Truncation<KS> {
+    pub a: Vec<KS>,
+    pub a_tags: Vec<KS>,
+    pub c: Vec<KS>,
+    pub c_tags: Vec<KS>,
+}
+
+/// Sent instead of the next protocol message in order to make the other party abort, too.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Abort;
+
 #[derive(Clone, Deserialize, Serialize)]
 struct ComMsg<S> {
     hat_a_tags_mod2s: Vec<S>,
@@ -15,27 +48,87 @@ struct ComMsg<S> {
     hat_c_tags_mod2s: Vec<S>,
 }
 
+#[cfg(feature = "fuzzing")]
+pub(crate) fn fuzz_opening(bytes: &[u8]) {
+    type S = <super::params::ToyPreprocK32S32 as SpdzParams>::S;
+    crate::fuzzing::framed_roundtrip::<Opening<ComMsg<S>>>(bytes);
+}
+
+/// The `Truncer` of the rings of `P`.
+pub type TruncerOf<P> = Truncer<<P as SpdzParams>::S>;
+
 pub struct Truncer<S>
 where
     S: GenericNativeResidue,
 {
-    ch_a: BiChannel<Vec<S>>,
-    ch_com: BiChannel<ComMsg<S>>,
-    mac_key: S,
+    ch_a: SubChannel<Vec<S>>,
+    ch_com: SubChannel<Result<Commitment<ComMsg<S>>, Abort>>,
+    ch_opening: SubChannel<Opening<ComMsg<S>>>,
+    ch_verdict: SubChannel<Result<(), Abort>>,
+    mac_key: MacKeyShare<S>,
+    /// Values that must be zero, checked in `batch_check()`.
+    pending: Vec<S>,
+    failure: Option<TruncationError>,
+    crypto_suite: CryptoSuite,
 }
 
 impl<S> Truncer<S>
 where
     S: GenericNativeResidue,
 {
-    pub async fn new(conn: &mut Connection, mac_key: S) -> Result<Self, StreamError> {
-        Ok(Self {
-            ch_a: BiChannel::open(conn, "Truncer:a").await?,
-            ch_com: BiChannel::open(conn, "Truncer:com").await?,
+    pub async fn new(conn: &mut Connection, mac_key: MacKeyShare<S>) -> Result<Self, StreamError> {
+        Ok(Self::with_channels(
+            BiChannel::open(conn, "Truncer:a").await?.into(),
+            BiChannel::open(conn, "Truncer:com").await?.into(),
+            BiChannel::open(conn, "Truncer:opening").await?.into(),
+            BiChannel::open(conn, "Truncer:verdict").await?.into(),
             mac_key,
-        })
+        ))
     }
 
+    /// Like `new()`, but opens a single stream for all channels, see `Multiplexer`.  Both parties
+    /// must either call `new()` or this.
+    pub async fn new_multiplexed(
+        conn: &mut Connection,
+        mac_key: MacKeyShare<S>,
+    ) -> Result<Self, StreamError> {
+        let mut mux = Multiplexer::open(conn, "Truncer").await?;
+        Ok(Self::with_channels(
+            mux.channel().await.into(),
+            mux.channel().await.into(),
+            mux.channel().await.into(),
+            mux.channel().await.into(),
+            mac_key,
+        ))
+    }
+
+    fn with_channels(
+        ch_a: SubChannel<Vec<S>>,
+        ch_com: SubChannel<Result<Commitment<ComMsg<S>>, Abort>>,
+        ch_opening: SubChannel<Opening<ComMsg<S>>>,
+        ch_verdict: SubChannel<Result<(), Abort>>,
+        mac_key: MacKeyShare<S>,
+    ) -> Self {
+        Self {
+            ch_a,
+            ch_com,
+            ch_opening,
+            ch_verdict,
+            mac_key,
+            pending: Vec::new(),
+            failure: None,
+            crypto_suite: CryptoSuite::default(),
+        }
+    }
+
+    /// Sets the hash function of the commitments.  Both parties must use the same suite.
+    pub fn set_crypto_suite(&mut self, suite: CryptoSuite) {
+        self.crypto_suite = suite;
+    }
+
+    /// Returns the truncated `a` and `c` with their tags.  The result must not be used before
+    /// `batch_check()` succeeded.  If an error is returned, then the other party also returns an error and the
+    /// `Truncer` must not be used anymore.
     pub async fn truncate<K, KS, KSS, const PID: usize>(
         &mut self,
         wide_a: &[KSS],
@@ -44,147 +137,269 @@ where
         b_tags: &[KS],
         wide_c: &[KSS],
         wide_c_tags: &[KSS],
-    ) -> (Vec<KS>, Vec<KS>, Vec<KS>, Vec<KS>)
+    ) -> Result<Truncation<KS>, TruncationError>
+    where
+        K: GenericNativeResidue,
+        KS: GenericNativeResidue,
+        KSS: GenericNativeResidue,
+    {
+        self.truncate_as(
+            Role::of::<PID>(),
+            wide_a,
+            wide_a_tags,
+            b,
+            b_tags,
+            wide_c,
+            wide_c_tags,
+        )
+        .await
+    }
+
+    /// Like `truncate()`, but for a `role` that is decided at runtime.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "truncation", skip_all)
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn truncate_as<K, KS, KSS>(
+        &mut self,
+        role: Role,
+        wide_a: &[KSS],
+        wide_a_tags: &[KSS],
+        b: &[K],
+        b_tags: &[KS],
+        wide_c: &[KSS],
+        wide_c_tags: &[KSS],
+    ) -> Result<Truncation<KS>, TruncationError>
     where
         K: GenericNativeResidue,
         KS: GenericNativeResidue,
         KSS: GenericNativeResidue,
     {
         let len = wide_a.len();
-        // TODO: Check all lengths against len
+        let local_lengths_match = [
+            wide_a_tags.len(),
+            b.len(),
+            b_tags.len(),
+            wide_c.len(),
+            wide_c_tags.len(),
+        ]
+        .iter()
+        .all(|l| *l == len);
 
-        let a_mod2s: Vec<_> = wide_a.iter().copied().map(S::from_unsigned).collect();
+        // This party's shares of `a` are sampled in `KS`, so only the other party's share makes
+        // the sum exceed `KS`, which `sigma_a` below corrects.
+        debug_assert!(wide_a.iter().all(|a| KS::try_from_unsigned(*a).is_some()));
+
+        let a_mod2s: Vec<S> = sampling::narrow(wide_a);
+
+        // The lengths are only checked after this exchange, so that the other party is also
+        // aborted if they don't match.
+        let remote_a_mod2s = exchange(&mut self.ch_a, a_mod2s.clone()).await?;
+        if !local_lengths_match || remote_a_mod2s.len() != len {
+            if local_lengths_match {
+                error!("Truncer::truncate received a_mod2s with wrong length");
+            } else {
+                error!("Truncer::truncate called with inputs of different lengths");
+            }
+            self.ch_com.send(Err(Abort)).await.map_err(channel_error)?;
+            return Err(TruncationError::WrongLength);
+        }
 
-        let (rx_a, tx_a) = self.ch_a.split();
+        let sigma_a: Vec<_> = a_mod2s
+            .iter()
+            .zip(remote_a_mod2s.iter())
+            .map(|(l, r)| KS::from_unsigned(*l) + KS::from_unsigned(*r))
+            .collect();
 
-        let (_, (_, (a, a_tags, c, c_tags))) = tokio::join!(
-            async {
-                tx_a.send(a_mod2s.clone()).await.unwrap();
-            },
-            async {
-                let remote_a_mod2s = rx_a.next().await.unwrap().unwrap();
-                if remote_a_mod2s.len() != len {
-                    // TODO: Error handling instead of panic
-                    panic!("received a_mod2s has wrong length");
+        let mut hat_a_tags: Vec<_> = wide_a_tags
+            .iter()
+            .zip(sigma_a.iter())
+            .map(|(a, s)| *a - self.mac_key.mul_widening::<_, KSS>(s))
+            .collect();
+        let mut hat_c: Vec<_> = wide_c
+            .iter()
+            .zip(sigma_a.iter())
+            .zip(b.iter())
+            .map(|((c, s), b)| *c - s.mul_widening::<_, KSS>(b))
+            .collect();
+        let mut hat_c_tags: Vec<_> = wide_c_tags
+            .iter()
+            .zip(sigma_a.iter())
+            .zip(b_tags.iter())
+            .map(|((c, s), b)| *c - s.mul_widening::<_, KSS>(b))
+            .collect();
+
+        let com_msg = ComMsg::<S> {
+            hat_a_tags_mod2s: sampling::narrow(&hat_a_tags),
+            hat_c_mod2s: sampling::narrow(&hat_c),
+            hat_c_tags_mod2s: sampling::narrow(&hat_c_tags),
+        };
+
+        let (com, opening) = commitment::commit_random(self.crypto_suite, com_msg.clone());
+
+        let remote_com = exchange(&mut self.ch_com, Ok(com))
+            .await?
+            .map_err(|_| TruncationError::AbortedByPeer)?;
+
+        // Only open our commitment after having received theirs.
+        let remote_opening = exchange(&mut self.ch_opening, opening).await?;
+
+        match commitment::open(self.crypto_suite, &remote_com, remote_opening) {
+            Ok(remote_com_msg) => {
+                if let Err(err) = self.accumulate::<KSS>(
+                    role,
+                    len,
+                    &com_msg,
+                    &remote_com_msg,
+                    &mut hat_a_tags,
+                    &mut hat_c,
+                    &mut hat_c_tags,
+                ) {
+                    self.failure.get_or_insert(err);
                 }
+            }
+            Err(_) => {
+                error!("Truncer::truncate received invalid opening");
+                self.failure.get_or_insert(TruncationError::InvalidOpening);
+            }
+        }
+
+        let a = wide_a.iter().copied().map(shift).collect();
+        let a_tags = hat_a_tags.iter().copied().map(shift).collect();
+        let c = hat_c.iter().copied().map(shift).collect();
+        let c_tags = hat_c_tags.iter().copied().map(shift).collect();
 
-                let sigma_a: Vec<_> = a_mod2s
-                    .iter()
-                    .zip(remote_a_mod2s.iter())
-                    .map(|(l, r)| KS::from_unsigned(*l) + KS::from_unsigned(*r))
-                    .collect();
-
-                let mut hat_a_tags: Vec<_> = wide_a_tags
-                    .iter()
-                    .zip(sigma_a.iter())
-                    .map(|(a, s)| *a - KSS::from_unsigned(*s) * KSS::from_unsigned(self.mac_key))
-                    .collect();
-                let mut hat_c: Vec<_> = wide_c
-                    .iter()
-                    .zip(sigma_a.iter())
-                    .zip(b.iter())
-                    .map(|((c, s), b)| *c - KSS::from_unsigned(*s) * KSS::from_unsigned(*b))
-                    .collect();
-                let mut hat_c_tags: Vec<_> = wide_c_tags
-                    .iter()
-                    .zip(sigma_a.iter())
-                    .zip(b_tags.iter())
-                    .map(|((c, s), b)| *c - KSS::from_unsigned(*s) * KSS::from_unsigned(*b))
-                    .collect();
-
-                let com_msg = ComMsg::<S> {
-                    hat_a_tags_mod2s: hat_a_tags.iter().map(|x| S::from_unsigned(*x)).collect(),
-                    hat_c_mod2s: hat_c.iter().map(|x| S::from_unsigned(*x)).collect(),
-                    hat_c_tags_mod2s: hat_c_tags.iter().map(|x| S::from_unsigned(*x)).collect(),
-                };
-
-                let (rx_com, tx_com) = self.ch_com.split();
-
-                tokio::join!(
-                    async {
-                        tx_com.send(com_msg.clone()).await.unwrap();
-                    },
-                    async {
-                        let remote_com = rx_com.next().await.unwrap().unwrap();
-                        // TODO: Error handling instead of panic
-                        if remote_com.hat_a_tags_mod2s.len() != len {
-                            panic!("received hat_a_tags_mod2s has wrong length");
-                        }
-                        if remote_com.hat_c_mod2s.len() != len {
-                            panic!("received hat_c_mod2s has wrong length");
-                        }
-                        if remote_com.hat_c_tags_mod2s.len() != len {
-                            panic!("received hat_c_tags_mod2s has wrong length");
-                        }
-
-                        if PID == 0 {
-                            for (dst, src) in hat_a_tags
-                                .iter_mut()
-                                .zip(remote_com.hat_a_tags_mod2s.iter())
-                            {
-                                *dst += KSS::from_unsigned(*src);
-                                Self::check_is_zero_mod2s(*dst);
-                            }
-                            for (dst, src) in hat_c.iter_mut().zip(remote_com.hat_c_mod2s.iter()) {
-                                *dst += KSS::from_unsigned(*src);
-                                Self::check_is_zero_mod2s(*dst);
-                            }
-                            for (dst, src) in hat_c_tags
-                                .iter_mut()
-                                .zip(remote_com.hat_c_tags_mod2s.iter())
-                            {
-                                *dst += KSS::from_unsigned(*src);
-                                Self::check_is_zero_mod2s(*dst);
-                            }
-                        } else {
-                            for (l, r) in com_msg
-                                .hat_a_tags_mod2s
-                                .iter()
-                                .zip(remote_com.hat_a_tags_mod2s.iter())
-                            {
-                                Self::check_is_zero_mod2s(
-                                    KS::from_unsigned(*l) + KS::from_unsigned(*r),
-                                );
-                            }
-                            for (l, r) in com_msg
-                                .hat_c_mod2s
-                                .iter()
-                                .zip(remote_com.hat_c_mod2s.iter())
-                            {
-                                Self::check_is_zero_mod2s(
-                                    KS::from_unsigned(*l) + KS::from_unsigned(*r),
-                                );
-                            }
-                            for (l, r) in com_msg
-                                .hat_c_tags_mod2s
-                                .iter()
-                                .zip(remote_com.hat_c_tags_mod2s.iter())
-                            {
-                                Self::check_is_zero_mod2s(
-                                    KS::from_unsigned(*l) + KS::from_unsigned(*r),
-                                );
-                            }
-                        }
-
-                        info!("Trunc: check passed");
-
-                        let a = wide_a.iter().copied().map(shift).collect();
-                        let a_tags = hat_a_tags.iter().copied().map(shift).collect();
-                        let c = hat_c.iter().copied().map(shift).collect();
-                        let c_tags = hat_c_tags.iter().copied().map(shift).collect();
-
-                        (a, a_tags, c, c_tags)
+        Ok(Truncation {
+            a,
+            a_tags,
+            c,
+            c_tags,
+        })
+    }
+
+    /// Checks all truncations since the last call at once, by a random combination of the opened
+    /// values, whose coefficients are sampled jointly via `opener`.  Both parties must call this
+    /// after the same truncations.  `W` must have at least twice as many bits as `S`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "truncation_check", skip_all)
+    )]
+    pub async fn batch_check<W, KS>(
+        &mut self,
+        opener: &mut MacCheckOpener<KS, S>,
+    ) -> Result<(), TruncationError>
+    where
+        W: GenericNativeResidue,
+        KS: GenericNativeResidue,
+    {
+        assert!(W::BITS >= 2 * S::BITS);
+
+        // The opened values are public, so the coefficients must not be known to either party
+        // before it has committed to its values.
+        let result = match opener.joint_prng("Truncer:batch_check").await {
+            Ok(mut prng) => {
+                // Since the values are lifted to a ring with at least 2s bits, a non-zero value is
+                // detected except with probability 2^-s.
+                let combination = self.pending.iter().fold(W::ZERO, |mut acc, x| {
+                    acc.mul_add_assign(
+                        W::from_unsigned(*x),
+                        W::from_unsigned(S::random(&mut prng)),
+                    );
+                    acc
+                });
+                match self.failure.take() {
+                    Some(err) => Err(err),
+                    None if combination != W::ZERO => {
+                        error!("Truncer::batch_check failed");
+                        Err(TruncationError::CheckFailed)
                     }
-                )
+                    None => Ok(()),
+                }
             }
-        );
+            Err(_) => Err(TruncationError::InvalidSeed),
+        };
+        self.pending.clear();
+        self.failure = None;
+
+        // Exchange the verdicts, so that both parties abort if one of the checks failed.
+        let verdict = if result.is_ok() { Ok(()) } else { Err(Abort) };
+        let remote_verdict = exchange(&mut self.ch_verdict, verdict).await;
+        result?;
+        let remote_verdict = remote_verdict?;
+        remote_verdict.map_err(|_| TruncationError::AbortedByPeer)?;
 
-        (a, a_tags, c, c_tags)
+        info!("Trunc: check passed");
+
+        Ok(())
     }
 
-    fn check_is_zero_mod2s(x: impl GenericNativeResidue) {
-        // TODO: Error handling instead
-        assert_eq!(S::from_unsigned(x), S::ZERO);
+    /// Records the values that must be zero mod 2^s.  Party 0 also adds the other party's values,
+    /// such that the lower bits of the shares sum up to zero.
+    #[allow(clippy::too_many_arguments)]
+    fn accumulate<KSS>(
+        &mut self,
+        role: Role,
+        len: usize,
+        com_msg: &ComMsg<S>,
+        remote_com_msg: &ComMsg<S>,
+        hat_a_tags: &mut [KSS],
+        hat_c: &mut [KSS],
+        hat_c_tags: &mut [KSS],
+    ) -> Result<(), TruncationError>
+    where
+        KSS: GenericNativeResidue,
+    {
+        if remote_com_msg.hat_a_tags_mod2s.len() != len
+            || remote_com_msg.hat_c_mod2s.len() != len
+            || remote_com_msg.hat_c_tags_mod2s.len() != len
+        {
+            error!("Truncer::truncate received commitment message with wrong length");
+            return Err(TruncationError::WrongLength);
+        }
+
+        for (local, remote, hat) in [
+            (
+                &com_msg.hat_a_tags_mod2s,
+                &remote_com_msg.hat_a_tags_mod2s,
+                hat_a_tags,
+            ),
+            (&com_msg.hat_c_mod2s, &remote_com_msg.hat_c_mod2s, hat_c),
+            (
+                &com_msg.hat_c_tags_mod2s,
+                &remote_com_msg.hat_c_tags_mod2s,
+                hat_c_tags,
+            ),
+        ] {
+            for ((l, r), dst) in local.iter().zip(remote).zip(hat.iter_mut()) {
+                if role.is_p0() {
+                    *dst += KSS::from_unsigned(*r);
+                }
+                self.pending.push(*l + *r);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends `message` to the other party and receives its message at the same time.
+async fn exchange<T>(ch: &mut SubChannel<T>, message: T) -> Result<T, TruncationError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    ch.exchange(message).await.map_err(channel_error)
+}
+
+fn channel_error(err: MuxError) -> TruncationError {
+    match err {
+        MuxError::FailedToSerialize(e) | MuxError::FailedToSend(e) => {
+            TruncationError::FailedToSend(e)
+        }
+        MuxError::FailedToReceive(e) | MuxError::FailedToDeserialize(e) => {
+            TruncationError::FailedToReceive(e)
+        }
+        MuxError::Closed => TruncationError::ConnectionClosed,
     }
 }
 
@@ -195,3 +410,213 @@ where
 {
     KS::from_unsigned(x.shr_vartime(KSS::BITS - KS::BITS))
 }
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{Random, Zero};
+
+    use crate::bgv::residue::native::GenericNativeResidue;
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::MacKeyShare;
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+    use crate::mac_check_opener::MacCheckOpener;
+
+    use super::{TruncationError, Truncer};
+
+    type P = ToyPreprocK32S32;
+    type K = <P as PreprocessorParameters>::K;
+    type KS = <P as PreprocessorParameters>::KS;
+    #[allow(clippy::upper_case_acronyms)]
+    type KSS = <P as PreprocessorParameters>::KSS;
+    type S = <P as PreprocessorParameters>::S;
+
+    /// Inputs of `Truncer::truncate()` for one party, where `a` and `b` are zero.
+    struct Input {
+        wide_a: Vec<KSS>,
+        wide_a_tags: Vec<KSS>,
+        b: Vec<K>,
+        b_tags: Vec<KS>,
+        wide_c: Vec<KSS>,
+        wide_c_tags: Vec<KSS>,
+    }
+
+    /// Shares of zero, whose tags and values of `c` are multiples of `2^s`, so that the truncation
+    /// check passes.
+    fn shares_of_zero(n: usize) -> [Input; 2] {
+        let mut rng = rand::thread_rng();
+        let mut multiples = || {
+            let shares0: Vec<_> = (0..n).map(|_| KSS::random(&mut rng)).collect();
+            let shares1 = shares0
+                .iter()
+                .map(|share| KSS::from_unsigned(S::random(&mut rng)).shl_vartime(S::BITS) - *share)
+                .collect();
+            [shares0, shares1]
+        };
+        let (a_tags, c, c_tags) = (multiples(), multiples(), multiples());
+        let mut inputs =
+            a_tags
+                .into_iter()
+                .zip(c)
+                .zip(c_tags)
+                .map(|((wide_a_tags, wide_c), wide_c_tags)| Input {
+                    wide_a: vec![KSS::ZERO; n],
+                    wide_a_tags,
+                    b: vec![K::ZERO; n],
+                    b_tags: vec![KS::ZERO; n],
+                    wide_c,
+                    wide_c_tags,
+                });
+        [inputs.next().unwrap(), inputs.next().unwrap()]
+    }
+
+    async fn truncate_and_check<const PID: usize>(
+        truncer: &mut Truncer<S>,
+        opener: &mut MacCheckOpener<KS, S>,
+        input: &Input,
+    ) -> Result<(), TruncationError> {
+        truncer
+            .truncate::<K, KS, KSS, PID>(
+                &input.wide_a,
+                &input.wide_a_tags,
+                &input.b,
+                &input.b_tags,
+                &input.wide_c,
+                &input.wide_c_tags,
+            )
+            .await?;
+        truncer.batch_check::<KSS, _>(opener).await
+    }
+
+    #[tokio::test]
+    async fn corrupted_truncation_fails_batch_check() {
+        const P0_ADDR: &str = "[::1]:50171";
+        const P1_ADDR: &str = "[::1]:50172";
+        const N: usize = 10;
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let mut rng = rand::thread_rng();
+        let [mac_key0, mac_key1] = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let (truncer0, truncer1) = tokio::join!(
+            Truncer::new(&mut conn0, mac_key0.clone()),
+            Truncer::new(&mut conn1, mac_key1.clone())
+        );
+        let (opener0, opener1) = tokio::join!(
+            MacCheckOpener::<KS, S>::new(&mut conn0, mac_key0),
+            MacCheckOpener::<KS, S>::new(&mut conn1, mac_key1)
+        );
+        let (mut truncer0, mut truncer1) = (truncer0.unwrap(), truncer1.unwrap());
+        let (mut opener0, mut opener1) = (opener0.unwrap(), opener1.unwrap());
+
+        let [input0, input1] = shares_of_zero(N);
+        let (result0, result1) = tokio::join!(
+            truncate_and_check::<0>(&mut truncer0, &mut opener0, &input0),
+            truncate_and_check::<1>(&mut truncer1, &mut opener1, &input1)
+        );
+        result0.unwrap();
+        result1.unwrap();
+
+        // An error in the lower bits is only detected by the deferred check.
+        let [input0, mut input1] = shares_of_zero(N);
+        input1.wide_c[N / 2] += KSS::from_i64(1);
+        let (result0, result1) = tokio::join!(
+            truncate_and_check::<0>(&mut truncer0, &mut opener0, &input0),
+            truncate_and_check::<1>(&mut truncer1, &mut opener1, &input1)
+        );
+        assert!(matches!(result0, Err(TruncationError::CheckFailed)));
+        assert!(matches!(result1, Err(TruncationError::CheckFailed)));
+    }
+
+    #[tokio::test]
+    async fn wrong_length() {
+        const P0_ADDR: &str = "[::1]:50173";
+        const P1_ADDR: &str = "[::1]:50174";
+        const N: usize = 10;
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let mut rng = rand::thread_rng();
+        let [mac_key0, mac_key1] = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let (truncer0, truncer1) = tokio::join!(
+            Truncer::new(&mut conn0, mac_key0.clone()),
+            Truncer::new(&mut conn1, mac_key1.clone())
+        );
+        let (mut truncer0, mut truncer1) = (truncer0.unwrap(), truncer1.unwrap());
+
+        // Party 1 passes one tag too few, which both parties notice before committing.
+        let [input0, mut input1] = shares_of_zero(N);
+        input1.b_tags.pop();
+        let (result0, result1) = tokio::join!(
+            truncer0.truncate::<K, KS, KSS, 0>(
+                &input0.wide_a,
+                &input0.wide_a_tags,
+                &input0.b,
+                &input0.b_tags,
+                &input0.wide_c,
+                &input0.wide_c_tags,
+            ),
+            truncer1.truncate::<K, KS, KSS, 1>(
+                &input1.wide_a,
+                &input1.wide_a_tags,
+                &input1.b,
+                &input1.b_tags,
+                &input1.wide_c,
+                &input1.wide_c_tags,
+            )
+        );
+        assert!(matches!(result0, Err(TruncationError::AbortedByPeer)));
+        assert!(matches!(result1, Err(TruncationError::WrongLength)));
+    }
+
+    #[tokio::test]
+    async fn multiplexed() {
+        const P0_ADDR: &str = "[::1]:50175";
+        const P1_ADDR: &str = "[::1]:50176";
+        const N: usize = 10;
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let mut rng = rand::thread_rng();
+        let [mac_key0, mac_key1] = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let (truncer0, truncer1) = tokio::join!(
+            Truncer::new_multiplexed(&mut conn0, mac_key0.clone()),
+            Truncer::new_multiplexed(&mut conn1, mac_key1.clone())
+        );
+        let (opener0, opener1) = tokio::join!(
+            MacCheckOpener::<KS, S>::new(&mut conn0, mac_key0),
+            MacCheckOpener::<KS, S>::new(&mut conn1, mac_key1)
+        );
+        let (mut truncer0, mut truncer1) = (truncer0.unwrap(), truncer1.unwrap());
+        let (mut opener0, mut opener1) = (opener0.unwrap(), opener1.unwrap());
+
+        for _ in 0..3 {
+            let [input0, input1] = shares_of_zero(N);
+            let (result0, result1) = tokio::join!(
+                truncate_and_check::<0>(&mut truncer0, &mut opener0, &input0),
+                truncate_and_check::<1>(&mut truncer1, &mut opener1, &input1)
+            );
+            result0.unwrap();
+            result1.unwrap();
+        }
+
+        let [input0, mut input1] = shares_of_zero(N);
+        input1.wide_c[N / 2] += KSS::from_i64(1);
+        let (result0, result1) = tokio::join!(
+            truncate_and_check::<0>(&mut truncer0, &mut opener0, &input0),
+            truncate_and_check::<1>(&mut truncer1, &mut opener1, &input1)
+        );
+        assert!(matches!(result0, Err(TruncationError::CheckFailed)));
+        assert!(matches!(result1, Err(TruncationError::CheckFailed)));
+    }
+}
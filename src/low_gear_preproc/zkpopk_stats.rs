@@ -0,0 +1,61 @@
+//! Statistics on the aborts of the ZKPoPK.
+//!
+//! The prover aborts with a probability of roughly `1 / inv_fail_prob` per proof, in which case the
+//! proof is repeated.  A larger `ZKPOPK_INV_FAIL_PROB` makes aborts rarer, but increases the
+//! bounds (and thus the noise) of the proven ciphertexts.
+
+use std::fmt;
+
+/// Aborts of the ZKPoPKs of a session.  Both parties observe the same aborts, with `local` and
+/// `remote` swapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZkpopkStats {
+    /// Number of ZKPoPKs (of `ZKPOPK_AMORTIZE` ciphertexts per stacked batch each) that were
+    /// completed by each party.
+    pub proofs: u64,
+    /// Number of aborted responses of this party.
+    pub local_aborts: u64,
+    /// Number of aborted responses of the other party.
+    pub remote_aborts: u64,
+}
+
+impl ZkpopkStats {
+    /// Fraction of the attempts of both parties that were aborted.
+    pub fn abort_rate(&self) -> f64 {
+        let aborts = self.local_aborts + self.remote_aborts;
+        let attempts = 2 * self.proofs + aborts;
+        if attempts == 0 {
+            return 0.0;
+        }
+        aborts as f64 / attempts as f64
+    }
+}
+
+impl fmt::Display for ZkpopkStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} proofs, {} local aborts, {} remote aborts (abort rate {:.4})",
+            self.proofs,
+            self.local_aborts,
+            self.remote_aborts,
+            self.abort_rate()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZkpopkStats;
+
+    #[test]
+    fn abort_rate() {
+        let stats = ZkpopkStats {
+            proofs: 4,
+            local_aborts: 1,
+            remote_aborts: 1,
+        };
+        assert_eq!(stats.abort_rate(), 0.2);
+        assert_eq!(ZkpopkStats::default().abort_rate(), 0.0);
+    }
+}
@@ -0,0 +1,204 @@
+//! Coalescing of concurrent `single_check()`s, see `CoalescedOpener`.
+
+use std::collections::VecDeque;
+
+use futures_util::{SinkExt, StreamExt};
+use log::debug;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+use crate::interface::Share;
+
+use super::{MacCheckFailed, MacCheckOpener};
+
+struct Request<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    share: Share<KS, K, PID>,
+    result: oneshot::Sender<Result<K, MacCheckFailed>>,
+}
+
+/// A handle to a `MacCheckOpener` that runs in the background, see `MacCheckOpener::coalesce()`.
+///
+/// The checks that are pending at the same time, i.e., that were requested since the last exchange
+/// started, are coalesced into a single exchange of messages via `MacCheckOpener::check_many()`.
+/// Both parties must request the same checks in the same order.  If the parties have a different
+/// number of pending checks, the surplus is checked in the next exchange.
+pub struct CoalescedOpener<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    requests: mpsc::UnboundedSender<Request<KS, K, PID>>,
+}
+
+impl<KS, K, const PID: usize> Clone for CoalescedOpener<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+        }
+    }
+}
+
+impl<KS, K, const PID: usize> CoalescedOpener<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    /// Like `MacCheckOpener::single_check()`, but can be called concurrently.  If the check fails,
+    /// all checks of the same exchange fail.
+    pub async fn single_check(&self, share: Share<KS, K, PID>) -> Result<K, MacCheckFailed> {
+        let (tx, rx) = oneshot::channel();
+        let request = Request { share, result: tx };
+        if self.requests.send(request).is_err() {
+            return Err(MacCheckFailed {});
+        }
+        rx.await.unwrap_or(Err(MacCheckFailed {}))
+    }
+}
+
+impl<KS, S> MacCheckOpener<KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    /// Moves the opener into a background task, which coalesces the checks of the returned
+    /// `CoalescedOpener`s.  Both parties must call this at the same point, since it opens a channel
+    /// on `conn`.  The task finishes the opener once all handles are dropped.
+    pub async fn coalesce<K, const PID: usize>(
+        self,
+        conn: &mut Connection,
+    ) -> Result<CoalescedOpener<KS, K, PID>, StreamError>
+    where
+        K: GenericNativeResidue,
+    {
+        let ch_count = BiChannel::open(conn, "MacCheckOpener:count").await?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::task::spawn(run(self, ch_count, rx));
+        Ok(CoalescedOpener { requests: tx })
+    }
+}
+
+async fn run<KS, K, S, const PID: usize>(
+    mut opener: MacCheckOpener<KS, S>,
+    mut ch_count: BiChannel<usize>,
+    mut requests: mpsc::UnboundedReceiver<Request<KS, K, PID>>,
+) where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    let mut pending = VecDeque::new();
+    loop {
+        if pending.is_empty() {
+            match requests.recv().await {
+                Some(request) => pending.push_back(request),
+                None => break,
+            }
+        }
+        // Let the other callers of this tick request their checks.
+        tokio::task::yield_now().await;
+        while let Ok(request) = requests.try_recv() {
+            pending.push_back(request);
+        }
+
+        let (rx, tx) = ch_count.split();
+        let (_, remote_count) = tokio::join!(
+            async {
+                // TODO: return error instead of unwrapping.
+                tx.send(pending.len()).await.unwrap();
+            },
+            // TODO: return error instead of unwrapping.
+            async { rx.next().await.unwrap().unwrap() }
+        );
+        let count = pending.len().min(remote_count);
+        debug!("MacCheckOpener: coalescing {} checks", count);
+
+        let batch: Vec<_> = pending.drain(..count).collect();
+        let shares: Vec<_> = batch.iter().map(|request| request.share).collect();
+        match opener.check_many(&shares).await {
+            Ok(values) => {
+                for (request, value) in batch.into_iter().zip(values) {
+                    let _ = request.result.send(Ok(value));
+                }
+            }
+            Err(_) => {
+                for request in batch {
+                    let _ = request.result.send(Err(MacCheckFailed {}));
+                }
+            }
+        }
+    }
+    let _ = ch_count.writer.into_inner().finish().await;
+    opener.finish().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::Random;
+    use futures_util::future::join_all;
+
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::{MacKeyShare, Share};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+    use crate::mac_check_opener::MacCheckOpener;
+
+    type K = <ToyPreprocK32S32 as PreprocessorParameters>::K;
+    type KS = <ToyPreprocK32S32 as PreprocessorParameters>::KS;
+    type S = <ToyPreprocK32S32 as PreprocessorParameters>::S;
+
+    #[tokio::test]
+    async fn concurrent_checks() {
+        const P0_ADDR: &str = "[::1]:50099";
+        const P1_ADDR: &str = "[::1]:50100";
+        const N: usize = 16;
+
+        let mut rng = rand::thread_rng();
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let mac_key = mac_keys[0].widen::<KS>() + mac_keys[1].widen::<KS>();
+        let values: Vec<_> = (0..N).map(|_| K::random(&mut rng)).collect();
+        let mut shares0 = Vec::new();
+        let mut shares1 = Vec::new();
+        for value in &values {
+            let value = KS::from_unsigned(*value);
+            let (val0, tag0) = (KS::random(&mut rng), KS::random(&mut rng));
+            shares0.push(Share::<KS, K, 0>::new(val0, tag0));
+            shares1.push(Share::<KS, K, 1>::new(value - val0, value * mac_key - tag0));
+        }
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let [mac_key0, mac_key1] = mac_keys;
+        let (opener0, opener1) = tokio::join!(
+            MacCheckOpener::<KS, S>::new(&mut conn0, mac_key0),
+            MacCheckOpener::<KS, S>::new(&mut conn1, mac_key1)
+        );
+        let (opener0, opener1) = tokio::join!(
+            opener0.unwrap().coalesce::<K, 0>(&mut conn0),
+            opener1.unwrap().coalesce::<K, 1>(&mut conn1)
+        );
+        let (opener0, opener1) = (opener0.unwrap(), opener1.unwrap());
+
+        let (opened0, opened1) = tokio::join!(
+            join_all(shares0.iter().map(|share| opener0.single_check(*share))),
+            join_all(shares1.iter().map(|share| opener1.single_check(*share)))
+        );
+        for opened in [opened0, opened1] {
+            let opened: Vec<_> = opened.into_iter().map(Result::unwrap).collect();
+            assert_eq!(opened, values);
+        }
+    }
+}
@@ -1,24 +1,143 @@
+use futures_util::stream::{self, Stream};
 use futures_util::{SinkExt, StreamExt};
 use log::{error, info};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 
 use crate::bgv::residue::native::GenericNativeResidue;
+use crate::bgv::residue::GenericResidue;
 use crate::bi_channel::BiChannel;
+use crate::commitment::{self, Commitment, Opening};
 use crate::connection::{Connection, StreamError};
-use crate::interface::Share;
+use crate::crypto_suite::CryptoSuite;
+use crate::interface::{MacKeyShare, Share, SpdzParams};
+use crate::transcript::{self, SessionId};
+
+pub use self::coalesced::CoalescedOpener;
+
+mod coalesced;
 
 #[derive(Debug, derive_more::Display, derive_more::Error)]
 pub struct MacCheckFailed {}
 
+/// How the mask of `batch_check()` is derived from authenticated values in `K`.
+///
+/// `batch_check()` opens a random linear combination of the shares plus the mask.  The lower
+/// `K::BITS` bits of the combination follow from the opened values, but the upper bits must not be
+/// revealed.  The mask is `\sum_i 2^{s_i} r_i` for the shifts `s_i` and the masking values `r_i`.
+/// If the ranges `[s_i, s_i + K::BITS)` cover the bits from `K::BITS` to `KS::BITS`, the upper
+/// bits of the mask are uniformly random, i.e., their statistical distance to uniform is zero.
+/// Otherwise, they are not hidden at all, so such strategies are rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaskStrategy {
+    shifts: Vec<usize>,
+    k_bits: usize,
+}
+
+impl MaskStrategy {
+    /// Masks all bits of `KS` with the shifts `0, K::BITS, 2 K::BITS, ...`.  For `KS::BITS <=
+    /// 2 K::BITS`, this is the mask `m + (r << K::BITS)` of the SPDZ2k batch check.
+    pub fn tiled<KS, K>() -> Self
+    where
+        KS: GenericResidue,
+        K: GenericResidue,
+    {
+        let shifts = (0..KS::BITS).step_by(K::BITS).collect();
+        Self::with_shifts::<KS, K>(shifts).unwrap()
+    }
+
+    /// Masks only the upper bits with the shifts `K::BITS, 2 K::BITS, ...`, which needs one masking
+    /// value less than `tiled()`.
+    pub fn upper<KS, K>() -> Self
+    where
+        KS: GenericResidue,
+        K: GenericResidue,
+    {
+        let shifts = (K::BITS..KS::BITS).step_by(K::BITS).collect();
+        Self::with_shifts::<KS, K>(shifts).unwrap()
+    }
+
+    /// Uses the given shifts.  Returns `None` if the upper bits are not covered, see
+    /// `MaskStrategy`, or a shift is not less than `KS::BITS`.
+    pub fn with_shifts<KS, K>(mut shifts: Vec<usize>) -> Option<Self>
+    where
+        KS: GenericResidue,
+        K: GenericResidue,
+    {
+        shifts.sort_unstable();
+        if shifts.iter().any(|shift| *shift >= KS::BITS) {
+            return None;
+        }
+        // Walk up from `K::BITS` through the ranges of the masking values.
+        let mut covered = K::BITS;
+        for shift in &shifts {
+            if *shift > covered {
+                break;
+            }
+            covered = covered.max(shift + K::BITS);
+        }
+        (covered >= KS::BITS).then_some(Self {
+            shifts,
+            k_bits: K::BITS,
+        })
+    }
+
+    /// Number of authenticated values that `combine()` takes.
+    pub fn num_values(&self) -> usize {
+        self.shifts.len()
+    }
+
+    pub fn shifts(&self) -> &[usize] {
+        &self.shifts
+    }
+
+    /// Combines `self.num_values()` authenticated random values to the mask of `batch_check()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values` has the wrong length.
+    pub fn combine<KS, K, const PID: usize>(
+        &self,
+        values: &[Share<KS, K, PID>],
+    ) -> Share<KS, K, PID>
+    where
+        KS: GenericNativeResidue,
+        K: GenericNativeResidue,
+    {
+        debug_assert_eq!(self.k_bits, K::BITS);
+        assert_eq!(
+            values.len(),
+            self.num_values(),
+            "the mask strategy takes {} values",
+            self.num_values()
+        );
+        values
+            .iter()
+            .zip(&self.shifts)
+            .fold(Share::ZERO, |mask, (value, shift)| {
+                mask + (*value << *shift)
+            })
+    }
+}
+
+/// The `MacCheckOpener` of the rings of `P`.
+pub type OpenerOf<P> = MacCheckOpener<<P as SpdzParams>::KS, <P as SpdzParams>::S>;
+
 pub struct MacCheckOpener<KS, S>
 where
     KS: GenericNativeResidue,
     S: GenericNativeResidue,
 {
     ch_values: BiChannel<Vec<KS>>,
+    ch_commitment: BiChannel<Commitment<Vec<KS>>>,
+    ch_opening: BiChannel<Opening<Vec<KS>>>,
     ch_seed: BiChannel<[u8; 32]>,
-    mac_key: S,
+    ch_seed_commitment: BiChannel<Commitment<[u8; 32]>>,
+    ch_seed_opening: BiChannel<Opening<[u8; 32]>>,
+    mac_key: MacKeyShare<S>,
+    session_id: SessionId,
+    mask_strategy: Option<MaskStrategy>,
+    crypto_suite: CryptoSuite,
 }
 
 impl<KS, S> MacCheckOpener<KS, S>
@@ -26,13 +145,55 @@ where
     KS: GenericNativeResidue,
     S: GenericNativeResidue,
 {
-    pub async fn new(conn: &mut Connection, mac_key: S) -> Result<Self, StreamError> {
+    pub async fn new(conn: &mut Connection, mac_key: MacKeyShare<S>) -> Result<Self, StreamError> {
         Ok(Self {
             ch_values: BiChannel::open(conn, "MacCheckOpener:values").await?,
+            ch_commitment: BiChannel::open(conn, "MacCheckOpener:commitment").await?,
+            ch_opening: BiChannel::open(conn, "MacCheckOpener:opening").await?,
             ch_seed: BiChannel::open(conn, "MacCheckOpener:seed").await?,
+            ch_seed_commitment: BiChannel::open(conn, "MacCheckOpener:seed_commitment").await?,
+            ch_seed_opening: BiChannel::open(conn, "MacCheckOpener:seed_opening").await?,
             mac_key,
+            session_id: SessionId::default(),
+            mask_strategy: None,
+            crypto_suite: CryptoSuite::default(),
         })
     }
+
+    /// Binds the seeds used by `batch_check()` to the session.
+    pub fn bind_session(&mut self, session_id: SessionId) {
+        self.session_id = session_id;
+    }
+
+    /// Sets the hash function of the commitments and of the seeds of `batch_check()`.  Both
+    /// parties must use the same suite.
+    pub fn set_crypto_suite(&mut self, suite: CryptoSuite) {
+        self.crypto_suite = suite;
+    }
+
+    /// Sets the strategy that the callers of `batch_check()` use to derive the mask.  Both parties
+    /// must use the same strategy.
+    pub fn set_mask_strategy(&mut self, strategy: MaskStrategy) {
+        self.mask_strategy = Some(strategy);
+    }
+
+    /// The strategy set by `set_mask_strategy()`, or `MaskStrategy::tiled()`.
+    pub fn mask_strategy<K>(&self) -> MaskStrategy
+    where
+        K: GenericNativeResidue,
+    {
+        match &self.mask_strategy {
+            Some(strategy) => {
+                assert_eq!(
+                    strategy.k_bits,
+                    K::BITS,
+                    "the mask strategy is for another K"
+                );
+                strategy.clone()
+            }
+            None => MaskStrategy::tiled::<KS, K>(),
+        }
+    }
 }
 
 impl<KS, S> MacCheckOpener<KS, S>
@@ -40,10 +201,23 @@ where
     KS: GenericNativeResidue,
     S: GenericNativeResidue,
 {
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "mac_check", skip_all))]
     pub async fn single_check<K, const PID: usize>(
         &mut self,
         share: Share<KS, K, PID>,
     ) -> Result<K, MacCheckFailed>
+    where
+        K: GenericNativeResidue,
+    {
+        Ok(self.check_many(&[share]).await?[0])
+    }
+
+    /// Opens the shares and checks each of their MAC tags, in a single exchange of messages.  Unlike
+    /// `batch_check()`, this needs no mask, but sends one commitment value per share.
+    pub async fn check_many<K, const PID: usize>(
+        &mut self,
+        shares: &[Share<KS, K, PID>],
+    ) -> Result<Vec<K>, MacCheckFailed>
     where
         K: GenericNativeResidue,
     {
@@ -51,53 +225,155 @@ where
 
         let (_, received) = tokio::join!(
             async {
-                let mut values = Vec::new();
-                values.push(share.val);
+                let values: Vec<_> = shares.iter().map(|share| share.val).collect();
                 tx.send(values).await.unwrap();
             },
             async { rx.next().await.unwrap().unwrap() }
         );
 
-        if received.len() != 1 {
+        if received.len() != shares.len() {
             error!(
-                "MacCheckOpener::single_check expected 1 value but received {}",
+                "MacCheckOpener::check_many expected {} values but received {}",
+                shares.len(),
                 received.len()
             );
             return Err(MacCheckFailed {});
         }
 
-        let val = share.val + received[0];
-        let z = share.tag - val * KS::from_unsigned(self.mac_key);
+        let vals: Vec<_> = shares
+            .iter()
+            .zip(received)
+            .map(|(share, remote)| share.val + remote)
+            .collect();
+        let z: Vec<_> = shares
+            .iter()
+            .zip(&vals)
+            .map(|(share, val)| share.tag - *val * self.mac_key.widen::<KS>())
+            .collect();
 
-        let (_, received) = tokio::join!(
+        // Commit to `z` first, so that the other party cannot choose its `z` depending on ours.
+        let (com, opening) = commitment::commit_random(self.crypto_suite, z.clone());
+
+        let (rx_com, tx_com) = self.ch_commitment.split();
+        let (_, remote_com) = tokio::join!(
             async {
-                let mut values = Vec::new();
-                values.push(z);
-                tx.send(values).await.unwrap();
+                tx_com.send(com).await.unwrap();
             },
-            async { rx.next().await.unwrap().unwrap() }
+            async { rx_com.next().await.unwrap().unwrap() }
+        );
+
+        let (rx_opening, tx_opening) = self.ch_opening.split();
+        let (_, remote_opening) = tokio::join!(
+            async {
+                tx_opening.send(opening).await.unwrap();
+            },
+            async { rx_opening.next().await.unwrap().unwrap() }
         );
 
-        if received.len() != 1 {
+        let received = match commitment::open(self.crypto_suite, &remote_com, remote_opening) {
+            Ok(received) => received,
+            Err(_) => {
+                error!("MacCheckOpener::check_many received invalid opening");
+                return Err(MacCheckFailed {});
+            }
+        };
+
+        if received.len() != shares.len() {
             error!(
-                "MacCheckOpener::single_check expected 1 value but received {}",
+                "MacCheckOpener::check_many expected {} values but received {}",
+                shares.len(),
                 received.len()
             );
             return Err(MacCheckFailed {});
         }
 
-        let sum = z + received[0];
-
-        if sum != KS::ZERO {
-            error!("MacCheckOpener::single_check failed");
+        if z.iter()
+            .zip(received)
+            .any(|(z, remote)| *z + remote != KS::ZERO)
+        {
+            error!("MacCheckOpener::check_many failed");
             return Err(MacCheckFailed {});
         }
 
         info!("MacCheck: check passed");
 
-        Ok(K::from_unsigned(val))
+        Ok(vals.into_iter().map(K::from_unsigned).collect())
+    }
+
+    /// Opens the shares of `shares` in batches of `batch_size` via `check_many()`, and yields the
+    /// values of each batch once it is checked, so that the caller can process them while the next
+    /// batch is opened.  A batch is opened once `batch_size` shares are available or `shares` ends,
+    /// so both parties must pass the same number of shares and the same `batch_size`.  If the check
+    /// of a batch fails, the stream yields the error and ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is zero.
+    pub fn open_stream<'a, K, const PID: usize>(
+        &'a mut self,
+        shares: impl Stream<Item = Share<KS, K, PID>> + 'a,
+        batch_size: usize,
+    ) -> impl Stream<Item = Result<K, MacCheckFailed>> + 'a
+    where
+        K: GenericNativeResidue,
+    {
+        assert!(batch_size > 0, "the batch size must be positive");
+        let batches = Box::pin(shares.chunks(batch_size));
+        stream::unfold(Some((self, batches)), |state| async move {
+            let (opener, mut batches) = state?;
+            let batch = batches.next().await?;
+            match opener.check_many(&batch).await {
+                Ok(values) => Some((
+                    values.into_iter().map(Ok).collect::<Vec<_>>(),
+                    Some((opener, batches)),
+                )),
+                Err(e) => Some((vec![Err(e)], None)),
+            }
+        })
+        .flat_map(stream::iter)
+    }
+
+    /// Opens the shares without checking their MAC tags.  The caller is responsible for checking
+    /// them afterwards, e.g. via `batch_check()`.
+    pub async fn open_unchecked<K, const PID: usize>(
+        &mut self,
+        shares: &[Share<KS, K, PID>],
+    ) -> Result<Vec<K>, MacCheckFailed>
+    where
+        K: GenericNativeResidue,
+    {
+        let (rx, tx) = self.ch_values.split();
+
+        let (_, received) = tokio::join!(
+            async {
+                let values: Vec<_> = shares.iter().map(|share| share.val).collect();
+                tx.send(values).await.unwrap();
+            },
+            async { rx.next().await.unwrap().unwrap() }
+        );
+
+        if received.len() != shares.len() {
+            error!(
+                "MacCheckOpener::open_unchecked expected {} values but received {}",
+                shares.len(),
+                received.len()
+            );
+            return Err(MacCheckFailed {});
+        }
+
+        Ok(shares
+            .iter()
+            .zip(received)
+            .map(|(share, remote)| K::from_unsigned(share.val + remote))
+            .collect())
     }
 
+    /// Checks the MAC tags of `shares`, which must have been opened before.  `mask` must be fresh
+    /// and derived according to `self.mask_strategy()`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "mac_batch_check", skip_all)
+    )]
     pub async fn batch_check<K, const PID: usize>(
         &mut self,
         shares: impl Iterator<Item = Share<KS, K, PID>>,
@@ -109,6 +385,8 @@ where
         let (rx, tx) = self.ch_seed.split();
 
         let local_seed: [u8; 32] = rand::thread_rng().gen();
+        let session_id = self.session_id;
+        let suite = self.crypto_suite;
 
         tokio::join!(
             async {
@@ -116,11 +394,16 @@ where
             },
             async {
                 let remote_seed = rx.next().await.unwrap().unwrap();
-                let mut seed = local_seed.clone();
+                let mut seed = local_seed;
                 for (dst, src) in seed.iter_mut().zip(remote_seed) {
                     *dst ^= src;
                 }
-                let mut prng = ChaCha20Rng::from_seed(seed);
+                let mut prng = ChaCha20Rng::from_seed(transcript::derive_seed(
+                    suite,
+                    &session_id,
+                    "MacCheckOpener:batch_check",
+                    &seed,
+                ));
                 for share in shares {
                     // TODO: random value should be in S
                     mask += share * K::random(&mut prng);
@@ -132,7 +415,215 @@ where
         Ok(())
     }
 
+    /// Samples a PRNG jointly with the other party, e.g., for a random combination of public values.
+    /// Unlike the seeds of `batch_check()`, which are protected by the secret MAC key, each seed is
+    /// committed to first, so that neither party can choose its seed depending on the other one.
+    /// Both parties must pass the same `label`.
+    pub async fn joint_prng(&mut self, label: &str) -> Result<ChaCha20Rng, MacCheckFailed> {
+        let local_seed: [u8; 32] = rand::thread_rng().gen();
+        let (com, opening) = commitment::commit_random(self.crypto_suite, local_seed);
+
+        let (rx_com, tx_com) = self.ch_seed_commitment.split();
+        let (_, remote_com) = tokio::join!(
+            async {
+                tx_com.send(com).await.unwrap();
+            },
+            async { rx_com.next().await.unwrap().unwrap() }
+        );
+
+        let (rx_opening, tx_opening) = self.ch_seed_opening.split();
+        let (_, remote_opening) = tokio::join!(
+            async {
+                tx_opening.send(opening).await.unwrap();
+            },
+            async { rx_opening.next().await.unwrap().unwrap() }
+        );
+
+        let remote_seed = match commitment::open(self.crypto_suite, &remote_com, remote_opening) {
+            Ok(remote_seed) => remote_seed,
+            Err(_) => {
+                error!("MacCheckOpener::joint_prng received invalid opening");
+                return Err(MacCheckFailed {});
+            }
+        };
+        let mut seed = local_seed;
+        for (dst, src) in seed.iter_mut().zip(remote_seed) {
+            *dst ^= src;
+        }
+        Ok(ChaCha20Rng::from_seed(transcript::derive_seed(
+            self.crypto_suite,
+            &self.session_id,
+            label,
+            &seed,
+        )))
+    }
+
     pub async fn finish(self) {
         let _ = self.ch_values.writer.into_inner().finish().await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{Random, Zero};
+    use futures_util::{stream, StreamExt};
+
+    use crate::bgv::generic_uint::GenericUint;
+    use crate::bgv::residue::native::GenericNativeResidue;
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::{MacKeyShare, Share};
+    #[cfg(feature = "params-k128")]
+    use crate::low_gear_preproc::params::PreprocK128S64;
+    #[cfg(feature = "params-k32")]
+    use crate::low_gear_preproc::params::PreprocK32S32;
+    #[cfg(feature = "params-k64")]
+    use crate::low_gear_preproc::params::PreprocK64S64;
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+
+    use super::{MacCheckOpener, MaskStrategy};
+
+    #[test]
+    fn tiled_is_the_spdz2k_mask() {
+        type P = ToyPreprocK32S32;
+        let strategy = MaskStrategy::tiled::<
+            <P as PreprocessorParameters>::KS,
+            <P as PreprocessorParameters>::K,
+        >();
+        assert_eq!(strategy.shifts(), [0, 32]);
+    }
+
+    #[cfg(feature = "params-k64")]
+    #[test]
+    fn uncovered_bits_are_rejected() {
+        type K = <PreprocK64S64 as PreprocessorParameters>::K;
+        type KS = <PreprocK64S64 as PreprocessorParameters>::KS;
+        assert!(MaskStrategy::with_shifts::<KS, K>(vec![0]).is_none());
+        assert!(MaskStrategy::with_shifts::<KS, K>(vec![0, 128]).is_none());
+        assert!(MaskStrategy::with_shifts::<KS, K>(vec![60]).is_none());
+        assert!(MaskStrategy::with_shifts::<KS, K>(vec![64, 0]).is_some());
+        assert!(MaskStrategy::with_shifts::<KS, K>(vec![64]).is_some());
+    }
+
+    #[test]
+    fn distribution_toy_k32_s32() {
+        distribution::<ToyPreprocK32S32>();
+    }
+
+    #[cfg(feature = "params-k32")]
+    #[test]
+    fn distribution_k32_s32() {
+        distribution::<PreprocK32S32>();
+    }
+
+    #[cfg(feature = "params-k64")]
+    #[test]
+    fn distribution_k64_s64() {
+        distribution::<PreprocK64S64>();
+    }
+
+    #[cfg(feature = "params-k128")]
+    #[test]
+    fn distribution_k128_s64() {
+        distribution::<PreprocK128S64>();
+    }
+
+    /// Checks that each of the upper bits of the mask is set in about half of the samples, for
+    /// masking values that are zero in their upper bits, as the dealer outputs them.
+    fn distribution<P>()
+    where
+        P: PreprocessorParameters,
+    {
+        const SAMPLES: usize = 2000;
+
+        let mut rng = rand::thread_rng();
+        for strategy in [
+            MaskStrategy::tiled::<P::KS, P::K>(),
+            MaskStrategy::upper::<P::KS, P::K>(),
+        ] {
+            let mut counts = vec![0; P::KS::BITS];
+            for _ in 0..SAMPLES {
+                let values: Vec<Share<P::KS, P::K, 0>> = (0..strategy.num_values())
+                    .map(|_| Share::new(P::KS::from_unsigned(P::K::random(&mut rng)), P::KS::ZERO))
+                    .collect();
+                let mask = strategy.combine(&values).val;
+                for (bit, count) in counts.iter_mut().enumerate() {
+                    if mask.shr_vartime(bit).retrieve().limbs()[0].0 & 1 == 1 {
+                        *count += 1;
+                    }
+                }
+            }
+            // The deviation of each count is about 22, so this fails with negligible probability.
+            for count in &counts[P::K::BITS..] {
+                assert!((SAMPLES / 2 - 200..SAMPLES / 2 + 200).contains(count));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn open_stream() {
+        const P0_ADDR: &str = "[::1]:50139";
+        const P1_ADDR: &str = "[::1]:50140";
+        const N: usize = 8;
+        const BATCH_SIZE: usize = 3;
+        type P = ToyPreprocK32S32;
+        type K = <P as PreprocessorParameters>::K;
+        type KS = <P as PreprocessorParameters>::KS;
+        type S = <P as PreprocessorParameters>::S;
+
+        let mut rng = rand::thread_rng();
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rng));
+        let mac_key = mac_keys[0].widen::<KS>() + mac_keys[1].widen::<KS>();
+        let values: Vec<_> = (0..N).map(|_| K::random(&mut rng)).collect();
+        let mut shares0 = Vec::new();
+        let mut shares1 = Vec::new();
+        for value in &values {
+            let value = KS::from_unsigned(*value);
+            let (val0, tag0) = (KS::random(&mut rng), KS::random(&mut rng));
+            shares0.push(Share::<KS, K, 0>::new(val0, tag0));
+            shares1.push(Share::<KS, K, 1>::new(value - val0, value * mac_key - tag0));
+        }
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let [mac_key0, mac_key1] = mac_keys;
+        let (opener0, opener1) = tokio::join!(
+            MacCheckOpener::<KS, S>::new(&mut conn0, mac_key0),
+            MacCheckOpener::<KS, S>::new(&mut conn1, mac_key1)
+        );
+        let (mut opener0, mut opener1) = (opener0.unwrap(), opener1.unwrap());
+
+        let (opened0, opened1) = tokio::join!(
+            opener0
+                .open_stream(stream::iter(shares0.clone()), BATCH_SIZE)
+                .collect::<Vec<_>>(),
+            opener1
+                .open_stream(stream::iter(shares1.clone()), BATCH_SIZE)
+                .collect::<Vec<_>>()
+        );
+        for opened in [opened0, opened1] {
+            let opened: Vec<_> = opened.into_iter().map(Result::unwrap).collect();
+            assert_eq!(opened, values);
+        }
+
+        // A wrong tag in the second batch fails it, and the stream ends.
+        shares1[BATCH_SIZE] = Share::new(shares1[BATCH_SIZE].val, KS::random(&mut rng));
+        let (opened0, opened1) = tokio::join!(
+            opener0
+                .open_stream(stream::iter(shares0), BATCH_SIZE)
+                .collect::<Vec<_>>(),
+            opener1
+                .open_stream(stream::iter(shares1), BATCH_SIZE)
+                .collect::<Vec<_>>()
+        );
+        for opened in [opened0, opened1] {
+            assert_eq!(opened.len(), BATCH_SIZE + 1);
+            assert!(opened[..BATCH_SIZE].iter().all(Result::is_ok));
+            assert!(opened[BATCH_SIZE].is_err());
+        }
+    }
+}
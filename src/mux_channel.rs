@@ -0,0 +1,369 @@
+//! Several typed channels of a subprotocol, multiplexed over a single QUIC stream.
+//!
+//! In contrast to opening a `BiChannel` per channel, a `Multiplexer` opens only one stream per
+//! subprotocol, and messages are delivered in the order in which they were sent, also across
+//! channels.  Each frame carries the channel id and a sequence number, which the receiver checks.
+//!
+//! Subprotocols hold their channels as `SubChannel`s, so that they can opt into a `Multiplexer`,
+//! e.g., `Truncer::new_multiplexed()`.
+
+use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
+use async_bincode::AsyncDestination;
+use futures_util::{SinkExt, StreamExt};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch, Mutex};
+
+use crate::bi_channel::BiChannel;
+use crate::codec;
+use crate::connection::{Connection, StreamError};
+use crate::oneshot_map::OneshotMap;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Frame {
+    channel: u32,
+    seq: u64,
+    payload: Vec<u8>,
+}
+
+#[cfg(feature = "fuzzing")]
+pub(crate) fn fuzz_frame(bytes: &[u8]) {
+    use crate::bgv::{params::ToyBgv, Ciphertext};
+
+    crate::fuzzing::framed_roundtrip::<Frame>(bytes);
+    if let Ok(frame) = codec::deserialize_framed::<Frame>(bytes) {
+        let _ = codec::deserialize::<Ciphertext<ToyBgv>>(&frame.payload);
+    }
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum MuxError {
+    FailedToSerialize(bincode::ErrorKind),
+    FailedToSend(bincode::ErrorKind),
+    /// Receiving from a `SubChannel` on a stream of its own failed.
+    FailedToReceive(bincode::ErrorKind),
+    FailedToDeserialize(bincode::ErrorKind),
+    /// The stream was closed or the remote party violated the framing.
+    Closed,
+}
+
+type ChannelSenders = OneshotMap<u32, mpsc::UnboundedSender<(u64, Vec<u8>)>>;
+
+struct SendState {
+    writer: AsyncBincodeWriter<quinn::SendStream, Frame, AsyncDestination>,
+    next_seq: u64,
+}
+
+pub struct Multiplexer {
+    send_state: Arc<Mutex<SendState>>,
+    senders: Arc<ChannelSenders>,
+    closed: watch::Receiver<()>,
+    num_channels: u32,
+}
+
+impl Multiplexer {
+    pub async fn open(conn: &mut Connection, name: &str) -> Result<Self, StreamError> {
+        let (tx, rx) = conn.open_bi(name).await?;
+        let senders = Arc::new(OneshotMap::default());
+        let (closed_tx, closed) = watch::channel(());
+        tokio::task::spawn(demultiplex(
+            name.to_owned(),
+            AsyncBincodeReader::from(rx),
+            Arc::clone(&senders),
+            closed_tx,
+        ));
+        Ok(Self {
+            send_state: Arc::new(Mutex::new(SendState {
+                writer: AsyncBincodeWriter::from(tx).for_async(),
+                next_seq: 0,
+            })),
+            senders,
+            closed,
+            num_channels: 0,
+        })
+    }
+
+    /// Returns the next channel.  Both parties must create their channels in the same order.
+    pub async fn channel<Message>(&mut self) -> MuxChannel<Message> {
+        let id = self.num_channels;
+        self.num_channels += 1;
+        let (tx, rx) = mpsc::unbounded_channel();
+        // `unwrap()` cannot fail, because we never reuse IDs.
+        self.senders.send(id, tx).await.unwrap();
+        MuxChannel {
+            id,
+            send_state: Arc::clone(&self.send_state),
+            inbox: Inbox {
+                receiver: rx,
+                closed: self.closed.clone(),
+            },
+            phantom: PhantomData,
+        }
+    }
+
+    pub async fn finish(self) {
+        let _ = self.send_state.lock().await.writer.get_mut().finish().await;
+    }
+}
+
+pub struct MuxChannel<Message> {
+    id: u32,
+    send_state: Arc<Mutex<SendState>>,
+    inbox: Inbox,
+    phantom: PhantomData<Message>,
+}
+
+/// The receiving half of a `MuxChannel`.
+struct Inbox {
+    receiver: mpsc::UnboundedReceiver<(u64, Vec<u8>)>,
+    closed: watch::Receiver<()>,
+}
+
+impl<Message> MuxChannel<Message>
+where
+    Message: Serialize + DeserializeOwned,
+{
+    /// Sends `message` and returns its sequence number.
+    pub async fn send(&self, message: &Message) -> Result<u64, MuxError> {
+        send_frame(self.id, &self.send_state, message).await
+    }
+
+    pub async fn recv(&mut self) -> Result<Message, MuxError> {
+        Ok(self.recv_with_seq().await?.1)
+    }
+
+    /// Receives the next message together with its sequence number.  The sequence numbers order
+    /// the messages of all channels of the `Multiplexer` as they were sent by the remote party.
+    pub async fn recv_with_seq(&mut self) -> Result<(u64, Message), MuxError> {
+        self.inbox.recv().await
+    }
+
+    /// Sends `message` and receives the next message at the same time.
+    pub async fn exchange(&mut self, message: &Message) -> Result<Message, MuxError> {
+        let (sent, received) = tokio::join!(
+            send_frame(self.id, &self.send_state, message),
+            self.inbox.recv()
+        );
+        sent?;
+        Ok(received?.1)
+    }
+}
+
+async fn send_frame<Message>(
+    channel: u32,
+    send_state: &Mutex<SendState>,
+    message: &Message,
+) -> Result<u64, MuxError>
+where
+    Message: Serialize,
+{
+    let payload = bincode::serialize(message).map_err(|b| MuxError::FailedToSerialize(*b))?;
+    let mut send_state = send_state.lock().await;
+    let seq = send_state.next_seq;
+    send_state
+        .writer
+        .send(Frame {
+            channel,
+            seq,
+            payload,
+        })
+        .await
+        .map_err(|b| MuxError::FailedToSend(*b))?;
+    send_state.next_seq += 1;
+    Ok(seq)
+}
+
+impl Inbox {
+    async fn recv<Message>(&mut self) -> Result<(u64, Message), MuxError>
+    where
+        Message: DeserializeOwned,
+    {
+        // Frames that were demultiplexed before the stream was closed are still delivered.
+        let (seq, payload) = tokio::select! {
+            biased;
+            received = self.receiver.recv() => received.ok_or(MuxError::Closed)?,
+            _ = self.closed.changed() => return Err(MuxError::Closed),
+        };
+        let message =
+            codec::deserialize(&payload).map_err(|b| MuxError::FailedToDeserialize(*b))?;
+        Ok((seq, message))
+    }
+}
+
+/// A channel of a subprotocol, which is either a `BiChannel` on a stream of its own or a
+/// `MuxChannel` that shares the stream of a `Multiplexer` with the other channels.
+pub enum SubChannel<Message> {
+    Stream(BiChannel<Message>),
+    Mux(MuxChannel<Message>),
+}
+
+impl<Message> From<BiChannel<Message>> for SubChannel<Message> {
+    fn from(ch: BiChannel<Message>) -> Self {
+        Self::Stream(ch)
+    }
+}
+
+impl<Message> From<MuxChannel<Message>> for SubChannel<Message> {
+    fn from(ch: MuxChannel<Message>) -> Self {
+        Self::Mux(ch)
+    }
+}
+
+impl<Message> SubChannel<Message>
+where
+    Message: Serialize + DeserializeOwned,
+{
+    pub async fn send(&mut self, message: Message) -> Result<(), MuxError> {
+        match self {
+            Self::Stream(ch) => ch
+                .writer
+                .send(message)
+                .await
+                .map_err(|b| MuxError::FailedToSend(*b)),
+            Self::Mux(ch) => ch.send(&message).await.map(|_| ()),
+        }
+    }
+
+    /// Sends `message` to the other party and receives its message at the same time.
+    pub async fn exchange(&mut self, message: Message) -> Result<Message, MuxError> {
+        match self {
+            Self::Stream(ch) => {
+                let (rx, tx) = ch.split();
+                let (sent, received) = tokio::join!(tx.send(message), rx.next());
+                sent.map_err(|b| MuxError::FailedToSend(*b))?;
+                received
+                    .ok_or(MuxError::Closed)?
+                    .map_err(|b| MuxError::FailedToReceive(*b))
+            }
+            Self::Mux(ch) => ch.exchange(&message).await,
+        }
+    }
+}
+
+async fn demultiplex(
+    name: String,
+    mut reader: AsyncBincodeReader<quinn::RecvStream, Frame>,
+    channel_senders: Arc<ChannelSenders>,
+    // Dropped on return, which closes all channels, including those whose senders are still
+    // parked in `channel_senders`.
+    _closed: watch::Sender<()>,
+) {
+    let mut senders = HashMap::new();
+    let mut expected_seq = 0;
+    while let Some(frame) = reader.next().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                error!("{}: Failed to receive frame: {}", name, e);
+                return;
+            }
+        };
+        if frame.seq != expected_seq {
+            error!(
+                "{}: Received frame {} but expected frame {}",
+                name, frame.seq, expected_seq
+            );
+            return;
+        }
+        expected_seq += 1;
+        let sender = match senders.entry(frame.channel) {
+            Occupied(entry) => entry.into_mut(),
+            // Waits until the channel is created locally.
+            Vacant(entry) => match channel_senders.recv(frame.channel).await {
+                Ok(sender) => entry.insert(sender),
+                Err(e) => {
+                    error!("{}: Failed to await channel {}: {}", name, frame.channel, e);
+                    return;
+                }
+            },
+        };
+        // Messages for dropped channels are discarded.
+        let _ = sender.send((frame.seq, frame.payload));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use async_bincode::tokio::AsyncBincodeWriter;
+    use futures_util::SinkExt;
+
+    use crate::connection::Connection;
+
+    use super::{Frame, Multiplexer, MuxError};
+
+    #[tokio::test]
+    async fn cross_channel_ordering() {
+        const P0_ADDR: &str = "[::1]:50065";
+        const P1_ADDR: &str = "[::1]:50066";
+
+        tokio::try_join!(
+            tokio::task::spawn(async move {
+                run_party(P0_ADDR, P1_ADDR).await.unwrap();
+            }),
+            tokio::task::spawn(async move {
+                run_party(P1_ADDR, P0_ADDR).await.unwrap();
+            }),
+        )
+        .unwrap();
+    }
+
+    async fn run_party(local: &str, remote: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = Connection::new(local.parse().unwrap(), remote.parse().unwrap()).await?;
+        let mut mux = Multiplexer::open(&mut conn, "test:mux").await?;
+        let mut ch_numbers = mux.channel::<u64>().await;
+        let mut ch_strings = mux.channel::<String>().await;
+
+        assert_eq!(ch_numbers.send(&1).await?, 0);
+        assert_eq!(ch_strings.send(&"two".into()).await?, 1);
+        assert_eq!(ch_numbers.send(&3).await?, 2);
+
+        // Receive in a different order than sent.
+        assert_eq!(ch_strings.recv_with_seq().await?, (1, "two".into()));
+        assert_eq!(ch_numbers.recv_with_seq().await?, (0, 1));
+        assert_eq!(ch_numbers.recv_with_seq().await?, (2, 3));
+
+        mux.finish().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_unexpected_sequence_number() {
+        const P0_ADDR: &str = "[::1]:50107";
+        const P1_ADDR: &str = "[::1]:50108";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (mux, stream) = tokio::join!(
+            Multiplexer::open(&mut conn0, "test:mux"),
+            conn1.open_bi("test:mux")
+        );
+        let mut mux = mux.unwrap();
+        let mut ch = mux.channel::<u64>().await;
+
+        // The remote party skips the frame with sequence number 0.
+        let mut writer = AsyncBincodeWriter::from(stream.unwrap().0).for_async();
+        let payload = bincode::serialize(&42u64).unwrap();
+        writer
+            .send(Frame {
+                channel: 0,
+                seq: 1,
+                payload,
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(ch.recv().await, Err(MuxError::Closed)));
+        mux.finish().await;
+    }
+}
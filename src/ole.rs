@@ -0,0 +1,294 @@
+//! Oblivious linear evaluation (OLE) over the slots of the tweaked interpolation packing.
+//!
+//! The receiver inputs a vector `x` and the sender inputs a vector `a`.  The receiver obtains
+//! `y = a * x + b` (slot-wise) and the sender obtains the random vector `b`.  This is the same
+//! mechanism that `LowGearPreprocessor` uses for multiplying its shares.
+//!
+//! Note that this protocol is only secure against semi-honest adversaries, because the receiver's
+//! ciphertext is not accompanied by a ZKPoPK.
+
+use crypto_bigint::Zero;
+use futures_util::{SinkExt, StreamExt};
+use log::info;
+
+use crate::bgv::poly::crt::CrtPoly;
+use crate::bgv::poly::power::PowerPoly;
+use crate::bgv::poly::CrtContext;
+use crate::bgv::residue::vec::GenericResidueVec;
+use crate::bgv::residue::GenericResidue;
+use crate::bgv::tweaked_interpolation_packing::{
+    self, get_random_unpacked, pack, pack_mask, unpack, TIPParameters,
+};
+use crate::bgv::{self, noise, BgvParameters, Ciphertext, Cleartext, PublicKey, SecretKey};
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+use crate::low_gear_preproc::PreprocessorParameters;
+use crate::util::{zeroize, SlotUsage};
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum OleError {
+    /// The plaintext modulus is too small to hold the products of `K` values.
+    InvalidParameters,
+    FailedToOpen(StreamError),
+    FailedToUnpack,
+}
+
+/// Number of bits of drowning noise for the sender.  The receiver's ciphertext is a fresh
+/// encryption, which is multiplied by the sender's input.
+const fn drown_bits<P>() -> usize
+where
+    P: PreprocessorParameters,
+{
+    let fresh_noise_bits = noise::fresh_noise_bits::<P::BgvParams>();
+    noise::drown_bits::<P::BgvParams>(noise::product_noise_bits::<P::BgvParams>(fresh_noise_bits))
+}
+
+pub struct Ole<P>
+where
+    P: PreprocessorParameters,
+{
+    ch_ciphertext: BiChannel<Ciphertext<P::BgvParams>>,
+    ctx_cipher: CrtContext<<P::BgvParams as BgvParameters>::CiphertextParams>,
+    ctx_plain: CrtContext<P::PlaintextParams>,
+    sk: SecretKey<P::BgvParams>,
+    pk: PublicKey<P::BgvParams>,
+    remote_pk: PublicKey<P::BgvParams>,
+    slot_usage: SlotUsage,
+}
+
+impl<P> Ole<P>
+where
+    P: PreprocessorParameters,
+{
+    pub async fn new(conn: &mut Connection) -> Result<Self, OleError> {
+        validate::<P>()?;
+
+        let mut ch_init = BiChannel::open(conn, "Ole:init")
+            .await
+            .map_err(OleError::FailedToOpen)?;
+        let ch_ciphertext = BiChannel::open(conn, "Ole:ciphertext")
+            .await
+            .map_err(OleError::FailedToOpen)?;
+
+        let ctx_cipher = CrtContext::gen().await;
+        let ctx_plain = CrtContext::gen().await;
+        let sk = SecretKey::gen(&ctx_cipher).await;
+        let pk = PublicKey::gen(&ctx_cipher, &sk).await;
+
+        let (rx_init, tx_init) = ch_init.split();
+        let (_, remote_pk) = tokio::join!(
+            async {
+                // TODO: return error instead of unwrapping.
+                tx_init.send(pk.clone()).await.unwrap();
+            },
+            // TODO: return error instead of unwrapping.
+            async { rx_init.next().await.unwrap().unwrap() }
+        );
+
+        Ok(Self {
+            ch_ciphertext,
+            ctx_cipher,
+            ctx_plain,
+            sk,
+            pk,
+            remote_pk,
+            slot_usage: SlotUsage::default(),
+        })
+    }
+
+    /// Number of slots used for and discarded by `send()` and `receive()` so far.
+    pub fn slot_usage(&self) -> SlotUsage {
+        self.slot_usage
+    }
+
+    /// Runs the receiver's side with input `x` and returns `y`.  The other party must call
+    /// `send()` concurrently.  `x` may have any length; it is split into batches of `capacity()`
+    /// values.
+    pub async fn receive(&mut self, x: &[P::K]) -> Result<Vec<P::K>, OleError> {
+        let mut y = Vec::with_capacity(x.len());
+        for chunk in x.chunks(capacity::<P>()) {
+            y.extend(self.receive_batch(chunk).await?);
+            self.slot_usage.record(chunk.len(), capacity::<P>());
+        }
+        Ok(y)
+    }
+
+    /// Runs the sender's side with input `a` and returns `b`.  The other party must call
+    /// `receive()` concurrently with an input of the same length.
+    pub async fn send(&mut self, a: &[P::K]) -> Result<Vec<P::K>, OleError> {
+        let mut b = Vec::with_capacity(a.len());
+        for chunk in a.chunks(capacity::<P>()) {
+            b.extend(self.send_batch(chunk).await?);
+            self.slot_usage.record(chunk.len(), capacity::<P>());
+        }
+        Ok(b)
+    }
+
+    async fn receive_batch(&mut self, x: &[P::K]) -> Result<Vec<P::K>, OleError> {
+        let wide_x: Vec<_> = x.iter().map(|x| P::KSS::from_unsigned(*x)).collect();
+        let cipher_x = bgv::encrypt(
+            &self.ctx_cipher,
+            &self.pk,
+            &PowerPoly::from_crt(&self.ctx_plain, &pack(&wide_x)).await,
+        )
+        .await;
+
+        let (rx, tx) = self.ch_ciphertext.split();
+        // TODO: return error instead of unwrapping.
+        tx.send(cipher_x).await.unwrap();
+        let cipher_y = rx.next().await.unwrap().unwrap();
+
+        let mut plain_y = bgv::decrypt(&self.ctx_cipher, &self.sk, &cipher_y).await;
+        let mut y = unpack::<_, P::K>(&CrtPoly::from_power(&self.ctx_plain, &plain_y).await)
+            .ok_or(OleError::FailedToUnpack)?;
+        // The unused slots hold the sender's masks, which must not be reused.
+        zeroize(plain_y.coefficients.iter_mut());
+        zeroize(&mut y[x.len()..]);
+        y.truncate(x.len());
+
+        info!("OLE: received {} values", y.len());
+
+        Ok(y)
+    }
+
+    async fn send_batch(&mut self, a: &[P::K]) -> Result<Vec<P::K>, OleError> {
+        // The mask covers the full slots, so that the receiver learns nothing beyond `y`.
+        let mut unpacked_e = get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng());
+        let wide_a: Vec<_> = a.iter().map(|a| P::KSS::from_unsigned(*a)).collect();
+
+        let (rx, tx) = self.ch_ciphertext.split();
+        // TODO: return error instead of unwrapping.
+        let mut cipher_y = rx.next().await.unwrap().unwrap();
+        cipher_y *= &Cleartext::new(
+            &self.ctx_cipher,
+            &PowerPoly::from_crt(&self.ctx_plain, &pack(&wide_a)).await,
+        )
+        .await;
+        cipher_y -= &bgv::encrypt_and_drown(
+            &self.ctx_cipher,
+            &self.remote_pk,
+            &PowerPoly::from_crt(&self.ctx_plain, &pack_mask(&unpacked_e)).await,
+            drown_bits::<P>(),
+        )
+        .await;
+        // TODO: return error instead of unwrapping.
+        tx.send(cipher_y).await.unwrap();
+
+        info!("OLE: sent {} values", a.len());
+
+        // y = a * x - e, hence b = -e.
+        let b = unpacked_e
+            .iter()
+            .take(a.len())
+            .map(|e| P::K::ZERO - P::K::from_unsigned(*e))
+            .collect();
+        zeroize(&mut unpacked_e);
+        Ok(b)
+    }
+
+    pub async fn finish(self) {
+        let _ = self.ch_ciphertext.writer.into_inner().finish().await;
+    }
+}
+
+/// Returns the number of slots per ciphertext, i.e., the batch size of `send()` and `receive()`.
+pub const fn capacity<P>() -> usize
+where
+    P: PreprocessorParameters,
+{
+    tweaked_interpolation_packing::packing_capacity::<P::PlaintextParams>()
+}
+
+/// Checks that the plaintext space can hold the products of `K` values in the packed slots.
+pub fn validate<P>() -> Result<(), OleError>
+where
+    P: PreprocessorParameters,
+{
+    let delta = <P::PlaintextParams as TIPParameters>::DELTA as usize;
+    if P::PlaintextResidue::BITS < P::K::BITS + 2 * delta || P::KSS::BITS < P::K::BITS {
+        return Err(OleError::InvalidParameters);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::Random;
+
+    use crate::connection::Connection;
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+    use crate::util::SlotUsage;
+
+    use super::{capacity, validate, Ole};
+
+    type P = ToyPreprocK32S32;
+
+    #[test]
+    fn validate_params() {
+        validate::<P>().unwrap();
+    }
+
+    #[tokio::test]
+    async fn ole() {
+        run_ole(capacity::<P>(), "[::1]:50061", "[::1]:50062").await;
+    }
+
+    #[tokio::test]
+    async fn ole_padded() {
+        let n = 2 * capacity::<P>() + 3;
+        let usage = run_ole(n, "[::1]:50071", "[::1]:50072").await;
+        assert_eq!(
+            usage,
+            SlotUsage {
+                used: n as u64,
+                discarded: (capacity::<P>() - 3) as u64,
+            }
+        );
+    }
+
+    async fn run_ole(n: usize, p0_addr: &'static str, p1_addr: &'static str) -> SlotUsage {
+        let x: Vec<_> = (0..n)
+            .map(|_| <P as PreprocessorParameters>::K::random(&mut rand::thread_rng()))
+            .collect();
+        let a: Vec<_> = (0..n)
+            .map(|_| <P as PreprocessorParameters>::K::random(&mut rand::thread_rng()))
+            .collect();
+
+        let receiver = {
+            let x = x.clone();
+            tokio::task::spawn(async move {
+                let mut conn = Connection::new(p0_addr.parse().unwrap(), p1_addr.parse().unwrap())
+                    .await
+                    .unwrap();
+                let mut ole = Ole::<P>::new(&mut conn).await.unwrap();
+                let y = ole.receive(&x).await.unwrap();
+                let usage = ole.slot_usage();
+                ole.finish().await;
+                (y, usage)
+            })
+        };
+        let sender = {
+            let a = a.clone();
+            tokio::task::spawn(async move {
+                let mut conn = Connection::new(p1_addr.parse().unwrap(), p0_addr.parse().unwrap())
+                    .await
+                    .unwrap();
+                let mut ole = Ole::<P>::new(&mut conn).await.unwrap();
+                let b = ole.send(&a).await.unwrap();
+                let usage = ole.slot_usage();
+                ole.finish().await;
+                (b, usage)
+            })
+        };
+        let ((y, usage), (b, sender_usage)) = tokio::try_join!(receiver, sender).unwrap();
+
+        assert_eq!(y.len(), n);
+        assert_eq!(b.len(), n);
+        assert_eq!(usage, sender_usage);
+        for (((x, a), y), b) in x.iter().zip(&a).zip(&y).zip(&b) {
+            assert_eq!(*y, *a * *x + *b);
+        }
+        usage
+    }
+}
@@ -1,67 +1,273 @@
+//! Rendezvous of values and their receivers by key, e.g., of incoming streams and the local code
+//! that opens them.
+//!
+//! Since the keys may be chosen by the remote party, the number of entries is bounded and values
+//! that nobody claims expire.  The keys of expired values are remembered (as many as the capacity),
+//! so that a receiver that comes too late gets an error instead of waiting forever.
+
 use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
+use std::time::Duration;
 
 use tokio::sync::{oneshot, Mutex};
+use tokio::time::Instant;
 
 #[derive(Debug, derive_more::Display, derive_more::Error)]
-pub struct SendBusy {}
+pub enum SendError {
+    /// A value was already sent for this key.
+    Busy,
+    /// The map has reached its capacity.
+    Full,
+    /// The receiver that waited for this key was dropped.
+    Dropped,
+}
 
 #[derive(Debug, derive_more::Display, derive_more::Error)]
-pub struct RecvBusy {}
+pub enum RecvError {
+    /// Another receiver already waits for this key.
+    Busy,
+    /// The map has reached its capacity.
+    Full,
+    /// The sender was dropped without sending.
+    Dropped,
+    /// The value was dropped because nobody received it within the TTL.
+    Expired,
+}
+
+/// Limits of a `OneshotMap`.  `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OneshotMapLimits {
+    /// Maximum number of entries, i.e., of unclaimed values and of waiting receivers.
+    pub capacity: Option<usize>,
+    /// Time after which an unclaimed value is dropped.
+    pub ttl: Option<Duration>,
+}
+
+/// Number of entries of a `OneshotMap` and of values that it discarded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OneshotMapStats {
+    /// Values that were sent but not yet received.
+    pub unclaimed: usize,
+    /// Receivers that wait for a value.
+    pub waiting: usize,
+    /// Values that were dropped because nobody received them within the TTL.
+    pub expired: u64,
+    /// Calls of `send()` and `recv()` that failed because the map was full.
+    pub rejected: u64,
+}
 
 pub struct OneshotMap<K, V> {
-    inner: Mutex<HashMap<K, Inner<V>>>,
+    inner: Mutex<State<K, V>>,
+    limits: OneshotMapLimits,
+}
+
+struct State<K, V> {
+    entries: HashMap<K, Inner<V>>,
+    /// Keys of expired values, oldest first.
+    expired_keys: VecDeque<K>,
+    stats: OneshotMapStats,
 }
 
 enum Inner<V> {
     Sender(oneshot::Sender<V>),
-    Receiver(oneshot::Receiver<V>),
+    Receiver(oneshot::Receiver<V>, Instant),
+}
+
+impl<K: Clone + Eq + Hash, V> State<K, V> {
+    /// Removes expired values and receivers that were dropped while waiting.
+    fn prune(&mut self, limits: &OneshotMapLimits) {
+        self.entries
+            .retain(|_, inner| !matches!(inner, Inner::Sender(tx) if tx.is_closed()));
+        let Some(ttl) = limits.ttl else {
+            return;
+        };
+        let now = Instant::now();
+        let expired: Vec<K> = self
+            .entries
+            .iter()
+            .filter(|(_, inner)| {
+                matches!(inner, Inner::Receiver(_, sent_at) if now.duration_since(*sent_at) >= ttl)
+            })
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in expired {
+            self.entries.remove(&k);
+            self.stats.expired += 1;
+            if limits
+                .capacity
+                .is_some_and(|capacity| self.expired_keys.len() >= capacity)
+            {
+                self.expired_keys.pop_front();
+            }
+            self.expired_keys.push_back(k);
+        }
+    }
+
+    /// Forgets that the value of `k` expired, and returns whether it did.
+    fn take_expired(&mut self, k: &K) -> bool {
+        let position = self.expired_keys.iter().position(|key| key == k);
+        if let Some(position) = position {
+            self.expired_keys.remove(position);
+        }
+        position.is_some()
+    }
+
+    fn is_full(&mut self, capacity: Option<usize>) -> bool {
+        let is_full = capacity.is_some_and(|capacity| self.entries.len() >= capacity);
+        self.stats.rejected += is_full as u64;
+        is_full
+    }
 }
 
-impl<K: Eq + Hash, V> OneshotMap<K, V> {
-    pub async fn send(&self, k: K, v: V) -> Result<(), SendBusy> {
-        let tx = match self.inner.lock().await.entry(k) {
-            Occupied(entry) => match entry.get() {
-                Inner::Sender(_) => match entry.remove_entry().1 {
-                    Inner::Sender(tx) => tx,
-                    _ => panic!(),
+impl<K: Clone + Eq + Hash, V> OneshotMap<K, V> {
+    pub fn with_limits(limits: OneshotMapLimits) -> Self {
+        Self {
+            inner: Mutex::new(State {
+                entries: HashMap::new(),
+                expired_keys: VecDeque::new(),
+                stats: OneshotMapStats::default(),
+            }),
+            limits,
+        }
+    }
+
+    pub async fn send(&self, k: K, v: V) -> Result<(), SendError> {
+        let tx = {
+            let mut state = self.inner.lock().await;
+            state.prune(&self.limits);
+            // A new value replaces the expired one.
+            state.take_expired(&k);
+            if !state.entries.contains_key(&k) && state.is_full(self.limits.capacity) {
+                return Err(SendError::Full);
+            }
+            match state.entries.entry(k) {
+                Occupied(entry) => match entry.get() {
+                    Inner::Sender(_) => match entry.remove() {
+                        Inner::Sender(tx) => tx,
+                        Inner::Receiver(..) => unreachable!(),
+                    },
+                    Inner::Receiver(..) => return Err(SendError::Busy),
                 },
-                Inner::Receiver(_) => return Err(SendBusy {}),
-            },
-            Vacant(entry) => {
-                let (tx, rx) = oneshot::channel();
-                entry.insert(Inner::Receiver(rx));
-                tx
+                Vacant(entry) => {
+                    let (tx, rx) = oneshot::channel();
+                    entry.insert(Inner::Receiver(rx, Instant::now()));
+                    tx
+                }
             }
         };
-        tx.send(v).ok().expect("Failed to send value");
-        Ok(())
+        tx.send(v).map_err(|_| SendError::Dropped)
     }
 
-    pub async fn recv(&self, k: K) -> Result<V, RecvBusy> {
-        let rx = match self.inner.lock().await.entry(k) {
-            Occupied(entry) => match entry.get() {
-                Inner::Sender(_) => return Err(RecvBusy {}),
-                Inner::Receiver(_) => match entry.remove_entry().1 {
-                    Inner::Receiver(rx) => rx,
-                    _ => panic!(),
+    /// Waits for the value of `k`.  Fails if it expired before, in which case the next call waits
+    /// for a new value.
+    pub async fn recv(&self, k: K) -> Result<V, RecvError> {
+        let rx = {
+            let mut state = self.inner.lock().await;
+            state.prune(&self.limits);
+            if state.take_expired(&k) {
+                return Err(RecvError::Expired);
+            }
+            if !state.entries.contains_key(&k) && state.is_full(self.limits.capacity) {
+                return Err(RecvError::Full);
+            }
+            match state.entries.entry(k) {
+                Occupied(entry) => match entry.get() {
+                    Inner::Sender(_) => return Err(RecvError::Busy),
+                    Inner::Receiver(..) => match entry.remove() {
+                        Inner::Receiver(rx, _) => rx,
+                        Inner::Sender(_) => unreachable!(),
+                    },
                 },
-            },
-            Vacant(entry) => {
-                let (tx, rx) = oneshot::channel();
-                entry.insert(Inner::Sender(tx));
-                rx
+                Vacant(entry) => {
+                    let (tx, rx) = oneshot::channel();
+                    entry.insert(Inner::Sender(tx));
+                    rx
+                }
             }
         };
-        Ok(rx.await.expect("Failed to receive value"))
+        rx.await.map_err(|_| RecvError::Dropped)
+    }
+
+    pub async fn stats(&self) -> OneshotMapStats {
+        let mut state = self.inner.lock().await;
+        state.prune(&self.limits);
+        let waiting = state
+            .entries
+            .values()
+            .filter(|inner| matches!(inner, Inner::Sender(_)))
+            .count();
+        OneshotMapStats {
+            unclaimed: state.entries.len() - waiting,
+            waiting,
+            ..state.stats
+        }
     }
 }
 
-impl<K, V> Default for OneshotMap<K, V> {
+impl<K: Clone + Eq + Hash, V> Default for OneshotMap<K, V> {
     fn default() -> Self {
-        Self {
-            inner: Default::default(),
-        }
+        Self::with_limits(OneshotMapLimits::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{OneshotMap, OneshotMapLimits, RecvError, SendError};
+
+    #[tokio::test]
+    async fn capacity() {
+        let map = OneshotMap::with_limits(OneshotMapLimits {
+            capacity: Some(2),
+            ttl: None,
+        });
+        map.send(0, 'a').await.unwrap();
+        map.send(1, 'b').await.unwrap();
+        assert!(matches!(map.send(2, 'c').await, Err(SendError::Full)));
+        assert!(matches!(map.recv(2).await, Err(RecvError::Full)));
+        assert!(matches!(map.send(1, 'd').await, Err(SendError::Busy)));
+        assert_eq!(map.recv(1).await.unwrap(), 'b');
+        map.send(2, 'c').await.unwrap();
+
+        let stats = map.stats().await;
+        assert_eq!((stats.unclaimed, stats.waiting, stats.rejected), (2, 0, 2));
+    }
+
+    #[tokio::test]
+    async fn ttl() {
+        let map = OneshotMap::with_limits(OneshotMapLimits {
+            capacity: None,
+            ttl: Some(Duration::from_millis(20)),
+        });
+        map.send(0, 'a').await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(map.stats().await.expired, 1);
+        assert_eq!(map.stats().await.unclaimed, 0);
+        assert!(matches!(map.recv(0).await, Err(RecvError::Expired)));
+
+        // Only the expiry of the last value is reported, and waiting receivers do not expire.
+        let map = Arc::new(map);
+        let waiting = tokio::spawn({
+            let map = Arc::clone(&map);
+            async move { map.recv(0).await }
+        });
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(map.stats().await.waiting, 1);
+        map.send(0, 'b').await.unwrap();
+        assert_eq!(waiting.await.unwrap().unwrap(), 'b');
+    }
+
+    #[tokio::test]
+    async fn dropped_receiver() {
+        let map = OneshotMap::default();
+        assert!(tokio::time::timeout(Duration::from_millis(10), map.recv(0))
+            .await
+            .is_err());
+        // The entry of the dropped receiver is removed, so the value waits for a new receiver.
+        map.send(0, 'a').await.unwrap();
+        assert_eq!(map.recv(0).await.unwrap(), 'a');
     }
 }
@@ -0,0 +1,290 @@
+//! Runs the preprocessing of one party end-to-end and reports its throughput.
+//!
+//! This is what the `low_gear` example and the benchmarks use, but it can be embedded by
+//! downstream users as well.  Both parties must call `run_preprocessing()` with matching
+//! configurations.
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Write};
+use std::net::AddrParseError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::info;
+use tokio::task::JoinError;
+
+use crate::connection::{Connection, ConnectionError, RetryPolicy, StreamError};
+use crate::context_set::ContextSet;
+use crate::heartbeat::{self, ConnectionHealth, HealthSnapshot, HeartbeatConfig};
+use crate::interface::BatchedPreprocessor;
+use crate::low_gear_preproc::memory::{self, MemoryCapExceeded};
+use crate::low_gear_preproc::{
+    self, BatchId, InitError, LowGearPreprocessor, PreprocessorError, PreprocessorParameters,
+};
+use crate::triple_audit::{AuditStats, TripleAuditor};
+use crate::util::resolve_host;
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum RunError {
+    MemoryCapExceeded(MemoryCapExceeded),
+    InvalidListenAddr(AddrParseError),
+    FailedToResolve(io::Error),
+    FailedToConnect(ConnectionError),
+    FailedToOpen(StreamError),
+    FailedToSetUp(InitError),
+    /// Writing to `Config::ready` failed.
+    FailedToAnnounce(io::Error),
+    #[display(fmt = "batch {} failed: {}", batch, error)]
+    PreprocessingFailed {
+        batch: BatchId,
+        #[error(source)]
+        error: PreprocessorError,
+    },
+    TaskFailed(JoinError),
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Address to listen on for the other party's connection.
+    pub local_addr: String,
+    /// Address (or `hostname:port`) of the other party.
+    pub remote_addr: String,
+    /// Number of worker threads of the runtime that runs the preprocessing.
+    pub num_threads: usize,
+    /// Number of batches, which are generated concurrently.
+    pub num_batches: usize,
+    /// If given, then the connection attempts are repeated until the other party is reachable, so
+    /// the two parties can be started in any order.
+    pub retry: Option<RetryPolicy>,
+    /// If given, then a JSON line announcing readiness is written to it once connected, e.g., for
+    /// a launcher that waits for both parties.  It is logged in any case.
+    pub ready: Option<ReadyWriter>,
+    /// If given, then the run fails before connecting if the estimated memory usage of all
+    /// batches exceeds this number of bytes.
+    pub memory_cap: Option<usize>,
+    /// If given, then this fraction of the triples of each batch is opened and checked, see
+    /// `triple_audit`.  The audited triples are lost, so this should only be used for testing a
+    /// deployment.
+    pub audit_fraction: Option<f64>,
+    /// If given, then the parties exchange heartbeats during the run, see `heartbeat`.
+    pub heartbeat: Option<HeartbeatConfig>,
+}
+
+/// Destination of the JSON line of `Config::ready`.
+#[derive(Clone)]
+pub struct ReadyWriter(Arc<Mutex<dyn Write + Send>>);
+
+impl ReadyWriter {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(writer)))
+    }
+
+    pub fn stderr() -> Self {
+        Self::new(io::stderr())
+    }
+
+    fn announce(&self, event: &serde_json::Value) -> io::Result<()> {
+        let mut writer = self.0.lock().unwrap();
+        writeln!(writer, "{}", event)?;
+        writer.flush()
+    }
+}
+
+impl Debug for ReadyWriter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ReadyWriter")
+    }
+}
+
+/// Wall-clock time spent in each phase.  The batches run concurrently within each phase.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhaseStats {
+    /// Establishing the connection to the other party.
+    pub connect: Duration,
+    /// Setting up the preprocessors, including key generation.
+    pub setup: Duration,
+    /// Generating the triples.
+    pub generation: Duration,
+    /// Closing the channels.
+    pub finish: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RunReport {
+    pub num_triples: usize,
+    /// Time spent generating the triples, i.e., `phases.generation`.
+    pub elapsed: Duration,
+    pub phases: PhaseStats,
+    /// Results of the audit, if `Config::audit_fraction` was given.
+    pub audit: Option<AuditStats>,
+    /// Liveness of the other party at the end of the run, if `Config::heartbeat` was given.
+    pub health: Option<HealthSnapshot>,
+}
+
+impl RunReport {
+    pub fn triples_per_sec(&self) -> f64 {
+        self.num_triples as f64 * 1_000_000_000f64 / self.elapsed.as_nanos() as f64
+    }
+}
+
+pub async fn run_preprocessing<P, const PID: usize>(config: Config) -> Result<RunReport, RunError>
+where
+    P: PreprocessorParameters,
+{
+    if let Some(cap) = config.memory_cap {
+        memory::check_memory_cap::<P>(config.num_batches, cap)
+            .map_err(RunError::MemoryCapExceeded)?;
+    }
+
+    let local_addr = config
+        .local_addr
+        .parse()
+        .map_err(RunError::InvalidListenAddr)?;
+    let remote_addr = resolve_host(&config.remote_addr).map_err(RunError::FailedToResolve)?;
+    let num_batches = config.num_batches;
+    let audit_fraction = config.audit_fraction;
+
+    let mut phases = PhaseStats::default();
+
+    let now = Instant::now();
+    let mut conn = match config.retry {
+        None => Connection::new(local_addr, remote_addr).await,
+        Some(retry) => Connection::with_retry(local_addr, remote_addr, retry).await,
+    }
+    .map_err(RunError::FailedToConnect)?;
+    phases.connect = now.elapsed();
+    let ready = serde_json::json!({
+        "event": "ready",
+        "player": PID,
+        "listen_addr": local_addr.to_string(),
+        "remote_addr": remote_addr.to_string(),
+    });
+    info!("{}", ready);
+    if let Some(writer) = &config.ready {
+        writer
+            .announce(&ready)
+            .map_err(RunError::FailedToAnnounce)?;
+    }
+
+    // The heartbeats run on this runtime, so they are not delayed by the computations.
+    let health: Option<ConnectionHealth> = match config.heartbeat {
+        Some(heartbeat) => Some(
+            heartbeat::start(&mut conn.fork(), heartbeat)
+                .await
+                .map_err(RunError::FailedToOpen)?,
+        ),
+        None => None,
+    };
+
+    let report = tokio::task::spawn_blocking(move || {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(config.num_threads)
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let now = Instant::now();
+                let mut conns = Vec::new();
+                for _ in 0..num_batches {
+                    conns.push(conn.fork());
+                }
+                // The batches use the same parameters, so they share the `CrtContext`s.
+                let contexts = Arc::new(ContextSet::default());
+                let preprocs = futures_util::future::join_all(conns.into_iter().map(|mut conn| {
+                    let contexts = Arc::clone(&contexts);
+                    tokio::task::spawn(async move {
+                        let preproc =
+                            LowGearPreprocessor::<P, PID>::with_contexts(&mut conn, &contexts)
+                                .await
+                                .map_err(RunError::FailedToSetUp)?;
+                        let auditor = match audit_fraction {
+                            Some(_) => Some(
+                                TripleAuditor::new(&mut conn.fork(), preproc.mac_key().clone())
+                                    .await
+                                    .map_err(RunError::FailedToOpen)?,
+                            ),
+                            None => None,
+                        };
+                        Ok::<_, RunError>((preproc, auditor))
+                    })
+                }))
+                .await
+                .into_iter()
+                .map(|preproc| preproc.map_err(RunError::TaskFailed)?)
+                .collect::<Result<Vec<_>, _>>()?;
+                phases.setup = now.elapsed();
+
+                let now = Instant::now();
+                let preprocs = futures_util::future::join_all(preprocs.into_iter().map(
+                    |(mut preproc, mut auditor)| {
+                        tokio::task::spawn(async move {
+                            let batch = preproc.batch_id();
+                            let triples = preproc
+                                .try_get_beaver_triples()
+                                .await
+                                .map_err(|error| RunError::PreprocessingFailed { batch, error })?;
+                            if let (Some(auditor), Some(fraction)) = (&mut auditor, audit_fraction)
+                            {
+                                // One more triple is consumed as mask for the MAC check.
+                                let n = (triples.len() as f64 * fraction).ceil() as usize + 1;
+                                let n = n.min(triples.len());
+                                auditor.audit(&triples[..n]).await;
+                                auditor.record_delivered(triples.len() - n);
+                            }
+                            Ok::<_, RunError>((preproc, auditor))
+                        })
+                    },
+                ))
+                .await
+                .into_iter()
+                .map(|preproc| preproc.map_err(RunError::TaskFailed)?)
+                .collect::<Result<Vec<_>, _>>()?;
+                phases.generation = now.elapsed();
+
+                let audit = audit_fraction.map(|_| {
+                    let mut total = AuditStats::default();
+                    for auditor in preprocs.iter().filter_map(|(_, auditor)| auditor.as_ref()) {
+                        total += auditor.stats();
+                    }
+                    info!("Audit: {}", total);
+                    total
+                });
+                let report = RunReport {
+                    num_triples: low_gear_preproc::batch_size::<P>() * num_batches,
+                    elapsed: phases.generation,
+                    phases,
+                    audit,
+                    health: None,
+                };
+                info!(
+                    "{} triples/s (produced {} triples in {} ms)",
+                    report.triples_per_sec(),
+                    report.num_triples,
+                    report.elapsed.as_millis()
+                );
+
+                let now = Instant::now();
+                for (preproc, auditor) in preprocs.into_iter() {
+                    preproc.finish().await;
+                    if let Some(auditor) = auditor {
+                        auditor.finish().await;
+                    }
+                }
+
+                Ok::<_, RunError>(RunReport {
+                    phases: PhaseStats {
+                        finish: now.elapsed(),
+                        ..report.phases
+                    },
+                    ..report
+                })
+            })
+    })
+    .await
+    .map_err(RunError::TaskFailed)??;
+
+    Ok(RunReport {
+        health: health.as_ref().map(ConnectionHealth::snapshot),
+        ..report
+    })
+}
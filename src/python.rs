@@ -0,0 +1,133 @@
+//! Python bindings for the triple generation (feature `python`).
+//!
+//! ```python
+//! import multipars
+//!
+//! preproc = multipars.PyPreprocessor.connect(0, "[::1]:50051", "[::1]:50052", k=64, s=64)
+//! triples = preproc.get_triples(1000)  # numpy.ndarray of shape (1000, 3, 2, limbs)
+//! preproc.finish()
+//! ```
+//!
+//! The axes of the array returned by `get_triples()` are the triple, the share (a, b, c), the
+//! component (value, MAC tag), and the little-endian 64-bit limbs of the residue modulo `2^(k+s)`.
+//! `get_raw_triples()` returns triples without MAC tags, whose shares are reduced modulo `2^k`.
+
+use numpy::ndarray::{Array3, Array4};
+use numpy::{IntoPyArray, PyArray1, PyArray3, PyArray4};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use tokio::runtime::Runtime;
+
+use crate::session::{self, LimbPreprocessor, SessionError};
+
+/// Generates triples in a background runtime and hands them out as numpy arrays.
+#[pyclass]
+pub struct PyPreprocessor {
+    runtime: Runtime,
+    inner: Option<Box<dyn LimbPreprocessor>>,
+}
+
+#[pymethods]
+impl PyPreprocessor {
+    /// Connects to the other party and sets up the preprocessing.  Both parties must pass the same
+    /// `k`, `s` and `toy`.  `budget` is the number of triples that are generated ahead of time, and
+    /// `threads` (at least 1) is the number of worker threads.  Invalid arguments raise a
+    /// `ValueError`.
+    #[staticmethod]
+    #[pyo3(signature = (player, local_addr, remote_addr, k = 32, s = 32, toy = false, budget = 0, threads = 1))]
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        py: Python<'_>,
+        player: usize,
+        local_addr: &str,
+        remote_addr: &str,
+        k: usize,
+        s: usize,
+        toy: bool,
+        budget: usize,
+        threads: usize,
+    ) -> PyResult<Self> {
+        let (runtime, inner) = py
+            .allow_threads(|| {
+                session::connect(player, local_addr, remote_addr, k, s, toy, budget, threads)
+            })
+            .map_err(|err| match err {
+                SessionError::InvalidPlayer
+                | SessionError::InvalidThreads
+                | SessionError::UnsupportedParameters => PyValueError::new_err(err.to_string()),
+                _ => PyRuntimeError::new_err(err.to_string()),
+            })?;
+        Ok(Self {
+            runtime,
+            inner: Some(inner),
+        })
+    }
+
+    /// Returns `n` triples as an array of shape `(n, 3, 2, limbs)` with dtype `uint64`.
+    fn get_triples<'py>(&mut self, py: Python<'py>, n: usize) -> PyResult<&'py PyArray4<u64>> {
+        let inner = self.inner.as_mut().ok_or_else(finished)?;
+        let limbs = inner.limbs();
+        let runtime = &self.runtime;
+        let mut flat = vec![0; flat_len(n, 6, limbs)?];
+        py.allow_threads(|| runtime.block_on(inner.get_triples(n, &mut flat)));
+        let array = Array4::from_shape_vec((n, 3, 2, limbs), flat).unwrap();
+        Ok(array.into_pyarray(py))
+    }
+
+    /// Returns `n` triples without MAC tags as an array of shape `(n, 3, raw_limbs)` with dtype
+    /// `uint64`.  If `verify` is set, `2 n + 1` triples are consumed to check them first.  Both
+    /// parties must pass the same `n` and `verify`.
+    #[pyo3(signature = (n, verify = false))]
+    fn get_raw_triples<'py>(
+        &mut self,
+        py: Python<'py>,
+        n: usize,
+        verify: bool,
+    ) -> PyResult<&'py PyArray3<u64>> {
+        let inner = self.inner.as_mut().ok_or_else(finished)?;
+        let limbs = inner.raw_limbs();
+        let runtime = &self.runtime;
+        let mut flat = vec![0; flat_len(n, 3, limbs)?];
+        py.allow_threads(|| runtime.block_on(inner.get_raw_triples(n, verify, &mut flat)))
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        let array = Array3::from_shape_vec((n, 3, limbs), flat).unwrap();
+        Ok(array.into_pyarray(py))
+    }
+
+    /// Returns the limbs of this party's share of the MAC key.
+    fn mac_key_share<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray1<u64>> {
+        let inner = self.inner.as_ref().ok_or_else(finished)?;
+        Ok(PyArray1::from_slice(py, inner.mac_key()))
+    }
+
+    /// Returns the number of triples per packed plaintext.  Requesting multiples of it avoids
+    /// discarding slots.
+    fn packing_capacity(&self) -> PyResult<usize> {
+        let inner = self.inner.as_ref().ok_or_else(finished)?;
+        Ok(inner.param_info().packing_capacity)
+    }
+
+    /// Closes the channels to the other party.  Afterwards, no more triples can be obtained.
+    fn finish(&mut self, py: Python<'_>) {
+        if let Some(inner) = self.inner.take() {
+            let runtime = &self.runtime;
+            py.allow_threads(|| runtime.block_on(inner.finish()));
+        }
+    }
+}
+
+fn flat_len(n: usize, factor: usize, limbs: usize) -> PyResult<usize> {
+    n.checked_mul(factor)
+        .and_then(|len| len.checked_mul(limbs))
+        .ok_or_else(|| PyValueError::new_err("n is too large"))
+}
+
+fn finished() -> PyErr {
+    PyRuntimeError::new_err("preprocessor is finished")
+}
+
+#[pymodule]
+fn multipars(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyPreprocessor>()?;
+    Ok(())
+}
@@ -0,0 +1,225 @@
+//! Throttling of bulk messages so that they do not saturate a constrained link.
+//!
+//! The preprocessing sends large ciphertexts, which can starve small control messages (e.g., of the
+//! MAC check) that share the same connection.  A `RateLimiter` bounds both the number of messages
+//! in flight and the average bandwidth.  It is shared via `Arc`, so that its limits can be changed
+//! at runtime, e.g., while the preprocessor is running in the background of a
+//! `BufferedPreprocessor`.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Limits of a `RateLimiter`.  `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Maximum number of messages that were sent but not yet answered by the other party.  A
+    /// value of zero is treated as one.
+    pub max_in_flight: Option<usize>,
+    /// Maximum average number of bytes per second.  Bursts of up to one second worth of bytes are
+    /// allowed.
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl RateLimit {
+    /// No limits at all.
+    pub const UNLIMITED: Self = Self {
+        max_in_flight: None,
+        bytes_per_sec: None,
+    };
+}
+
+struct State {
+    limit: RateLimit,
+    /// Acquired minus released messages.  It is negative if the answer of the other party arrived
+    /// before the matching `acquire()`, which then takes the credit instead of a new slot.
+    in_flight: isize,
+    /// Available bytes of the token bucket, which may become negative after a large message.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl State {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.limit.bytes_per_sec {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.last_refill = now;
+    }
+
+    /// Takes a slot and `bytes` tokens if possible.  Otherwise, returns how long to wait for
+    /// enough tokens, or `None` if a slot has to be released first.
+    fn try_acquire(&mut self, bytes: usize) -> Result<(), Option<Duration>> {
+        if let Some(max_in_flight) = self.limit.max_in_flight {
+            if self.in_flight >= max_in_flight.max(1) as isize {
+                return Err(None);
+            }
+        }
+        if let Some(rate) = self.limit.bytes_per_sec {
+            // Messages larger than the bucket only need a full bucket and leave a debt.
+            let required = (bytes as f64).min(rate as f64);
+            if self.tokens < required {
+                let secs = (required - self.tokens) / (rate as f64).max(1.0);
+                return Err(Some(Duration::from_secs_f64(secs)));
+            }
+            self.tokens -= bytes as f64;
+        }
+        self.in_flight += 1;
+        Ok(())
+    }
+}
+
+/// Token bucket for the bandwidth combined with a window for the messages in flight.
+///
+/// Before sending a message, `acquire()` waits until the limits allow it.  When the corresponding
+/// message of the other party has been received, `release()` frees its slot in the window.
+pub struct RateLimiter {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            state: Mutex::new(State {
+                limit,
+                in_flight: 0,
+                tokens: limit.bytes_per_sec.unwrap_or(0) as f64,
+                last_refill: Instant::now(),
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// The current limits.
+    pub fn limit(&self) -> RateLimit {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Changes the limits.  Waiting calls of `acquire()` are re-evaluated with the new limits.
+    pub fn set_limit(&self, limit: RateLimit) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.refill();
+            if let Some(rate) = limit.bytes_per_sec {
+                state.tokens = state.tokens.min(rate as f64);
+            }
+            state.limit = limit;
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// Number of messages that were acquired but not yet released.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight.max(0) as usize
+    }
+
+    /// Waits until a message of `bytes` bytes may be sent and counts it as in flight.
+    pub async fn acquire(&self, bytes: usize) {
+        loop {
+            // Register for notifications before checking the state, so none is missed.
+            let notified = self.notify.notified();
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                state.refill();
+                match state.try_acquire(bytes) {
+                    Ok(()) => return,
+                    Err(wait) => wait,
+                }
+            };
+            match wait {
+                Some(duration) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(duration) => {}
+                        _ = notified => {}
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Marks one message as answered, which frees its slot in the window.  Since both parties
+    /// send concurrently, the answer may arrive before the message is acquired, in which case the
+    /// next `acquire()` is matched with this release.
+    pub fn release(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.in_flight -= 1;
+        }
+        self.notify.notify_waiters();
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimit::UNLIMITED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::time::{timeout, Instant};
+
+    use super::{RateLimit, RateLimiter};
+
+    #[tokio::test]
+    async fn bytes_per_sec() {
+        let limiter = RateLimiter::new(RateLimit {
+            max_in_flight: None,
+            bytes_per_sec: Some(10_000),
+        });
+        let start = Instant::now();
+        limiter.acquire(10_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        limiter.acquire(2_000).await;
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn max_in_flight() {
+        let limiter = Arc::new(RateLimiter::new(RateLimit {
+            max_in_flight: Some(1),
+            bytes_per_sec: None,
+        }));
+        limiter.acquire(1).await;
+        assert!(timeout(Duration::from_millis(50), limiter.acquire(1))
+            .await
+            .is_err());
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(1).await }
+        });
+        limiter.release();
+        waiting.await.unwrap();
+        assert_eq!(limiter.in_flight(), 1);
+
+        limiter.set_limit(RateLimit::UNLIMITED);
+        limiter.acquire(1).await;
+        assert_eq!(limiter.in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn release_before_acquire() {
+        let limiter = RateLimiter::new(RateLimit {
+            max_in_flight: Some(1),
+            bytes_per_sec: None,
+        });
+        // The answer arrives before the message is acquired, so the slot is not lost.
+        limiter.release();
+        limiter.acquire(1).await;
+        assert_eq!(limiter.in_flight(), 0);
+        limiter.acquire(1).await;
+        assert_eq!(limiter.in_flight(), 1);
+        limiter.release();
+        assert_eq!(limiter.in_flight(), 0);
+    }
+}
@@ -0,0 +1,38 @@
+//! Sources of randomness.
+//!
+//! All randomness of the protocols is security-critical unless stated otherwise, and must come
+//! from a cryptographically secure RNG.  In particular:
+//!
+//! - the secret keys and the `a` part of the public keys of BGV (`SecretKey::gen_with_rng()`,
+//!   `PublicKey::gen_with_rng()`),
+//! - the encryption noise `v`, `e_0`, `e_1` and the drowning noise (`EncryptionWitness`,
+//!   `encrypt_batch_with_rng()`), whose leakage reveals the plaintexts,
+//! - the seed of the ZKPoPK pseudo-inputs (`Prover::with_seed_from()`), whose leakage reveals the
+//!   inputs of the proof,
+//! - the MAC key shares and the masks of the MAC checks and the truncation.
+//!
+//! Challenges of the ZKPoPK must be unpredictable for the prover, but need not be secret.
+//!
+//! By default, small secrets like keys and seeds are sampled with `os_rng()`, and noise, which is
+//! needed in large amounts, with `seeded_rng()`.  The functions that take an RNG allow to use
+//! another source, e.g., a certified DRBG.  `test_rng()` is only meant for reproducible tests.
+
+use rand::rngs::OsRng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+/// The randomness of the operating system, e.g., `getrandom(2)` on Linux.
+pub fn os_rng() -> OsRng {
+    OsRng
+}
+
+/// A fast CSPRNG, which is seeded from `os_rng()`.
+pub fn seeded_rng() -> ChaCha20Rng {
+    ChaCha20Rng::from_rng(OsRng).expect("the OS failed to provide randomness")
+}
+
+/// A deterministic RNG for tests and reproducible benchmarks.  It is not secure, since `seed` is
+/// guessable.
+pub fn test_rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
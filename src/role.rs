@@ -0,0 +1,169 @@
+//! The role of a party at runtime.
+//!
+//! Most of the crate takes the role as a const generic `PID`, which must be known at compile time.
+//! A `Role` can instead be decided at runtime, e.g., by `negotiate()` after connecting, and is
+//! turned into a `PID` by `Role::dispatch()`.  The logic that only depends on the role (e.g., the
+//! truncation) takes a `Role`, and its const-generic variants pass `Role::of::<PID>()`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Role {
+    P0,
+    P1,
+}
+
+impl Role {
+    /// Returns `None` unless `pid` is 0 or 1.
+    pub const fn from_pid(pid: usize) -> Option<Self> {
+        match pid {
+            0 => Some(Role::P0),
+            1 => Some(Role::P1),
+            _ => None,
+        }
+    }
+
+    /// The role of the const generic `PID`.
+    ///
+    /// # Panics
+    ///
+    /// Panics (at compile time, if evaluated in a const context) unless `PID` is 0 or 1.
+    pub const fn of<const PID: usize>() -> Self {
+        match Self::from_pid(PID) {
+            Some(role) => role,
+            None => panic!("PID must be 0 or 1"),
+        }
+    }
+
+    pub const fn pid(self) -> usize {
+        match self {
+            Role::P0 => 0,
+            Role::P1 => 1,
+        }
+    }
+
+    /// The role of the other party.
+    pub const fn other(self) -> Self {
+        match self {
+            Role::P0 => Role::P1,
+            Role::P1 => Role::P0,
+        }
+    }
+
+    /// Whether this is party 0, which adds public values to its shares and whose messages come
+    /// first in transcripts.
+    pub const fn is_p0(self) -> bool {
+        matches!(self, Role::P0)
+    }
+
+    /// Calls `visitor` with the `PID` of this role, e.g., to open a `LowGearPreprocessor` for a
+    /// role that was decided at runtime.
+    pub fn dispatch<V>(self, visitor: V) -> V::Output
+    where
+        V: RoleVisitor,
+    {
+        match self {
+            Role::P0 => visitor.visit::<0>(),
+            Role::P1 => visitor.visit::<1>(),
+        }
+    }
+}
+
+/// A computation that is generic over the const `PID`, see `Role::dispatch()`.
+pub trait RoleVisitor {
+    type Output;
+
+    fn visit<const PID: usize>(self) -> Self::Output;
+}
+
+#[cfg(feature = "protocol")]
+pub use self::negotiation::{negotiate, NegotiationError};
+
+#[cfg(feature = "protocol")]
+mod negotiation {
+    use futures_util::{SinkExt, StreamExt};
+
+    use crate::bi_channel::BiChannel;
+    use crate::connection::{Connection, StreamError};
+
+    use super::Role;
+
+    #[derive(Debug, derive_more::Display, derive_more::Error)]
+    pub enum NegotiationError {
+        FailedToOpen(StreamError),
+        FailedToSend(bincode::ErrorKind),
+        FailedToReceive(bincode::ErrorKind),
+        ConnectionClosed,
+    }
+
+    /// Decides the roles of both parties after connecting: each party draws a random number, and
+    /// the party with the larger one becomes party 0.  Both parties must call this at the same
+    /// point, since it opens a channel on `conn`.  Ties, which are unlikely, are redrawn.
+    ///
+    /// The roles are not authenticated, i.e., the other party can choose its role.  This is fine
+    /// for the protocols of this crate, which are secure for either role.
+    pub async fn negotiate(conn: &mut Connection) -> Result<Role, NegotiationError> {
+        let mut channel = BiChannel::<u64>::open(conn, "Role")
+            .await
+            .map_err(NegotiationError::FailedToOpen)?;
+        let (rx, tx) = channel.split();
+        loop {
+            let local: u64 = rand::random();
+            let (sent, received) = tokio::join!(tx.send(local), rx.next());
+            sent.map_err(|err| NegotiationError::FailedToSend(*err))?;
+            let remote = match received {
+                Some(Ok(remote)) => remote,
+                Some(Err(err)) => return Err(NegotiationError::FailedToReceive(*err)),
+                None => return Err(NegotiationError::ConnectionClosed),
+            };
+            if local != remote {
+                let _ = tx.get_mut().finish().await;
+                return Ok(if local > remote { Role::P0 } else { Role::P1 });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Role, RoleVisitor};
+
+    struct Pid;
+
+    impl RoleVisitor for Pid {
+        type Output = usize;
+
+        fn visit<const PID: usize>(self) -> usize {
+            PID
+        }
+    }
+
+    #[test]
+    fn dispatch() {
+        for role in [Role::P0, Role::P1] {
+            assert_eq!(role.dispatch(Pid), role.pid());
+            assert_eq!(Role::from_pid(role.pid()), Some(role));
+            assert_ne!(role.other(), role);
+        }
+        assert_eq!(Role::of::<1>(), Role::P1);
+        assert_eq!(Role::from_pid(2), None);
+    }
+
+    #[cfg(feature = "protocol")]
+    #[tokio::test]
+    async fn negotiate() {
+        use crate::connection::Connection;
+
+        const P0_ADDR: &str = "[::1]:50097";
+        const P1_ADDR: &str = "[::1]:50098";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (role0, role1) =
+            tokio::join!(super::negotiate(&mut conn0), super::negotiate(&mut conn1));
+        assert_eq!(role0.unwrap(), role1.unwrap().other());
+    }
+}
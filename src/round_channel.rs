@@ -0,0 +1,159 @@
+//! Channels whose message type is fixed by a protocol round.
+//!
+//! A `Round` names the channel of a round and the type of its messages.  A `RoundChannel` of that
+//! round only sends and receives messages of that type, and it is split into a `RoundSender` and a
+//! `RoundReceiver`, such that the directions of a round cannot be mixed up either.  Sending a
+//! message in the wrong round is thus a type error instead of a deserialization error at the other
+//! party.
+
+use std::marker::PhantomData;
+
+use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
+use async_bincode::AsyncDestination;
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+
+/// A round of a protocol, which is usually a zero-sized marker type.
+pub trait Round {
+    /// Name of the channel, which must be unique within the protocol.
+    const NAME: &'static str;
+
+    type Message: Serialize + DeserializeOwned;
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum RoundError {
+    FailedToSend(bincode::ErrorKind),
+    FailedToReceive(bincode::ErrorKind),
+    ConnectionClosed,
+}
+
+pub struct RoundChannel<R>
+where
+    R: Round,
+{
+    inner: BiChannel<R::Message>,
+    _round: PhantomData<fn() -> R>,
+}
+
+impl<R> RoundChannel<R>
+where
+    R: Round,
+{
+    pub async fn open(conn: &mut Connection) -> Result<Self, StreamError> {
+        Ok(Self {
+            inner: BiChannel::open(conn, R::NAME).await?,
+            _round: PhantomData,
+        })
+    }
+
+    pub fn split(&mut self) -> (RoundReceiver<'_, R>, RoundSender<'_, R>) {
+        let (reader, writer) = self.inner.split();
+        (
+            RoundReceiver {
+                reader,
+                _round: PhantomData,
+            },
+            RoundSender {
+                writer,
+                _round: PhantomData,
+            },
+        )
+    }
+
+    /// Sends `message` and receives the message of the other party concurrently.
+    pub async fn exchange(&mut self, message: R::Message) -> Result<R::Message, RoundError> {
+        let (mut rx, mut tx) = self.split();
+        let (sent, received) = tokio::join!(tx.send(message), rx.recv());
+        sent.map_err(|err| RoundError::FailedToSend(*err))?;
+        received
+    }
+
+    pub async fn finish(self) {
+        let _ = self.inner.writer.into_inner().finish().await;
+    }
+}
+
+/// The sending half of a `RoundChannel`.
+pub struct RoundSender<'a, R>
+where
+    R: Round,
+{
+    writer: &'a mut AsyncBincodeWriter<quinn::SendStream, R::Message, AsyncDestination>,
+    _round: PhantomData<fn() -> R>,
+}
+
+impl<R> RoundSender<'_, R>
+where
+    R: Round,
+{
+    pub async fn send(&mut self, message: R::Message) -> Result<(), bincode::Error> {
+        self.writer.send(message).await
+    }
+}
+
+/// The receiving half of a `RoundChannel`.
+pub struct RoundReceiver<'a, R>
+where
+    R: Round,
+{
+    reader: &'a mut AsyncBincodeReader<quinn::RecvStream, R::Message>,
+    _round: PhantomData<fn() -> R>,
+}
+
+impl<R> RoundReceiver<'_, R>
+where
+    R: Round,
+{
+    pub async fn recv(&mut self) -> Result<R::Message, RoundError> {
+        match self.reader.next().await {
+            Some(Ok(message)) => Ok(message),
+            Some(Err(err)) => Err(RoundError::FailedToReceive(*err)),
+            None => Err(RoundError::ConnectionClosed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Connection;
+
+    use super::{Round, RoundChannel};
+
+    struct Ping;
+
+    impl Round for Ping {
+        const NAME: &'static str = "test:ping";
+
+        type Message = u32;
+    }
+
+    #[tokio::test]
+    async fn exchange() {
+        const P0_ADDR: &str = "[::1]:50103";
+        const P1_ADDR: &str = "[::1]:50104";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (ch0, ch1) = tokio::join!(
+            RoundChannel::<Ping>::open(&mut conn0),
+            RoundChannel::<Ping>::open(&mut conn1)
+        );
+        let (mut ch0, mut ch1) = (ch0.unwrap(), ch1.unwrap());
+
+        let (received0, received1) = tokio::join!(ch0.exchange(0), ch1.exchange(1));
+        assert_eq!((received0.unwrap(), received1.unwrap()), (1, 0));
+
+        let (mut rx1, _) = ch1.split();
+        let (_, mut tx0) = ch0.split();
+        tx0.send(2).await.unwrap();
+        assert_eq!(rx1.recv().await.unwrap(), 2);
+    }
+}
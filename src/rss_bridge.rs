@@ -0,0 +1,340 @@
+//! Bridge from two-party SPDZ2k triples to three-party replicated secret sharing (RSS), as used by
+//! ABY3-style online phases.
+//!
+//! In three-party RSS, a value `x` is split as `x = x_0 + x_1 + x_2` and RSS party `i` holds the
+//! pair `(x_i, x_{i+1})`.  Players 0 and 1 of the two-party protocol become RSS parties 0 and 1,
+//! and a third party (the helper) becomes RSS party 2.  The conversion works as follows:
+//!
+//! - Player 0 and the helper derive `x_0` from a common seed, and so do player 1 and the helper for
+//!   `x_2`.
+//! - Player 0 sends `s_0 - x_0` to player 1 and player 1 sends `s_1 - x_2` to player 0, where `s_0`
+//!   and `s_1` are the additive SPDZ2k shares.  Both then compute `x_1 = (s_0 - x_0) + (s_1 - x_2)`.
+//!
+//! Since the conversion is linear, the relation `c = a * b` of a Beaver triple is preserved.  MAC
+//! tags are dropped, because RSS relies on an honest majority instead.
+
+use std::marker::PhantomData;
+
+use futures_util::{SinkExt, StreamExt};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::bi_channel::BiChannel;
+use crate::connection::{Connection, StreamError};
+use crate::interface::{BeaverTriple, Share};
+use crate::role::Role;
+
+/// Index of the helper in the replicated sharing.
+pub const HELPER_ID: usize = 2;
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum RssError {
+    FailedToOpen(StreamError),
+    FailedToSend(bincode::ErrorKind),
+    FailedToReceive(bincode::ErrorKind),
+    ConnectionClosed,
+    /// The other player converted another number of triples.
+    LengthMismatch,
+}
+
+/// RSS party `i`'s share `(x_i, x_{i+1})` of a value `x = x_0 + x_1 + x_2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplicatedShare<K>
+where
+    K: GenericNativeResidue,
+{
+    pub first: K,
+    pub second: K,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplicatedTriple<K>
+where
+    K: GenericNativeResidue,
+{
+    pub a: ReplicatedShare<K>,
+    pub b: ReplicatedShare<K>,
+    pub c: ReplicatedShare<K>,
+}
+
+/// Converts the triples of player `PID` (0 or 1) into replicated sharing.
+pub struct RssBridge<K, const PID: usize>
+where
+    K: GenericNativeResidue,
+{
+    ch_peer: BiChannel<Vec<K>>,
+    prng: ChaCha20Rng,
+}
+
+/// The third party, which receives its shares without taking part in triple generation.
+pub struct RssHelper<K>
+where
+    K: GenericNativeResidue,
+{
+    prng_0: ChaCha20Rng,
+    prng_1: ChaCha20Rng,
+    phantom: PhantomData<K>,
+}
+
+impl<K> ReplicatedShare<K>
+where
+    K: GenericNativeResidue,
+{
+    pub const fn new(first: K, second: K) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<K, const PID: usize> RssBridge<K, PID>
+where
+    K: GenericNativeResidue,
+{
+    /// `conn_peer` connects to the other player and `conn_helper` connects to the helper.
+    pub async fn new(
+        conn_peer: &mut Connection,
+        conn_helper: &mut Connection,
+    ) -> Result<Self, RssError> {
+        assert!(PID < HELPER_ID);
+
+        let ch_peer = BiChannel::open(conn_peer, "RssBridge:masked")
+            .await
+            .map_err(RssError::FailedToOpen)?;
+        let mut ch_seed = BiChannel::<[u8; 32]>::open(conn_helper, "RssBridge:seed")
+            .await
+            .map_err(RssError::FailedToOpen)?;
+
+        let seed: [u8; 32] = rand::thread_rng().gen();
+        ch_seed
+            .writer
+            .send(seed)
+            .await
+            .map_err(|e| RssError::FailedToSend(*e))?;
+        let _ = ch_seed.writer.into_inner().finish().await;
+
+        Ok(Self {
+            ch_peer,
+            prng: ChaCha20Rng::from_seed(seed),
+        })
+    }
+
+    /// Converts `triples`, where the other player must convert its shares of the same triples.
+    pub async fn convert<KS>(
+        &mut self,
+        triples: &[BeaverTriple<KS, K, PID>],
+    ) -> Result<Vec<ReplicatedTriple<K>>, RssError>
+    where
+        KS: GenericNativeResidue,
+    {
+        // The order in which masks are drawn must match `RssHelper::triples()`.
+        let masks: Vec<_> = (0..3 * triples.len())
+            .map(|_| K::random(&mut self.prng))
+            .collect();
+        let masked: Vec<_> = triples
+            .iter()
+            .flat_map(|triple| [&triple.a, &triple.b, &triple.c])
+            .zip(&masks)
+            .map(|(share, mask)| mask_share(share, *mask))
+            .collect();
+
+        let (rx, tx) = self.ch_peer.split();
+        let (sent, received) = tokio::join!(tx.send(masked.clone()), rx.next());
+        sent.map_err(|e| RssError::FailedToSend(*e))?;
+        let remote_masked = received
+            .ok_or(RssError::ConnectionClosed)?
+            .map_err(|e| RssError::FailedToReceive(*e))?;
+        if remote_masked.len() != masked.len() {
+            return Err(RssError::LengthMismatch);
+        }
+
+        let shares: Vec<_> = masked
+            .iter()
+            .zip(&remote_masked)
+            .zip(&masks)
+            .map(|((local, remote), mask)| reshare(Role::of::<PID>(), *local, *remote, *mask))
+            .collect();
+        Ok(shares
+            .chunks(3)
+            .map(|chunk| ReplicatedTriple {
+                a: chunk[0],
+                b: chunk[1],
+                c: chunk[2],
+            })
+            .collect())
+    }
+
+    pub async fn finish(self) {
+        let _ = self.ch_peer.writer.into_inner().finish().await;
+    }
+}
+
+impl<K> RssHelper<K>
+where
+    K: GenericNativeResidue,
+{
+    /// `conn_p0` and `conn_p1` connect to player 0 and player 1, respectively.
+    pub async fn new(conn_p0: &mut Connection, conn_p1: &mut Connection) -> Result<Self, RssError> {
+        let mut ch_seed_0 = BiChannel::<[u8; 32]>::open(conn_p0, "RssBridge:seed")
+            .await
+            .map_err(RssError::FailedToOpen)?;
+        let mut ch_seed_1 = BiChannel::<[u8; 32]>::open(conn_p1, "RssBridge:seed")
+            .await
+            .map_err(RssError::FailedToOpen)?;
+
+        let (seed_0, seed_1) = tokio::join!(ch_seed_0.reader.next(), ch_seed_1.reader.next());
+        let receive = |seed: Option<Result<[u8; 32], bincode::Error>>| {
+            seed.ok_or(RssError::ConnectionClosed)?
+                .map_err(|e| RssError::FailedToReceive(*e))
+        };
+
+        Ok(Self {
+            prng_0: ChaCha20Rng::from_seed(receive(seed_0)?),
+            prng_1: ChaCha20Rng::from_seed(receive(seed_1)?),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the helper's shares of the next `n` triples converted by the players.
+    pub fn triples(&mut self, n: usize) -> Vec<ReplicatedTriple<K>> {
+        let mut next_share = || {
+            let x_0 = K::random(&mut self.prng_0);
+            let x_2 = K::random(&mut self.prng_1);
+            ReplicatedShare::new(x_2, x_0)
+        };
+        (0..n)
+            .map(|_| ReplicatedTriple {
+                a: next_share(),
+                b: next_share(),
+                c: next_share(),
+            })
+            .collect()
+    }
+}
+
+/// Returns the share with the pseudorandom component removed, i.e. `s_0 - x_0` or `s_1 - x_2`.
+fn mask_share<KS, K, const PID: usize>(share: &Share<KS, K, PID>, mask: K) -> K
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    K::from_unsigned(share.val) - mask
+}
+
+fn reshare<K>(role: Role, local: K, remote: K, mask: K) -> ReplicatedShare<K>
+where
+    K: GenericNativeResidue,
+{
+    let x_1 = local + remote;
+    if role.is_p0() {
+        ReplicatedShare::new(mask, x_1)
+    } else {
+        ReplicatedShare::new(x_1, mask)
+    }
+}
+
+/// Reconstructs the value from the shares of all three RSS parties.
+pub fn open<K>(shares: &[ReplicatedShare<K>; 3]) -> K
+where
+    K: GenericNativeResidue,
+{
+    debug_assert!(shares[0].second == shares[1].first);
+    debug_assert!(shares[1].second == shares[2].first);
+    debug_assert!(shares[2].second == shares[0].first);
+    shares[0].first + shares[1].first + shares[2].first
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{Random, Zero};
+
+    use crate::bgv::residue::{native::NativeResidue, GenericResidue};
+    use crate::connection::Connection;
+    use crate::interface::{BeaverTriple, Share};
+    use crate::role::Role;
+
+    use super::{mask_share, open, reshare, ReplicatedShare, RssBridge, RssHelper};
+
+    type K = NativeResidue<64, 1>;
+    type KS = NativeResidue<128, 2>;
+
+    #[test]
+    fn reshare_preserves_value_and_product() {
+        let mut rng = rand::thread_rng();
+        let a = K::random(&mut rng);
+        let b = K::random(&mut rng);
+
+        let mut opened = Vec::new();
+        for val in [a, b, a * b] {
+            let val_0 = KS::random(&mut rng);
+            let val_1 = KS::from_unsigned(val) - val_0;
+            let share_0 = Share::<KS, K, 0>::new(val_0, KS::ZERO);
+            let share_1 = Share::<KS, K, 1>::new(val_1, KS::ZERO);
+            let x_0 = K::random(&mut rng);
+            let x_2 = K::random(&mut rng);
+
+            let masked_0 = mask_share(&share_0, x_0);
+            let masked_1 = mask_share(&share_1, x_2);
+            let shares = [
+                reshare(Role::P0, masked_0, masked_1, x_0),
+                reshare(Role::P1, masked_1, masked_0, x_2),
+                ReplicatedShare::new(x_2, x_0),
+            ];
+            opened.push(open(&shares));
+        }
+
+        assert_eq!(opened, [a, b, a * b]);
+    }
+
+    async fn connect(addr_0: &str, addr_1: &str) -> (Connection, Connection) {
+        let (conn_0, conn_1) = tokio::join!(
+            Connection::new(addr_0.parse().unwrap(), addr_1.parse().unwrap()),
+            Connection::new(addr_1.parse().unwrap(), addr_0.parse().unwrap())
+        );
+        (conn_0.unwrap(), conn_1.unwrap())
+    }
+
+    /// Additive shares of `val` without MAC tags, which the conversion drops anyway.
+    fn share(val: K) -> (Share<KS, K, 0>, Share<KS, K, 1>) {
+        let val_0 = KS::random(&mut rand::thread_rng());
+        let val_1 = KS::from_unsigned(val) - val_0;
+        (Share::new(val_0, KS::ZERO), Share::new(val_1, KS::ZERO))
+    }
+
+    #[tokio::test]
+    async fn convert_round_trip() {
+        const N: usize = 5;
+
+        let (mut peer_0, mut peer_1) = connect("[::1]:50114", "[::1]:50115").await;
+        let (mut helper_0, mut to_helper_0) = connect("[::1]:50117", "[::1]:50118").await;
+        let (mut helper_1, mut to_helper_1) = connect("[::1]:50143", "[::1]:50144").await;
+        let (bridge_0, bridge_1, helper) = tokio::join!(
+            RssBridge::<K, 0>::new(&mut peer_0, &mut to_helper_0),
+            RssBridge::<K, 1>::new(&mut peer_1, &mut to_helper_1),
+            RssHelper::<K>::new(&mut helper_0, &mut helper_1)
+        );
+        let (mut bridge_0, mut bridge_1, mut helper) =
+            (bridge_0.unwrap(), bridge_1.unwrap(), helper.unwrap());
+
+        let mut rng = rand::thread_rng();
+        let (mut triples_0, mut triples_1) = (Vec::new(), Vec::new());
+        for _ in 0..N {
+            let (a, b) = (K::random(&mut rng), K::random(&mut rng));
+            let [(a_0, a_1), (b_0, b_1), (c_0, c_1)] = [a, b, a * b].map(share);
+            triples_0.push(BeaverTriple::new(a_0, b_0, c_0));
+            triples_1.push(BeaverTriple::new(a_1, b_1, c_1));
+        }
+        let (converted_0, converted_1) =
+            tokio::join!(bridge_0.convert(&triples_0), bridge_1.convert(&triples_1));
+        let (converted_0, converted_1) = (converted_0.unwrap(), converted_1.unwrap());
+        let converted_2 = helper.triples(N);
+
+        for ((triple_0, triple_1), triple_2) in
+            converted_0.iter().zip(&converted_1).zip(&converted_2)
+        {
+            let a = open(&[triple_0.a, triple_1.a, triple_2.a]);
+            let b = open(&[triple_0.b, triple_1.b, triple_2.b]);
+            assert_eq!(open(&[triple_0.c, triple_1.c, triple_2.c]), a * b);
+        }
+        tokio::join!(bridge_0.finish(), bridge_1.finish());
+    }
+}
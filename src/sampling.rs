@@ -0,0 +1,121 @@
+//! Sampling of vectors of residues and conversions between their widths.
+//!
+//! The preprocessors sample their values in one width (e.g., `KS`) and compute with them in another
+//! (e.g., `KSS`).  Sampling a value in `N` and widening it to `W` yields a uniformly random element
+//! of `W` whose representative fits into `N`, which is what `random_widened()` returns.  The `_into`
+//! variants overwrite existing buffers instead of allocating new ones.
+
+use rand::{CryptoRng, RngCore};
+
+use crate::bgv::residue::native::GenericNativeResidue;
+
+/// Samples `n` uniformly random residues.
+pub fn random_vec<T>(mut rng: impl CryptoRng + RngCore, n: usize) -> Vec<T>
+where
+    T: GenericNativeResidue,
+{
+    (0..n).map(|_| T::random(&mut rng)).collect()
+}
+
+/// Overwrites `out` with uniformly random residues.
+pub fn random_into<T>(mut rng: impl CryptoRng + RngCore, out: &mut [T])
+where
+    T: GenericNativeResidue,
+{
+    for value in out {
+        *value = T::random(&mut rng);
+    }
+}
+
+/// Appends `n` uniformly random residues to `values`.
+pub fn extend_random<T>(mut rng: impl CryptoRng + RngCore, values: &mut Vec<T>, n: usize)
+where
+    T: GenericNativeResidue,
+{
+    values.extend((0..n).map(|_| T::random(&mut rng)));
+}
+
+/// Samples `n` residues uniformly in `N` and widens them to `W`.
+pub fn random_widened<N, W>(mut rng: impl CryptoRng + RngCore, n: usize) -> Vec<W>
+where
+    N: GenericNativeResidue,
+    W: GenericNativeResidue,
+{
+    (0..n)
+        .map(|_| W::from_unsigned(N::random(&mut rng)))
+        .collect()
+}
+
+/// Converts each residue to the wider `W`, which preserves its representative.
+pub fn widen<N, W>(values: &[N]) -> Vec<W>
+where
+    N: GenericNativeResidue,
+    W: GenericNativeResidue,
+{
+    debug_assert!(N::BITS <= W::BITS);
+    values
+        .iter()
+        .map(|value| W::from_unsigned(*value))
+        .collect()
+}
+
+/// Reduces each residue modulo `2^N::BITS`, i.e., discards its upper bits.
+pub fn narrow<W, N>(values: &[W]) -> Vec<N>
+where
+    W: GenericNativeResidue,
+    N: GenericNativeResidue,
+{
+    debug_assert!(N::BITS <= W::BITS);
+    values
+        .iter()
+        .map(|value| N::from_unsigned(*value))
+        .collect()
+}
+
+/// Like `narrow()`, but overwrites `out`, which must have the same length as `values`.
+pub fn narrow_into<W, N>(values: &[W], out: &mut [N])
+where
+    W: GenericNativeResidue,
+    N: GenericNativeResidue,
+{
+    assert_eq!(values.len(), out.len());
+    for (dst, value) in out.iter_mut().zip(values) {
+        *dst = N::from_unsigned(*value);
+    }
+}
+
+/// Like `narrow()`, but returns `None` if any residue does not fit into `N`.
+pub fn try_narrow<W, N>(values: &[W]) -> Option<Vec<N>>
+where
+    W: GenericNativeResidue,
+    N: GenericNativeResidue,
+{
+    values
+        .iter()
+        .map(|value| N::try_from_unsigned(*value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bgv::residue::native::NativeResidue;
+    use crate::bgv::residue::GenericResidue;
+    use crate::rng::test_rng;
+
+    use super::{narrow, random_widened, try_narrow, widen};
+
+    type N = NativeResidue<64, 1>;
+    type W = NativeResidue<128, 2>;
+
+    #[test]
+    fn widen_and_narrow() {
+        let wide: Vec<W> = random_widened::<N, W>(test_rng(0), 16);
+        let narrow_values = try_narrow::<W, N>(&wide).unwrap();
+        assert_eq!(narrow::<W, N>(&wide), narrow_values);
+        assert_eq!(widen::<N, W>(&narrow_values), wide);
+
+        let mut too_wide = wide;
+        too_wide[3] = too_wide[3] + W::from_unsigned(N::from_i64(-1)) + W::from_i64(1);
+        assert!(try_narrow::<W, N>(&too_wide).is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! Pre-flight checks that validate an installation for a parameter set before a long run.
+//!
+//! `selftest()` checks that the factor files under `params/` exist, can be parsed and match the
+//! parameters, generates the CRT contexts, and runs a BGV roundtrip, a pack/unpack roundtrip and a
+//! loopback authentication with two `LowGearDealer`s.
+
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crypto_bigint::Random;
+use log::{error, info};
+
+use crate::bgv::poly::crt::{CrtPoly, CrtPolyParameters};
+use crate::bgv::poly::power::PowerPoly;
+use crate::bgv::poly::{CrtContext, CrtStrategy, FactorsContext};
+use crate::bgv::residue::GenericResidue;
+use crate::bgv::tweaked_interpolation_packing::{get_random_unpacked, pack_mask, unpack};
+use crate::bgv::{self, PublicKey, SecretKey};
+use crate::connection::Connection;
+use crate::interface::MacKeyShare;
+use crate::low_gear_dealer::LowGearDealer;
+use crate::low_gear_preproc::PreprocessorParameters;
+
+pub struct CheckResult {
+    pub name: &'static str,
+    pub result: Result<(), String>,
+    pub elapsed: Duration,
+}
+
+pub struct SelftestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelftestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.result {
+                Ok(()) => write!(f, "PASS")?,
+                Err(_) => write!(f, "FAIL")?,
+            }
+            write!(f, " {} ({} ms)", check.name, check.elapsed.as_millis())?;
+            if let Err(msg) = &check.result {
+                write!(f, ": {}", msg)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs all checks for the parameter set `P`.  The loopback authentication listens on `p0_addr`
+/// and `p1_addr`.  A failing check does not prevent the subsequent checks from running.
+pub async fn selftest<P>(p0_addr: SocketAddr, p1_addr: SocketAddr) -> SelftestReport
+where
+    P: PreprocessorParameters,
+{
+    let mut checks = Vec::new();
+    run_check(&mut checks, "factor files", async {
+        check_factors_file::<P::CiphertextParams>()?;
+        check_factors_file::<P::PlaintextParams>()
+    })
+    .await;
+    run_check(&mut checks, "contexts", async {
+        CrtContext::<P::CiphertextParams>::gen().await;
+        CrtContext::<P::PlaintextParams>::gen().await;
+        Ok(())
+    })
+    .await;
+    run_check(&mut checks, "bgv roundtrip", bgv_roundtrip::<P>()).await;
+    run_check(&mut checks, "packing roundtrip", packing_roundtrip::<P>()).await;
+    run_check(
+        &mut checks,
+        "dealer authentication",
+        dealer_authentication::<P>(p0_addr, p1_addr),
+    )
+    .await;
+    SelftestReport { checks }
+}
+
+/// Runs `check` in its own task, such that panics (e.g. due to missing files) are reported as
+/// failures.
+async fn run_check<F>(checks: &mut Vec<CheckResult>, name: &'static str, check: F)
+where
+    F: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let now = Instant::now();
+    let result = match tokio::task::spawn(check).await {
+        Ok(result) => result,
+        Err(err) => Err(format!("panicked: {}", err)),
+    };
+    let elapsed = now.elapsed();
+    match &result {
+        Ok(()) => info!("selftest: {} passed", name),
+        Err(msg) => error!("selftest: {} failed: {}", name, msg),
+    }
+    checks.push(CheckResult {
+        name,
+        result,
+        elapsed,
+    });
+}
+
+fn check_factors_file<P>() -> Result<(), String>
+where
+    P: CrtPolyParameters,
+{
+    if let CrtStrategy::Factors { file } = P::CRT_STRATEGY {
+        let json = std::fs::read(file).map_err(|err| format!("{}: {}", file, err))?;
+        FactorsContext::<P>::from_json(file, &json).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+async fn bgv_roundtrip<P>() -> Result<(), String>
+where
+    P: PreprocessorParameters,
+{
+    let ctx = CrtContext::gen().await;
+    let sk = SecretKey::<P::BgvParams>::gen(&ctx).await;
+    let pk = PublicKey::gen(&ctx, &sk).await;
+    let plaintext = PowerPoly::random(rand::thread_rng());
+    let ciphertext = bgv::encrypt(&ctx, &pk, &plaintext).await;
+    if bgv::decrypt(&ctx, &sk, &ciphertext).await != plaintext {
+        return Err("decryption does not match plaintext".into());
+    }
+    Ok(())
+}
+
+async fn packing_roundtrip<P>() -> Result<(), String>
+where
+    P: PreprocessorParameters,
+{
+    let ctx = CrtContext::<P::PlaintextParams>::gen().await;
+    let unpacked = get_random_unpacked::<P::PlaintextParams, P::KSS>(rand::thread_rng());
+    // Only values scaled by `2^{2 delta}`, like products and masks, can be unpacked.
+    let power = PowerPoly::from_crt(&ctx, &pack_mask(&unpacked)).await;
+    let roundtrip = unpack::<_, P::KSS>(&CrtPoly::from_power(&ctx, &power).await)
+        .ok_or_else(|| String::from("failed to unpack"))?;
+    if roundtrip != unpacked {
+        return Err("unpacked values do not match".into());
+    }
+    Ok(())
+}
+
+async fn dealer_authentication<P>(p0_addr: SocketAddr, p1_addr: SocketAddr) -> Result<(), String>
+where
+    P: PreprocessorParameters,
+{
+    let (mac_keys, values) = {
+        let mut rng = rand::thread_rng();
+        let mac_keys = [P::S::random(&mut rng), P::S::random(&mut rng)];
+        let values = [(); 2].map(|_| [(); 16].map(|_| P::K::random(&mut rng)));
+        (mac_keys, values)
+    };
+
+    let (conn0, conn1) = tokio::join!(
+        Connection::new(p0_addr, p1_addr),
+        Connection::new(p1_addr, p0_addr)
+    );
+    let mut conns = [
+        conn0.map_err(|err| err.to_string())?,
+        conn1.map_err(|err| err.to_string())?,
+    ];
+    let [conn0, conn1] = &mut conns;
+    let (dealer0, dealer1) = tokio::join!(
+        LowGearDealer::<P::DealerParams>::new(conn0, MacKeyShare::from_secret(mac_keys[0])),
+        LowGearDealer::<P::DealerParams>::new(conn1, MacKeyShare::from_secret(mac_keys[1]))
+    );
+    let mut dealer0 = dealer0.map_err(|err| err.to_string())?;
+    let mut dealer1 = dealer1.map_err(|err| err.to_string())?;
+    let (tags0, tags1) = tokio::join!(
+        dealer0.authenticate(&values[0]),
+        dealer1.authenticate(&values[1])
+    );
+    tokio::join!(dealer0.finish(), dealer1.finish());
+
+    let mac_key = P::KS::from_unsigned(mac_keys[0]) + P::KS::from_unsigned(mac_keys[1]);
+    for (((x0, x1), t0), t1) in values[0].iter().zip(&values[1]).zip(&tags0).zip(&tags1) {
+        let x = P::KS::from_unsigned(*x0) + P::KS::from_unsigned(*x1);
+        if *t0 + *t1 != x * mac_key {
+            return Err("MAC tags do not match".into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+
+    use super::selftest;
+
+    #[tokio::test]
+    async fn selftest_toy() {
+        const P0_ADDR: &str = "[::1]:50063";
+        const P1_ADDR: &str = "[::1]:50064";
+
+        let report =
+            selftest::<ToyPreprocK32S32>(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()).await;
+        assert!(report.passed(), "{}", report);
+    }
+}
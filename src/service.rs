@@ -0,0 +1,466 @@
+//! gRPC service that hands out the preprocessing material of one party (see
+//! `proto/multipars.proto`).
+//!
+//! Each party runs its own service.  The clients of both parties must request the same amounts in
+//! the same order, so that they obtain matching shares.  Requests are served one after another by
+//! a task that owns the preprocessors.
+
+use std::cmp::min;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use log::warn;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::{Request, Response, Status};
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::buffered_preproc::BufferedPreprocessor;
+use crate::interface::{BitPreprocessor, Preprocessor};
+use crate::low_gear_preproc::param_info::ParamInfo;
+
+pub mod proto {
+    tonic::include_proto!("multipars");
+}
+
+use self::proto::preprocessing_server::{Preprocessing, PreprocessingServer};
+use self::proto::{
+    BitChunk, Capabilities, GetBitsRequest, GetCapabilitiesRequest, GetTriplesRequest, TripleChunk,
+};
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// Number of response messages per request that are generated ahead of the client.
+const CHUNKS_AHEAD: usize = 4;
+
+pub struct PreprocessingService<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    triples: BufferedPreprocessor<KS, K, PID>,
+    bits: Option<Box<dyn BitPreprocessor<KS, K, PID> + Send>>,
+    batch_size: usize,
+    param_info: Option<ParamInfo>,
+}
+
+impl<KS, K, const PID: usize> PreprocessingService<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    /// `batch_size` is reported to clients and used as default chunk size.
+    pub fn new(triples: BufferedPreprocessor<KS, K, PID>, batch_size: usize) -> Self {
+        Self {
+            triples,
+            bits: None,
+            batch_size,
+            param_info: None,
+        }
+    }
+
+    /// Reports the name and packing capacity of the parameters to clients.
+    pub fn with_param_info(mut self, param_info: ParamInfo) -> Self {
+        self.param_info = Some(param_info);
+        self
+    }
+
+    /// Enables `GetBits`, which otherwise returns `UNIMPLEMENTED`.
+    pub fn with_bits<B>(mut self, bits: B) -> Self
+    where
+        B: BitPreprocessor<KS, K, PID> + Send + 'static,
+    {
+        self.bits = Some(Box::new(bits));
+        self
+    }
+
+    /// Returns the service, such that each request must pass `auth` (e.g. a check of a bearer
+    /// token in the metadata).  Use `|req| Ok(req)` to accept all requests.
+    ///
+    /// This spawns the task that serves the requests, so it must be called within a Tokio runtime.
+    pub fn into_server<F>(
+        self,
+        auth: F,
+    ) -> InterceptedService<PreprocessingServer<ServiceHandle<KS, K, PID>>, F>
+    where
+        F: Interceptor,
+    {
+        PreprocessingServer::with_interceptor(self.start(), auth)
+    }
+
+    /// Serves on `addr` until the server fails.
+    pub async fn serve<F>(self, addr: SocketAddr, auth: F) -> Result<(), tonic::transport::Error>
+    where
+        F: Interceptor + Clone + Send + Sync + 'static,
+    {
+        tonic::transport::Server::builder()
+            .add_service(self.into_server(auth))
+            .serve(addr)
+            .await
+    }
+
+    /// Spawns the task that owns the preprocessors and serves the requests one after another.
+    fn start(self) -> ServiceHandle<KS, K, PID> {
+        let (jobs_tx, jobs_rx) = mpsc::channel(1);
+        let handle = ServiceHandle {
+            jobs: jobs_tx,
+            batch_size: self.batch_size,
+            bits: self.bits.is_some(),
+            param_info: self.param_info,
+            phantom: PhantomData,
+        };
+        tokio::task::spawn(serve_jobs(self.triples, self.bits, jobs_rx));
+        handle
+    }
+}
+
+/// A request that the task of the service serves after the previous ones.
+enum Job {
+    Triples {
+        count: usize,
+        chunk_size: usize,
+        tx: mpsc::Sender<Result<TripleChunk, Status>>,
+    },
+    Bits {
+        count: usize,
+        chunk_size: usize,
+        tx: mpsc::Sender<Result<BitChunk, Status>>,
+    },
+}
+
+/// The running `PreprocessingService`, which passes the requests to its task.  The task stops once
+/// the handle is dropped.
+pub struct ServiceHandle<KS, K, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    jobs: mpsc::Sender<Job>,
+    batch_size: usize,
+    bits: bool,
+    param_info: Option<ParamInfo>,
+    phantom: PhantomData<(KS, K)>,
+}
+
+impl<KS, K, const PID: usize> ServiceHandle<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    fn chunk_size(&self, requested: u64) -> Result<usize, Status> {
+        match requested {
+            0 => Ok(self.batch_size.max(1)),
+            n => usize::try_from(n).map_err(|_| Status::invalid_argument("chunk size too large")),
+        }
+    }
+
+    async fn submit(&self, job: Job) -> Result<(), Status> {
+        self.jobs
+            .send(job)
+            .await
+            .map_err(|_| Status::unavailable("the preprocessor stopped"))
+    }
+}
+
+/// Serves the jobs in the order in which they arrive, such that the clients of both parties
+/// obtain matching shares.
+///
+/// The material of a request is generated completely even if its client goes away, such that the
+/// next request starts at the same position as the one of the other party's client.  The other
+/// party's client has to discard the material of the cancelled request.
+async fn serve_jobs<KS, K, const PID: usize>(
+    mut triples: BufferedPreprocessor<KS, K, PID>,
+    mut bits: Option<Box<dyn BitPreprocessor<KS, K, PID> + Send>>,
+    mut jobs: mpsc::Receiver<Job>,
+) where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    while let Some(job) = jobs.recv().await {
+        match job {
+            Job::Triples {
+                count,
+                chunk_size,
+                tx,
+            } => {
+                let mut remaining = count;
+                let mut cancelled = false;
+                while remaining > 0 {
+                    let n = min(remaining, chunk_size);
+                    let chunk = triples.get_beaver_triples(n).await;
+                    remaining -= n;
+                    if cancelled {
+                        continue;
+                    }
+                    let chunk = chunk
+                        .iter()
+                        .map(bincode::serialize)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(|triples| TripleChunk { triples })
+                        .map_err(|err| Status::internal(err.to_string()));
+                    if tx.send(chunk).await.is_err() {
+                        warn!(
+                            "PreprocessingService: client cancelled GetTriples, discarding {} triples",
+                            remaining
+                        );
+                        cancelled = true;
+                    }
+                }
+            }
+            Job::Bits {
+                count,
+                chunk_size,
+                tx,
+            } => {
+                let bits = match &mut bits {
+                    Some(bits) => bits,
+                    None => continue,
+                };
+                let mut remaining = count;
+                let mut cancelled = false;
+                while remaining > 0 {
+                    let n = min(remaining, chunk_size);
+                    let chunk = bits.get_dabits(n).await;
+                    remaining -= n;
+                    if cancelled {
+                        continue;
+                    }
+                    let chunk = chunk
+                        .iter()
+                        .map(|dabit| {
+                            Ok(proto::DaBit {
+                                arith: bincode::serialize(&dabit.arith)?,
+                                boolean: dabit.boolean,
+                                xor_shares: dabit
+                                    .xor_shares
+                                    .iter()
+                                    .map(bincode::serialize)
+                                    .collect::<bincode::Result<_>>()?,
+                            })
+                        })
+                        .collect::<bincode::Result<Vec<_>>>()
+                        .map(|dabits| BitChunk { dabits })
+                        .map_err(|err| Status::internal(err.to_string()));
+                    if tx.send(chunk).await.is_err() {
+                        warn!(
+                            "PreprocessingService: client cancelled GetBits, discarding {} bits",
+                            remaining
+                        );
+                        cancelled = true;
+                    }
+                }
+            }
+        }
+    }
+    triples.finish().await;
+}
+
+#[tonic::async_trait]
+impl<KS, K, const PID: usize> Preprocessing for ServiceHandle<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    type GetTriplesStream = ResponseStream<TripleChunk>;
+    type GetBitsStream = ResponseStream<BitChunk>;
+
+    async fn get_triples(
+        &self,
+        request: Request<GetTriplesRequest>,
+    ) -> Result<Response<Self::GetTriplesStream>, Status> {
+        let request = request.into_inner();
+        let count = usize::try_from(request.count)
+            .map_err(|_| Status::invalid_argument("count too large"))?;
+        let chunk_size = self.chunk_size(request.chunk_size)?;
+        let (tx, rx) = mpsc::channel(CHUNKS_AHEAD);
+        self.submit(Job::Triples {
+            count,
+            chunk_size,
+            tx,
+        })
+        .await?;
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn get_bits(
+        &self,
+        request: Request<GetBitsRequest>,
+    ) -> Result<Response<Self::GetBitsStream>, Status> {
+        if !self.bits {
+            return Err(Status::unimplemented("bits are not available"));
+        }
+        let request = request.into_inner();
+        let count = usize::try_from(request.count)
+            .map_err(|_| Status::invalid_argument("count too large"))?;
+        let chunk_size = self.chunk_size(request.chunk_size)?;
+        let (tx, rx) = mpsc::channel(CHUNKS_AHEAD);
+        self.submit(Job::Bits {
+            count,
+            chunk_size,
+            tx,
+        })
+        .await?;
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn get_capabilities(
+        &self,
+        _request: Request<GetCapabilitiesRequest>,
+    ) -> Result<Response<Capabilities>, Status> {
+        Ok(Response::new(Capabilities {
+            player: PID as u32,
+            k: K::BITS as u32,
+            s: (KS::BITS - K::BITS) as u32,
+            batch_size: self.batch_size as u64,
+            bits: self.bits,
+            params: self
+                .param_info
+                .as_ref()
+                .map_or_else(String::new, |info| info.name.to_owned()),
+            packing_capacity: self
+                .param_info
+                .as_ref()
+                .map_or(0, |info| info.packing_capacity as u64),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use async_trait::async_trait;
+    use crypto_bigint::Zero;
+    use futures_util::StreamExt;
+    use tonic::{Code, Request};
+
+    use crate::bgv::residue::GenericResidue;
+    use crate::buffered_preproc::BufferedPreprocessor;
+    use crate::connection::Connection;
+    use crate::interface::{BatchedPreprocessor, BeaverTriple, Share, TripleOrigin};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+    use crate::transcript::SessionId;
+
+    use super::proto::preprocessing_server::Preprocessing;
+    use super::proto::{GetBitsRequest, GetTriplesRequest};
+    use super::{PreprocessingService, ServiceHandle};
+
+    type KS = <ToyPreprocK32S32 as PreprocessorParameters>::KS;
+    type K = <ToyPreprocK32S32 as PreprocessorParameters>::K;
+
+    /// Numbers its triples consecutively (in `a`).
+    #[derive(Default)]
+    struct Counter {
+        next: i64,
+    }
+
+    #[async_trait]
+    impl<const PID: usize> BatchedPreprocessor<KS, K, PID> for Counter {
+        const BATCH_SIZE: usize = 10;
+
+        async fn get_beaver_triples(&mut self) -> Vec<BeaverTriple<KS, K, PID>> {
+            (0..<Self as BatchedPreprocessor<KS, K, PID>>::BATCH_SIZE)
+                .map(|_| {
+                    self.next += 1;
+                    let a = Share::new(KS::from_i64(self.next - 1), KS::ZERO);
+                    BeaverTriple::new(a, Share::ZERO, Share::ZERO)
+                })
+                .collect()
+        }
+
+        async fn finish(self) {}
+    }
+
+    impl TripleOrigin<KS> for Counter {
+        fn mac_key_share(&self) -> KS {
+            KS::ZERO
+        }
+
+        fn session_id(&self) -> SessionId {
+            [0; 32]
+        }
+    }
+
+    /// Requests `count` triples and returns the values of `a` of the first `take` chunks.
+    async fn get_triples(
+        service: &ServiceHandle<KS, K, 0>,
+        count: u64,
+        take: usize,
+    ) -> Vec<Vec<KS>> {
+        let request = GetTriplesRequest {
+            count,
+            chunk_size: 4,
+        };
+        let stream = service
+            .get_triples(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+        stream
+            .take(take)
+            .map(|chunk| {
+                chunk
+                    .unwrap()
+                    .triples
+                    .iter()
+                    .map(|bytes| {
+                        let triple: BeaverTriple<KS, K, 0> = bincode::deserialize(bytes).unwrap();
+                        triple.a.val
+                    })
+                    .collect()
+            })
+            .collect()
+            .await
+    }
+
+    fn numbers(range: Range<i64>) -> Vec<KS> {
+        range.map(KS::from_i64).collect()
+    }
+
+    #[tokio::test]
+    async fn serves_in_order() {
+        const P0_ADDR: &str = "[::1]:50165";
+        const P1_ADDR: &str = "[::1]:50166";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        // Only the service of party 0 is tested, but the producers of both parties coordinate.
+        let (preproc0, preproc1) = tokio::join!(
+            BufferedPreprocessor::<KS, K, 0>::with_connection(&mut conn0, Counter::default(), 20),
+            BufferedPreprocessor::<KS, K, 1>::with_connection(&mut conn1, Counter::default(), 20)
+        );
+        let service = PreprocessingService::new(preproc0.unwrap(), 10).start();
+        let _preproc1 = preproc1.unwrap();
+
+        assert_eq!(
+            get_triples(&service, 10, usize::MAX).await,
+            [numbers(0..4), numbers(4..8), numbers(8..10)]
+        );
+
+        // The triples of a cancelled request are discarded, such that the next request starts at
+        // the same position as on the other party's service.
+        assert_eq!(get_triples(&service, 10, 1).await, [numbers(10..14)]);
+        assert_eq!(
+            get_triples(&service, 2, usize::MAX).await,
+            [numbers(20..22)]
+        );
+
+        let status = service
+            .get_bits(Request::new(GetBitsRequest {
+                count: 1,
+                chunk_size: 0,
+            }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::Unimplemented);
+    }
+}
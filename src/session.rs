@@ -0,0 +1,395 @@
+//! Preprocessors whose parameters are selected at runtime, as used by the language bindings.
+//!
+//! Residues are exported as little-endian 64-bit limbs.  The triples are laid out triple by triple,
+//! each consisting of the shares of a, b and c, each consisting of the value and the MAC tag.
+//! Triples without MAC tags (see `LimbPreprocessor::get_raw_triples()`) consist of the shares of
+//! a, b and c modulo `2^k`.  In columnar form (see `LimbPreprocessor::get_triple_columns()`), the
+//! values and MAC tags are laid out column by column as in `TripleBatch::columns()`.
+
+use std::io;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use crypto_bigint::Limb;
+use futures_util::future::BoxFuture;
+use tokio::runtime::Runtime;
+
+use crate::bgv::generic_uint::GenericUint;
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::buffered_preproc::{BufferedPreprocessor, SetupError};
+use crate::connection::Connection;
+#[cfg(feature = "ffi")]
+use crate::interface::TripleBatch;
+use crate::interface::{BeaverTriple, Preprocessor};
+use crate::low_gear_preproc::param_info::ParamInfo;
+#[cfg(feature = "params-k128")]
+use crate::low_gear_preproc::params::PreprocK128S64;
+#[cfg(feature = "params-k32")]
+use crate::low_gear_preproc::params::PreprocK32S32;
+#[cfg(feature = "params-k64")]
+use crate::low_gear_preproc::params::PreprocK64S64;
+use crate::low_gear_preproc::params::ToyPreprocK32S32;
+use crate::low_gear_preproc::{LowGearPreprocessor, PreprocessorParameters};
+use crate::orchestrator::RunError;
+use crate::role::{Role, RoleVisitor};
+use crate::triple_verifier::{TripleVerifier, VerificationError};
+use crate::util::resolve_host;
+
+type OpenFn = fn(Role, SocketAddr, SocketAddr, usize) -> BoxFuture<'static, OpenResult>;
+type OpenResult = Result<Box<dyn LimbPreprocessor>, SessionError>;
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum SessionError {
+    InvalidPlayer,
+    /// The runtime needs at least one worker thread.
+    InvalidThreads,
+    /// No parameters for `k`, `s` and `toy` exist, or their `params-*` feature is disabled.
+    UnsupportedParameters,
+    FailedToStartRuntime(io::Error),
+    FailedToOpen(RunError),
+    FailedToBuffer(SetupError),
+}
+
+/// Object-safe view of a `BufferedPreprocessor`.
+pub trait LimbPreprocessor: Send {
+    /// Number of limbs per residue of a triple.
+    fn limbs(&self) -> usize;
+
+    /// Limbs of this party's share of the MAC key.
+    fn mac_key(&self) -> &[u64];
+
+    /// The parameters of the preprocessor.
+    fn param_info(&self) -> &ParamInfo;
+
+    /// Writes the limbs of `n` triples to `out`, which must have length `n * 6 * self.limbs()`.
+    fn get_triples<'a>(&'a mut self, n: usize, out: &'a mut [u64]) -> BoxFuture<'a, ()>;
+
+    /// Like `get_triples()`, but writes the triples column by column, i.e., first the `n` values
+    /// of a, then the `n` MAC tags of a, and so on.
+    #[cfg(feature = "ffi")]
+    fn get_triple_columns<'a>(&'a mut self, n: usize, out: &'a mut [u64]) -> BoxFuture<'a, ()>;
+
+    /// Number of limbs per share of a triple without MAC tags.
+    fn raw_limbs(&self) -> usize;
+
+    /// Writes the limbs of `n` triples without MAC tags to `out`, which must have length
+    /// `n * 3 * self.raw_limbs()`.  If `verify` is set, `2 n + 1` triples are consumed and checked
+    /// with a `TripleVerifier` before the MAC tags are dropped.  Both parties must pass the same
+    /// `n` and `verify`.
+    fn get_raw_triples<'a>(
+        &'a mut self,
+        n: usize,
+        verify: bool,
+        out: &'a mut [u64],
+    ) -> BoxFuture<'a, Result<(), VerificationError>>;
+
+    fn finish(self: Box<Self>) -> BoxFuture<'static, ()>;
+}
+
+struct Session<KS, K, S, const PID: usize>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    preproc: BufferedPreprocessor<KS, K, PID>,
+    verifier: TripleVerifier<KS, S>,
+    mac_key: MacKeyLimbs,
+    param_info: ParamInfo,
+    conn: Connection,
+}
+
+/// Limbs of this party's share of the MAC key, which are zeroized when dropped like the
+/// `MacKeyShare` that they are copied from.
+struct MacKeyLimbs(Vec<u64>);
+
+impl Drop for MacKeyLimbs {
+    fn drop(&mut self) {
+        for limb in self.0.iter_mut() {
+            // SAFETY: `limb` is a valid and aligned reference.
+            unsafe { ptr::write_volatile(limb, 0) };
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl<KS, K, S, const PID: usize> LimbPreprocessor for Session<KS, K, S, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    fn limbs(&self) -> usize {
+        limbs::<KS>()
+    }
+
+    fn mac_key(&self) -> &[u64] {
+        &self.mac_key.0
+    }
+
+    fn param_info(&self) -> &ParamInfo {
+        &self.param_info
+    }
+
+    fn get_triples<'a>(&'a mut self, n: usize, out: &'a mut [u64]) -> BoxFuture<'a, ()> {
+        assert_eq!(out.len(), n * 6 * self.limbs());
+        Box::pin(async move {
+            let mut chunks = out.chunks_exact_mut(limbs::<KS>());
+            for triple in self.preproc.get_beaver_triples(n).await {
+                for share in [triple.a, triple.b, triple.c] {
+                    write_limbs(share.val, chunks.next().unwrap());
+                    write_limbs(share.tag, chunks.next().unwrap());
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "ffi")]
+    fn get_triple_columns<'a>(&'a mut self, n: usize, out: &'a mut [u64]) -> BoxFuture<'a, ()> {
+        assert_eq!(out.len(), n * 6 * self.limbs());
+        Box::pin(async move {
+            let batch: TripleBatch<KS, K, PID> = self
+                .preproc
+                .get_beaver_triples(n)
+                .await
+                .into_iter()
+                .collect();
+            let mut chunks = out.chunks_exact_mut(limbs::<KS>());
+            for column in batch.columns() {
+                for residue in column {
+                    write_limbs(*residue, chunks.next().unwrap());
+                }
+            }
+        })
+    }
+
+    fn raw_limbs(&self) -> usize {
+        limbs::<K>()
+    }
+
+    fn get_raw_triples<'a>(
+        &'a mut self,
+        n: usize,
+        verify: bool,
+        out: &'a mut [u64],
+    ) -> BoxFuture<'a, Result<(), VerificationError>> {
+        assert_eq!(out.len(), n * 3 * self.raw_limbs());
+        Box::pin(async move {
+            let raw = if verify {
+                let triples = self.preproc.get_beaver_triples(2 * n + 1).await;
+                self.verifier.verify_and_strip(&triples).await?
+            } else {
+                let triples = self.preproc.get_beaver_triples(n).await;
+                triples.iter().map(BeaverTriple::strip_macs).collect()
+            };
+            let mut chunks = out.chunks_exact_mut(limbs::<K>());
+            for triple in raw {
+                for share in [triple.a, triple.b, triple.c] {
+                    write_limbs(share, chunks.next().unwrap());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn finish(self: Box<Self>) -> BoxFuture<'static, ()> {
+        let Session {
+            preproc,
+            verifier,
+            conn,
+            ..
+        } = *self;
+        Box::pin(async move {
+            preproc.finish().await;
+            verifier.finish().await;
+            drop(conn);
+        })
+    }
+}
+
+/// Connects to the other party and sets up a preprocessor for the given parameters on a new
+/// runtime with `threads` worker threads, which must be positive.  `budget` is the number of
+/// triples that are generated ahead of time.
+#[allow(clippy::too_many_arguments)]
+pub fn connect(
+    player: usize,
+    local_addr: &str,
+    remote_addr: &str,
+    k: usize,
+    s: usize,
+    toy: bool,
+    budget: usize,
+    threads: usize,
+) -> Result<(Runtime, Box<dyn LimbPreprocessor>), SessionError> {
+    let role = Role::from_pid(player).ok_or(SessionError::InvalidPlayer)?;
+    if threads == 0 {
+        return Err(SessionError::InvalidThreads);
+    }
+    let (open, _) = lookup(k, s, toy).ok_or(SessionError::UnsupportedParameters)?;
+    let local_addr = local_addr
+        .parse()
+        .map_err(|err| SessionError::FailedToOpen(RunError::InvalidListenAddr(err)))?;
+    let remote_addr = resolve_host(remote_addr)
+        .map_err(|err| SessionError::FailedToOpen(RunError::FailedToResolve(err)))?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(threads)
+        .enable_all()
+        .build()
+        .map_err(SessionError::FailedToStartRuntime)?;
+    let preproc = runtime.block_on(open(role, local_addr, remote_addr, budget))?;
+    Ok((runtime, preproc))
+}
+
+/// Returns the parameters that `connect()` selects for `k`, `s` and `toy`, if they are supported.
+/// The production parameters are only supported if their `params-*` feature is enabled.
+#[cfg(feature = "ffi")]
+pub fn param_info(k: usize, s: usize, toy: bool) -> Option<ParamInfo> {
+    lookup(k, s, toy).map(|(_, info)| info())
+}
+
+fn lookup(k: usize, s: usize, toy: bool) -> Option<(OpenFn, fn() -> ParamInfo)> {
+    match (toy, k, s) {
+        (true, 32, 32) => Some(entry::<ToyPreprocK32S32>()),
+        #[cfg(feature = "params-k32")]
+        (false, 32, 32) => Some(entry::<PreprocK32S32>()),
+        #[cfg(feature = "params-k64")]
+        (false, 64, 64) => Some(entry::<PreprocK64S64>()),
+        #[cfg(feature = "params-k128")]
+        (false, 128, 64) => Some(entry::<PreprocK128S64>()),
+        _ => None,
+    }
+}
+
+fn entry<P>() -> (OpenFn, fn() -> ParamInfo)
+where
+    P: PreprocessorParameters,
+{
+    (open::<P>, ParamInfo::of::<P>)
+}
+
+fn open<P>(
+    role: Role,
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    budget: usize,
+) -> BoxFuture<'static, OpenResult>
+where
+    P: PreprocessorParameters,
+{
+    role.dispatch(OpenSession::<P> {
+        local_addr,
+        remote_addr,
+        budget,
+        _params: PhantomData,
+    })
+}
+
+struct OpenSession<P> {
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    budget: usize,
+    _params: PhantomData<P>,
+}
+
+impl<P> RoleVisitor for OpenSession<P>
+where
+    P: PreprocessorParameters,
+{
+    type Output = BoxFuture<'static, OpenResult>;
+
+    fn visit<const PID: usize>(self) -> Self::Output {
+        Box::pin(open_session::<P, PID>(
+            self.local_addr,
+            self.remote_addr,
+            self.budget,
+        ))
+    }
+}
+
+async fn open_session<P, const PID: usize>(
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    budget: usize,
+) -> OpenResult
+where
+    P: PreprocessorParameters,
+{
+    let mut conn = Connection::new(local_addr, remote_addr)
+        .await
+        .map_err(|err| SessionError::FailedToOpen(RunError::FailedToConnect(err)))?;
+    let inner = LowGearPreprocessor::<P, PID>::new(&mut conn)
+        .await
+        .map_err(|err| SessionError::FailedToOpen(RunError::FailedToSetUp(err)))?;
+    let mut verifier = TripleVerifier::new(&mut conn.fork(), inner.mac_key().clone())
+        .await
+        .map_err(|err| SessionError::FailedToOpen(RunError::FailedToOpen(err)))?;
+    verifier.set_crypto_suite(inner.crypto_suite());
+    let mut mac_key = MacKeyLimbs(vec![0; limbs::<P::S>()]);
+    write_limbs(inner.mac_key().expose_secret(), &mut mac_key.0);
+    let preproc = BufferedPreprocessor::with_connection(&mut conn.fork(), inner, budget)
+        .await
+        .map_err(SessionError::FailedToBuffer)?;
+    Ok(Box::new(Session {
+        preproc,
+        verifier,
+        mac_key,
+        param_info: ParamInfo::of::<P>(),
+        conn,
+    }))
+}
+
+fn limbs<R>() -> usize
+where
+    R: GenericNativeResidue,
+{
+    R::BITS.div_ceil(Limb::BITS)
+}
+
+fn write_limbs<R>(residue: R, out: &mut [u64])
+where
+    R: GenericNativeResidue,
+{
+    let uint = residue.retrieve();
+    for (dst, limb) in out.iter_mut().zip(GenericUint::limbs(&uint)) {
+        *dst = u64::from(*limb);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn try_connect(player: usize, k: usize, threads: usize) -> Result<(), SessionError> {
+        // The arguments are validated before connecting, so nothing listens on these ports.
+        connect(
+            player,
+            "[::1]:50155",
+            "[::1]:50156",
+            k,
+            32,
+            true,
+            0,
+            threads,
+        )
+        .map(|_| ())
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        assert!(matches!(
+            try_connect(2, 32, 1),
+            Err(SessionError::InvalidPlayer)
+        ));
+        assert!(matches!(
+            try_connect(0, 32, 0),
+            Err(SessionError::InvalidThreads)
+        ));
+        assert!(matches!(
+            try_connect(0, 16, 1),
+            Err(SessionError::UnsupportedParameters)
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+//! Golden transcripts for the toy parameters, which guard against accidental changes of the wire
+//! format and serve as test vectors for other implementations.
+//!
+//! The transcript consists of named messages (keys, ciphertexts, packed plaintexts, challenges,
+//! triples, ...) that are derived from a fixed seed and serialized with `bincode`, as they are sent
+//! over the wire.  It is stored in `testdata/toy_k32s32.bin` as a `bincode`-serialized
+//! `Vec<(String, Vec<u8>)>`.
+//!
+//! If the wire format is changed deliberately, regenerate the file with
+//! `MULTIPARS_BLESS=1 cargo test testdata` and commit it.  Otherwise, the test fails if the file
+//! is missing.
+//!
+//! Key generation and encryption sample their randomness internally, so the keys and ciphertexts
+//! of the transcript are sampled directly from the seed instead.  They are well-formed messages but
+//! not valid encryptions, which does not matter for the wire format.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crypto_bigint::Random;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+
+use crate::bgv::params::{ToyBgv, ToyCipher, ToyPlain};
+use crate::bgv::poly::crt::CrtPoly;
+use crate::bgv::poly::power::PowerPoly;
+use crate::bgv::poly::{CrtContext, PolyParameters};
+use crate::bgv::tweaked_interpolation_packing::{get_random_unpacked, pack, pack_mask, unpack};
+use crate::bgv::zkpopk::Challenge;
+use crate::bgv::{self, Ciphertext, Cleartext, PreCiphertext, PublicKey, SecretKey};
+use crate::interface::{BeaverTriple, Share};
+use crate::low_gear_preproc::params::ToyPreprocK32S32;
+use crate::low_gear_preproc::PreprocessorParameters;
+
+type KS = <ToyPreprocK32S32 as PreprocessorParameters>::KS;
+type K = <ToyPreprocK32S32 as PreprocessorParameters>::K;
+#[allow(clippy::upper_case_acronyms)]
+type KSS = <ToyPreprocK32S32 as PreprocessorParameters>::KSS;
+
+const SEED: [u8; 32] = *b"multipars golden transcript v1\0\0";
+
+struct Transcript(Vec<(String, Vec<u8>)>);
+
+impl Transcript {
+    fn append(&mut self, name: &str, message: &impl Serialize) {
+        self.0
+            .push((name.to_owned(), bincode::serialize(message).unwrap()));
+    }
+}
+
+/// Generates the golden transcript for the toy parameters.
+async fn toy_transcript() -> Vec<(String, Vec<u8>)> {
+    let mut rng = ChaCha20Rng::from_seed(SEED);
+    let mut transcript = Transcript(Vec::new());
+    let ctx_cipher = CrtContext::<ToyCipher>::gen().await;
+    let ctx_plain = CrtContext::<ToyPlain>::gen().await;
+
+    // Keys
+    let sk: SecretKey<ToyBgv> = {
+        let e: Vec<i64> = (0..ToyCipher::CYCLOTOMIC_DEGREE)
+            .map(|_| rng.gen_range(-1..=1))
+            .collect();
+        let mut power_e = PowerPoly::new();
+        power_e.clone_from_i64s(&e);
+        let s = CrtPoly::from_power(&ctx_cipher, &power_e).await;
+        // `SecretKey` is serialized like its polynomial.
+        bincode::deserialize(&bincode::serialize(&s).unwrap()).unwrap()
+    };
+    transcript.append("secret_key", &sk);
+    let pk = PublicKey::<ToyBgv> {
+        b: CrtPoly::random(&mut rng),
+        a: CrtPoly::random(&mut rng),
+    };
+    transcript.append("public_key", &pk);
+
+    // Plaintexts and packing
+    let plaintext = PowerPoly::<ToyPlain>::random(&mut rng);
+    transcript.append("plaintext", &plaintext);
+    transcript.append(
+        "plaintext_crt",
+        &CrtPoly::from_power(&ctx_plain, &plaintext).await,
+    );
+    let unpacked = get_random_unpacked::<ToyPlain, KSS>(&mut rng);
+    transcript.append("unpacked", &unpacked);
+    let packed = pack::<ToyPlain>(&unpacked);
+    transcript.append("packed", &packed);
+    // Only values scaled by `2^{2 delta}`, like products and masks, can be unpacked.
+    let masked = pack_mask::<ToyPlain>(&unpacked);
+    transcript.append("repacked", &unpack::<ToyPlain, K>(&masked).unwrap());
+
+    // Ciphertexts
+    let pre_ciphertext = PreCiphertext::<ToyBgv> {
+        c_0: PowerPoly::random(&mut rng),
+        c_1: PowerPoly::random(&mut rng),
+    };
+    transcript.append("pre_ciphertext", &pre_ciphertext);
+    let mut ciphertext: Ciphertext<ToyBgv> = pre_ciphertext.ciphertext(&ctx_cipher).await;
+    transcript.append("ciphertext", &ciphertext);
+    let cleartext = Cleartext::new(&ctx_cipher, &plaintext).await;
+    transcript.append("cleartext", &cleartext);
+    ciphertext *= &cleartext;
+    transcript.append("product", &ciphertext);
+    transcript.append(
+        "decrypted",
+        &bgv::decrypt(&ctx_cipher, &sk, &ciphertext).await,
+    );
+
+    // ZKPoPK challenge
+    let challenge: Challenge = bincode::deserialize(&rng.gen::<[u8; 32]>()).unwrap();
+    transcript.append("challenge", &challenge);
+
+    // Shares
+    let share = |rng: &mut ChaCha20Rng| Share::<KS, K, 0>::new(KS::random(rng), KS::random(rng));
+    let triple = BeaverTriple::new(share(&mut rng), share(&mut rng), share(&mut rng));
+    transcript.append("triple", &triple);
+
+    transcript.0
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata")
+        .join(name)
+}
+
+#[tokio::test]
+async fn toy_golden_transcript() {
+    let path = golden_path("toy_k32s32.bin");
+    let transcript = toy_transcript().await;
+
+    if env::var_os("MULTIPARS_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, bincode::serialize(&transcript).unwrap()).unwrap();
+        eprintln!("wrote golden transcript {}", path.display());
+        return;
+    }
+
+    let golden = fs::read(&path).unwrap_or_else(|err| {
+        panic!(
+            "cannot read golden transcript {} (see `testdata` for how to generate it): {}",
+            path.display(),
+            err
+        )
+    });
+    let golden: Vec<(String, Vec<u8>)> = bincode::deserialize(&golden).unwrap();
+    let names: Vec<_> = transcript.iter().map(|(name, _)| name).collect();
+    let golden_names: Vec<_> = golden.iter().map(|(name, _)| name).collect();
+    assert_eq!(
+        names, golden_names,
+        "the messages of the transcript changed"
+    );
+    for ((name, bytes), (_, golden_bytes)) in transcript.iter().zip(&golden) {
+        assert!(
+            bytes == golden_bytes,
+            "the encoding of `{}` changed (see `testdata` for how to regenerate)",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn toy_transcript_is_deterministic() {
+    assert_eq!(toy_transcript().await, toy_transcript().await);
+}
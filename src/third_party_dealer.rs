@@ -0,0 +1,373 @@
+//! Authentication by a semi-trusted third party instead of the BGV-based `LowGearDealer`.
+//!
+//! Each party connects to the third party (e.g., a service in an enclave) on a `Connection` of its
+//! own and sends its MAC key share once.  In each call of `authenticate()`, both parties send their
+//! shares of the values, and the third party returns fresh additive shares of the tags.  The third
+//! party thus learns the MAC key and all values, so it must not collude with either party, but the
+//! parties neither need BGV keys for the dealer nor exchange any ciphertexts for it.
+//!
+//! The third party runs `serve()`.  The parties select this backend via `DealerBackend`.
+
+use async_bincode::tokio::{AsyncBincodeReader, AsyncBincodeWriter};
+use async_bincode::AsyncDestination;
+use crypto_bigint::Random;
+use futures_util::{SinkExt, StreamExt};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::bgv::residue::GenericResidue;
+use crate::connection::{Connection, StreamError};
+use crate::interface::MacKeyShare;
+use crate::low_gear_dealer::{DealerError, DealerParameters, DealerState, MessageKind};
+
+/// A message of a party to the third party.
+#[derive(Deserialize, Serialize)]
+#[serde(bound(serialize = "", deserialize = ""))]
+enum Request<P>
+where
+    P: DealerParameters,
+{
+    /// The MAC key share of the party, which is sent once.
+    Init { mac_key: P::S },
+    /// The shares of the values of the `round`-th call of `authenticate()`.
+    Values { round: u64, values: Vec<P::K> },
+}
+
+/// The tag shares of the `round`-th call of `authenticate()`.
+#[derive(Deserialize, Serialize)]
+#[serde(bound(serialize = "", deserialize = ""))]
+struct Tags<P>
+where
+    P: DealerParameters,
+{
+    round: u64,
+    tags: Vec<P::KS>,
+}
+
+/// A party's client of the third party.  Like a `LowGearDealer`, it is `Ready` between the calls
+/// of `authenticate()` and ends in `Failed` once a call fails.
+pub struct ThirdPartyDealer<P>
+where
+    P: DealerParameters,
+{
+    /// The connection to the third party, which is closed when the dealer is dropped.
+    _conn: Connection,
+    bincode_tx: AsyncBincodeWriter<quinn::SendStream, Request<P>, AsyncDestination>,
+    bincode_rx: AsyncBincodeReader<quinn::RecvStream, Tags<P>>,
+    round: u64,
+    state: DealerState,
+}
+
+impl<P> ThirdPartyDealer<P>
+where
+    P: DealerParameters,
+{
+    /// Connects to the third party on `conn`, which must be connected to the address on which the
+    /// third party runs `serve()` for this party.
+    pub async fn new(conn: Connection, mac_key: MacKeyShare<P::S>) -> Result<Self, StreamError> {
+        match Self::try_new(conn, mac_key).await {
+            Ok(dealer) => Ok(dealer),
+            Err(DealerError::FailedToOpen(err)) => Err(err),
+            // TODO: return error instead of unwrapping.
+            Err(err) => panic!("Dealer initialization failed: {}", err),
+        }
+    }
+
+    /// Like `new()`, but also returns an error if the MAC key share can't be sent.
+    pub async fn try_new(
+        mut conn: Connection,
+        mac_key: MacKeyShare<P::S>,
+    ) -> Result<Self, DealerError> {
+        let (tx, rx) = conn
+            .open_bi("ThirdPartyDealer")
+            .await
+            .map_err(DealerError::FailedToOpen)?;
+        let mut bincode_tx = AsyncBincodeWriter::from(tx).for_async();
+        let init = Request::Init {
+            mac_key: mac_key.expose_secret(),
+        };
+        bincode_tx
+            .send(init)
+            .await
+            .map_err(|err| DealerError::FailedToSend(*err))?;
+        Ok(Self {
+            _conn: conn,
+            bincode_tx,
+            bincode_rx: AsyncBincodeReader::from(rx),
+            round: 0,
+            state: DealerState::Ready,
+        })
+    }
+
+    pub fn state(&self) -> DealerState {
+        self.state
+    }
+
+    /// Number of completed calls of `authenticate()`.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Returns the MAC tag shares of `values`.  Both parties must call this with the same number of
+    /// values.
+    pub async fn authenticate(&mut self, values: &[P::K]) -> Vec<P::KS> {
+        // TODO: return error instead of unwrapping.
+        self.try_authenticate(values).await.unwrap()
+    }
+
+    /// Like `authenticate()`, but returns an error if the third party fails to answer, e.g.,
+    /// because the other party is in another round.
+    pub async fn try_authenticate(&mut self, values: &[P::K]) -> Result<Vec<P::KS>, DealerError> {
+        if self.state != DealerState::Ready {
+            return Err(DealerError::NotReady(self.state));
+        }
+        self.state = DealerState::Authenticate {
+            round: self.round,
+            received: 0,
+            expected: 1,
+        };
+        let result = self.exchange(values).await;
+        self.state = match result {
+            Ok(_) => {
+                self.round += 1;
+                DealerState::Ready
+            }
+            Err(_) => DealerState::Failed,
+        };
+        result
+    }
+
+    async fn exchange(&mut self, values: &[P::K]) -> Result<Vec<P::KS>, DealerError> {
+        let request = Request::Values {
+            round: self.round,
+            values: values.to_vec(),
+        };
+        self.bincode_tx
+            .send(request)
+            .await
+            .map_err(|err| DealerError::FailedToSend(*err))?;
+        let Tags { round, tags } = match self.bincode_rx.next().await {
+            Some(Ok(tags)) => tags,
+            Some(Err(err)) => return Err(DealerError::FailedToReceive(*err)),
+            None => return Err(DealerError::ConnectionClosed),
+        };
+        if round != self.round {
+            return Err(DealerError::TagsOutOfOrder {
+                expected_round: self.round,
+                expected_chunk: 0,
+                round,
+                chunk: 0,
+            });
+        }
+        if tags.len() != values.len() {
+            return Err(DealerError::UnexpectedMessage {
+                state: self.state,
+                received: MessageKind::Tags,
+            });
+        }
+        Ok(tags)
+    }
+
+    /// Finishes the stream and waits until the third party finishes its stream as well, such that
+    /// the connection is only closed once the third party is done.
+    pub async fn finish(mut self) {
+        let _ = self.bincode_tx.into_inner().finish().await;
+        let _ = self.bincode_rx.next().await;
+    }
+}
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum ServiceError {
+    FailedToOpen(StreamError),
+    FailedToSend(bincode::ErrorKind),
+    FailedToReceive(bincode::ErrorKind),
+    /// A party closed its stream before the other one.
+    ConnectionClosed,
+    /// A party did not start with its MAC key share, or sent it again.
+    UnexpectedRequest,
+    /// The parties are in different rounds or authenticate different numbers of values.
+    #[display(
+        fmt = "party 0 sent {} values of round {}, but party 1 sent {} values of round {}",
+        len0,
+        round0,
+        len1,
+        round1
+    )]
+    Mismatch {
+        round0: u64,
+        len0: usize,
+        round1: u64,
+        len1: usize,
+    },
+}
+
+/// Runs the third party for the parties on `conn0` and `conn1` until both parties finish their
+/// `ThirdPartyDealer`.  On an error, drop the connections, so that the parties fail instead of
+/// waiting for their tags.
+pub async fn serve<P>(conn0: &mut Connection, conn1: &mut Connection) -> Result<(), ServiceError>
+where
+    P: DealerParameters,
+{
+    let (stream0, stream1) = tokio::join!(
+        conn0.open_bi("ThirdPartyDealer"),
+        conn1.open_bi("ThirdPartyDealer")
+    );
+    let (tx0, rx0) = stream0.map_err(ServiceError::FailedToOpen)?;
+    let (tx1, rx1) = stream1.map_err(ServiceError::FailedToOpen)?;
+    let mut tx = [tx0, tx1].map(|tx| AsyncBincodeWriter::<_, Tags<P>, _>::from(tx).for_async());
+    let mut rx = [rx0, rx1].map(AsyncBincodeReader::<_, Request<P>>::from);
+
+    let (init0, init1) = {
+        let [rx0, rx1] = &mut rx;
+        tokio::join!(rx0.next(), rx1.next())
+    };
+    let mac_key = match (init0, init1) {
+        (Some(Ok(Request::Init { mac_key: key0 })), Some(Ok(Request::Init { mac_key: key1 }))) => {
+            P::KS::from_unsigned(key0) + P::KS::from_unsigned(key1)
+        }
+        (Some(Err(err)), _) | (_, Some(Err(err))) => {
+            return Err(ServiceError::FailedToReceive(*err))
+        }
+        (None, _) | (_, None) => return Err(ServiceError::ConnectionClosed),
+        _ => return Err(ServiceError::UnexpectedRequest),
+    };
+    info!("ThirdPartyDealer: received the MAC key shares");
+
+    loop {
+        let (request0, request1) = {
+            let [rx0, rx1] = &mut rx;
+            tokio::join!(rx0.next(), rx1.next())
+        };
+        let (round, values0, values1) = match (request0, request1) {
+            (None, None) => break,
+            (
+                Some(Ok(Request::Values {
+                    round: round0,
+                    values: values0,
+                })),
+                Some(Ok(Request::Values {
+                    round: round1,
+                    values: values1,
+                })),
+            ) => {
+                if round0 != round1 || values0.len() != values1.len() {
+                    return Err(ServiceError::Mismatch {
+                        round0,
+                        len0: values0.len(),
+                        round1,
+                        len1: values1.len(),
+                    });
+                }
+                (round0, values0, values1)
+            }
+            (Some(Err(err)), _) | (_, Some(Err(err))) => {
+                return Err(ServiceError::FailedToReceive(*err))
+            }
+            (None, _) | (_, None) => return Err(ServiceError::ConnectionClosed),
+            _ => return Err(ServiceError::UnexpectedRequest),
+        };
+
+        let (tags0, tags1): (Vec<_>, Vec<_>) = {
+            let mut rng = rand::thread_rng();
+            values0
+                .iter()
+                .zip(&values1)
+                .map(|(x0, x1)| {
+                    let tag = (P::KS::from_unsigned(*x0) + P::KS::from_unsigned(*x1)) * mac_key;
+                    let tag0 = P::KS::random(&mut rng);
+                    (tag0, tag - tag0)
+                })
+                .unzip()
+        };
+        let [tx0, tx1] = &mut tx;
+        let (sent0, sent1) = tokio::join!(
+            tx0.send(Tags { round, tags: tags0 }),
+            tx1.send(Tags { round, tags: tags1 })
+        );
+        sent0.map_err(|err| ServiceError::FailedToSend(*err))?;
+        sent1.map_err(|err| ServiceError::FailedToSend(*err))?;
+    }
+
+    let [mut tx0, mut tx1] = tx.map(|tx| tx.into_inner());
+    let _ = tokio::join!(tx0.finish(), tx1.finish());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use crate::connection::Connection;
+    use crate::dealer::DealerBackend;
+    use crate::interface::{reconstruct, BatchedPreprocessor};
+    use crate::low_gear_dealer::DealerState;
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::{LowGearPreprocessor, PreprocessorParameters};
+
+    use super::serve;
+
+    type P = ToyPreprocK32S32;
+    type KS = <P as PreprocessorParameters>::KS;
+
+    #[tokio::test]
+    async fn preprocessor_with_third_party_dealer() {
+        let addr = |port: u16| -> SocketAddr { format!("[::1]:{}", port).parse().unwrap() };
+        let (p0_addr, p1_addr) = (addr(50125), addr(50126));
+        let (p0_dealer_addr, dealer_p0_addr) = (addr(50127), addr(50128));
+        let (p1_dealer_addr, dealer_p1_addr) = (addr(50129), addr(50130));
+
+        let (conn0, conn1, dealer_conn0, dealer_conn1, service_conn0, service_conn1) = tokio::join!(
+            Connection::new(p0_addr, p1_addr),
+            Connection::new(p1_addr, p0_addr),
+            Connection::new(p0_dealer_addr, dealer_p0_addr),
+            Connection::new(p1_dealer_addr, dealer_p1_addr),
+            Connection::new(dealer_p0_addr, p0_dealer_addr),
+            Connection::new(dealer_p1_addr, p1_dealer_addr)
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let (mut service_conn0, mut service_conn1) =
+            (service_conn0.unwrap(), service_conn1.unwrap());
+        let service = tokio::task::spawn(async move {
+            serve::<<P as PreprocessorParameters>::DealerParams>(
+                &mut service_conn0,
+                &mut service_conn1,
+            )
+            .await
+        });
+
+        let (preproc0, preproc1) = tokio::join!(
+            LowGearPreprocessor::<P, 0>::builder()
+                .dealer(DealerBackend::ThirdParty(dealer_conn0.unwrap()))
+                .build(&mut conn0),
+            LowGearPreprocessor::<P, 1>::builder()
+                .dealer(DealerBackend::ThirdParty(dealer_conn1.unwrap()))
+                .build(&mut conn1)
+        );
+        let (mut preproc0, mut preproc1) = (preproc0.unwrap(), preproc1.unwrap());
+        let (triples0, triples1) = tokio::join!(
+            preproc0.try_get_beaver_triples(),
+            preproc1.try_get_beaver_triples()
+        );
+        let (triples0, triples1) = (triples0.unwrap(), triples1.unwrap());
+        assert_eq!(preproc0.dealer_state(), DealerState::Ready);
+
+        let mac_key = preproc0.mac_key().widen::<KS>() + preproc1.mac_key().widen::<KS>();
+        assert_eq!(triples0.len(), triples1.len());
+        for (triple0, triple1) in triples0.iter().zip(&triples1) {
+            let a = reconstruct(&triple0.a, &triple1.a);
+            let b = reconstruct(&triple0.b, &triple1.b);
+            assert_eq!(a * b, reconstruct(&triple0.c, &triple1.c));
+            for (share0, share1) in [
+                (&triple0.a, &triple1.a),
+                (&triple0.b, &triple1.b),
+                (&triple0.c, &triple1.c),
+            ] {
+                assert_eq!(share0.tag + share1.tag, (share0.val + share1.val) * mac_key);
+            }
+        }
+
+        // The service stops once both dealers are finished.
+        tokio::join!(preproc0.finish(), preproc1.finish());
+        service.await.unwrap().unwrap();
+    }
+}
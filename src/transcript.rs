@@ -0,0 +1,138 @@
+//! Session transcript hash.
+//!
+//! Both parties hash the messages exchanged during session setup (e.g. the public keys) in the same
+//! order.  Seeds that are derived later on are bound to the resulting session ID, so that protocol
+//! messages cannot be replayed in another session.  The hash function is given by a
+//! `CryptoSuite`.
+
+use serde::Serialize;
+
+use crate::crypto_suite::{CryptoSuite, Hasher};
+
+pub type SessionId = [u8; 32];
+
+#[derive(Clone)]
+pub struct Transcript {
+    suite: CryptoSuite,
+    hasher: Hasher,
+}
+
+impl Transcript {
+    /// A transcript with the default `CryptoSuite`.
+    pub fn new(protocol: &str) -> Self {
+        Self::with_suite(CryptoSuite::default(), protocol)
+    }
+
+    pub fn with_suite(suite: CryptoSuite, protocol: &str) -> Self {
+        let mut transcript = Self {
+            suite,
+            hasher: suite.hasher(),
+        };
+        transcript.append_bytes("protocol", protocol.as_bytes());
+        transcript
+    }
+
+    pub fn suite(&self) -> CryptoSuite {
+        self.suite
+    }
+
+    /// Appends the `bincode` serialization of `msg`.
+    pub fn append<T>(&mut self, label: &str, msg: &T)
+    where
+        T: Serialize,
+    {
+        let bytes = bincode::serialize(msg).unwrap();
+        self.append_bytes(label, &bytes);
+    }
+
+    pub fn append_bytes(&mut self, label: &str, bytes: &[u8]) {
+        // Length prefixes make the encoding injective.
+        self.hasher.update((label.len() as u64).to_le_bytes());
+        self.hasher.update(label.as_bytes());
+        self.hasher.update((bytes.len() as u64).to_le_bytes());
+        self.hasher.update(bytes);
+    }
+
+    pub fn session_id(&self) -> SessionId {
+        self.hasher.clone().finalize()
+    }
+}
+
+/// Derives a seed for the purpose given by `label` from `input` (e.g. a jointly sampled seed),
+/// bound to the session.
+pub fn derive_seed(
+    suite: CryptoSuite,
+    session_id: &SessionId,
+    label: &str,
+    input: &[u8; 32],
+) -> [u8; 32] {
+    let mut transcript = Transcript::with_suite(suite, "derive_seed");
+    transcript.append_bytes("session_id", session_id);
+    transcript.append_bytes(label, input);
+    transcript.session_id()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_suite::CryptoSuite;
+
+    use super::{derive_seed, Transcript};
+
+    #[test]
+    fn session_id_depends_on_messages_and_order() {
+        let mut t_0 = Transcript::new("test");
+        t_0.append("x", &1u64);
+        t_0.append("y", &2u64);
+
+        let mut t_1 = Transcript::new("test");
+        t_1.append("x", &1u64);
+        t_1.append("y", &2u64);
+        assert_eq!(t_0.session_id(), t_1.session_id());
+
+        let mut t_2 = Transcript::new("test");
+        t_2.append("y", &2u64);
+        t_2.append("x", &1u64);
+        assert_ne!(t_0.session_id(), t_2.session_id());
+
+        let mut t_3 = Transcript::new("test");
+        t_3.append("x", &1u64);
+        t_3.append("y", &3u64);
+        assert_ne!(t_0.session_id(), t_3.session_id());
+    }
+
+    #[test]
+    fn derived_seed_depends_on_session() {
+        let input = [7; 32];
+        let suite = CryptoSuite::default();
+        let seed_0 = derive_seed(suite, &[0; 32], "label", &input);
+        let seed_1 = derive_seed(suite, &[1; 32], "label", &input);
+        let seed_2 = derive_seed(suite, &[0; 32], "other", &input);
+        assert_ne!(seed_0, seed_1);
+        assert_ne!(seed_0, seed_2);
+    }
+
+    #[test]
+    fn session_id_depends_on_suite() {
+        let session_ids: Vec<_> = CryptoSuite::ALL
+            .iter()
+            .map(|&suite| {
+                let mut transcript = Transcript::with_suite(suite, "test");
+                transcript.append("x", &1u64);
+                assert_eq!(transcript.suite(), suite);
+                transcript.session_id()
+            })
+            .collect();
+        for (i, session_id) in session_ids.iter().enumerate() {
+            assert!(!session_ids[i + 1..].contains(session_id));
+        }
+        assert_eq!(
+            session_ids[0],
+            {
+                let mut transcript = Transcript::new("test");
+                transcript.append("x", &1u64);
+                transcript.session_id()
+            },
+            "the default suite is SHA-256"
+        );
+    }
+}
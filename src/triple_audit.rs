@@ -0,0 +1,363 @@
+//! Audit mode, in which a fraction of the produced triples is sacrificed to check the deployment.
+//!
+//! The audited triples are opened completely, i.e., both parties learn `a`, `b` and `c`, and check
+//! that `c = a b` holds modulo `2^k` and that the MAC tags are valid.  The opened triples must not
+//! be used afterwards, so auditing costs triples and should only be enabled deliberately, e.g.
+//! while bringing up a new deployment.
+
+use std::fmt;
+use std::ops::AddAssign;
+
+use async_trait::async_trait;
+use log::{error, info, warn};
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::connection::{Connection, StreamError};
+use crate::interface::{BeaverTriple, MacKeyShare, Preprocessor, Share};
+use crate::mac_check_opener::MacCheckOpener;
+use crate::triple_verifier::triple_mask;
+
+/// Results of the audits performed so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AuditStats {
+    /// Number of triples that were handed out to the caller.
+    pub delivered: u64,
+    /// Number of triples that were opened and checked.
+    pub audited: u64,
+    /// Number of audited triples for which `c != a b`.
+    pub product_failures: u64,
+    /// Number of audits whose MAC check failed.
+    pub mac_check_failures: u64,
+}
+
+impl AuditStats {
+    pub fn passed(&self) -> bool {
+        self.product_failures == 0 && self.mac_check_failures == 0
+    }
+}
+
+impl AddAssign for AuditStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.delivered += rhs.delivered;
+        self.audited += rhs.audited;
+        self.product_failures += rhs.product_failures;
+        self.mac_check_failures += rhs.mac_check_failures;
+    }
+}
+
+impl fmt::Display for AuditStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: audited {} triples ({} delivered), {} product failures, {} MAC check failures",
+            if self.passed() { "PASS" } else { "FAIL" },
+            self.audited,
+            self.delivered,
+            self.product_failures,
+            self.mac_check_failures
+        )
+    }
+}
+
+pub struct TripleAuditor<KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    opener: MacCheckOpener<KS, S>,
+    stats: AuditStats,
+}
+
+impl<KS, S> TripleAuditor<KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    /// `conn` connects to the other party.  It must not be shared with another `MacCheckOpener`
+    /// (e.g. the one of a `LowGearPreprocessor`), so use a fork if necessary.
+    pub async fn new(conn: &mut Connection, mac_key: MacKeyShare<S>) -> Result<Self, StreamError> {
+        Ok(Self {
+            opener: MacCheckOpener::new(conn, mac_key).await?,
+            stats: AuditStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> AuditStats {
+        self.stats
+    }
+
+    /// Records that `n` triples were handed out without being audited.
+    pub fn record_delivered(&mut self, n: usize) {
+        self.stats.delivered += n as u64;
+    }
+
+    /// Opens and checks the given triples, which must not be used afterwards.  The first triple
+    /// is consumed as mask for the MAC check, so `triples.len() - 1` triples are audited.  Both
+    /// parties must pass their shares of the same triples.
+    pub async fn audit<K, const PID: usize>(&mut self, triples: &[BeaverTriple<KS, K, PID>])
+    where
+        K: GenericNativeResidue,
+    {
+        let Some((mask, audited)) = triples.split_first() else {
+            return;
+        };
+        if audited.is_empty() {
+            return;
+        }
+
+        let shares: Vec<Share<KS, K, PID>> = audited
+            .iter()
+            .flat_map(|triple| [triple.a, triple.b, triple.c])
+            .collect();
+        let opened = match self.opener.open_unchecked(&shares).await {
+            Ok(opened) => opened,
+            Err(_) => {
+                error!("TripleAuditor: failed to open {} triples", audited.len());
+                self.stats.audited += audited.len() as u64;
+                self.stats.mac_check_failures += 1;
+                return;
+            }
+        };
+        let product_failures = opened
+            .chunks_exact(3)
+            .filter(|abc| abc[0] * abc[1] != abc[2])
+            .count() as u64;
+
+        // The upper bits of the mask are not opened, so they hide the upper bits of the
+        // combination.
+        let mac_check = self
+            .opener
+            .batch_check(shares.into_iter(), triple_mask(&self.opener, mask))
+            .await;
+
+        self.stats.audited += audited.len() as u64;
+        self.stats.product_failures += product_failures;
+        if product_failures > 0 {
+            error!(
+                "TripleAuditor: {} of {} triples are incorrect",
+                product_failures,
+                audited.len()
+            );
+        }
+        if mac_check.is_err() {
+            error!("TripleAuditor: MAC check failed");
+            self.stats.mac_check_failures += 1;
+        }
+        info!("TripleAuditor: {}", self.stats);
+    }
+
+    pub async fn finish(self) {
+        self.opener.finish().await;
+    }
+}
+
+/// Wraps a `Preprocessor` and audits a fraction of its triples before handing out the rest.
+///
+/// For every `n` requested triples, roughly `fraction * n` additional triples (plus one mask per
+/// request) are obtained from `inner` and sacrificed.  The number of audited triples only depends
+/// on the requested amounts, so both parties audit the same triples.
+pub struct AuditingPreprocessor<Preproc, KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    inner: Preproc,
+    auditor: TripleAuditor<KS, S>,
+    fraction: f64,
+}
+
+impl<Preproc, KS, S> AuditingPreprocessor<Preproc, KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    /// Audits `fraction` (between 0 and 1) of the triples of `inner`.
+    pub fn new(inner: Preproc, auditor: TripleAuditor<KS, S>, fraction: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "audit fraction must be between 0 and 1"
+        );
+        warn!(
+            "Audit mode enabled: {}% of the triples are opened and discarded",
+            fraction * 100.0
+        );
+        Self {
+            inner,
+            auditor,
+            fraction,
+        }
+    }
+
+    pub fn stats(&self) -> AuditStats {
+        self.auditor.stats()
+    }
+
+    /// Number of triples to audit such that `fraction` of all triples are audited after `n` more
+    /// triples are delivered.
+    fn num_to_audit(&self, n: usize) -> usize {
+        let stats = self.auditor.stats();
+        let target = ((stats.delivered + n as u64) as f64 * self.fraction).ceil() as u64;
+        target.saturating_sub(stats.audited) as usize
+    }
+}
+
+#[async_trait]
+impl<Preproc, KS, K, S, const PID: usize> Preprocessor<KS, K, PID>
+    for AuditingPreprocessor<Preproc, KS, S>
+where
+    Preproc: Preprocessor<KS, K, PID> + Send,
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    async fn get_beaver_triples(&mut self, n: usize) -> Vec<BeaverTriple<KS, K, PID>> {
+        let num_audited = self.num_to_audit(n);
+        if num_audited > 0 {
+            let sacrificed = self.inner.get_beaver_triples(num_audited + 1).await;
+            self.auditor.audit(&sacrificed).await;
+        }
+        self.auditor.record_delivered(n);
+        self.inner.get_beaver_triples(n).await
+    }
+
+    async fn finish(self) {
+        info!("TripleAuditor: {}", self.auditor.stats());
+        self.auditor.finish().await;
+        self.inner.finish().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use async_trait::async_trait;
+    use crypto_bigint::Random;
+
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::{BeaverTriple, MacKeyShare, Preprocessor, Share};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+
+    use super::{AuditStats, AuditingPreprocessor, TripleAuditor};
+
+    type P = ToyPreprocK32S32;
+    type KS = <P as PreprocessorParameters>::KS;
+    type K = <P as PreprocessorParameters>::K;
+    type S = <P as PreprocessorParameters>::S;
+
+    /// Deals correct (or, if `faulty`, incorrect) triples from a PRNG with a shared seed.
+    struct FakePreprocessor<const PID: usize> {
+        prng: rand_chacha::ChaCha20Rng,
+        mac_key: KS,
+        faulty: bool,
+    }
+
+    impl<const PID: usize> FakePreprocessor<PID> {
+        fn share(&mut self, val: KS) -> Share<KS, K, PID> {
+            let tag = val * self.mac_key;
+            let (val_mask, tag_mask) = (KS::random(&mut self.prng), KS::random(&mut self.prng));
+            if PID == 0 {
+                Share::new(val - val_mask, tag - tag_mask)
+            } else {
+                Share::new(val_mask, tag_mask)
+            }
+        }
+    }
+
+    #[async_trait]
+    impl<const PID: usize> Preprocessor<KS, K, PID> for FakePreprocessor<PID> {
+        async fn get_beaver_triples(&mut self, n: usize) -> Vec<BeaverTriple<KS, K, PID>> {
+            (0..n)
+                .map(|_| {
+                    let a = KS::random(&mut self.prng);
+                    let b = KS::random(&mut self.prng);
+                    let c = if self.faulty {
+                        a * b + KS::from_i64(1)
+                    } else {
+                        a * b
+                    };
+                    BeaverTriple {
+                        a: self.share(a),
+                        b: self.share(b),
+                        c: self.share(c),
+                        phantom: PhantomData,
+                    }
+                })
+                .collect()
+        }
+
+        async fn finish(self) {}
+    }
+
+    #[tokio::test]
+    async fn audit() {
+        assert_eq!(run_audit(false, "[::1]:50073", "[::1]:50074").await, (0, 0));
+        assert_eq!(run_audit(true, "[::1]:50075", "[::1]:50076").await, (0, 10));
+    }
+
+    /// Returns the number of failed MAC checks and of incorrect triples.
+    async fn run_audit(faulty: bool, p0_addr: &'static str, p1_addr: &'static str) -> (u64, u64) {
+        let mac_keys = [
+            S::random(&mut rand::thread_rng()),
+            S::random(&mut rand::thread_rng()),
+        ];
+        let mac_key = KS::from_unsigned(mac_keys[0]) + KS::from_unsigned(mac_keys[1]);
+        let seed = rand::random();
+
+        let p0 = tokio::task::spawn(run_party::<0>(
+            p0_addr,
+            p1_addr,
+            mac_keys[0],
+            mac_key,
+            seed,
+            faulty,
+        ));
+        let p1 = tokio::task::spawn(run_party::<1>(
+            p1_addr,
+            p0_addr,
+            mac_keys[1],
+            mac_key,
+            seed,
+            faulty,
+        ));
+        let (stats0, stats1) = tokio::try_join!(p0, p1).unwrap();
+        assert_eq!(stats0, stats1);
+        assert_eq!(stats0.delivered, 100);
+        assert_eq!(stats0.audited, 10);
+        if faulty {
+            assert!(!stats0.passed());
+        }
+        (stats0.mac_check_failures, stats0.product_failures)
+    }
+
+    async fn run_party<const PID: usize>(
+        local: &'static str,
+        remote: &'static str,
+        local_mac_key: S,
+        mac_key: KS,
+        seed: [u8; 32],
+        faulty: bool,
+    ) -> AuditStats {
+        let mut conn = Connection::new(local.parse().unwrap(), remote.parse().unwrap())
+            .await
+            .unwrap();
+        let auditor = TripleAuditor::new(&mut conn, MacKeyShare::from_secret(local_mac_key))
+            .await
+            .unwrap();
+        let inner = FakePreprocessor::<PID> {
+            prng: rand::SeedableRng::from_seed(seed),
+            mac_key,
+            faulty,
+        };
+        let mut preproc = AuditingPreprocessor::new(inner, auditor, 0.1);
+        for _ in 0..2 {
+            let triples = Preprocessor::<KS, K, PID>::get_beaver_triples(&mut preproc, 50).await;
+            assert_eq!(triples.len(), 50);
+        }
+        let stats = preproc.stats();
+        Preprocessor::<KS, K, PID>::finish(preproc).await;
+        stats
+    }
+}
@@ -0,0 +1,458 @@
+//! Verification of Beaver triples that were generated by an untrusted delegate (e.g. in outsourced
+//! preprocessing), without running the generation pipeline.
+//!
+//! The triples are checked by sacrificing: for each pair of triples `(a, b, c)` and `(a', b', c')`
+//! and a random challenge `t` in `S`, the parties open `rho = t a - a'` and `sigma = b - b'` and
+//! check that `t c - c' - sigma a' - rho b' - sigma rho` is zero.  As in SPDZ2k, this is computed
+//! modulo `2^{k+s}`: modulo `2^k`, an error of `2^{k-1}` in `c` would pass for every even `t`.  So
+//! `rho` and `sigma` are opened modulo `2^{k+s}`, and the values `a'` and `b'` of the sacrificed
+//! triples only hide the upper bits of `a` and `b` if they are uniformly random modulo `2^{k+s}`.
+//! The MAC tags of all opened values are checked as well.  The challenges are derived from a seed
+//! to which both parties commit before either opens its part, so that neither party can choose
+//! them.
+
+use futures_util::{SinkExt, StreamExt};
+use log::{error, info};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::bgv::residue::native::GenericNativeResidue;
+use crate::bi_channel::BiChannel;
+use crate::codec;
+use crate::commitment::{self, Commitment, Opening};
+use crate::connection::{Connection, StreamError};
+use crate::crypto_suite::CryptoSuite;
+use crate::interface::{BeaverTriple, MacKeyShare, RawTriple, Share};
+use crate::mac_check_opener::{MacCheckFailed, MacCheckOpener};
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum VerificationError {
+    FailedToDeserialize(bincode::ErrorKind),
+    /// The serialized triples exceed the limit of `set_max_serialized_size()`.
+    TooLarge,
+    MacCheckFailed(MacCheckFailed),
+    SacrificeFailed,
+    /// The other party's opening of its part of the seed does not match its commitment.
+    SeedMismatch,
+    FailedToSend(bincode::ErrorKind),
+    FailedToReceive(bincode::ErrorKind),
+    ConnectionClosed,
+}
+
+pub struct TripleVerifier<KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    opener: MacCheckOpener<KS, S>,
+    ch_seed_commitment: BiChannel<Commitment<[u8; 32]>>,
+    ch_seed_opening: BiChannel<Opening<[u8; 32]>>,
+    mac_key: MacKeyShare<S>,
+    max_serialized_size: u64,
+    crypto_suite: CryptoSuite,
+}
+
+impl<KS, S> TripleVerifier<KS, S>
+where
+    KS: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    /// `conn` connects to the other party.  It must not be shared with a `MacCheckOpener` (e.g. the
+    /// one of a `LowGearPreprocessor`), so use a fork if necessary.
+    pub async fn new(conn: &mut Connection, mac_key: MacKeyShare<S>) -> Result<Self, StreamError> {
+        Ok(Self {
+            opener: MacCheckOpener::new(conn, mac_key.clone()).await?,
+            ch_seed_commitment: BiChannel::open(conn, "TripleVerifier:seed_commitment").await?,
+            ch_seed_opening: BiChannel::open(conn, "TripleVerifier:seed_opening").await?,
+            mac_key,
+            max_serialized_size: codec::MAX_MESSAGE_SIZE,
+            crypto_suite: CryptoSuite::default(),
+        })
+    }
+
+    /// Sets the hash function of the commitments and of the seed of the challenges, e.g., to the
+    /// one of the preprocessor (see `LowGearPreprocessor::crypto_suite()`).  Both parties must use
+    /// the same suite.
+    pub fn set_crypto_suite(&mut self, suite: CryptoSuite) {
+        self.crypto_suite = suite;
+        self.opener.set_crypto_suite(suite);
+    }
+
+    /// Limits the size of the input of `verify_serialized()`, which defaults to
+    /// `codec::MAX_MESSAGE_SIZE`.
+    pub fn set_max_serialized_size(&mut self, max_bytes: u64) {
+        self.max_serialized_size = max_bytes;
+    }
+
+    /// Like `verify()`, but takes the triples as serialized by `bincode::serialize()`.  Fails with
+    /// `TooLarge` if there are more bytes than allowed by `set_max_serialized_size()`, without
+    /// exchanging any messages, so both parties must pass the same number of bytes.
+    pub async fn verify_serialized<K, const PID: usize>(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<Vec<BeaverTriple<KS, K, PID>>, VerificationError>
+    where
+        K: GenericNativeResidue,
+    {
+        if bytes.len() as u64 > self.max_serialized_size {
+            return Err(VerificationError::TooLarge);
+        }
+        let triples: Vec<_> = codec::deserialize(bytes)
+            .map_err(|err| VerificationError::FailedToDeserialize(*err))?;
+        self.verify(&triples).await
+    }
+
+    /// Verifies the given triples and returns the ones that may be used.  The first triple is
+    /// consumed as the mask of the MAC check and half of the remaining ones are sacrificed, so
+    /// `(triples.len() - 1) / 2` triples are returned.
+    pub async fn verify<K, const PID: usize>(
+        &mut self,
+        triples: &[BeaverTriple<KS, K, PID>],
+    ) -> Result<Vec<BeaverTriple<KS, K, PID>>, VerificationError>
+    where
+        K: GenericNativeResidue,
+    {
+        let Some((mask, rest)) = triples.split_first() else {
+            return Ok(Vec::new());
+        };
+        let num_checked = rest.len() / 2;
+        let (checked, sacrificed) = rest[..2 * num_checked].split_at(num_checked);
+
+        let mut prng = ChaCha20Rng::from_seed(self.joint_seed().await?);
+        let challenges: Vec<_> = (0..num_checked)
+            .map(|_| KS::from_unsigned(S::random(&mut prng)))
+            .collect();
+
+        let masked: Vec<Share<KS, KS, PID>> = checked
+            .iter()
+            .zip(sacrificed)
+            .zip(&challenges)
+            .map(|((x, y), t)| widen(&x.a) * *t - widen(&y.a))
+            .chain(
+                checked
+                    .iter()
+                    .zip(sacrificed)
+                    .map(|(x, y)| widen(&x.b) - widen(&y.b)),
+            )
+            .collect();
+        let opened = self
+            .opener
+            .open_unchecked(&masked)
+            .await
+            .map_err(VerificationError::MacCheckFailed)?;
+        let (rhos, sigmas) = opened.split_at(num_checked);
+
+        // Each `z` is zero modulo `2^{k+s}`, so the combination reveals nothing and needs no mask.
+        let mut zero = Share::<KS, KS, PID>::ZERO;
+        for (((x, y), t), (rho, sigma)) in checked
+            .iter()
+            .zip(sacrificed)
+            .zip(&challenges)
+            .zip(rhos.iter().zip(sigmas))
+        {
+            let z = (widen(&x.c) * *t - widen(&y.c) - widen(&y.a) * *sigma - widen(&y.b) * *rho)
+                .add_public(KS::ZERO - *sigma * *rho, &self.mac_key);
+            zero += z * KS::from_unsigned(S::random(&mut prng));
+        }
+
+        // The MAC tags only authenticate the values modulo `2^k`, so the openings are checked in `K`.
+        let opened_shares = masked
+            .into_iter()
+            .map(|share| Share::new(share.val, share.tag));
+        self.opener
+            .batch_check::<K, PID>(opened_shares, triple_mask(&self.opener, mask))
+            .await
+            .map_err(VerificationError::MacCheckFailed)?;
+
+        let zero = self
+            .opener
+            .single_check(zero)
+            .await
+            .map_err(VerificationError::MacCheckFailed)?;
+        if zero != KS::ZERO {
+            error!("TripleVerifier::verify sacrifice failed");
+            return Err(VerificationError::SacrificeFailed);
+        }
+
+        info!("TripleVerifier: {} triples verified", num_checked);
+
+        Ok(checked.to_vec())
+    }
+
+    /// Like `verify()`, but returns the triples without MAC tags, see `BeaverTriple::strip_macs()`.
+    pub async fn verify_and_strip<K, const PID: usize>(
+        &mut self,
+        triples: &[BeaverTriple<KS, K, PID>],
+    ) -> Result<Vec<RawTriple<K, PID>>, VerificationError>
+    where
+        K: GenericNativeResidue,
+    {
+        let checked = self.verify(triples).await?;
+        Ok(checked.iter().map(BeaverTriple::strip_macs).collect())
+    }
+
+    pub async fn finish(self) {
+        self.opener.finish().await;
+        let _ = self.ch_seed_commitment.writer.into_inner().finish().await;
+        let _ = self.ch_seed_opening.writer.into_inner().finish().await;
+    }
+
+    /// Returns a seed that neither party can bias.  Each party commits to its part before the parts
+    /// are opened, so the party that opens last cannot choose its part depending on the other one.
+    async fn joint_seed(&mut self) -> Result<[u8; 32], VerificationError> {
+        let local_seed: [u8; 32] = rand::thread_rng().gen();
+        let suite = self.crypto_suite;
+        let (com, opening) = commitment::commit_random(suite, local_seed);
+
+        let remote_com = exchange(&mut self.ch_seed_commitment, com).await?;
+        let remote_opening = exchange(&mut self.ch_seed_opening, opening).await?;
+        let remote_seed = match commitment::open(suite, &remote_com, remote_opening) {
+            Ok(remote_seed) => remote_seed,
+            Err(_) => {
+                error!("TripleVerifier: the seed of the other party does not match its commitment");
+                return Err(VerificationError::SeedMismatch);
+            }
+        };
+
+        let mut seed = local_seed;
+        for (dst, src) in seed.iter_mut().zip(remote_seed) {
+            *dst ^= src;
+        }
+        Ok(seed)
+    }
+}
+
+/// Sends `message` to the other party and receives its message at the same time.
+async fn exchange<T>(ch: &mut BiChannel<T>, message: T) -> Result<T, VerificationError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let (rx, tx) = ch.split();
+    let (sent, received) = tokio::join!(tx.send(message), rx.next());
+    sent.map_err(|e| VerificationError::FailedToSend(*e))?;
+    received
+        .ok_or(VerificationError::ConnectionClosed)?
+        .map_err(|e| VerificationError::FailedToReceive(*e))
+}
+
+/// Views `share` as a share of its value modulo `2^{k+s}`.
+fn widen<KS, K, const PID: usize>(share: &Share<KS, K, PID>) -> Share<KS, KS, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    Share::new(share.val, share.tag)
+}
+
+/// Derives the mask of a batch check from the a and b of a triple, see `MaskStrategy`.
+///
+/// # Panics
+///
+/// Panics if the mask strategy of `opener` takes more than two values.
+pub(crate) fn triple_mask<KS, K, S, const PID: usize>(
+    opener: &MacCheckOpener<KS, S>,
+    triple: &BeaverTriple<KS, K, PID>,
+) -> Share<KS, K, PID>
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    let strategy = opener.mask_strategy::<K>();
+    assert!(
+        strategy.num_values() <= 2,
+        "a triple provides only two masking values"
+    );
+    strategy.combine(&[triple.a, triple.b][..strategy.num_values()])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use crypto_bigint::Random;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use crate::bgv::residue::native::GenericNativeResidue;
+    use crate::bgv::residue::GenericResidue;
+    use crate::connection::Connection;
+    use crate::interface::{BeaverTriple, MacKeyShare, Share};
+    use crate::low_gear_preproc::params::ToyPreprocK32S32;
+    use crate::low_gear_preproc::PreprocessorParameters;
+
+    use super::{TripleVerifier, VerificationError};
+
+    type P = ToyPreprocK32S32;
+    type KS = <P as PreprocessorParameters>::KS;
+    type K = <P as PreprocessorParameters>::K;
+    type S = <P as PreprocessorParameters>::S;
+
+    /// How the triples of a party deviate from the protocol.
+    #[derive(Clone, Copy, PartialEq)]
+    enum Fault {
+        None,
+        /// The delegate deals triples with `c = a b + 1`.
+        WrongProduct,
+        /// The delegate deals triples with `c = a b + 2^{k-1}`, which a sacrifice modulo `2^k`
+        /// only detects for odd challenges.
+        HalfProduct,
+        /// Party 1 changes its share of each `a`, without a matching MAC tag.
+        ChangedShare,
+    }
+
+    fn share<const PID: usize>(prng: &mut ChaCha20Rng, mac_key: KS, val: KS) -> Share<KS, K, PID> {
+        let tag = val * mac_key;
+        let (val_mask, tag_mask) = (KS::random(&mut *prng), KS::random(&mut *prng));
+        if PID == 0 {
+            Share::new(val - val_mask, tag - tag_mask)
+        } else {
+            Share::new(val_mask, tag_mask)
+        }
+    }
+
+    /// Deals shares of triples from a PRNG with a seed that is shared by both parties.
+    fn deal<const PID: usize>(
+        seed: [u8; 32],
+        mac_key: KS,
+        n: usize,
+        fault: Fault,
+    ) -> Vec<BeaverTriple<KS, K, PID>> {
+        let mut prng = ChaCha20Rng::from_seed(seed);
+        (0..n)
+            .map(|_| {
+                let (a, b) = (KS::random(&mut prng), KS::random(&mut prng));
+                let c = match fault {
+                    Fault::WrongProduct => a * b + KS::from_i64(1),
+                    Fault::HalfProduct => a * b + KS::from_i64(1).shl_vartime(K::BITS - 1),
+                    _ => a * b,
+                };
+                let mut a = share::<PID>(&mut prng, mac_key, a);
+                if fault == Fault::ChangedShare && PID == 1 {
+                    a.val += KS::from_i64(1);
+                }
+                BeaverTriple {
+                    a,
+                    b: share(&mut prng, mac_key, b),
+                    c: share(&mut prng, mac_key, c),
+                    phantom: PhantomData,
+                }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn verify() {
+        assert_eq!(run(Fault::None, "[::1]:50149", "[::1]:50150").await, Ok(9));
+        assert_eq!(
+            run(Fault::WrongProduct, "[::1]:50151", "[::1]:50152").await,
+            Err("SacrificeFailed")
+        );
+        assert_eq!(
+            run(Fault::ChangedShare, "[::1]:50153", "[::1]:50154").await,
+            Err("MacCheckFailed")
+        );
+        assert_eq!(
+            run(Fault::HalfProduct, "[::1]:50169", "[::1]:50170").await,
+            Err("SacrificeFailed")
+        );
+    }
+
+    #[tokio::test]
+    async fn closed_connection() {
+        const P0_ADDR: &str = "[::1]:50145";
+        const P1_ADDR: &str = "[::1]:50146";
+
+        let (conn0, conn1) = tokio::join!(
+            Connection::new(P0_ADDR.parse().unwrap(), P1_ADDR.parse().unwrap()),
+            Connection::new(P1_ADDR.parse().unwrap(), P0_ADDR.parse().unwrap())
+        );
+        let (mut conn0, mut conn1) = (conn0.unwrap(), conn1.unwrap());
+        let mac_keys = [(); 2].map(|_| MacKeyShare::<S>::random(&mut rand::thread_rng()));
+        let (verifier0, verifier1) = tokio::join!(
+            TripleVerifier::<KS, S>::new(&mut conn0, mac_keys[0].clone()),
+            TripleVerifier::<KS, S>::new(&mut conn1, mac_keys[1].clone())
+        );
+        let mut verifier0 = verifier0.unwrap();
+
+        // The other party stops before the verification, which fails instead of panicking.
+        verifier1.unwrap().finish().await;
+        let triples = deal::<0>([0; 32], KS::from_i64(1), 3, Fault::None);
+        assert!(matches!(
+            verifier0.verify(&triples).await,
+            Err(VerificationError::ConnectionClosed | VerificationError::FailedToSend(_))
+        ));
+    }
+
+    /// Returns the number of verified triples, or the name of the error of both parties.
+    async fn run(
+        fault: Fault,
+        p0_addr: &'static str,
+        p1_addr: &'static str,
+    ) -> Result<usize, &'static str> {
+        let mac_keys = [
+            S::random(&mut rand::thread_rng()),
+            S::random(&mut rand::thread_rng()),
+        ];
+        let mac_key = KS::from_unsigned(mac_keys[0]) + KS::from_unsigned(mac_keys[1]);
+        let seed = rand::random();
+
+        let p0 = tokio::task::spawn(run_party::<0>(
+            p0_addr,
+            p1_addr,
+            mac_keys[0],
+            mac_key,
+            seed,
+            fault,
+        ));
+        let p1 = tokio::task::spawn(run_party::<1>(
+            p1_addr,
+            p0_addr,
+            mac_keys[1],
+            mac_key,
+            seed,
+            fault,
+        ));
+        let (result0, result1) = tokio::try_join!(p0, p1).unwrap();
+        assert_eq!(result0, result1);
+        result0
+    }
+
+    async fn run_party<const PID: usize>(
+        local: &'static str,
+        remote: &'static str,
+        local_mac_key: S,
+        mac_key: KS,
+        seed: [u8; 32],
+        fault: Fault,
+    ) -> Result<usize, &'static str> {
+        let mut conn = Connection::new(local.parse().unwrap(), remote.parse().unwrap())
+            .await
+            .unwrap();
+        let mut verifier =
+            TripleVerifier::<KS, _>::new(&mut conn, MacKeyShare::from_secret(local_mac_key))
+                .await
+                .unwrap();
+        let triples = deal::<PID>(seed, mac_key, 20, fault);
+        let bytes = bincode::serialize(&triples).unwrap();
+
+        // Oversized inputs are rejected before any message is exchanged.
+        verifier.set_max_serialized_size(bytes.len() as u64 - 1);
+        assert!(matches!(
+            verifier.verify_serialized::<K, PID>(&bytes).await,
+            Err(VerificationError::TooLarge)
+        ));
+        verifier.set_max_serialized_size(bytes.len() as u64);
+
+        let result = match verifier.verify_serialized::<K, PID>(&bytes).await {
+            Ok(checked) => Ok(checked.len()),
+            Err(VerificationError::SacrificeFailed) => Err("SacrificeFailed"),
+            Err(VerificationError::MacCheckFailed(_)) => Err("MacCheckFailed"),
+            Err(err) => panic!("unexpected error: {}", err),
+        };
+        verifier.finish().await;
+        result
+    }
+}
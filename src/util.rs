@@ -1,11 +1,126 @@
 use std::{
     fmt::Debug,
+    future::Future,
     io,
     net::{SocketAddr, ToSocketAddrs},
+    ptr,
+    sync::atomic::{compiler_fence, Ordering},
+};
+#[cfg(any(feature = "enclave", not(feature = "protocol")))]
+use std::{
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
 };
 
+use crypto_bigint::Zero;
 use log::{error, info};
 
+/// Instruments the future with a span named `$name` if the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+#[cfg_attr(not(feature = "protocol"), allow(unused_macros))]
+macro_rules! phase {
+    ($name:literal, $fut:expr) => {
+        tracing::Instrument::instrument($fut, tracing::info_span!($name))
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[cfg_attr(not(feature = "protocol"), allow(unused_macros))]
+macro_rules! phase {
+    ($name:literal, $fut:expr) => {
+        $fut
+    };
+}
+
+#[cfg_attr(not(feature = "protocol"), allow(unused_imports))]
+pub(crate) use phase;
+
+/// Yields to the runtime during long computations.  Without the `protocol` feature, there is no
+/// `tokio` runtime, so this does nothing.
+#[cfg(feature = "protocol")]
+pub(crate) async fn yield_now() {
+    tokio::task::yield_now().await;
+}
+
+#[cfg(not(feature = "protocol"))]
+pub(crate) async fn yield_now() {}
+
+/// Runs a CPU-bound future on a blocking thread of the runtime.  In contrast to `tokio::join!`,
+/// which polls its futures on the same thread, the future then runs in parallel with the caller.
+/// A panic of the future is resumed in the caller.
+#[cfg(feature = "protocol")]
+pub(crate) async fn run_blocking<F>(fut: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let handle = tokio::runtime::Handle::current();
+    match tokio::task::spawn_blocking(move || handle.block_on(fut)).await {
+        Ok(output) => output,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Runs CPU-bound futures in parallel and returns their outputs in order.  With the `protocol`
+/// feature, each of them runs on a blocking thread of the runtime (see `run_blocking()`), so that
+/// the workers of the runtime are not blocked.  Without it, there is no runtime, and each of them
+/// runs on a thread of its own.
+pub(crate) async fn run_parallel<F>(futs: Vec<F>) -> Vec<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "protocol")]
+    {
+        futures_util::future::join_all(futs.into_iter().map(run_blocking)).await
+    }
+    #[cfg(not(feature = "protocol"))]
+    {
+        let workers: Vec<_> = futs
+            .into_iter()
+            .map(|fut| thread::spawn(move || block_on(fut)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(any(feature = "enclave", not(feature = "protocol")))]
+struct ThreadWaker(Thread);
+
+#[cfg(any(feature = "enclave", not(feature = "protocol")))]
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future on the current thread until it completes.  Meant for CPU-bound futures on worker
+/// threads, which only await `yield_now()`: outside of the runtime, it wakes the task immediately.
+#[cfg(any(feature = "enclave", not(feature = "protocol")))]
+pub(crate) fn block_on<F>(fut: F) -> F::Output
+where
+    F: Future,
+{
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
 pub fn log_error(name: &str, res: Result<(), impl Debug>) {
     if let Err(e) = res {
         error!("{} failed with error: {:?}", name, e)
@@ -23,3 +138,32 @@ pub fn resolve_host(hostname_port: &str) -> io::Result<SocketAddr> {
     })?;
     Ok(socketaddr)
 }
+
+/// Overwrites `values` with zeros in a way that is not optimized away.  Used for discarding
+/// unused slots, such that their content cannot be reused by mistake.
+pub fn zeroize<'a, T>(values: impl IntoIterator<Item = &'a mut T>)
+where
+    T: Zero + 'a,
+{
+    for value in values {
+        // SAFETY: `value` is a valid and aligned reference, and `T::ZERO` is a valid value.
+        unsafe { ptr::write_volatile(value, T::ZERO) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Accounting of the slots of packed plaintexts: `used` slots carried values requested by the
+/// caller, and `discarded` slots were padding, which was zeroized after use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SlotUsage {
+    pub used: u64,
+    pub discarded: u64,
+}
+
+impl SlotUsage {
+    /// Records a batch of `used` values packed into `capacity` slots.
+    pub fn record(&mut self, used: usize, capacity: usize) {
+        self.used += used as u64;
+        self.discarded += (capacity - used) as u64;
+    }
+}
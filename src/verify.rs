@@ -0,0 +1,54 @@
+//! Checks that do not need a connection to the other party, e.g., for auditing a recorded run.
+//!
+//! Together with `bgv` and `interface`, this module compiles without the `protocol` feature and
+//! hence without `tokio` and `quinn`, e.g., with `cargo build --no-default-features`.  Like the rest
+//! of the crate, it requires a 64-bit target.
+
+use std::sync::Arc;
+
+use crate::bgv::{
+    poly::CrtContext,
+    residue::native::GenericNativeResidue,
+    zkpopk::{verifier::Verifier, Challenge, Commitment, Response, ZkpopkVersion},
+    BgvParameters, PreCiphertext, PublicKey,
+};
+use crate::interface::{MacKeyShare, Share};
+
+/// Verifies a ZKPoPK for `ciphertexts` given the commitment, challenge and response of a run of
+/// the protocol.  The parameters and the `version` must be the ones the prover used.
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_zkpopk<P>(
+    ctx: &Arc<CrtContext<P::CiphertextParams>>,
+    pk: &PublicKey<P>,
+    ciphertexts: &[PreCiphertext<P>],
+    commitment: Commitment<P>,
+    challenge: Challenge,
+    response: Response<P>,
+    inv_fail_prob: usize,
+    snd_sec: usize,
+    version: ZkpopkVersion,
+) -> bool
+where
+    P: BgvParameters,
+{
+    Verifier::<P>::with_challenge(inv_fail_prob, ciphertexts.len(), snd_sec, challenge)
+        .with_version(version)
+        .verify(ctx, pk, ciphertexts, commitment, response)
+        .await
+}
+
+/// Checks the MAC of an opened value given both parties' shares and MAC key shares.
+pub fn check_mac<KS, K, S>(
+    share_0: &Share<KS, K, 0>,
+    share_1: &Share<KS, K, 1>,
+    mac_key_0: &MacKeyShare<S>,
+    mac_key_1: &MacKeyShare<S>,
+) -> bool
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+    S: GenericNativeResidue,
+{
+    let mac_key = mac_key_0.widen::<KS>() + mac_key_1.widen::<KS>();
+    share_0.tag + share_1.tag == (share_0.val + share_1.val) * mac_key
+}
@@ -4,17 +4,15 @@ use async_trait::async_trait;
 
 use crate::{
     bgv::residue::native::GenericNativeResidue,
-    interface::{BeaverTriple, Preprocessor, Share},
+    interface::{
+        BeaverTriple, BitDecomposition, BitPreprocessor, DaBit, EdaBit, Preprocessor, Share,
+        ZeroSharePreprocessor,
+    },
 };
 
+#[derive(Default)]
 pub struct ZeroPreprocessor {}
 
-impl Default for ZeroPreprocessor {
-    fn default() -> Self {
-        Self {}
-    }
-}
-
 #[async_trait]
 impl<KS, K, const PID: usize> Preprocessor<KS, K, PID> for ZeroPreprocessor
 where
@@ -33,3 +31,53 @@ where
 
     async fn finish(self) {}
 }
+
+#[async_trait]
+impl<KS, K, const PID: usize> BitPreprocessor<KS, K, PID> for ZeroPreprocessor
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    async fn get_dabits(&mut self, n: usize) -> Vec<DaBit<KS, K, PID>> {
+        let zero = DaBit {
+            arith: Share::ZERO,
+            boolean: false,
+            xor_shares: [Share::ZERO; 2],
+        };
+        vec![zero; n]
+    }
+
+    async fn get_edabits(&mut self, n: usize, len: usize) -> Vec<EdaBit<KS, K, PID>> {
+        let zero = EdaBit {
+            arith: Share::ZERO,
+            booleans: vec![false; len],
+            xor_shares: vec![[Share::ZERO; 2]; len],
+        };
+        vec![zero; n]
+    }
+
+    async fn get_bit_decompositions(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Vec<BitDecomposition<KS, K, PID>> {
+        let zero = BitDecomposition {
+            arith: Share::ZERO,
+            bits: vec![Share::ZERO; len],
+            booleans: vec![false; len],
+            xor_shares: vec![[Share::ZERO; 2]; len],
+        };
+        vec![zero; n]
+    }
+}
+
+#[async_trait]
+impl<KS, K, const PID: usize> ZeroSharePreprocessor<KS, K, PID> for ZeroPreprocessor
+where
+    KS: GenericNativeResidue,
+    K: GenericNativeResidue,
+{
+    async fn get_zero_shares(&mut self, n: usize) -> Vec<Share<KS, K, PID>> {
+        vec![Share::ZERO; n]
+    }
+}
@@ -0,0 +1,98 @@
+//! Compares the results of the `packing` benchmarks against `benches/baseline.json`.
+//!
+//! Run `cargo bench --bench main -- packing` first and then
+//! `cargo test --test bench_regression -- --ignored`.  Benchmarks without a result are skipped
+//! (e.g., the production parameters without `MULTIPARS_BENCH_PRODUCTION`), but the test fails if a
+//! result has no baseline or if no benchmark was compared at all.  To update the baseline, run the
+//! test with `BENCH_BASELINE_UPDATE=1` and commit `benches/baseline.json`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// A benchmark regressed if its mean is more than this fraction above the baseline.
+const MAX_REGRESSION: f64 = 0.2;
+
+const BENCHMARKS: &[&str] = &[
+    "packing/toy_k32_s32_pack",
+    "packing/toy_k32_s32_pack_mask",
+    "packing/toy_k32_s32_unpack",
+    "packing/toy_k32_s32_truncate",
+    "packing/k64_s64_pack",
+    "packing/k64_s64_pack_mask",
+    "packing/k64_s64_unpack",
+    "packing/k64_s64_truncate",
+];
+
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("benches")
+        .join("baseline.json")
+}
+
+/// Returns the mean running time in nanoseconds of the last run of `benchmark`, if any.
+fn mean_ns(benchmark: &str) -> Option<f64> {
+    let target_dir = env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"));
+    let path = target_dir
+        .join("criterion")
+        .join(benchmark)
+        .join("new")
+        .join("estimates.json");
+    let estimates: serde_json::Value = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+    estimates["mean"]["point_estimate"].as_f64()
+}
+
+#[test]
+#[ignore = "requires the results of `cargo bench`"]
+fn no_regressions() {
+    let mut baseline: BTreeMap<String, f64> =
+        serde_json::from_slice(&fs::read(baseline_path()).unwrap()).unwrap();
+
+    if env::var_os("BENCH_BASELINE_UPDATE").is_some() {
+        for benchmark in BENCHMARKS {
+            if let Some(mean) = mean_ns(benchmark) {
+                baseline.insert(benchmark.to_string(), mean);
+            }
+        }
+        let json = serde_json::to_string_pretty(&baseline).unwrap();
+        fs::write(baseline_path(), json + "\n").unwrap();
+        return;
+    }
+
+    let mut regressions = Vec::new();
+    let mut missing = Vec::new();
+    let mut compared = 0;
+    for benchmark in BENCHMARKS {
+        let Some(mean) = mean_ns(benchmark) else {
+            eprintln!("skipping {}: no result", benchmark);
+            continue;
+        };
+        let Some(&base) = baseline.get(*benchmark) else {
+            missing.push(*benchmark);
+            continue;
+        };
+        compared += 1;
+        let change = mean / base - 1.0;
+        eprintln!("{}: {:.0} ns ({:+.1}%)", benchmark, mean, change * 100.0);
+        if change > MAX_REGRESSION {
+            regressions.push(format!("{} ({:+.1}%)", benchmark, change * 100.0));
+        }
+    }
+    assert!(
+        missing.is_empty(),
+        "no baseline for {} (see `BENCH_BASELINE_UPDATE`)",
+        missing.join(", ")
+    );
+    assert!(
+        compared > 0,
+        "no benchmark results, run `cargo bench --bench main -- packing` first"
+    );
+    assert!(
+        regressions.is_empty(),
+        "regressions: {}",
+        regressions.join(", ")
+    );
+}
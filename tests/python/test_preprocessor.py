@@ -0,0 +1,52 @@
+import threading
+
+import numpy as np
+import pytest
+
+import multipars
+
+P0_ADDR = "[::1]:50157"
+P1_ADDR = "[::1]:50158"
+
+
+@pytest.mark.parametrize("kwargs", [{"threads": 0}, {"k": 16}])
+def test_connect_rejects_invalid_arguments(kwargs):
+    with pytest.raises(ValueError):
+        multipars.PyPreprocessor.connect(0, P0_ADDR, P1_ADDR, toy=True, **kwargs)
+
+
+def test_connect_rejects_invalid_player():
+    with pytest.raises(ValueError):
+        multipars.PyPreprocessor.connect(2, P0_ADDR, P1_ADDR, toy=True)
+
+
+def test_triples():
+    results = [None, None]
+
+    def run(player, local_addr, remote_addr):
+        preproc = multipars.PyPreprocessor.connect(
+            player, local_addr, remote_addr, toy=True, threads=2
+        )
+        results[player] = (preproc.get_triples(10), preproc.get_raw_triples(10))
+        preproc.finish()
+
+    parties = [
+        threading.Thread(target=run, args=(0, P0_ADDR, P1_ADDR)),
+        threading.Thread(target=run, args=(1, P1_ADDR, P0_ADDR)),
+    ]
+    for party in parties:
+        party.start()
+    for party in parties:
+        party.join()
+
+    for triples, raw in results:
+        assert triples.dtype == np.uint64
+        assert triples.shape[:3] == (10, 3, 2)
+        assert raw.dtype == np.uint64
+        assert raw.shape == (10, 3, 1)
+
+    # The shares of the raw triples are reduced modulo 2^k with k = 32.
+    (_, raw0), (_, raw1) = results
+    for share0, share1 in zip(raw0, raw1):
+        a, b, c = ((int(x[0]) + int(y[0])) % 2**32 for x, y in zip(share0, share1))
+        assert a * b % 2**32 == c
@@ -0,0 +1,81 @@
+//! Checks the components that compile without the `protocol` feature:
+//!
+//! `cargo test --no-default-features --test verify_only`
+
+use std::sync::Arc;
+
+use crypto_bigint::Random;
+use multipars::bgv::{
+    params::ToyBgv,
+    poly::{power::PowerPoly, CrtContext},
+    residue::{native::NativeResidue, GenericResidue},
+    zkpopk::{prover::Prover, Challenge, Statement, ZkpopkVersion},
+    PreCiphertext, PublicKey, SecretKey,
+};
+use multipars::interface::{MacKeyShare, Share};
+use multipars::verify::{check_mac, verify_zkpopk};
+
+const INV_FAIL_PROB: usize = 1 << 20;
+const NUM_CIPHERTEXTS: usize = 2;
+const SND_SEC: usize = 64;
+
+#[tokio::test]
+async fn zkpopk() {
+    let mut rng = rand::thread_rng();
+    let ctx = Arc::new(CrtContext::gen().await);
+    let sk = SecretKey::<ToyBgv>::gen(&ctx).await;
+    let pk = PublicKey::gen(&ctx, &sk).await;
+    let mut ciphertexts = Vec::new();
+    let mut inputs = Vec::new();
+    for _ in 0..NUM_CIPHERTEXTS {
+        let plaintext = PowerPoly::random(&mut rng);
+        let mut ciphertext = PreCiphertext::default();
+        let input = Prover::encrypt_into(&ctx, &pk, &plaintext, &mut ciphertext).await;
+        ciphertexts.push(ciphertext);
+        inputs.push(input);
+    }
+
+    let challenge = Challenge::random(&mut rng);
+    let prover = Prover::<ToyBgv>::new(INV_FAIL_PROB, NUM_CIPHERTEXTS, SND_SEC);
+    let commitment = prover.commit(&ctx, &pk).await;
+    let response = prover
+        .respond(&inputs, &Statement::of(&ciphertexts), challenge)
+        .unwrap();
+    assert!(
+        verify_zkpopk(
+            &ctx,
+            &pk,
+            &ciphertexts,
+            commitment,
+            challenge,
+            response,
+            INV_FAIL_PROB,
+            SND_SEC,
+            ZkpopkVersion::default(),
+        )
+        .await
+    );
+}
+
+#[test]
+fn mac() {
+    type K = NativeResidue<32, 1>;
+    type KS = NativeResidue<64, 1>;
+    type S = NativeResidue<32, 1>;
+
+    let mut rng = rand::thread_rng();
+    let (key_0, key_1) = (S::random(&mut rng), S::random(&mut rng));
+    let mac_key = KS::from_unsigned(key_0) + KS::from_unsigned(key_1);
+    let (key_0, key_1) = (
+        MacKeyShare::from_secret(key_0),
+        MacKeyShare::from_secret(key_1),
+    );
+    let (val, val_0) = (KS::random(&mut rng), KS::random(&mut rng));
+    let tag_0 = KS::random(&mut rng);
+    let share_0 = Share::<KS, K, 0>::new(val_0, tag_0);
+    let share_1 = Share::<KS, K, 1>::new(val - val_0, val * mac_key - tag_0);
+    assert!(check_mac(&share_0, &share_1, &key_0, &key_1));
+
+    let forged = Share::<KS, K, 1>::new(val - val_0 + KS::from_i64(1), share_1.tag);
+    assert!(!check_mac(&share_0, &forged, &key_0, &key_1));
+}